@@ -1,9 +1,28 @@
-use clap::{Parser, Subcommand};
-use game_core::GameState;
+use clap::{Parser, Subcommand, ValueEnum};
+use flip7_client::{Client, ClientOptions, Locale};
+use futures_util::StreamExt;
+use game_core::bots::{parse_strategy, Strategy};
+use game_core::encryption::key_from_hex;
+use game_core::replay::{self, Replay};
+use game_core::config::GameConfig;
+use game_core::deck::DeckSpec;
+use game_core::elimination::EliminationRule;
+use game_core::handicap::Handicap;
+use game_core::simulate::{record_batch, run_batch, write_samples, DataFormat, MAX_ROUNDS};
+use game_core::tournament::{round_robin, Entrant};
+use game_core::variant::{VariantRegistry, DEFAULT_VARIANT};
+use game_core::{GameMove, GameState};
+use std::env;
 use std::fs;
+use std::io::{self, Write};
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, BufReader};
 
 const GAME_STATE_FILE: &str = "game_state.json";
+const ENCRYPTED_GAME_STATE_FILE: &str = "game_state.enc";
+/// Hex-encoded 256-bit key; when set, saves are encrypted with AES-GCM
+const SAVE_KEY_ENV: &str = "FLIP7_SAVE_KEY";
 
 #[derive(Parser)]
 #[command(name = "flip7_cli")]
@@ -23,6 +42,40 @@ enum Commands {
         /// Random seed for reproducible games
         #[arg(long, default_value = "42")]
         seed: u64,
+        /// Rules variant to play, e.g. classic or blitz; the options below
+        /// override its settings
+        #[arg(long, default_value = DEFAULT_VARIANT)]
+        variant: String,
+        /// Seconds each player has per turn before being auto-stayed
+        #[arg(long)]
+        turn_timer: Option<u64>,
+        /// Deal from a custom deck instead, as VALUE:COPIES pairs (e.g. 7:4,3:10)
+        #[arg(long)]
+        deck: Option<String>,
+        /// Number of decks shuffled together, for large tables
+        #[arg(long, default_value = "1")]
+        decks: u32,
+        /// Eliminate players who bust this many rounds in a row
+        #[arg(long)]
+        eliminate_after_busts: Option<u32>,
+        /// Eliminate players who fall this many points behind the leader
+        #[arg(long)]
+        eliminate_behind: Option<u32>,
+        /// Give a player a handicap, as PLAYER:START[:PERCENT] (e.g. 1:20:150); repeatable
+        #[arg(long)]
+        handicap: Vec<String>,
+    },
+    /// Start a single-player score-attack game
+    Solo {
+        /// Number of rounds to play
+        #[arg(long, default_value = "5")]
+        rounds: u32,
+        /// Score to beat by the end of the last round
+        #[arg(long, default_value = "75")]
+        target: u32,
+        /// Random seed for reproducible games
+        #[arg(long, default_value = "42")]
+        seed: u64,
     },
     /// Draw a card for a player
     Draw {
@@ -34,6 +87,55 @@ enum Commands {
         /// Player ID (0-based index)
         player: usize,
     },
+    /// Let a computer strategy play for some players
+    Bot {
+        /// Player ID (0-based index); repeat to let the bot play several seats
+        #[arg(long = "player", required = true)]
+        players: Vec<usize>,
+        /// easy, medium, hard, reckless, threshold[:TOTAL], bust-chance:PROBABILITY,
+        /// random[:SEED] or monte-carlo[:ROLLOUTS]
+        #[arg(long, default_value = "threshold")]
+        strategy: String,
+        /// Keep playing their turns until the round ends or someone else is up,
+        /// instead of making one move
+        #[arg(long)]
+        until_round_end: bool,
+    },
+    /// Play bots against each other and report on the rules' balance
+    Analyze {
+        /// Number of games to play
+        #[arg(long, default_value = "1000")]
+        games: u32,
+        /// Strategy of one seat, as for the bot command; repeat for each seat
+        #[arg(long = "strategy", required = true)]
+        strategies: Vec<String>,
+        /// Rules variant to play
+        #[arg(long, default_value = DEFAULT_VARIANT)]
+        variant: String,
+        /// Seed of the first game; game N is seeded SEED + N
+        #[arg(long, default_value = "42")]
+        seed: u64,
+        /// Also write every move as training data: CSV if the file ends in
+        /// .csv, JSON lines otherwise
+        #[arg(long)]
+        record: Option<String>,
+    },
+    /// Play every bot listed in a TOML file against every other and print
+    /// their win rates
+    BotTournament {
+        /// TOML file with a [[bot]] table per entrant, each with a name and a
+        /// strategy as for the bot command
+        bots: String,
+        /// Games per pairing, half of them in each seat order
+        #[arg(long, default_value = "200")]
+        games: u32,
+        /// Rules variant to play
+        #[arg(long, default_value = DEFAULT_VARIANT)]
+        variant: String,
+        /// Seed of the first game of each pairing; game N is seeded SEED + N
+        #[arg(long, default_value = "42")]
+        seed: u64,
+    },
     /// Display current game state
     State,
     /// Simulate a series of commands from a script
@@ -41,14 +143,79 @@ enum Commands {
         /// Path to script file
         script: String,
     },
+    /// Play a seat on a server, typing draw, stay, start, state or quit
+    Play {
+        /// Server to connect to, as tcp://HOST:PORT
+        #[arg(long, default_value = "tcp://127.0.0.1:7777")]
+        server: String,
+        /// Name to play under
+        #[arg(long)]
+        name: String,
+        /// Game to join; a new one is created without
+        #[arg(long)]
+        game: Option<String>,
+    },
+    /// List servers and their public games on the local network
+    Discover {
+        /// Seconds to wait for servers to answer
+        #[arg(long, default_value_t = 2)]
+        wait: u64,
+    },
+    /// Shrink a failing replay to the fewest steps that still fail
+    Reduce {
+        /// Replay JSON, or a saved game to derive the replay from
+        replay: String,
+        /// Failure the reduced replay must keep showing
+        #[arg(long, value_enum)]
+        predicate: Predicate,
+        /// Write the reduced replay here instead of printing it
+        #[arg(long)]
+        out: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Predicate {
+    /// Replaying panics
+    Panics,
+    /// A game invariant is broken after some step
+    InvariantFails,
+}
+
+impl Predicate {
+    fn holds(self, replay: &Replay) -> bool {
+        let result = std::panic::catch_unwind(|| match self {
+            Predicate::Panics => {
+                replay.run();
+                true
+            }
+            Predicate::InvariantFails => replay.check().is_ok(),
+        });
+        match self {
+            Predicate::Panics => result.is_err(),
+            Predicate::InvariantFails => matches!(result, Ok(false)),
+        }
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::New { players, seed } => {
-            if let Err(e) = handle_new(players, seed) {
+        Commands::New { players, seed, variant, turn_timer, deck, decks, eliminate_after_busts, eliminate_behind, handicap } => {
+            let elimination = (eliminate_after_busts.is_some() || eliminate_behind.is_some()).then_some(EliminationRule {
+                consecutive_busts: eliminate_after_busts,
+                points_behind: eliminate_behind,
+            });
+            let result = game_config(&variant, turn_timer, deck.as_deref(), decks, elimination)
+                .and_then(|config| handle_new(players, seed, config, &handicap));
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Solo { rounds, target, seed } => {
+            if let Err(e) = handle_solo(rounds, target, seed) {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
@@ -65,6 +232,24 @@ fn main() {
                 std::process::exit(1);
             }
         }
+        Commands::Bot { players, strategy, until_round_end } => {
+            if let Err(e) = handle_bot(&players, &strategy, until_round_end) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Analyze { games, strategies, variant, seed, record } => {
+            if let Err(e) = handle_analyze(games, &strategies, &variant, seed, record.as_deref()) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::BotTournament { bots, games, variant, seed } => {
+            if let Err(e) = handle_bot_tournament(&bots, games, &variant, seed) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
         Commands::State => {
             if let Err(e) = handle_state() {
                 eprintln!("Error: {}", e);
@@ -77,59 +262,170 @@ fn main() {
                 std::process::exit(1);
             }
         }
+        Commands::Play { server, name, game } => {
+            if let Err(e) = handle_play(&server, &name, game.as_deref()) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Discover { wait } => {
+            if let Err(e) = handle_discover(wait) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Reduce { replay, predicate, out } => {
+            if let Err(e) = handle_reduce(&replay, predicate, out.as_deref()) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Settings of the named variant, with the given options overriding it.
+fn game_config(
+    variant: &str,
+    turn_timer: Option<u64>,
+    deck: Option<&str>,
+    decks: u32,
+    elimination: Option<EliminationRule>,
+) -> Result<GameConfig, String> {
+    if decks < 1 {
+        return Err("Number of decks must be at least 1".to_string());
+    }
+
+    let mut config = VariantRegistry::builtin().get(variant)?.config.clone();
+    if let Some(secs) = turn_timer {
+        config.turn_time_limit_ms = Some(secs * 1000);
+    }
+    if let Some(deck) = deck {
+        config.deck = parse_deck_spec(deck)?;
     }
+    config.deck = config.deck.with_decks(decks);
+    if elimination.is_some() {
+        config.elimination = elimination;
+    }
+    Ok(config)
 }
 
-fn handle_new(players: usize, seed: u64) -> Result<(), String> {
+fn handle_new(players: usize, seed: u64, config: GameConfig, handicaps: &[String]) -> Result<(), String> {
     if players < 1 {
         return Err("Number of players must be at least 1".to_string());
     }
-    if players > 8 {
-        return Err("Number of players cannot exceed 8".to_string());
+    // Each deck comfortably serves up to 8 players
+    let decks = config.deck.num_decks();
+    let max_players = 8 * decks as usize;
+    if players > max_players {
+        return Err(format!("Number of players cannot exceed {} with {} deck(s); add --decks", max_players, decks));
     }
 
-    let mut game = GameState::new_with_seed(seed);
+    let mut game = GameState::new_with_config(seed, config);
+    VariantRegistry::builtin().restore_scoring(&mut game)?;
 
     // Add players
     for i in 0..players {
         game.add_player(i.to_string(), format!("Player {}", i));
     }
+    for handicap in handicaps {
+        let (player, handicap) = parse_handicap(handicap)?;
+        game.set_handicap(&player, handicap)?;
+    }
 
     // Start the first round
     game.start_round().map_err(|e| format!("Failed to start round: {}", e))?;
+    game.tick(now_ms());
 
     // Save game state
     save_game_state(&game)?;
 
-    println!("New game started with {} players (seed: {})", players, seed);
-    println!("Game state saved to {}", GAME_STATE_FILE);
+    let variant = game.config.variant.as_deref().unwrap_or(DEFAULT_VARIANT);
+    println!("New {} game started with {} players (seed: {})", variant, players, seed);
+    if let Some(limit_ms) = game.config.turn_time_limit_ms {
+        println!("Each turn times out after {} seconds", limit_ms / 1000);
+    }
+    println!("Game state saved to {}", state_file());
+
+    Ok(())
+}
+
+/// Parses `VALUE:COPIES` pairs separated by commas, e.g. `7:4,3:10`.
+fn parse_deck_spec(spec: &str) -> Result<DeckSpec, String> {
+    let mut counts = Vec::new();
+    for pair in spec.split(',') {
+        let (value, copies) = pair.split_once(':')
+            .ok_or_else(|| format!("Invalid deck entry '{}', expected VALUE:COPIES", pair))?;
+        let value: u8 = value.trim().parse()
+            .map_err(|_| format!("Invalid card value '{}'", value))?;
+        let copies: u32 = copies.trim().parse()
+            .map_err(|_| format!("Invalid copy count '{}'", copies))?;
+        counts.push((value, copies));
+    }
+    DeckSpec::with_counts(counts)
+}
+
+/// Parses `PLAYER:START[:PERCENT]`, e.g. `1:20:150`.
+fn parse_handicap(spec: &str) -> Result<(String, Handicap), String> {
+    let parts: Vec<&str> = spec.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(format!("Invalid handicap '{}', expected PLAYER:START[:PERCENT]", spec));
+    }
+    let starting_score: u32 = parts[1].trim().parse()
+        .map_err(|_| format!("Invalid starting score '{}'", parts[1]))?;
+    let score_percent: u32 = match parts.get(2) {
+        Some(percent) => percent.trim().parse()
+            .map_err(|_| format!("Invalid score percentage '{}'", percent))?,
+        None => 100,
+    };
+    Ok((parts[0].trim().to_string(), Handicap { starting_score, score_percent }))
+}
+
+fn handle_solo(rounds: u32, target: u32, seed: u64) -> Result<(), String> {
+    if rounds < 1 {
+        return Err("Number of rounds must be at least 1".to_string());
+    }
+
+    let mut game = GameState::new_solo(seed, rounds, target);
+    game.start_round().map_err(|e| format!("Failed to start round: {}", e))?;
+
+    save_game_state(&game)?;
+
+    println!("Solo game started: {} rounds to reach {} points (seed: {})", rounds, target, seed);
+    println!("Play as player 0. Game state saved to {}", state_file());
 
     Ok(())
 }
 
 fn handle_draw(player: usize) -> Result<(), String> {
     let mut game = load_game_state()?;
+    run_turn_timers(&mut game)?;
 
     if player >= game.players.len() {
         return Err(format!("Player {} does not exist. Valid players: 0-{}", player, game.players.len() - 1));
     }
 
     let player_id = player.to_string();
-    game.player_draw(&player_id).map_err(|e| format!("Draw failed: {}", e))?;
+    game.make_move_at(GameMove::Draw { player_id: player_id.clone() }, now_ms())
+        .map_err(|e| format!("Draw failed: {}", e))?;
+    // Start the next player's clock
+    game.tick(now_ms());
 
     save_game_state(&game)?;
 
     let player_obj = &game.players[player];
-    println!("Player {} drew a card. Hand total: {} (cards: {})",
-             player,
-             player_obj.hand.total_value(),
-             player_obj.hand.cards.len());
-
-    if player_obj.hand.is_bust() {
-        println!("Player {} is bust!", player);
+    println!("Player {} drew a card: {}", player, player_obj.hand);
+    if !player_obj.has_stayed {
+        let bust_probability = game.bust_probability(&player_id)?;
+        let expected_value = game.expected_draw_value(&player_id)?;
+        println!("Next draw: {:.0}% bust chance, expected score {:.1}",
+                 bust_probability * 100.0,
+                 expected_value);
     }
-    if player_obj.hand.has_flip7() {
-        println!("Player {} has Flip7!", player);
+
+    // Busting can end the round too
+    if game.round_state.is_finished {
+        finish_round(&mut game)?;
+        save_game_state(&game)?;
     }
 
     Ok(())
@@ -137,32 +433,219 @@ fn handle_draw(player: usize) -> Result<(), String> {
 
 fn handle_stay(player: usize) -> Result<(), String> {
     let mut game = load_game_state()?;
+    run_turn_timers(&mut game)?;
 
     if player >= game.players.len() {
         return Err(format!("Player {} does not exist. Valid players: 0-{}", player, game.players.len() - 1));
     }
 
     let player_id = player.to_string();
-    game.player_stay(&player_id).map_err(|e| format!("Stay failed: {}", e))?;
+    game.make_move_at(GameMove::Stay { player_id }, now_ms())
+        .map_err(|e| format!("Stay failed: {}", e))?;
+    // Start the next player's clock
+    game.tick(now_ms());
 
     save_game_state(&game)?;
 
     println!("Player {} stayed", player);
 
-    // Check if round is finished
     if game.round_state.is_finished {
-        println!("Round finished! Computing scores...");
-        let scores = game.compute_scores();
-        for (id, score) in scores {
-            let player_idx: usize = id.parse().unwrap();
-            println!("Player {}: {} points this round", player_idx, score);
-        }
+        finish_round(&mut game)?;
         save_game_state(&game)?;
     }
 
     Ok(())
 }
 
+fn handle_bot(players: &[usize], strategy: &str, until_round_end: bool) -> Result<(), String> {
+    let strategy = parse_strategy(strategy)?;
+    // Each seat keeps its own strategy state between moves
+    let mut bots: Vec<(usize, Box<dyn Strategy>)> = players.iter().map(|&p| (p, strategy.clone())).collect();
+    let mut round_number = None;
+
+    loop {
+        let mut game = load_game_state()?;
+        run_turn_timers(&mut game)?;
+        if let Some(&player) = players.iter().find(|&&p| p >= game.players.len()) {
+            return Err(format!("Player {} does not exist. Valid players: 0-{}", player, game.players.len() - 1));
+        }
+
+        let moved = round_number.is_some();
+        if round_number.is_some_and(|round| round != game.round_state.round_number) || game.round_state.is_finished {
+            return if moved { Ok(()) } else { Err("Round is finished".to_string()) };
+        }
+        round_number = Some(game.round_state.round_number);
+
+        let current = game.round_state.current_player_index;
+        let Some((_, bot)) = bots.iter_mut().find(|(p, _)| *p == current) else {
+            if moved {
+                println!("Waiting for player {}", current);
+                return Ok(());
+            }
+            return Err(format!("It is player {}'s turn", current));
+        };
+        match game.bot_move(bot.as_mut()) {
+            Some(GameMove::Draw { .. }) => handle_draw(current)?,
+            Some(GameMove::Stay { .. }) => handle_stay(current)?,
+            None => return Err(format!("Player {} cannot move right now", current)),
+        }
+
+        if !until_round_end {
+            return Ok(());
+        }
+    }
+}
+
+fn handle_analyze(games: u32, strategies: &[String], variant: &str, seed: u64, record: Option<&str>) -> Result<(), String> {
+    if games < 1 {
+        return Err("Number of games must be at least 1".to_string());
+    }
+    let config = game_config(variant, None, None, 1, None)?;
+    let bots = strategies.iter()
+        .map(|spec| parse_strategy(spec))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let stats = match record {
+        Some(path) => {
+            let (stats, samples) = record_batch(&config, &bots, games, seed);
+            let format = if path.ends_with(".csv") { DataFormat::Csv } else { DataFormat::Jsonl };
+            let mut file = io::BufWriter::new(fs::File::create(path)
+                .map_err(|e| format!("Failed to create {}: {}", path, e))?);
+            write_samples(&samples, format, &mut file)
+                .and_then(|()| file.flush())
+                .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            println!("Wrote {} moves to {}", samples.len(), path);
+            stats
+        }
+        None => run_batch(&config, &bots, games, seed),
+    };
+
+    println!("{} games of {} ({} abandoned after {} rounds)", stats.games, variant, stats.unfinished, MAX_ROUNDS);
+    println!("Rounds per game: {:.1}", stats.average_rounds);
+    println!("Average winning score: {:.1}", stats.average_winning_score);
+    println!("Flip 7 in {:.1}% of hands", stats.flip7_rate * 100.0);
+    println!();
+    for (seat, spec) in strategies.iter().enumerate() {
+        println!("Seat {} ({}): {:.1}% wins, average score {:.1}, busted {:.1}% of rounds",
+                 seat, spec,
+                 stats.win_rates[seat] * 100.0,
+                 stats.average_scores[seat],
+                 stats.bust_rates[seat] * 100.0);
+    }
+    println!();
+    println!("Bust chance per draw, by cards in hand:");
+    for (size, rate) in &stats.bust_rate_by_hand_size {
+        println!("  {:>2} cards: {:>5.1}% of {} draws", size, rate * 100.0, stats.draws_by_hand_size[size]);
+    }
+
+    Ok(())
+}
+
+/// Entrants of a bot tournament, as read from its TOML file.
+#[derive(serde::Deserialize)]
+struct TournamentFile {
+    #[serde(rename = "bot")]
+    bots: Vec<TournamentBot>,
+}
+
+#[derive(serde::Deserialize)]
+struct TournamentBot {
+    name: String,
+    strategy: String,
+}
+
+fn handle_bot_tournament(path: &str, games: u32, variant: &str, seed: u64) -> Result<(), String> {
+    if games < 2 {
+        return Err("Number of games must be at least 2".to_string());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let file: TournamentFile = toml::from_str(&content)
+        .map_err(|e| format!("Invalid tournament file {}: {}", path, e))?;
+    if file.bots.len() < 2 {
+        return Err("A tournament needs at least 2 bots".to_string());
+    }
+
+    let mut entrants: Vec<Entrant> = Vec::new();
+    for bot in file.bots {
+        if entrants.iter().any(|e| e.name == bot.name) {
+            return Err(format!("Bot {} is listed twice", bot.name));
+        }
+        let strategy = parse_strategy(&bot.strategy)
+            .map_err(|e| format!("Bot {}: {}", bot.name, e))?;
+        entrants.push(Entrant::new(bot.name, strategy));
+    }
+
+    let config = game_config(variant, None, None, 1, None)?;
+    let table = round_robin(&config, &entrants, games, seed);
+    println!("{} games of {} per pairing; win rates of each row against each column, with 95% intervals", games, variant);
+    println!();
+    print!("{}", table);
+    Ok(())
+}
+
+/// Auto-stays players whose turn ran out since the last command, and saves
+/// the result even if the command itself then fails.
+fn run_turn_timers(game: &mut GameState) -> Result<(), String> {
+    let timed_out = game.tick(now_ms());
+    if timed_out.is_empty() {
+        return Ok(());
+    }
+
+    for player_id in &timed_out {
+        println!("Player {} ran out of time and stays", player_id);
+    }
+    if game.round_state.is_finished {
+        finish_round(game)?;
+        game.tick(now_ms());
+    }
+    save_game_state(game)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn finish_round(game: &mut GameState) -> Result<(), String> {
+    println!("Round finished! Computing scores...");
+    let already_out: Vec<bool> = game.players.iter().map(|p| p.eliminated).collect();
+    let summary = game.finish_round()?;
+    for result in summary.players {
+        let player_idx: usize = result.player_id.parse().unwrap();
+        if result.raw_score != result.round_score {
+            println!("Player {}: {} points this round, {} after handicap (total: {})", player_idx, result.raw_score, result.round_score, result.total_score);
+        } else {
+            println!("Player {}: {} points this round (total: {})", player_idx, result.round_score, result.total_score);
+        }
+    }
+    for (player, was_out) in game.players.iter().zip(already_out) {
+        if player.eliminated && !was_out {
+            println!("Player {} is eliminated", player.id);
+        }
+    }
+
+    if let Some(result) = game.solo_result() {
+        println!("Solo game over after {} rounds: {} points (target {})", result.rounds_played, result.total_score, result.target_score);
+        println!("Best round: {}, busts: {}, Flip7s: {}", result.best_round, result.busts, result.flip7s);
+        println!("{}", if result.beat_target { "Target beaten!" } else { "Target missed." });
+    } else if game.is_solo() {
+        game.start_round().map_err(|e| format!("Failed to start round: {}", e))?;
+        println!("Round {} started", game.round_state.round_number);
+    } else if let Some(outcome) = &game.outcome {
+        match outcome.team {
+            Some(team) => println!("Game over! Team {} wins ({:?})", team, outcome.reason),
+            None => println!("Game over! Player {} wins ({:?})", outcome.winner_ids.join(", "), outcome.reason),
+        }
+    } else if !game.sudden_death.is_empty() {
+        println!("Tie for the lead! Sudden death between players {}", game.sudden_death.join(", "));
+    }
+
+    Ok(())
+}
+
 fn handle_state() -> Result<(), String> {
     let game = load_game_state()?;
     let json = game.to_json().map_err(|e| format!("Failed to serialize game state: {}", e))?;
@@ -201,7 +684,20 @@ fn handle_simulate(script_path: &str) -> Result<(), String> {
                 let seed = if parts.len() > 2 {
                     parts[2].parse().map_err(|_| format!("Invalid seed on line {}", line_num + 1))?
                 } else { 42 };
-                handle_new(players, seed)?;
+                let config = game_config(DEFAULT_VARIANT, None, parts.get(3).copied(), 1, None)?;
+                handle_new(players, seed, config, &[])?;
+            }
+            "solo" => {
+                let rounds = if parts.len() > 1 {
+                    parts[1].parse().map_err(|_| format!("Invalid round count on line {}", line_num + 1))?
+                } else { 5 };
+                let target = if parts.len() > 2 {
+                    parts[2].parse().map_err(|_| format!("Invalid target score on line {}", line_num + 1))?
+                } else { 75 };
+                let seed = if parts.len() > 3 {
+                    parts[3].parse().map_err(|_| format!("Invalid seed on line {}", line_num + 1))?
+                } else { 42 };
+                handle_solo(rounds, target, seed)?;
             }
             "draw" => {
                 if parts.len() < 2 {
@@ -219,6 +715,15 @@ fn handle_simulate(script_path: &str) -> Result<(), String> {
                     .map_err(|_| format!("Invalid player ID on line {}", line_num + 1))?;
                 handle_stay(player)?;
             }
+            "bot" => {
+                if parts.len() < 3 {
+                    return Err(format!("Missing player or strategy argument on line {}", line_num + 1));
+                }
+                let players = parts[1].split(',')
+                    .map(|player| player.parse().map_err(|_| format!("Invalid player ID on line {}", line_num + 1)))
+                    .collect::<Result<Vec<usize>, String>>()?;
+                handle_bot(&players, parts[2], parts.get(3) == Some(&"until-round-end"))?;
+            }
             "state" => {
                 handle_state()?;
             }
@@ -231,19 +736,169 @@ fn handle_simulate(script_path: &str) -> Result<(), String> {
     Ok(())
 }
 
+fn handle_play(server: &str, name: &str, game: Option<&str>) -> Result<(), String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| format!("Failed to start the runtime: {}", e))?;
+    runtime.block_on(play_online(server, name, game))
+}
+
+async fn play_online(server: &str, name: &str, game: Option<&str>) -> Result<(), String> {
+    let options = ClientOptions {
+        locale: system_locale(),
+        ..ClientOptions::default()
+    };
+    let client = Client::connect_with(server, options).await?;
+    let seat = match game {
+        Some(game_id) => client.join_game(name, game_id.parse()?).await?,
+        None => client.join(name).await?,
+    };
+    println!("Joined game {} as player {} (join code {})", seat.game_id, seat.player_id, seat.join_code);
+    println!("Type draw, stay, start, state or quit");
+
+    let mut events = client.events();
+    let mut input = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            event = events.next() => match event {
+                Some(event) => println!("{:?}", event),
+                None => return Err("Lost the connection to the server".to_string()),
+            },
+            line = input.next_line() => {
+                let line = line.map_err(|e| format!("Failed to read input: {}", e))?;
+                let result = match line.as_deref().map(str::trim) {
+                    None | Some("quit") => return Ok(()),
+                    Some("draw") => client.draw().await.map(|_| ()),
+                    Some("stay") => client.stay().await.map(|_| ()),
+                    Some("start") => client.start().await,
+                    Some("state") => match client.state() {
+                        Some(game) => game.to_json().map(|json| println!("{}", json))
+                            .map_err(|e| format!("Failed to serialize game state: {}", e)),
+                        None => Err("No state received yet".to_string()),
+                    },
+                    Some(other) => Err(format!("Unknown command '{}'", other)),
+                };
+                if let Err(e) = result {
+                    eprintln!("Error: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// The language the user reads messages in, as POSIX locale variables set it.
+fn system_locale() -> Option<Locale> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| Locale::from_tag(&value))
+}
+
+fn handle_discover(wait: u64) -> Result<(), String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| format!("Failed to start the runtime: {}", e))?;
+    let hosts = runtime
+        .block_on(flip7_client::discover_local_games(Duration::from_secs(wait)))
+        .map_err(|e| format!("Failed to search the local network: {}", e))?;
+    if hosts.is_empty() {
+        println!("No servers found on the local network");
+    }
+    for host in hosts {
+        println!("{} at tcp://{}", host.name, host.addr);
+        for game in host.games {
+            println!(
+                "  game {} ({}, {} players, {:?})",
+                game.game_id, game.variant, game.player_count, game.status
+            );
+        }
+    }
+    Ok(())
+}
+
+fn handle_reduce(path: &str, predicate: Predicate, out: Option<&str>) -> Result<(), String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read replay: {}", e))?;
+    let original = match serde_json::from_str::<Replay>(&json) {
+        Ok(replay) => replay,
+        Err(_) => {
+            let game = GameState::from_json(&json)
+                .map_err(|e| format!("File is neither a replay nor a saved game: {}", e))?;
+            Replay::from_game(&game)
+        }
+    };
+
+    // Panics are expected while reducing; keep them from flooding the output
+    std::panic::set_hook(Box::new(|_| {}));
+    if !predicate.holds(&original) {
+        let _ = std::panic::take_hook();
+        return Err("The replay does not fail the predicate".to_string());
+    }
+    let reduced = replay::reduce(&original, |candidate| predicate.holds(candidate));
+    let _ = std::panic::take_hook();
+
+    println!("Reduced {} steps to {}", original.steps.len(), reduced.steps.len());
+    let json = serde_json::to_string_pretty(&reduced)
+        .map_err(|e| format!("Failed to serialize replay: {}", e))?;
+    match out {
+        Some(out) => {
+            fs::write(out, json).map_err(|e| format!("Failed to write replay: {}", e))?;
+            println!("Reduced replay saved to {}", out);
+        }
+        None => println!("{}", json),
+    }
+    if let Predicate::InvariantFails = predicate {
+        if let Err(violation) = reduced.check() {
+            println!("{}", violation);
+        }
+    }
+
+    Ok(())
+}
+
+fn save_key() -> Result<Option<[u8; 32]>, String> {
+    match env::var(SAVE_KEY_ENV) {
+        Ok(hex) => key_from_hex(&hex).map(Some).map_err(|e| format!("Invalid {}: {}", SAVE_KEY_ENV, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn state_file() -> &'static str {
+    if env::var_os(SAVE_KEY_ENV).is_some() {
+        ENCRYPTED_GAME_STATE_FILE
+    } else {
+        GAME_STATE_FILE
+    }
+}
+
 fn load_game_state() -> Result<GameState, String> {
-    if !Path::new(GAME_STATE_FILE).exists() {
-        return Err(format!("No game state found. Run 'cargo run -- new' to start a new game."));
+    if !Path::new(state_file()).exists() {
+        return Err("No game state found. Run 'cargo run -- new' to start a new game.".to_string());
+    }
+
+    if let Some(key) = save_key()? {
+        let bytes = fs::read(ENCRYPTED_GAME_STATE_FILE)
+            .map_err(|e| format!("Failed to read game state: {}", e))?;
+        let mut game = GameState::from_encrypted(&bytes, &key)?;
+        VariantRegistry::builtin().restore_scoring(&mut game)?;
+        return Ok(game);
     }
 
     let json = fs::read_to_string(GAME_STATE_FILE)
         .map_err(|e| format!("Failed to read game state: {}", e))?;
 
-    GameState::from_json(&json)
-        .map_err(|e| format!("Failed to parse game state: {}", e))
+    let mut game = GameState::from_json(&json)
+        .map_err(|e| format!("Failed to parse game state: {}", e))?;
+    // The variant's scoring isn't saved with the game
+    VariantRegistry::builtin().restore_scoring(&mut game)?;
+    Ok(game)
 }
 
 fn save_game_state(game: &GameState) -> Result<(), String> {
+    if let Some(key) = save_key()? {
+        let bytes = game.to_encrypted(&key)?;
+        fs::write(ENCRYPTED_GAME_STATE_FILE, bytes)
+            .map_err(|e| format!("Failed to save game state: {}", e))?;
+        return Ok(());
+    }
+
     let json = game.to_json()
         .map_err(|e| format!("Failed to serialize game state: {}", e))?;
 
@@ -251,4 +906,4 @@ fn save_game_state(game: &GameState) -> Result<(), String> {
         .map_err(|e| format!("Failed to save game state: {}", e))?;
 
     Ok(())
-}
\ No newline at end of file
+}
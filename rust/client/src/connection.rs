@@ -0,0 +1,316 @@
+//! The task behind a `Client`. It owns the connection: it writes requests,
+//! tells their replies from what the server pushes, sends heartbeats, and
+//! dials again when the connection is lost.
+
+use crate::{ClientOptions, Seat};
+use game_core::events::GameEvent;
+use game_core::GameState;
+use net::handshake::PROTOCOL_VERSION;
+use net::{Encoding, Locale, Message, Response, SessionToken};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// What requests in flight fail with when the connection drops under them.
+pub(crate) const CONNECTION_LOST: &str = "The connection to the server was lost";
+
+/// Features asked for in the `Hello`.
+const FEATURES: &[&str] = &["reconnect", "heartbeat"];
+
+/// A message for the server, and who is waiting for its reply.
+pub(crate) struct Command {
+    pub(crate) message: Message,
+    /// Whether a response is the reply to this message rather than something
+    /// pushed. Errors always are.
+    pub(crate) expects: fn(&Response) -> bool,
+    /// None for the heartbeats the task sends itself
+    pub(crate) reply: Option<oneshot::Sender<Result<Response, String>>>,
+}
+
+/// What the task knows of the client's game, shared with the `Client`.
+#[derive(Default)]
+pub(crate) struct Shared {
+    pub(crate) seat: Option<Seat>,
+    pub(crate) session_token: Option<SessionToken>,
+    pub(crate) state: Option<GameState>,
+    pub(crate) subscribers: Vec<mpsc::UnboundedSender<GameEvent>>,
+}
+
+impl Shared {
+    /// Takes in anything the server said about the client's seat or game.
+    fn observe(&mut self, response: &Response) {
+        match response {
+            Response::GameJoined {
+                game_id,
+                join_code,
+                player_id,
+                session_token: Some(token),
+                ..
+            } => {
+                self.seat = Some(Seat {
+                    game_id: *game_id,
+                    player_id: *player_id,
+                    join_code: join_code.clone(),
+                });
+                self.session_token = Some(*token);
+                self.state = None;
+            }
+            Response::StateUpdate { game_id, game_state } | Response::Reconnected { game_id, game_state, .. }
+                if self.plays(*game_id) =>
+            {
+                self.update(game_state)
+            }
+            Response::Resync {
+                game_id,
+                full_state,
+                last_event_id,
+            } if self.plays(*game_id) => self.resync(full_state, *last_event_id),
+            // The seat and session token carry over to the rematch
+            Response::RematchStarted { game_id, new_game_id } if self.plays(*game_id) => {
+                if let Some(seat) = &mut self.seat {
+                    seat.game_id = *new_game_id;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn plays(&self, game_id: net::GameId) -> bool {
+        self.seat.as_ref().is_some_and(|seat| seat.game_id == game_id)
+    }
+
+    /// Keeps the new state and passes on the events it adds. A state that
+    /// doesn't follow on from the last one, e.g. a rematch's, has all of its
+    /// events passed on.
+    fn update(&mut self, game_state: &GameState) {
+        let added = self
+            .state
+            .as_ref()
+            .and_then(|state| game_state.events.strip_prefix(state.events.as_slice()))
+            .unwrap_or(&game_state.events)
+            .to_vec();
+        self.state = Some(game_state.clone());
+        self.pass_on(added);
+    }
+
+    /// Takes the server's state over one that diverged from it, passing on
+    /// its events past those both agree on, up to `last_event_id`.
+    fn resync(&mut self, game_state: &GameState, last_event_id: u64) {
+        let agreed = self.state.as_ref().map_or(0, |state| {
+            state
+                .events
+                .iter()
+                .zip(&game_state.events)
+                .take_while(|(held, event)| held == event)
+                .count()
+        });
+        let last = game_state.events.len().min(last_event_id as usize);
+        let added = game_state.events.get(agreed..last).unwrap_or_default().to_vec();
+        self.state = Some(game_state.clone());
+        self.pass_on(added);
+    }
+
+    fn pass_on(&mut self, events: Vec<GameEvent>) {
+        self.subscribers
+            .retain(|subscriber| events.iter().all(|event| subscriber.send(event.clone()).is_ok()));
+    }
+
+    fn lose_seat(&mut self) {
+        self.seat = None;
+        self.session_token = None;
+        self.state = None;
+    }
+}
+
+/// A connection that completed the handshake, one JSON message per line.
+pub(crate) struct Link {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Link {
+    /// Connects and says `Hello`, asking for errors in the locale.
+    pub(crate) async fn open(addr: &str, locale: Option<Locale>) -> Result<Self, String> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|err| format!("Cannot connect to {}: {}", addr, err))?;
+        let (reader, writer) = stream.into_split();
+        let mut link = Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        };
+        let hello = Message::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            encodings: vec![Encoding::Json],
+            features: FEATURES.iter().map(|feature| feature.to_string()).collect(),
+            locale: locale.map(|locale| locale.tag().to_string()),
+        };
+        match link.request(&hello).await? {
+            Response::Welcome { .. } => Ok(link),
+            Response::Error { message } => Err(message.into()),
+            other => Err(format!("Expected Welcome, got {}", other.kind())),
+        }
+    }
+
+    async fn send(&mut self, message: &Message) -> Result<(), String> {
+        let mut line = serde_json::to_vec(message).map_err(|err| err.to_string())?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await.map_err(|err| err.to_string())
+    }
+
+    /// Sends a message and reads the reply, for when nothing else can be in
+    /// flight yet.
+    async fn request(&mut self, message: &Message) -> Result<Response, String> {
+        self.send(message).await?;
+        match self.lines.next_line().await {
+            Ok(Some(line)) => serde_json::from_str(&line).map_err(|err| format!("Invalid reply: {}", err)),
+            Ok(None) => Err(CONNECTION_LOST.to_string()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+/// Why serving a connection stopped.
+enum End {
+    ClientDropped,
+    ConnectionLost,
+}
+
+/// Serves the client's requests over `link`, and over new connections each
+/// time it is lost, until the client is dropped or the server can't be
+/// reached again.
+pub(crate) async fn run(
+    addr: String,
+    mut link: Link,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    shared: Arc<Mutex<Shared>>,
+    options: ClientOptions,
+) {
+    while let End::ConnectionLost = serve(&mut link, &mut commands, &shared, &options).await {
+        match reconnect(&addr, &shared, &options).await {
+            Some(new_link) => link = new_link,
+            None => break,
+        }
+    }
+    // Ends every event stream
+    shared.lock().unwrap().subscribers.clear();
+}
+
+async fn serve(
+    link: &mut Link,
+    commands: &mut mpsc::UnboundedReceiver<Command>,
+    shared: &Mutex<Shared>,
+    options: &ClientOptions,
+) -> End {
+    // Replies come in the order the messages went out
+    let mut pending: VecDeque<Command> = VecDeque::new();
+    let mut heartbeat = tokio::time::interval_at(Instant::now() + options.heartbeat, options.heartbeat);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut heard = Instant::now();
+
+    let end = loop {
+        tokio::select! {
+            command = commands.recv() => {
+                let Some(command) = command else {
+                    break End::ClientDropped;
+                };
+                let sent = link.send(&command.message).await;
+                pending.push_back(command);
+                if sent.is_err() {
+                    break End::ConnectionLost;
+                }
+            }
+            line = link.lines.next_line() => {
+                let Ok(Some(line)) = line else {
+                    break End::ConnectionLost;
+                };
+                heard = Instant::now();
+                // Likely something a newer server added
+                let Ok(response) = serde_json::from_str::<Response>(&line) else {
+                    continue;
+                };
+                shared.lock().unwrap().observe(&response);
+                let answers = pending.front().is_some_and(|command| {
+                    matches!(response, Response::Error { .. } | Response::RateLimited { .. })
+                        || (command.expects)(&response)
+                });
+                if answers {
+                    if let Some(reply) = pending.pop_front().and_then(|command| command.reply) {
+                        let _ = reply.send(Ok(response));
+                    }
+                }
+            }
+            _ = heartbeat.tick() => {
+                if heard.elapsed() > options.heartbeat * options.missed_limit {
+                    break End::ConnectionLost;
+                }
+                let ping = Command {
+                    message: Message::Ping,
+                    expects: |response| matches!(response, Response::Pong),
+                    reply: None,
+                };
+                let sent = link.send(&ping.message).await;
+                pending.push_back(ping);
+                if sent.is_err() {
+                    break End::ConnectionLost;
+                }
+            }
+        }
+    };
+
+    for reply in pending.into_iter().filter_map(|command| command.reply) {
+        let _ = reply.send(Err(CONNECTION_LOST.to_string()));
+    }
+    end
+}
+
+/// Dials again, backing off between attempts.
+async fn reconnect(addr: &str, shared: &Mutex<Shared>, options: &ClientOptions) -> Option<Link> {
+    let mut delay = options.reconnect_delay;
+    for _ in 0..options.reconnect_attempts {
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        if let Ok(link) = resume(addr, shared, options.locale).await {
+            return Some(link);
+        }
+    }
+    None
+}
+
+/// Connects and takes the client's seat back, if it has one, catching up
+/// on the events it missed.
+async fn resume(addr: &str, shared: &Mutex<Shared>, locale: Option<Locale>) -> Result<Link, String> {
+    let mut link = Link::open(addr, locale).await?;
+    let token = shared.lock().unwrap().session_token;
+    if let Some(token) = token {
+        let response = link.request(&Message::Reconnect { token }).await?;
+        let mut shared = shared.lock().unwrap();
+        match response {
+            Response::Reconnected { .. } => shared.observe(&response),
+            // The game is gone, or the player was removed meanwhile
+            Response::Error { .. } => shared.lose_seat(),
+            other => return Err(format!("Expected Reconnected, got {}", other.kind())),
+        }
+    }
+    Ok(link)
+}
+
+/// Responses the server only ever pushes, so a `Client::request` never
+/// takes them for its reply.
+pub(crate) fn is_push(response: &Response) -> bool {
+    matches!(
+        response,
+        Response::StateUpdate { .. }
+            | Response::StateDelta { .. }
+            | Response::TurnTimedOut { .. }
+            | Response::PlayerDisconnected { .. }
+            | Response::PlayerReconnected { .. }
+            | Response::RoundResult { .. }
+            | Response::RematchStarted { .. }
+            | Response::MatchFound { .. }
+    )
+}
@@ -0,0 +1,436 @@
+//! A typed async client for Flip7 servers, so apps, bots and tests play
+//! without speaking the wire protocol themselves.
+//!
+//! `Client::connect` opens a connection and does the handshake; `join`,
+//! `start` and `make_move` then play a seat, and `events` streams the
+//! game's `GameEvent`s as the server reports them. A task in the background
+//! sends the heartbeats the server expects and, when the connection drops,
+//! dials again and takes the seat back with its session token, passing on
+//! the events missed meanwhile. Moves cut off by the drop are sent again
+//! under the same `MoveId` once reconnected, so none is played twice.
+//! `verify_state` checks the state held against the server's and takes the
+//! server's over it if they diverged.
+//!
+//! It speaks JSON lines over TCP, the server's default framing.
+//! `discover_local_games` finds servers on the local network to connect to.
+
+mod connection;
+
+use connection::{Command, Link, Shared, CONNECTION_LOST};
+use futures_core::Stream;
+use game_core::events::GameEvent;
+use game_core::{GameMove, GameState};
+use net::{Message, MoveId, Response};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+pub use net::{GameId, JoinCode, Locale, PlayerId};
+pub use net::lan::{discover_local_games, LocalHost};
+
+/// How many times a move cut off by a lost connection is sent again.
+const MOVE_RESUBMITS: u32 = 3;
+
+/// How the background task keeps the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientOptions {
+    /// How often a `Ping` is sent; keep it under the server's heartbeat
+    /// interval
+    pub heartbeat: Duration,
+    /// Heartbeats the server may leave unanswered before the connection
+    /// counts as lost
+    pub missed_limit: u32,
+    /// Times to dial again once the connection is lost, before giving up
+    pub reconnect_attempts: u32,
+    /// Wait before dialing again, doubled after each failed attempt
+    pub reconnect_delay: Duration,
+    /// Language the server sends errors in, English if unset
+    pub locale: Option<Locale>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            heartbeat: Duration::from_secs(5),
+            missed_limit: 3,
+            reconnect_attempts: 5,
+            reconnect_delay: Duration::from_millis(500),
+            locale: None,
+        }
+    }
+}
+
+/// The seat a client plays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seat {
+    pub game_id: GameId,
+    pub player_id: PlayerId,
+    /// For other players to join the game with
+    pub join_code: JoinCode,
+}
+
+/// A connection to a server, playing one seat at a time. Dropping it closes
+/// the connection.
+pub struct Client {
+    commands: mpsc::UnboundedSender<Command>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Client {
+    /// Connects to a server at `tcp://HOST:PORT`, or just `HOST:PORT`.
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        Self::connect_with(url, ClientOptions::default()).await
+    }
+
+    pub async fn connect_with(url: &str, options: ClientOptions) -> Result<Self, String> {
+        let addr = address(url)?;
+        let link = Link::open(&addr, options.locale).await?;
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(connection::run(addr, link, receiver, shared.clone(), options));
+        Ok(Self { commands, shared })
+    }
+
+    /// Sends any message and waits for its reply; errors the server answers
+    /// with come back as `Err`. Replies that the server also pushes to
+    /// other players, like `PlayerKicked`, may be mistaken for a push that
+    /// came in first.
+    pub async fn request(&self, message: Message) -> Result<Response, String> {
+        self.send(message, |response| !connection::is_push(response)).await
+    }
+
+    async fn send(&self, message: Message, expects: fn(&Response) -> bool) -> Result<Response, String> {
+        let (reply, receiver) = oneshot::channel();
+        let command = Command {
+            message,
+            expects,
+            reply: Some(reply),
+        };
+        let closed = || "The client gave up reconnecting to the server".to_string();
+        self.commands.send(command).map_err(|_| closed())?;
+        match receiver.await.map_err(|_| closed())?? {
+            Response::Error { message } => Err(message.into()),
+            Response::RateLimited { limit, .. } => Err(format!("Over the server's {:?} limit", limit)),
+            response => Ok(response),
+        }
+    }
+
+    /// Creates a game and takes its first seat.
+    pub async fn join(&self, player_name: &str) -> Result<Seat, String> {
+        self.take_seat(player_name, None).await
+    }
+
+    /// Takes a seat in an existing game.
+    pub async fn join_game(&self, player_name: &str, game_id: GameId) -> Result<Seat, String> {
+        self.take_seat(player_name, Some(game_id)).await
+    }
+
+    async fn take_seat(&self, player_name: &str, game_id: Option<GameId>) -> Result<Seat, String> {
+        let message = Message::JoinGame {
+            player_name: player_name.to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        match self.send(message, |response| matches!(response, Response::GameJoined { .. })).await? {
+            Response::GameJoined {
+                game_id,
+                join_code,
+                player_id,
+                ..
+            } => Ok(Seat {
+                game_id,
+                player_id,
+                join_code,
+            }),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Starts the client's game, or its next round, which only its host may
+    /// do.
+    pub async fn start(&self) -> Result<(), String> {
+        let game_id = self.seated()?.game_id;
+        let host = self.shared.lock().unwrap().session_token;
+        self.send(Message::StartGame { game_id, host }, |response| {
+            matches!(response, Response::GameStarted { .. })
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Makes a move in the client's game and returns the hash of the state
+    /// it led to.
+    pub async fn make_move(&self, game_move: GameMove) -> Result<u64, String> {
+        let game_id = self.seated()?.game_id;
+        let move_id = MoveId::new();
+        let mut resubmits = 0;
+        loop {
+            let message = Message::MakeMove {
+                game_id,
+                game_move: game_move.clone(),
+                move_id: Some(move_id),
+            };
+            match self.send(message, |response| matches!(response, Response::MoveAccepted { .. })).await {
+                Ok(Response::MoveAccepted { state_hash, .. }) => return Ok(state_hash),
+                Ok(other) => return Err(unexpected(&other)),
+                // Sent again once reconnected; the server remembers the move id
+                Err(err) if err == CONNECTION_LOST && resubmits < MOVE_RESUBMITS => resubmits += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Checks the state the client holds against the server's, which sends
+    /// its own back if they differ. Returns whether it did; the events the
+    /// client hadn't passed on then come out of `events`.
+    pub async fn verify_state(&self) -> Result<bool, String> {
+        let game_id = self.seated()?.game_id;
+        let state_hash = self.state().ok_or("No state of the game yet")?.state_hash();
+        let ack = Message::AckState { game_id, state_hash };
+        match self
+            .send(ack, |response| {
+                matches!(response, Response::StateAcked { .. } | Response::Resync { .. })
+            })
+            .await?
+        {
+            Response::StateAcked { .. } => Ok(false),
+            Response::Resync { .. } => Ok(true),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Draws a card for the client's seat.
+    pub async fn draw(&self) -> Result<u64, String> {
+        let player_id = self.seated()?.player_id.to_string();
+        self.make_move(GameMove::Draw { player_id }).await
+    }
+
+    /// Stays for the client's seat.
+    pub async fn stay(&self) -> Result<u64, String> {
+        let player_id = self.seated()?.player_id.to_string();
+        self.make_move(GameMove::Stay { player_id }).await
+    }
+
+    /// The events of the client's game from now on, including those of its
+    /// rematches. The stream ends once the client gives up reconnecting.
+    pub fn events(&self) -> Events {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.shared.lock().unwrap().subscribers.push(sender);
+        Events { receiver }
+    }
+
+    pub fn seat(&self) -> Option<Seat> {
+        self.shared.lock().unwrap().seat.clone()
+    }
+
+    /// The latest state of the client's game the server sent.
+    pub fn state(&self) -> Option<GameState> {
+        self.shared.lock().unwrap().state.clone()
+    }
+
+    fn seated(&self) -> Result<Seat, String> {
+        self.seat().ok_or_else(|| "Join a game first".to_string())
+    }
+}
+
+/// A `Stream` of a client's game events; see `Client::events`.
+pub struct Events {
+    receiver: mpsc::UnboundedReceiver<GameEvent>,
+}
+
+impl Stream for Events {
+    type Item = GameEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<GameEvent>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// The `HOST:PORT` a URL points at.
+fn address(url: &str) -> Result<String, String> {
+    match url.split_once("://") {
+        None => Ok(url.to_string()),
+        Some(("tcp", addr)) => Ok(addr.trim_end_matches('/').to_string()),
+        Some((scheme, _)) => Err(format!("Unsupported scheme '{}'; only tcp:// is spoken", scheme)),
+    }
+}
+
+fn unexpected(response: &Response) -> String {
+    format!("Unexpected reply {}", response.kind())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use net::{Framing, GameServer, TcpTransport, Transport, TrustLevel};
+    use std::net::SocketAddr;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task::JoinHandle;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    /// A server trusting its clients no more than a public one does.
+    async fn serve() -> SocketAddr {
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::Lines)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+        addr
+    }
+
+    /// Forwards connections to `upstream` until they are cut.
+    async fn proxy(upstream: SocketAddr) -> (SocketAddr, Arc<Mutex<Vec<JoinHandle<()>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut client, _)) = listener.accept().await {
+                let forward = tokio::spawn(async move {
+                    let mut server = TcpStream::connect(upstream).await.unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                });
+                accepted.lock().unwrap().push(forward);
+            }
+        });
+        (addr, connections)
+    }
+
+    /// Waits for the first event the predicate holds for.
+    async fn next_matching(events: &mut Events, predicate: impl Fn(&GameEvent) -> bool) -> GameEvent {
+        timeout(WAIT, async {
+            loop {
+                let event = events.next().await.expect("The event stream ended");
+                if predicate(&event) {
+                    return event;
+                }
+            }
+        })
+        .await
+        .expect("Timed out waiting for an event")
+    }
+
+    /// The one of the two clients whose turn it is.
+    fn to_play<'a>(alice: &'a Client, bob: &'a Client) -> &'a Client {
+        let state = alice.state().unwrap();
+        let current = state.current_player().unwrap().id.clone();
+        if current == alice.seat().unwrap().player_id.to_string() {
+            alice
+        } else {
+            bob
+        }
+    }
+
+    #[tokio::test]
+    async fn test_play_over_the_network() {
+        let addr = serve().await;
+        let alice = Client::connect(&format!("tcp://{}", addr)).await.unwrap();
+        let bob = Client::connect(&addr.to_string()).await.unwrap();
+        assert!(alice.draw().await.is_err());
+
+        let seat = alice.join("Alice").await.unwrap();
+        let bob_seat = bob.join_game("Bob", seat.game_id).await.unwrap();
+        assert_eq!(bob_seat.join_code, seat.join_code);
+        assert_eq!(alice.seat(), Some(seat));
+
+        let mut events = alice.events();
+        alice.start().await.unwrap();
+        next_matching(&mut events, |event| matches!(event, GameEvent::RoundStarted { .. })).await;
+        // Dealt cards come with the round's first update
+        next_matching(&mut events, |event| matches!(event, GameEvent::CardDealt { .. })).await;
+
+        let player = to_play(&alice, &bob);
+        let player_id = player.seat().unwrap().player_id.to_string();
+        player.stay().await.unwrap();
+        let stayed = next_matching(&mut events, |event| matches!(event, GameEvent::PlayerStayed { .. })).await;
+        assert_eq!(stayed, GameEvent::PlayerStayed { player_id });
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_and_resumes_the_seat() {
+        let server = serve().await;
+        let (addr, connections) = proxy(server).await;
+        let options = ClientOptions {
+            reconnect_delay: Duration::from_millis(10),
+            ..ClientOptions::default()
+        };
+        let alice = Client::connect_with(&addr.to_string(), options).await.unwrap();
+        let bob = Client::connect(&server.to_string()).await.unwrap();
+        let seat = alice.join("Alice").await.unwrap();
+        bob.join_game("Bob", seat.game_id).await.unwrap();
+        let mut events = alice.events();
+        alice.start().await.unwrap();
+        next_matching(&mut events, |event| matches!(event, GameEvent::CardDealt { .. })).await;
+
+        // Cut Alice off; whoever is up moves while she dials again
+        for connection in connections.lock().unwrap().drain(..) {
+            connection.abort();
+        }
+        let player = to_play(&alice, &bob);
+        let player_id = player.seat().unwrap().player_id.to_string();
+        timeout(WAIT, player.stay()).await.unwrap().unwrap();
+        let stayed = next_matching(&mut events, |event| matches!(event, GameEvent::PlayerStayed { .. })).await;
+        assert_eq!(stayed, GameEvent::PlayerStayed { player_id });
+
+        assert_eq!(alice.seat(), Some(seat));
+        timeout(WAIT, alice.request(Message::ListGames)).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resyncs_a_diverged_state() {
+        let addr = serve().await;
+        let alice = Client::connect(&addr.to_string()).await.unwrap();
+        let bob = Client::connect(&addr.to_string()).await.unwrap();
+        let seat = alice.join("Alice").await.unwrap();
+        bob.join_game("Bob", seat.game_id).await.unwrap();
+        let mut events = alice.events();
+        alice.start().await.unwrap();
+        next_matching(&mut events, |event| matches!(event, GameEvent::CardDealt { .. })).await;
+        assert!(!alice.verify_state().await.unwrap());
+
+        // Lose the last event, as if an update went missing
+        let lost = {
+            let mut shared = alice.shared.lock().unwrap();
+            shared.state.as_mut().unwrap().events.pop().unwrap()
+        };
+        assert!(alice.verify_state().await.unwrap());
+        next_matching(&mut events, |event| *event == lost).await;
+        let Response::GameState { game_state } = alice
+            .request(Message::GetGameState { game_id: seat.game_id, access: None })
+            .await
+            .unwrap()
+        else {
+            panic!("Expected GameState");
+        };
+        assert_eq!(alice.state(), Some(*game_state));
+    }
+
+    #[tokio::test]
+    async fn test_errors_in_the_locale() {
+        let addr = serve().await;
+        let options = ClientOptions {
+            locale: Some(Locale::Fr),
+            ..ClientOptions::default()
+        };
+        let client = Client::connect_with(&addr.to_string(), options).await.unwrap();
+        assert_eq!(
+            client.join_game("Alice", GameId::new()).await,
+            Err("Partie introuvable".to_string())
+        );
+    }
+
+    #[test]
+    fn test_address() {
+        assert_eq!(address("tcp://127.0.0.1:7777/").unwrap(), "127.0.0.1:7777");
+        assert_eq!(address("localhost:7777").unwrap(), "localhost:7777");
+        assert!(address("ws://localhost:7778").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Baselines for the engine's hot paths: shuffling, drawing, whole rounds
+//! and saves. Run with `cargo bench --bench game`; flip7 detection and hand
+//! building are in `hand`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use game_core::{Deck, GameState};
+
+fn bench_shuffle(c: &mut Criterion) {
+    c.bench_function("shuffle", |b| {
+        b.iter_batched(
+            || Deck::new(7),
+            |mut deck| {
+                deck.shuffle();
+                deck
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_draw(c: &mut Criterion) {
+    c.bench_function("draw_until_bust", |b| {
+        b.iter_batched(
+            || {
+                let mut game = GameState::new_with_seed(7);
+                game.add_player("p1".to_string(), "Alice".to_string());
+                game.start_round().unwrap();
+                game
+            },
+            |mut game| {
+                while !game.round_state.is_finished {
+                    game.player_draw("p1").unwrap();
+                }
+                game
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+// Four players who draw below 15, the way the bots in `net` play.
+fn play_round(game: &mut GameState) {
+    game.start_round().unwrap();
+    while !game.round_state.is_finished {
+        let player = game.current_player().unwrap();
+        let id = player.id.clone();
+        if player.hand.total_value() < 15 {
+            game.player_draw(&id).unwrap();
+        } else {
+            game.player_stay(&id).unwrap();
+        }
+    }
+    game.finish_round().unwrap();
+}
+
+fn four_players(seed: u64) -> GameState {
+    let mut game = GameState::new_with_seed(seed);
+    for i in 0..4 {
+        game.add_player(format!("p{}", i), format!("Player {}", i));
+    }
+    game
+}
+
+fn bench_round(c: &mut Criterion) {
+    c.bench_function("full_round", |b| {
+        b.iter_batched(
+            || four_players(7),
+            |mut game| {
+                play_round(&mut game);
+                game
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_json(c: &mut Criterion) {
+    let mut game = four_players(7);
+    for _ in 0..5 {
+        play_round(&mut game);
+    }
+    game.start_round().unwrap();
+
+    c.bench_function("to_json", |b| {
+        b.iter(|| black_box(&game).to_json().unwrap())
+    });
+    let json = game.to_json().unwrap();
+    c.bench_function("from_json", |b| {
+        b.iter(|| GameState::from_json(black_box(&json)).unwrap())
+    });
+}
+
+// Run with `--features archive` to compare with JSON.
+#[cfg(feature = "archive")]
+fn bench_archive(c: &mut Criterion) {
+    let mut game = four_players(7);
+    for _ in 0..5 {
+        play_round(&mut game);
+    }
+    game.start_round().unwrap();
+
+    c.bench_function("to_archive", |b| {
+        b.iter(|| black_box(&game).to_archive().unwrap())
+    });
+    let bytes = game.to_archive().unwrap();
+    c.bench_function("access_archive", |b| {
+        b.iter(|| {
+            GameState::access_archive(black_box(&bytes))
+                .unwrap()
+                .players
+                .len()
+        })
+    });
+    c.bench_function("from_archive", |b| {
+        b.iter(|| GameState::from_archive(black_box(&bytes)).unwrap())
+    });
+}
+
+#[cfg(not(feature = "archive"))]
+fn bench_archive(_: &mut Criterion) {}
+
+criterion_group!(
+    benches,
+    bench_shuffle,
+    bench_draw,
+    bench_round,
+    bench_json,
+    bench_archive
+);
+criterion_main!(benches);
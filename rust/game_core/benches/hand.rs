@@ -0,0 +1,60 @@
+//! Hand operations that simulations run millions of times.
+//!
+//! `cargo bench --bench hand` compares `Hand::can_sum_to` with the recursive
+//! subset search it replaced, on hands where no subset hits the target and
+//! the search has to try them all, and times building a hand from scratch.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use game_core::{Card, Hand};
+
+// The search `can_sum_to` used before, exponential in the number of cards.
+fn recursive_sum_to(values: &[u8], target: u8) -> bool {
+    if target == 0 {
+        return true;
+    }
+    for (i, &value) in values.iter().enumerate() {
+        if value == target {
+            return true;
+        }
+        if value < target && recursive_sum_to(&values[i + 1..], target - value) {
+            return true;
+        }
+    }
+    false
+}
+
+fn bench_flip7(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flip7");
+    for size in [4, 8, 16, 24] {
+        let mut hand = Hand::new();
+        for i in 0..size {
+            // Evens never sum to 7; small ones keep every subset under the target
+            hand.add_card(Card::new(if i % 2 == 0 { 2 } else { 4 }));
+        }
+        let values: Vec<u8> = hand.cards.iter().map(|card| card.value()).collect();
+
+        group.bench_with_input(BenchmarkId::new("dp", size), &hand, |b, hand| {
+            b.iter(|| black_box(hand).can_sum_to(7))
+        });
+        group.bench_with_input(BenchmarkId::new("recursive", size), &values, |b, values| {
+            b.iter(|| recursive_sum_to(black_box(values), 7))
+        });
+    }
+    group.finish();
+}
+
+fn bench_build_hand(c: &mut Criterion) {
+    let values = [3, 9, 0, 5, 2, 1];
+    c.bench_function("build_hand", |b| {
+        b.iter(|| {
+            let mut hand = Hand::new();
+            for value in black_box(values) {
+                hand.add_card(Card::new(value));
+            }
+            (hand.is_bust(), hand.has_flip7())
+        })
+    });
+}
+
+criterion_group!(benches, bench_flip7, bench_build_hand);
+criterion_main!(benches);
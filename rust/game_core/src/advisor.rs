@@ -0,0 +1,123 @@
+//! Coach mode: advice on the next move for hint UIs, from the same
+//! evaluation the Hard bots play with.
+
+use crate::bots::{Budget, MonteCarlo, PlayerView, Verdict};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Rollouts behind each recommendation. The search is seeded the same way
+/// every time, so a given position always gets the same advice.
+pub const ADVISOR_ROLLOUTS: u32 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Action {
+    Draw,
+    Stay,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Recommendation {
+    pub action: Action,
+    /// Chance that drawing one more card busts the hand
+    pub bust_probability: f64,
+    /// Round score the player can expect by following the advice
+    pub expected_value: f64,
+    /// One sentence for the player on why
+    pub explanation: String,
+}
+
+pub fn recommend(view: &PlayerView) -> Recommendation {
+    let verdict = MonteCarlo::new(Budget::Rollouts(ADVISOR_ROLLOUTS), 0).evaluate(view);
+    let bust_probability = view.bust_probability();
+    let stay_value = view.stay_value();
+    let bust_percent = (bust_probability * 100.0).round();
+
+    let (expected_value, explanation) = match verdict {
+        Verdict::ReachesTarget => (
+            stay_value as f64,
+            format!(
+                "Staying on {} takes you to {}, enough to reach the {} target.",
+                stay_value,
+                view.score + stay_value,
+                view.target_score
+            ),
+        ),
+        Verdict::DeckEmpty => (
+            stay_value as f64,
+            "The deck is empty, so there is nothing to draw.".to_string(),
+        ),
+        Verdict::CannotBust => (
+            view.expected_draw_value(),
+            format!(
+                "No card left can bust your {}, so drawing is free.",
+                view.hand.total_value()
+            ),
+        ),
+        Verdict::DrawPaysOff { draw_value } => (
+            draw_value,
+            format!(
+                "{}% bust chance, but drawing is worth {:.1} points on average against {} for staying.",
+                bust_percent, draw_value, stay_value
+            ),
+        ),
+        Verdict::StayPaysOff { draw_value } => (
+            stay_value as f64,
+            format!(
+                "{}% bust chance: drawing is only worth {:.1} points on average against {} for staying.",
+                bust_percent, draw_value, stay_value
+            ),
+        ),
+    };
+
+    Recommendation {
+        action: if verdict.draws() {
+            Action::Draw
+        } else {
+            Action::Stay
+        },
+        bust_probability,
+        expected_value,
+        explanation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Card, GameState, Hand};
+
+    fn view(hand: &[u8], deck: &[u8]) -> PlayerView {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.players[0].hand = Hand::new();
+        for value in hand {
+            game.players[0].hand.add_card(Card::new(*value));
+        }
+        game.deck.cards = deck.iter().map(|value| Card::new(*value)).collect();
+        game.player_view("p1").unwrap()
+    }
+
+    #[test]
+    fn test_recommend() {
+        let advice = recommend(&view(&[10], &[1, 2, 3]));
+        assert_eq!(advice.action, Action::Draw);
+        assert_eq!(advice.bust_probability, 0.0);
+        assert_eq!(advice.expected_value, 12.0);
+        assert!(advice.explanation.contains("No card left can bust your 10"));
+
+        let advice = recommend(&view(&[10, 9], &[1, 2, 3, 4, 5, 6]));
+        assert_eq!(advice.action, Action::Stay);
+        assert_eq!(advice.expected_value, 19.0);
+        assert!(advice.explanation.starts_with("67% bust chance"));
+
+        let advice = recommend(&view(&[12], &[1, 2, 3, 4, 5, 6, 12]));
+        assert_eq!(advice.action, Action::Draw);
+        assert!(advice.expected_value > 12.0);
+
+        // The same position always gets the same advice
+        assert_eq!(advice, recommend(&view(&[12], &[1, 2, 3, 4, 5, 6, 12])));
+        assert_eq!(recommend(&view(&[12], &[])).action, Action::Stay);
+    }
+}
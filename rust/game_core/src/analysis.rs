@@ -0,0 +1,100 @@
+//! Odds of the next draw, for bots, the CLI and hint UIs.
+
+use crate::errors;
+use crate::rules::RuleBehavior;
+use crate::{Card, GameState, Hand};
+
+/// Chance that drawing one card from `remaining` busts `hand`.
+pub fn bust_probability_for(hand: &Hand, remaining: &[Card], rules: &RuleBehavior) -> f64 {
+    if remaining.is_empty() {
+        return 0.0;
+    }
+
+    let total = hand.total_value() as u32;
+    let busting = remaining
+        .iter()
+        .filter(|card| total + card.value as u32 > rules.bust_threshold as u32)
+        .count();
+    busting as f64 / remaining.len() as f64
+}
+
+/// Expected round score of `hand` after drawing one card from `remaining`.
+/// With nothing left to draw this is the hand's current score.
+pub fn expected_draw_value_for(hand: &Hand, remaining: &[Card], rules: &RuleBehavior) -> f64 {
+    if remaining.is_empty() {
+        return rules.score_hand(hand) as f64;
+    }
+
+    let total: u32 = remaining
+        .iter()
+        .map(|card| {
+            let mut next = hand.clone();
+            next.add_card(*card);
+            rules.score_hand(&next)
+        })
+        .sum();
+    total as f64 / remaining.len() as f64
+}
+
+impl GameState {
+    /// Chance that the player busts if they draw now, given the cards left in the deck.
+    pub fn bust_probability(&self, player_id: &str) -> Result<f64, String> {
+        let hand = self.hand_of(player_id)?;
+        Ok(bust_probability_for(hand, &self.deck.cards, &self.rules()))
+    }
+
+    /// Round score the player can expect after drawing one more card, to
+    /// compare with the score of staying (`round_scores`).
+    pub fn expected_draw_value(&self, player_id: &str) -> Result<f64, String> {
+        let hand = self.hand_of(player_id)?;
+        Ok(expected_draw_value_for(
+            hand,
+            &self.deck.cards,
+            &self.rules(),
+        ))
+    }
+
+    fn hand_of(&self, player_id: &str) -> Result<&Hand, String> {
+        self.player(player_id)
+            .map(|p| &p.hand)
+            .ok_or_else(|| errors::PLAYER_NOT_FOUND.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game_with_deck(hand: &[u8], deck: &[u8]) -> GameState {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        for value in hand {
+            game.players[0].hand.add_card(Card::new(*value));
+        }
+        game.deck.cards = deck.iter().map(|value| Card::new(*value)).collect();
+        game
+    }
+
+    #[test]
+    fn test_bust_probability() {
+        // 18 in hand: only the 4 and the 12 bust
+        let game = game_with_deck(&[10, 8], &[1, 2, 4, 12]);
+        assert_eq!(game.bust_probability("p1").unwrap(), 0.5);
+
+        let empty = game_with_deck(&[10, 8], &[]);
+        assert_eq!(empty.bust_probability("p1").unwrap(), 0.0);
+
+        assert!(game.bust_probability("nobody").is_err());
+    }
+
+    #[test]
+    fn test_expected_draw_value() {
+        // 18 + 1 = 19, 18 + 2 = 20, the 4 and 12 bust for 0
+        let game = game_with_deck(&[10, 8], &[1, 2, 4, 12]);
+        assert_eq!(game.expected_draw_value("p1").unwrap(), 39.0 / 4.0);
+
+        // Drawing the 4 next to a 3 makes a Flip7
+        let game = game_with_deck(&[3], &[4]);
+        assert_eq!(game.expected_draw_value("p1").unwrap(), 21.0);
+    }
+}
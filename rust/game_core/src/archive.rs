@@ -0,0 +1,100 @@
+//! Zero-copy archives of a `GameState`, for the server's broadcast path.
+//!
+//! `to_archive` lays the game out so that clients can read it straight from
+//! the received bytes with `access_archive`, which only validates the layout,
+//! and deserialize it with `from_archive` when they need an owned game.
+//! Archives have no schema migrations: they are for live traffic between
+//! matching builds, not for saves. Like a synced game, they are trusted to
+//! hold valid cards.
+
+use crate::{ArchivedGameState, GameState};
+use rkyv::rancor::Error;
+use rkyv::util::AlignedVec;
+
+impl GameState {
+    pub fn to_archive(&self) -> Result<AlignedVec, Error> {
+        rkyv::to_bytes::<Error>(self)
+    }
+
+    /// Read-only view of an archived game, without copying or deserializing it.
+    /// The bytes must be aligned to 16, as an `AlignedVec` is.
+    pub fn access_archive(bytes: &[u8]) -> Result<&ArchivedGameState, Error> {
+        rkyv::access::<ArchivedGameState, Error>(bytes)
+    }
+
+    /// Deserializes an archived game. Observers and the scoring strategy are
+    /// not archived and start out as the defaults, as with `from_json`.
+    pub fn from_archive(bytes: &[u8]) -> Result<Self, Error> {
+        rkyv::from_bytes::<Self, Error>(bytes)
+    }
+}
+
+/// Leaves the deck's generator out of archives; like a JSON save, an archived
+/// deck comes back with the default one.
+#[cfg(feature = "rng")]
+pub struct SkipRng;
+
+#[cfg(feature = "rng")]
+mod skip_rng {
+    use super::SkipRng;
+    use rand_chacha::ChaCha8Rng;
+    use rkyv::rancor::Fallible;
+    use rkyv::with::{ArchiveWith, DeserializeWith, SerializeWith};
+    use rkyv::Place;
+
+    impl ArchiveWith<ChaCha8Rng> for SkipRng {
+        type Archived = ();
+        type Resolver = ();
+
+        fn resolve_with(_: &ChaCha8Rng, _: Self::Resolver, _: Place<Self::Archived>) {}
+    }
+
+    impl<S: Fallible + ?Sized> SerializeWith<ChaCha8Rng, S> for SkipRng {
+        fn serialize_with(_: &ChaCha8Rng, _: &mut S) -> Result<Self::Resolver, S::Error> {
+            Ok(())
+        }
+    }
+
+    impl<D: Fallible + ?Sized> DeserializeWith<(), ChaCha8Rng, D> for SkipRng {
+        fn deserialize_with(_: &(), _: &mut D) -> Result<ChaCha8Rng, D::Error> {
+            Ok(crate::default_rng())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let mut game = GameState::new_with_seed(7);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+
+        let bytes = game.to_archive().unwrap();
+        let archived = GameState::access_archive(&bytes).unwrap();
+        assert_eq!(archived.players.len(), 2);
+        assert_eq!(archived.players[1].name, "Bob");
+        assert!(archived.players[0].has_stayed);
+        assert_eq!(archived.round_state.round_number, 1);
+
+        let loaded = GameState::from_archive(&bytes).unwrap();
+        assert_eq!(loaded, game);
+        assert_eq!(loaded.state_hash(), game.state_hash());
+    }
+
+    #[test]
+    fn test_corrupt_archive_is_rejected() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        let bytes = game.to_archive().unwrap();
+
+        let mut truncated = AlignedVec::<16>::new();
+        truncated.extend_from_slice(&bytes[..bytes.len() / 2]);
+        assert!(GameState::access_archive(&truncated).is_err());
+        assert!(GameState::from_archive(&truncated).is_err());
+    }
+}
@@ -0,0 +1,675 @@
+//! Computer players: a `Strategy` picks a move from what its seat can see,
+//! so the CLI, the server and simulations can all fill seats with bots.
+//!
+//! Strategies only get a `PlayerView`: every hand is face up in Flip 7, but
+//! the order of the deck is hidden, so they see the cards left in it sorted.
+
+use crate::analysis::{bust_probability_for, expected_draw_value_for};
+use crate::rules::RuleBehavior;
+#[cfg(feature = "rng")]
+use crate::shuffle::ShuffleSource;
+use crate::{Card, GameMove, GameState, Hand, Player};
+#[cfg(feature = "rng")]
+use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "rng")]
+use rand_core::{RngCore, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What one player knows on their turn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerView {
+    pub player_id: String,
+    pub hand: Hand,
+    pub score: u32,
+    pub target_score: u32,
+    /// Everyone else still in the game, in seat order
+    pub opponents: Vec<Player>,
+    /// Cards left in the deck, sorted so their order gives nothing away
+    pub remaining: Vec<Card>,
+    rules: RuleBehavior,
+}
+
+impl PlayerView {
+    /// Chance that drawing now busts the hand.
+    pub fn bust_probability(&self) -> f64 {
+        bust_probability_for(&self.hand, &self.remaining, &self.rules)
+    }
+
+    /// Expected round score after drawing one more card.
+    pub fn expected_draw_value(&self) -> f64 {
+        expected_draw_value_for(&self.hand, &self.remaining, &self.rules)
+    }
+
+    /// Round score of staying on the current hand.
+    pub fn stay_value(&self) -> u32 {
+        self.rules.score_hand(&self.hand)
+    }
+
+    /// The view boiled down to numbers, for training policies outside the engine.
+    pub fn features(&self) -> Features {
+        Features {
+            hand_total: self.hand.total_value() as u32,
+            hand_size: self.hand.cards.len() as u32,
+            has_flip7: self.rules.has_flip7(&self.hand),
+            stay_value: self.stay_value(),
+            bust_probability: self.bust_probability(),
+            expected_draw_value: self.expected_draw_value(),
+            score: self.score,
+            points_to_target: self.target_score.saturating_sub(self.score),
+            best_opponent_score: self.opponents.iter().map(|p| p.score).max().unwrap_or(0),
+            opponents_drawing: self.opponents.iter().filter(|p| !p.has_stayed).count() as u32,
+            cards_remaining: self.remaining.len() as u32,
+        }
+    }
+}
+
+/// Numeric summary of a `PlayerView`; see `PlayerView::features`.
+///
+/// Exported training data is read by code outside this repository, so the
+/// schema only ever grows: new features go at the end of the struct and of
+/// `COLUMNS`, and existing ones keep their name and meaning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Features {
+    pub hand_total: u32,
+    pub hand_size: u32,
+    pub has_flip7: bool,
+    pub stay_value: u32,
+    pub bust_probability: f64,
+    pub expected_draw_value: f64,
+    pub score: u32,
+    pub points_to_target: u32,
+    pub best_opponent_score: u32,
+    /// Opponents who haven't stayed or busted this round
+    pub opponents_drawing: u32,
+    pub cards_remaining: u32,
+}
+
+impl Features {
+    /// Names of the `values`, in order.
+    pub const COLUMNS: [&'static str; 11] = [
+        "hand_total",
+        "hand_size",
+        "has_flip7",
+        "stay_value",
+        "bust_probability",
+        "expected_draw_value",
+        "score",
+        "points_to_target",
+        "best_opponent_score",
+        "opponents_drawing",
+        "cards_remaining",
+    ];
+
+    /// Every feature as a number, in `COLUMNS` order; `has_flip7` is 0 or 1.
+    pub fn values(&self) -> [f64; 11] {
+        [
+            self.hand_total as f64,
+            self.hand_size as f64,
+            self.has_flip7 as u8 as f64,
+            self.stay_value as f64,
+            self.bust_probability,
+            self.expected_draw_value,
+            self.score as f64,
+            self.points_to_target as f64,
+            self.best_opponent_score as f64,
+            self.opponents_drawing as f64,
+            self.cards_remaining as f64,
+        ]
+    }
+}
+
+/// Picks a seat's moves. Strategies may keep state between turns, so each
+/// seat gets its own instance.
+pub trait Strategy: StrategyClone + Send + Sync {
+    /// Called on the seat's turn with its legal moves, which always include
+    /// a stay. A move that isn't one of them is replaced by a stay.
+    fn choose(&mut self, view: &PlayerView, legal: &[GameMove]) -> GameMove;
+}
+
+/// Lets boxed strategies be cloned, e.g. to seat the same bot in many games.
+/// Implemented for every `Strategy` that is `Clone`.
+pub trait StrategyClone {
+    fn clone_box(&self) -> Box<dyn Strategy>;
+}
+
+impl<T: Strategy + Clone + 'static> StrategyClone for T {
+    fn clone_box(&self) -> Box<dyn Strategy> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Strategy> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Draws while the hand is worth less than the given total, then stays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlwaysStayAt(pub u8);
+
+impl AlwaysStayAt {
+    /// Total `parse_strategy` stays at when it isn't given one.
+    pub const DEFAULT_TOTAL: u8 = 17;
+}
+
+impl Strategy for AlwaysStayAt {
+    fn choose(&mut self, view: &PlayerView, legal: &[GameMove]) -> GameMove {
+        if view.hand.total_value() < self.0 {
+            draw_or_stay(legal)
+        } else {
+            stay(legal)
+        }
+    }
+}
+
+/// Draws while the chance of busting is below the given probability.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThresholdProbability(pub f64);
+
+impl Strategy for ThresholdProbability {
+    fn choose(&mut self, view: &PlayerView, legal: &[GameMove]) -> GameMove {
+        if view.bust_probability() < self.0 {
+            draw_or_stay(legal)
+        } else {
+            stay(legal)
+        }
+    }
+}
+
+/// Picks uniformly among the legal moves, from its own seeded generator.
+#[cfg(feature = "rng")]
+#[derive(Debug, Clone)]
+pub struct Random {
+    rng: ChaCha8Rng,
+}
+
+#[cfg(feature = "rng")]
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+#[cfg(feature = "rng")]
+impl Strategy for Random {
+    fn choose(&mut self, _view: &PlayerView, legal: &[GameMove]) -> GameMove {
+        let index = self.rng.next_u32() as usize % legal.len();
+        legal[index].clone()
+    }
+}
+
+/// How much searching a `MonteCarlo` bot does per move.
+#[cfg(feature = "rng")]
+#[derive(Debug, Clone, Copy)]
+pub enum Budget {
+    /// Play out exactly this many deals
+    Rollouts(u32),
+    /// Keep dealing until `ms` have passed on `clock`, which returns
+    /// milliseconds; the engine never reads the clock itself
+    Time { ms: u64, clock: fn() -> u64 },
+}
+
+/// Deals rolled out between looks at the clock under a time budget.
+#[cfg(feature = "rng")]
+const ROLLOUT_BATCH: u32 = 64;
+
+/// Searches by playing out random deals of the cards left.
+///
+/// Each rollout shuffles the unseen cards and scores the plans "draw k more,
+/// then stay" on that deal. The bot draws if the best plan beats staying on
+/// average. Since every plan is scored on the same deals, they are compared
+/// fairly even with few rollouts. It also stays whenever staying reaches the
+/// target score, and draws without searching when no card left can bust it.
+#[cfg(feature = "rng")]
+#[derive(Debug, Clone)]
+pub struct MonteCarlo {
+    budget: Budget,
+    rng: ChaCha8Rng,
+}
+
+#[cfg(feature = "rng")]
+impl MonteCarlo {
+    /// Rollouts per move when `parse_strategy` isn't given a number.
+    pub const DEFAULT_ROLLOUTS: u32 = 1000;
+
+    /// Most rollouts per move. More hardly changes what the bot plays, and
+    /// servers play bot turns on the clock of everyone at their tables.
+    pub const MAX_ROLLOUTS: u32 = 10_000;
+
+    /// A bot searching on `budget`, down to `MAX_ROLLOUTS` per move.
+    pub fn new(budget: Budget, seed: u64) -> Self {
+        let budget = match budget {
+            Budget::Rollouts(n) => Budget::Rollouts(n.min(Self::MAX_ROLLOUTS)),
+            budget => budget,
+        };
+        Self {
+            budget,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Average round score of the best way to keep drawing, over the
+    /// budget's rollouts; compare it with `PlayerView::stay_value`.
+    pub fn draw_value(&mut self, view: &PlayerView) -> f64 {
+        // Draw k + 1 cards, then stay: total score over the rollouts
+        let mut plan_totals = vec![0u64; view.remaining.len()];
+        let mut rollouts = 0u64;
+        let mut deal = view.remaining.clone();
+
+        let mut rollout_batch = |n: u32, rng: &mut ChaCha8Rng| {
+            for _ in 0..n {
+                rng.shuffle(&mut deal);
+                let mut hand = view.hand.clone();
+                for (k, card) in deal.iter().enumerate() {
+                    hand.add_card(*card);
+                    let score = view.rules.score_hand(&hand) as u64;
+                    if view.rules.is_bust(&hand) {
+                        // Busting ends the turn, so longer plans score the same
+                        plan_totals[k..]
+                            .iter_mut()
+                            .for_each(|total| *total += score);
+                        break;
+                    }
+                    plan_totals[k] += score;
+                }
+            }
+            rollouts += n as u64;
+        };
+        match self.budget {
+            Budget::Rollouts(n) => rollout_batch(n.max(1), &mut self.rng),
+            Budget::Time { ms, clock } => {
+                let started = clock();
+                loop {
+                    rollout_batch(ROLLOUT_BATCH, &mut self.rng);
+                    if clock().saturating_sub(started) >= ms {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let best_plan = plan_totals.iter().copied().max().unwrap_or(0);
+        best_plan as f64 / rollouts as f64
+    }
+}
+
+/// Why a `MonteCarlo` bot draws or stays; see `MonteCarlo::evaluate`.
+#[cfg(feature = "rng")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    /// Stay: that reaches the target score
+    ReachesTarget,
+    /// Stay: there is nothing left to draw
+    DeckEmpty,
+    /// Draw: no card left can bust the hand, and none lowers its score
+    CannotBust,
+    /// Draw: the best way to keep drawing scores `draw_value` on average,
+    /// more than staying
+    DrawPaysOff { draw_value: f64 },
+    /// Stay: drawing only scores `draw_value` on average
+    StayPaysOff { draw_value: f64 },
+}
+
+#[cfg(feature = "rng")]
+impl Verdict {
+    pub fn draws(&self) -> bool {
+        matches!(self, Verdict::CannotBust | Verdict::DrawPaysOff { .. })
+    }
+}
+
+#[cfg(feature = "rng")]
+impl MonteCarlo {
+    /// Decides between drawing and staying, searching only when it has to.
+    pub fn evaluate(&mut self, view: &PlayerView) -> Verdict {
+        if view.score + view.stay_value() >= view.target_score {
+            return Verdict::ReachesTarget;
+        }
+        if view.remaining.is_empty() {
+            return Verdict::DeckEmpty;
+        }
+        if view.bust_probability() == 0.0 {
+            return Verdict::CannotBust;
+        }
+
+        let draw_value = self.draw_value(view);
+        if draw_value > view.stay_value() as f64 {
+            Verdict::DrawPaysOff { draw_value }
+        } else {
+            Verdict::StayPaysOff { draw_value }
+        }
+    }
+}
+
+#[cfg(feature = "rng")]
+impl Strategy for MonteCarlo {
+    fn choose(&mut self, view: &PlayerView, legal: &[GameMove]) -> GameMove {
+        if self.evaluate(view).draws() {
+            draw_or_stay(legal)
+        } else {
+            stay(legal)
+        }
+    }
+}
+
+fn stay(legal: &[GameMove]) -> GameMove {
+    legal
+        .iter()
+        .find(|m| matches!(m, GameMove::Stay { .. }))
+        .expect("staying is always legal")
+        .clone()
+}
+
+fn draw_or_stay(legal: &[GameMove]) -> GameMove {
+    legal
+        .iter()
+        .find(|m| matches!(m, GameMove::Draw { .. }))
+        .cloned()
+        .unwrap_or_else(|| stay(legal))
+}
+
+/// Parses a strategy name as the CLI and server take it: `stay-at[:TOTAL]`
+/// (also called `threshold`),
+/// `bust-chance:PROBABILITY` (e.g. `bust-chance:0.3`), `random[:SEED]`,
+/// `monte-carlo[:ROLLOUTS]`, or a persona preset such as `hard` (see
+/// `persona::BotConfig::preset`).
+pub fn parse_strategy(spec: &str) -> Result<Box<dyn Strategy>, String> {
+    #[cfg(feature = "rng")]
+    if let Some(config) = crate::persona::BotConfig::preset(spec.trim()) {
+        return Ok(Box::new(crate::persona::Bot::from_config(config)));
+    }
+
+    let (name, arg) = match spec.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(arg.trim())),
+        None => (spec.trim(), None),
+    };
+    match (name, arg) {
+        ("stay-at" | "threshold", total) => {
+            let total: u8 = match total {
+                Some(total) => total
+                    .parse()
+                    .map_err(|_| format!("Invalid hand total '{}'", total))?,
+                None => AlwaysStayAt::DEFAULT_TOTAL,
+            };
+            Ok(Box::new(AlwaysStayAt(total)))
+        }
+        ("bust-chance", Some(probability)) => match probability.parse::<f64>() {
+            Ok(p) if (0.0..=1.0).contains(&p) => Ok(Box::new(ThresholdProbability(p))),
+            _ => Err(format!(
+                "Invalid bust chance '{}', expected a probability from 0 to 1",
+                probability
+            )),
+        },
+        #[cfg(feature = "rng")]
+        ("random", seed) => {
+            let seed: u64 = match seed {
+                Some(seed) => seed.parse().map_err(|_| format!("Invalid seed '{}'", seed))?,
+                None => 0,
+            };
+            Ok(Box::new(Random::new(seed)))
+        }
+        #[cfg(feature = "rng")]
+        ("monte-carlo", rollouts) => {
+            let rollouts: u32 = match rollouts {
+                Some(n) => n
+                    .parse()
+                    .map_err(|_| format!("Invalid rollout count '{}'", n))?,
+                None => MonteCarlo::DEFAULT_ROLLOUTS,
+            };
+            // Strategies come from players too, who must not tie up the host
+            if rollouts > MonteCarlo::MAX_ROLLOUTS {
+                return Err(format!(
+                    "Too many rollouts {}, expected at most {}",
+                    rollouts,
+                    MonteCarlo::MAX_ROLLOUTS
+                ));
+            }
+            Ok(Box::new(MonteCarlo::new(Budget::Rollouts(rollouts), 0)))
+        }
+        _ => Err(format!(
+            "Unknown strategy '{}', expected easy, medium, hard, reckless, stay-at[:TOTAL], bust-chance:PROBABILITY, random[:SEED] or monte-carlo[:ROLLOUTS]",
+            spec
+        )),
+    }
+}
+
+impl GameState {
+    /// Moves a player may make right now: none unless it's their turn in a
+    /// running round, otherwise a stay, and a draw if they can still draw.
+    pub fn legal_moves(&self, player_id: &str) -> Vec<GameMove> {
+        let Some(current) = self.current_player() else {
+            return Vec::new();
+        };
+        if self.round_state.is_finished || self.is_paused() || current.id != player_id {
+            return Vec::new();
+        }
+
+        let mut moves = vec![GameMove::Stay {
+            player_id: player_id.to_string(),
+        }];
+        if !current.has_stayed && !self.deck.is_empty() {
+            moves.push(GameMove::Draw {
+                player_id: player_id.to_string(),
+            });
+        }
+        moves
+    }
+
+    /// The game as the given player sees it, or `None` if they aren't in it.
+    pub fn player_view(&self, player_id: &str) -> Option<PlayerView> {
+        let player = self.player(player_id)?;
+        let mut remaining = self.deck.cards.clone();
+        remaining.sort();
+        Some(PlayerView {
+            player_id: player.id.clone(),
+            hand: player.hand.clone(),
+            score: player.score,
+            target_score: self.config.target_score,
+            opponents: self
+                .active_players()
+                .filter(|p| p.id != player_id)
+                .cloned()
+                .collect(),
+            remaining,
+            rules: self.rules(),
+        })
+    }
+
+    /// Asks the strategy for the current player's move, without applying it.
+    /// `None` if nobody can move, e.g. because the round is finished.
+    pub fn bot_move(&self, strategy: &mut dyn Strategy) -> Option<GameMove> {
+        let player = self.current_player()?;
+        let legal = self.legal_moves(&player.id);
+        if legal.is_empty() {
+            return None;
+        }
+        let view = self.player_view(&player.id)?;
+
+        let chosen = strategy.choose(&view, &legal);
+        Some(if legal.contains(&chosen) {
+            chosen
+        } else {
+            stay(&legal)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "rng")]
+    fn game_with_hand(hand: &[u8], deck: &[u8]) -> GameState {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        game.players[0].hand = Hand::new();
+        for value in hand {
+            game.players[0].hand.add_card(Card::new(*value));
+        }
+        game.deck.cards = deck.iter().map(|value| Card::new(*value)).collect();
+        game
+    }
+
+    #[cfg(feature = "rng")]
+    fn draw(player_id: &str) -> GameMove {
+        GameMove::Draw {
+            player_id: player_id.to_string(),
+        }
+    }
+
+    #[cfg(feature = "rng")]
+    fn stay_move(player_id: &str) -> GameMove {
+        GameMove::Stay {
+            player_id: player_id.to_string(),
+        }
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_legal_moves() {
+        let mut game = game_with_hand(&[5], &[3, 1]);
+        assert_eq!(game.legal_moves("p1"), vec![stay_move("p1"), draw("p1")]);
+        assert!(game.legal_moves("p2").is_empty());
+
+        game.deck.cards.clear();
+        assert_eq!(game.legal_moves("p1"), vec![stay_move("p1")]);
+
+        game.pause(0).unwrap();
+        assert!(game.legal_moves("p1").is_empty());
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_player_view_hides_deck_order() {
+        let game = game_with_hand(&[10, 8], &[12, 1, 4, 2]);
+        let view = game.player_view("p1").unwrap();
+        assert_eq!(view.remaining, [1, 2, 4, 12].map(Card::new));
+        assert_eq!(view.opponents.len(), 1);
+        assert_eq!(view.opponents[0].id, "p2");
+        assert_eq!(view.bust_probability(), 0.5);
+        assert_eq!(view.stay_value(), 18);
+        assert!(game.player_view("p3").is_none());
+
+        let features = view.features();
+        assert_eq!(features.hand_total, 18);
+        assert_eq!(features.hand_size, 2);
+        assert_eq!(features.points_to_target, 200);
+        assert_eq!(features.opponents_drawing, 1);
+        assert_eq!(features.values()[4], 0.5);
+        assert_eq!(features.values().len(), Features::COLUMNS.len());
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_builtin_strategies() {
+        let game = game_with_hand(&[10, 3], &[12, 1, 4, 2]);
+
+        assert_eq!(game.bot_move(&mut AlwaysStayAt(15)), Some(draw("p1")));
+        assert_eq!(game.bot_move(&mut AlwaysStayAt(13)), Some(stay_move("p1")));
+
+        // 13 in hand: only the 12 busts
+        assert_eq!(
+            game.bot_move(&mut ThresholdProbability(0.3)),
+            Some(draw("p1"))
+        );
+        assert_eq!(
+            game.bot_move(&mut ThresholdProbability(0.2)),
+            Some(stay_move("p1"))
+        );
+
+        let mut random = Random::new(1);
+        let legal = game.legal_moves("p1");
+        for _ in 0..20 {
+            assert!(legal.contains(&game.bot_move(&mut random).unwrap()));
+        }
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_monte_carlo() {
+        let mut bot = MonteCarlo::new(Budget::Rollouts(500), 1);
+
+        // Nothing left can bust 10, and everything left busts 20
+        let game = game_with_hand(&[4, 6], &[1, 2, 3]);
+        assert_eq!(game.bot_move(&mut bot), Some(draw("p1")));
+        let game = game_with_hand(&[12, 8], &[2, 3, 12]);
+        assert_eq!(game.bot_move(&mut bot), Some(stay_move("p1")));
+
+        // Drawing risks 19 points for at most 2 more
+        let game = game_with_hand(&[10, 9], &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(game.bot_move(&mut bot), Some(stay_move("p1")));
+        // Only the 12 in seven cards busts 12
+        let game = game_with_hand(&[12], &[1, 2, 3, 4, 5, 6, 12]);
+        assert_eq!(game.bot_move(&mut bot), Some(draw("p1")));
+
+        // Staying wins the game
+        let mut game = game_with_hand(&[4, 6], &[1, 2, 3]);
+        game.players[0].score = 190;
+        assert_eq!(game.bot_move(&mut bot), Some(stay_move("p1")));
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_monte_carlo_time_budget() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        // A clock that moves one millisecond per look
+        static NOW: AtomicU64 = AtomicU64::new(0);
+        fn clock() -> u64 {
+            NOW.fetch_add(1, Ordering::Relaxed)
+        }
+
+        let mut bot = MonteCarlo::new(Budget::Time { ms: 10, clock }, 1);
+        let game = game_with_hand(&[10, 9], &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(game.bot_move(&mut bot), Some(stay_move("p1")));
+        assert!(NOW.load(Ordering::Relaxed) >= 11);
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_bots_play_a_round() {
+        let mut game = GameState::new_with_seed(3);
+        for id in ["p1", "p2", "p3"] {
+            game.add_player(id.to_string(), id.to_string());
+        }
+        let mut bots: Vec<Box<dyn Strategy>> = ["stay-at:15", "bust-chance:0.4", "monte-carlo:50"]
+            .iter()
+            .map(|spec| parse_strategy(spec).unwrap())
+            .collect();
+
+        game.start_round().unwrap();
+        while let Some(game_move) =
+            game.bot_move(bots[game.round_state.current_player_index].as_mut())
+        {
+            game.make_move(game_move).unwrap();
+        }
+        assert!(game.round_state.is_finished);
+        assert!(game.bot_move(bots[0].as_mut()).is_none());
+    }
+
+    #[test]
+    fn test_parse_strategy_errors() {
+        assert!(parse_strategy("stay-at:lots").is_err());
+        assert!(parse_strategy("bust-chance:1.5").is_err());
+        assert!(parse_strategy("random:x").is_err());
+        assert!(parse_strategy("psychic").is_err());
+        assert!(parse_strategy("monte-carlo:many").is_err());
+        assert!(parse_strategy("monte-carlo:4000000000").is_err());
+        assert!(parse_strategy("threshold").is_ok());
+
+        // Randomized strategies and personas come with the `rng` feature
+        #[cfg(feature = "rng")]
+        {
+            assert!(parse_strategy("monte-carlo:10000").is_ok());
+            assert!(parse_strategy("random").is_ok());
+            assert!(parse_strategy("monte-carlo").is_ok());
+            assert!(parse_strategy("reckless").is_ok());
+        }
+    }
+}
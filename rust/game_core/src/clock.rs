@@ -0,0 +1,142 @@
+//! Move timestamps and the game clock, for stats and replays.
+//!
+//! Like the turn timers this runs on the caller's clock, in milliseconds since
+//! the Unix epoch. A turn starts at the first `tick` of that turn or when the
+//! previous move was made with `make_move_at`; time spent paused never counts.
+
+use crate::{GameMove, GameState};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A move applied with `GameState::make_move_at`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct TimedMove {
+    pub round_number: u32,
+    pub player_id: String,
+    pub at_ms: u64,
+    /// Time from the start of the turn to the move, pauses excluded; 0 if
+    /// the turn's start wasn't known
+    pub thinking_ms: u64,
+}
+
+impl GameState {
+    /// Applies a move like `make_move`, recording when it was made.
+    pub fn make_move_at(&mut self, game_move: GameMove, now_ms: u64) -> Result<(), String> {
+        self.start_clock(now_ms);
+        let player_id = match &game_move {
+            GameMove::Draw { player_id } | GameMove::Stay { player_id } => player_id.clone(),
+        };
+        let round_number = self.round_state.round_number;
+        let turn_started = self.round_state.turn_started_ms.unwrap_or(now_ms);
+
+        self.make_move(game_move)?;
+
+        let elapsed = now_ms.saturating_sub(turn_started);
+        self.timed_moves.push(TimedMove {
+            round_number,
+            player_id,
+            at_ms: now_ms,
+            thinking_ms: elapsed.saturating_sub(self.paused_ms_since(turn_started, now_ms)),
+        });
+        if !self.round_state.is_finished {
+            self.round_state.turn_started_ms = Some(now_ms);
+        }
+        Ok(())
+    }
+
+    /// Game time from when the clock started to the last timestamped move,
+    /// pauses excluded.
+    pub fn duration_ms(&self) -> u64 {
+        let (Some(started), Some(last)) = (self.started_at_ms, self.timed_moves.last()) else {
+            // No move of this round is timed yet, so the last was in an earlier one
+            return self.history.last().map_or(0, |summary| summary.game_duration_ms);
+        };
+        last.at_ms
+            .saturating_sub(started)
+            .saturating_sub(self.paused_ms_since(started, last.at_ms))
+    }
+
+    /// Time a player spent on their timestamped moves in the given round, if
+    /// it is the current one; earlier rounds' are in their summaries.
+    pub fn thinking_ms(&self, player_id: &str, round_number: u32) -> u64 {
+        self.timed_moves
+            .iter()
+            .filter(|m| m.round_number == round_number && m.player_id == player_id)
+            .map(|m| m.thinking_ms)
+            .sum()
+    }
+
+    /// Starts the game clock, and the current turn's, if they aren't running yet.
+    pub(crate) fn start_clock(&mut self, now_ms: u64) {
+        self.started_at_ms.get_or_insert(now_ms);
+        if !self.round_state.is_finished && !self.is_paused() && !self.players.is_empty() {
+            self.round_state.turn_started_ms.get_or_insert(now_ms);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    fn stay(player_id: &str) -> GameMove {
+        GameMove::Stay {
+            player_id: player_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_moves_are_timed() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+
+        game.tick(1_000);
+        game.make_move_at(stay("p1"), 4_000).unwrap();
+        game.pause(5_000).unwrap();
+        game.resume(15_000).unwrap();
+        game.make_move_at(stay("p2"), 16_000).unwrap();
+        assert!(game.make_move_at(stay("p2"), 17_000).is_err());
+
+        assert_eq!(game.timed_moves.len(), 2);
+        assert_eq!(game.timed_moves[1].at_ms, 16_000);
+        assert_eq!(game.thinking_ms("p1", 1), 3_000);
+        assert_eq!(game.thinking_ms("p2", 1), 2_000);
+        assert_eq!(game.duration_ms(), 5_000);
+
+        let summary = game.finish_round().unwrap();
+        assert_eq!(summary.game_duration_ms, 5_000);
+        assert_eq!(summary.players[1].thinking_ms, 2_000);
+
+        // The next round times its own moves; the game clock runs on
+        game.start_round().unwrap();
+        assert!(game.timed_moves.is_empty());
+        assert_eq!(game.duration_ms(), 5_000);
+        game.make_move_at(stay("p1"), 19_000).unwrap();
+        assert_eq!(game.timed_moves.len(), 1);
+        assert_eq!(game.duration_ms(), 8_000);
+    }
+
+    #[test]
+    fn test_timing_left_out_of_hash() {
+        let mut timed = GameState::new_with_seed(3);
+        timed.add_player("p1".to_string(), "Alice".to_string());
+        timed.add_player("p2".to_string(), "Bob".to_string());
+        timed.start_round().unwrap();
+        let mut untimed = timed.clone();
+
+        timed.tick(1_000);
+        timed.make_move_at(stay("p1"), 4_000).unwrap();
+        timed.make_move_at(stay("p2"), 9_000).unwrap();
+        untimed.make_move(stay("p1")).unwrap();
+        untimed.make_move(stay("p2")).unwrap();
+        timed.finish_round().unwrap();
+        untimed.finish_round().unwrap();
+
+        assert_ne!(timed, untimed);
+        assert_eq!(timed.state_hash(), untimed.state_hash());
+    }
+}
@@ -0,0 +1,168 @@
+//! Commit-reveal scheme that lets clients check the host didn't stack the deck.
+//!
+//! When a round starts the host publishes `Deck::commitment()`, a SHA-256 hash
+//! of a secret salt and the shuffled card order. The salt never leaves the
+//! host with the game: once the round is over it sends a `DeckReveal` of the
+//! salt and the opening deck order (`GameState::deck_reveal`), and every
+//! client checks it against the commitment it received.
+//!
+//! Salts come from the OS, so without the `rng` feature there are none and no
+//! commitments either; neither are there for a round whose deck was loaded
+//! from a save, which leaves the salt out.
+
+use crate::events::{self, GameEvent};
+use crate::{Card, Deck, GameState};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "rng")]
+pub(crate) fn random_salt() -> Option<[u8; 32]> {
+    use rand_core::{OsRng, RngCore};
+    let mut salt = [0u8; 32];
+    OsRng.fill_bytes(&mut salt);
+    Some(salt)
+}
+
+// No entropy source, so no salt a client couldn't guess
+#[cfg(not(feature = "rng"))]
+pub(crate) fn random_salt() -> Option<[u8; 32]> {
+    None
+}
+
+fn commit(cards: &[Card], salt: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(cards.iter().map(|card| card.value).collect::<Vec<u8>>());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// What a finished round's deck commitment opens to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeckReveal {
+    pub round_number: u32,
+    pub commitment: String,
+    pub salt: [u8; 32],
+    /// The deck in the order it had when the round started, drawn from the back
+    pub cards: Vec<Card>,
+}
+
+impl DeckReveal {
+    /// Checks the revealed deck against the commitment published when the
+    /// round started, which clients should have kept from then rather than
+    /// take from the reveal.
+    pub fn verify(&self, commitment: &str) -> bool {
+        self.commitment == commitment && commit(&self.cards, &self.salt) == commitment
+    }
+}
+
+impl Deck {
+    /// Hex SHA-256 commitment to the current card order, or none without a
+    /// salt to hide it with.
+    pub fn commitment(&self) -> Option<String> {
+        Some(commit(&self.cards, self.salt.as_ref()?))
+    }
+
+    /// Checks that this deck, in its revealed opening order, matches a
+    /// commitment published earlier.
+    pub fn verify(&self, commitment: &str, salt: &[u8; 32]) -> bool {
+        commit(&self.cards, salt) == commitment
+    }
+}
+
+impl GameState {
+    /// Rebuilds the current round's deck in the order it had when the round
+    /// started, by putting every card dealt or drawn this round back on top.
+    pub fn revealed_deck(&self) -> Deck {
+        let mut deck = self.deck.clone();
+        for event in events::current_round(&self.events).iter().rev() {
+            match event {
+                GameEvent::CardDealt { card, .. } | GameEvent::CardDrawn { card, .. } => {
+                    deck.cards.push(*card)
+                }
+                _ => {}
+            }
+        }
+        deck
+    }
+
+    /// The game as players and spectators are sent it. The deck's remaining
+    /// cards are sorted and the seed and salt left out, so the order they
+    /// come in stays secret until `deck_reveal` opens it.
+    pub fn public_view(&self) -> GameState {
+        let mut view = self.clone();
+        view.deck.cards.sort();
+        view.deck.salt = None;
+        view.seed = 0;
+        view
+    }
+
+    /// Opens the deck commitment of the round just played, once it is over.
+    /// None while it is being played, or if it had no commitment.
+    pub fn deck_reveal(&self) -> Option<DeckReveal> {
+        if !self.round_state.is_finished {
+            return None;
+        }
+        let commitment = self.round_state.deck_commitment.clone()?;
+        let salt = self.deck.salt?;
+        Some(DeckReveal {
+            round_number: self.round_state.round_number,
+            commitment,
+            salt,
+            cards: self.revealed_deck().cards,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_reveal() {
+        let mut game = GameState::new_with_seed(5);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        let commitment = game.round_state.deck_commitment.clone().unwrap();
+
+        game.player_draw("p1").unwrap();
+        assert_eq!(game.deck_reveal(), None);
+        game.player_stay("p2").unwrap();
+
+        let reveal = game.deck_reveal().unwrap();
+        assert_eq!(reveal.cards.len(), 79);
+        assert!(reveal.verify(&commitment));
+        assert!(game.revealed_deck().verify(&commitment, &reveal.salt));
+
+        // A stacked deck or a wrong salt is caught
+        let mut stacked = reveal.clone();
+        stacked.cards.swap(0, 78);
+        assert!(!stacked.verify(&commitment));
+        let mut salted = reveal.clone();
+        salted.salt = [0u8; 32];
+        assert!(!salted.verify(&commitment));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_salt_stays_secret() {
+        let mut game = GameState::new_with_seed(5);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+        assert!(game.deck_reveal().is_some());
+
+        // Saves leave the salt out, so a loaded round can't be opened
+        let json = game.to_json().unwrap();
+        assert!(!json.contains("salt"));
+        let loaded = GameState::from_json(&json).unwrap();
+        assert_eq!(loaded.deck_reveal(), None);
+        assert_eq!(loaded.state_hash(), game.state_hash());
+    }
+}
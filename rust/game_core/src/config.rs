@@ -0,0 +1,82 @@
+use crate::deck::DeckSpec;
+use crate::elimination::EliminationRule;
+use crate::handicap::Handicap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Score that ends the game in the standard rules.
+pub const DEFAULT_TARGET_SCORE: u32 = 200;
+
+/// Match settings chosen when the game is created.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct GameConfig {
+    /// The game ends after the round in which someone reaches this total
+    pub target_score: u32,
+    /// How a tie for the lead at the end of the game is broken: each rule
+    /// in turn narrows the tied leaders, and any tie left after the last one
+    /// goes to a sudden-death round
+    pub tiebreakers: Vec<Tiebreaker>,
+    /// Score-attack games (see `solo`) end after this many rounds instead,
+    /// and the target score is only something to beat
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub round_limit: Option<u32>,
+    /// Date of the daily challenge this game is, if any; see `daily`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub challenge_date: Option<String>,
+    /// Players who take longer than this over a turn are auto-stayed; see
+    /// `timer`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub turn_time_limit_ms: Option<u64>,
+    /// Cards every round is dealt from
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deck: DeckSpec,
+    /// Elimination variant, off by default
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub elimination: Option<EliminationRule>,
+    /// Handicaps by player id; see `handicap`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub handicaps: BTreeMap<String, Handicap>,
+    /// Name of the variant the game was created from; see `variant`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub variant: Option<String>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            target_score: DEFAULT_TARGET_SCORE,
+            tiebreakers: default_tiebreakers(),
+            round_limit: None,
+            challenge_date: None,
+            turn_time_limit_ms: None,
+            deck: DeckSpec::standard(),
+            elimination: None,
+            handicaps: BTreeMap::new(),
+            variant: None,
+        }
+    }
+}
+
+/// Standard tie-break: whoever got there in fewer rounds, then sudden death.
+pub fn default_tiebreakers() -> Vec<Tiebreaker> {
+    vec![Tiebreaker::FewestRounds, Tiebreaker::SuddenDeath]
+}
+
+/// Ways to pick a winner among players tied for the highest total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum Tiebreaker {
+    /// Play extra rounds until one of the tied players scores the most in a
+    /// round; ends the chain, as it always settles the game
+    SuddenDeath,
+    /// Fewest rounds played, e.g. over someone who joined later
+    FewestRounds,
+    /// Best score in any single round
+    HighestSingleRound,
+    /// Fewest busted rounds
+    FewestBusts,
+}
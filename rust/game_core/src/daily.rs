@@ -0,0 +1,140 @@
+//! Daily challenge: a solo game whose shuffles are derived from the date, so
+//! everyone playing on the same day gets the same cards and can compare scores.
+
+use crate::solo::SoloResult;
+use crate::GameState;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const DAILY_ROUNDS: u32 = 5;
+pub const DAILY_TARGET_SCORE: u32 = 75;
+
+/// A finished daily challenge, ready to submit to a leaderboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChallengeResult {
+    /// Challenge date, `YYYY-MM-DD`
+    pub date: String,
+    pub result: SoloResult,
+    /// `state_hash` of the finished game, so a server replaying the day's
+    /// seed can check the submission
+    pub state_hash: u64,
+}
+
+/// Seed of the challenge for `date`, the same on every platform.
+pub fn daily_seed(date: &str) -> Result<u64, String> {
+    validate_date(date)?;
+
+    let digest = Sha256::digest(format!("flip7-daily:{}", date).as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn validate_date(date: &str) -> Result<(), String> {
+    let invalid = || format!("Invalid challenge date '{}', expected YYYY-MM-DD", date);
+
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return Err(invalid());
+    }
+
+    let year: u32 = year.parse().map_err(|_| invalid())?;
+    let month: u32 = month.parse().map_err(|_| invalid())?;
+    let day: u32 = day.parse().map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl GameState {
+    /// Creates the solo challenge for `date` (`YYYY-MM-DD`). The first round
+    /// still has to be started with `start_round`.
+    pub fn daily_challenge(date: &str) -> Result<Self, String> {
+        let mut game = Self::new_solo(daily_seed(date)?, DAILY_ROUNDS, DAILY_TARGET_SCORE);
+        game.config.challenge_date = Some(date.to_string());
+        Ok(game)
+    }
+
+    /// Leaderboard entry, once every round of a daily challenge has been played.
+    pub fn challenge_result(&self) -> Option<ChallengeResult> {
+        let date = self.config.challenge_date.clone()?;
+        Some(ChallengeResult {
+            date,
+            result: self.solo_result()?,
+            state_hash: self.state_hash(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "rng")]
+    use crate::solo::SOLO_PLAYER_ID;
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_same_date_same_cards() {
+        let mut a = GameState::daily_challenge("2024-03-01").unwrap();
+        let mut b = GameState::daily_challenge("2024-03-01").unwrap();
+        let mut c = GameState::daily_challenge("2024-03-02").unwrap();
+        for game in [&mut a, &mut b, &mut c] {
+            game.start_round().unwrap();
+        }
+
+        assert_eq!(a.deck.cards, b.deck.cards);
+        assert_ne!(a.deck.cards, c.deck.cards);
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_challenge_result() {
+        let mut game = GameState::daily_challenge("2024-03-01").unwrap();
+        for _ in 0..DAILY_ROUNDS {
+            assert!(game.challenge_result().is_none());
+            game.start_round().unwrap();
+            game.player_stay(SOLO_PLAYER_ID).unwrap();
+            game.finish_round().unwrap();
+        }
+
+        let entry = game.challenge_result().unwrap();
+        assert_eq!(entry.date, "2024-03-01");
+        assert_eq!(entry.result.rounds_played, DAILY_ROUNDS);
+        assert_eq!(entry.state_hash, game.state_hash());
+    }
+
+    #[test]
+    fn test_invalid_dates_rejected() {
+        for date in [
+            "",
+            "2024-3-01",
+            "2024-13-01",
+            "2024-01-32",
+            "2024-02-31",
+            "2023-04-31",
+            "2023-02-29",
+            "1900-02-29",
+            "march 1st",
+        ] {
+            assert!(daily_seed(date).is_err(), "{}", date);
+        }
+        for date in ["2024-02-29", "2000-02-29", "2023-12-31"] {
+            assert!(daily_seed(date).is_ok(), "{}", date);
+        }
+    }
+}
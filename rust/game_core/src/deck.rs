@@ -0,0 +1,307 @@
+//! Deck compositions. Every round is dealt from a fresh deck built from the
+//! game's `DeckSpec`, so tests and variants can play with any mix of cards.
+
+use crate::{Card, Deck};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How many copies of each card value a deck holds.
+///
+/// Large tables can shuffle several copies of the composition together
+/// (`with_decks`) so the deck doesn't run dry mid-round. Nothing in the rules
+/// assumes cards are unique: a Flip 7 is any subset of the hand summing to 7,
+/// duplicates included, and bust only looks at the total.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawDeckSpec"))]
+pub struct DeckSpec {
+    /// `(value, copies)` in the order the cards are laid out before shuffling
+    counts: Vec<(u8, u32)>,
+    /// Copies of the whole composition shuffled together
+    #[cfg_attr(feature = "serde", serde(default = "one_deck"))]
+    num_decks: u32,
+}
+
+// Same layout as `DeckSpec`, for validating deserialized card values.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawDeckSpec {
+    counts: Vec<(u8, u32)>,
+    #[cfg_attr(feature = "serde", serde(default = "one_deck"))]
+    num_decks: u32,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawDeckSpec> for DeckSpec {
+    type Error = String;
+
+    fn try_from(raw: RawDeckSpec) -> Result<Self, Self::Error> {
+        for &(value, _) in &raw.counts {
+            Card::try_new(value)?;
+        }
+        Ok(Self {
+            counts: raw.counts,
+            num_decks: raw.num_decks,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+fn one_deck() -> u32 {
+    1
+}
+
+impl Default for DeckSpec {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl DeckSpec {
+    /// The 79-card deck: n copies of each value n from 1 to 12, and a single 0.
+    pub fn standard() -> Self {
+        // The 0 goes last: that is the order seeded games have always been
+        // shuffled from, so existing seeds keep dealing the same cards
+        let mut counts: Vec<(u8, u32)> = (1..=12).map(|value| (value, value as u32)).collect();
+        counts.push((0, 1));
+        Self {
+            counts,
+            num_decks: 1,
+        }
+    }
+
+    /// A deck with the given number of copies of each value. Values may be
+    /// listed more than once; their copies add up. Fails on values no card
+    /// can have.
+    pub fn with_counts(counts: impl IntoIterator<Item = (u8, u32)>) -> Result<Self, String> {
+        let mut merged: Vec<(u8, u32)> = Vec::new();
+        for (value, copies) in counts {
+            Card::try_new(value)?;
+            match merged.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, total)) => *total += copies,
+                None => merged.push((value, copies)),
+            }
+        }
+        merged.retain(|&(_, copies)| copies > 0);
+        merged.sort();
+        Ok(Self {
+            counts: merged,
+            num_decks: 1,
+        })
+    }
+
+    /// The same composition, `num_decks` times over (at least once).
+    pub fn with_decks(self, num_decks: u32) -> Self {
+        Self {
+            num_decks: num_decks.max(1),
+            ..self
+        }
+    }
+
+    /// Composition of a single deck.
+    pub fn counts(&self) -> &[(u8, u32)] {
+        &self.counts
+    }
+
+    pub fn num_decks(&self) -> u32 {
+        self.num_decks
+    }
+
+    /// Number of copies of `value` across all decks.
+    pub fn copies_of(&self, value: u8) -> u32 {
+        self.counts
+            .iter()
+            .find(|(v, _)| *v == value)
+            .map_or(0, |&(_, copies)| copies * self.num_decks)
+    }
+
+    /// Total number of cards across all decks.
+    pub fn len(&self) -> usize {
+        let per_deck: usize = self.counts.iter().map(|&(_, copies)| copies as usize).sum();
+        per_deck * self.num_decks as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every card, unshuffled, one deck after the other.
+    pub fn cards(&self) -> Vec<Card> {
+        let mut cards = Vec::with_capacity(self.len());
+        self.fill(&mut cards);
+        cards
+    }
+
+    // Replaces `cards` with `cards()`, reusing its allocation.
+    fn fill(&self, cards: &mut Vec<Card>) {
+        cards.clear();
+        for _ in 0..self.num_decks {
+            for &(value, copies) in &self.counts {
+                cards.extend((0..copies).map(|_| Card::new(value)));
+            }
+        }
+    }
+}
+
+impl Deck {
+    /// An unshuffled deck of the cards in `spec`, shuffled later from `seed`.
+    pub fn from_spec(spec: &DeckSpec, seed: u64) -> Self {
+        Self::with_cards(spec.cards(), seed)
+    }
+
+    /// Turns this deck into `Deck::from_spec(spec, seed)` in place, with a
+    /// fresh commitment salt. The card buffer is kept, so dealing round after
+    /// round doesn't reallocate it.
+    #[cfg_attr(not(feature = "rng"), allow(unused_variables))]
+    pub fn refill(&mut self, spec: &DeckSpec, seed: u64) {
+        spec.fill(&mut self.cards);
+        #[cfg(feature = "rng")]
+        {
+            use rand_chacha::rand_core::SeedableRng;
+            self.rng = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
+        }
+        self.salt = crate::commitment::random_salt();
+        self.stacked = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_spec_matches_deck() {
+        let spec = DeckSpec::standard();
+        assert_eq!(spec.len(), 79);
+        assert_eq!(spec.copies_of(12), 12);
+        assert_eq!(spec.copies_of(0), 1);
+        assert_eq!(Deck::from_spec(&spec, 7), Deck::new(7));
+    }
+
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    #[test]
+    fn test_custom_counts() {
+        use std::collections::HashMap;
+
+        let counts: HashMap<u8, u32> = [(7, 3), (2, 0), (11, 2)].into_iter().collect();
+        let spec = DeckSpec::with_counts(counts).unwrap();
+
+        assert_eq!(spec.counts(), &[(7, 3), (11, 2)]);
+        assert_eq!(DeckSpec::with_counts([(5, 1), (5, 2)]).unwrap().counts(), &[(5, 3)]);
+        assert!(DeckSpec::with_counts([(13, 1)]).is_err());
+        assert!(serde_json::from_str::<DeckSpec>(r#"{"counts":[[40,2]]}"#).is_err());
+
+        let mut deck = Deck::from_spec(&spec, 1);
+        deck.shuffle();
+        let mut values: Vec<u8> = deck.cards.iter().map(|card| card.value()).collect();
+        values.sort();
+        assert_eq!(values, vec![7, 7, 7, 11, 11]);
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_rounds_reuse_the_deck_buffer() {
+        let mut game = crate::GameState::new_with_seed(5);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+        let buffer = game.deck.cards.as_ptr();
+        let commitment = game.deck.commitment();
+
+        game.player_stay("p1").unwrap();
+        game.finish_round().unwrap();
+        game.start_round().unwrap();
+        assert_eq!(game.deck.cards.as_ptr(), buffer);
+        assert_ne!(game.deck.commitment(), commitment);
+        assert_eq!(game.check_invariants(), Ok(()));
+
+        let mut refilled = Deck::new(1);
+        refilled.draw();
+        refilled.refill(&DeckSpec::standard(), 8);
+        refilled.shuffle();
+        let mut fresh = Deck::new(8);
+        fresh.shuffle();
+        assert_eq!(refilled, fresh);
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_game_deals_from_configured_deck() {
+        let config = crate::config::GameConfig {
+            deck: DeckSpec::with_counts([(3, 40)]).unwrap(),
+            ..Default::default()
+        };
+        let mut game = crate::GameState::new_with_config(1, config);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+
+        assert_eq!(game.players[0].hand.to_string(), "[3 3] = 6");
+        assert_eq!(game.deck.len(), 38);
+        assert_eq!(game.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_multiple_decks() {
+        for num_decks in [2, 3] {
+            let spec = DeckSpec::standard().with_decks(num_decks);
+            assert_eq!(spec.len(), 79 * num_decks as usize);
+            let cards = spec.cards();
+            assert_eq!(cards.len(), spec.len());
+            for value in 0..=12 {
+                let copies = value.max(1) as u32 * num_decks;
+                assert_eq!(spec.copies_of(value), copies);
+                assert_eq!(cards.iter().filter(|card| card.value() == value).count(), copies as usize);
+            }
+        }
+        assert_eq!(DeckSpec::standard().with_decks(0).num_decks(), 1);
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_multiple_decks_deal() {
+        // Fourteen players draw until they bust or hold six cards; every card
+        // of every deck is either in a hand or still in the deck
+        for (num_decks, dealt) in [(2, 44), (3, 45)] {
+            let spec = DeckSpec::standard().with_decks(num_decks);
+            let config = crate::config::GameConfig {
+                deck: spec.clone(),
+                ..Default::default()
+            };
+            let mut game = crate::GameState::new_with_config(3, config);
+            for i in 0..14 {
+                game.add_player(i.to_string(), format!("Player {}", i));
+            }
+            game.start_round().unwrap();
+            while !game.round_state.is_finished {
+                let player = game.current_player().unwrap();
+                let id = player.id.clone();
+                if player.has_stayed || player.hand.cards.len() >= 6 {
+                    game.player_stay(&id).unwrap();
+                } else {
+                    game.player_draw(&id).unwrap();
+                }
+            }
+
+            let mut cards: Vec<u8> = game.players.iter().flat_map(|p| &p.hand.cards).map(|card| card.value()).collect();
+            assert_eq!(cards.len(), dealt);
+            assert_eq!(game.deck.len(), spec.len() - dealt);
+            cards.extend(game.deck.cards.iter().map(|card| card.value()));
+            cards.sort();
+            let mut expected: Vec<u8> = spec.cards().iter().map(|card| card.value()).collect();
+            expected.sort();
+            assert_eq!(cards, expected);
+            assert_eq!(game.check_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_duplicate_cards_score_correctly() {
+        let hand = |values: &[u8]| crate::Hand {
+            cards: values.iter().map(|&v| Card::new(v)).collect(),
+        };
+        assert!(hand(&[7, 7]).has_flip7());
+        assert!(hand(&[3, 3, 1]).has_flip7());
+        assert!(!hand(&[3, 3, 3]).has_flip7());
+        assert!(hand(&[12, 12]).is_bust());
+    }
+}
@@ -0,0 +1,105 @@
+//! Human-readable summaries for the CLI, the demo and logs, e.g. a hand
+//! prints as `[3 7 11] = 21`.
+
+use crate::pause::GamePhase;
+use crate::{Card, GameState, Hand, Player};
+use std::fmt;
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+/// Cards in canonical order, then the total, flagged when the hand is a
+/// Flip 7 or bust.
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cards: Vec<String> = self.canonical().iter().map(Card::to_string).collect();
+        write!(f, "[{}] = {}", cards.join(" "), self.total_value())?;
+        // Flip 7 is checked first when scoring, so it wins over bust here too
+        if self.has_flip7() {
+            write!(f, " FLIP7")
+        } else if self.is_bust() {
+            write!(f, " BUST")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}, score {}", self.name, self.hand, self.score)?;
+        if self.has_stayed {
+            write!(f, ", stayed")?;
+        }
+        Ok(())
+    }
+}
+
+/// One line for the state of the game, then one per player, with the
+/// player whose turn it is marked `>`.
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let round = self.round_state.round_number;
+        match self.phase() {
+            GamePhase::InRound => write!(f, "Round {}", round)?,
+            GamePhase::BetweenRounds => write!(f, "Round {} finished", round)?,
+            GamePhase::Paused => write!(f, "Round {} (paused)", round)?,
+            GamePhase::GameOver => write!(f, "Game over after round {}", round)?,
+        }
+        write!(f, ", {} cards left", self.deck.len())?;
+
+        let in_round = matches!(self.phase(), GamePhase::InRound | GamePhase::Paused);
+        for (seat, player) in self.players.iter().enumerate() {
+            let marker = if in_round && seat == self.round_state.current_player_index {
+                '>'
+            } else {
+                ' '
+            };
+            write!(f, "\n{} {}", marker, player)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand(values: &[u8]) -> Hand {
+        Hand {
+            cards: values.iter().map(|&v| Card::new(v)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_hand_display() {
+        assert_eq!(hand(&[]).to_string(), "[] = 0");
+        assert_eq!(hand(&[2, 8, 11]).to_string(), "[2 8 11] = 21");
+        assert_eq!(hand(&[3, 4]).to_string(), "[3 4] = 7 FLIP7");
+        assert_eq!(hand(&[10, 12, 9]).to_string(), "[9 10 12] = 31 BUST");
+        assert_eq!(hand(&[8, 2, 11]).to_string(), hand(&[2, 11, 8]).to_string());
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_game_display() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+
+        let alice = &game.players[0];
+        let bob = &game.players[1];
+        assert_eq!(
+            game.to_string(),
+            format!(
+                "Round 1, 75 cards left\n  Alice {}, score 0, stayed\n> Bob {}, score 0",
+                alice.hand, bob.hand
+            )
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Elimination variant: players who keep busting, or fall too far behind,
+//! are knocked out. Eliminated players are dealt no cards and skipped in turn
+//! order, and the last side standing wins.
+
+use crate::events::GameEvent;
+use crate::GameState;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// When players are eliminated; checked after every scored round.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct EliminationRule {
+    /// Busting this many rounds in a row
+    pub consecutive_busts: Option<u32>,
+    /// Being this many points or more behind the leader
+    pub points_behind: Option<u32>,
+}
+
+impl GameState {
+    /// Eliminates the players the configured rule knocks out after the round
+    /// just scored. Nobody is eliminated if that would leave no one at all.
+    pub(crate) fn apply_elimination(&mut self) {
+        let Some(rule) = self.config.elimination.clone() else {
+            return;
+        };
+
+        let leader_score = self.active_players().map(|p| p.score).max().unwrap_or(0);
+        let knocked_out: Vec<String> = self
+            .active_players()
+            .filter(|p| {
+                rule.consecutive_busts
+                    .is_some_and(|n| self.consecutive_busts(&p.id) >= n)
+                    || rule
+                        .points_behind
+                        .is_some_and(|x| leader_score - p.score >= x)
+            })
+            .map(|p| p.id.clone())
+            .collect();
+        if knocked_out.len() == self.active_players().count() {
+            return;
+        }
+
+        for player_id in knocked_out {
+            if let Some(player) = self.players.iter_mut().find(|p| p.id == player_id) {
+                player.eliminated = true;
+            }
+            self.emit(GameEvent::PlayerEliminated { player_id });
+        }
+    }
+
+    /// Rounds busted in a row, counting back from the latest one.
+    fn consecutive_busts(&self, player_id: &str) -> u32 {
+        self.history
+            .iter()
+            .rev()
+            .map_while(|round| {
+                round
+                    .players
+                    .iter()
+                    .find(|r| r.player_id == player_id)
+                    .filter(|r| r.busted)
+            })
+            .count() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    #[cfg(feature = "rng")]
+    use crate::outcome::WinReason;
+
+    fn elimination_game(rule: EliminationRule) -> GameState {
+        let config = GameConfig {
+            elimination: Some(rule),
+            ..GameConfig::default()
+        };
+        let mut game = GameState::new_with_config(42, config);
+        for id in ["p1", "p2", "p3"] {
+            game.add_player(id.to_string(), id.to_string());
+        }
+        game
+    }
+
+    // Plays a round in which everyone stays on their dealt cards.
+    #[cfg(feature = "rng")]
+    fn play_round(game: &mut GameState) {
+        game.start_round().unwrap();
+        while !game.round_state.is_finished {
+            let id = game.current_player().unwrap().id.clone();
+            game.player_stay(&id).unwrap();
+        }
+        game.finish_round().unwrap();
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_player_falling_behind_is_eliminated() {
+        let mut game = elimination_game(EliminationRule {
+            consecutive_busts: None,
+            points_behind: Some(8),
+        });
+        game.players[2].score = 10;
+        play_round(&mut game);
+
+        // Staying on 14, 18 and 14 puts p1 10 points behind p3 and p2 6
+        assert!(game.players[0].eliminated);
+        assert!(!game.players[1].eliminated);
+        assert!(game.events.contains(&GameEvent::PlayerEliminated {
+            player_id: "p1".to_string()
+        }));
+
+        // The eliminated player is dealt nothing and never has a turn
+        game.start_round().unwrap();
+        assert!(game.players[0].hand.cards.is_empty());
+        assert_eq!(game.round_state.current_player_index, 1);
+        game.player_stay("p2").unwrap();
+        assert_eq!(game.round_state.current_player_index, 2);
+        game.player_stay("p3").unwrap();
+        assert!(game.round_state.is_finished);
+        let summary = game.finish_round().unwrap();
+        assert!(summary.players.iter().all(|r| r.player_id != "p1"));
+    }
+
+    #[test]
+    fn test_consecutive_busts() {
+        let mut game = elimination_game(EliminationRule {
+            consecutive_busts: Some(2),
+            points_behind: None,
+        });
+        let bust = |game: &mut GameState, busted: bool| {
+            let mut round = crate::RoundSummary {
+                round_number: game.history.len() as u32 + 1,
+                players: Vec::new(),
+                highlights: Vec::new(),
+                game_duration_ms: 0,
+            };
+            round.players.push(crate::PlayerRoundResult {
+                player_id: "p3".to_string(),
+                cards: Vec::new(),
+                busted,
+                flip7_bonus: false,
+                raw_score: 0,
+                round_score: 0,
+                total_score: 0,
+                thinking_ms: 0,
+            });
+            game.history.push(round);
+            game.apply_elimination();
+        };
+
+        bust(&mut game, true);
+        bust(&mut game, false);
+        bust(&mut game, true);
+        assert!(!game.players[2].eliminated);
+        bust(&mut game, true);
+        assert!(game.players[2].eliminated);
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_last_player_standing_wins() {
+        let mut game = elimination_game(EliminationRule {
+            consecutive_busts: None,
+            points_behind: Some(1),
+        });
+        play_round(&mut game);
+
+        assert_eq!(game.active_players().count(), 1);
+        let outcome = game.outcome.clone().unwrap();
+        assert_eq!(outcome.reason, WinReason::LastPlayerStanding);
+        assert_eq!(
+            outcome.winner_ids,
+            vec![game.active_players().next().unwrap().id.clone()]
+        );
+        assert!(game.start_round().is_err());
+    }
+}
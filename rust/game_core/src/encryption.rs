@@ -0,0 +1,101 @@
+use crate::GameState;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+const NONCE_LEN: usize = 12;
+
+/// Parses a 256-bit key given as 64 hex characters, the form host apps pass it in.
+pub fn key_from_hex(hex: &str) -> Result<[u8; 32], String> {
+    let bytes = from_hex(hex.trim())?;
+    bytes
+        .try_into()
+        .map_err(|_| "Encryption key must be 32 bytes (64 hex characters)".to_string())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err("Hex string has an odd length".to_string());
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| "Invalid hex string".to_string())
+        })
+        .collect()
+}
+
+impl GameState {
+    /// Encrypts the saved game with AES-256-GCM so it can't be read or edited
+    /// without the key. The output is the random nonce followed by the ciphertext.
+    pub fn to_encrypted(&self, key: &[u8; 32]) -> Result<Vec<u8>, String> {
+        let json = self
+            .to_json()
+            .map_err(|e| format!("Failed to serialize game state: {}", e))?;
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, json.as_bytes())
+            .map_err(|_| "Failed to encrypt game state".to_string())?;
+
+        let mut bytes = nonce.to_vec();
+        bytes.extend(ciphertext);
+        Ok(bytes)
+    }
+
+    /// Decrypts a save written by `to_encrypted`. Fails if the key is wrong or
+    /// the data was tampered with.
+    pub fn from_encrypted(bytes: &[u8], key: &[u8; 32]) -> Result<Self, String> {
+        if bytes.len() < NONCE_LEN {
+            return Err("Encrypted save is too short".to_string());
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        let json = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt game state: wrong key or corrupted save".to_string())?;
+
+        let json =
+            String::from_utf8(json).map_err(|_| "Decrypted save is not UTF-8".to_string())?;
+        GameState::from_json(&json).map_err(|e| format!("Failed to parse game state: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let key = [7u8; 32];
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+
+        let bytes = game.to_encrypted(&key).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("Alice"));
+        assert_eq!(GameState::from_encrypted(&bytes, &key).unwrap(), game);
+
+        assert!(GameState::from_encrypted(&bytes, &[8u8; 32]).is_err());
+
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(GameState::from_encrypted(&tampered, &key).is_err());
+    }
+
+    #[test]
+    fn test_key_from_hex() {
+        let hex = "00".repeat(31) + "ff";
+        assert_eq!(key_from_hex(&hex).unwrap()[31], 0xff);
+        assert!(key_from_hex("abcd").is_err());
+        assert!(key_from_hex(&"zz".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! The errors the game refuses moves with. Each is made from its text here,
+//! so frontends can tell them apart and translate them by comparing with
+//! these rather than with copies of the wording.
+
+pub const PLAYER_NOT_FOUND: &str = "Player not found";
+pub const NO_PLAYERS_ADDED: &str = "No players added";
+pub const NOT_YOUR_TURN: &str = "Not your turn";
+pub const ALREADY_STAYED: &str = "Player has already stayed";
+pub const DECK_EMPTY: &str = "Deck is empty";
+pub const PLAYER_ELIMINATED: &str = "Current player has been eliminated";
+pub const ROUND_FINISHED: &str = "Round is finished";
+pub const ROUND_NOT_FINISHED: &str = "Round is not finished";
+pub const ROUND_SCORED: &str = "Round has already been scored";
+pub const GAME_OVER: &str = "Game is over";
+pub const GAME_PAUSED: &str = "Game is paused";
+pub const GAME_NOT_PAUSED: &str = "Game is not paused";
+pub const GAME_ALREADY_PAUSED: &str = "Game is already paused";
+pub const RESUME_UNPAUSED: &str = "Cannot resume before the game was paused";
+pub const HANDICAPS_LOCKED: &str = "Handicaps can only be set before the first round is scored";
+pub const SKIP_VOTED: &str = "Already voted to skip this turn";
+pub const SKIP_OWN_TURN: &str = "Cannot vote to skip your own turn";
+pub const SKIP_TOO_SOON: &str = "Turn has not been running long enough to skip";
+
+/// Every error above.
+pub const ALL: &[&str] = &[
+    PLAYER_NOT_FOUND,
+    NO_PLAYERS_ADDED,
+    NOT_YOUR_TURN,
+    ALREADY_STAYED,
+    DECK_EMPTY,
+    PLAYER_ELIMINATED,
+    ROUND_FINISHED,
+    ROUND_NOT_FINISHED,
+    ROUND_SCORED,
+    GAME_OVER,
+    GAME_PAUSED,
+    GAME_NOT_PAUSED,
+    GAME_ALREADY_PAUSED,
+    RESUME_UNPAUSED,
+    HANDICAPS_LOCKED,
+    SKIP_VOTED,
+    SKIP_OWN_TURN,
+    SKIP_TOO_SOON,
+];
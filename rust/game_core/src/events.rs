@@ -0,0 +1,72 @@
+use crate::outcome::WinReason;
+use crate::Card;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Something that happened during the game, appended to `GameState::events`
+/// in the order it happened.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum GameEvent {
+    RoundStarted {
+        round_number: u32,
+    },
+    /// Card dealt face up at the start of a round
+    CardDealt {
+        player_id: String,
+        card: Card,
+    },
+    CardDrawn {
+        player_id: String,
+        card: Card,
+    },
+    PlayerBusted {
+        player_id: String,
+    },
+    PlayerStayed {
+        player_id: String,
+    },
+    /// The other players voted to auto-stay an unresponsive player
+    TurnSkipped {
+        player_id: String,
+    },
+    /// The player ran out of time and was auto-stayed
+    TurnTimedOut {
+        player_id: String,
+    },
+    /// Knocked out under the elimination variant
+    PlayerEliminated {
+        player_id: String,
+    },
+    /// Players tied for the lead who play on until one of them wins a round
+    SuddenDeath {
+        player_ids: Vec<String>,
+    },
+    /// The winning player, or every member of the winning team
+    GameWon {
+        #[cfg_attr(
+            feature = "serde",
+            serde(alias = "player_id", deserialize_with = "crate::outcome::one_or_many")
+        )]
+        player_ids: Vec<String>,
+        team: Option<u8>,
+        reason: WinReason,
+    },
+    /// Timestamps are milliseconds since the Unix epoch, as passed to `pause`
+    GamePaused {
+        at_ms: u64,
+    },
+    GameResumed {
+        at_ms: u64,
+    },
+}
+
+/// Events of the most recent round, starting at its `RoundStarted` event.
+pub fn current_round(events: &[GameEvent]) -> &[GameEvent] {
+    let start = events
+        .iter()
+        .rposition(|event| matches!(event, GameEvent::RoundStarted { .. }))
+        .unwrap_or(0);
+    &events[start..]
+}
@@ -29,8 +29,8 @@ mod ffi_tests {
 
     #[test]
     fn test_ffi_full_game_flow() {
-        // Create a new game
-        let new_game_result = flip7_new_game(2, 123);
+        // Create a new game; with seed 42 player 0's draw doesn't bust
+        let new_game_result = flip7_new_game(2, 42);
         let result_str = unsafe {
             std::ffi::CStr::from_ptr(new_game_result).to_string_lossy().into_owned()
         };
@@ -47,6 +47,16 @@ mod ffi_tests {
         println!("Initial state: {}", state_str);
         flip7_free_string(state_result);
 
+        // Ask for advice before moving
+        let recommend_result = flip7_recommend(game_id_cstr.as_ptr(), 0);
+        let recommend_str = unsafe {
+            std::ffi::CStr::from_ptr(recommend_result).to_string_lossy().into_owned()
+        };
+        let recommend: serde_json::Value = serde_json::from_str(&recommend_str).unwrap();
+        assert_eq!(recommend["success"], true);
+        assert!(recommend["recommendation"]["explanation"].is_string());
+        flip7_free_string(recommend_result);
+
         // Player 0 draws
         let draw_result = flip7_draw(game_id_cstr.as_ptr(), 0);
         let draw_str = unsafe {
@@ -76,4 +86,44 @@ mod ffi_tests {
 
         flip7_free_string(stay_result);
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_ffi_encrypted_export_import() {
+        let new_game_result = flip7_new_game(2, 99);
+        let result_str = unsafe {
+            std::ffi::CStr::from_ptr(new_game_result).to_string_lossy().into_owned()
+        };
+        let result: serde_json::Value = serde_json::from_str(&result_str).unwrap();
+        let game_id = CString::new(result["game_id"].as_str().unwrap()).unwrap();
+        flip7_free_string(new_game_result);
+
+        let key = CString::new("11".repeat(32)).unwrap();
+        let export_result = flip7_export_encrypted(game_id.as_ptr(), key.as_ptr());
+        let export_str = unsafe {
+            std::ffi::CStr::from_ptr(export_result).to_string_lossy().into_owned()
+        };
+        let export: serde_json::Value = serde_json::from_str(&export_str).unwrap();
+        assert_eq!(export["success"], true);
+        flip7_free_string(export_result);
+
+        let data = CString::new(export["data"].as_str().unwrap()).unwrap();
+        let import_result = flip7_import_encrypted(data.as_ptr(), key.as_ptr());
+        let import_str = unsafe {
+            std::ffi::CStr::from_ptr(import_result).to_string_lossy().into_owned()
+        };
+        let import: serde_json::Value = serde_json::from_str(&import_str).unwrap();
+        assert_eq!(import["success"], true);
+        assert_ne!(import["game_id"], result["game_id"]);
+        flip7_free_string(import_result);
+
+        let wrong_key = CString::new("22".repeat(32)).unwrap();
+        let failed_result = flip7_import_encrypted(data.as_ptr(), wrong_key.as_ptr());
+        let failed_str = unsafe {
+            std::ffi::CStr::from_ptr(failed_result).to_string_lossy().into_owned()
+        };
+        let failed: serde_json::Value = serde_json::from_str(&failed_str).unwrap();
+        assert_eq!(failed["success"], false);
+        flip7_free_string(failed_result);
+    }
+}
@@ -0,0 +1,120 @@
+//! Handicaps, so players of different skill can have a close game: a head
+//! start on the score sheet, and/or a percentage applied to every round score.
+
+use crate::errors;
+use crate::GameState;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A player's handicap. The default is no handicap.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct Handicap {
+    /// Points the player starts the game with
+    pub starting_score: u32,
+    /// Percentage of each round score the player is credited, e.g. 150 for
+    /// a beginner or 80 for an expert
+    pub score_percent: u32,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            starting_score: 0,
+            score_percent: 100,
+        }
+    }
+}
+
+impl Handicap {
+    /// Round score after applying the percentage, rounded down.
+    pub fn adjust(&self, raw_score: u32) -> u32 {
+        (raw_score as u64 * self.score_percent as u64 / 100) as u32
+    }
+}
+
+impl GameState {
+    /// Gives a seated player a handicap. Only allowed before the first round
+    /// is scored, since it sets their starting score.
+    pub fn set_handicap(&mut self, player_id: &str, handicap: Handicap) -> Result<(), String> {
+        if !self.history.is_empty() {
+            return Err(errors::HANDICAPS_LOCKED.to_string());
+        }
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.id == player_id)
+            .ok_or(errors::PLAYER_NOT_FOUND)?;
+
+        player.score = handicap.starting_score;
+        self.config
+            .handicaps
+            .insert(player_id.to_string(), handicap);
+        Ok(())
+    }
+
+    pub fn handicap_of(&self, player_id: &str) -> Handicap {
+        self.config
+            .handicaps
+            .get(player_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_handicap_applied_to_round_scores() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.set_handicap(
+            "p1",
+            Handicap {
+                starting_score: 20,
+                score_percent: 150,
+            },
+        )
+        .unwrap();
+        assert_eq!(game.players[0].score, 20);
+
+        // Both stay on their dealt cards: Alice has 6, Bob 18
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+        game.player_stay("p2").unwrap();
+        let summary = game.finish_round().unwrap();
+
+        let alice = &summary.players[0];
+        assert_eq!((alice.raw_score, alice.round_score), (6, 9));
+        assert_eq!(alice.total_score, 29);
+        let bob = &summary.players[1];
+        assert_eq!((bob.raw_score, bob.round_score), (18, 18));
+        assert_eq!(game.check_invariants(), Ok(()));
+
+        assert!(game.set_handicap("p2", Handicap::default()).is_err());
+    }
+
+    #[test]
+    fn test_configured_handicap_applies_to_new_players() {
+        let mut config = crate::config::GameConfig::default();
+        config.handicaps.insert(
+            "p1".to_string(),
+            Handicap {
+                starting_score: 50,
+                score_percent: 100,
+            },
+        );
+        let mut game = GameState::new_with_config(42, config);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+
+        assert_eq!(game.players[0].score, 50);
+        assert_eq!(game.players[1].score, 0);
+        assert_eq!(Handicap::default().adjust(17), 17);
+    }
+}
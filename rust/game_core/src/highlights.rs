@@ -0,0 +1,143 @@
+use crate::analysis;
+use crate::events::GameEvent;
+use crate::rules::RuleBehavior;
+use crate::deck::DeckSpec;
+use crate::{Card, Hand};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A notable moment of a round, shown on the end-of-round recap screen.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum Highlight {
+    /// The draw that had the highest chance to bust and still didn't
+    RiskiestHit {
+        player_id: String,
+        card: Card,
+        bust_chance_percent: u8,
+    },
+    /// The first player to hold a Flip7, and how many cards it took
+    FastestFlip7 {
+        player_id: String,
+        cards_held: usize,
+    },
+}
+
+/// Finds the highlights of one round from its events.
+///
+/// Bust chances are computed from what the table could see at the time: the
+/// full `deck` minus every card already face up this round.
+pub fn analyze_round(events: &[GameEvent], deck: &DeckSpec, rules: &RuleBehavior) -> Vec<Highlight> {
+    let mut unseen: Vec<Card> = deck.cards();
+    let mut hands: HashMap<&str, Hand> = HashMap::new();
+    let mut riskiest: Option<Highlight> = None;
+    let mut riskiest_chance = 0;
+    let mut fastest_flip7: Option<Highlight> = None;
+
+    for event in events {
+        let (player_id, card) = match event {
+            GameEvent::CardDealt { player_id, card } => (player_id, *card),
+            GameEvent::CardDrawn { player_id, card } => {
+                let hand = hands.entry(player_id).or_default();
+                let chance = bust_chance_percent(hand, &unseen, rules);
+                let mut after = hand.clone();
+                after.add_card(*card);
+                if chance > riskiest_chance && !rules.is_bust(&after) {
+                    riskiest_chance = chance;
+                    riskiest = Some(Highlight::RiskiestHit {
+                        player_id: player_id.clone(),
+                        card: *card,
+                        bust_chance_percent: chance,
+                    });
+                }
+                (player_id, *card)
+            }
+            _ => continue,
+        };
+
+        if let Some(index) = unseen.iter().position(|c| *c == card) {
+            unseen.swap_remove(index);
+        }
+
+        let hand = hands.entry(player_id).or_default();
+        hand.add_card(card);
+        if fastest_flip7.is_none() && rules.has_flip7(hand) {
+            fastest_flip7 = Some(Highlight::FastestFlip7 {
+                player_id: player_id.clone(),
+                cards_held: hand.cards.len(),
+            });
+        }
+    }
+
+    riskiest.into_iter().chain(fastest_flip7).collect()
+}
+
+fn bust_chance_percent(hand: &Hand, unseen: &[Card], rules: &RuleBehavior) -> u8 {
+    (analysis::bust_probability_for(hand, unseen, rules) * 100.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::behavior_for;
+
+    fn dealt(player_id: &str, value: u8) -> GameEvent {
+        GameEvent::CardDealt {
+            player_id: player_id.to_string(),
+            card: Card::new(value),
+        }
+    }
+
+    fn drawn(player_id: &str, value: u8) -> GameEvent {
+        GameEvent::CardDrawn {
+            player_id: player_id.to_string(),
+            card: Card::new(value),
+        }
+    }
+
+    #[test]
+    fn test_round_highlights() {
+        let rules = behavior_for(1).unwrap();
+        let events = vec![
+            GameEvent::RoundStarted { round_number: 1 },
+            dealt("p1", 10),
+            dealt("p2", 2),
+            dealt("p1", 8),
+            dealt("p2", 9),
+            drawn("p1", 3), // 18 -> 21: most cards would have busted
+            drawn("p2", 5), // 2 + 5 makes a Flip7
+        ];
+
+        let highlights = analyze_round(&events, &DeckSpec::standard(), &rules);
+        assert_eq!(highlights.len(), 2);
+
+        match &highlights[0] {
+            Highlight::RiskiestHit {
+                player_id,
+                bust_chance_percent,
+                ..
+            } => {
+                assert_eq!(player_id, "p1");
+                assert!(*bust_chance_percent > 80);
+            }
+            other => panic!("Expected RiskiestHit, got {:?}", other),
+        }
+        assert_eq!(
+            highlights[1],
+            Highlight::FastestFlip7 {
+                player_id: "p2".to_string(),
+                cards_held: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_bust_is_not_a_highlight() {
+        let rules = behavior_for(1).unwrap();
+        let events = vec![dealt("p1", 12), dealt("p1", 9), drawn("p1", 11)];
+
+        assert!(analyze_round(&events, &DeckSpec::standard(), &rules).is_empty());
+    }
+}
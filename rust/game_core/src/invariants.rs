@@ -0,0 +1,97 @@
+use crate::errors;
+use crate::GameState;
+
+impl GameState {
+    /// Checks facts that must hold after any sequence of public calls. Used by
+    /// the replay reducer and fuzzers to notice a corrupted game early.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        if !self.players.is_empty() && self.round_state.current_player_index >= self.players.len() {
+            return Err(format!(
+                "Current player index {} out of range for {} players",
+                self.round_state.current_player_index,
+                self.players.len()
+            ));
+        }
+
+        if !self.round_state.is_finished
+            && self
+                .players
+                .get(self.round_state.current_player_index)
+                .is_some_and(|p| p.eliminated)
+        {
+            return Err(errors::PLAYER_ELIMINATED.to_string());
+        }
+
+        let cards_in_play = self.deck.len()
+            + self
+                .players
+                .iter()
+                .map(|p| p.hand.cards.len())
+                .sum::<usize>();
+        let full_deck = self.config.deck.len();
+        // Stacked decks carry over between rounds, so cards leave play
+        if !self.deck.stacked && cards_in_play != full_deck {
+            return Err(format!(
+                "{} cards in deck and hands, expected {}",
+                cards_in_play, full_deck
+            ));
+        }
+
+        for player in &self.players {
+            // The first round in history may start from points scored before
+            // history was kept, so only later rounds are checked against it
+            let mut total: Option<u32> = None;
+            for result in self
+                .history
+                .iter()
+                .flat_map(|round| &round.players)
+                .filter(|result| result.player_id == player.id)
+            {
+                if let Some(previous) = total {
+                    if result.total_score != previous + result.round_score {
+                        return Err(format!(
+                            "Player {} total jumped to {} in history, expected {}",
+                            player.id,
+                            result.total_score,
+                            previous + result.round_score
+                        ));
+                    }
+                }
+                total = Some(result.total_score);
+            }
+            if let Some(total) = total {
+                if total != player.score {
+                    return Err(format!(
+                        "Player {} has {} points but history adds up to {}",
+                        player.id, player.score, total
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invariants() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+        game.finish_round().unwrap();
+        assert_eq!(game.check_invariants(), Ok(()));
+
+        let mut lost_card = game.clone();
+        lost_card.deck.draw();
+        assert!(lost_card.check_invariants().is_err());
+
+        let mut wrong_score = game.clone();
+        wrong_score.players[0].score += 1;
+        assert!(wrong_score.check_invariants().is_err());
+    }
+}
@@ -1,56 +1,185 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "rng")]
 use rand_chacha::{ChaCha8Rng, rand_core::SeedableRng};
+use smallvec::SmallVec;
 use std::collections::HashMap;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+use std::hash::{Hash, Hasher};
+
+#[cfg(feature = "rng")]
+pub mod advisor;
+pub mod analysis;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod bots;
+pub mod clock;
+pub mod commitment;
+pub mod config;
+pub mod daily;
+pub mod deck;
+mod display;
+pub mod elimination;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod errors;
+pub mod events;
+pub mod handicap;
+pub mod hash;
+pub mod highlights;
+pub mod invariants;
+pub mod observer;
+pub mod outcome;
+pub mod pause;
+#[cfg(feature = "rng")]
+pub mod persona;
+// Replays re-deal every round from the game seed
+#[cfg(feature = "rng")]
+pub mod replay;
+pub mod rules;
+pub mod schema;
+pub mod scoring;
+pub mod shuffle;
+#[cfg(feature = "simulate")]
+pub mod simulate;
+pub mod skip_vote;
+pub mod solo;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+pub mod theme;
+pub mod timer;
+#[cfg(feature = "simulate")]
+pub mod tournament;
+pub mod variant;
+
+use clock::TimedMove;
+use config::GameConfig;
+use deck::DeckSpec;
+use events::GameEvent;
+use highlights::Highlight;
+use observer::Observers;
+use outcome::GameOutcome;
+use pause::PausePeriod;
+use shuffle::ShuffleSource;
+use rules::ENGINE_RULES_VERSION;
+use theme::SeatTheme;
+
+/// Highest value a card can have; the deck holds values 0 to 12.
+pub const MAX_CARD_VALUE: u8 = 12;
+
+/// A number card. Only values up to `MAX_CARD_VALUE` can be constructed or
+/// deserialized, so corrupted saves and forged network payloads can't sneak
+/// impossible cards into a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawCard"))]
 pub struct Card {
-    pub value: u8,
+    value: u8,
+}
+
+// Same layout as `Card`, for validating deserialized cards.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawCard {
+    value: u8,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawCard> for Card {
+    type Error = String;
+
+    fn try_from(raw: RawCard) -> Result<Self, Self::Error> {
+        Card::try_new(raw.value)
+    }
 }
 
 impl Card {
+    /// A card of the given value.
+    ///
+    /// # Panics
+    ///
+    /// If `value` is above `MAX_CARD_VALUE`; use `try_new` for values that
+    /// come from outside the program.
     pub fn new(value: u8) -> Self {
-        Self { value }
+        Self::try_new(value).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_new(value: u8) -> Result<Self, String> {
+        if value > MAX_CARD_VALUE {
+            return Err(format!("Invalid card value {} (cards go up to {})", value, MAX_CARD_VALUE));
+        }
+        Ok(Self { value })
+    }
+
+    pub fn value(self) -> u8 {
+        self.value
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
 pub struct Deck {
     pub cards: Vec<Card>,
-    #[serde(skip, default = "default_rng")]
+    #[cfg(feature = "rng")]
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_rng"))]
+    #[cfg_attr(feature = "archive", rkyv(with = archive::SkipRng))]
     rng: ChaCha8Rng,
+    /// Secret mixed into the deck commitment; see `Deck::commitment`. It is
+    /// never saved, archived or sent, and only comes out in a `DeckReveal`
+    /// once the round is over
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "archive", rkyv(with = rkyv::with::Skip))]
+    salt: Option<[u8; 32]>,
+    /// Laid out by hand to be drawn in order; see `testing`
+    #[cfg_attr(feature = "serde", serde(default))]
+    stacked: bool,
+}
+
+// Two decks are equal when they hold the same cards in the same order;
+// the RNG state and commitment salt are implementation details and are not compared.
+impl PartialEq for Deck {
+    fn eq(&self, other: &Self) -> bool {
+        self.cards == other.cards
+    }
 }
 
+impl Eq for Deck {}
+
+impl Hash for Deck {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cards.hash(state);
+    }
+}
+
+#[cfg(all(any(feature = "serde", feature = "archive"), feature = "rng"))]
 fn default_rng() -> ChaCha8Rng {
     ChaCha8Rng::seed_from_u64(42)
 }
 
 impl Deck {
+    /// The standard deck; see `DeckSpec::standard`.
     pub fn new(seed: u64) -> Self {
-        let mut cards = Vec::new();
+        Self::from_spec(&DeckSpec::standard(), seed)
+    }
 
-        // Cards 1-12 have n copies each (card value 1 has 1 copy, card value 2 has 2 copies, etc.)
-        for value in 1..=12 {
-            for _ in 0..value {
-                cards.push(Card::new(value));
-            }
+    // Without the `rng` feature, decks are shuffled with `shuffle_with` and
+    // the seed goes unused.
+    #[cfg_attr(not(feature = "rng"), allow(unused_variables))]
+    fn with_cards(cards: Vec<Card>, seed: u64) -> Self {
+        Self {
+            cards,
+            #[cfg(feature = "rng")]
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            salt: commitment::random_salt(),
+            stacked: false,
         }
-
-        // One unique card with value 0
-        cards.push(Card::new(0));
-
-        let rng = ChaCha8Rng::seed_from_u64(seed);
-
-        Self { cards, rng }
     }
 
+    /// Shuffles with the deck's own generator, seeded when it was built.
+    #[cfg(feature = "rng")]
     pub fn shuffle(&mut self) {
-        use rand_chacha::rand_core::RngCore;
-
-        // Fisher-Yates shuffle
-        for i in (1..self.cards.len()).rev() {
-            let j = (self.rng.next_u32() as usize) % (i + 1);
-            self.cards.swap(i, j);
-        }
+        self.rng.shuffle(&mut self.cards);
     }
 
     pub fn draw(&mut self) -> Option<Card> {
@@ -66,20 +195,68 @@ impl Deck {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Cards of a hand. They are stored inline, so building and copying hands
+/// doesn't allocate: a standard hand busts long before it outgrows them.
+pub type HandCards = SmallVec<[Card; 12]>;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
 pub struct Hand {
-    pub cards: Vec<Card>,
+    /// Cards in the order they were drawn; saves list them in canonical order
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_canonical"))]
+    pub cards: HandCards,
+}
+
+// The order in which cards were drawn does not matter for a hand, so hands
+// compare, hash and serialize as multisets of cards; see `Hand::canonical`.
+impl PartialEq for Hand {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
+    }
+}
+
+impl Eq for Hand {}
+
+impl Hash for Hand {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical().hash(state);
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_canonical<S: serde::Serializer>(cards: &HandCards, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut cards = cards.clone();
+    cards.sort();
+    cards.serialize(serializer)
+}
+
+impl Default for Hand {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Hand {
     pub fn new() -> Self {
-        Self { cards: Vec::new() }
+        Self {
+            cards: HandCards::new(),
+        }
     }
 
     pub fn add_card(&mut self, card: Card) {
         self.cards.push(card);
     }
 
+    /// The cards in a stable order that doesn't depend on how they were
+    /// drawn, so equal hands always look the same. Number cards are the only
+    /// kind in the deck, so that is by ascending value.
+    pub fn canonical(&self) -> HandCards {
+        let mut cards = self.cards.clone();
+        cards.sort();
+        cards
+    }
+
     pub fn total_value(&self) -> u8 {
         self.cards.iter().map(|card| card.value).sum()
     }
@@ -91,41 +268,47 @@ impl Hand {
     pub fn has_flip7(&self) -> bool {
         // Flip7 is when hand contains cards that sum to exactly 7
         // This could be a single 7, or combinations like 3+4, 1+6, 2+5, 1+2+4, etc.
-        let target = 7;
-        let values: Vec<u8> = self.cards.iter().map(|card| card.value).collect();
-        Self::can_sum_to_target(&values, target)
+        self.can_sum_to(7)
     }
 
-    fn can_sum_to_target(values: &[u8], target: u8) -> bool {
-        if target == 0 {
-            return true;
-        }
-        if values.is_empty() || target > values.iter().sum::<u8>() {
-            return false;
-        }
-
-        for (i, &value) in values.iter().enumerate() {
-            if value == target {
-                return true;
+    /// Whether some subset of the cards sums to exactly `target`.
+    pub fn can_sum_to(&self, target: u8) -> bool {
+        // Subset-sum DP, O(cards * target): reachable[s] is whether some of
+        // the cards seen so far sum to s. Walking sums downwards uses each
+        // card at most once.
+        let target = target as usize;
+        let mut reachable = [false; 256];
+        reachable[0] = true;
+        for card in &self.cards {
+            let value = card.value as usize;
+            for sum in (value..=target).rev() {
+                reachable[sum] |= reachable[sum - value];
             }
-            if value < target {
-                let remaining = &values[i + 1..];
-                if Self::can_sum_to_target(remaining, target - value) {
-                    return true;
-                }
+            if reachable[target] {
+                return true;
             }
         }
-        false
+        reachable[target]
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
 pub struct Player {
     pub id: String,
     pub name: String,
     pub hand: Hand,
     pub score: u32,
     pub has_stayed: bool,
+    /// Palette slot and pattern frontends draw this seat with
+    pub theme: SeatTheme,
+    /// Team in team mode; teammates' scores count together
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub team: Option<u8>,
+    /// Knocked out under the elimination variant; see `elimination`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub eliminated: bool,
 }
 
 impl Player {
@@ -136,6 +319,9 @@ impl Player {
             hand: Hand::new(),
             score: 0,
             has_stayed: false,
+            theme: SeatTheme::for_seat(0),
+            team: None,
+            eliminated: false,
         }
     }
 
@@ -153,11 +339,58 @@ impl Player {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
 pub struct RoundState {
     pub round_number: u32,
     pub current_player_index: usize,
     pub is_finished: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub is_scored: bool,
+    /// Commitment to the deck order published when the round started
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deck_commitment: Option<String>,
+    /// Players who voted to skip the current turn
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub skip_votes: Vec<String>,
+    /// When the current turn times out, in milliseconds since the Unix
+    /// epoch; see `timer`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub turn_deadline_ms: Option<u64>,
+    /// When the current turn started, in milliseconds since the Unix epoch;
+    /// see `clock`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub turn_started_ms: Option<u64>,
+}
+
+// The deck commitment is salted afresh every round, and turn times are taken
+// on the caller's clock, so they are left out of the hash: a game replayed
+// from its moves hashes the same as the one played.
+impl Hash for RoundState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            round_number,
+            current_player_index,
+            is_finished,
+            is_scored,
+            deck_commitment: _,
+            skip_votes,
+            turn_deadline_ms: _,
+            turn_started_ms: _,
+        } = self;
+        round_number.hash(state);
+        current_player_index.hash(state);
+        is_finished.hash(state);
+        is_scored.hash(state);
+        skip_votes.hash(state);
+    }
+}
+
+impl Default for RoundState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RoundState {
@@ -166,96 +399,381 @@ impl RoundState {
             round_number: 1,
             current_player_index: 0,
             is_finished: false,
+            is_scored: false,
+            deck_commitment: None,
+            skip_votes: Vec::new(),
+            turn_deadline_ms: None,
+            turn_started_ms: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// One player's line on the score sheet for a finished round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct PlayerRoundResult {
+    pub player_id: String,
+    /// Cards the player ended the round with
+    pub cards: Vec<Card>,
+    pub busted: bool,
+    pub flip7_bonus: bool,
+    /// Score of the hand itself, before any handicap
+    pub raw_score: u32,
+    /// Score credited for the round, with the player's handicap applied
+    pub round_score: u32,
+    /// Cumulative score after this round
+    pub total_score: u32,
+    /// Time spent on timestamped moves this round; see `clock`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub thinking_ms: u64,
+}
+
+// Thinking time is only known for moves made with `make_move_at`, so it is
+// left out of the hash, like the other timings; see `GameState`.
+impl Hash for PlayerRoundResult {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            player_id,
+            cards,
+            busted,
+            flip7_bonus,
+            raw_score,
+            round_score,
+            total_score,
+            thinking_ms: _,
+        } = self;
+        player_id.hash(state);
+        cards.hash(state);
+        busted.hash(state);
+        flip7_bonus.hash(state);
+        raw_score.hash(state);
+        round_score.hash(state);
+        total_score.hash(state);
+    }
+}
+
+/// Outcome of a finished round, returned once by `GameState::finish_round`
+/// and recorded in `GameState::history`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct RoundSummary {
+    pub round_number: u32,
+    pub players: Vec<PlayerRoundResult>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub highlights: Vec<Highlight>,
+    /// Game time played up to the end of this round; see `GameState::duration_ms`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub game_duration_ms: u64,
+}
+
+impl Hash for RoundSummary {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            round_number,
+            players,
+            highlights,
+            game_duration_ms: _,
+        } = self;
+        round_number.hash(state);
+        players.hash(state);
+        highlights.hash(state);
+    }
+}
+
+impl RoundSummary {
+    /// Round score per player id.
+    pub fn scores(&self) -> HashMap<String, u32> {
+        self.players
+            .iter()
+            .map(|result| (result.player_id.clone(), result.round_score))
+            .collect()
+    }
+}
+
+/// A single player action, as submitted over the network or by a bot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum GameMove {
+    Draw { player_id: String },
+    Stay { player_id: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
 pub struct GameState {
+    /// Layout version of the serialized state; see `schema::migrate`
+    pub schema_version: u32,
     pub players: Vec<Player>,
     pub deck: Deck,
+    /// Seed every round's shuffle is derived from
+    #[cfg_attr(feature = "serde", serde(default = "legacy_seed"))]
+    pub seed: u64,
     pub round_state: RoundState,
+    /// Rules version the game is scored with; see `rules::behavior_for`.
+    #[cfg_attr(feature = "serde", serde(
+        default = "legacy_rules_version",
+        deserialize_with = "rules::deserialize_version"
+    ))]
+    pub engine_rules_version: u32,
+    /// Summaries of every finished round, oldest first
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub history: Vec<RoundSummary>,
+    /// Everything that happened so far, oldest first
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub events: Vec<GameEvent>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub config: GameConfig,
+    /// Set once the game has a winner; no more rounds can be started
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub outcome: Option<GameOutcome>,
+    /// Tied leaders playing a sudden-death round, if any
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sudden_death: Vec<String>,
+    /// Every time the game was paused, oldest first; see `pause`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pauses: Vec<PausePeriod>,
+    /// When the game's clock started; see `clock`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub started_at_ms: Option<u64>,
+    /// Every move of the current round made with `make_move_at`, oldest
+    /// first; earlier rounds' times are kept in `history`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timed_moves: Vec<TimedMove>,
+    /// Embedder callbacks; see `observer::GameObserver`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "archive", rkyv(with = rkyv::with::Skip))]
+    pub observers: Observers,
+    /// How hands are scored; see `scoring::ScoringStrategy`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "archive", rkyv(with = rkyv::with::Skip))]
+    pub scoring: scoring::Scoring,
+}
+
+// Times are taken on the caller's clock and only recorded by `make_move_at`,
+// so they are left out of `state_hash`: a game replayed from its moves, or
+// played without timestamps, hashes the same as the one played live.
+impl Hash for GameState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            schema_version,
+            players,
+            deck,
+            seed,
+            round_state,
+            engine_rules_version,
+            history,
+            events,
+            config,
+            outcome,
+            sudden_death,
+            pauses,
+            started_at_ms: _,
+            timed_moves: _,
+            observers,
+            scoring,
+        } = self;
+        schema_version.hash(state);
+        players.hash(state);
+        deck.hash(state);
+        seed.hash(state);
+        round_state.hash(state);
+        engine_rules_version.hash(state);
+        history.hash(state);
+        events.hash(state);
+        config.hash(state);
+        outcome.hash(state);
+        sudden_death.hash(state);
+        pauses.hash(state);
+        observers.hash(state);
+        scoring.hash(state);
+    }
+}
+
+// Saves written before the field existed were all scored with version 1.
+#[cfg(feature = "serde")]
+fn legacy_rules_version() -> u32 {
+    1
+}
+
+// Before the seed was stored, every game shuffled its rounds from seed 42.
+#[cfg(feature = "serde")]
+fn legacy_seed() -> u64 {
+    42
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameState {
     pub fn new() -> Self {
         let deck = Deck::new(42); // Default seed
         Self {
+            schema_version: schema::CURRENT_SCHEMA_VERSION,
             players: Vec::new(),
             deck,
+            seed: 42,
             round_state: RoundState::new(),
+            engine_rules_version: ENGINE_RULES_VERSION,
+            history: Vec::new(),
+            events: Vec::new(),
+            config: GameConfig::default(),
+            outcome: None,
+            sudden_death: Vec::new(),
+            pauses: Vec::new(),
+            started_at_ms: None,
+            timed_moves: Vec::new(),
+            observers: Observers::default(),
+            scoring: scoring::Scoring::default(),
         }
     }
 
     pub fn new_with_seed(seed: u64) -> Self {
         let deck = Deck::new(seed);
         Self {
+            schema_version: schema::CURRENT_SCHEMA_VERSION,
             players: Vec::new(),
             deck,
+            seed,
             round_state: RoundState::new(),
+            engine_rules_version: ENGINE_RULES_VERSION,
+            history: Vec::new(),
+            events: Vec::new(),
+            config: GameConfig::default(),
+            outcome: None,
+            sudden_death: Vec::new(),
+            pauses: Vec::new(),
+            started_at_ms: None,
+            timed_moves: Vec::new(),
+            observers: Observers::default(),
+            scoring: scoring::Scoring::default(),
+        }
+    }
+
+    pub fn new_with_config(seed: u64, config: GameConfig) -> Self {
+        Self {
+            deck: Deck::from_spec(&config.deck, seed),
+            config,
+            ..Self::new_with_seed(seed)
         }
     }
 
     pub fn add_player(&mut self, id: String, name: String) {
-        let player = Player::new(id, name);
+        let mut player = Player::new(id, name);
+        player.theme = self.next_free_theme();
+        player.score = self.handicap_of(&player.id).starting_score;
         self.players.push(player);
     }
 
-    pub fn start_round(&mut self) -> Result<(), String> {
+    /// Starts the next round, shuffling its fresh deck with `shuffle`.
+    /// `start_round` does the same with the game's seeded generator.
+    pub fn start_round_with(&mut self, shuffle: &mut dyn ShuffleSource) -> Result<(), String> {
         if self.players.is_empty() {
-            return Err("No players added".to_string());
+            return Err(errors::NO_PLAYERS_ADDED.to_string());
+        }
+        if self.is_game_over() {
+            return Err(errors::GAME_OVER.to_string());
+        }
+        if self.is_paused() {
+            return Err(errors::GAME_PAUSED.to_string());
         }
 
-        // Reset all players for new round
+        // Only this round's moves are timed; see `timed_moves`
+        self.timed_moves.clear();
+
+        // Reset all players for new round; eliminated ones sit it out
         for player in &mut self.players {
             player.reset_for_round();
+            player.has_stayed = player.eliminated;
+        }
+
+        // Refill the deck and shuffle; a stacked deck is dealt on as it lies
+        if !self.deck.stacked {
+            self.deck.refill(
+                &self.config.deck,
+                self.seed.wrapping_add(self.round_state.round_number as u64),
+            );
+            self.deck.shuffle_with(shuffle);
         }
+        self.round_state.deck_commitment = self.deck.commitment();
 
-        // Create new deck and shuffle
-        self.deck = Deck::new(42 + self.round_state.round_number as u64);
-        self.deck.shuffle();
+        self.emit(GameEvent::RoundStarted {
+            round_number: self.round_state.round_number,
+        });
 
         // Deal initial cards (each player gets 2 cards)
         for _ in 0..2 {
-            for player in &mut self.players {
+            for i in 0..self.players.len() {
+                if self.players[i].eliminated {
+                    continue;
+                }
                 if let Some(card) = self.deck.draw() {
-                    player.draw_card(card);
+                    self.players[i].draw_card(card);
+                    self.emit(GameEvent::CardDealt {
+                        player_id: self.players[i].id.clone(),
+                        card,
+                    });
                 }
             }
         }
 
-        self.round_state.current_player_index = 0;
+        self.round_state.current_player_index =
+            self.players.iter().position(|p| !p.eliminated).unwrap_or(0);
         self.round_state.is_finished = false;
+        self.round_state.is_scored = false;
+        self.round_state.skip_votes.clear();
+        self.round_state.turn_deadline_ms = None;
+        self.round_state.turn_started_ms = None;
 
         Ok(())
     }
 
     pub fn player_draw(&mut self, player_id: &str) -> Result<(), String> {
         if self.round_state.is_finished {
-            return Err("Round is finished".to_string());
+            return Err(errors::ROUND_FINISHED.to_string());
+        }
+        if self.is_paused() {
+            return Err(errors::GAME_PAUSED.to_string());
         }
 
         let current_player = &mut self.players[self.round_state.current_player_index];
         if current_player.id != player_id {
-            return Err("Not your turn".to_string());
+            return Err(errors::NOT_YOUR_TURN.to_string());
         }
 
         if current_player.has_stayed {
-            return Err("Player has already stayed".to_string());
+            return Err(errors::ALREADY_STAYED.to_string());
         }
 
         if let Some(card) = self.deck.draw() {
             current_player.draw_card(card);
 
             // Check if player is bust
-            if current_player.hand.is_bust() {
+            let busted = current_player.hand.is_bust();
+            if busted {
                 current_player.stay(); // Auto-stay on bust
             }
 
+            self.emit(GameEvent::CardDrawn {
+                player_id: player_id.to_string(),
+                card,
+            });
+            if busted {
+                self.emit(GameEvent::PlayerBusted {
+                    player_id: player_id.to_string(),
+                });
+            }
+
             // Move to next player
             self.advance_turn();
         } else {
-            return Err("Deck is empty".to_string());
+            return Err(errors::DECK_EMPTY.to_string());
         }
 
         Ok(())
@@ -263,23 +781,44 @@ impl GameState {
 
     pub fn player_stay(&mut self, player_id: &str) -> Result<(), String> {
         if self.round_state.is_finished {
-            return Err("Round is finished".to_string());
+            return Err(errors::ROUND_FINISHED.to_string());
+        }
+        if self.is_paused() {
+            return Err(errors::GAME_PAUSED.to_string());
         }
 
         let current_player = &mut self.players[self.round_state.current_player_index];
         if current_player.id != player_id {
-            return Err("Not your turn".to_string());
+            return Err(errors::NOT_YOUR_TURN.to_string());
         }
 
         current_player.stay();
+        self.emit(GameEvent::PlayerStayed {
+            player_id: player_id.to_string(),
+        });
         self.advance_turn();
 
         Ok(())
     }
 
+    pub fn make_move(&mut self, game_move: GameMove) -> Result<(), String> {
+        match game_move {
+            GameMove::Draw { player_id } => self.player_draw(&player_id),
+            GameMove::Stay { player_id } => self.player_stay(&player_id),
+        }
+    }
+
     fn advance_turn(&mut self) {
-        self.round_state.current_player_index =
-            (self.round_state.current_player_index + 1) % self.players.len();
+        let seats = self.players.len();
+        let current = self.round_state.current_player_index;
+        // Next seat still in the game, wrapping around to the current one
+        self.round_state.current_player_index = (1..=seats)
+            .map(|offset| (current + offset) % seats)
+            .find(|&seat| !self.players[seat].eliminated)
+            .unwrap_or(current);
+        self.round_state.skip_votes.clear();
+        self.round_state.turn_deadline_ms = None;
+        self.round_state.turn_started_ms = None;
 
         // Check if all players have stayed or busted
         if self.players.iter().all(|p| p.has_stayed) {
@@ -287,43 +826,167 @@ impl GameState {
         }
     }
 
-    pub fn compute_scores(&mut self) -> HashMap<String, u32> {
-        let mut scores = HashMap::new();
+    fn rules(&self) -> rules::RuleBehavior {
+        // Versions are validated on deserialization, so only direct mutation can get here
+        rules::behavior_for(self.engine_rules_version).expect("unsupported engine rules version")
+    }
 
-        for player in &mut self.players {
-            let mut round_score = 0;
-
-            if player.hand.has_flip7() {
-                // Flip7 bonus
-                round_score += 21;
-            } else if !player.hand.is_bust() {
-                // Normal scoring: hand value
-                round_score += player.hand.total_value() as u32;
+    /// Scores each player would earn for the current round, without changing any state.
+    pub fn round_scores(&self) -> HashMap<String, u32> {
+        self.players
+            .iter()
+            .map(|player| (player.id.clone(), self.score_hand(&player.id, &player.hand)))
+            .collect()
+    }
+
+    /// Puts a player on a team, or takes them off with `None`.
+    pub fn set_team(&mut self, player_id: &str, team: Option<u8>) -> Result<(), String> {
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.id == player_id)
+            .ok_or(errors::PLAYER_NOT_FOUND)?;
+        player.team = team;
+        Ok(())
+    }
+
+    /// Total score of each team, summed over its members.
+    pub fn team_scores(&self) -> HashMap<u8, u32> {
+        let mut scores = HashMap::new();
+        for player in &self.players {
+            if let Some(team) = player.team {
+                *scores.entry(team).or_insert(0) += player.score;
             }
-            // Bust = 0 points
+        }
+        scores
+    }
+
+    /// Adds the round scores to each player's total, records the round in `history`
+    /// and moves on to the next round number, declaring a winner once the target score
+    /// is reached (see `outcome`). Can only be called once per finished round.
+    pub fn finish_round(&mut self) -> Result<RoundSummary, String> {
+        if !self.round_state.is_finished {
+            return Err(errors::ROUND_NOT_FINISHED.to_string());
+        }
+        if self.round_state.is_scored {
+            return Err(errors::ROUND_SCORED.to_string());
+        }
 
+        let rules = self.rules();
+        let round_number = self.round_state.round_number;
+        let scored: Vec<(u32, u64)> = self
+            .players
+            .iter()
+            .map(|player| {
+                (
+                    self.score_hand(&player.id, &player.hand),
+                    self.thinking_ms(&player.id, round_number),
+                )
+            })
+            .collect();
+        let mut results = Vec::new();
+        for (player, (raw_score, thinking_ms)) in self.players.iter_mut().zip(scored) {
+            if player.eliminated {
+                continue;
+            }
+            let round_score = self
+                .config
+                .handicaps
+                .get(&player.id)
+                .map_or(raw_score, |handicap| handicap.adjust(raw_score));
             player.score += round_score;
-            scores.insert(player.id.clone(), round_score);
+
+            results.push(PlayerRoundResult {
+                player_id: player.id.clone(),
+                cards: player.hand.canonical().to_vec(),
+                busted: rules.is_bust(&player.hand),
+                flip7_bonus: rules.has_flip7(&player.hand),
+                raw_score,
+                round_score,
+                total_score: player.score,
+                thinking_ms,
+            });
         }
 
+        let summary = RoundSummary {
+            round_number: self.round_state.round_number,
+            players: results,
+            highlights: highlights::analyze_round(events::current_round(&self.events), &self.config.deck, &rules),
+            game_duration_ms: self.duration_ms(),
+        };
+        self.history.push(summary.clone());
+
+        self.round_state.is_scored = true;
         self.round_state.round_number += 1;
-        scores
+        self.observers.round_ended(&summary);
+        self.apply_elimination();
+        self.resolve_outcome();
+        Ok(summary)
     }
 
     pub fn is_flip7(&self, player_id: &str) -> Result<bool, String> {
-        let player = self.players.iter()
-            .find(|p| p.id == player_id)
-            .ok_or("Player not found")?;
+        let player = self.player(player_id).ok_or(errors::PLAYER_NOT_FOUND)?;
 
         Ok(player.hand.has_flip7())
     }
 
+    /// Every player, in seat order.
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    /// The player whose turn it is, or `None` before anyone has joined.
+    pub fn current_player(&self) -> Option<&Player> {
+        self.players.get(self.round_state.current_player_index)
+    }
+
+    pub fn player(&self, player_id: &str) -> Option<&Player> {
+        self.players.iter().find(|p| p.id == player_id)
+    }
+
+    /// Players still in the game, in seat order.
+    pub fn active_players(&self) -> impl Iterator<Item = &Player> {
+        self.players.iter().filter(|p| !p.eliminated)
+    }
+
+    /// Every player from highest to lowest total score; ties keep seat order.
+    pub fn standings(&self) -> Vec<&Player> {
+        let mut standings: Vec<&Player> = self.players.iter().collect();
+        standings.sort_by_key(|p| std::cmp::Reverse(p.score));
+        standings
+    }
+
+    /// Platform-independent hash of the game, consistent with `==` (RNG state and
+    /// card order within a hand are ignored). Clients compare it with the
+    /// server's after every move to detect desynchronization.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = hash::StableHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[cfg(feature = "serde")]
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(self)
     }
 
+    /// Loads a saved game, upgrading saves written with an older schema.
+    #[cfg(feature = "serde")]
     pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        schema::migrate(&mut value).map_err(serde::de::Error::custom)?;
+        serde_json::from_value(value)
+    }
+
+    /// Compact binary encoding, much smaller than JSON on mobile networks.
+    #[cfg(feature = "binary")]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, postcard::Error> {
+        postcard::to_allocvec(self)
+    }
+
+    #[cfg(feature = "binary")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, postcard::Error> {
+        postcard::from_bytes(bytes)
     }
 }
 
@@ -394,6 +1057,22 @@ mod tests {
         assert!(!hand4.has_flip7());
     }
 
+    #[test]
+    fn test_can_sum_to() {
+        let mut hand = Hand::new();
+        assert!(hand.can_sum_to(0));
+        assert!(!hand.can_sum_to(7));
+
+        // Each card counts once, however many are in the hand
+        for _ in 0..30 {
+            hand.add_card(Card::new(12));
+        }
+        hand.add_card(Card::new(5));
+        assert!(hand.can_sum_to(5 + 12 * 20));
+        assert!(!hand.can_sum_to(7));
+        assert!(!hand.can_sum_to(13));
+    }
+
     #[test]
     fn test_scoring_accuracy() {
         let mut game = GameState::new();
@@ -405,244 +1084,201 @@ mod tests {
         game.players[1].hand.add_card(Card::new(10)); // Normal hand
         game.players[1].hand.add_card(Card::new(5)); // Total 15
 
-        let scores = game.compute_scores();
+        let scores = game.round_scores();
 
         assert_eq!(scores["player1"], 21); // Flip7 bonus
         assert_eq!(scores["player2"], 15); // Hand value
     }
 
+    #[cfg(feature = "rng")]
     #[test]
-    fn test_game_flow() {
+    fn test_finish_round_only_once() {
         let mut game = GameState::new();
         game.add_player("p1".to_string(), "Player 1".to_string());
         game.add_player("p2".to_string(), "Player 2".to_string());
+        game.start_round().unwrap();
 
-        assert!(game.start_round().is_ok());
-
-        // Each player should have 2 cards initially
-        assert_eq!(game.players[0].hand.cards.len(), 2);
-        assert_eq!(game.players[1].hand.cards.len(), 2);
-
-        // Test serialization
-        assert!(game.to_json().is_ok());
-    }
-}
-
-// FFI module for React Native integration
-use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
-use std::sync::{Mutex, OnceLock};
-
-// Global game state storage
-static GAME_STATES: OnceLock<Mutex<HashMap<String, GameState>>> = OnceLock::new();
-static mut NEXT_GAME_ID: u32 = 1;
+        assert!(game.finish_round().is_err()); // Round still in progress
 
-// Helper function to convert Rust string to C string
-fn to_c_string(s: String) -> *mut c_char {
-    match CString::new(s) {
-        Ok(c_string) => c_string.into_raw(),
-        Err(_) => std::ptr::null_mut(),
-    }
-}
+        game.player_stay("p1").unwrap();
+        game.player_stay("p2").unwrap();
 
-// Helper function to convert C string to Rust string
-fn from_c_string(ptr: *const c_char) -> Result<String, String> {
-    if ptr.is_null() {
-        return Err("Null pointer".to_string());
-    }
+        let expected = game.round_scores();
+        let summary = game.finish_round().unwrap();
+        assert_eq!(summary.round_number, 1);
+        assert_eq!(summary.scores(), expected);
+        assert_eq!(game.players[0].score, expected["p1"]);
 
-    unsafe {
-        match CStr::from_ptr(ptr).to_str() {
-            Ok(s) => Ok(s.to_string()),
-            Err(_) => Err("Invalid UTF-8".to_string()),
-        }
+        // A second call must not double-count
+        assert!(game.finish_round().is_err());
+        assert_eq!(game.players[0].score, expected["p1"]);
+        assert_eq!(game.round_state.round_number, 2);
     }
-}
 
-#[no_mangle]
-pub extern "C" fn flip7_new_game(players: u32, seed: u64) -> *mut c_char {
-    let result = (|| -> Result<String, String> {
-        if players < 1 || players > 8 {
-            return Err("Number of players must be between 1 and 8".to_string());
-        }
-
-        let mut game = GameState::new_with_seed(seed);
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    #[test]
+    fn test_round_history() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Player 1".to_string());
+        game.add_player("p2".to_string(), "Player 2".to_string());
 
-        // Add players
-        for i in 0..players {
-            game.add_player(i.to_string(), format!("Player {}", i));
+        for _ in 0..2 {
+            game.start_round().unwrap();
+            game.player_stay("p1").unwrap();
+            game.player_stay("p2").unwrap();
+            game.finish_round().unwrap();
         }
 
-        // Start the first round
-        game.start_round().map_err(|e| format!("Failed to start round: {}", e))?;
+        assert_eq!(game.history.len(), 2);
+        assert_eq!(game.history[1].round_number, 2);
+
+        let last = &game.history[1].players[0];
+        assert_eq!(last.player_id, "p1");
+        assert_eq!(last.cards.len(), 2);
+        assert_eq!(last.total_score, game.players[0].score);
+        assert_eq!(
+            last.total_score,
+            game.history[0].players[0].round_score + last.round_score
+        );
+
+        // History survives a save/load round-trip
+        let restored = GameState::from_json(&game.to_json().unwrap()).unwrap();
+        assert_eq!(restored.history, game.history);
+    }
 
-        let game_id = unsafe {
-            let id = NEXT_GAME_ID;
-            NEXT_GAME_ID += 1;
-            id.to_string()
-        };
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    #[test]
+    fn test_game_flow() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Player 1".to_string());
+        game.add_player("p2".to_string(), "Player 2".to_string());
 
-        // Initialize or get the game states
-        let states = GAME_STATES.get_or_init(|| Mutex::new(HashMap::new()));
-        let mut states = states.lock().map_err(|_| "Failed to lock game states")?;
-        states.insert(game_id.clone(), game);
-
-        // Return success response with game ID
-        let response = serde_json::json!({
-            "success": true,
-            "game_id": game_id,
-            "players": players,
-            "seed": seed
-        });
+        assert!(game.start_round().is_ok());
 
-        Ok(response.to_string())
-    })();
+        // Each player should have 2 cards initially
+        assert_eq!(game.players[0].hand.cards.len(), 2);
+        assert_eq!(game.players[1].hand.cards.len(), 2);
 
-    match result {
-        Ok(json) => to_c_string(json),
-        Err(err) => {
-            let error_response = serde_json::json!({
-                "success": false,
-                "error": err
-            });
-            to_c_string(error_response.to_string())
-        }
+        // Test serialization
+        assert!(game.to_json().is_ok());
     }
-}
 
-#[no_mangle]
-pub extern "C" fn flip7_get_state(game_id: *const c_char) -> *mut c_char {
-    let result = (|| -> Result<String, String> {
-        let game_id_str = from_c_string(game_id)?;
+    #[test]
+    fn test_player_queries() {
+        let mut game = GameState::new();
+        assert!(game.current_player().is_none());
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.add_player("p3".to_string(), "Carol".to_string());
+        game.players[1].score = 30;
+        game.players[2].score = 30;
+        game.players[2].eliminated = true;
+
+        assert_eq!(game.players().len(), 3);
+        assert_eq!(game.current_player().unwrap().id, "p1");
+        assert_eq!(game.player("p2").unwrap().name, "Bob");
+        assert!(game.player("p4").is_none());
+        assert_eq!(game.active_players().count(), 2);
+        let standings: Vec<&str> = game.standings().iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(standings, vec!["p2", "p3", "p1"]);
+    }
 
-        let states = GAME_STATES.get_or_init(|| Mutex::new(HashMap::new()));
-        let states = states.lock().map_err(|_| "Failed to lock game states")?;
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    #[test]
+    fn test_card_values_are_validated() {
+        assert_eq!(Card::try_new(12).map(Card::value), Ok(12));
+        assert!(Card::try_new(13).is_err());
 
-        match states.get(&game_id_str) {
-            Some(game) => {
-                let response = serde_json::json!({
-                    "success": true,
-                    "game_state": game
-                });
-                Ok(response.to_string())
-            }
-            None => Err("Game not found".to_string())
-        }
-    })();
+        assert_eq!(serde_json::from_str::<Card>(r#"{"value":0}"#).unwrap(), Card::new(0));
+        assert!(serde_json::from_str::<Card>(r#"{"value":200}"#).is_err());
 
-    match result {
-        Ok(json) => to_c_string(json),
-        Err(err) => {
-            let error_response = serde_json::json!({
-                "success": false,
-                "error": err
-            });
-            to_c_string(error_response.to_string())
-        }
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+        let mut forged: serde_json::Value = serde_json::from_str(&game.to_json().unwrap()).unwrap();
+        forged["players"][0]["hand"]["cards"][0]["value"] = serde_json::json!(99);
+        let err = GameState::from_json(&forged.to_string()).unwrap_err();
+        assert!(err.to_string().contains("Invalid card value 99"), "{}", err);
     }
-}
 
-#[no_mangle]
-pub extern "C" fn flip7_draw(game_id: *const c_char, player: u32) -> *mut c_char {
-    let result = (|| -> Result<String, String> {
-        let game_id_str = from_c_string(game_id)?;
-
-        let states = GAME_STATES.get_or_init(|| Mutex::new(HashMap::new()));
-        let mut states = states.lock().map_err(|_| "Failed to lock game states")?;
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_hand_equality_ignores_order() {
+        let mut hand1 = Hand::new();
+        hand1.add_card(Card::new(3));
+        hand1.add_card(Card::new(9));
 
-        match states.get_mut(&game_id_str) {
-            Some(game) => {
-                if player as usize >= game.players.len() {
-                    return Err(format!("Player {} does not exist", player));
-                }
+        let mut hand2 = Hand::new();
+        hand2.add_card(Card::new(9));
+        hand2.add_card(Card::new(3));
 
-                let player_id = player.to_string();
-                game.player_draw(&player_id).map_err(|e| format!("Draw failed: {}", e))?;
-
-                let player_obj = &game.players[player as usize];
-                let response = serde_json::json!({
-                    "success": true,
-                    "player": player,
-                    "hand_total": player_obj.hand.total_value(),
-                    "cards_count": player_obj.hand.cards.len(),
-                    "is_bust": player_obj.hand.is_bust(),
-                    "has_flip7": player_obj.hand.has_flip7(),
-                    "round_finished": game.round_state.is_finished
-                });
+        assert_eq!(hand1, hand2);
 
-                Ok(response.to_string())
-            }
-            None => Err("Game not found".to_string())
-        }
-    })();
+        assert_eq!(hand1.canonical().as_slice(), [Card::new(3), Card::new(9)]);
+        assert_eq!(
+            serde_json::to_string(&hand1).unwrap(),
+            serde_json::to_string(&hand2).unwrap()
+        );
 
-    match result {
-        Ok(json) => to_c_string(json),
-        Err(err) => {
-            let error_response = serde_json::json!({
-                "success": false,
-                "error": err
-            });
-            to_c_string(error_response.to_string())
-        }
+        let mut set = std::collections::HashSet::new();
+        set.insert(hand1);
+        assert!(set.contains(&hand2));
     }
-}
 
-#[no_mangle]
-pub extern "C" fn flip7_stay(game_id: *const c_char, player: u32) -> *mut c_char {
-    let result = (|| -> Result<String, String> {
-        let game_id_str = from_c_string(game_id)?;
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    #[test]
+    fn test_game_state_equality() {
+        let mut game = GameState::new_with_seed(7);
+        game.add_player("p1".to_string(), "Player 1".to_string());
+        game.start_round().unwrap();
 
-        let states = GAME_STATES.get_or_init(|| Mutex::new(HashMap::new()));
-        let mut states = states.lock().map_err(|_| "Failed to lock game states")?;
+        // The RNG is not serialized, yet the restored state compares equal
+        let restored = GameState::from_json(&game.to_json().unwrap()).unwrap();
+        assert_eq!(restored, game);
 
-        match states.get_mut(&game_id_str) {
-            Some(game) => {
-                if player as usize >= game.players.len() {
-                    return Err(format!("Player {} does not exist", player));
-                }
+        let mut drawn = game.clone();
+        drawn.player_draw("p1").unwrap();
+        assert_ne!(drawn, game);
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn test_binary_round_trip() {
+        let mut game = GameState::new_with_seed(7);
+        game.add_player("p1".to_string(), "Player 1".to_string());
+        game.start_round().unwrap();
 
-                let player_id = player.to_string();
-                game.player_stay(&player_id).map_err(|e| format!("Stay failed: {}", e))?;
+        let bytes = game.to_bytes().unwrap();
+        assert!(bytes.len() < game.to_json().unwrap().len());
+        assert_eq!(GameState::from_bytes(&bytes).unwrap(), game);
+    }
 
-                let mut scores = None;
-                if game.round_state.is_finished {
-                    scores = Some(game.compute_scores());
-                }
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    #[test]
+    fn test_state_hash() {
+        let mut game = GameState::new_with_seed(7);
+        game.add_player("p1".to_string(), "Player 1".to_string());
+        game.add_player("p2".to_string(), "Player 2".to_string());
+        game.start_round().unwrap();
 
-                let response = serde_json::json!({
-                    "success": true,
-                    "player": player,
-                    "round_finished": game.round_state.is_finished,
-                    "scores": scores
-                });
+        let restored = GameState::from_json(&game.to_json().unwrap()).unwrap();
+        assert_eq!(restored.state_hash(), game.state_hash());
 
-                Ok(response.to_string())
-            }
-            None => Err("Game not found".to_string())
-        }
-    })();
+        let mut moved = game.clone();
+        moved.player_stay("p1").unwrap();
+        assert_ne!(moved.state_hash(), game.state_hash());
 
-    match result {
-        Ok(json) => to_c_string(json),
-        Err(err) => {
-            let error_response = serde_json::json!({
-                "success": false,
-                "error": err
-            });
-            to_c_string(error_response.to_string())
-        }
+        // Pinned value: must be identical on every platform and Rust release
+        assert_eq!(game.state_hash(), 12409465000391143087);
     }
 }
 
-#[no_mangle]
-pub extern "C" fn flip7_free_string(ptr: *mut c_char) {
-    if !ptr.is_null() {
-        unsafe {
-            let _ = CString::from_raw(ptr);
-        }
-    }
-}
+// FFI for React Native integration; JSON in and out, so it needs serde
+// The C API passes games as JSON and deals with the built-in shuffle
+#[cfg(all(feature = "serde", feature = "rng"))]
+mod ffi;
+#[cfg(all(feature = "serde", feature = "rng"))]
+pub use ffi::*;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "serde", feature = "rng"))]
 mod ffi_test;
@@ -22,25 +22,7 @@ fn main() {
     }
 
     println!("\n=== Initial Hands ===");
-    for player in game.players.iter() {
-        println!("{}: {} cards, total value: {}",
-                 player.name,
-                 player.hand.cards.len(),
-                 player.hand.total_value());
-
-        print!("  Cards: ");
-        for card in &player.hand.cards {
-            print!("{} ", card.value);
-        }
-        println!();
-
-        if player.hand.has_flip7() {
-            println!("  🎉 {} has FLIP7!", player.name);
-        }
-        if player.hand.is_bust() {
-            println!("  💥 {} is BUST!", player.name);
-        }
-    }
+    println!("{}", game);
 
     // Simulate some draws
     println!("\n=== Game Simulation ===");
@@ -67,21 +49,19 @@ fn main() {
     }
 
     println!("\n=== Final Results ===");
-    let scores = game.compute_scores();
+    let scores = match game.finish_round() {
+        Ok(summary) => summary.scores(),
+        Err(e) => {
+            println!("✗ Failed to finish round: {}", e);
+            return;
+        }
+    };
 
     for player in &game.players {
-        println!("{}: {} cards, total value: {}, round score: {}",
+        println!("{}: {}, round score: {}",
                  player.name,
-                 player.hand.cards.len(),
-                 player.hand.total_value(),
+                 player.hand,
                  scores.get(&player.id).unwrap_or(&0));
-
-        if player.hand.has_flip7() {
-            println!("  🎉 FLIP7 bonus!");
-        }
-        if player.hand.is_bust() {
-            println!("  💥 BUST!");
-        }
     }
 
     // Test serialization
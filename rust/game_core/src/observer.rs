@@ -0,0 +1,181 @@
+//! Callbacks for embedders (RN bridge, TUI, server) that want to react to
+//! what happens in a game instead of diffing JSON snapshots.
+
+use crate::events::GameEvent;
+use crate::outcome::GameOutcome;
+use crate::{Card, GameState, RoundSummary};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Receives game events as they happen. Every callback has an empty default,
+/// so implementors only override what they care about.
+///
+/// Callbacks run synchronously inside the call that caused them and take
+/// `&self`; use interior mutability to keep state.
+pub trait GameObserver: Send + Sync {
+    fn on_round_started(&self, _round_number: u32) {}
+    /// Card dealt face up at the start of a round
+    fn on_card_dealt(&self, _player_id: &str, _card: Card) {}
+    fn on_card_drawn(&self, _player_id: &str, _card: Card) {}
+    fn on_bust(&self, _player_id: &str) {}
+    fn on_stay(&self, _player_id: &str) {}
+    fn on_turn_skipped(&self, _player_id: &str) {}
+    fn on_turn_timed_out(&self, _player_id: &str) {}
+    /// A round was scored
+    fn on_round_end(&self, _summary: &RoundSummary) {}
+    fn on_eliminated(&self, _player_id: &str) {}
+    fn on_sudden_death(&self, _player_ids: &[String]) {}
+    fn on_game_over(&self, _outcome: &GameOutcome) {}
+    fn on_paused(&self, _at_ms: u64) {}
+    fn on_resumed(&self, _at_ms: u64) {}
+}
+
+/// Observers registered on a game. They are not part of the game itself:
+/// they are never serialized, and are ignored by `==` and `state_hash`.
+#[derive(Clone, Default)]
+pub struct Observers(Vec<Arc<dyn GameObserver>>);
+
+impl Observers {
+    fn notify(&self, event: &GameEvent) {
+        for observer in &self.0 {
+            match event {
+                GameEvent::RoundStarted { round_number } => {
+                    observer.on_round_started(*round_number)
+                }
+                GameEvent::CardDealt { player_id, card } => {
+                    observer.on_card_dealt(player_id, *card)
+                }
+                GameEvent::CardDrawn { player_id, card } => {
+                    observer.on_card_drawn(player_id, *card)
+                }
+                GameEvent::PlayerBusted { player_id } => observer.on_bust(player_id),
+                GameEvent::PlayerStayed { player_id } => observer.on_stay(player_id),
+                GameEvent::TurnSkipped { player_id } => observer.on_turn_skipped(player_id),
+                GameEvent::TurnTimedOut { player_id } => observer.on_turn_timed_out(player_id),
+                GameEvent::PlayerEliminated { player_id } => observer.on_eliminated(player_id),
+                GameEvent::SuddenDeath { player_ids } => observer.on_sudden_death(player_ids),
+                GameEvent::GameWon {
+                    player_ids,
+                    team,
+                    reason,
+                } => observer.on_game_over(&GameOutcome {
+                    winner_ids: player_ids.clone(),
+                    team: *team,
+                    reason: *reason,
+                }),
+                GameEvent::GamePaused { at_ms } => observer.on_paused(*at_ms),
+                GameEvent::GameResumed { at_ms } => observer.on_resumed(*at_ms),
+            }
+        }
+    }
+
+    pub(crate) fn round_ended(&self, summary: &RoundSummary) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(round_number = summary.round_number, "round scored");
+        for observer in &self.0 {
+            observer.on_round_end(summary);
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+impl PartialEq for Observers {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Observers {}
+
+impl Hash for Observers {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl GameState {
+    pub fn add_observer(&mut self, observer: Arc<dyn GameObserver>) {
+        self.observers.0.push(observer);
+    }
+
+    /// Records an event in the log and tells every observer about it. With
+    /// the `tracing` feature it is traced too, inside whatever span the
+    /// caller has entered.
+    pub(crate) fn emit(&mut self, event: GameEvent) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?event, "game event");
+        self.observers.notify(&event);
+        self.events.push(event);
+    }
+}
+
+#[cfg(all(test, any(feature = "rng", feature = "serde")))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl GameObserver for Recorder {
+        fn on_card_drawn(&self, player_id: &str, card: Card) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("drawn {} {}", player_id, card.value));
+        }
+
+        fn on_stay(&self, player_id: &str) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("stay {}", player_id));
+        }
+
+        fn on_round_end(&self, summary: &RoundSummary) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("round end {}", summary.round_number));
+        }
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_observer_callbacks() {
+        let recorder = Arc::new(Recorder::default());
+        let mut game = GameState::new();
+        game.add_observer(recorder.clone());
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+
+        game.player_draw("p1").unwrap();
+        let card = game.players[0].hand.cards[2];
+        if !game.round_state.is_finished {
+            game.player_stay("p1").unwrap();
+        }
+        game.finish_round().unwrap();
+
+        let calls = recorder.calls.lock().unwrap();
+        assert_eq!(calls[0], format!("drawn p1 {}", card.value));
+        assert_eq!(calls.last().unwrap(), "round end 1");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_observers_are_not_game_state() {
+        let mut game = GameState::new();
+        let plain = game.clone();
+        game.add_observer(Arc::new(Recorder::default()));
+
+        assert_eq!(game, plain);
+        assert_eq!(game.state_hash(), plain.state_hash());
+        assert!(!game.to_json().unwrap().contains("observers"));
+    }
+}
@@ -0,0 +1,506 @@
+//! End-of-game orchestration: decides when the match is over and who won,
+//! applying `GameConfig::tiebreakers` when several players share the lead.
+
+use crate::config::Tiebreaker;
+use crate::errors;
+use crate::events::GameEvent;
+use crate::{GameState, PlayerRoundResult};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+/// Why the winner was chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum WinReason {
+    /// Sole leader once the target score was reached
+    HighestScore,
+    /// Best score in a sudden-death round among the tied players
+    SuddenDeath,
+    /// Tied on total, but played fewer rounds
+    FewestRounds,
+    /// Tied on total, but had the best single round
+    HighestSingleRound,
+    /// Tied on total, but busted the fewest times
+    FewestBusts,
+    /// Everyone else was eliminated; see `elimination`
+    LastPlayerStanding,
+    /// Stopped early with `GameState::stop`; everyone tied for the lead wins
+    Stopped,
+}
+
+/// Final result of a finished game.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct GameOutcome {
+    /// The winning player, or every member of the winning team
+    #[cfg_attr(feature = "serde", serde(alias = "winner_id", deserialize_with = "one_or_many"))]
+    pub winner_ids: Vec<String>,
+    /// Winning team in team mode
+    pub team: Option<u8>,
+    pub reason: WinReason,
+}
+
+// Saves from before team mode name a single `winner_id`, and a `player_id`
+// in `GameWon` events; those load as a list of one. Binary encodings carry
+// no field names, so only human-readable ones can be in the old form.
+#[cfg(feature = "serde")]
+pub(crate) fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    if !deserializer.is_human_readable() {
+        return Vec::deserialize(deserializer);
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(id) => vec![id],
+        OneOrMany::Many(ids) => ids,
+    })
+}
+
+/// Players who win or lose together: a team, or a single player without one.
+type Side = Vec<String>;
+
+impl GameState {
+    pub fn is_game_over(&self) -> bool {
+        self.outcome.is_some()
+            || self
+                .config
+                .round_limit
+                .is_some_and(|limit| self.history.len() as u32 >= limit)
+    }
+
+    /// Ends the game where it stands, e.g. when an operator stops a stuck
+    /// one. The round in progress, if any, is dropped unscored, and every
+    /// side tied for the highest total wins without tiebreakers.
+    pub fn stop(&mut self) -> Result<(), String> {
+        if self.is_game_over() {
+            return Err(errors::GAME_OVER.to_string());
+        }
+        let total = |game: &Self, id: &str| {
+            game.players
+                .iter()
+                .find(|p| p.id == id)
+                .map_or(0, |p| p.score)
+        };
+        let leaders = self.best_by(self.sides(), total, sum);
+        let team = match leaders.as_slice() {
+            [winners] => self.players.iter().find(|p| p.id == winners[0]).and_then(|p| p.team),
+            _ => None,
+        };
+        let winner_ids: Vec<String> = leaders.into_iter().flatten().collect();
+
+        self.round_state.is_finished = true;
+        self.round_state.is_scored = true;
+        self.round_state.turn_deadline_ms = None;
+        self.sudden_death.clear();
+        self.emit(GameEvent::GameWon {
+            player_ids: winner_ids.clone(),
+            team,
+            reason: WinReason::Stopped,
+        });
+        self.outcome = Some(GameOutcome {
+            winner_ids,
+            team,
+            reason: WinReason::Stopped,
+        });
+        Ok(())
+    }
+
+    /// Called after every scored round. Declares a winner once a player (or a
+    /// team, in team mode) has reached the target score, breaking ties for the
+    /// lead with the configured tiebreakers in order. Ties they can't settle
+    /// go to a sudden-death round.
+    pub(crate) fn resolve_outcome(&mut self) {
+        if self.config.round_limit.is_some() {
+            // Score attack has no winner, it just runs out of rounds
+            return;
+        }
+
+        let sides = self.sides();
+
+        if self.config.elimination.is_some() && sides.len() == 1 {
+            self.sudden_death.clear();
+            self.declare_or_sudden_death(sides, WinReason::LastPlayerStanding);
+            return;
+        }
+
+        if !self.sudden_death.is_empty() {
+            // Everyone plays the sudden-death round but only the tied players' scores count
+            let tied = std::mem::take(&mut self.sudden_death);
+            let contenders: Vec<Side> = sides
+                .into_iter()
+                .filter(|side| side.iter().any(|id| tied.contains(id)))
+                .collect();
+            let leaders = self.best_by(contenders, |game, id| game.last_round_score(id), sum);
+            self.declare_or_sudden_death(leaders, WinReason::SuddenDeath);
+            return;
+        }
+
+        let total = |game: &Self, id: &str| {
+            game.players
+                .iter()
+                .find(|p| p.id == id)
+                .map_or(0, |p| p.score)
+        };
+        let reached_target = sides.iter().any(|side| {
+            let totals: Vec<u32> = side.iter().map(|id| total(self, id)).collect();
+            sum(&totals) >= self.config.target_score
+        });
+        if !reached_target {
+            return;
+        }
+
+        let mut leaders = self.best_by(sides, total, sum);
+        if leaders.len() == 1 {
+            self.declare_or_sudden_death(leaders, WinReason::HighestScore);
+            return;
+        }
+
+        for tiebreaker in self.config.tiebreakers.clone() {
+            let reason = match tiebreaker {
+                Tiebreaker::SuddenDeath => break,
+                Tiebreaker::FewestRounds => {
+                    leaders = self.best_by(
+                        leaders,
+                        |game, id| game.rounds_played(id),
+                        |rounds| Reverse(max(rounds)),
+                    );
+                    WinReason::FewestRounds
+                }
+                Tiebreaker::HighestSingleRound => {
+                    leaders = self.best_by(leaders, |game, id| game.best_round_score(id), max);
+                    WinReason::HighestSingleRound
+                }
+                Tiebreaker::FewestBusts => {
+                    leaders = self.best_by(
+                        leaders,
+                        |game, id| game.bust_count(id),
+                        |busts| Reverse(sum(busts)),
+                    );
+                    WinReason::FewestBusts
+                }
+            };
+            if leaders.len() == 1 {
+                self.declare_or_sudden_death(leaders, reason);
+                return;
+            }
+        }
+        self.declare_or_sudden_death(leaders, WinReason::SuddenDeath);
+    }
+
+    /// Teams in seat order of their first member, then each teamless player
+    /// on their own. Eliminated players are left out.
+    fn sides(&self) -> Vec<Side> {
+        let mut sides: Vec<(Option<u8>, Side)> = Vec::new();
+        for player in self.active_players() {
+            match sides
+                .iter_mut()
+                .find(|(team, _)| team.is_some() && *team == player.team)
+            {
+                Some((_, side)) => side.push(player.id.clone()),
+                None => sides.push((player.team, vec![player.id.clone()])),
+            }
+        }
+        sides.into_iter().map(|(_, side)| side).collect()
+    }
+
+    fn declare_or_sudden_death(&mut self, leaders: Vec<Side>, reason: WinReason) {
+        if let [winners] = leaders.as_slice() {
+            let team = self
+                .players
+                .iter()
+                .find(|p| p.id == winners[0])
+                .and_then(|p| p.team);
+            self.emit(GameEvent::GameWon {
+                player_ids: winners.clone(),
+                team,
+                reason,
+            });
+            self.outcome = Some(GameOutcome {
+                winner_ids: winners.clone(),
+                team,
+                reason,
+            });
+        } else {
+            let tied: Vec<String> = leaders.into_iter().flatten().collect();
+            self.emit(GameEvent::SuddenDeath {
+                player_ids: tied.clone(),
+            });
+            self.sudden_death = tied;
+        }
+    }
+
+    /// Sides with the highest `key`, where a side's key combines its members'
+    /// `per_player` values.
+    fn best_by<K: Ord>(
+        &self,
+        sides: Vec<Side>,
+        per_player: impl Fn(&Self, &str) -> u32,
+        key: impl Fn(&[u32]) -> K,
+    ) -> Vec<Side> {
+        let side_key = |side: &Side| {
+            let values: Vec<u32> = side.iter().map(|id| per_player(self, id)).collect();
+            key(&values)
+        };
+        let best = sides.iter().map(side_key).max();
+        sides
+            .into_iter()
+            .filter(|side| Some(side_key(side)) == best)
+            .collect()
+    }
+
+    fn results_of<'a>(&'a self, player_id: &'a str) -> impl Iterator<Item = &'a PlayerRoundResult> {
+        self.history
+            .iter()
+            .flat_map(|round| round.players.iter())
+            .filter(move |result| result.player_id == player_id)
+    }
+
+    fn last_round_score(&self, player_id: &str) -> u32 {
+        self.history
+            .last()
+            .and_then(|round| round.players.iter().find(|r| r.player_id == player_id))
+            .map_or(0, |result| result.round_score)
+    }
+
+    fn rounds_played(&self, player_id: &str) -> u32 {
+        self.results_of(player_id).count() as u32
+    }
+
+    fn best_round_score(&self, player_id: &str) -> u32 {
+        self.results_of(player_id)
+            .map(|result| result.round_score)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn bust_count(&self, player_id: &str) -> u32 {
+        self.results_of(player_id)
+            .filter(|result| result.busted)
+            .count() as u32
+    }
+}
+
+fn sum(values: &[u32]) -> u32 {
+    values.iter().sum()
+}
+
+fn max(values: &[u32]) -> u32 {
+    values.iter().copied().max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::RoundSummary;
+
+    // Two players tied on 20 points after rounds of (12, 8) and (10, 10), the
+    // second of which Alice busted.
+    fn tied_game(tiebreaker: Tiebreaker) -> GameState {
+        let config = GameConfig {
+            target_score: 20,
+            tiebreakers: vec![tiebreaker],
+            ..GameConfig::default()
+        };
+        let mut game = GameState::new_with_config(42, config);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+
+        let result = |player_id: &str, round_score, busted| PlayerRoundResult {
+            player_id: player_id.to_string(),
+            cards: Vec::new(),
+            busted,
+            flip7_bonus: false,
+            raw_score: round_score,
+            round_score,
+            total_score: 0,
+            thinking_ms: 0,
+        };
+        game.history = vec![
+            RoundSummary {
+                round_number: 1,
+                players: vec![result("p1", 20, false), result("p2", 10, false)],
+                highlights: Vec::new(),
+                game_duration_ms: 0,
+            },
+            RoundSummary {
+                round_number: 2,
+                players: vec![result("p1", 0, true), result("p2", 10, false)],
+                highlights: Vec::new(),
+                game_duration_ms: 0,
+            },
+        ];
+        game.players[0].score = 20;
+        game.players[1].score = 20;
+        game
+    }
+
+    #[test]
+    fn test_no_winner_below_target() {
+        let mut game = tied_game(Tiebreaker::SuddenDeath);
+        game.config.target_score = 21;
+        game.resolve_outcome();
+        assert!(!game.is_game_over());
+        assert!(game.events.is_empty());
+    }
+
+    #[test]
+    fn test_sole_leader_wins() {
+        let mut game = tied_game(Tiebreaker::SuddenDeath);
+        game.players[1].score = 25;
+        game.resolve_outcome();
+        assert_eq!(
+            game.outcome,
+            Some(GameOutcome {
+                winner_ids: vec!["p2".to_string()],
+                team: None,
+                reason: WinReason::HighestScore
+            })
+        );
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_stop() {
+        let mut game = tied_game(Tiebreaker::SuddenDeath);
+        game.config.target_score = 100;
+        game.start_round().unwrap();
+        game.stop().unwrap();
+        assert!(game.is_game_over());
+        assert!(game.player_draw("p1").is_err());
+        assert_eq!(
+            game.outcome,
+            Some(GameOutcome {
+                winner_ids: vec!["p1".to_string(), "p2".to_string()],
+                team: None,
+                reason: WinReason::Stopped
+            })
+        );
+        assert!(game.stop().is_err());
+    }
+
+    #[test]
+    fn test_tiebreakers() {
+        let mut game = tied_game(Tiebreaker::HighestSingleRound);
+        game.resolve_outcome();
+        assert_eq!(
+            game.events.last(),
+            Some(&GameEvent::GameWon {
+                player_ids: vec!["p1".to_string()],
+                team: None,
+                reason: WinReason::HighestSingleRound
+            })
+        );
+
+        let mut game = tied_game(Tiebreaker::FewestBusts);
+        game.resolve_outcome();
+        assert_eq!(
+            game.events.last(),
+            Some(&GameEvent::GameWon {
+                player_ids: vec!["p2".to_string()],
+                team: None,
+                reason: WinReason::FewestBusts
+            })
+        );
+    }
+
+    #[test]
+    fn test_fewest_rounds_tiebreaker() {
+        // Bob joined for the second round only and got to 20 just the same
+        let mut game = tied_game(Tiebreaker::SuddenDeath);
+        game.config.tiebreakers = crate::config::default_tiebreakers();
+        game.history[0].players.retain(|r| r.player_id == "p1");
+        game.resolve_outcome();
+        assert_eq!(
+            game.outcome.map(|o| (o.winner_ids, o.reason)),
+            Some((vec!["p2".to_string()], WinReason::FewestRounds))
+        );
+    }
+
+    #[test]
+    fn test_tiebreakers_apply_in_order() {
+        // Same number of rounds, so the chain moves on to the best round
+        let mut game = tied_game(Tiebreaker::FewestRounds);
+        game.config.tiebreakers.push(Tiebreaker::HighestSingleRound);
+        game.resolve_outcome();
+        assert_eq!(
+            game.outcome.map(|o| (o.winner_ids, o.reason)),
+            Some((vec!["p1".to_string()], WinReason::HighestSingleRound))
+        );
+
+        // A tie the whole chain can't break goes to sudden death
+        let mut game = tied_game(Tiebreaker::FewestRounds);
+        game.resolve_outcome();
+        assert!(game.outcome.is_none());
+        assert_eq!(game.sudden_death.len(), 2);
+
+        // Sudden death ends the chain even with rules after it
+        let mut game = tied_game(Tiebreaker::SuddenDeath);
+        game.config.tiebreakers.push(Tiebreaker::FewestBusts);
+        game.resolve_outcome();
+        assert!(game.outcome.is_none());
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_sudden_death() {
+        let mut game = tied_game(Tiebreaker::SuddenDeath);
+        game.resolve_outcome();
+        assert!(!game.is_game_over());
+        assert_eq!(game.sudden_death, vec!["p1".to_string(), "p2".to_string()]);
+
+        // Both stay on their dealt cards: Alice has 6, Bob 18
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+        game.player_stay("p2").unwrap();
+        game.finish_round().unwrap();
+
+        assert_eq!(
+            game.outcome,
+            Some(GameOutcome {
+                winner_ids: vec!["p2".to_string()],
+                team: None,
+                reason: WinReason::SuddenDeath
+            })
+        );
+        assert!(game.sudden_death.is_empty());
+        assert!(game.start_round().is_err());
+    }
+
+    #[test]
+    fn test_team_reaching_target_wins() {
+        let config = GameConfig {
+            target_score: 30,
+            tiebreakers: vec![Tiebreaker::SuddenDeath],
+            ..GameConfig::default()
+        };
+        let mut game = GameState::new_with_config(42, config);
+        for (id, team) in [("p1", 1), ("p2", 2), ("p3", 1), ("p4", 2)] {
+            game.add_player(id.to_string(), id.to_string());
+            game.set_team(id, Some(team)).unwrap();
+        }
+        // Nobody reached 30 alone, but team 2 did together
+        for (player, score) in game.players.iter_mut().zip([20, 18, 9, 14]) {
+            player.score = score;
+        }
+
+        game.resolve_outcome();
+        assert_eq!(
+            game.outcome,
+            Some(GameOutcome {
+                winner_ids: vec!["p2".to_string(), "p4".to_string()],
+                team: Some(2),
+                reason: WinReason::HighestScore
+            })
+        );
+        assert_eq!(game.team_scores()[&1], 29);
+    }
+}
@@ -0,0 +1,157 @@
+//! Pausing a game, e.g. while a disconnected player reconnects. A paused game
+//! rejects every move; the periods it spent paused are kept so a server can
+//! take them off its turn clock.
+//!
+//! The engine has no clock of its own: callers pass the current time as
+//! milliseconds since the Unix epoch.
+
+use crate::errors;
+use crate::events::GameEvent;
+use crate::GameState;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where the game is, as far as what may happen next is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GamePhase {
+    /// A round is being played
+    InRound,
+    /// Waiting for the next round to be started
+    BetweenRounds,
+    /// Paused; nothing can happen until the game is resumed
+    Paused,
+    GameOver,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct PausePeriod {
+    pub paused_at_ms: u64,
+    /// `None` while the game is still paused
+    pub resumed_at_ms: Option<u64>,
+}
+
+impl GameState {
+    pub fn phase(&self) -> GamePhase {
+        if self.is_paused() {
+            GamePhase::Paused
+        } else if self.is_game_over() {
+            GamePhase::GameOver
+        } else if self.round_state.is_finished {
+            GamePhase::BetweenRounds
+        } else {
+            GamePhase::InRound
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pauses
+            .last()
+            .is_some_and(|period| period.resumed_at_ms.is_none())
+    }
+
+    pub fn pause(&mut self, now_ms: u64) -> Result<(), String> {
+        if self.is_paused() {
+            return Err(errors::GAME_ALREADY_PAUSED.to_string());
+        }
+        if self.is_game_over() {
+            return Err(errors::GAME_OVER.to_string());
+        }
+
+        self.pauses.push(PausePeriod {
+            paused_at_ms: now_ms,
+            resumed_at_ms: None,
+        });
+        self.emit(GameEvent::GamePaused { at_ms: now_ms });
+        Ok(())
+    }
+
+    pub fn resume(&mut self, now_ms: u64) -> Result<(), String> {
+        let Some(period) = self.pauses.last_mut() else {
+            return Err(errors::GAME_NOT_PAUSED.to_string());
+        };
+        if period.resumed_at_ms.is_some() {
+            return Err(errors::GAME_NOT_PAUSED.to_string());
+        }
+        if now_ms < period.paused_at_ms {
+            return Err(errors::RESUME_UNPAUSED.to_string());
+        }
+
+        period.resumed_at_ms = Some(now_ms);
+        // The pause doesn't count against the player whose turn it was
+        let paused = now_ms - period.paused_at_ms;
+        if let Some(deadline) = &mut self.round_state.turn_deadline_ms {
+            *deadline += paused;
+        }
+        self.emit(GameEvent::GameResumed { at_ms: now_ms });
+        Ok(())
+    }
+
+    /// Total time spent paused since `since_ms`, counting a pause that is
+    /// still running up to `now_ms`.
+    pub fn paused_ms_since(&self, since_ms: u64, now_ms: u64) -> u64 {
+        self.pauses
+            .iter()
+            .map(|period| {
+                let start = period.paused_at_ms.max(since_ms);
+                let end = period.resumed_at_ms.unwrap_or(now_ms).min(now_ms);
+                end.saturating_sub(start)
+            })
+            .sum()
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    fn started_game() -> GameState {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        game
+    }
+
+    #[test]
+    fn test_paused_game_rejects_moves() {
+        let mut game = started_game();
+        game.pause(1_000).unwrap();
+
+        assert_eq!(game.phase(), GamePhase::Paused);
+        assert_eq!(game.player_draw("p1"), Err("Game is paused".to_string()));
+        assert_eq!(game.player_stay("p1"), Err("Game is paused".to_string()));
+        assert_eq!(game.pause(2_000), Err("Game is already paused".to_string()));
+
+        game.resume(5_000).unwrap();
+        assert_eq!(game.phase(), GamePhase::InRound);
+        assert!(game.player_stay("p1").is_ok());
+        assert_eq!(game.resume(6_000), Err("Game is not paused".to_string()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_pause_periods() {
+        let mut game = started_game();
+        game.pause(1_000).unwrap();
+        game.resume(4_000).unwrap();
+        game.pause(10_000).unwrap();
+
+        assert_eq!(game.paused_ms_since(0, 12_000), 5_000);
+        assert_eq!(game.paused_ms_since(2_000, 12_000), 4_000);
+
+        let restored = GameState::from_json(&game.to_json().unwrap()).unwrap();
+        assert!(restored.is_paused());
+        assert_eq!(restored.pauses, game.pauses);
+        assert_eq!(
+            &game.events[game.events.len() - 3..],
+            &[
+                GameEvent::GamePaused { at_ms: 1_000 },
+                GameEvent::GameResumed { at_ms: 4_000 },
+                GameEvent::GamePaused { at_ms: 10_000 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Bot personas: a `BotConfig` describes how a computer player behaves, and
+//! `Bot::from_config` picks and tunes the strategy behind it, so frontends
+//! can offer difficulties without knowing how the bots work.
+
+use crate::bots::{AlwaysStayAt, Budget, MonteCarlo, PlayerView, Strategy, ThresholdProbability};
+use crate::GameMove;
+
+/// How well a bot plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difficulty {
+    /// Stays at a fixed hand total, whatever is left in the deck
+    Easy,
+    /// Draws while the odds of busting are acceptable
+    Medium,
+    /// Searches with a `MonteCarlo` rollout
+    Hard,
+}
+
+/// Personality of a bot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotConfig {
+    pub difficulty: Difficulty,
+    /// From 0 to 1: how readily the bot draws. 0.5 is neutral for a Hard
+    /// bot, which then only draws when it expects to gain from it.
+    pub risk_tolerance: f64,
+    /// From 0 to 1: how much more risk the bot takes the further it trails
+    /// the leader, relative to the target score.
+    pub target_aggression: f64,
+}
+
+impl BotConfig {
+    pub fn easy() -> Self {
+        Self {
+            difficulty: Difficulty::Easy,
+            risk_tolerance: 0.5,
+            target_aggression: 0.0,
+        }
+    }
+
+    pub fn medium() -> Self {
+        Self {
+            difficulty: Difficulty::Medium,
+            risk_tolerance: 0.3,
+            target_aggression: 0.5,
+        }
+    }
+
+    pub fn hard() -> Self {
+        Self {
+            difficulty: Difficulty::Hard,
+            risk_tolerance: 0.5,
+            target_aggression: 0.5,
+        }
+    }
+
+    /// Draws into long odds, and more so when behind.
+    pub fn reckless() -> Self {
+        Self {
+            difficulty: Difficulty::Medium,
+            risk_tolerance: 0.6,
+            target_aggression: 1.0,
+        }
+    }
+
+    /// The preset with the given name: easy, medium, hard or reckless.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Self::easy()),
+            "medium" => Some(Self::medium()),
+            "hard" => Some(Self::hard()),
+            "reckless" => Some(Self::reckless()),
+            _ => None,
+        }
+    }
+}
+
+/// A bot playing as a `BotConfig` describes.
+#[derive(Debug, Clone)]
+pub struct Bot {
+    config: BotConfig,
+    search: MonteCarlo,
+}
+
+impl Bot {
+    /// Rollouts per move of a Hard bot.
+    pub const HARD_ROLLOUTS: u32 = 1000;
+
+    pub fn from_config(config: BotConfig) -> Self {
+        Self {
+            config,
+            search: MonteCarlo::new(Budget::Rollouts(Self::HARD_ROLLOUTS), 0),
+        }
+    }
+
+    pub fn config(&self) -> &BotConfig {
+        &self.config
+    }
+
+    /// Risk tolerance on this turn, raised by how far behind the bot is.
+    fn risk(&self, view: &PlayerView) -> f64 {
+        let leader = view.opponents.iter().map(|p| p.score).max().unwrap_or(0);
+        let behind = leader.saturating_sub(view.score) as f64 / view.target_score.max(1) as f64;
+        (self.config.risk_tolerance + self.config.target_aggression * behind.min(1.0))
+            .clamp(0.0, 1.0)
+    }
+}
+
+impl Strategy for Bot {
+    fn choose(&mut self, view: &PlayerView, legal: &[GameMove]) -> GameMove {
+        let risk = self.risk(view);
+        match self.config.difficulty {
+            // A neutral bot stays at 15; at full risk it draws up to 20
+            Difficulty::Easy => AlwaysStayAt(10 + (risk * 10.0).round() as u8).choose(view, legal),
+            Difficulty::Medium => ThresholdProbability(risk).choose(view, legal),
+            Difficulty::Hard => {
+                let stay = legal.iter().find(|m| matches!(m, GameMove::Stay { .. }));
+                let draw = legal.iter().find(|m| matches!(m, GameMove::Draw { .. }));
+                let (Some(stay), Some(draw)) = (stay, draw) else {
+                    return legal[0].clone();
+                };
+                if view.score + view.stay_value() >= view.target_score {
+                    return stay.clone();
+                }
+
+                // Risk scales the rollouts' draw value from half to one and a half times
+                let draw_value = self.search.draw_value(view) * (0.5 + risk);
+                if view.bust_probability() == 0.0 || draw_value > view.stay_value() as f64 {
+                    draw.clone()
+                } else {
+                    stay.clone()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Card, GameState, Hand};
+
+    fn view(hand: &[u8], score: u32, leader_score: u32) -> (PlayerView, Vec<GameMove>) {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        game.players[0].hand = Hand::new();
+        for value in hand {
+            game.players[0].hand.add_card(Card::new(*value));
+        }
+        game.players[0].score = score;
+        game.players[1].score = leader_score;
+        (game.player_view("p1").unwrap(), game.legal_moves("p1"))
+    }
+
+    fn draws(config: BotConfig, hand: &[u8], score: u32, leader_score: u32) -> bool {
+        let (view, legal) = view(hand, score, leader_score);
+        matches!(
+            Bot::from_config(config).choose(&view, &legal),
+            GameMove::Draw { .. }
+        )
+    }
+
+    #[test]
+    fn test_presets() {
+        for name in ["easy", "medium", "hard", "reckless"] {
+            assert!(BotConfig::preset(name).is_some());
+        }
+        assert!(BotConfig::preset("godlike").is_none());
+
+        // On 13 about half the deck busts: the easy bot draws regardless,
+        // the medium bot stays and the reckless one pushes on
+        assert!(draws(BotConfig::easy(), &[8, 5], 0, 0));
+        assert!(!draws(BotConfig::medium(), &[8, 5], 0, 0));
+        assert!(draws(BotConfig::reckless(), &[8, 5], 0, 0));
+        assert!(!draws(BotConfig::hard(), &[12, 8], 0, 0));
+    }
+
+    #[test]
+    fn test_aggression_when_behind() {
+        let calm = BotConfig {
+            target_aggression: 0.0,
+            ..BotConfig::easy()
+        };
+        let aggressive = BotConfig {
+            target_aggression: 1.0,
+            ..BotConfig::easy()
+        };
+        // Stays at 15 when level, but 100 points behind draws up to 20
+        assert!(!draws(aggressive, &[10, 7], 50, 50));
+        assert!(!draws(calm, &[10, 7], 50, 150));
+        assert!(draws(aggressive, &[10, 7], 50, 150));
+    }
+}
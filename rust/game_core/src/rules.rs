@@ -0,0 +1,106 @@
+use crate::Hand;
+#[cfg(feature = "serde")]
+use serde::{de::Error, Deserialize, Deserializer};
+
+/// Version of the scoring rules implemented by this engine.
+///
+/// Bump this whenever a change alters how a hand is scored, and add the new
+/// behavior to `behavior_for` so games recorded under older versions keep
+/// scoring the way they did when they were played.
+pub const ENGINE_RULES_VERSION: u32 = 1;
+
+/// Scoring-affecting behavior of one rules version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleBehavior {
+    pub bust_threshold: u8,
+    pub flip7_target: u8,
+    pub flip7_bonus: u32,
+}
+
+impl RuleBehavior {
+    pub fn is_bust(&self, hand: &Hand) -> bool {
+        hand.total_value() > self.bust_threshold
+    }
+
+    pub fn has_flip7(&self, hand: &Hand) -> bool {
+        hand.can_sum_to(self.flip7_target)
+    }
+
+    pub fn score_hand(&self, hand: &Hand) -> u32 {
+        if self.has_flip7(hand) {
+            self.flip7_bonus
+        } else if self.is_bust(hand) {
+            0
+        } else {
+            hand.total_value() as u32
+        }
+    }
+}
+
+/// Returns the behavior of the given rules version, or `None` if this engine
+/// does not know it (e.g. a save written by a newer engine).
+pub fn behavior_for(version: u32) -> Option<RuleBehavior> {
+    match version {
+        1 => Some(RuleBehavior {
+            bust_threshold: 21,
+            flip7_target: 7,
+            flip7_bonus: 21,
+        }),
+        _ => None,
+    }
+}
+
+/// Rejects rules versions this engine cannot score, so an unknown version never
+/// makes it into a `GameState`.
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_version<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if behavior_for(version).is_none() {
+        return Err(D::Error::custom(format!(
+            "unsupported engine rules version {}",
+            version
+        )));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Card;
+    #[cfg(feature = "serde")]
+    use crate::GameState;
+
+    #[test]
+    fn test_current_version_is_known() {
+        assert!(behavior_for(ENGINE_RULES_VERSION).is_some());
+        assert!(behavior_for(ENGINE_RULES_VERSION + 1).is_none());
+    }
+
+    #[test]
+    fn test_v1_scoring() {
+        let rules = behavior_for(1).unwrap();
+
+        let mut flip7 = Hand::new();
+        flip7.add_card(Card::new(3));
+        flip7.add_card(Card::new(4));
+        assert_eq!(rules.score_hand(&flip7), 21);
+
+        let mut bust = Hand::new();
+        bust.add_card(Card::new(12));
+        bust.add_card(Card::new(11));
+        assert_eq!(rules.score_hand(&bust), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_unknown_version_rejected_on_load() {
+        let mut game = GameState::new();
+        game.engine_rules_version = ENGINE_RULES_VERSION + 1;
+        let json = game.to_json().unwrap();
+
+        assert!(GameState::from_json(&json).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+#[cfg(feature = "serde")]
+use crate::theme::SeatTheme;
+#[cfg(feature = "serde")]
+use serde_json::{json, Value};
+
+/// Version of the serialized `GameState` layout.
+///
+/// Bump this whenever a change would stop older saves from deserializing (or
+/// would load them with the wrong meaning), add a `migrate_vN` step below and
+/// check in a fixture of the old format under `fixtures/`.
+pub const CURRENT_SCHEMA_VERSION: u32 = 4;
+
+/// Upgrades a serialized game in place to `CURRENT_SCHEMA_VERSION`.
+///
+/// Saves written before versioning existed have no `schema_version` field and
+/// are treated as version 0.
+#[cfg(feature = "serde")]
+pub fn migrate(value: &mut Value) -> Result<(), String> {
+    let mut version = match value.get("schema_version") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .ok_or("schema_version must be a non-negative integer")? as u32,
+    };
+
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "Save uses schema version {} but this engine only supports up to {}",
+            version, CURRENT_SCHEMA_VERSION
+        ));
+    }
+
+    while version < CURRENT_SCHEMA_VERSION {
+        match version {
+            0 => migrate_v0(value)?,
+            1 => migrate_v1(value)?,
+            2 => migrate_v2(value)?,
+            3 => migrate_v3(value)?,
+            _ => unreachable!("missing migration for schema version {}", version),
+        }
+        version += 1;
+        value["schema_version"] = json!(version);
+    }
+
+    Ok(())
+}
+
+// v0 -> v1: rules version, history and event log were added, and rounds track
+// whether they were scored. v0 tools always scored a round as soon as it
+// finished, so a finished round is an already-scored one.
+#[cfg(feature = "serde")]
+fn migrate_v0(value: &mut Value) -> Result<(), String> {
+    let game = value
+        .as_object_mut()
+        .ok_or("Game state must be a JSON object")?;
+
+    game.entry("engine_rules_version").or_insert(json!(1));
+    game.entry("history").or_insert(json!([]));
+    game.entry("events").or_insert(json!([]));
+
+    let round_state = game
+        .get_mut("round_state")
+        .and_then(Value::as_object_mut)
+        .ok_or("Game state is missing round_state")?;
+    let is_finished = round_state
+        .get("is_finished")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    round_state.entry("is_scored").or_insert(json!(is_finished));
+
+    Ok(())
+}
+
+// v1 -> v2: players got a seat theme. Hand them out in seat order, as
+// `add_player` would have.
+#[cfg(feature = "serde")]
+fn migrate_v1(value: &mut Value) -> Result<(), String> {
+    let players = value
+        .get_mut("players")
+        .and_then(Value::as_array_mut)
+        .ok_or("Game state is missing players")?;
+
+    for (seat, player) in players.iter_mut().enumerate() {
+        let player = player
+            .as_object_mut()
+            .ok_or("Player must be a JSON object")?;
+        let theme = serde_json::to_value(SeatTheme::for_seat(seat)).map_err(|e| e.to_string())?;
+        player.entry("theme").or_insert(theme);
+    }
+
+    Ok(())
+}
+
+// v2 -> v3: the single `tiebreaker` became an ordered list of them. Ties it
+// couldn't break already went to sudden death, so the list is just that one.
+#[cfg(feature = "serde")]
+fn migrate_v2(value: &mut Value) -> Result<(), String> {
+    let Some(config) = value.get_mut("config") else {
+        // Saves from before configs existed load with the default one
+        return Ok(());
+    };
+    let config = config
+        .as_object_mut()
+        .ok_or("Game config must be a JSON object")?;
+
+    if let Some(tiebreaker) = config.remove("tiebreaker") {
+        config.insert("tiebreakers".to_string(), json!([tiebreaker]));
+    }
+
+    Ok(())
+}
+
+// v3 -> v4: round results record the raw hand score next to the
+// handicapped one. There were no handicaps before, so they are the same.
+#[cfg(feature = "serde")]
+fn migrate_v3(value: &mut Value) -> Result<(), String> {
+    let Some(history) = value.get_mut("history").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+
+    for round in history {
+        let results = round
+            .get_mut("players")
+            .and_then(Value::as_array_mut)
+            .ok_or("Round summary is missing players")?;
+        for result in results {
+            let result = result
+                .as_object_mut()
+                .ok_or("Round result must be a JSON object")?;
+            let round_score = result.get("round_score").cloned().unwrap_or(json!(0));
+            result.entry("raw_score").or_insert(round_score);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::config::Tiebreaker;
+    use crate::GameState;
+
+    // Golden saves of every past schema version; they must keep loading.
+    const V0_SAVE: &str = include_str!("../fixtures/game_state_v0.json");
+    const V1_SAVE: &str = include_str!("../fixtures/game_state_v1.json");
+    const V2_SAVE: &str = include_str!("../fixtures/game_state_v2.json");
+    const V3_SAVE: &str = include_str!("../fixtures/game_state_v3.json");
+    // A game won before team mode, when it had a single winner
+    const V2_WON_SAVE: &str = include_str!("../fixtures/game_state_v2_won.json");
+
+    #[test]
+    fn test_v0_save_loads() {
+        let game = GameState::from_json(V0_SAVE).unwrap();
+
+        assert_eq!(game.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(game.players.len(), 3);
+        assert_eq!(game.players[1].score, 18);
+        // The round was scored by the tool that wrote the save
+        assert!(game.round_state.is_scored);
+    }
+
+    #[test]
+    fn test_v1_save_loads() {
+        let game = GameState::from_json(V1_SAVE).unwrap();
+
+        assert_eq!(game.players.len(), 2);
+        assert_eq!(game.history.len(), 1);
+        assert!(!game.events.is_empty());
+        assert_eq!(game.players[1].theme, SeatTheme::for_seat(1));
+    }
+
+    #[test]
+    fn test_v2_save_loads() {
+        let game = GameState::from_json(V2_SAVE).unwrap();
+
+        assert_eq!(game.config.target_score, 150);
+        assert_eq!(game.config.tiebreakers, vec![Tiebreaker::FewestBusts]);
+        assert_eq!(game.history.len(), 1);
+    }
+
+    #[test]
+    fn test_v2_won_save_loads() {
+        let game = GameState::from_json(V2_WON_SAVE).unwrap();
+
+        let outcome = game.outcome.as_ref().unwrap();
+        assert_eq!(outcome.winner_ids, vec!["1".to_string()]);
+        assert_eq!(outcome.team, None);
+        assert_eq!(
+            game.events.last(),
+            Some(&crate::events::GameEvent::GameWon {
+                player_ids: vec!["1".to_string()],
+                team: None,
+                reason: outcome.reason,
+            })
+        );
+    }
+
+    #[test]
+    fn test_v3_save_loads() {
+        let game = GameState::from_json(V3_SAVE).unwrap();
+
+        let first = &game.history[0].players[0];
+        assert_eq!((first.raw_score, first.round_score), (19, 19));
+        assert!(game.config.handicaps.is_empty());
+        assert_eq!(game.config.elimination.as_ref().unwrap().points_behind, Some(60));
+    }
+
+    #[test]
+    fn test_future_schema_rejected() {
+        let mut game = GameState::new();
+        game.schema_version = CURRENT_SCHEMA_VERSION + 1;
+
+        assert!(GameState::from_json(&game.to_json().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Pluggable hand scoring, so variants can score hands their own way without
+//! touching `finish_round`. Games use `OfficialScoring` unless told otherwise.
+
+use crate::rules::RuleBehavior;
+use crate::{GameState, Hand};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// What a strategy knows about the hand it is scoring.
+#[derive(Debug, Clone, Copy)]
+pub struct RoundContext<'a> {
+    pub round_number: u32,
+    pub player_id: &'a str,
+    /// Behavior of the rules version the game is played under
+    pub rules: RuleBehavior,
+}
+
+/// Scores a player's hand at the end of a round, before any handicap.
+pub trait ScoringStrategy: Send + Sync {
+    fn score_hand(&self, hand: &Hand, ctx: &RoundContext) -> u32;
+}
+
+/// The published rules: 21 points for a Flip 7, nothing for a bust,
+/// otherwise the hand's total.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OfficialScoring;
+
+impl ScoringStrategy for OfficialScoring {
+    fn score_hand(&self, hand: &Hand, ctx: &RoundContext) -> u32 {
+        ctx.rules.score_hand(hand)
+    }
+}
+
+/// The strategy a game is scored with. Like observers, it is not part of the
+/// game state: it is never serialized and is ignored by `==` and `state_hash`,
+/// so embedders set it again after loading a save.
+#[derive(Clone)]
+pub struct Scoring(Arc<dyn ScoringStrategy>);
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self(Arc::new(OfficialScoring))
+    }
+}
+
+impl fmt::Debug for Scoring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scoring")
+    }
+}
+
+impl PartialEq for Scoring {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Scoring {}
+
+impl Hash for Scoring {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl GameState {
+    /// Scores rounds with `strategy` from now on.
+    pub fn set_scoring(&mut self, strategy: Arc<dyn ScoringStrategy>) {
+        self.scoring = Scoring(strategy);
+    }
+
+    /// Score of a player's current hand under the game's strategy.
+    pub(crate) fn score_hand(&self, player_id: &str, hand: &Hand) -> u32 {
+        let ctx = RoundContext {
+            round_number: self.round_state.round_number,
+            player_id,
+            rules: self.rules(),
+        };
+        self.scoring.0.score_hand(hand, &ctx)
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    // Busting costs nothing: every hand scores its total, capped at 21.
+    struct Forgiving;
+
+    impl ScoringStrategy for Forgiving {
+        fn score_hand(&self, hand: &Hand, _ctx: &RoundContext) -> u32 {
+            (hand.total_value() as u32).min(21)
+        }
+    }
+
+    #[test]
+    fn test_custom_scoring_strategy() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.set_scoring(Arc::new(Forgiving));
+        game.start_round().unwrap();
+
+        // Bob's dealt 6 and 12, then draws into a bust
+        game.player_stay("p1").unwrap();
+        while !game.round_state.is_finished {
+            game.player_draw("p2").unwrap();
+        }
+        let summary = game.finish_round().unwrap();
+
+        let bob = &summary.players[1];
+        assert!(bob.busted);
+        assert_eq!(bob.round_score, 21);
+
+        // The strategy is not part of the state
+        let mut official = game.clone();
+        official.scoring = Scoring::default();
+        assert_eq!(game.state_hash(), official.state_hash());
+    }
+}
@@ -0,0 +1,160 @@
+//! Where deck shuffles come from. With the `rng` feature every round is
+//! shuffled by a ChaCha8 generator seeded from the game seed, so seeded games
+//! replay exactly. Builds without it, e.g. a server that gets its shuffles
+//! from elsewhere, hand `start_round_with` their own `ShuffleSource`.
+
+use crate::{Card, Deck, GameState};
+
+/// Puts a freshly built deck in random order before a round is dealt.
+/// Cards are drawn from the end of the slice.
+pub trait ShuffleSource {
+    fn shuffle(&mut self, cards: &mut [Card]);
+}
+
+/// Fisher-Yates, the shuffle seeded games have always been dealt with.
+///
+/// A seed deals the same order on every platform, 32 or 64 bit: replays and
+/// deck commitments are checked on other machines than the one that dealt.
+/// ChaCha8 is defined byte for byte and every draw is reduced as a `u32`
+/// before becoming an index, so nothing depends on `usize` or endianness.
+/// `test_shuffle_vectors` pins the orders; CI runs it on ARM and wasm32 too.
+#[cfg(feature = "rng")]
+impl ShuffleSource for rand_chacha::ChaCha8Rng {
+    fn shuffle(&mut self, cards: &mut [Card]) {
+        use rand_chacha::rand_core::RngCore;
+
+        for i in (1..cards.len()).rev() {
+            let j = (self.next_u32() % (i as u32 + 1)) as usize;
+            cards.swap(i, j);
+        }
+    }
+}
+
+impl Deck {
+    pub fn shuffle_with(&mut self, source: &mut dyn ShuffleSource) {
+        source.shuffle(&mut self.cards);
+    }
+}
+
+impl GameState {
+    /// Starts the next round, shuffling its deck with the game's own seeded
+    /// generator; see `start_round_with`.
+    #[cfg(feature = "rng")]
+    pub fn start_round(&mut self) -> Result<(), String> {
+        use rand_chacha::rand_core::SeedableRng;
+
+        let seed = self.seed.wrapping_add(self.round_state.round_number as u64);
+        self.start_round_with(&mut rand_chacha::ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deals the deck in the order it was built, like a server that has
+    // already shuffled the cards it hands over.
+    struct NoShuffle;
+
+    impl ShuffleSource for NoShuffle {
+        fn shuffle(&mut self, _cards: &mut [Card]) {}
+    }
+
+    #[test]
+    fn test_injected_shuffle() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round_with(&mut NoShuffle).unwrap();
+
+        // The standard deck is laid out 1..=12 then 0, and dealt from the end
+        assert_eq!(game.players[0].hand.to_string(), "[0 12] = 12");
+        assert_eq!(game.check_invariants(), Ok(()));
+    }
+
+    // Deals the deck back to front, lowest cards first.
+    struct Reverse;
+
+    impl ShuffleSource for Reverse {
+        fn shuffle(&mut self, cards: &mut [Card]) {
+            cards.reverse();
+        }
+    }
+
+    // Runs in builds without `rng` too, where a source is the only way to
+    // shuffle; CI tests game_core with no default features.
+    #[test]
+    fn test_rounds_dealt_by_a_source() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        for round in 1..=2 {
+            game.start_round_with(&mut Reverse).unwrap();
+            // The one 1, then the two 2s, then a 3, dealt in turn
+            assert_eq!(game.players[0].hand.to_string(), "[1 2] = 3");
+            assert_eq!(game.players[1].hand.to_string(), "[2 3] = 5");
+            game.player_stay("p1").unwrap();
+            game.player_stay("p2").unwrap();
+            game.finish_round().unwrap();
+            assert_eq!(game.players[1].score, 5 * round);
+        }
+        assert_eq!(game.check_invariants(), Ok(()));
+    }
+
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_seeded_shuffle_matches_deck_shuffle() {
+        let mut deck = Deck::new(9);
+        deck.shuffle();
+
+        let mut game = GameState::new_with_seed(8);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+
+        // Round 1 of a seed 8 game is shuffled from seed 9
+        let dealt = game.players[0].hand.cards.len();
+        assert_eq!(game.deck.cards, deck.cards[..deck.len() - dealt]);
+    }
+
+    // Reference orders of `Deck::new(seed).shuffle()`. Never update these to
+    // make the test pass: a change here breaks every recorded game.
+    #[cfg(feature = "rng")]
+    #[test]
+    fn test_shuffle_vectors() {
+        let vectors: [(u64, [u8; 79]); 3] = [
+            (
+                0,
+                [
+                    12, 7, 12, 12, 6, 7, 10, 6, 4, 6, 10, 11, 9, 10, 10, 8, 5, 10, 10, 9, 12, 11,
+                    8, 6, 8, 4, 7, 9, 12, 12, 11, 5, 8, 11, 8, 12, 8, 6, 9, 11, 7, 3, 3, 6, 10, 7,
+                    5, 4, 10, 11, 9, 5, 9, 8, 12, 12, 7, 8, 11, 9, 1, 3, 9, 11, 9, 12, 12, 12, 10,
+                    5, 2, 11, 11, 0, 2, 7, 11, 4, 10,
+                ],
+            ),
+            (
+                42,
+                [
+                    6, 1, 9, 9, 9, 12, 10, 2, 3, 4, 5, 8, 0, 10, 9, 10, 10, 12, 8, 10, 6, 3, 8, 8,
+                    6, 12, 8, 11, 10, 5, 11, 12, 7, 9, 7, 12, 6, 10, 11, 7, 7, 8, 11, 4, 9, 9, 11,
+                    11, 8, 11, 11, 5, 11, 10, 12, 12, 7, 10, 12, 7, 7, 8, 9, 5, 9, 4, 12, 12, 12,
+                    2, 10, 11, 3, 5, 6, 6, 4, 12, 11,
+                ],
+            ),
+            (
+                u64::MAX,
+                [
+                    5, 8, 10, 4, 10, 5, 6, 7, 7, 4, 2, 11, 10, 12, 3, 12, 6, 2, 4, 6, 10, 12, 12,
+                    11, 7, 8, 10, 10, 12, 9, 11, 5, 12, 8, 11, 12, 8, 8, 10, 9, 12, 4, 7, 6, 11, 8,
+                    3, 11, 8, 11, 6, 10, 9, 11, 11, 5, 5, 10, 9, 9, 7, 7, 11, 6, 9, 8, 1, 0, 12, 9,
+                    7, 11, 3, 10, 12, 9, 12, 12, 9,
+                ],
+            ),
+        ];
+
+        for (seed, expected) in vectors {
+            let mut deck = Deck::new(seed);
+            deck.shuffle();
+            let order: Vec<u8> = deck.cards.iter().map(|card| card.value()).collect();
+            assert_eq!(order, expected, "seed {}", seed);
+        }
+    }
+}
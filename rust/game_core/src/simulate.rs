@@ -0,0 +1,462 @@
+//! Batch simulation for balance testing: plays many seeded games between
+//! bots across every core and aggregates how each seat did.
+//!
+//! Games are seeded `seed`, `seed + 1`, ... and the stats don't depend on how
+//! rayon schedules them, so a batch is reproducible from its seed.
+//!
+//! `record_batch` also keeps every decision the bots made, for training a
+//! policy outside the engine; `write_samples` exports them.
+
+use crate::advisor::Action;
+use crate::bots::{Features, Strategy};
+use crate::config::GameConfig;
+use crate::events::GameEvent;
+use crate::{GameMove, GameState};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+/// Rounds after which a simulated game is abandoned, for rules whose target
+/// score bots never reach.
+pub const MAX_ROUNDS: u32 = 500;
+
+/// Aggregated results of a batch. Per-seat vectors are in the order the bots
+/// were given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchStats {
+    pub games: u32,
+    /// Games abandoned after `MAX_ROUNDS` rounds; they count as nobody's win
+    pub unfinished: u32,
+    /// Share of games each seat won
+    pub win_rates: Vec<f64>,
+    /// Final score of each seat, averaged over games
+    pub average_scores: Vec<f64>,
+    /// Share of the rounds each seat played that it busted in
+    pub bust_rates: Vec<f64>,
+    /// Rounds per game, averaged
+    pub average_rounds: f64,
+    /// Final score of the winner, averaged over finished games
+    pub average_winning_score: f64,
+    /// Share of the hands played that scored a Flip 7
+    pub flip7_rate: f64,
+    /// Share of draws that busted, by the number of cards held before drawing
+    pub bust_rate_by_hand_size: BTreeMap<usize, f64>,
+    /// Draws made, by the number of cards held before drawing
+    pub draws_by_hand_size: BTreeMap<usize, u32>,
+}
+
+/// One move of a recorded batch, with how the hand and the game turned out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    /// Index of the game in the batch; it was seeded `seed + game`
+    pub game: u32,
+    pub round: u32,
+    pub seat: usize,
+    /// What the seat saw before moving
+    pub features: Features,
+    pub action: Action,
+    /// Score the seat was credited with for the round
+    pub round_score: u32,
+    /// Whether the seat went on to win the game
+    pub won: bool,
+}
+
+/// File formats `write_samples` can export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    /// One JSON object per line, features nested
+    Jsonl,
+    /// A header row, then one row per sample with the features flattened
+    Csv,
+}
+
+// What one game contributes to the batch.
+struct GameResult {
+    finished: bool,
+    wins: Vec<bool>,
+    scores: Vec<u32>,
+    busts: Vec<u32>,
+    rounds_played: Vec<u32>,
+    rounds: u32,
+    winning_score: Option<u32>,
+    flip7s: u32,
+    /// (draws, busts) by cards held before drawing
+    draws: BTreeMap<usize, (u32, u32)>,
+    /// Empty unless recording
+    samples: Vec<Sample>,
+}
+
+/// Plays `n_games` games of `rules` with one seat per bot and aggregates
+/// the results. Every game seats fresh clones of the given bots.
+pub fn run_batch(
+    rules: &GameConfig,
+    bots: &[Box<dyn Strategy>],
+    n_games: u32,
+    seed: u64,
+) -> BatchStats {
+    let results = play_batch(rules, bots, n_games, seed, false);
+    aggregate(&results, bots.len(), n_games)
+}
+
+/// Plays a batch like `run_batch`, also returning every move the bots made,
+/// in game order.
+pub fn record_batch(
+    rules: &GameConfig,
+    bots: &[Box<dyn Strategy>],
+    n_games: u32,
+    seed: u64,
+) -> (BatchStats, Vec<Sample>) {
+    let mut results = play_batch(rules, bots, n_games, seed, true);
+    let stats = aggregate(&results, bots.len(), n_games);
+    let samples = results
+        .iter_mut()
+        .flat_map(|result| std::mem::take(&mut result.samples))
+        .collect();
+    (stats, samples)
+}
+
+/// Writes samples in the given format. The CSV columns are `game`, `round`,
+/// `seat`, `Features::COLUMNS`, `action` (draw or stay), `round_score` and
+/// `won` (0 or 1).
+pub fn write_samples(
+    samples: &[Sample],
+    format: DataFormat,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    match format {
+        DataFormat::Jsonl => {
+            for sample in samples {
+                serde_json::to_writer(&mut *out, sample)?;
+                writeln!(out)?;
+            }
+        }
+        DataFormat::Csv => {
+            writeln!(
+                out,
+                "game,round,seat,{},action,round_score,won",
+                Features::COLUMNS.join(",")
+            )?;
+            for sample in samples {
+                let features: Vec<String> = sample
+                    .features
+                    .values()
+                    .iter()
+                    .map(f64::to_string)
+                    .collect();
+                let action = match sample.action {
+                    Action::Draw => "draw",
+                    Action::Stay => "stay",
+                };
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{}",
+                    sample.game,
+                    sample.round,
+                    sample.seat,
+                    features.join(","),
+                    action,
+                    sample.round_score,
+                    sample.won as u8
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn play_batch(
+    rules: &GameConfig,
+    bots: &[Box<dyn Strategy>],
+    n_games: u32,
+    seed: u64,
+    record: bool,
+) -> Vec<GameResult> {
+    (0..n_games)
+        .into_par_iter()
+        .map(|i| play_game(rules, bots, i, seed.wrapping_add(i as u64), record))
+        .collect()
+}
+
+fn aggregate(results: &[GameResult], seats: usize, n_games: u32) -> BatchStats {
+    let mut wins = vec![0u32; seats];
+    let mut scores = vec![0u64; seats];
+    let mut busts = vec![0u32; seats];
+    let mut rounds_played = vec![0u32; seats];
+    let mut draws: BTreeMap<usize, (u32, u32)> = BTreeMap::new();
+    for result in results {
+        for seat in 0..seats {
+            wins[seat] += result.wins[seat] as u32;
+            scores[seat] += result.scores[seat] as u64;
+            busts[seat] += result.busts[seat];
+            rounds_played[seat] += result.rounds_played[seat];
+        }
+        for (&size, &(n, busted)) in &result.draws {
+            let entry = draws.entry(size).or_default();
+            entry.0 += n;
+            entry.1 += busted;
+        }
+    }
+    let winning_scores: Vec<u32> = results
+        .iter()
+        .filter_map(|result| result.winning_score)
+        .collect();
+
+    let ratio = |count: f64, total: f64| if total == 0.0 { 0.0 } else { count / total };
+    let games = n_games as f64;
+    BatchStats {
+        games: n_games,
+        unfinished: results.iter().filter(|result| !result.finished).count() as u32,
+        win_rates: wins.iter().map(|&w| ratio(w as f64, games)).collect(),
+        average_scores: scores.iter().map(|&s| ratio(s as f64, games)).collect(),
+        bust_rates: busts
+            .iter()
+            .zip(&rounds_played)
+            .map(|(&b, &played)| ratio(b as f64, played as f64))
+            .collect(),
+        average_rounds: ratio(
+            results.iter().map(|result| result.rounds as f64).sum(),
+            games,
+        ),
+        average_winning_score: ratio(
+            winning_scores.iter().map(|&score| score as f64).sum(),
+            winning_scores.len() as f64,
+        ),
+        flip7_rate: ratio(
+            results.iter().map(|result| result.flip7s as f64).sum(),
+            rounds_played.iter().map(|&played| played as f64).sum(),
+        ),
+        bust_rate_by_hand_size: draws
+            .iter()
+            .map(|(&size, &(n, busted))| (size, ratio(busted as f64, n as f64)))
+            .collect(),
+        draws_by_hand_size: draws.iter().map(|(&size, &(n, _))| (size, n)).collect(),
+    }
+}
+
+fn play_game(
+    rules: &GameConfig,
+    bots: &[Box<dyn Strategy>],
+    index: u32,
+    seed: u64,
+    record: bool,
+) -> GameResult {
+    let mut bots = bots.to_vec();
+    let mut game = GameState::new_with_config(seed, rules.clone());
+    for seat in 0..bots.len() {
+        game.add_player(seat.to_string(), format!("Bot {}", seat));
+    }
+
+    let mut samples = Vec::new();
+    while !game.is_game_over() && (game.history.len() as u32) < MAX_ROUNDS {
+        if game.start_round().is_err() {
+            break;
+        }
+        let round_start = samples.len();
+        while let Some(game_move) =
+            game.bot_move(bots[game.round_state.current_player_index].as_mut())
+        {
+            if record {
+                let seat = game.round_state.current_player_index;
+                let view = game
+                    .player_view(&game.players[seat].id)
+                    .expect("the current player is in the game");
+                samples.push(Sample {
+                    game: index,
+                    round: game.round_state.round_number,
+                    seat,
+                    features: view.features(),
+                    action: match game_move {
+                        GameMove::Draw { .. } => Action::Draw,
+                        GameMove::Stay { .. } => Action::Stay,
+                    },
+                    round_score: 0,
+                    won: false,
+                });
+            }
+            game.make_move(game_move)
+                .expect("bots only make legal moves");
+        }
+        let summary = game.finish_round().expect("the round has just finished");
+        for sample in &mut samples[round_start..] {
+            let seat_id = sample.seat.to_string();
+            sample.round_score = summary
+                .players
+                .iter()
+                .find(|r| r.player_id == seat_id)
+                .map_or(0, |r| r.round_score);
+        }
+    }
+
+    let winners = game.outcome.as_ref().map(|outcome| &outcome.winner_ids);
+    for sample in &mut samples {
+        sample.won = winners.is_some_and(|ids| ids.contains(&sample.seat.to_string()));
+    }
+    let seat_results = |seat: usize| {
+        game.history.iter().filter_map(move |round| {
+            round
+                .players
+                .iter()
+                .find(|r| r.player_id == seat.to_string())
+        })
+    };
+    GameResult {
+        finished: game.is_game_over(),
+        wins: game
+            .players
+            .iter()
+            .map(|player| winners.is_some_and(|ids| ids.contains(&player.id)))
+            .collect(),
+        scores: game.players.iter().map(|player| player.score).collect(),
+        busts: (0..bots.len())
+            .map(|seat| seat_results(seat).filter(|r| r.busted).count() as u32)
+            .collect(),
+        rounds_played: (0..bots.len())
+            .map(|seat| seat_results(seat).count() as u32)
+            .collect(),
+        rounds: game.history.len() as u32,
+        winning_score: game
+            .players
+            .iter()
+            .filter(|player| winners.is_some_and(|ids| ids.contains(&player.id)))
+            .map(|player| player.score)
+            .max(),
+        flip7s: game
+            .history
+            .iter()
+            .flat_map(|round| &round.players)
+            .filter(|r| r.flip7_bonus)
+            .count() as u32,
+        draws: draws_by_hand_size(&game.events),
+        samples,
+    }
+}
+
+// Replays the event log, counting each draw under the size of the hand it
+// was drawn into. A bust is always logged right after the draw that caused it.
+fn draws_by_hand_size(events: &[GameEvent]) -> BTreeMap<usize, (u32, u32)> {
+    let mut hand_sizes: HashMap<&str, usize> = HashMap::new();
+    let mut draws: BTreeMap<usize, (u32, u32)> = BTreeMap::new();
+    let mut last_draw = None;
+    for event in events {
+        match event {
+            GameEvent::RoundStarted { .. } => hand_sizes.clear(),
+            GameEvent::CardDealt { player_id, .. } => {
+                *hand_sizes.entry(player_id).or_default() += 1;
+            }
+            GameEvent::CardDrawn { player_id, .. } => {
+                let size = hand_sizes.entry(player_id).or_default();
+                draws.entry(*size).or_default().0 += 1;
+                last_draw = Some(*size);
+                *size += 1;
+                continue;
+            }
+            GameEvent::PlayerBusted { .. } => {
+                if let Some(size) = last_draw {
+                    draws.entry(size).or_default().1 += 1;
+                }
+            }
+            _ => {}
+        }
+        last_draw = None;
+    }
+    draws
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bots::{AlwaysStayAt, Budget, MonteCarlo};
+
+    fn bots() -> Vec<Box<dyn Strategy>> {
+        vec![Box::new(AlwaysStayAt(12)), Box::new(AlwaysStayAt(18))]
+    }
+
+    #[test]
+    fn test_batch_is_reproducible() {
+        let rules = GameConfig::default();
+        let stats = run_batch(&rules, &bots(), 200, 1);
+        assert_eq!(stats, run_batch(&rules, &bots(), 200, 1));
+
+        assert_eq!(stats.games, 200);
+        assert_eq!(stats.unfinished, 0);
+        assert!((stats.win_rates.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(stats.average_rounds > 1.0);
+        assert!(stats.average_winning_score >= GameConfig::default().target_score as f64);
+        assert!(stats.flip7_rate > 0.0 && stats.flip7_rate < 1.0);
+        // Nobody draws below the two dealt cards, and bigger hands bust more
+        assert_eq!(stats.draws_by_hand_size.keys().next(), Some(&2));
+        assert!(stats.bust_rate_by_hand_size[&2] < stats.bust_rate_by_hand_size[&3]);
+        // The cautious bot busts less often than the reckless one
+        assert!(stats.bust_rates[0] < stats.bust_rates[1]);
+    }
+
+    #[test]
+    fn test_recorded_samples() {
+        let rules = GameConfig::default();
+        let (stats, samples) = record_batch(&rules, &bots(), 5, 1);
+        assert_eq!(stats, run_batch(&rules, &bots(), 5, 1));
+        assert_eq!(samples.last().unwrap().game, 4);
+
+        // The cautious seat never draws on 12 or more, and staying banks the hand
+        for sample in samples.iter().filter(|s| s.seat == 0) {
+            assert_eq!(
+                sample.action == Action::Draw,
+                sample.features.hand_total < 12
+            );
+        }
+        for sample in &samples {
+            if sample.action == Action::Stay && !sample.features.has_flip7 {
+                let busted = sample.features.hand_total > 21;
+                let expected = if busted { 0 } else { sample.features.hand_total };
+                assert_eq!(sample.round_score, expected);
+            }
+        }
+        assert!(samples.iter().any(|s| s.won));
+
+        let mut csv = Vec::new();
+        write_samples(&samples, DataFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("game,round,seat,hand_total,"));
+        assert_eq!(lines.count(), samples.len());
+
+        let mut jsonl = Vec::new();
+        write_samples(&samples[..2], DataFormat::Jsonl, &mut jsonl).unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(String::from_utf8(jsonl).unwrap().lines().next().unwrap())
+                .unwrap();
+        assert_eq!(first["action"], serde_json::json!(samples[0].action));
+        assert_eq!(
+            first["features"]["hand_total"],
+            samples[0].features.hand_total
+        );
+    }
+
+    #[test]
+    fn test_unreachable_target_is_abandoned() {
+        let rules = GameConfig {
+            target_score: u32::MAX,
+            ..GameConfig::default()
+        };
+        let stats = run_batch(&rules, &bots(), 2, 1);
+        assert_eq!(stats.unfinished, 2);
+        assert_eq!(stats.average_rounds, MAX_ROUNDS as f64);
+        assert_eq!(stats.win_rates, vec![0.0, 0.0]);
+        assert_eq!(stats.average_winning_score, 0.0);
+    }
+
+    #[test]
+    fn test_monte_carlo_beats_threshold() {
+        let bots: Vec<Box<dyn Strategy>> = vec![
+            Box::new(AlwaysStayAt(17)),
+            Box::new(MonteCarlo::new(Budget::Rollouts(100), 1)),
+        ];
+        let stats = run_batch(&GameConfig::default(), &bots, 100, 1);
+        assert!(stats.win_rates[1] > 0.6, "{:?}", stats);
+    }
+}
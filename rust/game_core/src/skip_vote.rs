@@ -0,0 +1,124 @@
+use crate::errors;
+use crate::events::GameEvent;
+use crate::GameState;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How long a turn must have been running before anyone may vote to skip it.
+pub const MIN_SKIP_WAIT_MS: u64 = 30_000;
+
+/// Result of a successful skip vote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SkipVoteOutcome {
+    /// The vote counted but more are needed
+    Recorded { votes: usize, needed: usize },
+    /// Enough players voted; the current player was auto-stayed
+    Skipped { player_id: String },
+}
+
+impl GameState {
+    /// Votes to skip the current player's turn, auto-staying them once a
+    /// majority of the other players agree.
+    ///
+    /// To keep this from being used as a weapon, the turn must have been
+    /// running for at least `MIN_SKIP_WAIT_MS`, only other seated players may
+    /// vote, each of them once per turn, and votes reset whenever the turn moves on.
+    pub fn vote_skip_turn(
+        &mut self,
+        voter_id: &str,
+        turn_elapsed_ms: u64,
+    ) -> Result<SkipVoteOutcome, String> {
+        if self.round_state.is_finished {
+            return Err(errors::ROUND_FINISHED.to_string());
+        }
+        if self.is_paused() {
+            return Err(errors::GAME_PAUSED.to_string());
+        }
+        if turn_elapsed_ms < MIN_SKIP_WAIT_MS {
+            return Err(errors::SKIP_TOO_SOON.to_string());
+        }
+        if !self.active_players().any(|p| p.id == voter_id) {
+            return Err(errors::PLAYER_NOT_FOUND.to_string());
+        }
+
+        let current_id = self.current_player().ok_or(errors::NO_PLAYERS_ADDED)?.id.clone();
+        if current_id == voter_id {
+            return Err(errors::SKIP_OWN_TURN.to_string());
+        }
+        if self.round_state.skip_votes.iter().any(|id| id == voter_id) {
+            return Err(errors::SKIP_VOTED.to_string());
+        }
+
+        self.round_state.skip_votes.push(voter_id.to_string());
+
+        let needed = (self.active_players().count() - 1) / 2 + 1;
+        let votes = self.round_state.skip_votes.len();
+        if votes < needed {
+            return Ok(SkipVoteOutcome::Recorded { votes, needed });
+        }
+
+        self.players[self.round_state.current_player_index].stay();
+        self.emit(GameEvent::TurnSkipped {
+            player_id: current_id.clone(),
+        });
+        self.advance_turn();
+
+        Ok(SkipVoteOutcome::Skipped {
+            player_id: current_id,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    fn three_player_game() -> GameState {
+        let mut game = GameState::new();
+        for id in ["p1", "p2", "p3"] {
+            game.add_player(id.to_string(), id.to_string());
+        }
+        game.start_round().unwrap();
+        game
+    }
+
+    #[test]
+    fn test_majority_skips_turn() {
+        let mut game = three_player_game();
+
+        assert_eq!(
+            game.vote_skip_turn("p2", MIN_SKIP_WAIT_MS),
+            Ok(SkipVoteOutcome::Recorded {
+                votes: 1,
+                needed: 2
+            })
+        );
+        assert_eq!(
+            game.vote_skip_turn("p3", MIN_SKIP_WAIT_MS),
+            Ok(SkipVoteOutcome::Skipped {
+                player_id: "p1".to_string()
+            })
+        );
+
+        assert!(game.players[0].has_stayed);
+        assert_eq!(game.round_state.current_player_index, 1);
+        assert!(game.round_state.skip_votes.is_empty());
+    }
+
+    #[test]
+    fn test_skip_vote_safeguards() {
+        let mut game = three_player_game();
+
+        assert!(game.vote_skip_turn("p2", MIN_SKIP_WAIT_MS - 1).is_err());
+        assert!(game.vote_skip_turn("p1", MIN_SKIP_WAIT_MS).is_err());
+        assert!(game.vote_skip_turn("stranger", MIN_SKIP_WAIT_MS).is_err());
+
+        game.vote_skip_turn("p2", MIN_SKIP_WAIT_MS).unwrap();
+        assert!(game.vote_skip_turn("p2", MIN_SKIP_WAIT_MS).is_err());
+
+        // Acting resets the votes for the next turn
+        game.player_stay("p1").unwrap();
+        assert!(game.round_state.skip_votes.is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+//! Single-player score attack: one player plays a fixed number of rounds and
+//! tries to finish at or above a target score. Works offline, with no
+//! opponents to wait for.
+
+use crate::config::GameConfig;
+use crate::GameState;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Id of the only player in a solo game.
+pub const SOLO_PLAYER_ID: &str = "0";
+
+/// Summary of a finished solo game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SoloResult {
+    pub rounds_played: u32,
+    pub total_score: u32,
+    pub target_score: u32,
+    pub beat_target: bool,
+    pub best_round: u32,
+    pub busts: u32,
+    pub flip7s: u32,
+}
+
+impl GameState {
+    /// Creates a solo game of `rounds` rounds against `target_score`. The
+    /// first round still has to be started with `start_round`.
+    pub fn new_solo(seed: u64, rounds: u32, target_score: u32) -> Self {
+        let config = GameConfig {
+            target_score,
+            round_limit: Some(rounds),
+            ..GameConfig::default()
+        };
+        let mut game = Self::new_with_config(seed, config);
+        game.add_player(SOLO_PLAYER_ID.to_string(), "Player 0".to_string());
+        game
+    }
+
+    pub fn is_solo(&self) -> bool {
+        self.players.len() == 1 && self.config.round_limit.is_some()
+    }
+
+    /// Final summary, once every round of a solo game has been played.
+    pub fn solo_result(&self) -> Option<SoloResult> {
+        if !self.is_solo() || !self.is_game_over() {
+            return None;
+        }
+
+        let results: Vec<_> = self
+            .history
+            .iter()
+            .flat_map(|round| round.players.iter())
+            .collect();
+        let total_score = self.players[0].score;
+
+        Some(SoloResult {
+            rounds_played: self.history.len() as u32,
+            total_score,
+            target_score: self.config.target_score,
+            beat_target: total_score >= self.config.target_score,
+            best_round: results.iter().map(|r| r.round_score).max().unwrap_or(0),
+            busts: results.iter().filter(|r| r.busted).count() as u32,
+            flip7s: results.iter().filter(|r| r.flip7_bonus).count() as u32,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solo_game_plays_fixed_rounds() {
+        let mut game = GameState::new_solo(7, 3, 30);
+
+        for _ in 0..3 {
+            assert!(game.solo_result().is_none());
+            game.start_round().unwrap();
+            game.player_stay(SOLO_PLAYER_ID).unwrap();
+            game.finish_round().unwrap();
+        }
+
+        let result = game.solo_result().unwrap();
+        assert_eq!(result.rounds_played, 3);
+        assert_eq!(result.total_score, game.players[0].score);
+        assert_eq!(result.beat_target, result.total_score >= 30);
+        assert!(game.outcome.is_none());
+        assert!(game.start_round().is_err());
+    }
+
+    #[test]
+    fn test_target_does_not_end_solo_game_early() {
+        let mut game = GameState::new_solo(7, 2, 1);
+
+        game.start_round().unwrap();
+        game.player_stay(SOLO_PLAYER_ID).unwrap();
+        game.finish_round().unwrap();
+
+        assert!(!game.is_game_over());
+        assert!(game.start_round().is_ok());
+    }
+}
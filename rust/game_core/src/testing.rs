@@ -0,0 +1,70 @@
+//! Stacked decks for tests and scenario tooling, behind the `test-utils`
+//! feature. A stacked deck is never shuffled or replaced: rounds deal from it
+//! in the given order, carrying on where the previous round stopped, so a
+//! test can spell out every card instead of hunting for a seed.
+
+use crate::{Card, Deck, GameState};
+
+impl Deck {
+    /// A deck that deals `cards` in order, `cards[0]` first.
+    pub fn from_ordered(mut cards: Vec<Card>) -> Self {
+        // Cards are drawn from the end
+        cards.reverse();
+        Self {
+            stacked: true,
+            ..Self::with_cards(cards, 0)
+        }
+    }
+}
+
+impl GameState {
+    /// A new game whose rounds are dealt from `cards` in order; see
+    /// `Deck::from_ordered`.
+    pub fn with_deck(cards: Vec<Card>) -> Self {
+        Self {
+            deck: Deck::from_ordered(cards),
+            ..Self::new()
+        }
+    }
+}
+
+/// Cards with the given values, for building stacked decks.
+pub fn cards(values: &[u8]) -> Vec<Card> {
+    values.iter().map(|&value| Card::new(value)).collect()
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stacked_deck_deals_in_order() {
+        // Two players dealt 1/2 and 3/4, then Alice draws 12 and Bob 6
+        let mut game = GameState::with_deck(cards(&[1, 3, 2, 4, 12, 6, 5, 5]));
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+
+        assert_eq!(game.players[0].hand.cards.to_vec(), cards(&[1, 2]));
+        assert_eq!(game.players[1].hand.cards.to_vec(), cards(&[3, 4]));
+
+        game.player_draw("p1").unwrap();
+        game.player_draw("p2").unwrap();
+        assert_eq!(game.players[0].hand.cards.to_vec(), cards(&[1, 2, 12]));
+        assert!(game.players[1].hand.has_flip7());
+        assert_eq!(game.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_next_round_continues_the_stack() {
+        let mut game = GameState::with_deck(cards(&[1, 2, 9, 10]));
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+        game.finish_round().unwrap();
+
+        game.start_round().unwrap();
+        assert_eq!(game.players[0].hand.cards.to_vec(), cards(&[9, 10]));
+        assert!(game.deck.is_empty());
+    }
+}
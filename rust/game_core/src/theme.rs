@@ -0,0 +1,121 @@
+//! Seat identity for frontends. Core hands out abstract palette slots and
+//! patterns rather than colors, so every client tells players apart the same
+//! way and each frontend maps slots to its own color-blind-safe palette.
+
+use crate::GameState;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Number of palette slots frontends must provide colors for.
+pub const PALETTE_SLOTS: usize = 8;
+
+/// Fill pattern drawn alongside the seat color, so players stay
+/// distinguishable without relying on color at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum Pattern {
+    Solid,
+    Stripes,
+    Dots,
+    Checks,
+    Waves,
+    Zigzag,
+    Crosshatch,
+    Diamonds,
+}
+
+const PATTERNS: [Pattern; 8] = [
+    Pattern::Solid,
+    Pattern::Stripes,
+    Pattern::Dots,
+    Pattern::Checks,
+    Pattern::Waves,
+    Pattern::Zigzag,
+    Pattern::Crosshatch,
+    Pattern::Diamonds,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub struct SeatTheme {
+    pub palette_slot: u8,
+    pub pattern: Pattern,
+}
+
+impl SeatTheme {
+    /// Theme of the n-th seat. The first `PALETTE_SLOTS` seats all differ in
+    /// both slot and pattern; after that each lap of the palette shifts the
+    /// patterns by one, so large tables get distinct themes up to
+    /// `PALETTE_SLOTS * PATTERNS.len()` seats.
+    pub fn for_seat(seat: usize) -> Self {
+        let lap = seat / PALETTE_SLOTS;
+        Self {
+            palette_slot: (seat % PALETTE_SLOTS) as u8,
+            pattern: PATTERNS[(seat + lap) % PATTERNS.len()],
+        }
+    }
+}
+
+impl GameState {
+    /// First seat theme no current player has. Players keep their theme for
+    /// the whole game, so someone leaving never recolors the others.
+    pub(crate) fn next_free_theme(&self) -> SeatTheme {
+        (0..PALETTE_SLOTS * PATTERNS.len())
+            .map(SeatTheme::for_seat)
+            .find(|theme| !self.players.iter().any(|p| p.theme == *theme))
+            // Past that many players themes have to repeat
+            .unwrap_or_else(|| SeatTheme::for_seat(self.players.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seats_get_distinct_themes() {
+        let mut game = GameState::new();
+        for i in 0..PALETTE_SLOTS {
+            game.add_player(i.to_string(), format!("Player {}", i));
+        }
+
+        for (i, a) in game.players.iter().enumerate() {
+            for b in &game.players[i + 1..] {
+                assert_ne!(a.theme.palette_slot, b.theme.palette_slot);
+                assert_ne!(a.theme.pattern, b.theme.pattern);
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_tables_get_distinct_themes() {
+        let mut game = GameState::new();
+        for i in 0..20 {
+            game.add_player(i.to_string(), format!("Player {}", i));
+        }
+
+        for (i, a) in game.players.iter().enumerate() {
+            for b in &game.players[i + 1..] {
+                assert_ne!(a.theme, b.theme);
+            }
+        }
+    }
+
+    #[test]
+    fn test_theme_kept_when_a_player_leaves() {
+        let mut game = GameState::new();
+        for id in ["p1", "p2", "p3"] {
+            game.add_player(id.to_string(), id.to_string());
+        }
+        let third = game.players[2].theme;
+
+        game.players.remove(0);
+        assert_eq!(game.players[1].theme, third);
+
+        // A newcomer takes the freed seat theme
+        game.add_player("p4".to_string(), "p4".to_string());
+        assert_eq!(game.players[2].theme, SeatTheme::for_seat(0));
+    }
+}
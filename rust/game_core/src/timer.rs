@@ -0,0 +1,121 @@
+//! Turn timers. With `GameConfig::turn_time_limit_ms` set, a player who
+//! doesn't finish their turn in time is auto-stayed so the game can't stall.
+//!
+//! Like pausing, this runs on the caller's clock: `tick` is called with the
+//! current time in milliseconds since the Unix epoch. A turn's deadline is
+//! set by the first `tick` of that turn, so call it regularly (the server does
+//! once a second) or before every move.
+
+use crate::events::GameEvent;
+use crate::GameState;
+
+impl GameState {
+    /// Starts the current turn's timer if it isn't running yet, and auto-stays
+    /// the current player once it has run out. Returns the ids of the players
+    /// who timed out, in order. Also starts the game clock; see `clock`.
+    pub fn tick(&mut self, now_ms: u64) -> Vec<String> {
+        self.start_clock(now_ms);
+        let Some(limit) = self.config.turn_time_limit_ms else {
+            return Vec::new();
+        };
+
+        let mut timed_out = Vec::new();
+        while !self.round_state.is_finished && !self.is_paused() && !self.players.is_empty() {
+            let deadline = *self
+                .round_state
+                .turn_deadline_ms
+                .get_or_insert(now_ms.saturating_add(limit));
+            if now_ms < deadline {
+                break;
+            }
+
+            let player_id = self.players[self.round_state.current_player_index]
+                .id
+                .clone();
+            self.players[self.round_state.current_player_index].stay();
+            self.emit(GameEvent::TurnTimedOut {
+                player_id: player_id.clone(),
+            });
+            self.advance_turn();
+            // The next player's time starts when the last one ran out
+            if !self.round_state.is_finished {
+                self.round_state.turn_deadline_ms = Some(deadline.saturating_add(limit));
+                self.round_state.turn_started_ms = Some(deadline);
+            }
+            timed_out.push(player_id);
+        }
+        timed_out
+    }
+}
+
+#[cfg(all(test, feature = "rng"))]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+
+    fn timed_game() -> GameState {
+        let config = GameConfig {
+            turn_time_limit_ms: Some(10_000),
+            ..GameConfig::default()
+        };
+        let mut game = GameState::new_with_config(42, config);
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        game
+    }
+
+    #[test]
+    fn test_expired_turn_auto_stays() {
+        let mut game = timed_game();
+
+        assert!(game.tick(1_000).is_empty());
+        assert_eq!(game.round_state.turn_deadline_ms, Some(11_000));
+        assert!(game.tick(10_999).is_empty());
+
+        assert_eq!(game.tick(11_000), vec!["p1".to_string()]);
+        assert!(game.players[0].has_stayed);
+        assert_eq!(game.round_state.current_player_index, 1);
+        assert_eq!(
+            game.events.last(),
+            Some(&GameEvent::TurnTimedOut {
+                player_id: "p1".to_string()
+            })
+        );
+
+        // A move clears the deadline; the next tick starts a fresh one
+        game.player_draw("p2").unwrap();
+        assert_eq!(game.round_state.turn_deadline_ms, None);
+    }
+
+    #[test]
+    fn test_long_gap_times_out_several_turns() {
+        let mut game = timed_game();
+        game.tick(0);
+
+        assert_eq!(game.tick(25_000), vec!["p1".to_string(), "p2".to_string()]);
+        assert!(game.round_state.is_finished);
+    }
+
+    #[test]
+    fn test_pause_stops_the_clock() {
+        let mut game = timed_game();
+        game.tick(0);
+        game.pause(5_000).unwrap();
+
+        assert!(game.tick(20_000).is_empty());
+        game.resume(20_000).unwrap();
+        assert_eq!(game.round_state.turn_deadline_ms, Some(25_000));
+        assert!(game.tick(24_999).is_empty());
+    }
+
+    #[test]
+    fn test_no_limit_never_times_out() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+
+        assert!(game.tick(u64::MAX).is_empty());
+        assert_eq!(game.round_state.turn_deadline_ms, None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Round-robin tournaments between bots, for comparing a strategy against
+//! others with enough games to tell real differences from luck.
+//!
+//! Every pairing plays half its games in each seat order, on the same seeds,
+//! so neither the first seat's edge nor a lucky run of deals favors either bot.
+
+use crate::bots::Strategy;
+use crate::config::GameConfig;
+use crate::simulate::run_batch;
+use std::fmt;
+
+/// z-score of the 95% confidence intervals.
+const Z_95: f64 = 1.96;
+
+/// A named bot taking part in a tournament.
+#[derive(Clone)]
+pub struct Entrant {
+    pub name: String,
+    pub strategy: Box<dyn Strategy>,
+}
+
+impl Entrant {
+    pub fn new(name: impl Into<String>, strategy: Box<dyn Strategy>) -> Self {
+        Self {
+            name: name.into(),
+            strategy,
+        }
+    }
+}
+
+/// How one entrant fared against another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairResult {
+    pub games: u32,
+    pub wins: u32,
+    pub win_rate: f64,
+    /// 95% Wilson score interval of the win rate
+    pub low: f64,
+    pub high: f64,
+}
+
+impl PairResult {
+    fn new(wins: u32, games: u32) -> Self {
+        let (low, high) = wilson_interval(wins, games);
+        Self {
+            games,
+            wins,
+            win_rate: if games == 0 {
+                0.0
+            } else {
+                wins as f64 / games as f64
+            },
+            low,
+            high,
+        }
+    }
+}
+
+/// Results of a round robin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossTable {
+    pub names: Vec<String>,
+    /// `results[i][j]`: how entrant `i` fared against entrant `j`; `None` on
+    /// the diagonal
+    pub results: Vec<Vec<Option<PairResult>>>,
+}
+
+impl CrossTable {
+    /// Win rate of an entrant over all its games.
+    pub fn overall(&self, entrant: usize) -> f64 {
+        let (wins, games) = self.results[entrant]
+            .iter()
+            .flatten()
+            .fold((0, 0), |(wins, games), r| (wins + r.wins, games + r.games));
+        if games == 0 {
+            0.0
+        } else {
+            wins as f64 / games as f64
+        }
+    }
+}
+
+/// Plays `games_per_pairing` two-player games of `rules` between every pair
+/// of entrants. Game `n` of every pairing is seeded `seed + n`.
+pub fn round_robin(
+    rules: &GameConfig,
+    entrants: &[Entrant],
+    games_per_pairing: u32,
+    seed: u64,
+) -> CrossTable {
+    let n = entrants.len();
+    let mut results = vec![vec![None; n]; n];
+    let first_half = games_per_pairing / 2;
+
+    for i in 0..n {
+        for j in i + 1..n {
+            let (a, b) = (&entrants[i].strategy, &entrants[j].strategy);
+            let a_first = run_batch(rules, &[a.clone(), b.clone()], first_half, seed);
+            let b_first = run_batch(
+                rules,
+                &[b.clone(), a.clone()],
+                games_per_pairing - first_half,
+                seed,
+            );
+
+            let wins = |first: f64, second: f64| {
+                (first * first_half as f64 + second * (games_per_pairing - first_half) as f64)
+                    .round() as u32
+            };
+            let a_wins = wins(a_first.win_rates[0], b_first.win_rates[1]);
+            let b_wins = wins(a_first.win_rates[1], b_first.win_rates[0]);
+            results[i][j] = Some(PairResult::new(a_wins, games_per_pairing));
+            results[j][i] = Some(PairResult::new(b_wins, games_per_pairing));
+        }
+    }
+
+    CrossTable {
+        names: entrants.iter().map(|e| e.name.clone()).collect(),
+        results,
+    }
+}
+
+fn wilson_interval(wins: u32, games: u32) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+/// One row per entrant: its win rate against each column, with the 95%
+/// interval, then over all its games.
+impl fmt::Display for CrossTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name_width = self.names.iter().map(|n| n.len()).max().unwrap_or(0).max(4);
+        const CELL: usize = 18;
+
+        write!(f, "{:name_width$}", "")?;
+        for name in &self.names {
+            write!(f, " {:>CELL$}", name)?;
+        }
+        writeln!(f, " {:>8}", "overall")?;
+
+        for (i, name) in self.names.iter().enumerate() {
+            write!(f, "{:name_width$}", name)?;
+            for result in &self.results[i] {
+                let cell = match result {
+                    Some(r) => format!(
+                        "{:.1}% [{:.0}-{:.0}]",
+                        r.win_rate * 100.0,
+                        r.low * 100.0,
+                        r.high * 100.0
+                    ),
+                    None => "-".to_string(),
+                };
+                write!(f, " {:>CELL$}", cell)?;
+            }
+            writeln!(f, " {:>7.1}%", self.overall(i) * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::AlwaysStayAt;
+
+    #[test]
+    fn test_round_robin() {
+        let entrants = vec![
+            Entrant::new("cautious", Box::new(AlwaysStayAt(15))),
+            Entrant::new("greedy", Box::new(AlwaysStayAt(19))),
+            Entrant::new("timid", Box::new(AlwaysStayAt(10))),
+        ];
+        let table = round_robin(&GameConfig::default(), &entrants, 200, 1);
+
+        assert_eq!(table.names, vec!["cautious", "greedy", "timid"]);
+        assert!(table.results[1][1].is_none());
+        let cautious_vs_greedy = table.results[0][1].unwrap();
+        let greedy_vs_cautious = table.results[1][0].unwrap();
+        assert_eq!(cautious_vs_greedy.games, 200);
+        assert_eq!(cautious_vs_greedy.wins + greedy_vs_cautious.wins, 200);
+        assert!(cautious_vs_greedy.low > 0.5);
+        assert!(cautious_vs_greedy.low < cautious_vs_greedy.win_rate);
+        assert!(cautious_vs_greedy.high > cautious_vs_greedy.win_rate);
+        assert!(table.overall(0) > table.overall(1));
+
+        let printed = table.to_string();
+        assert_eq!(printed.lines().count(), 4);
+        assert!(printed.lines().nth(1).unwrap().starts_with("cautious"));
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        let (low, high) = wilson_interval(50, 100);
+        assert!((low - 0.404).abs() < 0.001 && (high - 0.596).abs() < 0.001);
+        assert_eq!(wilson_interval(0, 0), (0.0, 1.0));
+        let (low, high) = wilson_interval(10, 10);
+        assert!(low > 0.7 && high == 1.0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Named rule variants: a preset `GameConfig` plus the `ScoringStrategy`
+//! that goes with it, picked by name in the CLI and when creating a game
+//! over the network.
+
+use crate::config::GameConfig;
+use crate::scoring::{OfficialScoring, ScoringStrategy};
+use crate::GameState;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Variant new games use when none is asked for.
+pub const DEFAULT_VARIANT: &str = "classic";
+
+/// One named variant.
+#[derive(Clone)]
+pub struct Variant {
+    pub description: String,
+    /// Settings new games start from; `variant` is set to the variant's name
+    pub config: GameConfig,
+    pub scoring: Arc<dyn ScoringStrategy>,
+}
+
+/// Variants by name. `builtin()` holds the ones shipped with the engine;
+/// embedders can register their own on top.
+#[derive(Clone)]
+pub struct VariantRegistry {
+    variants: BTreeMap<String, Variant>,
+}
+
+impl Default for VariantRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl VariantRegistry {
+    /// A registry with no variants at all.
+    pub fn empty() -> Self {
+        Self {
+            variants: BTreeMap::new(),
+        }
+    }
+
+    /// The standard rules, plus the built-in variants.
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        registry.register(
+            DEFAULT_VARIANT,
+            "The standard rules: first to 200",
+            GameConfig::default(),
+            Arc::new(OfficialScoring),
+        );
+        registry.register(
+            "blitz",
+            "Quick games: first to 100, with 10 seconds per turn",
+            GameConfig {
+                target_score: 100,
+                turn_time_limit_ms: Some(10_000),
+                ..GameConfig::default()
+            },
+            Arc::new(OfficialScoring),
+        );
+        registry
+    }
+
+    /// Adds a variant, replacing any with the same name.
+    pub fn register(
+        &mut self,
+        name: &str,
+        description: &str,
+        mut config: GameConfig,
+        scoring: Arc<dyn ScoringStrategy>,
+    ) {
+        config.variant = Some(name.to_string());
+        self.variants.insert(
+            name.to_string(),
+            Variant {
+                description: description.to_string(),
+                config,
+                scoring,
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Variant, String> {
+        self.variants.get(name).ok_or_else(|| {
+            format!(
+                "Unknown variant '{}' (known: {})",
+                name,
+                self.names().collect::<Vec<_>>().join(", ")
+            )
+        })
+    }
+
+    /// Names of every variant, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.variants.keys().map(String::as_str)
+    }
+
+    /// A new game of the named variant.
+    pub fn new_game(&self, name: &str, seed: u64) -> Result<GameState, String> {
+        let variant = self.get(name)?;
+        let mut game = GameState::new_with_config(seed, variant.config.clone());
+        game.set_scoring(variant.scoring.clone());
+        Ok(game)
+    }
+
+    /// Sets the scoring strategy of the game's variant again, which is not
+    /// saved with the game. Games without a variant keep the official scoring.
+    pub fn restore_scoring(&self, game: &mut GameState) -> Result<(), String> {
+        if let Some(name) = &game.config.variant {
+            let scoring = self.get(name)?.scoring.clone();
+            game.set_scoring(scoring);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    use crate::scoring::RoundContext;
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    use crate::Hand;
+
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    struct Doubled;
+
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    impl ScoringStrategy for Doubled {
+        fn score_hand(&self, hand: &Hand, ctx: &RoundContext) -> u32 {
+            ctx.rules.score_hand(hand) * 2
+        }
+    }
+
+    #[test]
+    fn test_builtin_variants() {
+        let registry = VariantRegistry::builtin();
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            vec!["blitz", "classic"]
+        );
+
+        let game = registry.new_game("blitz", 1).unwrap();
+        assert_eq!(game.config.target_score, 100);
+        assert_eq!(game.config.variant.as_deref(), Some("blitz"));
+        assert!(registry.new_game("speedy", 1).is_err());
+    }
+
+    #[cfg(all(feature = "rng", feature = "serde"))]
+    #[test]
+    fn test_custom_variant_scoring_survives_reload() {
+        let mut registry = VariantRegistry::builtin();
+        registry.register(
+            "doubled",
+            "Every round counts twice",
+            GameConfig::default(),
+            Arc::new(Doubled),
+        );
+        let mut game = registry.new_game("doubled", 42).unwrap();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.start_round().unwrap();
+        game.player_stay("p1").unwrap();
+        let scores = game.round_scores();
+
+        let mut loaded = GameState::from_json(&game.to_json().unwrap()).unwrap();
+        assert_ne!(loaded.round_scores(), scores);
+        registry.restore_scoring(&mut loaded).unwrap();
+        assert_eq!(loaded.round_scores(), scores);
+        assert!(VariantRegistry::empty()
+            .restore_scoring(&mut loaded)
+            .is_err());
+    }
+}
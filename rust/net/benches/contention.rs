@@ -0,0 +1,117 @@
+//! Moves in different games played at the same time, with every game behind
+//! one lock and with games spread over shards. With one lock the tables take
+//! turns; sharded, they play side by side on as many cores as there are. Run
+//! with `cargo bench --bench contention`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use game_core::GameMove;
+use net::{GameId, GameServer, Message, Response, DEFAULT_SHARDS};
+use tokio::runtime::Runtime;
+
+const TABLES: usize = 8;
+const ROUNDS: usize = 10;
+
+fn join(game_id: Option<GameId>, player_name: &str) -> Message {
+    Message::JoinGame {
+        player_name: player_name.to_string(),
+        game_id,
+        team: None,
+        variant: None,
+        code: None,
+        access: None,
+    }
+}
+
+/// A new two player game on the server.
+async fn seat_table(server: &GameServer) -> GameId {
+    let game_id = match server.handle_message(join(None, "Alice")).await {
+        Response::GameJoined { game_id, .. } => game_id,
+        other => panic!("Expected GameJoined response, got {:?}", other),
+    };
+    server.handle_message(join(Some(game_id), "Bob")).await;
+    game_id
+}
+
+/// Plays rounds in which the player whose turn it is draws once, then
+/// everyone stays.
+async fn play_rounds(server: GameServer, game_id: GameId) {
+    for _ in 0..ROUNDS {
+        if !matches!(
+            server.handle_message(Message::StartGame { game_id, host: None }).await,
+            Response::GameStarted { .. }
+        ) {
+            // Someone reached the target score
+            return;
+        }
+        let mut drawn = false;
+        loop {
+            let game = match server
+                .handle_message(Message::GetGameState { game_id, access: None })
+                .await
+            {
+                Response::GameState { game_state } => game_state,
+                other => panic!("Expected GameState response, got {:?}", other),
+            };
+            if game.round_state.is_finished {
+                break;
+            }
+            let Some(player) = game.current_player() else {
+                break;
+            };
+            let player_id = player.id.clone();
+            let game_move = if drawn {
+                GameMove::Stay { player_id }
+            } else {
+                GameMove::Draw { player_id }
+            };
+            drawn = true;
+            let response = server
+                .handle_message(Message::MakeMove {
+                    game_id,
+                    game_move,
+                    move_id: None,
+                })
+                .await;
+            if let Response::Error { message } = response {
+                panic!("Move rejected: {}", message);
+            }
+        }
+    }
+}
+
+fn bench_tables(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("moves_in_different_games");
+    for (name, shards) in [("one_lock", 1), ("sharded", DEFAULT_SHARDS)] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    runtime.block_on(async {
+                        let server = GameServer::with_shards(shards);
+                        let mut tables = Vec::new();
+                        for _ in 0..TABLES {
+                            tables.push(seat_table(&server).await);
+                        }
+                        (server, tables)
+                    })
+                },
+                |(server, tables)| {
+                    runtime.block_on(async {
+                        let players: Vec<_> = tables
+                            .into_iter()
+                            .map(|game_id| tokio::spawn(play_rounds(server.clone(), game_id)))
+                            .collect();
+                        for player in players {
+                            player.await.unwrap();
+                        }
+                    })
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_tables);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // The gRPC service is generated from proto/flip7.proto, with a vendored
+    // protoc so building it doesn't need one installed
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/flip7.proto");
+        let protoc =
+            protoc_bin_vendored::protoc_bin_path().expect("protoc is vendored for this platform");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/flip7.proto").expect("proto/flip7.proto compiles");
+    }
+}
@@ -0,0 +1,423 @@
+//! Operator commands for a live server: look over every game, finish or
+//! clean up stuck ones, and drain the server before a deploy.
+//!
+//! They are served on a port of their own by `AdminTransport`, one JSON
+//! `AdminRequest` per line answered by one `AdminResponse` per line, and only
+//! once the server was given a token with `GameServer::set_admin_token`. A
+//! request with the wrong token is answered a second late, and the
+//! connection closed. Players never see this port, so keep it off the public
+//! internet.
+
+use crate::lobby::GameStatus;
+use crate::transport::MAX_FRAME_LEN;
+use crate::{GameId, GameServer, PlayerId, ProtocolEngine, Response};
+use game_core::events::GameEvent;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// How long a request with the wrong token waits for its answer, before its
+/// connection is closed
+const BAD_TOKEN_DELAY: Duration = Duration::from_secs(1);
+
+/// One operator command, with the token that allows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminRequest {
+    pub token: String,
+    pub command: AdminCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdminCommand {
+    /// Every game on the server, public or not
+    ListGames,
+    /// Ends a game where it stands; see `GameState::stop`
+    FinishGame { game_id: GameId },
+    /// Removes a player from a game, as its host could
+    KickPlayer { game_id: GameId, player_id: PlayerId },
+    /// Everything that happened in a game so far
+    EventLog { game_id: GameId },
+    /// Stops new games from being created or matched, so the server can be
+    /// taken down once the games in flight are done. Sent again, it reports
+    /// how many are left.
+    Drain,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdminResponse {
+    Games { games: Vec<GameInfo> },
+    GameFinished { game_id: GameId },
+    PlayerKicked { game_id: GameId, player_id: PlayerId },
+    EventLog { game_id: GameId, events: Vec<GameEvent> },
+    /// The server is draining, with this many games not finished yet
+    Draining { games_left: usize },
+    Error { message: String },
+}
+
+/// What an operator sees of one game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameInfo {
+    pub game_id: GameId,
+    pub status: GameStatus,
+    pub player_count: usize,
+    /// Seconds since the game was created, or since this process first saw it
+    pub age_secs: u64,
+    /// Seconds since the game last changed
+    pub idle_secs: u64,
+}
+
+impl GameServer {
+    /// Lets operators in with the given token; see `admin`. Without one,
+    /// every admin request is refused.
+    pub fn set_admin_token(&self, token: String) {
+        *self.admin_token.write().unwrap() = Some(token);
+    }
+
+    /// Whether `Drain` was sent: new games are refused.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    fn admin_allowed(&self, token: &str) -> bool {
+        self.admin_token
+            .read()
+            .unwrap()
+            .as_deref()
+            .is_some_and(|expected| same_token(expected, token))
+    }
+
+    pub async fn handle_admin(&self, request: AdminRequest) -> AdminResponse {
+        if !self.admin_allowed(&request.token) {
+            return AdminResponse::Error {
+                message: "Not allowed".to_string(),
+            };
+        }
+
+        tracing::info!(command = ?request.command, "admin command");
+        let result = match request.command {
+            AdminCommand::ListGames => {
+                let now = Instant::now();
+                let mut games = Vec::new();
+                for engine in self.shards.all() {
+                    games.extend(engine.read().await.game_infos(now));
+                }
+                games.sort_by_key(|info| (info.status, info.game_id));
+                Ok(AdminResponse::Games { games })
+            }
+            AdminCommand::FinishGame { game_id } => self
+                .shards
+                .of(game_id)
+                .write()
+                .await
+                .finish_game(game_id)
+                .map(|()| AdminResponse::GameFinished { game_id }),
+            AdminCommand::KickPlayer { game_id, player_id } => self
+                .shards
+                .of(game_id)
+                .write()
+                .await
+                .remove_player(game_id, player_id)
+                .map(|()| AdminResponse::PlayerKicked { game_id, player_id }),
+            AdminCommand::EventLog { game_id } => match self.shards.of(game_id).read().await.games.get(&game_id) {
+                Some(game) => Ok(AdminResponse::EventLog {
+                    game_id,
+                    events: game.events.clone(),
+                }),
+                None => Err("Game not found".to_string()),
+            },
+            AdminCommand::Drain => {
+                self.draining.store(true, Ordering::Relaxed);
+                let mut games_left = 0;
+                for engine in self.shards.all() {
+                    let engine = engine.read().await;
+                    games_left += engine
+                        .games
+                        .values()
+                        .filter(|game| GameStatus::of(game) != GameStatus::Finished)
+                        .count();
+                }
+                Ok(AdminResponse::Draining { games_left })
+            }
+        };
+        result.unwrap_or_else(|message| AdminResponse::Error { message })
+    }
+}
+
+impl ProtocolEngine {
+    fn game_infos(&self, now: Instant) -> Vec<GameInfo> {
+        self.games
+            .iter()
+            .map(|(game_id, game)| {
+                let since = |at: Option<&Instant>| now.duration_since(at.copied().unwrap_or(now)).as_secs();
+                GameInfo {
+                    game_id: *game_id,
+                    status: GameStatus::of(game),
+                    player_count: game.players.len(),
+                    age_secs: since(self.created.get(game_id)),
+                    idle_secs: since(self.changed.get(game_id)),
+                }
+            })
+            .collect()
+    }
+
+    fn finish_game(&mut self, game_id: GameId) -> Result<(), String> {
+        let game = self.games.get_mut(&game_id).ok_or("Game not found")?;
+        game.stop()?;
+        self.publish(game_id);
+        Ok(())
+    }
+
+    fn remove_player(&mut self, game_id: GameId, player_id: PlayerId) -> Result<(), String> {
+        if !self.is_seated(game_id, player_id) {
+            return Err("No such player in this game".to_string());
+        }
+        self.leave_game(game_id, player_id);
+        self.notify(game_id, Response::PlayerKicked { game_id, player_id });
+        self.publish(game_id);
+        Ok(())
+    }
+}
+
+/// Compares tokens without stopping at the first difference, so their
+/// bytes can't be guessed one at a time from response times.
+fn same_token(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Serves admin requests over TCP, one JSON object per line each way.
+pub struct AdminTransport {
+    listener: TcpListener,
+}
+
+impl AdminTransport {
+    pub async fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr).await?))
+    }
+
+    pub fn from_listener(listener: TcpListener) -> Self {
+        Self { listener }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answers operators until the listener fails. Like the player
+    /// transports it runs no timers; see `run_timers`.
+    pub async fn serve(self, server: GameServer) -> io::Result<()> {
+        loop {
+            let (stream, addr) = self.listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle_connection(server, stream).await {
+                    tracing::debug!(peer = %addr, %err, "admin connection failed");
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection(server: GameServer, stream: TcpStream) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = Vec::new();
+        let mut limited = (&mut reader).take(u64::from(MAX_FRAME_LEN) + 1);
+        if limited.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(());
+        }
+        if line.len() > MAX_FRAME_LEN as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Line of over {} bytes is too long", MAX_FRAME_LEN),
+            ));
+        }
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let (response, refused) = match serde_json::from_slice::<AdminRequest>(&line) {
+            Ok(request) if !server.admin_allowed(&request.token) => {
+                // Guessing tokens takes a connection and a second each
+                tokio::time::sleep(BAD_TOKEN_DELAY).await;
+                (server.handle_admin(request).await, true)
+            }
+            Ok(request) => (server.handle_admin(request).await, false),
+            Err(err) => {
+                let message = format!("Invalid request: {}", err);
+                (AdminResponse::Error { message }, false)
+            }
+        };
+        let mut reply = serde_json::to_vec(&response).map_err(io::Error::other)?;
+        reply.push(b'\n');
+        writer.write_all(&reply).await?;
+        if refused {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Message;
+
+    const TOKEN: &str = "s3cret";
+
+    fn request(command: AdminCommand) -> AdminRequest {
+        AdminRequest {
+            token: TOKEN.to_string(),
+            command,
+        }
+    }
+
+    async fn join(server: &GameServer, game_id: Option<GameId>) -> (GameId, PlayerId) {
+        let response = server
+            .handle_message(Message::JoinGame {
+                player_name: "Alice".to_string(),
+                game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            })
+            .await;
+        match response {
+            Response::GameJoined { game_id, player_id, .. } => (game_id, player_id),
+            other => panic!("Expected GameJoined, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_needs_token() {
+        let server = GameServer::new();
+        let response = server.handle_admin(request(AdminCommand::ListGames)).await;
+        assert!(matches!(response, AdminResponse::Error { .. }));
+
+        server.set_admin_token(TOKEN.to_string());
+        let wrong = AdminRequest {
+            token: "guess".to_string(),
+            command: AdminCommand::ListGames,
+        };
+        assert!(matches!(server.handle_admin(wrong).await, AdminResponse::Error { .. }));
+        assert_eq!(
+            server.handle_admin(request(AdminCommand::ListGames)).await,
+            AdminResponse::Games { games: Vec::new() }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_commands() {
+        let server = GameServer::new();
+        server.set_admin_token(TOKEN.to_string());
+        let (game_id, alice) = join(&server, None).await;
+        let (_, bob) = join(&server, Some(game_id)).await;
+
+        match server.handle_admin(request(AdminCommand::ListGames)).await {
+            AdminResponse::Games { games } => {
+                assert_eq!(games.len(), 1);
+                assert_eq!(games[0].game_id, game_id);
+                assert_eq!(games[0].player_count, 2);
+            }
+            other => panic!("Expected Games, got {:?}", other),
+        }
+
+        let kick = AdminCommand::KickPlayer {
+            game_id,
+            player_id: bob,
+        };
+        assert_eq!(
+            server.handle_admin(request(kick.clone())).await,
+            AdminResponse::PlayerKicked {
+                game_id,
+                player_id: bob
+            }
+        );
+        assert!(matches!(server.handle_admin(request(kick)).await, AdminResponse::Error { .. }));
+
+        server.handle_message(Message::StartGame { game_id, host: None }).await;
+        match server.handle_admin(request(AdminCommand::EventLog { game_id })).await {
+            AdminResponse::EventLog { events, .. } => {
+                assert!(matches!(events[0], GameEvent::RoundStarted { .. }));
+            }
+            other => panic!("Expected EventLog, got {:?}", other),
+        }
+
+        assert_eq!(
+            server.handle_admin(request(AdminCommand::Drain)).await,
+            AdminResponse::Draining { games_left: 1 }
+        );
+        let create = server
+            .handle_message(Message::CreateGame {
+                rules: None,
+                visibility: Default::default(),
+                turn_clock: None,
+            })
+            .await;
+        assert!(matches!(create, Response::Error { .. }));
+
+        assert_eq!(
+            server.handle_admin(request(AdminCommand::FinishGame { game_id })).await,
+            AdminResponse::GameFinished { game_id }
+        );
+        match server.handle_message(Message::GetGameState { game_id, access: None }).await {
+            Response::GameState { game_state } => {
+                assert_eq!(game_state.outcome.unwrap().winner_ids, vec![alice.to_string()]);
+            }
+            other => panic!("Expected GameState, got {:?}", other),
+        }
+        assert_eq!(
+            server.handle_admin(request(AdminCommand::Drain)).await,
+            AdminResponse::Draining { games_left: 0 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_connection() {
+        let server = GameServer::new();
+        server.set_admin_token(TOKEN.to_string());
+        let transport = AdminTransport::bind("127.0.0.1:0").await.unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(server));
+        let send = |request: &AdminRequest| {
+            let mut line = serde_json::to_vec(request).unwrap();
+            line.push(b'\n');
+            line
+        };
+
+        let (reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(&send(&request(AdminCommand::ListGames))).await.unwrap();
+        let games = lines.next_line().await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_str::<AdminResponse>(&games).unwrap(),
+            AdminResponse::Games { games: Vec::new() }
+        );
+        // A wrong token closes the connection
+        let wrong = AdminRequest {
+            token: "guess".to_string(),
+            command: AdminCommand::ListGames,
+        };
+        writer.write_all(&send(&wrong)).await.unwrap();
+        let refused = lines.next_line().await.unwrap().unwrap();
+        assert!(matches!(
+            serde_json::from_str::<AdminResponse>(&refused).unwrap(),
+            AdminResponse::Error { .. }
+        ));
+        assert_eq!(lines.next_line().await.unwrap(), None);
+
+        // So does a line too long to be a request, before it is all read
+        let (reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(&vec![b'x'; MAX_FRAME_LEN as usize + 1]).await.unwrap();
+        assert!(!matches!(lines.next_line().await, Ok(Some(_))));
+    }
+}
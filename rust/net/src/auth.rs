@@ -0,0 +1,234 @@
+//! Optional authentication. A server given an `Auth` lets clients sign in
+//! with a token, checked by a pluggable `Authenticator`; a signed-in
+//! connection plays under the identity the token stands for, and may only act
+//! for the seats it took. Connections that haven't signed in can be limited
+//! to spectating.
+//!
+//! With the `jwt` feature, `JwtAuthenticator` checks HS256-signed JSON Web
+//! Tokens, as issued by an account service sharing the secret.
+
+use crate::i18n::{text, Text};
+use crate::{Message, PlayerId};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Who a token says the client is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Identity {
+    /// Stable id of the account
+    pub user_id: String,
+    /// Name the account plays under
+    pub name: String,
+}
+
+/// Checks the tokens clients sign in with.
+pub trait Authenticator: Send + Sync {
+    /// The identity the token stands for, or why it was refused.
+    fn authenticate(&self, token: &str) -> Result<Identity, Text>;
+}
+
+/// How a server authenticates its clients.
+#[derive(Clone)]
+pub struct Auth {
+    pub authenticator: Arc<dyn Authenticator>,
+    /// Whether connections that haven't signed in may play, rather than
+    /// only spectate
+    pub guests_may_play: bool,
+}
+
+impl Auth {
+    /// Auth where guests can only spectate.
+    pub fn required(authenticator: impl Authenticator + 'static) -> Self {
+        Self {
+            authenticator: Arc::new(authenticator),
+            guests_may_play: false,
+        }
+    }
+}
+
+/// Whether a connection that hasn't signed in may send the message: it can
+/// look around, but not take a seat or act for one.
+pub(crate) fn guest_may_send(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::Hello { .. }
+            | Message::Authenticate { .. }
+            | Message::ListGames
+            | Message::GetGameState { .. }
+            | Message::AckState { .. }
+            | Message::Spectate { .. }
+            | Message::NegotiateEncoding { .. }
+            | Message::GetLeaderboard { .. }
+            | Message::GetTournament { .. }
+            | Message::Ping
+    )
+}
+
+/// Who a request's bearer token says the client is, for transports without
+/// a connection to sign in on; `None` on servers without an `Auth`, and for
+/// guests allowed to send the message.
+#[cfg(any(feature = "http", feature = "grpc"))]
+pub(crate) fn sign_in_request(
+    auth: Option<&Auth>,
+    token: Option<&str>,
+    message: &Message,
+) -> Result<Option<Identity>, Text> {
+    let Some(auth) = auth else {
+        return Ok(None);
+    };
+    match token {
+        Some(token) => auth.authenticator.authenticate(token).map(Some),
+        None if auth.guests_may_play || guest_may_send(message) => Ok(None),
+        None => Err(text!("sign_in_to_play")),
+    }
+}
+
+/// The player a message acts for, if it names one.
+pub(crate) fn acting_player(message: &Message) -> Option<Result<PlayerId, Text>> {
+    let player_id = match message {
+        Message::MakeMove { game_move, .. } => match game_move {
+            game_core::GameMove::Draw { player_id } | game_core::GameMove::Stay { player_id } => {
+                return Some(player_id.parse().map_err(|_| text!("invalid_player_id", player_id)));
+            }
+        },
+        Message::LeaveGame { player_id, .. }
+        | Message::VoteSkipTurn {
+            voter_id: player_id,
+            ..
+        }
+        | Message::Reaction { player_id, .. }
+        | Message::Ready { player_id, .. }
+        | Message::RequestRematch { player_id, .. }
+        | Message::LeaveQueue { player_id } => *player_id,
+        _ => return None,
+    };
+    Some(Ok(player_id))
+}
+
+#[cfg(feature = "jwt")]
+pub use jwt::{Claims, JwtAuthenticator};
+
+#[cfg(feature = "jwt")]
+mod jwt {
+    use super::{Authenticator, Identity};
+    use crate::i18n::{text, Text};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use hmac::{Hmac, Mac};
+    use serde::{Deserialize, Serialize};
+    use sha2::Sha256;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+    /// What a token says, as registered JWT claims.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Claims {
+        /// The account's user id
+        pub sub: String,
+        pub name: String,
+        /// Expiry, in seconds since the Unix epoch
+        pub exp: u64,
+    }
+
+    #[derive(Deserialize)]
+    struct Header {
+        alg: String,
+    }
+
+    /// Checks HS256 JSON Web Tokens signed with a shared secret.
+    pub struct JwtAuthenticator {
+        secret: Vec<u8>,
+    }
+
+    impl JwtAuthenticator {
+        pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+            Self {
+                secret: secret.into(),
+            }
+        }
+
+        /// A token for the claims, e.g. for tests or a development account
+        /// service.
+        pub fn sign(&self, claims: &Claims) -> String {
+            let payload = serde_json::to_vec(claims).expect("claims always serialize");
+            let signed = format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(HEADER),
+                URL_SAFE_NO_PAD.encode(payload)
+            );
+            let signature = URL_SAFE_NO_PAD.encode(self.mac(&signed).finalize().into_bytes());
+            format!("{}.{}", signed, signature)
+        }
+
+        fn mac(&self, signed: &str) -> Hmac<Sha256> {
+            let mut mac =
+                Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any size");
+            mac.update(signed.as_bytes());
+            mac
+        }
+    }
+
+    impl Authenticator for JwtAuthenticator {
+        fn authenticate(&self, token: &str) -> Result<Identity, Text> {
+            let (signed, signature) = token.rsplit_once('.').ok_or(text!("malformed_token"))?;
+            let (header, payload) = signed.split_once('.').ok_or(text!("malformed_token"))?;
+            let signature = URL_SAFE_NO_PAD
+                .decode(signature)
+                .map_err(|_| text!("malformed_token"))?;
+            self.mac(signed)
+                .verify_slice(&signature)
+                .map_err(|_| text!("invalid_signature"))?;
+
+            let decode = |part: &str| {
+                URL_SAFE_NO_PAD
+                    .decode(part)
+                    .map_err(|_| text!("malformed_token"))
+            };
+            let header: Header =
+                serde_json::from_slice(&decode(header)?).map_err(|_| text!("malformed_token"))?;
+            if header.alg != "HS256" {
+                return Err(text!("unsupported_algorithm", header.alg));
+            }
+            let claims: Claims =
+                serde_json::from_slice(&decode(payload)?).map_err(|_| text!("malformed_token"))?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            if claims.exp <= now {
+                return Err(text!("token_expired"));
+            }
+            Ok(Identity {
+                user_id: claims.sub,
+                name: claims.name,
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_jwt() {
+            let authenticator = JwtAuthenticator::new("secret");
+            let claims = Claims {
+                sub: "user-1".to_string(),
+                name: "Alice".to_string(),
+                exp: u64::MAX,
+            };
+            let token = authenticator.sign(&claims);
+            let identity = authenticator.authenticate(&token).unwrap();
+            assert_eq!(identity.user_id, "user-1");
+            assert_eq!(identity.name, "Alice");
+
+            assert!(JwtAuthenticator::new("other").authenticate(&token).is_err());
+            let expired = authenticator.sign(&Claims { exp: 1, ..claims });
+            assert_eq!(
+                authenticator.authenticate(&expired),
+                Err(text!("token_expired"))
+            );
+            assert!(authenticator.authenticate("not-a-token").is_err());
+        }
+    }
+}
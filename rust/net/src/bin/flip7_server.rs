@@ -0,0 +1,335 @@
+//! Game server.
+//!
+//! Serves the wire protocol over TCP until interrupted. Every client is an
+//! `UntrustedPeer`: it may play, but never push game states, and is held to
+//! the default `Limits` on how fast it sends, how many games it is in and how
+//! large its messages are.
+//!
+//! Usage:
+//!   flip7_server [--config PATH] [--<setting> VALUE]...
+//!
+//! Settings come from `flip7-server.toml`, or the file `--config` names, then
+//! `FLIP7_<SETTING>` environment variables, then `--<setting>` options; see
+//! `net::config` for all of them. With none set it listens on 0.0.0.0:7777
+//! with line framing; `length-prefixed` framing also lets clients negotiate
+//! the binary encoding. With `ws_addr` (feature `websocket`) browsers can
+//! join the same games over WebSocket, and with `http_addr` (feature `http`)
+//! web clients and curl can play them through the HTTP API, or as GraphQL at
+//! `/graphql` (feature `graphql`). With `grpc_addr` (feature `grpc`) backend
+//! services can join them over gRPC.
+//! Players who stop sending heartbeats are reported disconnected, and with
+//! `auto_stay_disconnected` the server stays for them when their turn comes.
+//! With `pause_disconnected` their round is paused instead, until they are
+//! all back.
+//! Quick play seats 3 to 4 players a game unless `quick_play_players` says
+//! otherwise, e.g. `2-2` for heads-up games. Ranked quick play seats players
+//! rated within 200 of each other, or `rating_band`, at first. Between
+//! rounds the table waits up to 30 seconds, or `ready_timeout`, for everyone
+//! to be ready.
+//! Finished games are dropped after 30 minutes, or `finished_ttl`, and
+//! lobbies nobody started after an hour, or `lobby_ttl`, and games nobody
+//! has moved in for two hours, or `idle_ttl`. With `db`
+//! (feature `sqlite`) games are kept in an SQLite database, and those in
+//! flight when the server stopped are picked up again on start. With
+//! `redis` (feature `redis`) they are kept in Redis instead, where several
+//! instances behind a load balancer can share them.
+//! With `jwt_secret` set (feature `jwt`), usually as `FLIP7_JWT_SECRET`,
+//! clients sign in with JSON Web Tokens signed with that secret, and those
+//! who don't can only spectate unless `guests_may_play`.
+//! With `tls_cert` and `tls_key` (feature `tls`) TCP and WebSocket
+//! clients connect over TLS with that PEM certificate chain and key.
+//! With `admin_addr` and `admin_token` set, operators can list, finish and
+//! clean up games and drain the server on that port; see `net::admin`.
+//! With `lan_name` set, players on the local network find the server under
+//! that name without typing its address; see `net::lan`.
+//! With `webhooks` (feature `webhooks`), each game being created, started,
+//! scored and finished is POSTed as JSON to those URLs; see `net::webhook`.
+//! Traces go to stderr, filtered by `RUST_LOG` (e.g. `net=debug`); with
+//! `game_core/tracing` enabled they include each move's game events.
+
+use net::{AdminTransport, Auth, GameServer, GameStore, ServerConfig, TcpTransport, Transport, TrustLevel};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config_path = args
+        .iter()
+        .position(|arg| arg == "--config")
+        .map(|i| args.get(i + 1).map(PathBuf::from));
+    let config_path = match config_path {
+        Some(Some(path)) => Some(path),
+        Some(None) => {
+            eprintln!("Usage: flip7_server [--config PATH] [--<setting> VALUE]...");
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
+    let config = match ServerConfig::load(config_path.as_deref(), std::env::vars(), &args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(config).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(config: ServerConfig) -> Result<(), String> {
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => Some(load_tls(cert, key)?),
+        _ => None,
+    };
+
+    let server = GameServer::new();
+    server.set_expiry(config.expiry()).await;
+    server.set_round_flow(config.round_flow()).await;
+    server.set_matchmaking(config.matchmaking()?).await?;
+    server.set_heartbeat(config.heartbeat()).await;
+    server.set_turn_clock(config.turn_clock()).await?;
+    server.set_max_games(config.max_games);
+
+    let store = match (&config.db, &config.redis) {
+        (Some(path), None) => Some((open_sqlite(path), path.display().to_string())),
+        (None, Some(url)) => Some((open_redis(url), url.clone())),
+        (None, None) => None,
+        (Some(_), Some(_)) => return Err("Set either db or redis, not both".to_string()),
+    };
+    if let Some((store, place)) = store {
+        let restored = match store {
+            Ok(store) => server.set_store(store).await,
+            Err(err) => Err(err),
+        };
+        let games = restored.map_err(|err| format!("Cannot use {}: {}", place, err))?;
+        println!("Restored {} games from {}", games.len(), place);
+    }
+    if let Some(secret) = &config.jwt_secret {
+        let auth = jwt_auth(secret.0.clone())
+            .ok_or("jwt_secret is set but the server was built without the jwt feature")?;
+        server.set_auth(Auth {
+            guests_may_play: config.guests_may_play,
+            ..auth
+        });
+    }
+
+    if !config.webhooks.is_empty() {
+        post_webhooks(&server, config.webhooks.clone())?;
+    }
+
+    let listen_error = |addr: SocketAddr| move |err: std::io::Error| format!("Cannot listen on {}: {}", addr, err);
+    if let Some(ws_addr) = config.ws_addr {
+        serve_websocket(server.clone(), ws_addr, tls.clone())
+            .await
+            .map_err(listen_error(ws_addr))?;
+    }
+    if let Some(http_addr) = config.http_addr {
+        serve_http(server.clone(), http_addr)
+            .await
+            .map_err(listen_error(http_addr))?;
+    }
+    if let Some(grpc_addr) = config.grpc_addr {
+        serve_grpc(server.clone(), grpc_addr)
+            .await
+            .map_err(listen_error(grpc_addr))?;
+    }
+    if let (Some(admin_addr), Some(token)) = (config.admin_addr, &config.admin_token) {
+        server.set_admin_token(token.0.clone());
+        serve_admin(server.clone(), admin_addr)
+            .await
+            .map_err(listen_error(admin_addr))?;
+    }
+
+    let transport = TcpTransport::bind(config.addr, TrustLevel::UntrustedPeer, config.framing)
+        .await
+        .map_err(listen_error(config.addr))?;
+    let transport = tcp_with_tls(transport, tls);
+    match transport.local_addr() {
+        Ok(addr) => println!("Listening on {} ({:?} framing)", addr, config.framing),
+        Err(_) => println!("Listening ({:?} framing)", config.framing),
+    }
+    if let Some(name) = config.lan_name {
+        announce(server.clone(), name, config.addr.port());
+    }
+    // Once, however many transports serve the games
+    let timers = server.clone();
+    tokio::spawn(async move { net::run_timers(&timers).await });
+    transport.serve(server).await.map_err(|err| err.to_string())
+}
+
+/// Starts answering players looking for games on the local network in the
+/// background.
+fn announce(server: GameServer, name: String, port: u16) {
+    println!("Announcing as '{}' on the local network", name);
+    tokio::spawn(async move {
+        if let Err(err) = net::lan::announce(server, name, port, net::lan::DISCOVERY_GROUP).await {
+            eprintln!("LAN discovery error: {}", err);
+        }
+    });
+}
+
+/// Auth checking tokens signed with the secret.
+#[cfg(feature = "jwt")]
+fn jwt_auth(secret: String) -> Option<Auth> {
+    Some(Auth::required(net::auth::JwtAuthenticator::new(secret)))
+}
+
+#[cfg(not(feature = "jwt"))]
+fn jwt_auth(_secret: String) -> Option<Auth> {
+    None
+}
+
+/// Starts posting lifecycle events in the background.
+#[cfg(feature = "webhooks")]
+fn post_webhooks(server: &GameServer, urls: Vec<String>) -> Result<(), String> {
+    println!("Posting game events to {} webhooks", urls.len());
+    let webhooks = net::webhook::Webhooks::new(urls)?;
+    tokio::spawn(webhooks.run(server.subscribe_lifecycle()));
+    Ok(())
+}
+
+#[cfg(not(feature = "webhooks"))]
+fn post_webhooks(_server: &GameServer, _urls: Vec<String>) -> Result<(), String> {
+    Err("webhooks are set but the server was built without the webhooks feature".to_string())
+}
+
+/// What TLS connections are accepted with.
+#[cfg(feature = "tls")]
+type Tls = net::tls::TlsAcceptor;
+
+/// Without the tls feature there is nothing to accept TLS connections with.
+#[cfg(not(feature = "tls"))]
+#[derive(Clone)]
+enum Tls {}
+
+#[cfg(feature = "tls")]
+fn load_tls(cert_path: &Path, key_path: &Path) -> Result<Tls, String> {
+    net::TlsConfig {
+        cert_path: cert_path.to_path_buf(),
+        key_path: key_path.to_path_buf(),
+    }
+    .acceptor()
+}
+
+#[cfg(not(feature = "tls"))]
+fn load_tls(_cert_path: &Path, _key_path: &Path) -> Result<Tls, String> {
+    Err("tls_cert is set but the server was built without the tls feature".to_string())
+}
+
+fn tcp_with_tls(transport: TcpTransport, tls: Option<Tls>) -> TcpTransport {
+    match tls {
+        #[cfg(feature = "tls")]
+        Some(tls) => transport.with_tls(tls),
+        #[cfg(not(feature = "tls"))]
+        Some(never) => match never {},
+        None => transport,
+    }
+}
+
+/// The SQLite database at the path.
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &Path) -> Result<Arc<dyn GameStore>, String> {
+    Ok(Arc::new(net::store::SqliteStore::open(path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(_path: &Path) -> Result<Arc<dyn GameStore>, String> {
+    Err("built without the sqlite feature".to_string())
+}
+
+/// The Redis server at the URL.
+#[cfg(feature = "redis")]
+fn open_redis(url: &str) -> Result<Arc<dyn GameStore>, String> {
+    Ok(Arc::new(net::store::RedisStore::open(url)?))
+}
+
+#[cfg(not(feature = "redis"))]
+fn open_redis(_url: &str) -> Result<Arc<dyn GameStore>, String> {
+    Err("built without the redis feature".to_string())
+}
+
+/// Starts serving WebSocket clients in the background.
+#[cfg(feature = "websocket")]
+async fn serve_websocket(server: GameServer, addr: SocketAddr, tls: Option<Tls>) -> std::io::Result<()> {
+    let transport = net::WebSocketTransport::bind(addr, TrustLevel::UntrustedPeer).await?;
+    let transport = match tls {
+        #[cfg(feature = "tls")]
+        Some(tls) => transport.with_tls(tls),
+        #[cfg(not(feature = "tls"))]
+        Some(never) => match never {},
+        None => transport,
+    };
+    println!("Listening for WebSocket clients on {}", transport.local_addr()?);
+    tokio::spawn(async move {
+        if let Err(err) = transport.serve(server).await {
+            eprintln!("WebSocket error: {}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "websocket"))]
+async fn serve_websocket(_server: GameServer, _addr: SocketAddr, _tls: Option<Tls>) -> std::io::Result<()> {
+    Err(std::io::Error::other("built without the websocket feature"))
+}
+
+/// Starts serving the HTTP API in the background.
+#[cfg(feature = "http")]
+async fn serve_http(server: GameServer, addr: SocketAddr) -> std::io::Result<()> {
+    let transport = net::HttpTransport::bind(addr, TrustLevel::UntrustedPeer).await?;
+    println!("Listening for HTTP requests on {}", transport.local_addr()?);
+    tokio::spawn(async move {
+        if let Err(err) = transport.serve(server).await {
+            eprintln!("HTTP error: {}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+async fn serve_http(_server: GameServer, _addr: SocketAddr) -> std::io::Result<()> {
+    Err(std::io::Error::other("built without the http feature"))
+}
+
+/// Starts serving the gRPC service in the background.
+#[cfg(feature = "grpc")]
+async fn serve_grpc(server: GameServer, addr: SocketAddr) -> std::io::Result<()> {
+    let transport = net::GrpcTransport::bind(addr, TrustLevel::UntrustedPeer).await?;
+    println!("Listening for gRPC clients on {}", transport.local_addr()?);
+    tokio::spawn(async move {
+        if let Err(err) = transport.serve(server).await {
+            eprintln!("gRPC error: {}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "grpc"))]
+async fn serve_grpc(_server: GameServer, _addr: SocketAddr) -> std::io::Result<()> {
+    Err(std::io::Error::other("built without the grpc feature"))
+}
+
+/// Starts serving operators in the background.
+async fn serve_admin(server: GameServer, addr: SocketAddr) -> std::io::Result<()> {
+    let transport = AdminTransport::bind(addr).await?;
+    println!("Listening for operators on {}", transport.local_addr()?);
+    tokio::spawn(async move {
+        if let Err(err) = transport.serve(server).await {
+            eprintln!("Admin error: {}", err);
+        }
+    });
+    Ok(())
+}
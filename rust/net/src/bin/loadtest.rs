@@ -0,0 +1,293 @@
+//! Load-testing harness.
+//!
+//! Plays the tables of a predefined load profile against a server over the
+//! line-delimited JSON TCP transport and reports throughput and latency.
+//!
+//! Usage:
+//!   loadtest --profile <casual-evening|tournament-burst|correspondence-heavy>
+//!            [--addr HOST:PORT] [--out report.json] [--compare baseline.json]
+//!
+//! Without `--addr` an in-process server is started on localhost. Reports
+//! written with `--out` can be passed to `--compare` on a later run.
+
+use game_core::{GameMove, GameState};
+use net::load::{LoadProfile, LoadReport, ProfileSpec};
+use net::{lan, GameId, GameServer, Message, MoveId, Response, TrustLevel};
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Default)]
+struct Samples {
+    latencies_us: Vec<u64>,
+    errors: u64,
+}
+
+/// One player's connection to the server.
+struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    samples: Arc<Mutex<Samples>>,
+}
+
+impl Client {
+    async fn connect(addr: SocketAddr, samples: Arc<Mutex<Samples>>) -> Result<Self, String> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            samples,
+        })
+    }
+
+    async fn request(&mut self, message: &Message) -> Result<Response, String> {
+        let mut line = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+
+        let started = Instant::now();
+        self.writer
+            .write_all(&line)
+            .await
+            .map_err(|e| format!("Send failed: {}", e))?;
+        let response = loop {
+            let reply = self
+                .lines
+                .next_line()
+                .await
+                .map_err(|e| format!("Receive failed: {}", e))?
+                .ok_or("Server closed the connection")?;
+            match serde_json::from_str(&reply).map_err(|e| format!("Invalid response: {}", e))? {
+                // Pushed about the table; the players poll instead
+                Response::StateUpdate { .. }
+                | Response::PlayerDisconnected { .. }
+                | Response::PlayerReconnected { .. } => continue,
+                response => break response,
+            }
+        };
+        let latency = started.elapsed();
+
+        let mut samples = self.samples.lock().unwrap();
+        samples.latencies_us.push(latency.as_micros() as u64);
+        if matches!(response, Response::Error { .. }) {
+            samples.errors += 1;
+        }
+        Ok(response)
+    }
+}
+
+/// Seats a table, then plays its rounds with every bot drawing below 15.
+async fn play_table(
+    addr: SocketAddr,
+    spec: ProfileSpec,
+    samples: Arc<Mutex<Samples>>,
+) -> Result<(), String> {
+    let mut clients = Vec::new();
+    let mut player_ids = Vec::new();
+    let mut game_id: Option<GameId> = None;
+    let mut host = None;
+
+    for seat in 0..spec.players_per_table {
+        let mut client = Client::connect(addr, samples.clone()).await?;
+        match client
+            .request(&Message::JoinGame {
+                player_name: format!("Load {}", seat),
+                game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            })
+            .await?
+        {
+            Response::GameJoined {
+                game_id: id,
+                player_id,
+                session_token,
+                ..
+            } => {
+                game_id = Some(id);
+                // The first player hosts the table, and starts its rounds
+                host = host.or(session_token);
+                player_ids.push(player_id);
+            }
+            other => return Err(format!("Join failed: {:?}", other)),
+        }
+        clients.push(client);
+    }
+    let game_id = game_id.ok_or("Table has no players")?;
+
+    for _ in 0..spec.rounds {
+        clients[0]
+            .request(&Message::StartGame { game_id, host })
+            .await?;
+
+        loop {
+            let game = state(&mut clients[0], game_id).await?;
+            if game.round_state.is_finished {
+                break;
+            }
+
+            let seat = game.round_state.current_player_index;
+            let player = &game.players[seat];
+            let player_id = player_ids[seat].to_string();
+            let game_move = if !player.has_stayed && player.hand.total_value() < 15 {
+                GameMove::Draw { player_id }
+            } else {
+                GameMove::Stay { player_id }
+            };
+
+            tokio::time::sleep(spec.think_time).await;
+            clients[seat]
+                .request(&Message::MakeMove {
+                    game_id,
+                    game_move,
+                    move_id: Some(MoveId::new()),
+                })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn state(client: &mut Client, game_id: GameId) -> Result<GameState, String> {
+    match client
+        .request(&Message::GetGameState { game_id, access: None })
+        .await?
+    {
+        Response::GameState { game_state } => Ok(*game_state),
+        other => Err(format!("GetGameState failed: {:?}", other)),
+    }
+}
+
+async fn run(profile: LoadProfile, addr: Option<SocketAddr>) -> Result<LoadReport, String> {
+    let addr = match addr {
+        Some(addr) => addr,
+        None => {
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .map_err(|e| format!("Failed to bind: {}", e))?;
+            let addr = listener.local_addr().map_err(|e| e.to_string())?;
+            // The harness starts its own games, so it connects as a trusted relay
+            tokio::spawn(lan::serve(
+                GameServer::new(),
+                listener,
+                TrustLevel::TrustedRelay,
+            ));
+            addr
+        }
+    };
+
+    let spec = profile.spec();
+    let samples = Arc::new(Mutex::new(Samples::default()));
+    let ramp_step = spec.ramp_up / spec.tables.max(1) as u32;
+    let started = Instant::now();
+
+    let mut tables = Vec::new();
+    for table in 0..spec.tables {
+        let spec = spec.clone();
+        let samples = samples.clone();
+        let delay = ramp_step * table as u32;
+        tables.push(tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            play_table(addr, spec, samples).await
+        }));
+    }
+
+    let mut failed_tables = 0;
+    for table in tables {
+        match table.await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                failed_tables += 1;
+                eprintln!("Table failed: {}", err);
+            }
+            Err(err) => {
+                failed_tables += 1;
+                eprintln!("Table panicked: {}", err);
+            }
+        }
+    }
+
+    let duration = started.elapsed();
+    let samples = std::mem::take(&mut *samples.lock().unwrap());
+    Ok(LoadReport::new(
+        profile,
+        samples.latencies_us,
+        samples.errors + failed_tables,
+        duration,
+    ))
+}
+
+fn usage() -> ExitCode {
+    let names: Vec<&str> = LoadProfile::ALL.iter().map(|p| p.name()).collect();
+    eprintln!(
+        "Usage: loadtest --profile <{}> [--addr HOST:PORT] [--out FILE] [--compare FILE]",
+        names.join("|")
+    );
+    ExitCode::FAILURE
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+
+    let Some(profile) = option("--profile").and_then(|name| LoadProfile::from_name(&name)) else {
+        return usage();
+    };
+    let addr = match option("--addr").map(|addr| addr.parse::<SocketAddr>()) {
+        Some(Ok(addr)) => Some(addr),
+        Some(Err(_)) => return usage(),
+        None => None,
+    };
+
+    let report = match run(profile, addr).await {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let json = report.to_json().expect("reports always serialize");
+    println!("{}", json);
+
+    if let Some(path) = option("--out") {
+        if let Err(err) = std::fs::write(&path, &json) {
+            eprintln!("Failed to write {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(path) = option("--compare") {
+        let baseline = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| LoadReport::from_json(&json).map_err(|e| e.to_string()));
+        match baseline.and_then(|baseline| report.compare(&baseline)) {
+            Ok(lines) => {
+                println!("Compared with {}:", path);
+                for line in lines {
+                    println!("  {}", line);
+                }
+            }
+            Err(err) => {
+                eprintln!("Cannot compare with {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,337 @@
+//! Self-play regression farm.
+//!
+//! Plays bot games through the wire protocol and checks every step against a
+//! reference copy of the last released rules: which moves are legal for whom,
+//! and how each finished round is scored. Any divergence is printed, so a
+//! subtle rules change that slipped into the engine gets noticed.
+//!
+//! The reference lives in `released` below and must only change when a new
+//! rules version is released.
+//!
+//! Usage: `selfplay [--games N]` (runs until interrupted without `--games`).
+//! Exits with status 1 if any divergence was found.
+
+use game_core::{GameMove, GameState};
+use net::{Encoding, GameId, Message, ProtocolEngine, Response, TrustLevel};
+use std::process::ExitCode;
+
+/// Safety net against games that never reach the target score.
+const MAX_ROUNDS: u32 = 500;
+
+/// Frozen copy of the released (version 1) rules.
+mod released {
+    use game_core::{Card, GameMove, GameState};
+
+    pub fn score(cards: &[Card]) -> u32 {
+        let total: u32 = cards.iter().map(|card| card.value() as u32).sum();
+
+        // Subset sums reachable with the hand, as a bitset
+        let mut sums: u32 = 1;
+        for card in cards {
+            if card.value() <= 7 {
+                sums |= sums << card.value();
+            }
+        }
+
+        if sums & (1 << 7) != 0 {
+            21
+        } else if total > 21 {
+            0
+        } else {
+            total
+        }
+    }
+
+    /// Moves a player may make right now.
+    pub fn legal_moves(game: &GameState, player_id: &str) -> Vec<GameMove> {
+        let round = &game.round_state;
+        let Some(current) = game.current_player() else {
+            return Vec::new();
+        };
+        if round.is_finished || current.id != player_id {
+            return Vec::new();
+        }
+
+        let mut moves = vec![GameMove::Stay {
+            player_id: player_id.to_string(),
+        }];
+        if !current.has_stayed && !game.deck.is_empty() {
+            moves.push(GameMove::Draw {
+                player_id: player_id.to_string(),
+            });
+        }
+        moves
+    }
+}
+
+struct Table {
+    engine: ProtocolEngine,
+    game_id: GameId,
+    /// In seat order, as the game knows them
+    player_ids: Vec<String>,
+    divergences: Vec<String>,
+}
+
+impl Table {
+    fn new(players: usize) -> Result<Self, String> {
+        let mut table = Self {
+            engine: ProtocolEngine::new(),
+            // Replaced by the id of the game the first bot creates
+            game_id: GameId::new(),
+            player_ids: Vec::new(),
+            divergences: Vec::new(),
+        };
+
+        for seat in 0..players {
+            let game_id = (seat > 0).then_some(table.game_id);
+            match table.send(Message::JoinGame {
+                player_name: format!("Bot {}", seat),
+                game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            }) {
+                Response::GameJoined {
+                    game_id, player_id, ..
+                } => {
+                    table.game_id = game_id;
+                    table.player_ids.push(player_id.to_string());
+                }
+                other => return Err(format!("Join failed: {:?}", other)),
+            }
+        }
+        Ok(table)
+    }
+
+    /// Sends a message over the JSON wire encoding, as a remote client would.
+    fn send(&mut self, message: Message) -> Response {
+        let bytes = Encoding::Json
+            .encode(&message)
+            .expect("messages always encode");
+        let reply =
+            self.engine
+                .handle_bytes(Encoding::Json, TrustLevel::AuthoritativeServer, &bytes);
+        Encoding::Json
+            .decode(&reply)
+            .unwrap_or_else(|err| Response::Error { message: err.into() })
+    }
+
+    fn state(&mut self) -> Result<GameState, String> {
+        match self.send(Message::GetGameState {
+            game_id: self.game_id,
+            access: None,
+        }) {
+            Response::GameState { game_state } => Ok(*game_state),
+            other => Err(format!("GetGameState failed: {:?}", other)),
+        }
+    }
+
+    fn start_round(&mut self) -> Result<(), String> {
+        match self.send(Message::StartGame {
+            game_id: self.game_id,
+            host: None,
+        }) {
+            Response::GameStarted { .. } => Ok(()),
+            other => Err(format!("StartGame failed: {:?}", other)),
+        }
+    }
+
+    fn diverged(&mut self, what: String) {
+        self.divergences.push(what);
+    }
+
+    /// Submits every move the released rules forbid and checks each is rejected.
+    /// Rejected moves don't change the game, so this is safe to do every turn.
+    fn check_illegal_moves_rejected(&mut self, game: &GameState) -> Result<(), String> {
+        for player_id in self.player_ids.clone() {
+            let legal = released::legal_moves(game, &player_id);
+            let candidates = [
+                GameMove::Draw {
+                    player_id: player_id.clone(),
+                },
+                GameMove::Stay {
+                    player_id: player_id.clone(),
+                },
+            ];
+
+            for game_move in candidates {
+                if legal.contains(&game_move) {
+                    continue;
+                }
+                let response = self.send(Message::MakeMove {
+                    game_id: self.game_id,
+                    game_move: game_move.clone(),
+                    move_id: None,
+                });
+                if !matches!(response, Response::Error { .. }) {
+                    // The game has moved on from the state we were checking
+                    return Err(format!(
+                        "round {}: illegal {:?} was accepted",
+                        game.round_state.round_number, game_move
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_round_scores(&mut self, game: &GameState, totals: &mut [u32]) {
+        let Some(summary) = game.history.last() else {
+            self.diverged("finished round was not scored".to_string());
+            return;
+        };
+
+        for result in &summary.players {
+            let Some(seat) = self
+                .player_ids
+                .iter()
+                .position(|id| *id == result.player_id)
+            else {
+                continue;
+            };
+            let expected = released::score(&result.cards);
+            if result.round_score != expected {
+                self.diverged(format!(
+                    "round {}: {:?} scored {} instead of {}",
+                    summary.round_number,
+                    result
+                        .cards
+                        .iter()
+                        .map(|card| card.value())
+                        .collect::<Vec<_>>(),
+                    result.round_score,
+                    expected
+                ));
+            }
+            totals[seat] += expected;
+            if result.total_score != totals[seat] {
+                self.diverged(format!(
+                    "round {}: total {} instead of {}",
+                    summary.round_number, result.total_score, totals[seat]
+                ));
+                totals[seat] = result.total_score;
+            }
+        }
+    }
+}
+
+/// Bots draw until their hand reaches a per-seat threshold, varied per game
+/// so the farm covers cautious and reckless play.
+fn choose_move(game: &GameState, player_id: &str, threshold: u8) -> GameMove {
+    let player = game
+        .players
+        .iter()
+        .find(|p| p.id == player_id)
+        .expect("bot is seated");
+    let total: u8 = player.hand.cards.iter().map(|card| card.value()).sum();
+
+    let draw = GameMove::Draw {
+        player_id: player_id.to_string(),
+    };
+    if total < threshold && released::legal_moves(game, player_id).contains(&draw) {
+        draw
+    } else {
+        GameMove::Stay {
+            player_id: player_id.to_string(),
+        }
+    }
+}
+
+/// Plays one game to the end and returns the divergences found.
+fn play_game(game_number: u64) -> Result<Vec<String>, String> {
+    let players = 2 + (game_number % 3) as usize;
+    let mut table = Table::new(players)?;
+    let mut totals = vec![0u32; players];
+    table.start_round()?;
+
+    loop {
+        let game = table.state()?;
+
+        if game.round_state.is_finished {
+            table.check_round_scores(&game, &mut totals);
+            if game.outcome.is_some() {
+                break;
+            }
+            if game.round_state.round_number > MAX_ROUNDS {
+                table.diverged(format!("no winner after {} rounds", MAX_ROUNDS));
+                break;
+            }
+            table.start_round()?;
+            continue;
+        }
+
+        if let Err(err) = table.check_illegal_moves_rejected(&game) {
+            table.diverged(err);
+            break;
+        }
+
+        let seat = game.round_state.current_player_index;
+        let player_id = game.players[seat].id.clone();
+        let threshold = 10 + ((game_number + seat as u64 * 3) % 12) as u8;
+        let game_move = choose_move(&game, &player_id, threshold);
+
+        match table.send(Message::MakeMove {
+            game_id: table.game_id,
+            game_move: game_move.clone(),
+            move_id: None,
+        }) {
+            Response::MoveAccepted { state_hash, .. } => {
+                if state_hash != table.state()?.state_hash() {
+                    table.diverged(format!("state hash mismatch after {:?}", game_move));
+                }
+            }
+            other => {
+                table.diverged(format!("legal {:?} was rejected: {:?}", game_move, other));
+                break;
+            }
+        }
+    }
+
+    Ok(table.divergences)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let games = match args.iter().position(|arg| arg == "--games") {
+        Some(i) => match args.get(i + 1).and_then(|n| n.parse::<u64>().ok()) {
+            Some(n) => Some(n),
+            None => {
+                eprintln!("Usage: selfplay [--games N]");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    let mut diverged_games = 0;
+    let mut game_number = 0;
+    while games.is_none_or(|n| game_number < n) {
+        match play_game(game_number) {
+            Ok(divergences) => {
+                if !divergences.is_empty() {
+                    diverged_games += 1;
+                }
+                for divergence in divergences {
+                    println!("DIVERGENCE game {}: {}", game_number, divergence);
+                }
+            }
+            Err(err) => {
+                diverged_games += 1;
+                println!("ERROR game {}: {}", game_number, err);
+            }
+        }
+
+        game_number += 1;
+        if game_number % 100 == 0 {
+            println!("{} games played, {} diverged", game_number, diverged_games);
+        }
+    }
+
+    println!("{} games played, {} diverged", game_number, diverged_games);
+    if diverged_games > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,49 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Wire encoding of messages and responses, agreed on at handshake time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    Json,
+    Binary,
+}
+
+impl Encoding {
+    /// Encodings this build can speak, most preferred first.
+    pub fn supported() -> Vec<Encoding> {
+        if cfg!(feature = "binary") {
+            vec![Encoding::Binary, Encoding::Json]
+        } else {
+            vec![Encoding::Json]
+        }
+    }
+
+    /// Picks the server's most preferred encoding among those the client offers.
+    /// Falls back to JSON, which every client understands.
+    pub fn negotiate(offered: &[Encoding]) -> Encoding {
+        Self::supported()
+            .into_iter()
+            .find(|encoding| offered.contains(encoding))
+            .unwrap_or(Encoding::Json)
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            #[cfg(feature = "binary")]
+            Encoding::Binary => postcard::to_allocvec(value).map_err(|e| e.to_string()),
+            #[cfg(not(feature = "binary"))]
+            Encoding::Binary => Err("Binary encoding is not enabled".to_string()),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            Encoding::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            #[cfg(feature = "binary")]
+            Encoding::Binary => postcard::from_bytes(bytes).map_err(|e| e.to_string()),
+            #[cfg(not(feature = "binary"))]
+            Encoding::Binary => Err("Binary encoding is not enabled".to_string()),
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! How a server is set up, read from `flip7-server.toml` with overrides from
+//! the environment and the command line.
+//!
+//! Every setting is a top-level key of the file. `FLIP7_<KEY>` environment
+//! variables override the file, and `--<key>` options (dashes for
+//! underscores) override both, e.g. `ws_addr`, `FLIP7_WS_ADDR` and
+//! `--ws-addr`. Outside the file, values of number and boolean settings are
+//! read as TOML and those of the rest taken as given, so
+//! `FLIP7_MAX_GAMES=500` is a number and `FLIP7_ADMIN_TOKEN=123456` a string.
+
+use crate::{Expiry, Framing, Heartbeat, Matchmaking, RoundFlow, TurnClock};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The file read when none is named.
+pub const DEFAULT_CONFIG_PATH: &str = "flip7-server.toml";
+
+/// Prefix of the environment variables overriding settings.
+pub const ENV_PREFIX: &str = "FLIP7_";
+
+/// Every setting, as named in the file.
+const KEYS: &[&str] = &[
+    "addr",
+    "framing",
+    "ws_addr",
+    "http_addr",
+    "grpc_addr",
+    "admin_addr",
+    "lan_name",
+    "max_games",
+    "turn_timeout",
+    "ready_timeout",
+    "finished_ttl",
+    "lobby_ttl",
+    "idle_ttl",
+    "quick_play_players",
+    "rating_band",
+    "auto_stay_disconnected",
+    "pause_disconnected",
+    "guests_may_play",
+    "db",
+    "redis",
+    "tls_cert",
+    "tls_key",
+    "jwt_secret",
+    "admin_token",
+    "webhooks",
+];
+
+/// Settings holding lists, which are given comma-separated outside the file.
+const LIST_KEYS: &[&str] = &["webhooks"];
+
+/// Settings holding numbers or booleans, which are read as TOML outside the
+/// file. Every other setting is a string there, taken as given.
+const VALUE_KEYS: &[&str] = &[
+    "max_games",
+    "turn_timeout",
+    "ready_timeout",
+    "finished_ttl",
+    "lobby_ttl",
+    "idle_ttl",
+    "rating_band",
+    "auto_stay_disconnected",
+    "pause_disconnected",
+    "guests_may_play",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Where TCP clients connect
+    pub addr: SocketAddr,
+    pub framing: Framing,
+    /// Where WebSocket clients connect, if anywhere (feature `websocket`)
+    pub ws_addr: Option<SocketAddr>,
+    /// Where the HTTP API is served, if anywhere (feature `http`)
+    pub http_addr: Option<SocketAddr>,
+    /// Where gRPC clients connect, if anywhere (feature `grpc`)
+    pub grpc_addr: Option<SocketAddr>,
+    /// Where operators connect, if anywhere; needs `admin_token`
+    pub admin_addr: Option<SocketAddr>,
+    /// Name the server is announced under to players on the local network,
+    /// if it is; see `lan::announce`
+    pub lan_name: Option<String>,
+    /// Most games the server holds at once; new ones are refused beyond it
+    pub max_games: Option<usize>,
+    /// Seconds a player has for each turn in games created without a turn
+    /// clock of their own; unlimited if unset
+    pub turn_timeout: Option<u32>,
+    /// Seconds the table waits between rounds for everyone to be ready
+    pub ready_timeout: u64,
+    /// Minutes finished games are kept
+    pub finished_ttl: u64,
+    /// Minutes lobbies nobody started are kept
+    pub lobby_ttl: u64,
+    /// Minutes games in progress nobody moves in are kept
+    pub idle_ttl: u64,
+    /// Players quick play seats together, as `MIN-MAX`
+    pub quick_play_players: String,
+    /// How far apart the ratings of players ranked quick play seats together
+    /// may be, at first; see `Matchmaking::rating_band`
+    pub rating_band: u32,
+    /// Whether the server stays for disconnected players when their turn comes
+    pub auto_stay_disconnected: bool,
+    /// Whether rounds are paused while any of their players is disconnected
+    pub pause_disconnected: bool,
+    /// Whether clients that didn't sign in may play rather than only spectate
+    pub guests_may_play: bool,
+    /// SQLite database games are kept in (feature `sqlite`)
+    pub db: Option<PathBuf>,
+    /// Redis server games are kept in (feature `redis`)
+    pub redis: Option<String>,
+    /// PEM certificate chain TCP and WebSocket clients connect with (feature `tls`)
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of `tls_cert`
+    pub tls_key: Option<PathBuf>,
+    /// Secret JWTs are signed with; clients must sign in when set (feature `jwt`)
+    pub jwt_secret: Option<Secret>,
+    /// Token operators send admin requests with; see `admin`
+    pub admin_token: Option<Secret>,
+    /// URLs game lifecycle events are POSTed to (feature `webhooks`); see
+    /// `webhook`
+    pub webhooks: Vec<String>,
+}
+
+/// A setting kept out of `Debug` output and logs.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let expiry = Expiry::default();
+        let matchmaking = Matchmaking::default();
+        Self {
+            addr: SocketAddr::from(([0, 0, 0, 0], 7777)),
+            framing: Framing::Lines,
+            ws_addr: None,
+            http_addr: None,
+            grpc_addr: None,
+            admin_addr: None,
+            lan_name: None,
+            max_games: None,
+            turn_timeout: None,
+            ready_timeout: RoundFlow::default().ready_timeout.as_secs(),
+            finished_ttl: expiry.finished_ttl.as_secs() / 60,
+            lobby_ttl: expiry.lobby_ttl.as_secs() / 60,
+            idle_ttl: expiry.idle_ttl.as_secs() / 60,
+            quick_play_players: format!("{}-{}", matchmaking.min_players, matchmaking.max_players),
+            rating_band: matchmaking.rating_band,
+            auto_stay_disconnected: false,
+            pause_disconnected: false,
+            guests_may_play: false,
+            db: None,
+            redis: None,
+            tls_cert: None,
+            tls_key: None,
+            jwt_secret: None,
+            admin_token: None,
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads the file at `path`, or `flip7-server.toml` if there is one,
+    /// applies the `FLIP7_*` variables of `env` and then the `--key value`
+    /// options of `args`, and validates the result.
+    pub fn load(
+        path: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+        args: &[String],
+    ) -> Result<Self, String> {
+        let mut table = match path {
+            Some(path) => read_table(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => read_table(Path::new(DEFAULT_CONFIG_PATH))?,
+            None => toml::Table::new(),
+        };
+
+        for (name, value) in env {
+            let Some(key) = name.strip_prefix(ENV_PREFIX).map(str::to_lowercase) else {
+                continue;
+            };
+            // Other tools may share the prefix
+            if KEYS.contains(&key.as_str()) {
+                let value = override_value(&key, &value);
+                table.insert(key, value);
+            }
+        }
+
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                return Err(format!("Unexpected argument '{}'", arg));
+            };
+            let key = name.replace('-', "_");
+            if key == "config" {
+                args.next();
+                continue;
+            }
+            if !KEYS.contains(&key.as_str()) {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+            // Options without a value are switches turned on
+            let value = match args.next_if(|next| !next.starts_with("--")) {
+                Some(value) => override_value(&key, value),
+                None => toml::Value::Boolean(true),
+            };
+            table.insert(key, value);
+        }
+
+        let config: Self = toml::Value::Table(table)
+            .try_into()
+            .map_err(|err| format!("Invalid setting: {}", err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks settings that are well-formed but don't make sense together.
+    pub fn validate(&self) -> Result<(), String> {
+        self.matchmaking()?.validate()?;
+        if let Some(clock) = self.turn_clock() {
+            clock.validate().map_err(|err| format!("turn_timeout: {}", err))?;
+        }
+        if self.max_games == Some(0) {
+            return Err("max_games must be at least 1".to_string());
+        }
+        if self.db.is_some() && self.redis.is_some() {
+            return Err("Set either db or redis, not both".to_string());
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return Err("tls_cert and tls_key must be set together".to_string());
+        }
+        if let Some(url) = self
+            .webhooks
+            .iter()
+            .find(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            return Err(format!("webhooks must be http:// or https:// URLs, not '{}'", url));
+        }
+        if self.admin_addr.is_some() && self.admin_token.is_none() {
+            return Err(format!(
+                "admin_addr needs admin_token, e.g. from {}ADMIN_TOKEN",
+                ENV_PREFIX
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn expiry(&self) -> Expiry {
+        Expiry {
+            finished_ttl: Duration::from_secs(self.finished_ttl * 60),
+            lobby_ttl: Duration::from_secs(self.lobby_ttl * 60),
+            idle_ttl: Duration::from_secs(self.idle_ttl * 60),
+        }
+    }
+
+    pub fn round_flow(&self) -> RoundFlow {
+        RoundFlow {
+            ready_timeout: Duration::from_secs(self.ready_timeout),
+        }
+    }
+
+    pub fn matchmaking(&self) -> Result<Matchmaking, String> {
+        let range = self
+            .quick_play_players
+            .split_once('-')
+            .and_then(|(min, max)| Some((min.trim().parse().ok()?, max.trim().parse().ok()?)));
+        let Some((min_players, max_players)) = range else {
+            return Err(format!(
+                "quick_play_players must look like 3-4, not '{}'",
+                self.quick_play_players
+            ));
+        };
+        Ok(Matchmaking {
+            min_players,
+            max_players,
+            rating_band: self.rating_band,
+            ..Matchmaking::default()
+        })
+    }
+
+    pub fn heartbeat(&self) -> Heartbeat {
+        Heartbeat {
+            auto_stay: self.auto_stay_disconnected,
+            pause: self.pause_disconnected,
+            ..Heartbeat::default()
+        }
+    }
+
+    /// The clock of games created without one.
+    pub fn turn_clock(&self) -> Option<TurnClock> {
+        self.turn_timeout.map(|seconds| TurnClock {
+            seconds,
+            auto_play: Default::default(),
+        })
+    }
+}
+
+fn read_table(path: &Path) -> Result<toml::Table, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+    text.parse()
+        .map_err(|err| format!("Invalid {}: {}", path.display(), err))
+}
+
+/// A value given outside the file: split at commas for a list, TOML for a
+/// number or boolean, and the string as given for anything else, so tokens
+/// and paths that look like numbers or quoted strings stay as they are.
+fn override_value(key: &str, raw: &str) -> toml::Value {
+    if LIST_KEYS.contains(&key) {
+        return toml::Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| toml::Value::String(item.to_string()))
+                .collect(),
+        );
+    }
+    if !VALUE_KEYS.contains(&key) {
+        return toml::Value::String(raw.to_string());
+    }
+    // One that doesn't parse is refused as the wrong type for the setting
+    format!("value = {}", raw)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults_match_the_server() {
+        let config = ServerConfig::load(Some(Path::new("/dev/null")), [], &[]).unwrap();
+        assert_eq!(config, ServerConfig::default());
+        assert_eq!(config.expiry(), Expiry::default());
+        assert_eq!(config.round_flow(), RoundFlow::default());
+        assert_eq!(config.matchmaking().unwrap(), Matchmaking::default());
+        assert_eq!(config.heartbeat(), Heartbeat::default());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let path = std::env::temp_dir().join(format!("flip7-server-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "addr = \"127.0.0.1:9000\"\nmax_games = 10\nframing = \"length-prefixed\"\n",
+        )
+        .unwrap();
+        let config = ServerConfig::load(
+            Some(&path),
+            env(&[
+                ("FLIP7_MAX_GAMES", "20"),
+                ("FLIP7_WS_ADDR", "127.0.0.1:9001"),
+                ("FLIP7_UNRELATED", "ignored"),
+                ("FLIP7_WEBHOOKS", "https://hooks.example/a, http://10.0.0.2/b"),
+                ("FLIP7_RATING_BAND", "150"),
+                ("FLIP7_PAUSE_DISCONNECTED", "true"),
+                ("HOME", "/root"),
+            ]),
+            &args(&["--max-games", "30", "--auto-stay-disconnected", "--ready-timeout", "5"]),
+        );
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.addr, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(config.framing, Framing::LengthPrefixed);
+        assert_eq!(config.ws_addr, Some("127.0.0.1:9001".parse().unwrap()));
+        assert_eq!(config.max_games, Some(30));
+        assert!(config.auto_stay_disconnected);
+        assert!(config.heartbeat().pause);
+        assert_eq!(config.round_flow().ready_timeout, Duration::from_secs(5));
+        assert_eq!(config.webhooks, ["https://hooks.example/a", "http://10.0.0.2/b"]);
+        assert_eq!(config.matchmaking().unwrap().rating_band, 150);
+    }
+
+    #[test]
+    fn test_helpful_errors() {
+        let load = |vars: &[(&str, &str)], options: &[&str]| ServerConfig::load(None, env(vars), &args(options));
+
+        let err = load(&[("FLIP7_ADDR", "nowhere")], &[]).unwrap_err();
+        assert!(err.contains("addr"), "{}", err);
+        let err = load(&[], &["--no-such-thing", "1"]).unwrap_err();
+        assert!(err.contains("--no-such-thing"), "{}", err);
+        let err = load(&[], &["--quick-play-players", "lots"]).unwrap_err();
+        assert!(err.contains("quick_play_players"), "{}", err);
+        let err = load(&[("FLIP7_WEBHOOKS", "hooks.example")], &[]).unwrap_err();
+        assert!(err.contains("webhooks"), "{}", err);
+        let err = load(&[], &["--turn-timeout", "0"]).unwrap_err();
+        assert!(err.contains("turn_timeout"), "{}", err);
+        let err = load(&[], &["--tls-cert", "cert.pem"]).unwrap_err();
+        assert!(err.contains("tls_key"), "{}", err);
+        let err = load(&[("FLIP7_DB", "games.db")], &["--redis", "redis://127.0.0.1/"]).unwrap_err();
+        assert!(err.contains("db or redis"), "{}", err);
+        let err = load(&[], &["--admin-addr", "127.0.0.1:9002"]).unwrap_err();
+        assert!(err.contains("FLIP7_ADMIN_TOKEN"), "{}", err);
+        assert!(load(&[("FLIP7_ADMIN_TOKEN", "s3cret")], &["--admin-addr", "127.0.0.1:9002"]).is_ok());
+        let err = load(&[("FLIP7_MAX_GAMES", "many")], &[]).unwrap_err();
+        assert!(err.contains("max_games"), "{}", err);
+    }
+
+    #[test]
+    fn test_strings_are_taken_as_given() {
+        let config = ServerConfig::load(
+            None,
+            env(&[("FLIP7_ADMIN_TOKEN", "123456"), ("FLIP7_JWT_SECRET", "\"abc\"")]),
+            &args(&["--lan-name", "true", "--redis", "redis://127.0.0.1/"]),
+        )
+        .unwrap();
+        assert_eq!(config.admin_token, Some(Secret("123456".to_string())));
+        assert_eq!(config.jwt_secret, Some(Secret("\"abc\"".to_string())));
+        assert_eq!(config.lan_name.as_deref(), Some("true"));
+        assert_eq!(config.redis.as_deref(), Some("redis://127.0.0.1/"));
+    }
+
+    #[test]
+    fn test_secrets_stay_out_of_debug() {
+        let config = ServerConfig {
+            jwt_secret: Some(Secret("hunter2".to_string())),
+            ..ServerConfig::default()
+        };
+        assert!(!format!("{:?}", config).contains("hunter2"));
+    }
+}
@@ -0,0 +1,147 @@
+//! State deltas: instead of the whole `GameState` after every move, clients
+//! that asked for the `state-deltas` feature in their `Hello` get the events
+//! and timed moves since the last state they acknowledged, and replay them.
+//!
+//! A delta carries the cards it draws in its `CardDrawn` events, so replaying
+//! never deals from the client's copy of the hidden deck. A new round shuffles
+//! a deck the client can't know, so round starts are always sent whole.
+//!
+//! Not every change can be replayed: players joining, skip votes and turn
+//! timer ticks leave no event behind. The server therefore replays each delta
+//! itself before sending it, and sends the full state whenever the replay
+//! doesn't come out exactly the same, or it doesn't know the client's state.
+
+use game_core::clock::TimedMove;
+use game_core::events::GameEvent;
+use game_core::{Card, GameMove, GameState};
+
+/// The events and timed moves that lead from `base` to `current`, if
+/// replaying them with `apply` gives exactly `current`.
+pub fn between(base: &GameState, current: &GameState) -> Option<(Vec<GameEvent>, Vec<TimedMove>)> {
+    let events = current.events.strip_prefix(base.events.as_slice())?;
+    let moves = current
+        .timed_moves
+        .strip_prefix(base.timed_moves.as_slice())?;
+    let replayed = apply(base, events, moves).ok()?;
+    (replayed == *current).then(|| (events.to_vec(), moves.to_vec()))
+}
+
+/// Replays a delta on the state it was taken from.
+pub fn apply(
+    base: &GameState,
+    events: &[GameEvent],
+    moves: &[TimedMove],
+) -> Result<GameState, String> {
+    let mut game = base.clone();
+    let start = base.events.len();
+    let mut moves = moves.iter();
+
+    while game.events.len() < start + events.len() {
+        let event = &events[game.events.len() - start];
+        match event {
+            GameEvent::CardDrawn { player_id, .. } | GameEvent::PlayerStayed { player_id } => {
+                if let GameEvent::CardDrawn { card, .. } = event {
+                    put_on_top(&mut game, *card);
+                }
+                let timed = moves.next().ok_or("The delta is missing a move")?;
+                let game_move = if matches!(event, GameEvent::CardDrawn { .. }) {
+                    GameMove::Draw {
+                        player_id: player_id.clone(),
+                    }
+                } else {
+                    GameMove::Stay {
+                        player_id: player_id.clone(),
+                    }
+                };
+                game.make_move_at(game_move, timed.at_ms)?;
+                // As the server does after every move
+                if game.round_state.is_finished && !game.round_state.is_scored {
+                    game.finish_round()?;
+                }
+            }
+            GameEvent::GamePaused { at_ms } => game.pause(*at_ms)?,
+            GameEvent::GameResumed { at_ms } => game.resume(*at_ms)?,
+            other => return Err(format!("Cannot replay {:?}", other)),
+        }
+
+        let produced = &game.events[start..];
+        if produced.len() > events.len() || produced != &events[..produced.len()] {
+            return Err("The delta doesn't replay to the same events".to_string());
+        }
+    }
+    if moves.next().is_some() {
+        return Err("The delta has moves without events".to_string());
+    }
+    Ok(game)
+}
+
+/// Makes `card` the next one drawn, taking it out of wherever the deck held it.
+fn put_on_top(game: &mut GameState, card: Card) {
+    let cards = &mut game.deck.cards;
+    if let Some(at) = cards.iter().rposition(|held| *held == card) {
+        cards.remove(at);
+    }
+    cards.push(card);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_between_and_apply() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        let base = game.clone();
+
+        let draw = GameMove::Draw {
+            player_id: "p1".to_string(),
+        };
+        game.make_move_at(draw, 1_000).unwrap();
+        game.pause(2_000).unwrap();
+        game.resume(3_000).unwrap();
+        let stay = GameMove::Stay {
+            player_id: game.current_player().unwrap().id.clone(),
+        };
+        game.make_move_at(stay, 4_000).unwrap();
+
+        let (events, moves) = between(&base, &game).unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(apply(&base, &events, &moves).unwrap(), game);
+        assert!(apply(&base, &events, &moves[..1]).is_err());
+
+        // Joining leaves no event to replay
+        let before_join = game.clone();
+        game.add_player("p3".to_string(), "Carol".to_string());
+        assert!(between(&before_join, &game).is_none());
+        assert!(between(&game, &base).is_none());
+    }
+
+    #[test]
+    fn test_apply_draws_the_cards_sent() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        game.add_player("p2".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        let base = game.clone();
+        for at_ms in [1_000, 2_000] {
+            let player_id = game.current_player().unwrap().id.clone();
+            game.make_move_at(GameMove::Draw { player_id }, at_ms).unwrap();
+        }
+        let (events, moves) = between(&base, &game).unwrap();
+
+        // A client whose deck is in another order still deals the cards sent
+        let mut client = base.clone();
+        client.deck.cards.reverse();
+        let replayed = apply(&client, &events, &moves).unwrap();
+        assert_eq!(replayed.players, game.players);
+        assert_eq!(replayed.events, game.events);
+
+        // New rounds deal a deck the client can't know
+        let mut next = game.clone();
+        next.start_round().unwrap();
+        assert!(between(&game, &next).is_none());
+    }
+}
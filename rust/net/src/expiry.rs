@@ -0,0 +1,96 @@
+//! Expiry of stale games, which would otherwise be kept forever. A finished
+//! game is dropped a while after its last move, and a lobby that hasn't
+//! changed for a while, nobody joining or starting it, counts as abandoned.
+//! So does a game in progress nobody has moved in for longer still.
+//!
+//! Every dropped game is announced with an `ExpiryEvent` carrying its final
+//! state, so a persistence layer following `GameServer::subscribe_expired`
+//! can archive it.
+
+use crate::lobby::GameStatus;
+use crate::GameId;
+use game_core::GameState;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long stale games are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expiry {
+    /// Finished games are dropped this long after their last change
+    pub finished_ttl: Duration,
+    /// Lobbies nobody has started are dropped this long after their last
+    /// change
+    pub lobby_ttl: Duration,
+    /// Games in progress nobody moves in are dropped this long after their
+    /// last change
+    pub idle_ttl: Duration,
+}
+
+impl Default for Expiry {
+    fn default() -> Self {
+        Self {
+            finished_ttl: Duration::from_secs(30 * 60),
+            lobby_ttl: Duration::from_secs(60 * 60),
+            idle_ttl: Duration::from_secs(2 * 60 * 60),
+        }
+    }
+}
+
+impl Expiry {
+    /// Why a game that hasn't changed for `idle` is dropped, if it is.
+    pub fn reason(&self, game: &GameState, idle: Duration) -> Option<ExpiryReason> {
+        match GameStatus::of(game) {
+            GameStatus::Finished if idle >= self.finished_ttl => Some(ExpiryReason::Finished),
+            GameStatus::Open if idle >= self.lobby_ttl => Some(ExpiryReason::AbandonedLobby),
+            GameStatus::InProgress if idle >= self.idle_ttl => Some(ExpiryReason::AbandonedGame),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpiryReason {
+    Finished,
+    AbandonedLobby,
+    AbandonedGame,
+}
+
+/// A game that was dropped, as it was last.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpiryEvent {
+    pub game_id: GameId,
+    pub reason: ExpiryReason,
+    pub game_state: Box<GameState>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reason() {
+        let expiry = Expiry::default();
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        assert_eq!(expiry.reason(&game, expiry.lobby_ttl / 2), None);
+        assert_eq!(
+            expiry.reason(&game, expiry.lobby_ttl),
+            Some(ExpiryReason::AbandonedLobby)
+        );
+
+        game.start_round().unwrap();
+        assert_eq!(expiry.reason(&game, expiry.lobby_ttl), None);
+        assert_eq!(
+            expiry.reason(&game, expiry.idle_ttl),
+            Some(ExpiryReason::AbandonedGame)
+        );
+
+        // A score attack out of rounds
+        game.config.round_limit = Some(0);
+        assert_eq!(expiry.reason(&game, Duration::ZERO), None);
+        assert_eq!(
+            expiry.reason(&game, expiry.finished_ttl),
+            Some(ExpiryReason::Finished)
+        );
+    }
+}
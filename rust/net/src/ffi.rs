@@ -0,0 +1,203 @@
+//! FFI for hosting a LAN table inside the mobile app, so "Host a table" works
+//! without a separate server binary. Follows the `game_core` FFI conventions:
+//! every function returns a JSON string that must be freed with
+//! `flip7_net_free_string`.
+
+use crate::{lan, Encoding, GameServer, TrustLevel};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+/// A server running in the app's process, on its own runtime.
+struct EmbeddedHost {
+    runtime: Runtime,
+    server: GameServer,
+}
+
+static HOSTS: OnceLock<Mutex<HashMap<u32, EmbeddedHost>>> = OnceLock::new();
+static NEXT_HOST_ID: AtomicU32 = AtomicU32::new(1);
+
+fn hosts() -> &'static Mutex<HashMap<u32, EmbeddedHost>> {
+    HOSTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn to_c_string(s: String) -> *mut c_char {
+    match CString::new(s) {
+        Ok(c_string) => c_string.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+fn from_c_string(ptr: *const c_char) -> Result<String, String> {
+    if ptr.is_null() {
+        return Err("Null pointer".to_string());
+    }
+
+    unsafe {
+        match CStr::from_ptr(ptr).to_str() {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => Err("Invalid UTF-8".to_string()),
+        }
+    }
+}
+
+fn respond(result: Result<String, String>) -> *mut c_char {
+    match result {
+        Ok(json) => to_c_string(json),
+        Err(err) => {
+            let error_response = serde_json::json!({
+                "success": false,
+                "error": err
+            });
+            to_c_string(error_response.to_string())
+        }
+    }
+}
+
+/// Starts hosting a table on all interfaces. Pass port 0 to let the OS pick
+/// one; the port actually used is returned so the app can show or advertise it.
+#[no_mangle]
+pub extern "C" fn flip7_host_start(port: u16) -> *mut c_char {
+    let result = (|| -> Result<String, String> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .map_err(|e| format!("Failed to start runtime: {}", e))?;
+
+        let listener = runtime
+            .block_on(TcpListener::bind(("0.0.0.0", port)))
+            .map_err(|e| format!("Failed to bind port {}: {}", port, e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read bound address: {}", e))?
+            .port();
+
+        let server = GameServer::new();
+        runtime.spawn(lan::serve(
+            server.clone(),
+            listener,
+            TrustLevel::UntrustedPeer,
+        ));
+
+        let host_id = NEXT_HOST_ID.fetch_add(1, Ordering::Relaxed);
+        let mut hosts = hosts().lock().map_err(|_| "Failed to lock hosts")?;
+        hosts.insert(host_id, EmbeddedHost { runtime, server });
+
+        let response = serde_json::json!({
+            "success": true,
+            "host_id": host_id,
+            "port": port
+        });
+        Ok(response.to_string())
+    })();
+
+    respond(result)
+}
+
+/// Sends a protocol message (JSON) to a hosted table on behalf of the host
+/// app itself, without going through a socket. The host is the authority for
+/// its own table, so every message type is allowed.
+#[no_mangle]
+pub extern "C" fn flip7_host_send(host_id: u32, message_json: *const c_char) -> *mut c_char {
+    let result = (|| -> Result<String, String> {
+        let message = from_c_string(message_json)?;
+
+        // Only hold the lock to look the host up: handling the message can
+        // take a while, and other tables must not wait on it
+        let (runtime, server) = {
+            let hosts = hosts().lock().map_err(|_| "Failed to lock hosts")?;
+            let host = hosts.get(&host_id).ok_or("Host not found")?;
+            (host.runtime.handle().clone(), host.server.clone())
+        };
+        let reply = runtime.block_on(server.handle_bytes(
+            Encoding::Json,
+            TrustLevel::AuthoritativeServer,
+            message.as_bytes(),
+        ));
+
+        let response = serde_json::json!({
+            "success": true,
+            "response": serde_json::from_slice::<serde_json::Value>(&reply)
+                .map_err(|e| format!("Invalid response: {}", e))?
+        });
+        Ok(response.to_string())
+    })();
+
+    respond(result)
+}
+
+/// Stops a hosted table, dropping its connections and games.
+#[no_mangle]
+pub extern "C" fn flip7_host_stop(host_id: u32) -> *mut c_char {
+    let result = (|| -> Result<String, String> {
+        let host = hosts()
+            .lock()
+            .map_err(|_| "Failed to lock hosts")?
+            .remove(&host_id)
+            .ok_or("Host not found")?;
+        host.runtime.shutdown_background();
+
+        Ok(serde_json::json!({ "success": true }).to_string())
+    })();
+
+    respond(result)
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn flip7_net_free_string(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        unsafe {
+            let _ = CString::from_raw(ptr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+
+    fn call(ptr: *mut c_char) -> serde_json::Value {
+        let json = unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_string();
+        flip7_net_free_string(ptr);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_ffi_host_table() {
+        let started = call(flip7_host_start(0));
+        assert_eq!(started["success"], true);
+        let host_id = started["host_id"].as_u64().unwrap() as u32;
+        let port = started["port"].as_u64().unwrap() as u16;
+
+        // The host app joins in-process...
+        let join = CString::new(r#"{"JoinGame":{"player_name":"Host","game_id":null}}"#).unwrap();
+        let joined = call(flip7_host_send(host_id, join.as_ptr()));
+        let game_id = joined["response"]["GameJoined"]["game_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        // ...and a phone on the LAN joins the same table over TCP
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        writeln!(
+            stream,
+            r#"{{"JoinGame":{{"player_name":"Guest","game_id":"{}"}}}}"#,
+            game_id
+        )
+        .unwrap();
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply).unwrap();
+        assert!(reply.contains("GameJoined"));
+
+        assert_eq!(call(flip7_host_stop(host_id))["success"], true);
+        assert_eq!(call(flip7_host_stop(host_id))["success"], false);
+    }
+}
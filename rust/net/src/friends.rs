@@ -0,0 +1,399 @@
+//! Friends, who can see each other's presence and invite each other to
+//! games. Only players signed in on a server with an `Auth` have friends,
+//! kept under their account's `Identity::user_id`.
+//!
+//! `AddFriend` asks another account to be friends, which is pushed to it as
+//! a `FriendRequest`; once it adds the asking account back, they are friends
+//! and both are told with a `FriendAdded`. `InviteFriend` pushes a
+//! `GameInvite` with the game's join code to every connection the friend
+//! signed in on. A `GameStore` keeps the friend lists, saving those that
+//! changed with the games.
+
+use crate::i18n::{text, Text};
+use crate::store::Rebase;
+use crate::{GameId, GameServer, Identity, InviteToken, JoinCode, PlayerId, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use tokio::sync::mpsc;
+
+/// Whether a friend can be invited, and whether they are busy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Presence {
+    Offline,
+    /// Signed in, without a seat in any game
+    Online,
+    /// Signed in and seated in the game
+    InGame { game_id: GameId },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Friend {
+    pub account: String,
+    pub name: String,
+    pub presence: Presence,
+}
+
+/// An account's friends and the accounts asking to be.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriendList {
+    /// Name the account last signed in under
+    pub name: String,
+    pub friends: BTreeSet<String>,
+    /// Accounts that asked to be friends, until added back or removed
+    pub requests: BTreeSet<String>,
+}
+
+/// Friends and requests added since are added again, and those removed since
+/// removed again.
+impl Rebase for FriendList {
+    fn rebase(&self, base: &Self, saved: &Self) -> Self {
+        let rebase = |ours: &BTreeSet<String>, base: &BTreeSet<String>, saved: &BTreeSet<String>| {
+            saved
+                .iter()
+                .filter(|account| ours.contains(*account) || !base.contains(*account))
+                .chain(ours.difference(base))
+                .cloned()
+                .collect()
+        };
+        Self {
+            name: self.name.clone(),
+            friends: rebase(&self.friends, &base.friends, &saved.friends),
+            requests: rebase(&self.requests, &base.requests, &saved.requests),
+        }
+    }
+}
+
+/// A connection signed in to an account.
+struct Connection {
+    updates: mpsc::Sender<Response>,
+    /// Game the connection has a seat in, if any
+    game: Option<GameId>,
+}
+
+/// Every account's friend list, the connections signed in to each, and
+/// which lists changed since the last save.
+#[derive(Default)]
+pub(crate) struct Friends {
+    lists: HashMap<String, FriendList>,
+    online: HashMap<String, HashMap<u64, Connection>>,
+    next_connection: u64,
+    unsaved: HashSet<String>,
+}
+
+impl Friends {
+    /// Notes a connection signed in as `identity`, whose pushed responses go
+    /// to `updates`. Returns its id, for `disconnect`.
+    pub(crate) fn connect(&mut self, identity: &Identity, updates: mpsc::Sender<Response>) -> u64 {
+        let list = self.lists.entry(identity.user_id.clone()).or_default();
+        if list.name != identity.name {
+            list.name = identity.name.clone();
+            self.unsaved.insert(identity.user_id.clone());
+        }
+        self.next_connection += 1;
+        let connection = Connection { updates, game: None };
+        self.online
+            .entry(identity.user_id.clone())
+            .or_default()
+            .insert(self.next_connection, connection);
+        self.next_connection
+    }
+
+    pub(crate) fn disconnect(&mut self, account: &str, connection: u64) {
+        if let Some(connections) = self.online.get_mut(account) {
+            connections.remove(&connection);
+            if connections.is_empty() {
+                self.online.remove(account);
+            }
+        }
+    }
+
+    /// Notes the game a connection has a seat in, if any.
+    pub(crate) fn seated(&mut self, account: &str, connection: u64, game: Option<GameId>) {
+        if let Some(connection) = self.online.get_mut(account).and_then(|online| online.get_mut(&connection)) {
+            connection.game = game;
+        }
+    }
+
+    pub(crate) fn presence(&self, account: &str) -> Presence {
+        let Some(connections) = self.online.get(account) else {
+            return Presence::Offline;
+        };
+        match connections.values().find_map(|connection| connection.game) {
+            Some(game_id) => Presence::InGame { game_id },
+            None => Presence::Online,
+        }
+    }
+
+    /// Adds `friend` to the account's friends if it asked to be, or else asks
+    /// it. Returns whether they are friends now.
+    pub(crate) fn add(&mut self, account: &str, friend: &str) -> Result<bool, Text> {
+        if account == friend {
+            return Err(text!("own_friend"));
+        }
+        if !self.lists.contains_key(friend) {
+            return Err(text!("unknown_account", friend));
+        }
+        let list = self.lists.entry(account.to_string()).or_default();
+        if list.friends.contains(friend) {
+            return Err(text!("already_friend", friend));
+        }
+        let name = list.name.clone();
+        if list.requests.remove(friend) {
+            list.friends.insert(friend.to_string());
+            self.unsaved.insert(account.to_string());
+            self.list_mut(friend).friends.insert(account.to_string());
+            self.push(friend, &Response::FriendAdded {
+                friend_id: account.to_string(),
+            });
+            return Ok(true);
+        }
+        if self.list_mut(friend).requests.insert(account.to_string()) {
+            let request = Response::FriendRequest {
+                from: account.to_string(),
+                name,
+            };
+            self.push(friend, &request);
+        }
+        Ok(false)
+    }
+
+    /// Ends a friendship, or takes back or turns down a request either way.
+    pub(crate) fn remove(&mut self, account: &str, friend: &str) -> Result<(), Text> {
+        let mut removed = false;
+        for (one, other) in [(account, friend), (friend, account)] {
+            let Some(list) = self.lists.get_mut(one) else {
+                continue;
+            };
+            if list.friends.remove(other) | list.requests.remove(other) {
+                self.unsaved.insert(one.to_string());
+                removed = true;
+            }
+        }
+        if !removed {
+            return Err(text!("not_friend", friend));
+        }
+        Ok(())
+    }
+
+    /// The account's friends, then the accounts asking to be.
+    pub(crate) fn list(&self, account: &str) -> (Vec<Friend>, Vec<Friend>) {
+        let Some(list) = self.lists.get(account) else {
+            return (Vec::new(), Vec::new());
+        };
+        let friend = |account: &String| Friend {
+            account: account.clone(),
+            name: self.lists.get(account).map(|list| list.name.clone()).unwrap_or_default(),
+            presence: self.presence(account),
+        };
+        (list.friends.iter().map(friend).collect(), list.requests.iter().map(friend).collect())
+    }
+
+    /// Pushes an invitation to the game to every connection of the friend.
+    pub(crate) fn invite(
+        &self,
+        account: &str,
+        friend: &str,
+        game_id: GameId,
+        join_code: JoinCode,
+        invite: InviteToken,
+    ) -> Result<(), Text> {
+        let list = self.lists.get(account);
+        if !list.is_some_and(|list| list.friends.contains(friend)) {
+            return Err(text!("not_friend", friend));
+        }
+        if self.presence(friend) == Presence::Offline {
+            return Err(text!("friend_offline", friend));
+        }
+        let invite = Response::GameInvite {
+            from: account.to_string(),
+            name: list.map(|list| list.name.clone()).unwrap_or_default(),
+            game_id,
+            join_code,
+            invite,
+        };
+        self.push(friend, &invite);
+        Ok(())
+    }
+
+    /// Takes the friend lists a store kept, over those known.
+    pub(crate) fn load(&mut self, lists: HashMap<String, FriendList>) {
+        self.lists.extend(lists);
+    }
+
+    /// The friend lists that changed since they were last taken.
+    pub(crate) fn take_unsaved(&mut self) -> Vec<(String, FriendList)> {
+        let mut changed: Vec<(String, FriendList)> = std::mem::take(&mut self.unsaved)
+            .into_iter()
+            .map(|account| {
+                let list = self.lists.get(&account).cloned().unwrap_or_default();
+                (account, list)
+            })
+            .collect();
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+        changed
+    }
+
+    /// Has the friend lists saved again with the next save, e.g. after it
+    /// failed.
+    pub(crate) fn mark_unsaved(&mut self, accounts: impl IntoIterator<Item = String>) {
+        self.unsaved.extend(accounts);
+    }
+
+    /// The account's list, to be saved with the next save.
+    fn list_mut(&mut self, account: &str) -> &mut FriendList {
+        self.unsaved.insert(account.to_string());
+        self.lists.entry(account.to_string()).or_default()
+    }
+
+    fn push(&self, account: &str, response: &Response) {
+        for connection in self.online.get(account).into_iter().flat_map(|online| online.values()) {
+            // The connection may be closing, or too far behind to take more
+            let _ = connection.updates.try_send(response.clone());
+        }
+    }
+}
+
+impl GameServer {
+    /// Answers a signed-in connection's `AddFriend`.
+    pub(crate) fn add_friend(&self, account: &str, friend_id: String) -> Response {
+        match self.friends.lock().unwrap().add(account, &friend_id) {
+            Ok(true) => Response::FriendAdded { friend_id },
+            Ok(false) => Response::FriendRequested { friend_id },
+            Err(message) => Response::Error { message },
+        }
+    }
+
+    /// Answers a signed-in connection's `RemoveFriend`.
+    pub(crate) fn remove_friend(&self, account: &str, friend_id: String) -> Response {
+        match self.friends.lock().unwrap().remove(account, &friend_id) {
+            Ok(()) => Response::FriendRemoved { friend_id },
+            Err(message) => Response::Error { message },
+        }
+    }
+
+    /// Answers a signed-in connection's `ListFriends`.
+    pub(crate) fn list_friends(&self, account: &str) -> Response {
+        let (friends, requests) = self.friends.lock().unwrap().list(account);
+        Response::FriendList { friends, requests }
+    }
+
+    /// Answers a signed-in connection's `InviteFriend`, to a game that
+    /// hasn't ended, from the connection's `seat` in it. The friend is sent
+    /// an invitation of the game's, so they get in whoever else it lets in.
+    pub(crate) async fn invite_friend(
+        &self,
+        account: &str,
+        seat: Option<PlayerId>,
+        friend_id: String,
+        game_id: GameId,
+    ) -> Response {
+        let (join_code, invite) = {
+            let mut engine = self.shards.of(game_id).write().await;
+            match engine.games.get(&game_id) {
+                Some(game) if game.is_game_over() => {
+                    return Response::Error {
+                        message: text!("game_over"),
+                    }
+                }
+                Some(_) if !seat.is_some_and(|player_id| engine.is_seated(game_id, player_id)) => {
+                    return Response::Error {
+                        message: text!("friends_in_game_only"),
+                    }
+                }
+                Some(_) => {
+                    let invite = InviteToken::new();
+                    engine.invites.insert(invite, game_id);
+                    (engine.join_code(game_id), invite)
+                }
+                None => {
+                    return Response::Error {
+                        message: text!("game_not_found"),
+                    }
+                }
+            }
+        };
+        let invited = self
+            .friends
+            .lock()
+            .unwrap()
+            .invite(account, &friend_id, game_id, join_code, invite);
+        match invited {
+            Ok(()) => Response::FriendInvited { friend_id, game_id },
+            Err(message) => {
+                self.shards.of(game_id).write().await.invites.remove(&invite);
+                Response::Error { message }
+            }
+        }
+    }
+
+    /// Notes a connection signed in as `identity`; see `Friends::connect`.
+    pub(crate) fn connect_friend(&self, identity: &Identity, updates: mpsc::Sender<Response>) -> u64 {
+        self.friends.lock().unwrap().connect(identity, updates)
+    }
+
+    pub(crate) fn disconnect_friend(&self, account: &str, connection: u64) {
+        self.friends.lock().unwrap().disconnect(account, connection);
+    }
+
+    /// Notes the game a signed-in connection has a seat in, for its friends
+    /// to see.
+    pub(crate) fn set_presence(&self, account: &str, connection: u64, game: Option<GameId>) {
+        self.friends.lock().unwrap().seated(account, connection, game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(user_id: &str, name: &str) -> Identity {
+        Identity {
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_friends() {
+        let mut friends = Friends::default();
+        let (ada_tx, mut ada) = mpsc::channel(8);
+        let (bo_tx, mut bo) = mpsc::channel(8);
+        let ada_connection = friends.connect(&identity("u1", "Ada"), ada_tx);
+        friends.connect(&identity("u2", "Bo"), bo_tx);
+        assert!(friends.add("u1", "u1").is_err());
+        assert!(friends.add("u1", "u3").is_err());
+
+        // Asking, then adding back
+        assert_eq!(friends.add("u1", "u2"), Ok(false));
+        assert!(matches!(bo.try_recv(), Ok(Response::FriendRequest { from, name }) if from == "u1" && name == "Ada"));
+        assert_eq!(friends.list("u2").1[0].name, "Ada");
+        assert_eq!(friends.add("u2", "u1"), Ok(true));
+        assert!(matches!(ada.try_recv(), Ok(Response::FriendAdded { friend_id }) if friend_id == "u2"));
+        assert!(friends.add("u1", "u2").is_err());
+
+        let (list, requests) = friends.list("u2");
+        assert!(requests.is_empty());
+        assert_eq!(list[0].presence, Presence::Online);
+        let game_id = GameId::new();
+        friends.seated("u1", ada_connection, Some(game_id));
+        assert_eq!(friends.presence("u1"), Presence::InGame { game_id });
+        friends.disconnect("u1", ada_connection);
+        assert_eq!(friends.presence("u1"), Presence::Offline);
+
+        // Offline friends and strangers can't be invited
+        let code = JoinCode::random();
+        let invite = InviteToken::new();
+        assert!(friends.invite("u2", "u1", game_id, code.clone(), invite).is_err());
+        friends.connect(&identity("u3", "Cy"), mpsc::channel(8).0);
+        assert!(friends.invite("u2", "u3", game_id, code.clone(), invite).is_err());
+        friends.invite("u1", "u2", game_id, code, invite).unwrap();
+        assert!(matches!(bo.try_recv(), Ok(Response::GameInvite { from, .. }) if from == "u1"));
+
+        let unsaved = friends.take_unsaved();
+        let accounts: Vec<&str> = unsaved.iter().map(|(account, _)| account.as_str()).collect();
+        assert_eq!(accounts, ["u1", "u2", "u3"]);
+        friends.remove("u2", "u1").unwrap();
+        assert!(friends.list("u1").0.is_empty());
+        assert!(friends.remove("u2", "u1").is_err());
+    }
+}
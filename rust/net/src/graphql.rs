@@ -0,0 +1,587 @@
+//! GraphQL API, served with the HTTP API for frontends built on GraphQL
+//! tooling:
+//!
+//! - `POST /graphql` takes queries (`game`, `lobby`) and mutations (`join`,
+//!   `move`)
+//! - `GET /graphql/ws` also takes subscriptions (`game`, its state every time
+//!   it changes), over WebSocket with either the `graphql-transport-ws` or
+//!   the older `graphql-ws` protocol
+//!
+//! As over HTTP, `join` takes the player's auth token as `Authorization:
+//! Bearer <token>` on servers with an `Auth`, or as `authorization` in the
+//! WebSocket's connection payload, and moves are made with the seat's
+//! session token. A WebSocket sending faster than its address may is closed.
+//! Errors come in the locale of the request's `Accept-Language`.
+
+use crate::auth;
+use crate::http::bearer;
+use crate::i18n::{text, Locale};
+use crate::lobby::GameStatus;
+use crate::{GameId, GameServer, JoinCode, Message, MoveId, Response, SessionToken, TrustLevel};
+use async_graphql::http::{WebSocket, WebSocketProtocols, WsMessage};
+use async_graphql::{Context, Data, Enum, Object, SimpleObject, Subscription, ID};
+use axum::body::Bytes;
+use axum::extract::ws::{CloseFrame, Message as Frame, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{future, stream, SinkExt, Stream, StreamExt};
+use game_core::GameMove;
+use std::net::SocketAddr;
+use tokio::sync::broadcast;
+
+pub type Schema = async_graphql::Schema<Query, Mutation, Subscription>;
+
+/// The schema, for serving some other way. Resolvers answer for clients of
+/// the given trust level.
+pub fn schema(server: GameServer, trust: TrustLevel) -> Schema {
+    Schema::build(Query, Mutation, Subscription)
+        .data(Api { server, trust })
+        .finish()
+}
+
+/// `/graphql` and `/graphql/ws`, which `http::router` serves too.
+pub(crate) fn router(server: GameServer, trust: TrustLevel) -> Router {
+    Router::new()
+        .route("/graphql", post(execute))
+        .route("/graphql/ws", get(subscribe))
+        .with_state(schema(server, trust))
+}
+
+struct Api {
+    server: GameServer,
+    trust: TrustLevel,
+}
+
+/// The auth token a request came with, if any.
+struct Bearer(String);
+
+/// Address of the client a request came from.
+struct ClientAddress(std::net::IpAddr);
+
+impl Api {
+    async fn handle(&self, message: Message) -> Response {
+        self.server
+            .handle_message_with_trust(self.trust, message)
+            .await
+    }
+}
+
+fn api<'a>(ctx: &Context<'a>) -> &'a Api {
+    ctx.data_unchecked::<Api>()
+}
+
+/// The locale errors go out in, from the `Accept-Language` the request or
+/// its WebSocket came with.
+fn locale(ctx: &Context<'_>) -> Locale {
+    ctx.data_opt::<Locale>().copied().unwrap_or_default()
+}
+
+fn accept_language(headers: &HeaderMap) -> Option<Locale> {
+    Locale::from_accept_language(headers.get(header::ACCEPT_LANGUAGE)?.to_str().ok()?)
+}
+
+fn error(response: Response, locale: Locale) -> async_graphql::Error {
+    match response {
+        Response::Error { message } => async_graphql::Error::new(message.in_locale(locale)),
+        Response::RateLimited { limit, .. } => {
+            async_graphql::Error::new(text!("over_limit", format!("{:?}", limit)).in_locale(locale))
+        }
+        other => async_graphql::Error::new(format!("Unexpected response {:?}", other)),
+    }
+}
+
+fn parse<T: std::str::FromStr<Err = String>>(value: &str) -> async_graphql::Result<T> {
+    value.parse().map_err(async_graphql::Error::new)
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "GameStatus")]
+enum Status {
+    /// No round has been dealt yet
+    Open,
+    InProgress,
+    Finished,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+enum MoveKind {
+    Draw,
+    Stay,
+}
+
+#[derive(SimpleObject)]
+struct Player {
+    id: ID,
+    name: String,
+    score: u32,
+    /// Values of the cards in the player's hand this round
+    cards: Vec<u8>,
+    has_stayed: bool,
+    busted: bool,
+    /// Knocked out under the elimination variant
+    eliminated: bool,
+    team: Option<u8>,
+}
+
+#[derive(SimpleObject)]
+struct Game {
+    id: ID,
+    status: Status,
+    round_number: u32,
+    players: Vec<Player>,
+    /// Whose turn it is, while a round is being played
+    current_player_id: Option<ID>,
+    /// The winning player, or every member of the winning team
+    winner_ids: Vec<ID>,
+}
+
+impl Game {
+    fn of(game_id: GameId, game: &game_core::GameState) -> Self {
+        let status = GameStatus::of(game);
+        let in_turn = status == GameStatus::InProgress && !game.round_state.is_finished;
+        Self {
+            id: ID(game_id.to_string()),
+            status: status.into(),
+            round_number: game.round_state.round_number,
+            players: game
+                .players
+                .iter()
+                .map(|player| Player {
+                    id: ID(player.id.clone()),
+                    name: player.name.clone(),
+                    score: player.score,
+                    cards: player.hand.cards.iter().map(|card| card.value()).collect(),
+                    has_stayed: player.has_stayed,
+                    busted: player.hand.is_bust(),
+                    eliminated: player.eliminated,
+                    team: player.team,
+                })
+                .collect(),
+            current_player_id: in_turn
+                .then(|| game.current_player().map(|player| ID(player.id.clone())))
+                .flatten(),
+            winner_ids: game
+                .outcome
+                .iter()
+                .flat_map(|outcome| outcome.winner_ids.iter().cloned().map(ID))
+                .collect(),
+        }
+    }
+}
+
+/// One line of the lobby.
+#[derive(SimpleObject)]
+struct LobbyGame {
+    id: ID,
+    join_code: String,
+    variant: String,
+    status: Status,
+    player_count: usize,
+}
+
+/// The seat `join` took.
+#[derive(SimpleObject)]
+struct Seat {
+    game_id: ID,
+    join_code: String,
+    player_id: ID,
+    /// For moves made for the seat; bot seats have none
+    session_token: Option<String>,
+    engine_rules_version: u32,
+}
+
+#[derive(SimpleObject)]
+struct MoveResult {
+    game_id: ID,
+    /// Hash of the state the move led to, in decimal as it is 64 bits
+    state_hash: String,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// The game, if there is one with that id.
+    async fn game(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<Game>> {
+        let game_id: GameId = parse(&id)?;
+        match api(ctx).handle(Message::GetGameState { game_id, access: None }).await {
+            Response::GameState { game_state } => Ok(Some(Game::of(game_id, &game_state))),
+            Response::Error { message } if message.key() == Some("game_not_found") => Ok(None),
+            other => Err(error(other, locale(ctx))),
+        }
+    }
+
+    /// Public games, open ones first.
+    async fn lobby(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<LobbyGame>> {
+        match api(ctx).handle(Message::ListGames).await {
+            Response::GameList { games } => Ok(games
+                .into_iter()
+                .map(|summary| LobbyGame {
+                    id: ID(summary.game_id.to_string()),
+                    join_code: summary.join_code.to_string(),
+                    variant: summary.variant,
+                    status: summary.status.into(),
+                    player_count: summary.player_count,
+                })
+                .collect()),
+            other => Err(error(other, locale(ctx))),
+        }
+    }
+}
+
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    /// Takes a seat in the game, or in a new one if no game is given.
+    async fn join(
+        &self,
+        ctx: &Context<'_>,
+        player_name: String,
+        game_id: Option<ID>,
+        join_code: Option<String>,
+    ) -> async_graphql::Result<Seat> {
+        let api = api(ctx);
+        let mut message = Message::JoinGame {
+            player_name,
+            game_id: game_id.map(|id| parse::<GameId>(&id)).transpose()?,
+            team: None,
+            variant: None,
+            code: join_code
+                .as_deref()
+                .map(|code| {
+                    code.parse::<JoinCode>()
+                        .map_err(|_| text!("invalid_join_code", code).in_locale(locale(ctx)))
+                })
+                .transpose()?,
+            access: None,
+        };
+        let token = ctx.data_opt::<Bearer>().map(|Bearer(token)| token.as_str());
+        let identity = auth::sign_in_request(api.server.auth().as_ref(), token, &message)
+            .map_err(|message| message.in_locale(locale(ctx)))?;
+        if let (Message::JoinGame { player_name, .. }, Some(identity)) = (&mut message, identity) {
+            *player_name = identity.name;
+        }
+
+        let joined = match ctx.data_opt::<ClientAddress>() {
+            Some(ClientAddress(address)) => api.server.handle_request(api.trust, *address, message).await,
+            None => api.handle(message).await,
+        };
+        match joined {
+            Response::GameJoined {
+                game_id,
+                join_code,
+                player_id,
+                engine_rules_version,
+                session_token,
+            } => Ok(Seat {
+                game_id: ID(game_id.to_string()),
+                join_code: join_code.to_string(),
+                player_id: ID(player_id.to_string()),
+                session_token: session_token.map(|token| token.to_string()),
+                engine_rules_version,
+            }),
+            other => Err(error(other, locale(ctx))),
+        }
+    }
+
+    /// Draws or stays for the seat the session token is for.
+    #[graphql(name = "move")]
+    async fn make_move(
+        &self,
+        ctx: &Context<'_>,
+        game_id: ID,
+        session_token: String,
+        kind: MoveKind,
+        move_id: Option<ID>,
+    ) -> async_graphql::Result<MoveResult> {
+        let api = api(ctx);
+        let game_id: GameId = parse(&game_id)?;
+        let token: SessionToken = parse(&session_token)?;
+        let player_id = api
+            .server
+            .seat_of(game_id, &token)
+            .await
+            .ok_or_else(|| text!("no_session_seat").in_locale(locale(ctx)))?
+            .to_string();
+        let game_move = match kind {
+            MoveKind::Draw => GameMove::Draw { player_id },
+            MoveKind::Stay => GameMove::Stay { player_id },
+        };
+        let message = Message::MakeMove {
+            game_id,
+            game_move,
+            move_id: move_id.map(|id| parse::<MoveId>(&id)).transpose()?,
+        };
+
+        match api.handle(message).await {
+            Response::MoveAccepted {
+                game_id,
+                state_hash,
+            } => Ok(MoveResult {
+                game_id: ID(game_id.to_string()),
+                state_hash: state_hash.to_string(),
+            }),
+            other => Err(error(other, locale(ctx))),
+        }
+    }
+}
+
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+    /// The game as it is now, and again every time it changes, until it is
+    /// closed.
+    async fn game(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<impl Stream<Item = Game>> {
+        let api = api(ctx);
+        let game_id: GameId = parse(&id)?;
+        // Subscribed before fetching the state, so no change falls in between
+        let updates = api
+            .server
+            .subscribe(game_id)
+            .await
+            .ok_or_else(|| text!("game_not_found").in_locale(locale(ctx)))?;
+        let first = match api.handle(Message::GetGameState { game_id, access: None }).await {
+            Response::GameState { game_state } => Game::of(game_id, &game_state),
+            other => return Err(error(other, locale(ctx))),
+        };
+
+        let changes = stream::unfold(updates, move |mut updates| async move {
+            loop {
+                match updates.recv().await {
+                    Ok(Response::StateUpdate { game_state, .. }) => {
+                        return Some((Game::of(game_id, &game_state), updates));
+                    }
+                    Ok(Response::GameClosed { .. }) | Err(broadcast::error::RecvError::Closed) => {
+                        return None
+                    }
+                    // Missed updates are made up for by the next one
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                }
+            }
+        });
+        Ok(stream::once(future::ready(first)).chain(changes))
+    }
+}
+
+async fn execute(
+    State(schema): State<Schema>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<async_graphql::Response>, (StatusCode, Json<async_graphql::Response>)> {
+    let api = schema.data::<Api>().expect("the schema has the server");
+    let locale = accept_language(&headers).unwrap_or_default();
+    if let Some(limited) = api
+        .server
+        .throttle_request(api.trust, addr.ip(), body.len())
+    {
+        let response = async_graphql::Response::from_errors(vec![
+            error(limited, locale).into_server_error(Default::default())
+        ]);
+        return Err((StatusCode::TOO_MANY_REQUESTS, Json(response)));
+    }
+    let request = match serde_json::from_slice::<async_graphql::Request>(&body) {
+        Ok(request) => request,
+        Err(err) => {
+            let invalid = async_graphql::Error::new(text!("invalid_request", err).in_locale(locale));
+            let response = async_graphql::Response::from_errors(vec![
+                invalid.into_server_error(Default::default())
+            ]);
+            return Err((StatusCode::BAD_REQUEST, Json(response)));
+        }
+    };
+    let request = request.data(ClientAddress(addr.ip())).data(locale);
+    let request = match bearer(&headers) {
+        Some(token) => request.data(Bearer(token.to_string())),
+        None => request,
+    };
+    Ok(Json(schema.execute(request).await))
+}
+
+async fn subscribe(
+    State(schema): State<Schema>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> axum::response::Response {
+    let locale = accept_language(&headers).unwrap_or_default();
+    let upgrade = upgrade.protocols(["graphql-transport-ws", "graphql-ws"]);
+    let protocol = upgrade
+        .selected_protocol()
+        .and_then(|protocol| protocol.to_str().ok()?.parse::<WebSocketProtocols>().ok())
+        .unwrap_or(WebSocketProtocols::GraphQLWS);
+
+    upgrade.on_upgrade(move |socket| async move {
+        let (mut sink, source) = socket.split();
+        let api = schema.data::<Api>().expect("the schema has the server");
+        let (server, trust) = (api.server.clone(), api.trust);
+        let input = Box::pin(
+            source
+                .take_while(move |frame| {
+                    let allowed = frame.as_ref().is_ok_and(|frame| {
+                        let len = match frame {
+                            Frame::Text(text) => text.len(),
+                            Frame::Binary(bytes) => bytes.len(),
+                            _ => 0,
+                        };
+                        server.throttle_request(trust, addr.ip(), len).is_none()
+                    });
+                    future::ready(allowed)
+                })
+                .filter_map(|frame| async move {
+                    match frame {
+                        Ok(Frame::Text(text)) => Some(text.as_bytes().to_vec()),
+                        Ok(Frame::Binary(bytes)) => Some(bytes.to_vec()),
+                        _ => None,
+                    }
+                }),
+        );
+
+        let mut output = WebSocket::new(schema, input, protocol).on_connection_init(
+            move |payload: serde_json::Value| async move {
+                let mut data = Data::default();
+                data.insert(ClientAddress(addr.ip()));
+                data.insert(locale);
+                let token = payload
+                    .get("authorization")
+                    .and_then(|value| value.as_str())
+                    .and_then(|value| value.strip_prefix("Bearer "));
+                if let Some(token) = token {
+                    data.insert(Bearer(token.to_string()));
+                }
+                Ok(data)
+            },
+        );
+        while let Some(message) = output.next().await {
+            let frame = match message {
+                WsMessage::Text(text) => Frame::Text(text.into()),
+                WsMessage::Close(code, reason) => Frame::Close(Some(CloseFrame {
+                    code,
+                    reason: reason.into(),
+                })),
+            };
+            if sink.send(frame).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::Request;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn post(server: &GameServer, query: &str) -> Value {
+        post_in(server, "en", query).await
+    }
+
+    async fn post_in(server: &GameServer, language: &str, query: &str) -> Value {
+        let app = router(server.clone(), TrustLevel::UntrustedPeer)
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 9000))));
+        let request = Request::post("/graphql")
+            .header("content-type", "application/json")
+            .header(header::ACCEPT_LANGUAGE, language)
+            .body(Body::from(json!({ "query": query }).to_string()))
+            .unwrap();
+        let reply = app.oneshot(request).await.unwrap();
+        assert_eq!(reply.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(reply.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_play_over_graphql() {
+        let server = GameServer::new();
+        let alice = post(
+            &server,
+            "mutation { join(playerName: \"Alice\") { gameId joinCode playerId sessionToken } }",
+        )
+        .await;
+        let seat = &alice["data"]["join"];
+        let game_id = seat["gameId"].as_str().unwrap().to_string();
+        let bob = post(
+            &server,
+            &format!(
+                "mutation {{ join(playerName: \"Bob\", joinCode: \"{}\") {{ gameId sessionToken }} }}",
+                seat["joinCode"].as_str().unwrap()
+            ),
+        )
+        .await;
+        assert_eq!(bob["data"]["join"]["gameId"], game_id);
+
+        // Games joined without creating them first are private
+        let lobby = post(&server, "{ lobby { id status playerCount } }").await;
+        assert_eq!(lobby["data"]["lobby"], json!([]));
+
+        // Subscribers get the game as it is, then every change
+        let schema = schema(server.clone(), TrustLevel::UntrustedPeer);
+        let mut updates = schema.execute_stream(format!(
+            "subscription {{ game(id: \"{}\") {{ status currentPlayerId players {{ id hasStayed }} }} }}",
+            game_id
+        ));
+        let open = updates.next().await.unwrap().data.into_json().unwrap();
+        assert_eq!(open["game"]["status"], "OPEN");
+
+        let game: GameId = game_id.parse().unwrap();
+        server
+            .handle_message_with_trust(
+                TrustLevel::AuthoritativeServer,
+                Message::StartGame { game_id: game, host: None },
+            )
+            .await;
+        let started = updates.next().await.unwrap().data.into_json().unwrap();
+        assert_eq!(started["game"]["status"], "IN_PROGRESS");
+        let current = started["game"]["currentPlayerId"].clone();
+        let token = if current == seat["playerId"] {
+            seat["sessionToken"].clone()
+        } else {
+            bob["data"]["join"]["sessionToken"].clone()
+        };
+
+        let moved = post(
+            &server,
+            &format!(
+                "mutation {{ move(gameId: \"{}\", sessionToken: {}, kind: STAY) {{ stateHash }} }}",
+                game_id, token
+            ),
+        )
+        .await;
+        assert!(moved["errors"].is_null(), "{}", moved);
+        let stayed = updates.next().await.unwrap().data.into_json().unwrap();
+        let players = stayed["game"]["players"].as_array().unwrap();
+        assert!(players
+            .iter()
+            .any(|player| player["id"] == current && player["hasStayed"] == true));
+
+        let state = post(&server, &format!("{{ game(id: \"{}\") {{ roundNumber }} }}", game_id)).await;
+        assert_eq!(state["data"]["game"]["roundNumber"], 1);
+        let missing = post(&server, &format!("{{ game(id: \"{}\") {{ id }} }}", GameId::new())).await;
+        assert!(missing["data"]["game"].is_null());
+
+        // Errors come in the language the client asked for
+        let query = format!(
+            "mutation {{ move(gameId: \"{}\", sessionToken: {}, kind: STAY) {{ stateHash }} }}",
+            GameId::new(),
+            token
+        );
+        let refused = post(&server, &query).await;
+        assert_eq!(refused["errors"][0]["message"], "No seat in the game has that session token");
+        let refused = post_in(&server, "fr-CA", &query).await;
+        assert_eq!(refused["errors"][0]["message"], "Aucune place de la partie n'a ce jeton de session");
+    }
+}
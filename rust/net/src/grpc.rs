@@ -0,0 +1,417 @@
+//! gRPC service, for backend services in other languages that would rather
+//! generate a typed client from `proto/flip7.proto` than parse the JSON wire
+//! protocol by hand. `Join`, `Move` and `StreamState` mirror `JoinGame`,
+//! `MakeMove` and the `StateUpdate`s of a followed game.
+//!
+//! Like the HTTP API there is no connection to sign in on: on servers with
+//! an `Auth`, `Join` takes the player's auth token as `authorization: Bearer
+//! <token>` metadata, and moves are made with the seat's session token.
+//! Errors come in the locale of the request's `accept-language` metadata.
+
+// Handlers answer tonic's `Status`, large as it is
+#![allow(clippy::result_large_err)]
+
+use crate::auth;
+use crate::i18n::{text, Locale};
+use crate::lobby::GameStatus;
+use crate::transport::Transport;
+use crate::{GameId, GameServer, JoinCode, Message, MoveId, Response, SessionToken, TrustLevel};
+use futures_util::{stream, Stream};
+use game_core::GameMove;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Request, Status};
+
+/// Types and stubs generated from `proto/flip7.proto`.
+pub mod proto {
+    tonic::include_proto!("flip7");
+}
+
+use proto::flip7_server::{Flip7, Flip7Server};
+
+pub struct GrpcTransport {
+    listener: TcpListener,
+    trust: TrustLevel,
+}
+
+impl GrpcTransport {
+    pub async fn bind(addr: impl ToSocketAddrs, trust: TrustLevel) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr).await?, trust))
+    }
+
+    pub fn from_listener(listener: TcpListener, trust: TrustLevel) -> Self {
+        Self { listener, trust }
+    }
+}
+
+impl Transport for GrpcTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    async fn serve(self, server: GameServer) -> io::Result<()> {
+        tonic::transport::Server::builder()
+            .add_service(service(server, self.trust))
+            .serve_with_incoming(TcpListenerStream::new(self.listener))
+            .await
+            .map_err(io::Error::other)
+    }
+}
+
+/// The service, for serving alongside others. Nothing runs the server's
+/// timers.
+pub fn service(server: GameServer, trust: TrustLevel) -> Flip7Server<Service> {
+    Flip7Server::new(Service { server, trust })
+}
+
+pub struct Service {
+    server: GameServer,
+    trust: TrustLevel,
+}
+
+/// The status of a response that isn't the one asked for, with its error in
+/// the locale.
+fn status(response: Response, locale: Locale) -> Status {
+    match response {
+        Response::Error { message } if message.key() == Some("game_not_found") => {
+            Status::not_found(message.in_locale(locale))
+        }
+        Response::Error { message } => Status::failed_precondition(message.in_locale(locale)),
+        Response::RateLimited { limit, .. } => {
+            Status::resource_exhausted(text!("over_limit", format!("{:?}", limit)).in_locale(locale))
+        }
+        other => Status::internal(format!("Unexpected response {:?}", other)),
+    }
+}
+
+/// The locale of the request's `accept-language` metadata, if it has one.
+fn locale<T>(request: &Request<T>) -> Locale {
+    request
+        .metadata()
+        .get("accept-language")
+        .and_then(|value| Locale::from_accept_language(value.to_str().ok()?))
+        .unwrap_or_default()
+}
+
+fn parse<T: std::str::FromStr<Err = String>>(value: &str) -> Result<T, Status> {
+    value.parse().map_err(Status::invalid_argument)
+}
+
+impl Service {
+    /// Holds an untrusted client to its address's rate, and its requests to
+    /// the size limit.
+    fn throttle<T: prost::Message>(&self, request: &Request<T>) -> Result<(), Status> {
+        let Some(addr) = request.remote_addr() else {
+            return Ok(());
+        };
+        let len = request.get_ref().encoded_len();
+        match self.server.throttle_request(self.trust, addr.ip(), len) {
+            Some(limited) => Err(status(limited, locale(request))),
+            None => Ok(()),
+        }
+    }
+
+    async fn handle(&self, message: Message) -> Response {
+        self.server
+            .handle_message_with_trust(self.trust, message)
+            .await
+    }
+
+    /// Handles a message that may take the client at `addr` into a game;
+    /// see `GameServer::handle_request`.
+    async fn handle_from(&self, addr: Option<SocketAddr>, message: Message) -> Response {
+        match addr {
+            Some(addr) => self.server.handle_request(self.trust, addr.ip(), message).await,
+            None => self.handle(message).await,
+        }
+    }
+}
+
+type StateStream = Pin<Box<dyn Stream<Item = Result<proto::GameState, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Flip7 for Service {
+    async fn join(
+        &self,
+        request: Request<proto::JoinRequest>,
+    ) -> Result<tonic::Response<proto::JoinReply>, Status> {
+        self.throttle(&request)?;
+        let locale = locale(&request);
+        let addr = request.remote_addr();
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string);
+        let join = request.into_inner();
+        let mut message = Message::JoinGame {
+            player_name: join.player_name,
+            game_id: join.game_id.as_deref().map(parse::<GameId>).transpose()?,
+            team: None,
+            variant: None,
+            code: join
+                .join_code
+                .as_deref()
+                .map(|code| {
+                    code.parse::<JoinCode>()
+                        .map_err(|_| Status::invalid_argument(text!("invalid_join_code", code).in_locale(locale)))
+                })
+                .transpose()?,
+            access: None,
+        };
+        let identity =
+            auth::sign_in_request(self.server.auth().as_ref(), token.as_deref(), &message)
+                .map_err(|message| Status::unauthenticated(message.in_locale(locale)))?;
+        if let (Message::JoinGame { player_name, .. }, Some(identity)) = (&mut message, identity) {
+            *player_name = identity.name;
+        }
+
+        match self.handle_from(addr, message).await {
+            Response::GameJoined {
+                game_id,
+                join_code,
+                player_id,
+                engine_rules_version,
+                session_token,
+            } => Ok(tonic::Response::new(proto::JoinReply {
+                game_id: game_id.to_string(),
+                join_code: join_code.to_string(),
+                player_id: player_id.to_string(),
+                session_token: session_token
+                    .map(|token| token.to_string())
+                    .unwrap_or_default(),
+                engine_rules_version,
+            })),
+            other => Err(status(other, locale)),
+        }
+    }
+
+    async fn r#move(
+        &self,
+        request: Request<proto::MoveRequest>,
+    ) -> Result<tonic::Response<proto::MoveReply>, Status> {
+        self.throttle(&request)?;
+        let locale = locale(&request);
+        let made = request.into_inner();
+        let game_id: GameId = parse(&made.game_id)?;
+        let token: SessionToken = parse(&made.session_token)?;
+        // The session token stands for the seat, so it is the one moved for
+        let player_id = self
+            .server
+            .seat_of(game_id, &token)
+            .await
+            .ok_or_else(|| Status::permission_denied(text!("no_session_seat").in_locale(locale)))?
+            .to_string();
+        let game_move = match made.kind() {
+            proto::MoveKind::Draw => GameMove::Draw { player_id },
+            proto::MoveKind::Stay => GameMove::Stay { player_id },
+        };
+        let message = Message::MakeMove {
+            game_id,
+            game_move,
+            move_id: made.move_id.as_deref().map(parse::<MoveId>).transpose()?,
+        };
+
+        match self.handle(message).await {
+            Response::MoveAccepted {
+                game_id,
+                state_hash,
+            } => Ok(tonic::Response::new(proto::MoveReply {
+                game_id: game_id.to_string(),
+                state_hash,
+            })),
+            other => Err(status(other, locale)),
+        }
+    }
+
+    type StreamStateStream = StateStream;
+
+    async fn stream_state(
+        &self,
+        request: Request<proto::StreamStateRequest>,
+    ) -> Result<tonic::Response<StateStream>, Status> {
+        self.throttle(&request)?;
+        let locale = locale(&request);
+        let game_id: GameId = parse(&request.into_inner().game_id)?;
+        // Subscribed before fetching the state, so no change falls in between
+        let updates = self
+            .server
+            .subscribe(game_id)
+            .await
+            .ok_or_else(|| Status::not_found(text!("game_not_found").in_locale(locale)))?;
+        let first = match self.handle(Message::GetGameState { game_id, access: None }).await {
+            Response::GameState { game_state } => game_state,
+            other => return Err(status(other, locale)),
+        };
+
+        let first = proto::GameState::of(game_id, &first);
+        let states = stream::unfold(
+            (Some(first), updates),
+            move |(first, mut updates)| async move {
+                if let Some(first) = first {
+                    return Some((Ok(first), (None, updates)));
+                }
+                loop {
+                    match updates.recv().await {
+                        Ok(Response::StateUpdate { game_state, .. }) => {
+                            let state = proto::GameState::of(game_id, &game_state);
+                            return Some((Ok(state), (None, updates)));
+                        }
+                        Ok(Response::GameClosed { .. })
+                        | Err(broadcast::error::RecvError::Closed) => return None,
+                        // Missed updates are made up for by the next one
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    }
+                }
+            },
+        );
+        Ok(tonic::Response::new(Box::pin(states)))
+    }
+}
+
+impl proto::GameState {
+    fn of(game_id: GameId, game: &game_core::GameState) -> Self {
+        let status = GameStatus::of(game);
+        let in_turn = status == GameStatus::InProgress && !game.round_state.is_finished;
+        Self {
+            game_id: game_id.to_string(),
+            status: match status {
+                GameStatus::Open => proto::GameStatus::Open,
+                GameStatus::InProgress => proto::GameStatus::InProgress,
+                GameStatus::Finished => proto::GameStatus::Finished,
+            }
+            .into(),
+            round_number: game.round_state.round_number,
+            players: game
+                .players
+                .iter()
+                .map(|player| proto::Player {
+                    player_id: player.id.clone(),
+                    name: player.name.clone(),
+                    score: player.score,
+                    cards: player
+                        .hand
+                        .cards
+                        .iter()
+                        .map(|card| u32::from(card.value()))
+                        .collect(),
+                    has_stayed: player.has_stayed,
+                    busted: player.hand.is_bust(),
+                    eliminated: player.eliminated,
+                    team: player.team.map(u32::from),
+                })
+                .collect(),
+            current_player_id: in_turn
+                .then(|| game.current_player().map(|player| player.id.clone()))
+                .flatten(),
+            winner_ids: game
+                .outcome
+                .as_ref()
+                .map(|outcome| outcome.winner_ids.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use proto::flip7_client::Flip7Client;
+    use tonic::Code;
+
+    #[tokio::test]
+    async fn test_play_over_grpc() {
+        let transport = GrpcTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        let server = GameServer::new();
+        tokio::spawn(transport.serve(server.clone()));
+        let mut client = Flip7Client::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+
+        let alice = client
+            .join(proto::JoinRequest {
+                player_name: "Alice".to_string(),
+                game_id: None,
+                join_code: None,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let bob = client
+            .join(proto::JoinRequest {
+                player_name: "Bob".to_string(),
+                game_id: None,
+                join_code: Some(alice.join_code.clone()),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(bob.game_id, alice.game_id);
+
+        let mut states = client
+            .stream_state(proto::StreamStateRequest {
+                game_id: alice.game_id.clone(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let open = states.next().await.unwrap().unwrap();
+        assert_eq!(open.status(), proto::GameStatus::Open);
+        assert_eq!(open.players.len(), 2);
+
+        let game_id: GameId = alice.game_id.parse().unwrap();
+        server
+            .handle_message_with_trust(
+                TrustLevel::AuthoritativeServer,
+                Message::StartGame { game_id, host: None },
+            )
+            .await;
+        let started = states.next().await.unwrap().unwrap();
+        assert_eq!(started.status(), proto::GameStatus::InProgress);
+        let current = started.current_player_id.unwrap();
+
+        // Moves are made for the seat of the session token
+        let (mover, waiting) = if current == alice.player_id {
+            (&alice, &bob)
+        } else {
+            (&bob, &alice)
+        };
+        let stay = |seat: &proto::JoinReply| proto::MoveRequest {
+            game_id: alice.game_id.clone(),
+            session_token: seat.session_token.clone(),
+            kind: proto::MoveKind::Stay.into(),
+            move_id: None,
+        };
+        let refused = client.r#move(stay(waiting)).await.unwrap_err();
+        assert_eq!(refused.code(), Code::FailedPrecondition);
+        assert_eq!(refused.message(), "Not your turn");
+        // Errors come in the language the client asked for
+        let mut request = Request::new(stay(waiting));
+        request.metadata_mut().insert("accept-language", "fr".parse().unwrap());
+        let refused = client.r#move(request).await.unwrap_err();
+        assert_eq!(refused.message(), "Ce n'est pas votre tour");
+        client.r#move(stay(mover)).await.unwrap();
+        let moved = states.next().await.unwrap().unwrap();
+        assert!(moved
+            .players
+            .iter()
+            .any(|player| player.player_id == mover.player_id && player.has_stayed));
+
+        let missing = client
+            .stream_state(proto::StreamStateRequest {
+                game_id: GameId::new().to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    GameId, GameServer, InviteToken, JoinCode, PlayerId, ProtocolEngine, SessionToken, Visibility,
+};
+use game_core::GameState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpSocket};
+
+/// Every live game of a server process, handed to its replacement during a deploy.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerSnapshot {
+    pub games: HashMap<GameId, GameState>,
+    /// Session tokens still valid, so clients can reconnect to the new process
+    #[serde(default)]
+    pub sessions: HashMap<SessionToken, (GameId, PlayerId)>,
+    /// Who can find and join each game
+    #[serde(default)]
+    pub visibility: HashMap<GameId, Visibility>,
+    #[serde(default)]
+    pub join_codes: HashMap<GameId, JoinCode>,
+    /// Invitations not used yet
+    #[serde(default)]
+    pub invites: HashMap<InviteToken, GameId>,
+    #[serde(default)]
+    pub hosts: HashMap<GameId, PlayerId>,
+    /// Account each seat of a ranked game is rated under; see `ratings`
+    #[serde(default)]
+    pub ranked: HashMap<GameId, HashMap<PlayerId, String>>,
+}
+
+impl ServerSnapshot {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Binds a listener that another process can bind to as well (SO_REUSEPORT on unix),
+/// so a warm standby can start accepting connections before the old process drains.
+pub fn bind_reusable(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+
+    socket.set_reuseaddr(true)?;
+    #[cfg(unix)]
+    socket.set_reuseport(true)?;
+
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+impl ServerSnapshot {
+    /// Adds the games of another snapshot, e.g. of another shard.
+    pub(crate) fn merge(&mut self, other: ServerSnapshot) {
+        self.games.extend(other.games);
+        self.sessions.extend(other.sessions);
+        self.visibility.extend(other.visibility);
+        self.join_codes.extend(other.join_codes);
+        self.invites.extend(other.invites);
+        self.hosts.extend(other.hosts);
+        self.ranked.extend(other.ranked);
+    }
+
+    /// Splits the snapshot into one per shard, by the shard of each game.
+    fn split(self, shards: usize) -> Vec<ServerSnapshot> {
+        let mut split = vec![ServerSnapshot::default(); shards];
+        for (id, game) in self.games {
+            split[id.shard(shards)].games.insert(id, game);
+        }
+        for (token, (id, player_id)) in self.sessions {
+            split[id.shard(shards)].sessions.insert(token, (id, player_id));
+        }
+        for (id, visibility) in self.visibility {
+            split[id.shard(shards)].visibility.insert(id, visibility);
+        }
+        for (id, code) in self.join_codes {
+            split[id.shard(shards)].join_codes.insert(id, code);
+        }
+        for (invite, id) in self.invites {
+            split[id.shard(shards)].invites.insert(invite, id);
+        }
+        for (id, host) in self.hosts {
+            split[id.shard(shards)].hosts.insert(id, host);
+        }
+        for (id, accounts) in self.ranked {
+            split[id.shard(shards)].ranked.insert(id, accounts);
+        }
+        split
+    }
+}
+
+impl ProtocolEngine {
+    fn export_games(&self) -> ServerSnapshot {
+        ServerSnapshot {
+            games: self.games.clone(),
+            sessions: self.sessions.clone(),
+            visibility: self.visibility.clone(),
+            join_codes: self.join_codes.clone(),
+            invites: self.invites.clone(),
+            hosts: self.hosts.clone(),
+            ranked: self.ranked.clone(),
+        }
+    }
+
+    /// What there is of one game, as a snapshot of it alone.
+    pub(crate) fn export_game(&self, game_id: GameId) -> Option<ServerSnapshot> {
+        let game = self.games.get(&game_id)?.clone();
+        let mut snapshot = ServerSnapshot {
+            games: HashMap::from([(game_id, game)]),
+            sessions: self
+                .sessions
+                .iter()
+                .filter(|(_, (id, _))| *id == game_id)
+                .map(|(token, seat)| (*token, *seat))
+                .collect(),
+            invites: self
+                .invites
+                .iter()
+                .filter(|(_, id)| **id == game_id)
+                .map(|(invite, id)| (*invite, *id))
+                .collect(),
+            ..ServerSnapshot::default()
+        };
+        if let Some(visibility) = self.visibility.get(&game_id) {
+            snapshot.visibility.insert(game_id, visibility.clone());
+        }
+        if let Some(code) = self.join_codes.get(&game_id) {
+            snapshot.join_codes.insert(game_id, code.clone());
+        }
+        if let Some(host) = self.hosts.get(&game_id) {
+            snapshot.hosts.insert(game_id, *host);
+        }
+        if let Some(accounts) = self.ranked.get(&game_id) {
+            snapshot.ranked.insert(game_id, accounts.clone());
+        }
+        Some(snapshot)
+    }
+
+    pub(crate) fn import_games(&mut self, snapshot: ServerSnapshot) -> Vec<GameId> {
+        let ids: Vec<GameId> = snapshot.games.keys().copied().collect();
+        for (id, mut game) in snapshot.games {
+            // A variant the new process doesn't know keeps the official scoring
+            let _ = self.variants.restore_scoring(&mut game);
+            self.games.insert(id, game);
+        }
+        self.sessions.extend(snapshot.sessions);
+        self.visibility.extend(snapshot.visibility);
+        for (id, code) in snapshot.join_codes {
+            // A code another shard's game took meanwhile is drawn again below
+            if self.join_codes.get(&id) == Some(&code) || self.claim_code(&code) {
+                self.join_codes.insert(id, code);
+            }
+        }
+        self.invites.extend(snapshot.invites);
+        self.hosts.extend(snapshot.hosts);
+        self.ranked.extend(snapshot.ranked);
+        // Games from a process without join codes get new ones
+        for id in &ids {
+            self.join_code(*id);
+        }
+        ids
+    }
+}
+
+impl GameServer {
+    /// Copies every live game so it can be handed over to another process.
+    pub async fn export_games(&self) -> ServerSnapshot {
+        let mut snapshot = ServerSnapshot::default();
+        for engine in self.shards.all() {
+            snapshot.merge(engine.read().await.export_games());
+        }
+        snapshot
+    }
+
+    /// Takes over the games of a previous process. Games already present are
+    /// replaced by the imported copy. Returns the ids of the imported games so
+    /// their clients can be told to resync.
+    pub async fn import_games(&self, snapshot: ServerSnapshot) -> Vec<GameId> {
+        let engines = self.shards.all();
+        let mut ids = Vec::new();
+        for (engine, part) in engines.iter().zip(snapshot.split(engines.len())) {
+            ids.extend(engine.write().await.import_games(part));
+        }
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Message, Response};
+
+    #[tokio::test]
+    async fn test_handover_keeps_games() {
+        let old_server = GameServer::new();
+        let (game_id, token) = match old_server
+            .handle_message(Message::JoinGame {
+                player_name: "Alice".to_string(),
+                game_id: None,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            })
+            .await
+        {
+            Response::GameJoined {
+                game_id,
+                session_token: Some(token),
+                ..
+            } => (game_id, token),
+            _ => panic!("Expected GameJoined response"),
+        };
+
+        let json = old_server.export_games().await.to_json().unwrap();
+
+        let new_server = GameServer::new();
+        let imported = new_server
+            .import_games(ServerSnapshot::from_json(&json).unwrap())
+            .await;
+        assert_eq!(imported, vec![game_id]);
+
+        match new_server
+            .handle_message(Message::GetGameState { game_id, access: None })
+            .await
+        {
+            Response::GameState { game_state } => assert_eq!(game_state.players.len(), 1),
+            _ => panic!("Expected GameState response"),
+        }
+
+        // Players can take their seats back on the new process
+        assert!(matches!(
+            new_server.handle_message(Message::Reconnect { token }).await,
+            Response::Reconnected { .. }
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_standby_can_bind_same_port() {
+        let active = bind_reusable("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = active.local_addr().unwrap();
+
+        let standby = bind_reusable(addr).unwrap();
+        assert_eq!(standby.local_addr().unwrap(), addr);
+    }
+}
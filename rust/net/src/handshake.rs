@@ -0,0 +1,89 @@
+//! The `Hello`/`Welcome` handshake clients open a connection with, so both
+//! ends agree on a protocol version, an encoding, the optional features they
+//! share and the locale errors come in before anything else is said. A
+//! client newer than the server is told to speak the server's version; one
+//! older than the server still serves is turned away with an error it can
+//! show, rather than with messages it cannot parse.
+//!
+//! Clients that skip the handshake are served as speaking version 1 in JSON.
+
+use crate::i18n::text;
+use crate::{Encoding, Locale, Response};
+
+/// Version of the protocol this build speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest protocol version still served.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features this server supports. They are named rather than an
+/// enum so that a server reading a `Hello` from a newer client ignores the
+/// features it has never heard of instead of failing to parse it.
+pub const FEATURES: &[&str] = &[
+    "state-updates",
+    "state-deltas",
+    "reconnect",
+    "heartbeat",
+    "lobby",
+    "invites",
+    "quick-play",
+    "host-controls",
+    "reactions",
+    "round-flow",
+    "turn-clock",
+    "rematch",
+    "spectate",
+];
+
+/// The server's answer to a client's `Hello`.
+pub(crate) fn welcome(
+    protocol_version: u32,
+    encodings: &[Encoding],
+    features: &[String],
+    locale: Option<&str>,
+) -> Response {
+    if protocol_version < MIN_PROTOCOL_VERSION {
+        return Response::Error {
+            message: text!("protocol_too_old", protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION),
+        };
+    }
+    Response::Welcome {
+        protocol_version: protocol_version.min(PROTOCOL_VERSION),
+        encoding: Encoding::negotiate(encodings),
+        features: features
+            .iter()
+            .filter(|feature| FEATURES.contains(&feature.as_str()))
+            .cloned()
+            .collect(),
+        locale: locale.and_then(Locale::from_tag).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_welcome() {
+        let features = vec!["reactions".to_string(), "teleport".to_string()];
+        match welcome(PROTOCOL_VERSION + 1, &[Encoding::Json], &features, Some("fr-BE")) {
+            Response::Welcome {
+                protocol_version,
+                encoding,
+                features,
+                locale,
+            } => {
+                assert_eq!(protocol_version, PROTOCOL_VERSION);
+                assert_eq!(encoding, Encoding::Json);
+                assert_eq!(features, vec!["reactions".to_string()]);
+                assert_eq!(locale, Locale::Fr);
+            }
+            other => panic!("Expected Welcome response, got {:?}", other),
+        }
+
+        assert!(matches!(
+            welcome(MIN_PROTOCOL_VERSION - 1, &[Encoding::Json], &[], None),
+            Response::Error { .. }
+        ));
+    }
+}
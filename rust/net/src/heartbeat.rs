@@ -0,0 +1,125 @@
+//! Disconnect detection. Clients count as alive as long as they send
+//! something, a `Ping` if nothing else, at least every heartbeat interval;
+//! a player who misses too many in a row is marked disconnected until they
+//! are heard from again.
+
+use crate::{GameId, PlayerId};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// When players count as gone, and what happens then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    /// How often clients are expected to send something
+    pub interval: Duration,
+    /// Intervals a player may miss before being marked disconnected
+    pub missed_limit: u32,
+    /// Whether the server stays for disconnected players when their turn
+    /// comes, rather than leaving the table waiting on them
+    pub auto_stay: bool,
+    /// Whether a round under way is paused while any of its players is
+    /// disconnected, so that turn clocks and skip votes wait for them, and
+    /// resumed once they are all back. Nobody can move in a paused game, so
+    /// this takes over from `auto_stay`.
+    pub pause: bool,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            missed_limit: 3,
+            auto_stay: false,
+            pause: false,
+        }
+    }
+}
+
+impl Heartbeat {
+    /// Silence after which a player is marked disconnected.
+    pub fn timeout(&self) -> Duration {
+        self.interval * self.missed_limit
+    }
+}
+
+/// When each player was last heard from. Only players seen at least once
+/// are tracked, so seats played in-process or by bots never time out.
+#[derive(Debug, Default)]
+pub(crate) struct Liveness {
+    last_seen: HashMap<PlayerId, (GameId, Instant)>,
+    disconnected: HashSet<PlayerId>,
+}
+
+impl Liveness {
+    /// Records that the player was heard from. True if they had been marked
+    /// disconnected.
+    pub(crate) fn seen(&mut self, game_id: GameId, player_id: PlayerId, now: Instant) -> bool {
+        self.last_seen.insert(player_id, (game_id, now));
+        self.disconnected.remove(&player_id)
+    }
+
+    /// Marks players silent for longer than `timeout` as disconnected, and
+    /// returns those who weren't already.
+    pub(crate) fn check(&mut self, timeout: Duration, now: Instant) -> Vec<(GameId, PlayerId)> {
+        let mut gone: Vec<(GameId, PlayerId)> = self
+            .last_seen
+            .iter()
+            .filter(|(player_id, (_, seen))| {
+                now.duration_since(*seen) > timeout && !self.disconnected.contains(player_id)
+            })
+            .map(|(player_id, (game_id, _))| (*game_id, *player_id))
+            .collect();
+        gone.sort();
+        self.disconnected.extend(gone.iter().map(|(_, player_id)| *player_id));
+        gone
+    }
+
+    pub(crate) fn is_disconnected(&self, player_id: &str) -> bool {
+        player_id
+            .parse()
+            .is_ok_and(|player_id: PlayerId| self.disconnected.contains(&player_id))
+    }
+
+    /// Whether any of the players is marked disconnected.
+    pub(crate) fn any_disconnected<'a>(&self, mut player_ids: impl Iterator<Item = &'a str>) -> bool {
+        player_ids.any(|player_id| self.is_disconnected(player_id))
+    }
+
+    pub(crate) fn forget(&mut self, player_id: PlayerId) {
+        self.last_seen.remove(&player_id);
+        self.disconnected.remove(&player_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liveness() {
+        let heartbeat = Heartbeat::default();
+        let mut liveness = Liveness::default();
+        let (game_id, alice, bob) = (GameId::new(), PlayerId::new(), PlayerId::new());
+        let start = Instant::now();
+        liveness.seen(game_id, alice, start);
+        liveness.seen(game_id, bob, start);
+
+        // Bob keeps pinging, Alice goes quiet
+        let later = start + heartbeat.timeout() + Duration::from_secs(1);
+        liveness.seen(game_id, bob, later - heartbeat.interval);
+        assert_eq!(liveness.check(heartbeat.timeout(), later), vec![(game_id, alice)]);
+        assert!(liveness.is_disconnected(&alice.to_string()));
+        assert!(!liveness.is_disconnected(&bob.to_string()));
+        assert!(!liveness.is_disconnected("not a player id"));
+
+        // Reported once, until heard from again
+        assert!(liveness.check(heartbeat.timeout(), later).is_empty());
+        assert!(liveness.seen(game_id, alice, later));
+        assert!(!liveness.seen(game_id, alice, later));
+        assert!(!liveness.is_disconnected(&alice.to_string()));
+
+        liveness.forget(alice);
+        let much_later = later + heartbeat.timeout() * 2;
+        assert_eq!(liveness.check(heartbeat.timeout(), much_later), vec![(game_id, bob)]);
+    }
+}
@@ -0,0 +1,691 @@
+//! HTTP API, for web clients and testing with curl where holding a socket
+//! open is a bother. Bodies are the protocol's own `Message`s and
+//! `Response`s, as JSON:
+//!
+//! - `POST /games` takes a `CreateGame`
+//! - `POST /games/{id}/join` takes a `JoinGame`
+//! - `POST /games/{id}/moves` takes a `MakeMove`, sent with the player's
+//!   session token as `Authorization: Bearer <token>`
+//! - `GET /games/{id}` answers a `GameState`
+//! - `GET /games/{id}/events` streams the game's `GameEvent`s as server-sent
+//!   events, for spectators and dashboards. Protected games show both only
+//!   to their players, with their session token as the bearer token
+//! - `GET /leaderboards/{board}` answers a `Leaderboard`, for `wins`,
+//!   `rating`, `solo` or `daily` with `?date=YYYY-MM-DD`. `offset` and
+//!   `limit` page it, and `among`, a comma-separated list of accounts,
+//!   ranks those alone
+//!
+//! With the `graphql` feature the same games are served as GraphQL at
+//! `/graphql`; see `graphql`.
+//!
+//! The game id in the path is the one acted on, whatever the body says.
+//! Players poll `GET /games/{id}` for updates; only events are pushed.
+//! On servers with an `Auth`, creating and joining games take the player's
+//! auth token as the bearer token instead, as guests can only look. Errors
+//! come in the locale of the request's `Accept-Language`.
+
+use crate::auth::{self, Identity};
+use crate::i18n::{text, Locale, Text};
+use crate::transport::Transport;
+use crate::{Access, Board, Encoding, GameId, GameServer, Message, Response, SessionToken, TrustLevel};
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{stream, Stream};
+use game_core::events::GameEvent;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::broadcast;
+
+pub struct HttpTransport {
+    listener: TcpListener,
+    trust: TrustLevel,
+}
+
+impl HttpTransport {
+    pub async fn bind(addr: impl ToSocketAddrs, trust: TrustLevel) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr).await?, trust))
+    }
+
+    pub fn from_listener(listener: TcpListener, trust: TrustLevel) -> Self {
+        Self { listener, trust }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    async fn serve(self, server: GameServer) -> io::Result<()> {
+        let app = router(server, self.trust).into_make_service_with_connect_info::<SocketAddr>();
+        axum::serve(self.listener, app).await
+    }
+}
+
+#[derive(Clone)]
+struct Api {
+    server: GameServer,
+    trust: TrustLevel,
+}
+
+/// The API's routes, for serving alongside others. Handlers need the
+/// client's `ConnectInfo<SocketAddr>`, and nothing runs the server's timers.
+pub fn router(server: GameServer, trust: TrustLevel) -> Router {
+    let router = Router::new()
+        .route("/games", post(create_game))
+        .route("/games/{id}", get(game_state))
+        .route("/games/{id}/join", post(join_game))
+        .route("/games/{id}/moves", post(make_move))
+        .route("/games/{id}/events", get(game_events))
+        .route("/leaderboards/{board}", get(leaderboard))
+        .with_state(Api {
+            server: server.clone(),
+            trust,
+        })
+        .layer(middleware::from_fn(localize));
+    #[cfg(feature = "graphql")]
+    let router = router.merge(crate::graphql::router(server, trust));
+    router
+}
+
+/// A response with its status. An error keeps its message on the HTTP
+/// response too, for `localize` to translate.
+struct Reply(StatusCode, Response);
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> axum::response::Response {
+        let mut response = (self.0, Json(&self.1)).into_response();
+        if let Response::Error { message } = self.1 {
+            response.extensions_mut().insert(message);
+        }
+        response
+    }
+}
+
+fn error(status: StatusCode, message: Text) -> Reply {
+    Reply(status, Response::Error { message })
+}
+
+fn reply(response: Response) -> Reply {
+    let status = match &response {
+        Response::Error { message } if message.key() == Some("game_not_found") => StatusCode::NOT_FOUND,
+        Response::Error { .. } => StatusCode::BAD_REQUEST,
+        Response::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::OK,
+    };
+    Reply(status, response)
+}
+
+/// Writes an error again in the locale of the request's `Accept-Language`.
+async fn localize(request: Request, next: Next) -> axum::response::Response {
+    let locale = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| Locale::from_accept_language(value.to_str().ok()?));
+    let response = next.run(request).await;
+    match (locale, response.extensions().get::<Text>()) {
+        (Some(locale), Some(message)) if locale != Locale::En => {
+            let message = message.in_locale(locale);
+            Reply(response.status(), Response::Error { message }).into_response()
+        }
+        _ => response,
+    }
+}
+
+impl Api {
+    /// Why a request from an untrusted client isn't handled, if it is too
+    /// large or the client's address sends too fast.
+    fn throttle(&self, addr: SocketAddr, body: &[u8]) -> Result<(), Reply> {
+        match self
+            .server
+            .throttle_request(self.trust, addr.ip(), body.len())
+        {
+            Some(limited) => Err(reply(limited)),
+            None => Ok(()),
+        }
+    }
+
+    /// Who the bearer token says the client is on a server with an `Auth`,
+    /// or `None` for guests allowed to send the message.
+    fn sign_in(&self, headers: &HeaderMap, message: &Message) -> Result<Option<Identity>, Reply> {
+        auth::sign_in_request(self.server.auth().as_ref(), bearer(headers), message)
+            .map_err(|message| error(StatusCode::UNAUTHORIZED, message))
+    }
+
+    /// Handles a message that may take the client at `addr` into a game;
+    /// see `GameServer::handle_request`.
+    async fn handle_from(&self, addr: SocketAddr, message: Message) -> Reply {
+        reply(
+            self.server
+                .handle_request(self.trust, addr.ip(), message)
+                .await,
+        )
+    }
+
+    async fn handle(&self, message: Message) -> Reply {
+        reply(
+            self.server
+                .handle_message_with_trust(self.trust, message)
+                .await,
+        )
+    }
+}
+
+pub(crate) fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// How a request looks at a protected game: with the session token of a
+/// seat in it, if it sends one.
+fn seat_access(headers: &HeaderMap) -> Option<Access> {
+    bearer(headers)?.parse().ok().map(Access::Seat)
+}
+
+fn parse_game_id(id: &str) -> Result<GameId, Reply> {
+    id.parse()
+        .map_err(|_: String| error(StatusCode::NOT_FOUND, text!("game_not_found")))
+}
+
+/// The body as a message, which must be of the kind the route takes.
+fn decode(body: &[u8], kind: &str, is_kind: fn(&Message) -> bool) -> Result<Message, Reply> {
+    let message = Encoding::Json
+        .decode::<Message>(body)
+        .map_err(|err| error(StatusCode::BAD_REQUEST, text!("invalid_json", err)))?;
+    if !is_kind(&message) {
+        return Err(error(StatusCode::BAD_REQUEST, text!("expected_message", kind)));
+    }
+    Ok(message)
+}
+
+async fn create_game(
+    State(api): State<Api>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Reply, Reply> {
+    api.throttle(addr, &body)?;
+    let message = decode(&body, "CreateGame", |message| {
+        matches!(message, Message::CreateGame { .. })
+    })?;
+    api.sign_in(&headers, &message)?;
+    Ok(api.handle_from(addr, message).await)
+}
+
+async fn join_game(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Reply, Reply> {
+    api.throttle(addr, &body)?;
+    let id = parse_game_id(&id)?;
+    let mut message = decode(&body, "JoinGame", |message| {
+        matches!(message, Message::JoinGame { .. })
+    })?;
+    let identity = api.sign_in(&headers, &message)?;
+    if let Message::JoinGame {
+        player_name,
+        game_id,
+        code,
+        ..
+    } = &mut message
+    {
+        *game_id = Some(id);
+        *code = None;
+        if let Some(identity) = identity {
+            *player_name = identity.name;
+        }
+    }
+    Ok(api.handle_from(addr, message).await)
+}
+
+async fn make_move(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Reply, Reply> {
+    api.throttle(addr, &body)?;
+    let id = parse_game_id(&id)?;
+    let mut message = decode(&body, "MakeMove", |message| {
+        matches!(message, Message::MakeMove { .. })
+    })?;
+    if let Message::MakeMove { game_id, .. } = &mut message {
+        *game_id = id;
+    }
+
+    // A move is only made with the session token of the seat it's for
+    let token: SessionToken = bearer(&headers)
+        .ok_or_else(|| error(StatusCode::UNAUTHORIZED, text!("moves_need_token")))?
+        .parse()
+        .map_err(|_: String| error(StatusCode::UNAUTHORIZED, text!("unknown_session")))?;
+    let seat = api.server.seat_of(id, &token).await;
+    let own = match (seat, auth::acting_player(&message)) {
+        (Some(seat), Some(Ok(player_id))) => seat == player_id,
+        _ => false,
+    };
+    if !own {
+        return Err(error(StatusCode::FORBIDDEN, text!("own_seats_only")));
+    }
+    Ok(api.handle(message).await)
+}
+
+async fn game_state(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Reply, Reply> {
+    api.throttle(addr, &[])?;
+    let id = parse_game_id(&id)?;
+    let message = Message::GetGameState {
+        game_id: id,
+        access: seat_access(&headers),
+    };
+    Ok(api.handle(message).await)
+}
+
+/// Streams the game's events as they happen. Each server-sent event holds
+/// one `GameEvent` as JSON, with its index in `GameState::events` as id, so
+/// a client reconnecting with `Last-Event-ID` picks up where it left off. The
+/// stream ends when the game is closed.
+async fn game_events(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, Reply> {
+    api.throttle(addr, &[])?;
+    let id = parse_game_id(&id)?;
+    // Subscribed before fetching the state, so no event falls in between
+    let updates = api
+        .server
+        .subscribe(id)
+        .await
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, text!("game_not_found")))?;
+    let message = Message::GetGameState {
+        game_id: id,
+        access: seat_access(&headers),
+    };
+    let game_state = match api.handle(message).await {
+        Reply(_, Response::GameState { game_state }) => game_state,
+        other => return Err(other),
+    };
+    let sent = headers
+        .get("last-event-id")
+        .and_then(|last| last.to_str().ok()?.parse::<usize>().ok())
+        .map_or(0, |last| last + 1);
+
+    let mut follow = Follow {
+        updates,
+        pending: VecDeque::new(),
+        sent,
+    };
+    follow.catch_up(&game_state.events);
+    let events = stream::unfold(follow, |mut follow| async move {
+        loop {
+            if let Some(event) = follow.pending.pop_front() {
+                return Some((Ok(event), follow));
+            }
+            match follow.updates.recv().await {
+                Ok(Response::StateUpdate { game_state, .. }) => follow.catch_up(&game_state.events),
+                Ok(Response::GameClosed { .. }) | Err(broadcast::error::RecvError::Closed) => {
+                    return None
+                }
+                // Missed updates are made up for by the next one's events
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            }
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct Page {
+    date: Option<String>,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    among: Option<String>,
+}
+
+async fn leaderboard(
+    State(api): State<Api>,
+    Path(board): Path<String>,
+    Query(page): Query<Page>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Result<Reply, Reply> {
+    api.throttle(addr, &[])?;
+    let board = match (board.as_str(), page.date) {
+        ("wins", _) => Board::Wins,
+        ("rating", _) => Board::Rating,
+        ("solo", _) => Board::SoloScore,
+        ("daily", Some(date)) => Board::Daily { date },
+        ("daily", None) => return Err(error(StatusCode::BAD_REQUEST, text!("daily_needs_date"))),
+        _ => return Err(error(StatusCode::NOT_FOUND, text!("unknown_board", board))),
+    };
+    let among = page
+        .among
+        .iter()
+        .flat_map(|among| among.split(','))
+        .filter(|account| !account.is_empty())
+        .map(str::to_string)
+        .collect();
+    let message = Message::GetLeaderboard {
+        board,
+        among,
+        offset: page.offset,
+        limit: page.limit,
+    };
+    Ok(api.handle(message).await)
+}
+
+/// A client following a game's events.
+struct Follow {
+    updates: broadcast::Receiver<Response>,
+    pending: VecDeque<Event>,
+    /// Events the client has had, or said it had
+    sent: usize,
+}
+
+impl Follow {
+    fn catch_up(&mut self, events: &[GameEvent]) {
+        for (index, event) in events.iter().enumerate().skip(self.sent) {
+            let event = Event::default()
+                .id(index.to_string())
+                .json_data(event)
+                .expect("events always serialize");
+            self.pending.push_back(event);
+        }
+        self.sent = self.sent.max(events.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Visibility;
+    use axum::body::Body;
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::Request;
+    use game_core::GameMove;
+    use tower::ServiceExt;
+
+    fn app(server: &GameServer) -> Router {
+        router(server.clone(), TrustLevel::UntrustedPeer)
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 9000))))
+    }
+
+    async fn send(
+        server: &GameServer,
+        path: &str,
+        token: Option<String>,
+        message: Option<Message>,
+    ) -> (StatusCode, Response) {
+        let request = Request::builder()
+            .method(if message.is_some() { "POST" } else { "GET" })
+            .uri(path);
+        let request = match token {
+            Some(token) => request.header(header::AUTHORIZATION, format!("Bearer {}", token)),
+            None => request,
+        };
+        let body = message.map_or_else(Body::empty, |message| {
+            Body::from(serde_json::to_vec(&message).unwrap())
+        });
+        let reply = app(server)
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = reply.status();
+        let body = axum::body::to_bytes(reply.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn join(name: &str) -> Message {
+        Message::JoinGame {
+            player_name: name.to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_play_over_http() {
+        let server = GameServer::new();
+        let create = Message::CreateGame {
+            rules: None,
+            visibility: Visibility::Public,
+            turn_clock: None,
+        };
+        let game_id = match send(&server, "/games", None, Some(create)).await {
+            (StatusCode::OK, Response::GameCreated { game_id, .. }) => game_id,
+            other => panic!("Expected GameCreated response, got {:?}", other),
+        };
+
+        let mut seats = Vec::new();
+        for name in ["Alice", "Bob"] {
+            let path = format!("/games/{}/join", game_id);
+            match send(&server, &path, None, Some(join(name))).await {
+                (
+                    StatusCode::OK,
+                    Response::GameJoined {
+                        game_id: joined,
+                        player_id,
+                        session_token: Some(token),
+                        ..
+                    },
+                ) => {
+                    assert_eq!(joined, game_id);
+                    seats.push((player_id, token));
+                }
+                other => panic!("Expected GameJoined response, got {:?}", other),
+            }
+        }
+        server
+            .handle_message_with_trust(
+                TrustLevel::AuthoritativeServer,
+                Message::StartGame { game_id, host: None },
+            )
+            .await;
+
+        let state = match send(&server, &format!("/games/{}", game_id), None, None).await {
+            (StatusCode::OK, Response::GameState { game_state }) => game_state,
+            other => panic!("Expected GameState response, got {:?}", other),
+        };
+        let current = state.current_player().unwrap().id.clone();
+        let (player_id, token) = seats
+            .iter()
+            .find(|(player_id, _)| player_id.to_string() == current)
+            .unwrap();
+        let (_, other_token) = seats
+            .iter()
+            .find(|(player_id, _)| player_id.to_string() != current)
+            .unwrap();
+        let stay = Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+            move_id: None,
+        };
+
+        // Only the seat's own session token makes its moves
+        let moves = format!("/games/{}/moves", game_id);
+        let (status, _) = send(&server, &moves, None, Some(stay.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(
+            &server,
+            &moves,
+            Some(other_token.to_string()),
+            Some(stay.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        match send(&server, &moves, Some(token.to_string()), Some(stay)).await {
+            (StatusCode::OK, Response::MoveAccepted { .. }) => {}
+            other => panic!("Expected MoveAccepted response, got {:?}", other),
+        }
+
+        // The route takes only its own message
+        let (status, reply) = send(&server, &moves, None, Some(join("Carol"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(
+            matches!(reply, Response::Error { message } if message == "Expected a MakeMove message")
+        );
+        let (status, _) = send(&server, &format!("/games/{}", GameId::new()), None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Errors come in the language the client asked for
+        let request = Request::builder()
+            .uri(format!("/games/{}", GameId::new()))
+            .header(header::ACCEPT_LANGUAGE, "fr-FR, fr;q=0.9")
+            .body(Body::empty())
+            .unwrap();
+        let reply = app(&server).oneshot(request).await.unwrap();
+        assert_eq!(reply.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(reply.into_body(), usize::MAX).await.unwrap();
+        match serde_json::from_slice(&body).unwrap() {
+            Response::Error { message } => assert_eq!(message, "Partie introuvable"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_follow_game_events() {
+        let server = GameServer::new();
+        let mut seats = Vec::new();
+        let mut game_id = None;
+        for name in ["Alice", "Bob"] {
+            let mut message = join(name);
+            if let Message::JoinGame {
+                game_id: joined, ..
+            } = &mut message
+            {
+                *joined = game_id;
+            }
+            match server.handle_message(message).await {
+                Response::GameJoined {
+                    game_id: joined,
+                    player_id,
+                    session_token: Some(token),
+                    ..
+                } => {
+                    game_id = Some(joined);
+                    seats.push((player_id, token));
+                }
+                other => panic!("Expected GameJoined response, got {:?}", other),
+            }
+        }
+        let game_id = game_id.unwrap();
+        server
+            .handle_message_with_trust(
+                TrustLevel::AuthoritativeServer,
+                Message::StartGame { game_id, host: None },
+            )
+            .await;
+
+        // Picks up after the round started
+        let request = Request::builder()
+            .uri(format!("/games/{}/events", game_id))
+            .header("last-event-id", "0")
+            .body(Body::empty())
+            .unwrap();
+        let reply = app(&server).oneshot(request).await.unwrap();
+        assert_eq!(reply.status(), StatusCode::OK);
+
+        let current = match server
+            .handle_message(Message::GetGameState { game_id, access: None })
+            .await
+        {
+            Response::GameState { game_state } => game_state.current_player().unwrap().id.clone(),
+            other => panic!("Expected GameState response, got {:?}", other),
+        };
+        server
+            .handle_message(Message::MakeMove {
+                game_id,
+                game_move: GameMove::Stay { player_id: current },
+                move_id: None,
+            })
+            .await;
+        server
+            .handle_message(Message::CloseGame {
+                game_id,
+                host: seats[0].1,
+            })
+            .await;
+
+        // Closing the game ends the stream
+        let body = axum::body::to_bytes(reply.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(!body.contains("RoundStarted"));
+        assert!(body.contains("id: 1\n"));
+        assert!(body.contains("PlayerStayed"));
+
+        let (status, _) = send(&server, &format!("/games/{}/events", game_id), None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_leaderboards() {
+        let server = GameServer::new();
+        {
+            let lobby = server.shards.lobby().read().await;
+            let mut boards = lobby.leaderboards.lock().unwrap();
+            for (account, wins) in [("ada", 2), ("bo", 3), ("cy", 1)] {
+                for _ in 0..wins {
+                    boards.won(account, account);
+                }
+            }
+        }
+
+        match send(&server, "/leaderboards/wins?offset=1&limit=1", None, None).await {
+            (StatusCode::OK, Response::Leaderboard { entries, total, .. }) => {
+                assert_eq!(total, 3);
+                assert_eq!(entries.len(), 1);
+                assert_eq!((entries[0].rank, entries[0].account.as_str()), (2, "ada"));
+            }
+            other => panic!("Expected Leaderboard response, got {:?}", other),
+        }
+        match send(&server, "/leaderboards/wins?among=cy,ada", None, None).await {
+            (StatusCode::OK, Response::Leaderboard { entries, .. }) => {
+                let accounts: Vec<&str> = entries.iter().map(|entry| entry.account.as_str()).collect();
+                assert_eq!(accounts, ["ada", "cy"]);
+            }
+            other => panic!("Expected Leaderboard response, got {:?}", other),
+        }
+        match send(&server, "/leaderboards/daily?date=2024-03-01", None, None).await {
+            (StatusCode::OK, Response::Leaderboard { board, total, .. }) => {
+                assert_eq!(board, Board::Daily { date: "2024-03-01".to_string() });
+                assert_eq!(total, 0);
+            }
+            other => panic!("Expected Leaderboard response, got {:?}", other),
+        }
+        let (status, _) = send(&server, "/leaderboards/daily", None, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&server, "/leaderboards/losses", None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,532 @@
+//! Translations of the errors the server sends players.
+//!
+//! Errors players see are made as `Text`: a catalog key and its arguments,
+//! with the English text alongside for logs and for clients that didn't ask
+//! for a locale. The `text!` macro builds one and checks at compile time that
+//! the key is in the catalog and takes that many arguments. On the way out,
+//! every transport writes the text again in the locale its client asked for,
+//! in `Hello` or in an `Accept-Language` header.
+//!
+//! The game core's errors are the constants of `game_core::errors`, and have
+//! their own entries. Any other message, such as a parse error from serde,
+//! goes out as it is.
+
+use game_core::errors;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::ops::Deref;
+
+/// A language errors can be sent in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+impl Locale {
+    /// The locale of a language tag such as `fr-CA`, or of a POSIX locale
+    /// such as `fr_FR.UTF-8`, if the catalog has its language.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_', '.']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Some(Locale::En),
+            "fr" => Some(Locale::Fr),
+            _ => None,
+        }
+    }
+
+    /// The first locale of an `Accept-Language` header the catalog has.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        header
+            .split(',')
+            .find_map(|range| Self::from_tag(range.split(';').next().unwrap_or_default().trim()))
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+        }
+    }
+}
+
+/// A message of the catalog, with its text in each locale. `{}` stands for
+/// an argument, filled in in order. Entries of the game core's errors are
+/// marked `core`, as those arrive as plain strings.
+struct Entry {
+    key: &'static str,
+    en: &'static str,
+    fr: &'static str,
+    core: bool,
+}
+
+const fn entry(key: &'static str, en: &'static str, fr: &'static str) -> Entry {
+    Entry { key, en, fr, core: false }
+}
+
+const fn core(key: &'static str, en: &'static str, fr: &'static str) -> Entry {
+    Entry { key, en, fr, core: true }
+}
+
+const CATALOG: &[Entry] = &[
+    // Games and seats
+    entry("game_not_found", "Game not found", "Partie introuvable"),
+    core("player_not_found", errors::PLAYER_NOT_FOUND, "Joueur introuvable"),
+    entry("no_such_player", "No such player in this game", "Ce joueur n'est pas dans cette partie"),
+    entry("invalid_player_id", "Invalid player id '{}'", "Identifiant de joueur invalide « {} »"),
+    entry("wrong_variant", "Game is playing the {} variant", "La partie se joue avec la variante {}"),
+    entry("turn_seconds", "Turns must last between 1 and {} seconds", "Les tours doivent durer entre 1 et {} secondes"),
+    entry("unknown_variant", "Unknown variant '{}' (known: {})", "Variante inconnue « {} » (connues : {})"),
+    entry("no_join_code", "No game has the join code {}", "Aucune partie n'a le code {}"),
+    entry("join_code_other_game", "Join code {} is for another game", "Le code {} est celui d'une autre partie"),
+    entry("invalid_join_code", "Invalid join code '{}'", "Code de partie invalide « {} »"),
+    entry("password_needed", "This game needs a password", "Cette partie demande un mot de passe"),
+    entry("wrong_password", "Wrong password", "Mot de passe incorrect"),
+    entry("invite_only", "This game is invite-only", "Cette partie est sur invitation uniquement"),
+    entry("invalid_invitation", "Invalid invitation", "Invitation invalide"),
+    entry("game_started", "The game has already started", "La partie a déjà commencé"),
+    entry("game_full", "The game is full", "La partie est complète"),
+    core("no_players_added", errors::NO_PLAYERS_ADDED, "Aucun joueur ajouté"),
+    entry("not_queued", "Not in the quick play queue", "Vous n'êtes pas dans la file de partie rapide"),
+    entry("unknown_session", "Unknown session token", "Jeton de session inconnu"),
+    entry(
+        "no_session_seat",
+        "No seat in the game has that session token",
+        "Aucune place de la partie n'a ce jeton de session",
+    ),
+    entry("seat_gone", "The seat of this session no longer exists", "La place de cette session n'existe plus"),
+    entry(
+        "game_changed_elsewhere",
+        "The game changed on another server; try again",
+        "La partie a changé sur un autre serveur ; réessayez",
+    ),
+    // Hosting
+    entry("host_only_invite", "Only the host can invite players", "Seul l'hôte peut inviter des joueurs"),
+    entry("host_only_kick", "Only the host can kick players", "Seul l'hôte peut exclure des joueurs"),
+    entry("host_only_transfer", "Only the host can hand over hosting", "Seul l'hôte peut céder son rôle d'hôte"),
+    entry("host_only_close", "Only the host can close the game", "Seul l'hôte peut fermer la partie"),
+    entry("host_only_start", "Only the host can start the game", "Seul l'hôte peut lancer la partie"),
+    entry(
+        "host_kicks_self",
+        "The host can't kick themselves; leave the game instead",
+        "L'hôte ne peut pas s'exclure lui-même ; quittez plutôt la partie",
+    ),
+    entry(
+        "host_not_seated",
+        "Only a player seated in the game can host it",
+        "Seul un joueur assis à la table peut en être l'hôte",
+    ),
+    entry("host_only_bots", "Only the host can add bots", "Seul l'hôte peut ajouter des robots"),
+    entry("unknown_strategy", "Unknown strategy '{}'", "Stratégie inconnue « {} »"),
+    entry(
+        "bots_before_start",
+        "Bots can only be added before the game starts",
+        "Les robots ne s'ajoutent qu'avant le début de la partie",
+    ),
+    // Play
+    core("not_your_turn", errors::NOT_YOUR_TURN, "Ce n'est pas votre tour"),
+    core("already_stayed", errors::ALREADY_STAYED, "Le joueur s'est déjà arrêté"),
+    core("deck_empty", errors::DECK_EMPTY, "La pioche est vide"),
+    core("player_eliminated", errors::PLAYER_ELIMINATED, "Le joueur actuel a été éliminé"),
+    core("round_finished", errors::ROUND_FINISHED, "La manche est terminée"),
+    core("round_not_finished", errors::ROUND_NOT_FINISHED, "La manche n'est pas terminée"),
+    core("round_scored", errors::ROUND_SCORED, "Les points de la manche ont déjà été comptés"),
+    entry("no_round_waiting", "No round is waiting to start", "Aucune manche n'attend de commencer"),
+    core("game_over", errors::GAME_OVER, "La partie est terminée"),
+    entry("game_not_over", "The game isn't over yet", "La partie n'est pas encore terminée"),
+    core("game_paused", errors::GAME_PAUSED, "La partie est en pause"),
+    core("game_not_paused", errors::GAME_NOT_PAUSED, "La partie n'est pas en pause"),
+    core("game_already_paused", errors::GAME_ALREADY_PAUSED, "La partie est déjà en pause"),
+    core(
+        "resume_unpaused",
+        errors::RESUME_UNPAUSED,
+        "Impossible de reprendre une partie qui n'a pas été mise en pause",
+    ),
+    core(
+        "handicaps_locked",
+        errors::HANDICAPS_LOCKED,
+        "Les handicaps ne se fixent qu'avant le décompte de la première manche",
+    ),
+    core("skip_voted", errors::SKIP_VOTED, "Vous avez déjà voté pour passer ce tour"),
+    core(
+        "skip_own_turn",
+        errors::SKIP_OWN_TURN,
+        "Vous ne pouvez pas voter pour passer votre propre tour",
+    ),
+    core(
+        "skip_too_soon",
+        errors::SKIP_TOO_SOON,
+        "Le tour n'a pas duré assez longtemps pour être passé",
+    ),
+    entry(
+        "table_only_rematch",
+        "Only players at the table can ask for a rematch",
+        "Seuls les joueurs à la table peuvent demander une revanche",
+    ),
+    entry(
+        "table_only_ready",
+        "Only players at the table can be ready",
+        "Seuls les joueurs à la table peuvent être prêts",
+    ),
+    entry("table_only_react", "Only players at the table can react", "Seuls les joueurs à la table peuvent réagir"),
+    entry(
+        "own_seats_only",
+        "Players can only act for their own seats",
+        "Les joueurs ne peuvent agir que pour leur propre place",
+    ),
+    // Connections
+    entry(
+        "hello_first",
+        "Hello must be the first message on a connection",
+        "Hello doit être le premier message d'une connexion",
+    ),
+    entry(
+        "protocol_too_old",
+        "Protocol version {} is no longer supported; this server speaks versions {} to {}",
+        "La version {} du protocole n'est plus prise en charge ; ce serveur parle les versions {} à {}",
+    ),
+    entry(
+        "invalid_message",
+        "Invalid message for protocol version {}: {}",
+        "Message invalide pour la version {} du protocole : {}",
+    ),
+    entry("invalid_json", "Invalid message: {}", "Message invalide : {}"),
+    entry("invalid_request", "Invalid request: {}", "Requête invalide : {}"),
+    entry("expected_message", "Expected a {} message", "Message {} attendu"),
+    entry(
+        "moves_need_token",
+        "Moves need the player's session token",
+        "Les coups demandent le jeton de session du joueur",
+    ),
+    entry("not_permitted", "Message not permitted for {} connection", "Message non autorisé pour une connexion {}"),
+    entry("server_full", "The server is full; try again later", "Le serveur est plein ; réessayez plus tard"),
+    entry(
+        "shutting_down",
+        "The server is shutting down; no new games can be started",
+        "Le serveur s'arrête ; aucune nouvelle partie ne peut commencer",
+    ),
+    entry("over_limit", "Over the {} limit", "Limite {} dépassée"),
+    // Signing in
+    entry("no_auth", "This server doesn't authenticate players", "Ce serveur n'authentifie pas les joueurs"),
+    entry(
+        "sign_in_to_play",
+        "Sign in to play; guests can only spectate",
+        "Connectez-vous pour jouer ; les invités peuvent seulement regarder",
+    ),
+    entry("token_expired", "Token has expired", "Le jeton a expiré"),
+    entry("malformed_token", "Malformed token", "Jeton mal formé"),
+    entry("unknown_token", "Unknown token", "Jeton inconnu"),
+    entry("invalid_signature", "Invalid token signature", "Signature du jeton invalide"),
+    entry("unsupported_algorithm", "Unsupported token algorithm {}", "Algorithme de jeton non pris en charge : {}"),
+    entry(
+        "sign_in_ranked",
+        "Sign in to play ranked games",
+        "Connectez-vous pour jouer des parties classées",
+    ),
+    entry(
+        "sign_in_scores",
+        "Sign in to submit scores",
+        "Connectez-vous pour envoyer vos scores",
+    ),
+    entry(
+        "sign_in_friends",
+        "Sign in to have friends",
+        "Connectez-vous pour avoir des amis",
+    ),
+    entry("ranked_queued", "Already queued for a ranked game", "Déjà dans la file des parties classées"),
+    // Leaderboards
+    entry(
+        "solo_unfinished",
+        "Only finished solo games can be submitted",
+        "Seules les parties solo terminées peuvent être envoyées",
+    ),
+    entry(
+        "solo_rounds",
+        "Only solo games of {} rounds are ranked",
+        "Seules les parties solo de {} manches sont classées",
+    ),
+    entry(
+        "not_daily",
+        "This isn't the daily challenge of {}",
+        "Ce n'est pas le défi du jour du {}",
+    ),
+    entry(
+        "replay_mismatch",
+        "The game doesn't replay to the state submitted",
+        "La partie rejouée n'aboutit pas à l'état envoyé",
+    ),
+    entry(
+        "solo_not_dealt",
+        "Only solo games the server dealt are ranked",
+        "Seules les parties solo distribuées par le serveur sont classées",
+    ),
+    entry("daily_needs_date", "The daily board needs a date", "Le classement du jour demande une date"),
+    entry("unknown_board", "No leaderboard '{}'", "Aucun classement « {} »"),
+    // Friends
+    entry("own_friend", "You can't be your own friend", "Vous ne pouvez pas être votre propre ami"),
+    entry("unknown_account", "Nobody has signed in as {}", "Personne ne s'est connecté en tant que {}"),
+    entry("already_friend", "{} is already a friend", "{} est déjà un ami"),
+    entry("not_friend", "{} isn't a friend", "{} n'est pas un ami"),
+    entry("friend_offline", "{} isn't online", "{} n'est pas en ligne"),
+    entry(
+        "friends_in_game_only",
+        "Only players in the game can invite friends to it",
+        "Seuls les joueurs de la partie peuvent y inviter des amis",
+    ),
+    // Tournaments
+    entry("tournament_not_found", "Tournament not found", "Tournoi introuvable"),
+    entry("tournament_full", "The tournament is full", "Le tournoi est complet"),
+    entry(
+        "tournament_size",
+        "Tournaments take 2 to {} entrants",
+        "Les tournois acceptent de 2 à {} participants",
+    ),
+    entry(
+        "swiss_rounds",
+        "A Swiss tournament of {} entrants plays 1 to {} rounds",
+        "Un tournoi suisse de {} participants se joue en 1 à {} rondes",
+    ),
+    entry(
+        "tournament_rematch",
+        "Tournament games can't be rematched",
+        "Les parties de tournoi ne peuvent pas être rejouées",
+    ),
+    entry(
+        "tournament_pairing_only",
+        "Tournament games only seat their pairing",
+        "Les parties de tournoi n'accueillent que leurs joueurs appariés",
+    ),
+    entry("tournament_joined", "Already in this tournament", "Vous êtes déjà inscrit à ce tournoi"),
+];
+
+/// Builds the `Text` of a catalog key with its arguments, failing to compile
+/// if the catalog has no such key or it takes another number of arguments.
+macro_rules! text {
+    ($key:literal $(, $arg:expr)* $(,)?) => {{
+        const _: () = assert!(
+            $crate::i18n::arg_count($key) == <[&str]>::len(&[$(stringify!($arg)),*]),
+            concat!("wrong number of arguments for ", $key),
+        );
+        $crate::i18n::Text::new($key, vec![$($arg.to_string()),*])
+    }};
+}
+
+pub(crate) use text;
+
+/// The number of arguments a catalog key takes. Fails, at compile time when
+/// called from `text!`, if the catalog doesn't have the key.
+pub const fn arg_count(key: &str) -> usize {
+    let mut i = 0;
+    while i < CATALOG.len() {
+        if same(CATALOG[i].key, key) {
+            return placeholders(CATALOG[i].en);
+        }
+        i += 1;
+    }
+    panic!("no such key in the catalog")
+}
+
+const fn same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn placeholders(template: &str) -> usize {
+    let bytes = template.as_bytes();
+    let (mut count, mut i) = (0, 0);
+    while i + 1 < bytes.len() {
+        if bytes[i] == b'{' && bytes[i + 1] == b'}' {
+            count += 1;
+        }
+        i += 1;
+    }
+    count
+}
+
+/// A message for a player: a catalog key and its arguments, and its text in
+/// one locale. Messages the catalog doesn't have carry no key and keep their
+/// text in every locale. On the wire it is just the text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(into = "String")]
+pub struct Text {
+    key: Option<&'static str>,
+    args: Vec<String>,
+    text: String,
+}
+
+impl Text {
+    /// The English text of a catalog key. Use `text!`, which checks the key
+    /// and the number of arguments.
+    pub fn new(key: &'static str, args: Vec<String>) -> Self {
+        match CATALOG.iter().find(|entry| entry.key == key) {
+            Some(entry) => Text {
+                text: render(entry.en, &args),
+                key: Some(entry.key),
+                args,
+            },
+            None => Text {
+                key: None,
+                args: Vec::new(),
+                text: key.to_string(),
+            },
+        }
+    }
+
+    /// The catalog key of the message, if it has one.
+    pub fn key(&self) -> Option<&'static str> {
+        self.key
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The same message in another locale.
+    pub fn in_locale(&self, locale: Locale) -> Text {
+        let Some(entry) = self.key.and_then(|key| CATALOG.iter().find(|entry| entry.key == key)) else {
+            return self.clone();
+        };
+        let template = match locale {
+            Locale::En => entry.en,
+            Locale::Fr => entry.fr,
+        };
+        Text {
+            key: self.key,
+            args: self.args.clone(),
+            text: render(template, &self.args),
+        }
+    }
+}
+
+/// A plain message: one of the game core's errors, or else one the catalog
+/// doesn't have.
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        let key = CATALOG.iter().find(|entry| entry.core && entry.en == text).map(|entry| entry.key);
+        Text { key, args: Vec::new(), text }
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        text.to_string().into()
+    }
+}
+
+impl From<Text> for String {
+    fn from(text: Text) -> Self {
+        text.text
+    }
+}
+
+// By hand, as a derived impl would borrow its `'static` key from the input
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Text::from)
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl PartialEq<str> for Text {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for Text {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+/// The template with its `{}`s filled in by the arguments, in order.
+fn render(template: &str, args: &[String]) -> String {
+    let mut pieces = template.split("{}");
+    let mut text = pieces.next().unwrap_or_default().to_string();
+    for (piece, arg) in pieces.zip(args.iter().map(String::as_str).chain(std::iter::repeat(""))) {
+        text.push_str(arg);
+        text.push_str(piece);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_in_locale() {
+        let text = text!("join_code_other_game", "ABCD");
+        assert_eq!(text, "Join code ABCD is for another game");
+        assert_eq!(text.key(), Some("join_code_other_game"));
+        assert_eq!(text.in_locale(Locale::Fr), "Le code ABCD est celui d'une autre partie");
+        assert_eq!(text.in_locale(Locale::Fr).in_locale(Locale::En), text);
+        assert_eq!(
+            text!("invalid_message", 1, "missing field `game_id`").in_locale(Locale::Fr),
+            "Message invalide pour la version 1 du protocole : missing field `game_id`"
+        );
+        // The game core's errors come as plain strings
+        let text = Text::from(errors::NOT_YOUR_TURN.to_string());
+        assert_eq!(text.key(), Some("not_your_turn"));
+        assert_eq!(text.in_locale(Locale::Fr), "Ce n'est pas votre tour");
+        // Not in the catalog
+        let text = Text::from("Something broke");
+        assert_eq!(text.key(), None);
+        assert_eq!(text.in_locale(Locale::Fr), "Something broke");
+        // On the wire it is only the text
+        let json = serde_json::to_string(&text!("game_not_found")).unwrap();
+        assert_eq!(json, "\"Game not found\"");
+        assert_eq!(serde_json::from_str::<Text>(&json).unwrap(), "Game not found");
+    }
+
+    #[test]
+    fn test_catalog_is_consistent() {
+        let mut keys = HashSet::new();
+        for entry in CATALOG {
+            assert!(keys.insert(entry.key), "{} is in the catalog twice", entry.key);
+            assert_eq!(placeholders(entry.en), placeholders(entry.fr), "{}", entry.key);
+            assert_eq!(arg_count(entry.key), placeholders(entry.en));
+            assert!(!entry.core || placeholders(entry.en) == 0, "{}", entry.key);
+        }
+        // Every error of the game core is translated
+        for error in errors::ALL {
+            assert!(Text::from(*error).key().is_some(), "{error} isn't in the catalog");
+        }
+    }
+
+    #[test]
+    fn test_from_tag() {
+        assert_eq!(Locale::from_tag("fr-CA"), Some(Locale::Fr));
+        assert_eq!(Locale::from_tag("fr_FR.UTF-8"), Some(Locale::Fr));
+        assert_eq!(Locale::from_tag("EN"), Some(Locale::En));
+        assert_eq!(Locale::from_tag("de-DE"), None);
+        assert_eq!(Locale::from_accept_language("de-DE, fr;q=0.8, en;q=0.5"), Some(Locale::Fr));
+        assert_eq!(Locale::from_accept_language("*"), None);
+    }
+}
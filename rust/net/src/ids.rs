@@ -0,0 +1,191 @@
+//! Typed identifiers for games, players and sessions, so they can't be mixed
+//! up. All are random UUIDs and travel as strings on the wire, like the plain
+//! strings they replace. Games also get a short `JoinCode` for people to share.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+macro_rules! uuid_id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(Uuid);
+
+        impl $name {
+            /// A new random id.
+            pub fn new() -> Self {
+                Self(Uuid::new_v4())
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Uuid::parse_str(s)
+                    .map(Self)
+                    .map_err(|_| format!("Invalid {} '{}'", stringify!($name), s))
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = String;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                s.parse()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.to_string()
+            }
+        }
+    };
+}
+
+uuid_id!(
+    /// Identifies a game hosted by a `ProtocolEngine`.
+    GameId
+);
+
+impl GameId {
+    /// Which of `shards` shards the game lives in.
+    pub(crate) fn shard(&self, shards: usize) -> usize {
+        (self.0.as_u128() % shards as u128) as usize
+    }
+}
+
+uuid_id!(
+    /// Identifies a player across the protocol. Inside the game it is the
+    /// player's `id`, as a string.
+    PlayerId
+);
+
+uuid_id!(
+    /// Secret a client presents to take its seat back after its connection
+    /// dropped. Whoever holds it plays as that player, so it is only ever
+    /// sent to the client that joined.
+    SessionToken
+);
+
+uuid_id!(
+    /// Picked by the client for each move it submits, so that resubmitting
+    /// the move after a dropped reply doesn't apply it twice.
+    MoveId
+);
+
+uuid_id!(
+    /// Lets one player into an invite-only or password-protected game.
+    InviteToken
+);
+
+uuid_id!(
+    /// Identifies a tournament; see `tournament`.
+    TournamentId
+);
+
+/// Short code friends can read out to each other to join a game, e.g.
+/// `K7Q2F`. Codes leave out characters that are easily confused, like 0 and
+/// O, and are read case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct JoinCode(String);
+
+impl JoinCode {
+    pub const LEN: usize = 5;
+    const ALPHABET: &'static [u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+    /// A new random code. Codes are not unique by themselves: the server
+    /// draws again on a clash.
+    pub fn random() -> Self {
+        let mut bits = Uuid::new_v4().as_u128();
+        let code = (0..Self::LEN)
+            .map(|_| {
+                let c = Self::ALPHABET[(bits % Self::ALPHABET.len() as u128) as usize];
+                bits /= Self::ALPHABET.len() as u128;
+                c as char
+            })
+            .collect();
+        Self(code)
+    }
+}
+
+impl fmt::Display for JoinCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for JoinCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_ascii_uppercase();
+        if code.len() != Self::LEN || !code.bytes().all(|c| Self::ALPHABET.contains(&c)) {
+            return Err(format!("Invalid join code '{}'", s));
+        }
+        Ok(Self(code))
+    }
+}
+
+impl TryFrom<String> for JoinCode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<JoinCode> for String {
+    fn from(code: JoinCode) -> Self {
+        code.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_round_trip_as_strings() {
+        let id = GameId::new();
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{}\"", id));
+        assert_eq!(serde_json::from_str::<GameId>(&json).unwrap(), id);
+        assert_eq!(id.to_string().parse::<GameId>(), Ok(id));
+
+        assert!("forged".parse::<PlayerId>().is_err());
+        assert!(serde_json::from_str::<PlayerId>("\"forged\"").is_err());
+    }
+
+    #[test]
+    fn test_join_codes() {
+        let code = JoinCode::random();
+        assert_eq!(code.to_string().len(), JoinCode::LEN);
+        assert_eq!(code.to_string().parse::<JoinCode>(), Ok(code.clone()));
+        assert_eq!(
+            serde_json::from_str::<JoinCode>(&serde_json::to_string(&code).unwrap()).unwrap(),
+            code
+        );
+
+        assert_eq!(" k7q2f ".parse::<JoinCode>().unwrap().to_string(), "K7Q2F");
+        assert!("K7Q2".parse::<JoinCode>().is_err());
+        assert!("K0Q2F".parse::<JoinCode>().is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! LAN play over `TcpTransport` with line framing: one JSON message per line
+//! in, one JSON response per line out.
+//!
+//! Players on the same network find hosts without typing addresses: a host
+//! `announce`s itself by answering the queries `discover_local_games`
+//! multicasts to `DISCOVERY_GROUP` with its name, port and public games.
+//! Only queries from private, link-local or loopback addresses are answered,
+//! so a host can't be used to reflect answers at spoofed addresses elsewhere.
+
+use crate::handshake::PROTOCOL_VERSION;
+use crate::lobby::GameSummary;
+use crate::transport::{run_timers, Framing, TcpTransport, Transport};
+use crate::{GameServer, Message, Response, TrustLevel};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::time::Instant;
+
+/// Multicast group and port hosts listen on for players looking for games.
+pub const DISCOVERY_GROUP: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 255, 70, 7), 7779);
+
+/// Most games a host lists in an answer, so it fits one datagram.
+const MAX_LISTED: usize = 10;
+
+/// Largest datagram read.
+const MAX_DATAGRAM: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Beacon {
+    /// Multicast by players looking for games
+    Query { protocol_version: u32 },
+    /// A host's answer, sent back to the player who asked
+    Host {
+        name: String,
+        port: u16,
+        protocol_version: u32,
+        games: Vec<GameSummary>,
+    },
+}
+
+/// A host found on the local network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalHost {
+    pub name: String,
+    /// Where to connect to play
+    pub addr: SocketAddr,
+    pub protocol_version: u32,
+    /// Some of its public games, open ones first
+    pub games: Vec<GameSummary>,
+}
+
+/// Accepts connections until the task is dropped, and keeps the turn timers
+/// of every game running. Every client gets the same trust level; player apps
+/// on a LAN should be `UntrustedPeer`.
+pub async fn serve(server: GameServer, listener: TcpListener, trust: TrustLevel) -> io::Result<()> {
+    let serving = TcpTransport::from_listener(listener, trust, Framing::Lines).serve(server.clone());
+    tokio::select! {
+        served = serving => served,
+        never = run_timers(&server) => match never {},
+    }
+}
+
+/// Answers players on the local network looking for games, under `name`
+/// and with the TCP `port` the server is served on, until the task is
+/// dropped. Hosts on one machine can share the group.
+pub async fn announce(server: GameServer, name: String, port: u16, group: SocketAddrV4) -> io::Result<()> {
+    let socket = join_group(group)?;
+    let mut datagram = vec![0; MAX_DATAGRAM];
+    loop {
+        let (len, from) = socket.recv_from(&mut datagram).await?;
+        if !is_local(from.ip()) {
+            continue;
+        }
+        let Ok(Beacon::Query { .. }) = serde_json::from_slice(&datagram[..len]) else {
+            continue;
+        };
+        let games = match server.handle_message(Message::ListGames).await {
+            Response::GameList { mut games } => {
+                games.truncate(MAX_LISTED);
+                games
+            }
+            _ => Vec::new(),
+        };
+        let answer = Beacon::Host {
+            name: name.clone(),
+            port,
+            protocol_version: PROTOCOL_VERSION,
+            games,
+        };
+        let answer = serde_json::to_vec(&answer).map_err(io::Error::other)?;
+        // Only that player misses out if it fails
+        if let Err(err) = socket.send_to(&answer, from).await {
+            tracing::debug!(%from, %err, "discovery answer failed");
+        }
+    }
+}
+
+/// Whether the address is on a local network, or this machine.
+fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local(IpAddr::V4(ip)),
+            // Unique local fc00::/7 and link-local fe80::/10
+            None => ip.is_loopback() || ip.segments()[0] & 0xfe00 == 0xfc00 || ip.segments()[0] & 0xffc0 == 0xfe80,
+        },
+    }
+}
+
+/// Hosts on the local network, as many as answer within `wait`.
+pub async fn discover_local_games(wait: Duration) -> io::Result<Vec<LocalHost>> {
+    discover_on(DISCOVERY_GROUP, wait).await
+}
+
+/// `discover_local_games` on another group than `DISCOVERY_GROUP`.
+pub async fn discover_on(group: SocketAddrV4, wait: Duration) -> io::Result<Vec<LocalHost>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    let query = Beacon::Query {
+        protocol_version: PROTOCOL_VERSION,
+    };
+    socket
+        .send_to(&serde_json::to_vec(&query).map_err(io::Error::other)?, group)
+        .await?;
+
+    let deadline = Instant::now() + wait;
+    let mut datagram = vec![0; MAX_DATAGRAM];
+    let mut hosts: Vec<LocalHost> = Vec::new();
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut datagram)).await {
+        let (len, from) = received?;
+        let Ok(Beacon::Host {
+            name,
+            port,
+            protocol_version,
+            games,
+        }) = serde_json::from_slice(&datagram[..len])
+        else {
+            continue;
+        };
+        let addr = SocketAddr::new(from.ip(), port);
+        if !hosts.iter().any(|host| host.addr == addr) {
+            hosts.push(LocalHost {
+                name,
+                addr,
+                protocol_version,
+                games,
+            });
+        }
+    }
+    Ok(hosts)
+}
+
+/// A socket receiving what is sent to the group.
+fn join_group(group: SocketAddrV4) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port()).into())?;
+    socket.join_multicast_v4(group.ip(), &Ipv4Addr::UNSPECIFIED)?;
+    UdpSocket::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Message, Response};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn test_join_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(
+            GameServer::new(),
+            listener,
+            TrustLevel::UntrustedPeer,
+        ));
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let join = Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let mut line = serde_json::to_vec(&join).unwrap();
+        line.push(b'\n');
+        writer.write_all(&line).await.unwrap();
+
+        let reply = BufReader::new(reader)
+            .lines()
+            .next_line()
+            .await
+            .unwrap()
+            .unwrap();
+        match serde_json::from_str(&reply).unwrap() {
+            Response::GameJoined { .. } => {}
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_announced_host() {
+        // Off the default group, so running servers don't answer
+        let group = SocketAddrV4::new(*DISCOVERY_GROUP.ip(), 47779);
+        let server = GameServer::new();
+        server
+            .handle_message(Message::CreateGame {
+                rules: None,
+                visibility: crate::Visibility::Public,
+                turn_clock: None,
+            })
+            .await;
+        tokio::spawn(announce(server, "Kitchen table".to_string(), 7777, group));
+        // Let the host join the group first
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Asked over loopback, as the machine running the tests may not be on a private network
+        let host = SocketAddrV4::new(Ipv4Addr::LOCALHOST, group.port());
+        let hosts = discover_on(host, Duration::from_millis(500)).await.unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].name, "Kitchen table");
+        assert_eq!(hosts[0].addr.port(), 7777);
+        assert_eq!(hosts[0].games.len(), 1);
+    }
+
+    #[test]
+    fn test_only_local_queries_are_answered() {
+        for local in ["192.168.1.20", "10.0.0.3", "169.254.7.1", "127.0.0.1", "fe80::1", "fd12::5", "::ffff:10.1.1.1"] {
+            assert!(is_local(local.parse().unwrap()), "{}", local);
+        }
+        for remote in ["8.8.8.8", "100.64.0.1", "2001:db8::1", "::ffff:1.1.1.1"] {
+            assert!(!is_local(remote.parse().unwrap()), "{}", remote);
+        }
+    }
+}
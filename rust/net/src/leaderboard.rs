@@ -0,0 +1,393 @@
+//! Leaderboards: players ranked by games won, rating, best solo score, and
+//! score in each day's daily challenge.
+//!
+//! The engines of a server share one `Leaderboards`, which tallies games as
+//! they end, so no query goes through the games stored. Each board is kept
+//! sorted once read, until something on it changes, and a page of it is a
+//! slice of that. Boards among a few players, e.g. a player and their
+//! friends, are sorted from those players' records alone.
+//!
+//! Players are known by the account they play under, as for `ratings`: their
+//! `Identity::user_id`. Guests, whose names anyone can take, aren't ranked.
+//! Solo games are played on the client and submitted once finished; the
+//! server replays them from their seed before taking their score. That seed
+//! is the day's for a daily challenge, and else the one the server last dealt
+//! the account, so nobody picks the deck they play.
+
+use crate::i18n::{text, Text};
+use crate::store::Rebase;
+use game_core::daily::{daily_seed, DAILY_ROUNDS, DAILY_TARGET_SCORE};
+use game_core::replay::Replay;
+use game_core::GameState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+
+/// Entries in a page when a request doesn't say.
+pub const PAGE_SIZE: usize = 20;
+
+/// Most entries in a page.
+pub const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Board {
+    /// Games won, ranked or not
+    Wins,
+    /// Rating from ranked games, rounded
+    Rating,
+    /// Best total score of a solo game of `DAILY_ROUNDS` rounds, daily
+    /// challenges included
+    SoloScore,
+    /// Total score of the daily challenge of `date` (`YYYY-MM-DD`)
+    Daily { date: String },
+}
+
+/// A player's place on a board. Players with the same value share a rank.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub account: String,
+    /// The name the player last played under
+    pub name: String,
+    pub value: u32,
+}
+
+/// What the boards know of one player.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub name: String,
+    pub wins: u32,
+    #[serde(default)]
+    pub rating: Option<f64>,
+    #[serde(default)]
+    pub best_solo: Option<u32>,
+    /// Best score in each daily challenge played, by date
+    #[serde(default)]
+    pub daily: BTreeMap<String, u32>,
+}
+
+impl PlayerRecord {
+    fn value(&self, board: &Board) -> Option<u32> {
+        match board {
+            Board::Wins => Some(self.wins).filter(|wins| *wins > 0),
+            Board::Rating => self.rating.map(|rating| rating.round() as u32),
+            Board::SoloScore => self.best_solo,
+            Board::Daily { date } => self.daily.get(date).copied(),
+        }
+    }
+}
+
+/// Wins counted since are counted again, a rating moved since moved again,
+/// and the best scores kept.
+impl Rebase for PlayerRecord {
+    fn rebase(&self, base: &Self, saved: &Self) -> Self {
+        let rating = match (self.rating, base.rating, saved.rating) {
+            (Some(ours), Some(base), Some(saved)) => Some(saved + ours - base),
+            (ours, base, _) if ours != base => ours,
+            (_, _, saved) => saved,
+        };
+        let mut daily = saved.daily.clone();
+        for (date, score) in &self.daily {
+            let best = daily.entry(date.clone()).or_default();
+            *best = (*best).max(*score);
+        }
+        Self {
+            name: self.name.clone(),
+            wins: (saved.wins + self.wins).saturating_sub(base.wins),
+            rating,
+            best_solo: self.best_solo.max(saved.best_solo),
+            daily,
+        }
+    }
+}
+
+/// A solo game's score, once checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SoloScore {
+    pub(crate) total_score: u32,
+    /// Date of the daily challenge the game was
+    pub(crate) date: Option<String>,
+}
+
+/// Checks a finished solo game submitted for the boards: its moves must
+/// replay from its seed to the same cards and score, and a daily challenge must have
+/// been dealt from its day's seed.
+pub(crate) fn check_solo_game(game: &GameState) -> Result<SoloScore, Text> {
+    let result = game.solo_result().ok_or(text!("solo_unfinished"))?;
+    if result.rounds_played != DAILY_ROUNDS {
+        return Err(text!("solo_rounds", DAILY_ROUNDS));
+    }
+    let date = game.config.challenge_date.clone();
+    if let Some(date) = &date {
+        if daily_seed(date) != Ok(game.seed) || game.config.target_score != DAILY_TARGET_SCORE {
+            return Err(text!("not_daily", date));
+        }
+    }
+    // Deck commitments are salted afresh, so the states themselves differ
+    let replayed = Replay::from_game(game).run();
+    if replayed.events != game.events || replayed.solo_result().as_ref() != Some(&result) {
+        return Err(text!("replay_mismatch"));
+    }
+    Ok(SoloScore {
+        total_score: result.total_score,
+        date,
+    })
+}
+
+/// Every player's record, each board sorted since it last changed, and
+/// which records changed since the last save.
+#[derive(Debug, Default)]
+pub(crate) struct Leaderboards {
+    records: HashMap<String, PlayerRecord>,
+    /// Accounts on each board read since it last changed, with their value,
+    /// best first
+    sorted: HashMap<Board, Vec<(String, u32)>>,
+    unsaved: HashSet<String>,
+    /// Seed of the solo game each account was last dealt, until submitted
+    solo_seeds: HashMap<String, u64>,
+}
+
+impl Leaderboards {
+    fn record(&mut self, account: &str, name: &str) -> &mut PlayerRecord {
+        self.unsaved.insert(account.to_string());
+        let record = self.records.entry(account.to_string()).or_default();
+        record.name = name.to_string();
+        record
+    }
+
+    pub(crate) fn won(&mut self, account: &str, name: &str) {
+        self.record(account, name).wins += 1;
+        self.sorted.remove(&Board::Wins);
+    }
+
+    pub(crate) fn rated(&mut self, account: &str, name: &str, rating: f64) {
+        self.record(account, name).rating = Some(rating);
+        self.sorted.remove(&Board::Rating);
+    }
+
+    /// Deals the account a solo game, returning its seed. It replaces any
+    /// game dealt before and not submitted.
+    pub(crate) fn start_solo(&mut self, account: &str) -> u64 {
+        let seed = Uuid::new_v4().as_u64_pair().0;
+        self.solo_seeds.insert(account.to_string(), seed);
+        seed
+    }
+
+    /// Whether `seed` is that of the solo game the account was last dealt,
+    /// which it then can't submit again.
+    pub(crate) fn finish_solo(&mut self, account: &str, seed: u64) -> bool {
+        if self.solo_seeds.get(account) != Some(&seed) {
+            return false;
+        }
+        self.solo_seeds.remove(account);
+        true
+    }
+
+    /// Records a checked solo game, returning the player's best score.
+    pub(crate) fn played_solo(&mut self, account: &str, name: &str, score: &SoloScore) -> u32 {
+        let record = self.record(account, name);
+        let best = record.best_solo.map_or(score.total_score, |best| best.max(score.total_score));
+        record.best_solo = Some(best);
+        if let Some(date) = &score.date {
+            let daily = record.daily.entry(date.clone()).or_default();
+            *daily = (*daily).max(score.total_score);
+            self.sorted.remove(&Board::Daily { date: date.clone() });
+        }
+        self.sorted.remove(&Board::SoloScore);
+        best
+    }
+
+    /// A page of the board: the `limit` entries from `offset` on, and how
+    /// many entries there are in all. Only the `among` accounts are ranked
+    /// if any are given.
+    pub(crate) fn page(
+        &mut self,
+        board: &Board,
+        among: &[String],
+        offset: usize,
+        limit: usize,
+    ) -> (Vec<LeaderboardEntry>, usize) {
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let chosen;
+        let sorted = if among.is_empty() {
+            if !self.sorted.contains_key(board) {
+                let rows = self
+                    .records
+                    .iter()
+                    .filter_map(|(account, record)| Some((account.clone(), record.value(board)?)))
+                    .collect();
+                self.sorted.insert(board.clone(), sort(rows));
+            }
+            &self.sorted[board]
+        } else {
+            let accounts: HashSet<&String> = among.iter().collect();
+            let rows = accounts
+                .into_iter()
+                .filter_map(|account| Some((account.clone(), self.records.get(account)?.value(board)?)))
+                .collect();
+            chosen = sort(rows);
+            &chosen
+        };
+
+        let mut entries = Vec::new();
+        let mut rank = 0;
+        let mut previous = None;
+        for (index, (account, value)) in sorted.iter().enumerate().take(offset.saturating_add(limit)) {
+            if previous != Some(*value) {
+                rank = index + 1;
+                previous = Some(*value);
+            }
+            if index >= offset {
+                entries.push(LeaderboardEntry {
+                    rank,
+                    account: account.clone(),
+                    name: self.records[account].name.clone(),
+                    value: *value,
+                });
+            }
+        }
+        (entries, sorted.len())
+    }
+
+    /// Takes the records a store kept, over those known.
+    pub(crate) fn load(&mut self, records: HashMap<String, PlayerRecord>) {
+        self.records.extend(records);
+        self.sorted.clear();
+    }
+
+    /// The records that changed since they were last taken.
+    pub(crate) fn take_unsaved(&mut self) -> Vec<(String, PlayerRecord)> {
+        let mut changed: Vec<(String, PlayerRecord)> = std::mem::take(&mut self.unsaved)
+            .into_iter()
+            .filter_map(|account| {
+                let record = self.records.get(&account)?.clone();
+                Some((account, record))
+            })
+            .collect();
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+        changed
+    }
+
+    /// Has the records saved again with the next save, e.g. after it failed.
+    pub(crate) fn mark_unsaved(&mut self, accounts: impl IntoIterator<Item = String>) {
+        self.unsaved.extend(accounts);
+    }
+}
+
+/// Best first, and in account order between equals.
+fn sort(mut rows: Vec<(String, u32)>) -> Vec<(String, u32)> {
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_core::solo::SOLO_PLAYER_ID;
+
+    fn solo(total_score: u32, date: Option<&str>) -> SoloScore {
+        SoloScore {
+            total_score,
+            date: date.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_page() {
+        let mut boards = Leaderboards::default();
+        for (account, wins) in [("ada", 3), ("bo", 5), ("cy", 3), ("di", 1)] {
+            for _ in 0..wins {
+                boards.won(account, &account.to_uppercase());
+            }
+        }
+        boards.rated("bo", "Bo", 1516.4);
+
+        let (entries, total) = boards.page(&Board::Wins, &[], 0, PAGE_SIZE);
+        assert_eq!(total, 4);
+        let ranks: Vec<(usize, &str, u32)> = entries
+            .iter()
+            .map(|entry| (entry.rank, entry.account.as_str(), entry.value))
+            .collect();
+        assert_eq!(ranks, [(1, "bo", 5), (2, "ada", 3), (2, "cy", 3), (4, "di", 1)]);
+        assert_eq!(entries[0].name, "Bo");
+
+        // Ties keep their rank across pages
+        let (entries, _) = boards.page(&Board::Wins, &[], 2, 1);
+        assert_eq!((entries[0].rank, entries[0].account.as_str()), (2, "cy"));
+
+        // A win re-sorts the board
+        boards.won("di", "Di");
+        boards.won("di", "Di");
+        let (entries, _) = boards.page(&Board::Wins, &[], 1, 1);
+        assert_eq!((entries[0].rank, entries[0].account.as_str(), entries[0].value), (2, "ada", 3));
+
+        // Among friends, ranks are theirs alone; strangers to the boards are left out
+        let among = ["cy".to_string(), "di".to_string(), "eve".to_string()];
+        boards.won("di", "Di");
+        let (entries, total) = boards.page(&Board::Wins, &among, 0, PAGE_SIZE);
+        assert_eq!(total, 2);
+        let ranks: Vec<(usize, &str)> = entries.iter().map(|entry| (entry.rank, entry.account.as_str())).collect();
+        assert_eq!(ranks, [(1, "di"), (2, "cy")]);
+
+        let (entries, total) = boards.page(&Board::Rating, &[], 0, PAGE_SIZE);
+        assert_eq!(total, 1);
+        assert_eq!(entries[0].value, 1516);
+    }
+
+    #[test]
+    fn test_played_solo() {
+        let mut boards = Leaderboards::default();
+        assert_eq!(boards.played_solo("ada", "Ada", &solo(60, None)), 60);
+        assert_eq!(boards.played_solo("ada", "Ada", &solo(48, Some("2024-03-01"))), 60);
+        assert_eq!(boards.played_solo("bo", "Bo", &solo(71, Some("2024-03-01"))), 71);
+
+        let daily = Board::Daily {
+            date: "2024-03-01".to_string(),
+        };
+        let (entries, _) = boards.page(&daily, &[], 0, PAGE_SIZE);
+        let values: Vec<(&str, u32)> = entries.iter().map(|entry| (entry.account.as_str(), entry.value)).collect();
+        assert_eq!(values, [("bo", 71), ("ada", 48)]);
+        let (entries, _) = boards.page(&Board::SoloScore, &[], 0, PAGE_SIZE);
+        assert_eq!(entries[1].value, 60);
+
+        let unsaved = boards.take_unsaved();
+        assert_eq!(unsaved.len(), 2);
+        assert_eq!(unsaved[0].1.daily["2024-03-01"], 48);
+        assert!(boards.take_unsaved().is_empty());
+    }
+
+    #[test]
+    fn test_solo_seeds() {
+        let mut boards = Leaderboards::default();
+        let first = boards.start_solo("ada");
+        let second = boards.start_solo("ada");
+        assert!(!boards.finish_solo("ada", first));
+        assert!(!boards.finish_solo("bo", second));
+        assert!(boards.finish_solo("ada", second));
+        assert!(!boards.finish_solo("ada", second));
+    }
+
+    #[test]
+    fn test_check_solo_game() {
+        let mut game = GameState::daily_challenge("2024-03-01").unwrap();
+        assert!(check_solo_game(&game).is_err());
+        while !game.is_game_over() {
+            game.start_round().unwrap();
+            game.player_stay(SOLO_PLAYER_ID).unwrap();
+            game.finish_round().unwrap();
+        }
+        let score = check_solo_game(&game).unwrap();
+        assert_eq!(score.total_score, game.players[0].score);
+        assert_eq!(score.date.as_deref(), Some("2024-03-01"));
+
+        // Scores that weren't played don't replay
+        let mut forged = game.clone();
+        forged.players[0].score += 50;
+        assert!(check_solo_game(&forged).is_err());
+
+        // Nor does another day's deck pass for the challenge
+        let mut other_day = game.clone();
+        other_day.config.challenge_date = Some("2024-03-02".to_string());
+        assert!(check_solo_game(&other_day).unwrap_err().contains("daily challenge"));
+    }
+}
@@ -1,141 +1,1003 @@
-use game_core::{GameState, GameMove, Player};
+use game_core::clock::TimedMove;
+use game_core::commitment::DeckReveal;
+use game_core::events::GameEvent;
+use game_core::{GameState, GameMove, RoundSummary};
+use i18n::text;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use limits::Limiter;
+use shard::Shards;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{broadcast, oneshot};
+
+pub mod admin;
+pub mod auth;
+pub mod codec;
+pub mod config;
+pub mod delta;
+pub mod expiry;
+pub mod ffi;
+pub mod friends;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod handover;
+pub mod handshake;
+pub mod heartbeat;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod i18n;
+pub mod ids;
+pub mod lan;
+pub mod leaderboard;
+pub mod load;
+pub mod limits;
+pub mod lobby;
+pub mod matchmaking;
+pub(crate) mod move_log;
+pub mod protocol;
+pub mod ratings;
+pub mod reaction;
+pub mod rounds;
+pub(crate) mod shard;
+pub mod store;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod tournament;
+pub mod transport;
+pub mod trust;
+pub mod turn_clock;
+pub mod webhook;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+pub use admin::{AdminCommand, AdminRequest, AdminResponse, AdminTransport};
+pub use auth::{Auth, Authenticator, Identity};
+pub use codec::Encoding;
+pub use config::ServerConfig;
+pub use expiry::{Expiry, ExpiryEvent, ExpiryReason};
+pub use friends::{Friend, Presence};
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcTransport;
+pub use handover::ServerSnapshot;
+pub use heartbeat::Heartbeat;
+#[cfg(feature = "http")]
+pub use http::HttpTransport;
+pub use i18n::{Locale, Text};
+pub use ids::{GameId, InviteToken, JoinCode, MoveId, PlayerId, SessionToken, TournamentId};
+pub use leaderboard::{Board, LeaderboardEntry};
+pub use limits::{Limit, Limits};
+pub use lobby::{Access, GameStatus, GameSummary, Visibility};
+pub use matchmaking::Matchmaking;
+pub use protocol::ProtocolEngine;
+pub use ratings::Rating;
+pub use reaction::Emoji;
+pub use rounds::RoundFlow;
+pub use shard::DEFAULT_SHARDS;
+pub use store::GameStore;
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+pub use tournament::{Format, Tournament};
+pub use transport::{run_timers, Framing, TcpTransport, Transport};
+pub use trust::TrustLevel;
+pub use turn_clock::{AutoPlay, TurnClock};
+pub use webhook::LifecycleEvent;
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketTransport;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
-    JoinGame { player_name: String, game_id: Option<String> },
-    StartGame { game_id: String },
-    MakeMove { game_id: String, game_move: GameMove },
-    GetGameState { game_id: String },
-    LeaveGame { game_id: String, player_id: String },
+    /// Opens a connection; see `handshake`
+    Hello {
+        protocol_version: u32,
+        /// Encodings the client speaks, most preferred first
+        #[serde(default)]
+        encodings: Vec<Encoding>,
+        /// Optional features the client would like to use
+        #[serde(default)]
+        features: Vec<String>,
+        /// Language tag of the player's language, e.g. `fr-FR`; errors come
+        /// in English when the server has no translations for it
+        #[serde(default)]
+        locale: Option<String>,
+    },
+    /// Signs the connection in with a token; see `auth`
+    Authenticate { token: String },
+    JoinGame {
+        player_name: String,
+        game_id: Option<GameId>,
+        /// Team to join in team mode
+        #[serde(default)]
+        team: Option<u8>,
+        /// Rules variant of a new game (see `game_core::variant`); when
+        /// joining an existing game it must match, if given
+        #[serde(default)]
+        variant: Option<String>,
+        /// Join code of the game to join, instead of its `game_id`
+        #[serde(default)]
+        code: Option<JoinCode>,
+        /// Password or invitation, for a game that isn't open to all
+        #[serde(default)]
+        access: Option<Access>,
+    },
+    /// Sets up a game without joining it, e.g. to list it in the lobby
+    CreateGame {
+        /// Rules variant, as for `JoinGame`
+        #[serde(default)]
+        rules: Option<String>,
+        #[serde(default)]
+        visibility: Visibility,
+        /// Time limit on each turn, if any
+        #[serde(default)]
+        turn_clock: Option<TurnClock>,
+    },
+    /// Lists the public games
+    ListGames,
+    /// Lets the host of a game invite one more player; `host` is the host's
+    /// session token
+    InvitePlayer { game_id: GameId, host: SessionToken },
+    /// Lets the host remove another player from the game
+    KickPlayer {
+        game_id: GameId,
+        host: SessionToken,
+        player_id: PlayerId,
+    },
+    /// Makes another player the host
+    TransferHost {
+        game_id: GameId,
+        host: SessionToken,
+        to: PlayerId,
+    },
+    /// Lets the host end the game for everyone
+    CloseGame { game_id: GameId, host: SessionToken },
+    /// Queues up for the next game quick play starts; see `Matchmaking`
+    QuickPlay { player_name: String },
+    /// Queues up for a ranked game, with players of a rating close to the
+    /// player's; see `ratings`
+    RankedQuickPlay {
+        player_name: String,
+        /// Account the player is rated under, which connections fill in for
+        /// players who signed in; never read off the wire
+        #[serde(skip)]
+        account: Option<String>,
+    },
+    /// Leaves the quick play queue; `player_id` is the one `Queued` gave
+    LeaveQueue { player_id: PlayerId },
+    /// Starts the game, or its next round. Players start their own game
+    /// with the host's session token; trusted connections may leave it out
+    StartGame {
+        game_id: GameId,
+        #[serde(default)]
+        host: Option<SessionToken>,
+    },
+    MakeMove {
+        game_id: GameId,
+        game_move: GameMove,
+        /// Resubmitting a move with the same id returns the first result
+        /// rather than moving again
+        #[serde(default)]
+        move_id: Option<MoveId>,
+    },
+    /// Asks for the game as it is now. Protected games are only shown to
+    /// those who could join them, and to their players
+    GetGameState {
+        game_id: GameId,
+        #[serde(default)]
+        access: Option<Access>,
+    },
+    /// Follows a game without taking a seat, with the `access` joining it
+    /// would take; an invitation isn't used up by watching
+    Spectate {
+        game_id: GameId,
+        #[serde(default)]
+        access: Option<Access>,
+    },
+    /// Tells the connection which state of the game the client holds, e.g.
+    /// after fetching it with `GetGameState`, so `StateDelta`s are taken from
+    /// it. Every update the client was sent counts as acknowledged already.
+    /// A hash the game doesn't have is answered with a `Resync`.
+    AckState { game_id: GameId, state_hash: u64 },
+    LeaveGame { game_id: GameId, player_id: PlayerId },
+    SyncState { game_id: GameId, game_state: Box<GameState> },
+    NegotiateEncoding { offered: Vec<Encoding> },
+    VoteSkipTurn { game_id: GameId, voter_id: PlayerId },
+    /// Lets the host seat a computer player before the game starts, which
+    /// the server plays for. `difficulty` is a persona (easy, medium, hard or
+    /// reckless) or any strategy `game_core::bots::parse_strategy` takes,
+    /// e.g. `stay-at:17`
+    AddBot {
+        game_id: GameId,
+        host: SessionToken,
+        difficulty: String,
+        /// Defaults to the difficulty, e.g. "hard bot"
+        #[serde(default)]
+        player_name: Option<String>,
+    },
+    /// Takes back the seat a `session_token` was issued for, e.g. after the
+    /// connection dropped
+    Reconnect { token: SessionToken },
+    /// Votes for a rematch of a game that is over. Once every player still
+    /// at the table has, the same seats play again under the same rules
+    RequestRematch { game_id: GameId, player_id: PlayerId },
+    /// Says the player is ready for the next round; see `rounds`
+    Ready { game_id: GameId, player_id: PlayerId },
+    /// Shows a reaction to everyone at the table
+    Reaction {
+        game_id: GameId,
+        player_id: PlayerId,
+        emoji: Emoji,
+    },
+    /// A page of a leaderboard, of every player or only of the `among`
+    /// accounts, e.g. the player's and their friends'; see `leaderboard`
+    GetLeaderboard {
+        board: Board,
+        #[serde(default)]
+        among: Vec<String>,
+        #[serde(default)]
+        offset: usize,
+        /// Entries in the page, `leaderboard::PAGE_SIZE` if not given
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Deals the account a solo game to play for the `SoloScore` board: the
+    /// server answers with its seed
+    StartSoloGame {
+        /// Filled in by connections, as for `RankedQuickPlay`
+        #[serde(skip)]
+        account: Option<String>,
+    },
+    /// Puts a finished solo game played on the client on the leaderboards,
+    /// once the server has replayed it. Only daily challenges and the game
+    /// `StartSoloGame` last dealt the account are ranked
+    SubmitSoloGame {
+        player_name: String,
+        game_state: Box<GameState>,
+        /// Filled in by connections, as for `RankedQuickPlay`
+        #[serde(skip)]
+        account: Option<String>,
+    },
+    /// Asks another account to be friends, or accepts its asking; see
+    /// `friends`. Only for connections that signed in, as are the other
+    /// friends messages
+    AddFriend { friend_id: String },
+    /// Ends a friendship, or takes back or turns down a request
+    RemoveFriend { friend_id: String },
+    ListFriends,
+    /// Pushes an invitation to the game to a friend who is online, from a
+    /// player seated in it
+    InviteFriend { friend_id: String, game_id: GameId },
+    /// Sets up a tournament, which starts once `entrants` players joined;
+    /// see `tournament`
+    CreateTournament {
+        name: String,
+        format: Format,
+        entrants: usize,
+    },
+    JoinTournament {
+        tournament_id: TournamentId,
+        player_name: String,
+        /// Filled in by connections, as for `RankedQuickPlay`: an account
+        /// enters a tournament once
+        #[serde(skip)]
+        account: Option<String>,
+    },
+    GetTournament { tournament_id: TournamentId },
+    /// Keeps an idle connection's players from being marked disconnected;
+    /// any other message does too
+    Ping,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Response {
-    GameJoined { game_id: String, player_id: String },
-    GameStarted { game_id: String },
-    MoveAccepted { game_id: String },
-    GameState { game_state: GameState },
-    Error { message: String },
-    PlayerLeft { game_id: String, player_id: String },
+    /// The version to speak, the encoding to switch to after this reply, and
+    /// the requested features the server supports
+    Welcome {
+        protocol_version: u32,
+        encoding: Encoding,
+        features: Vec<String>,
+        /// Locale errors are sent in from now on
+        #[serde(default)]
+        locale: Locale,
+    },
+    /// The identity the connection plays under from now on
+    Authenticated { identity: Identity },
+    GameJoined {
+        game_id: GameId,
+        join_code: JoinCode,
+        player_id: PlayerId,
+        engine_rules_version: u32,
+        /// For `Reconnect`; bot seats have none
+        #[serde(default)]
+        session_token: Option<SessionToken>,
+    },
+    GameCreated {
+        game_id: GameId,
+        join_code: JoinCode,
+        /// For the creator to take the first seat with, whoever else the
+        /// game lets in
+        #[serde(default)]
+        invite: Option<InviteToken>,
+    },
+    /// Public games, open ones first
+    GameList { games: Vec<GameSummary> },
+    /// For the host to pass on to the invited player
+    PlayerInvited { game_id: GameId, invite: InviteToken },
+    /// Also pushed to the game's followers, as are `HostTransferred` and
+    /// `GameClosed`
+    PlayerKicked { game_id: GameId, player_id: PlayerId },
+    /// Sent too when the host leaves and the next player takes over
+    HostTransferred { game_id: GameId, host: PlayerId },
+    GameClosed { game_id: GameId },
+    /// Waiting for enough players; the `MatchFound` is pushed when they are
+    Queued { player_id: PlayerId, players_waiting: usize },
+    LeftQueue { player_id: PlayerId },
+    /// The game quick play seated the player in, already started
+    MatchFound {
+        game_id: GameId,
+        join_code: JoinCode,
+        player_id: PlayerId,
+        session_token: SessionToken,
+        game_state: Box<GameState>,
+    },
+    GameStarted { game_id: GameId },
+    MoveAccepted { game_id: GameId, state_hash: u64 },
+    GameState { game_state: Box<GameState> },
+    /// The game as it is now; its updates follow
+    Spectating { game_id: GameId, game_state: Box<GameState> },
+    Error { message: Text },
+    /// Answered instead of handling a message over one of the server's
+    /// limits; see `limits`. Waiting `retry_after_ms` helps if given.
+    RateLimited { limit: Limit, retry_after_ms: Option<u64> },
+    PlayerLeft { game_id: GameId, player_id: PlayerId },
+    StateSynced { game_id: GameId },
+    EncodingSelected { encoding: Encoding },
+    SkipVoteRecorded { game_id: GameId, votes: usize, needed: usize },
+    TurnSkipped { game_id: GameId, player_id: PlayerId },
+    /// Pushed to the table when the server played for a player who ran out
+    /// of time; see `turn_clock`
+    TurnTimedOut { game_id: GameId, player_id: PlayerId, game_move: GameMove },
+    Reconnected { game_id: GameId, player_id: PlayerId, game_state: Box<GameState> },
+    Pong,
+    /// Pushed, unrequested, to every connection following a game whenever
+    /// its state changes
+    StateUpdate { game_id: GameId, game_state: Box<GameState> },
+    /// Pushed to the table, and answered, when a player votes for a rematch
+    RematchVoted {
+        game_id: GameId,
+        player_id: PlayerId,
+        votes: usize,
+        needed: usize,
+    },
+    /// Pushed to the table of a finished game when its rematch is set up,
+    /// just before its first round starts.
+    /// Players keep their ids and session tokens, which now stand for their
+    /// seats in `new_game_id`, and its followers follow the rematch.
+    RematchStarted { game_id: GameId, new_game_id: GameId },
+    /// Pushed to the table when a round is scored. No round follows once
+    /// `game_over`. `deck_reveal` opens the round's deck commitment, unless
+    /// the server restarted during the round and lost its salt.
+    RoundResult {
+        game_id: GameId,
+        summary: RoundSummary,
+        game_over: bool,
+        deck_reveal: Option<Box<DeckReveal>>,
+    },
+    /// Pushed to the table, and answered, when a player is ready for the
+    /// next round, which starts once `ready` reaches `needed`
+    ReadyRecorded {
+        game_id: GameId,
+        player_id: PlayerId,
+        ready: usize,
+        needed: usize,
+    },
+    /// Pushed to every follower of the game, the reacting client included;
+    /// it can tell its own reactions by `player_id`
+    Reacted {
+        game_id: GameId,
+        player_id: PlayerId,
+        emoji: Emoji,
+    },
+    /// Sent instead of a `StateUpdate` to clients using the `state-deltas`
+    /// feature; see `delta`. `state_hash` is that of the state it leads to.
+    StateDelta {
+        game_id: GameId,
+        base_hash: u64,
+        events: Vec<GameEvent>,
+        moves: Vec<TimedMove>,
+        state_hash: u64,
+    },
+    StateAcked { game_id: GameId },
+    /// Answers an `AckState` whose hash isn't the game's, with the game's
+    /// whole state. `last_event_id` counts its events, so a client can tell
+    /// which of them it hasn't seen.
+    Resync {
+        game_id: GameId,
+        full_state: Box<GameState>,
+        last_event_id: u64,
+    },
+    /// Pushed to a game's followers when a player stops sending heartbeats
+    PlayerDisconnected { game_id: GameId, player_id: PlayerId },
+    /// Pushed when a disconnected player is heard from again
+    PlayerReconnected { game_id: GameId, player_id: PlayerId },
+    /// `total` counts the entries of every page
+    Leaderboard {
+        board: Board,
+        entries: Vec<LeaderboardEntry>,
+        total: usize,
+    },
+    /// Seed to deal the solo game from, e.g. with `GameState::new_solo`
+    SoloGameStarted { seed: u64 },
+    /// The player's best score at solo games, this one included
+    SoloScoreRecorded { total_score: u32, best_score: u32 },
+    /// Also pushed to the friend who asked first, once added back
+    FriendAdded { friend_id: String },
+    /// The friend is asked, and becomes one once they add the player back
+    FriendRequested { friend_id: String },
+    /// Pushed to an account asked to be friends by `from`
+    FriendRequest { from: String, name: String },
+    FriendRemoved { friend_id: String },
+    /// The player's friends, then the accounts asking to be
+    FriendList { friends: Vec<Friend>, requests: Vec<Friend> },
+    FriendInvited { friend_id: String, game_id: GameId },
+    /// Pushed to every connection of a friend `from` invited to the game.
+    /// `invite` lets one of them in, as `Access::Invite`
+    GameInvite {
+        from: String,
+        name: String,
+        game_id: GameId,
+        join_code: JoinCode,
+        invite: InviteToken,
+    },
+    TournamentCreated { tournament_id: TournamentId },
+    /// `session_token` takes the player's seat in each of their tournament
+    /// games, with a `Reconnect`
+    TournamentJoined {
+        tournament_id: TournamentId,
+        player_id: PlayerId,
+        session_token: SessionToken,
+        tournament: Box<Tournament>,
+    },
+    Tournament { tournament: Box<Tournament> },
+    /// Pushed to the entrants whenever the bracket changes, e.g. when a
+    /// round's games are started
+    TournamentUpdated { tournament: Box<Tournament> },
+}
+
+impl Message {
+    /// The variant's name, e.g. for logs that shouldn't hold tokens.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Message::Hello { .. } => "Hello",
+            Message::Authenticate { .. } => "Authenticate",
+            Message::JoinGame { .. } => "JoinGame",
+            Message::CreateGame { .. } => "CreateGame",
+            Message::ListGames => "ListGames",
+            Message::InvitePlayer { .. } => "InvitePlayer",
+            Message::KickPlayer { .. } => "KickPlayer",
+            Message::TransferHost { .. } => "TransferHost",
+            Message::CloseGame { .. } => "CloseGame",
+            Message::QuickPlay { .. } => "QuickPlay",
+            Message::RankedQuickPlay { .. } => "RankedQuickPlay",
+            Message::LeaveQueue { .. } => "LeaveQueue",
+            Message::StartGame { .. } => "StartGame",
+            Message::MakeMove { .. } => "MakeMove",
+            Message::GetGameState { .. } => "GetGameState",
+            Message::Spectate { .. } => "Spectate",
+            Message::AckState { .. } => "AckState",
+            Message::LeaveGame { .. } => "LeaveGame",
+            Message::SyncState { .. } => "SyncState",
+            Message::NegotiateEncoding { .. } => "NegotiateEncoding",
+            Message::VoteSkipTurn { .. } => "VoteSkipTurn",
+            Message::AddBot { .. } => "AddBot",
+            Message::Reconnect { .. } => "Reconnect",
+            Message::RequestRematch { .. } => "RequestRematch",
+            Message::Ready { .. } => "Ready",
+            Message::Reaction { .. } => "Reaction",
+            Message::GetLeaderboard { .. } => "GetLeaderboard",
+            Message::StartSoloGame { .. } => "StartSoloGame",
+            Message::SubmitSoloGame { .. } => "SubmitSoloGame",
+            Message::AddFriend { .. } => "AddFriend",
+            Message::RemoveFriend { .. } => "RemoveFriend",
+            Message::ListFriends => "ListFriends",
+            Message::InviteFriend { .. } => "InviteFriend",
+            Message::CreateTournament { .. } => "CreateTournament",
+            Message::JoinTournament { .. } => "JoinTournament",
+            Message::GetTournament { .. } => "GetTournament",
+            Message::Ping => "Ping",
+        }
+    }
+
+    /// The game the message is about, if it names one.
+    pub fn game_id(&self) -> Option<GameId> {
+        match self {
+            Message::StartGame { game_id, .. }
+            | Message::MakeMove { game_id, .. }
+            | Message::GetGameState { game_id, .. }
+            | Message::Spectate { game_id, .. }
+            | Message::AckState { game_id, .. }
+            | Message::LeaveGame { game_id, .. }
+            | Message::SyncState { game_id, .. }
+            | Message::VoteSkipTurn { game_id, .. }
+            | Message::AddBot { game_id, .. }
+            | Message::InvitePlayer { game_id, .. }
+            | Message::KickPlayer { game_id, .. }
+            | Message::TransferHost { game_id, .. }
+            | Message::CloseGame { game_id, .. }
+            | Message::RequestRematch { game_id, .. }
+            | Message::Ready { game_id, .. }
+            | Message::Reaction { game_id, .. }
+            | Message::InviteFriend { game_id, .. } => Some(*game_id),
+            Message::JoinGame { game_id, .. } => *game_id,
+            Message::Hello { .. }
+            | Message::Authenticate { .. }
+            | Message::CreateGame { .. }
+            | Message::ListGames
+            | Message::QuickPlay { .. }
+            | Message::RankedQuickPlay { .. }
+            | Message::LeaveQueue { .. }
+            | Message::NegotiateEncoding { .. }
+            | Message::Reconnect { .. }
+            | Message::GetLeaderboard { .. }
+            | Message::StartSoloGame { .. }
+            | Message::SubmitSoloGame { .. }
+            | Message::AddFriend { .. }
+            | Message::RemoveFriend { .. }
+            | Message::ListFriends
+            | Message::CreateTournament { .. }
+            | Message::JoinTournament { .. }
+            | Message::GetTournament { .. }
+            | Message::Ping => None,
+        }
+    }
 }
 
+impl Response {
+    /// The variant's name.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Response::Welcome { .. } => "Welcome",
+            Response::Authenticated { .. } => "Authenticated",
+            Response::GameJoined { .. } => "GameJoined",
+            Response::GameCreated { .. } => "GameCreated",
+            Response::GameList { .. } => "GameList",
+            Response::PlayerInvited { .. } => "PlayerInvited",
+            Response::PlayerKicked { .. } => "PlayerKicked",
+            Response::HostTransferred { .. } => "HostTransferred",
+            Response::GameClosed { .. } => "GameClosed",
+            Response::Queued { .. } => "Queued",
+            Response::LeftQueue { .. } => "LeftQueue",
+            Response::MatchFound { .. } => "MatchFound",
+            Response::GameStarted { .. } => "GameStarted",
+            Response::MoveAccepted { .. } => "MoveAccepted",
+            Response::GameState { .. } => "GameState",
+            Response::Spectating { .. } => "Spectating",
+            Response::Error { .. } => "Error",
+            Response::RateLimited { .. } => "RateLimited",
+            Response::PlayerLeft { .. } => "PlayerLeft",
+            Response::StateSynced { .. } => "StateSynced",
+            Response::EncodingSelected { .. } => "EncodingSelected",
+            Response::SkipVoteRecorded { .. } => "SkipVoteRecorded",
+            Response::TurnSkipped { .. } => "TurnSkipped",
+            Response::TurnTimedOut { .. } => "TurnTimedOut",
+            Response::Reconnected { .. } => "Reconnected",
+            Response::Pong => "Pong",
+            Response::StateUpdate { .. } => "StateUpdate",
+            Response::RematchVoted { .. } => "RematchVoted",
+            Response::RematchStarted { .. } => "RematchStarted",
+            Response::RoundResult { .. } => "RoundResult",
+            Response::ReadyRecorded { .. } => "ReadyRecorded",
+            Response::Reacted { .. } => "Reacted",
+            Response::StateDelta { .. } => "StateDelta",
+            Response::StateAcked { .. } => "StateAcked",
+            Response::Resync { .. } => "Resync",
+            Response::PlayerDisconnected { .. } => "PlayerDisconnected",
+            Response::PlayerReconnected { .. } => "PlayerReconnected",
+            Response::Leaderboard { .. } => "Leaderboard",
+            Response::SoloGameStarted { .. } => "SoloGameStarted",
+            Response::SoloScoreRecorded { .. } => "SoloScoreRecorded",
+            Response::FriendAdded { .. } => "FriendAdded",
+            Response::FriendRequested { .. } => "FriendRequested",
+            Response::FriendRequest { .. } => "FriendRequest",
+            Response::FriendRemoved { .. } => "FriendRemoved",
+            Response::FriendList { .. } => "FriendList",
+            Response::FriendInvited { .. } => "FriendInvited",
+            Response::GameInvite { .. } => "GameInvite",
+            Response::TournamentCreated { .. } => "TournamentCreated",
+            Response::TournamentJoined { .. } => "TournamentJoined",
+            Response::Tournament { .. } => "Tournament",
+            Response::TournamentUpdated { .. } => "TournamentUpdated",
+        }
+    }
+}
+
+/// Expiry events a slow subscriber can fall behind by.
+const EXPIRY_BACKLOG: usize = 256;
+
+/// Async front of the `ProtocolEngine`, shared between connection tasks.
+/// Games are spread over several engines, each behind its own lock, so moves
+/// in one game don't wait on another; see `shard`. Clones share the same games.
+#[derive(Clone)]
 pub struct GameServer {
-    games: Arc<RwLock<HashMap<String, GameState>>>,
+    pub(crate) shards: Arc<Shards>,
+    auth: Arc<RwLock<Option<Auth>>>,
+    pub(crate) limiter: Arc<Limiter>,
+    /// Where the games dropped by `sweep_expired` are announced
+    expired: broadcast::Sender<ExpiryEvent>,
+    /// Where every shard announces games being created, started, scored and
+    /// finished
+    lifecycle: broadcast::Sender<LifecycleEvent>,
+    /// Where games are saved, if anywhere; see `store`
+    store: Arc<RwLock<Option<Arc<dyn GameStore>>>>,
+    /// Held on each game while it is saved; see `store::SaveLocks`
+    save_locks: Arc<store::SaveLocks>,
+    /// Token operators send admin requests with; see `admin`
+    admin_token: Arc<RwLock<Option<String>>>,
+    /// Whether new games are refused, ahead of a deploy
+    pub(crate) draining: Arc<AtomicBool>,
+    /// Most games held at once, if capped
+    max_games: Arc<RwLock<Option<usize>>>,
+    /// Signed-in players' friends and presence; see `friends`
+    pub(crate) friends: Arc<Mutex<friends::Friends>>,
+}
+
+impl Default for GameServer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameServer {
     pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// A server spreading its games over `shards` engines. One shard puts
+    /// every game behind the same lock.
+    pub fn with_shards(shards: usize) -> Self {
+        let lifecycle = broadcast::channel(protocol::LIFECYCLE_BACKLOG).0;
         Self {
-            games: Arc::new(RwLock::new(HashMap::new())),
+            shards: Arc::new(Shards::new(shards, lifecycle.clone())),
+            auth: Arc::new(RwLock::new(None)),
+            limiter: Arc::new(Limiter::default()),
+            expired: broadcast::channel(EXPIRY_BACKLOG).0,
+            lifecycle,
+            store: Arc::new(RwLock::new(None)),
+            save_locks: Arc::default(),
+            admin_token: Arc::new(RwLock::new(None)),
+            draining: Arc::new(AtomicBool::new(false)),
+            max_games: Arc::new(RwLock::new(None)),
+            friends: Arc::default(),
+        }
+    }
+
+    /// Has connections opened from now on sign in; see `auth`.
+    pub fn set_auth(&self, auth: Auth) {
+        *self.auth.write().unwrap() = Some(auth);
+    }
+
+    pub(crate) fn auth(&self) -> Option<Auth> {
+        self.auth.read().unwrap().clone()
+    }
+
+    /// Holds connections from untrusted peers to the limits; `Limits::default()`
+    /// until set.
+    pub fn set_limits(&self, limits: Limits) -> Result<(), String> {
+        limits.validate()?;
+        self.limiter.set_limits(limits);
+        Ok(())
+    }
+
+    /// Why a request of `len` bytes from an untrusted client isn't handled,
+    /// for transports without a connection to hold to its own rate: too
+    /// large, or its address sends too fast.
+    #[cfg(any(feature = "http", feature = "grpc"))]
+    pub(crate) fn throttle_request(
+        &self,
+        trust: TrustLevel,
+        address: std::net::IpAddr,
+        len: usize,
+    ) -> Option<Response> {
+        if trust != TrustLevel::UntrustedPeer {
+            return None;
+        }
+        let limits = self.limiter.limits();
+        if len > limits.max_message_len {
+            return Some(Response::RateLimited {
+                limit: Limit::MessageSize,
+                retry_after_ms: None,
+            });
+        }
+        let taken = self
+            .limiter
+            .take(address, &limits, std::time::Instant::now());
+        taken.err().map(|wait| Response::RateLimited {
+            limit: Limit::AddressMessages,
+            retry_after_ms: Some(wait.as_millis() as u64 + 1),
+        })
+    }
+
+    /// The player whose seat in the game the session token is for, if any.
+    #[cfg(any(feature = "http", feature = "grpc"))]
+    pub(crate) async fn seat_of(&self, game_id: GameId, token: &SessionToken) -> Option<PlayerId> {
+        let engine = self.shards.of(game_id).read().await;
+        match engine.sessions.get(token) {
+            Some((seat_game, player_id)) if *seat_game == game_id => Some(*player_id),
+            _ => None,
         }
     }
 
     pub async fn handle_message(&self, message: Message) -> Response {
-        match message {
-            Message::JoinGame { player_name, game_id } => {
-                self.join_game(player_name, game_id).await
-            }
-            Message::StartGame { game_id } => {
-                self.start_game(game_id).await
-            }
-            Message::MakeMove { game_id, game_move } => {
-                self.make_move(game_id, game_move).await
-            }
-            Message::GetGameState { game_id } => {
-                self.get_game_state(game_id).await
+        if let Some(refused) = self.refuse_new_game(&message).await {
+            return refused;
+        }
+        match self.shards.route(&message).await {
+            Some(engine) => {
+                self.handle_stored(engine, message, |engine, message| engine.handle(message))
+                    .await
             }
-            Message::LeaveGame { game_id, player_id } => {
-                self.leave_game(game_id, player_id).await
+            None => self.shards.list_games().await,
+        }
+    }
+
+    /// Handles a message received over a connection with the given trust level,
+    /// rejecting message types that level is not allowed to send.
+    pub async fn handle_message_with_trust(&self, trust: TrustLevel, message: Message) -> Response {
+        if let Some(refused) = self.refuse_new_game(&message).await {
+            return refused;
+        }
+        match self.shards.route(&message).await {
+            Some(engine) => {
+                self.handle_stored(engine, message, |engine, message| engine.handle_with_trust(trust, message))
+                    .await
             }
+            // Every trust level may list games
+            None => self.shards.list_games().await,
         }
     }
 
-    async fn join_game(&self, player_name: String, game_id: Option<String>) -> Response {
-        let mut games = self.games.write().await;
+    /// Handles a message from a client at `address`, as
+    /// `handle_message_with_trust` does, holding untrusted clients to the
+    /// games their address may be in; see `Limits::max_games_per_address`.
+    pub(crate) async fn handle_request(
+        &self,
+        trust: TrustLevel,
+        address: std::net::IpAddr,
+        message: Message,
+    ) -> Response {
+        let enters_game = matches!(message, Message::CreateGame { .. } | Message::JoinGame { .. });
+        if trust != TrustLevel::UntrustedPeer || !enters_game {
+            return self.handle_message_with_trust(trust, message).await;
+        }
 
-        let (game_id, game) = if let Some(id) = game_id {
-            if let Some(game) = games.get_mut(&id) {
-                (id, game)
-            } else {
-                return Response::Error {
-                    message: "Game not found".to_string(),
-                };
+        // Games that ended or were forgotten no longer count
+        let mut live = Vec::new();
+        for game_id in self.limiter.games_of(address) {
+            let engine = self.shards.of(game_id).read().await;
+            if engine.games.get(&game_id).is_some_and(|game| !game.is_game_over()) {
+                live.push(game_id);
             }
-        } else {
-            let new_game = GameState::new(7, 7);
-            let id = new_game.id.clone();
-            games.insert(id.clone(), new_game);
-            let game = games.get_mut(&id).unwrap();
-            (id, game)
+        }
+        self.limiter.keep_games(address, &live);
+        if live.len() >= self.limiter.limits().max_games_per_address {
+            return Response::RateLimited {
+                limit: Limit::AddressGames,
+                retry_after_ms: None,
+            };
+        }
+        let response = self.handle_message_with_trust(trust, message).await;
+        if let Response::GameCreated { game_id, .. }
+        | Response::GameJoined {
+            game_id,
+            session_token: Some(_),
+            ..
+        } = &response
+        {
+            self.limiter.entered(address, *game_id);
+        }
+        response
+    }
+
+    /// The answer to a message that would start a new game on a server
+    /// that is draining or full. Games already in flight carry on.
+    async fn refuse_new_game(&self, message: &Message) -> Option<Response> {
+        let new_game = matches!(
+            message,
+            Message::CreateGame { .. }
+                | Message::JoinGame {
+                    game_id: None,
+                    code: None,
+                    ..
+                }
+                | Message::QuickPlay { .. }
+                | Message::RankedQuickPlay { .. }
+                | Message::RequestRematch { .. }
+                | Message::CreateTournament { .. }
+        );
+        if !new_game {
+            return None;
+        }
+        if self.is_draining() {
+            return Some(Response::Error {
+                message: text!("shutting_down"),
+            });
+        }
+        let max_games = (*self.max_games.read().unwrap())?;
+        let mut games = 0;
+        for engine in self.shards.all() {
+            games += engine.read().await.games.len();
+        }
+        (games >= max_games).then(|| Response::Error {
+            message: text!("server_full"),
+        })
+    }
+
+    /// Decodes, handles and encodes one message; see `ProtocolEngine::handle_bytes`.
+    pub async fn handle_bytes(&self, encoding: Encoding, trust: TrustLevel, bytes: &[u8]) -> Vec<u8> {
+        let response = match encoding.decode::<Message>(bytes) {
+            Ok(message) => self.handle_message_with_trust(trust, message).await,
+            Err(err) => Response::Error {
+                message: text!("invalid_json", err),
+            },
         };
 
-        let player_id = game.add_player(player_name);
+        encoding.encode(&response).unwrap_or_else(|err| {
+            // Responses always encode; fall back to JSON if the codec itself failed
+            serde_json::to_vec(&Response::Error { message: err.into() }).unwrap_or_default()
+        })
+    }
+
+    /// See `ProtocolEngine::tick_turn_timers`.
+    pub async fn tick_turn_timers(&self) -> Vec<(GameId, PlayerId)> {
+        let mut timed_out = Vec::new();
+        for engine in self.shards.all() {
+            timed_out.extend(engine.write().await.tick_turn_timers());
+        }
+        timed_out
+    }
 
-        Response::GameJoined {
-            game_id: game_id.clone(),
-            player_id,
+    /// See `ProtocolEngine::take_bot_moves`.
+    pub async fn take_bot_moves(&self) -> Vec<(GameId, GameMove)> {
+        let mut moves = Vec::new();
+        for engine in self.shards.all() {
+            moves.extend(engine.write().await.take_bot_moves());
         }
+        moves
     }
 
-    async fn start_game(&self, game_id: String) -> Response {
-        let mut games = self.games.write().await;
+    /// See `ProtocolEngine::subscribe`.
+    pub async fn subscribe(&self, game_id: GameId) -> Option<broadcast::Receiver<Response>> {
+        self.shards.of(game_id).write().await.subscribe(game_id)
+    }
 
-        if let Some(game) = games.get_mut(&game_id) {
-            match game.start_game() {
-                Ok(()) => Response::GameStarted { game_id },
-                Err(err) => Response::Error { message: err },
-            }
-        } else {
-            Response::Error {
-                message: "Game not found".to_string(),
-            }
+    /// See `ProtocolEngine::seen`.
+    pub async fn seen(&self, seats: impl IntoIterator<Item = (GameId, PlayerId)>) {
+        // Collected first, so the future doesn't hold the caller's iterator
+        let seats: Vec<(GameId, PlayerId)> = seats.into_iter().collect();
+        for (game_id, player_id) in seats {
+            self.shards.of(game_id).write().await.seen(game_id, player_id);
         }
     }
 
-    async fn make_move(&self, game_id: String, game_move: GameMove) -> Response {
-        let mut games = self.games.write().await;
+    /// See `ProtocolEngine::check_heartbeats`.
+    pub async fn check_heartbeats(&self) -> Vec<(GameId, PlayerId)> {
+        let mut dropped = Vec::new();
+        for engine in self.shards.all() {
+            dropped.extend(engine.write().await.check_heartbeats());
+        }
+        dropped
+    }
 
-        if let Some(game) = games.get_mut(&game_id) {
-            match game.make_move(game_move) {
-                Ok(()) => Response::MoveAccepted { game_id },
-                Err(err) => Response::Error { message: err },
-            }
-        } else {
-            Response::Error {
-                message: "Game not found".to_string(),
-            }
+    /// See `ProtocolEngine::set_heartbeat`.
+    pub async fn set_heartbeat(&self, heartbeat: Heartbeat) {
+        for engine in self.shards.all() {
+            engine.write().await.set_heartbeat(heartbeat);
         }
     }
 
-    async fn get_game_state(&self, game_id: String) -> Response {
-        let games = self.games.read().await;
+    /// See `ProtocolEngine::wait_for_match`.
+    pub async fn wait_for_match(&self, player_id: PlayerId) -> Option<oneshot::Receiver<Response>> {
+        self.shards.lobby().write().await.wait_for_match(player_id)
+    }
 
-        if let Some(game) = games.get(&game_id) {
-            Response::GameState {
-                game_state: game.clone(),
-            }
-        } else {
-            Response::Error {
-                message: "Game not found".to_string(),
-            }
+    /// See `ProtocolEngine::subscribe_tournament`.
+    pub async fn subscribe_tournament(&self, tournament_id: TournamentId) -> Option<broadcast::Receiver<Response>> {
+        self.shards.lobby().write().await.subscribe_tournament(tournament_id)
+    }
+
+    /// See `ProtocolEngine::rating`.
+    pub async fn rating(&self, account: &str) -> Rating {
+        self.shards.lobby().read().await.rating(account)
+    }
+
+    /// Notes the account a signed-in player plays a seat under; see
+    /// `leaderboard`.
+    pub(crate) async fn set_account(&self, game_id: GameId, player_id: PlayerId, account: String) {
+        self.shards.of(game_id).write().await.set_account(game_id, player_id, account);
+    }
+
+    /// See `ProtocolEngine::check_queue`.
+    pub async fn check_queue(&self) -> Vec<GameId> {
+        self.shards.lobby().write().await.check_queue()
+    }
+
+    /// See `ProtocolEngine::check_rounds`.
+    pub async fn check_rounds(&self) -> Vec<GameId> {
+        let mut started = Vec::new();
+        for engine in self.shards.all() {
+            started.extend(engine.write().await.check_rounds());
         }
+        started
     }
 
-    async fn leave_game(&self, game_id: String, player_id: String) -> Response {
-        let mut games = self.games.write().await;
+    /// Caps the games held at once; `None` lifts the cap. Games finish and
+    /// expire as usual, making room for new ones.
+    pub fn set_max_games(&self, max_games: Option<usize>) {
+        *self.max_games.write().unwrap() = max_games;
+    }
 
-        if let Some(game) = games.get_mut(&game_id) {
-            game.players.retain(|p| p.id != player_id);
-            Response::PlayerLeft { game_id, player_id }
-        } else {
-            Response::Error {
-                message: "Game not found".to_string(),
+    /// See `ProtocolEngine::set_turn_clock`.
+    pub async fn set_turn_clock(&self, turn_clock: Option<TurnClock>) -> Result<(), String> {
+        for engine in self.shards.all() {
+            engine.write().await.set_turn_clock(turn_clock)?;
+        }
+        Ok(())
+    }
+
+    /// See `ProtocolEngine::set_round_flow`.
+    pub async fn set_round_flow(&self, round_flow: RoundFlow) {
+        for engine in self.shards.all() {
+            engine.write().await.set_round_flow(round_flow);
+        }
+    }
+
+    /// Drops the games that went stale on every shard and announces them to
+    /// `subscribe_expired`. Returns their ids.
+    pub async fn sweep_expired(&self) -> Vec<GameId> {
+        let mut expired = Vec::new();
+        for engine in self.shards.all() {
+            let events = engine.write().await.sweep_expired();
+            for event in events {
+                expired.push(event.game_id);
+                // Nobody may be archiving
+                let _ = self.expired.send(event);
             }
         }
+        expired
+    }
+
+    /// Where each game dropped from now on is announced, e.g. for a
+    /// persistence layer to archive it.
+    pub fn subscribe_expired(&self) -> broadcast::Receiver<ExpiryEvent> {
+        self.expired.subscribe()
+    }
+
+    /// Where games being created, started, scored and finished from now on
+    /// are announced, e.g. for `webhook::Webhooks` to post them.
+    pub fn subscribe_lifecycle(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.lifecycle.subscribe()
+    }
+
+    /// See `ProtocolEngine::set_expiry`.
+    pub async fn set_expiry(&self, expiry: Expiry) {
+        for engine in self.shards.all() {
+            engine.write().await.set_expiry(expiry);
+        }
+    }
+
+    /// See `ProtocolEngine::set_matchmaking`.
+    pub async fn set_matchmaking(&self, matchmaking: Matchmaking) -> Result<(), String> {
+        self.shards.lobby().write().await.set_matchmaking(matchmaking)
+    }
+
+    /// See `ProtocolEngine::pause_game`.
+    pub async fn pause_game(&self, game_id: GameId) -> Result<(), String> {
+        self.shards.of(game_id).write().await.pause_game(game_id)
+    }
+
+    /// See `ProtocolEngine::resume_game`.
+    pub async fn resume_game(&self, game_id: GameId) -> Result<(), String> {
+        self.shards.of(game_id).write().await.resume_game(game_id)
     }
 }
 
@@ -149,12 +1011,17 @@ mod tests {
         let response = server.handle_message(Message::JoinGame {
             player_name: "Alice".to_string(),
             game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
         }).await;
 
         match response {
-            Response::GameJoined { game_id, player_id } => {
-                assert!(!game_id.is_empty());
-                assert!(!player_id.is_empty());
+            Response::GameJoined { game_id, player_id, engine_rules_version, session_token, .. } => {
+                assert_ne!(game_id.to_string(), player_id.to_string());
+                assert_eq!(engine_rules_version, game_core::rules::ENGINE_RULES_VERSION);
+                assert!(session_token.is_some());
             }
             _ => panic!("Expected GameJoined response"),
         }
@@ -167,6 +1034,10 @@ mod tests {
         let join_response = server.handle_message(Message::JoinGame {
             player_name: "Alice".to_string(),
             game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
         }).await;
 
         let game_id = match join_response {
@@ -176,11 +1047,16 @@ mod tests {
 
         server.handle_message(Message::JoinGame {
             player_name: "Bob".to_string(),
-            game_id: Some(game_id.clone()),
+            game_id: Some(game_id),
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
         }).await;
 
         let start_response = server.handle_message(Message::StartGame {
-            game_id: game_id.clone(),
+            game_id,
+            host: None,
         }).await;
 
         match start_response {
@@ -188,4 +1064,159 @@ mod tests {
             _ => panic!("Expected GameStarted response"),
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_untrusted_peer_cannot_sync_state() {
+        let server = GameServer::new();
+        let message = Message::SyncState {
+            game_id: GameId::new(),
+            game_state: Box::new(GameState::new()),
+        };
+
+        let response = server
+            .handle_message_with_trust(TrustLevel::UntrustedPeer, message.clone())
+            .await;
+        assert!(matches!(response, Response::Error { .. }));
+
+        let response = server
+            .handle_message_with_trust(TrustLevel::TrustedRelay, message.clone())
+            .await;
+        assert!(matches!(response, Response::Error { .. }));
+
+        let response = server
+            .handle_message_with_trust(TrustLevel::AuthoritativeServer, message)
+            .await;
+        assert!(matches!(response, Response::StateSynced { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_peer_can_join_but_not_start() {
+        let server = GameServer::new();
+        let join_response = server
+            .handle_message_with_trust(
+                TrustLevel::UntrustedPeer,
+                Message::JoinGame {
+                    player_name: "Alice".to_string(),
+                    game_id: None,
+                    team: None,
+                    variant: None,
+                    code: None,
+                    access: None,
+                },
+            )
+            .await;
+
+        let game_id = match join_response {
+            Response::GameJoined { game_id, .. } => game_id,
+            _ => panic!("Expected GameJoined response"),
+        };
+
+        let start_response = server
+            .handle_message_with_trust(TrustLevel::UntrustedPeer, Message::StartGame { game_id, host: None })
+            .await;
+        assert!(matches!(start_response, Response::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_move_accepted_carries_state_hash() {
+        let server = GameServer::new();
+        let (game_id, player_id) = match server
+            .handle_message(Message::JoinGame {
+                player_name: "Alice".to_string(),
+                game_id: None,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            })
+            .await
+        {
+            Response::GameJoined { game_id, player_id, .. } => (game_id, player_id),
+            _ => panic!("Expected GameJoined response"),
+        };
+        server
+            .handle_message(Message::StartGame { game_id, host: None })
+            .await;
+
+        let state_hash = match server
+            .handle_message(Message::MakeMove {
+                game_id,
+                game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+                move_id: None,
+            })
+            .await
+        {
+            Response::MoveAccepted { state_hash, .. } => state_hash,
+            _ => panic!("Expected MoveAccepted response"),
+        };
+
+        match server.handle_message(Message::GetGameState { game_id, access: None }).await {
+            Response::GameState { game_state } => assert_eq!(game_state.state_hash(), state_hash),
+            _ => panic!("Expected GameState response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_encoding() {
+        let server = GameServer::new();
+        let response = server
+            .handle_message_with_trust(
+                TrustLevel::UntrustedPeer,
+                Message::NegotiateEncoding {
+                    offered: vec![Encoding::Json],
+                },
+            )
+            .await;
+        assert!(matches!(
+            response,
+            Response::EncodingSelected {
+                encoding: Encoding::Json
+            }
+        ));
+
+        let response = server
+            .handle_message(Message::NegotiateEncoding {
+                offered: vec![Encoding::Binary, Encoding::Json],
+            })
+            .await;
+        let expected = if cfg!(feature = "binary") {
+            Encoding::Binary
+        } else {
+            Encoding::Json
+        };
+        match response {
+            Response::EncodingSelected { encoding } => assert_eq!(encoding, expected),
+            _ => panic!("Expected EncodingSelected response"),
+        }
+    }
+
+    #[test]
+    fn test_message_kind_and_game() {
+        let game_id = GameId::new();
+        let message = Message::StartGame { game_id, host: None };
+        assert_eq!(message.kind(), "StartGame");
+        assert_eq!(message.game_id(), Some(game_id));
+        assert_eq!(Message::Ping.game_id(), None);
+        assert_eq!(Response::Pong.kind(), "Pong");
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn test_binary_encoding_round_trip() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        let response = Response::GameState {
+            game_state: Box::new(game.clone()),
+        };
+
+        let bytes = Encoding::Binary.encode(&response).unwrap();
+        assert!(bytes.len() < Encoding::Json.encode(&response).unwrap().len());
+
+        match Encoding::Binary.decode::<Response>(&bytes).unwrap() {
+            Response::GameState { game_state } => assert_eq!(*game_state, game),
+            _ => panic!("Expected GameState response"),
+        }
+    }
+}
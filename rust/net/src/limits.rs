@@ -0,0 +1,244 @@
+//! Abuse protection for connections from untrusted peers. Each connection,
+//! and all connections from one address together, may only send messages so
+//! fast; a connection, and an address over every transport, may only be in
+//! so many games at once; and messages past a size limit aren't handled. A
+//! client over a limit is answered `Response::RateLimited` instead of being
+//! served.
+//!
+//! Message rates are token buckets: a quiet client saves up a burst of
+//! messages, and after that is held to the sustained rate.
+
+use crate::GameId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// What untrusted clients may send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Messages a connection may send per second, sustained
+    pub messages_per_second: u32,
+    /// Messages a connection that has been quiet may send at once
+    pub burst: u32,
+    /// Messages all connections from one address may send per second
+    pub address_messages_per_second: u32,
+    pub address_burst: u32,
+    /// Games a connection may have created, joined or be queued for at once
+    pub max_games_per_client: usize,
+    /// Games the clients at one address may have created or joined at once,
+    /// over any transport and however often they reconnect
+    pub max_games_per_address: usize,
+    /// Largest message handled, in bytes
+    pub max_message_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            messages_per_second: 20,
+            burst: 40,
+            address_messages_per_second: 100,
+            address_burst: 200,
+            max_games_per_client: 4,
+            max_games_per_address: 16,
+            max_message_len: 64 * 1024,
+        }
+    }
+}
+
+impl Limits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.messages_per_second == 0 || self.address_messages_per_second == 0 {
+            return Err("Clients must be allowed at least one message per second".to_string());
+        }
+        if self.burst == 0 || self.address_burst == 0 {
+            return Err("Bursts must allow at least one message".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Which limit a client went over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Limit {
+    /// The connection's message rate
+    Messages,
+    /// The message rate of all connections from the client's address
+    AddressMessages,
+    /// `max_games_per_client`
+    Games,
+    /// `max_games_per_address`
+    AddressGames,
+    /// `max_message_len`
+    MessageSize,
+}
+
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn full(burst: u32, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(burst),
+            updated: now,
+        }
+    }
+
+    /// Takes a token if there is one, or says how long until there is.
+    pub(crate) fn take(&mut self, rate: u32, burst: u32, now: Instant) -> Result<(), Duration> {
+        self.refill(rate, burst, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64(
+            (1.0 - self.tokens) / f64::from(rate),
+        ))
+    }
+
+    fn refill(&mut self, rate: u32, burst: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(rate)).min(f64::from(burst));
+        self.updated = now;
+    }
+}
+
+/// The limits of a server, and the buckets of the addresses its clients
+/// connect from.
+#[derive(Debug, Default)]
+pub(crate) struct Limiter {
+    limits: RwLock<Limits>,
+    addresses: Mutex<HashMap<IpAddr, TokenBucket>>,
+    /// Games each address's clients created or joined, as last seen
+    games: Mutex<HashMap<IpAddr, HashSet<GameId>>>,
+}
+
+impl Limiter {
+    pub(crate) fn limits(&self) -> Limits {
+        *self.limits.read().unwrap()
+    }
+
+    pub(crate) fn set_limits(&self, limits: Limits) {
+        *self.limits.write().unwrap() = limits;
+    }
+
+    /// Takes a token of the address's bucket, or says how long until there
+    /// is one.
+    pub(crate) fn take(
+        &self,
+        address: IpAddr,
+        limits: &Limits,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        self.addresses
+            .lock()
+            .unwrap()
+            .entry(address)
+            .or_insert_with(|| TokenBucket::full(limits.address_burst, now))
+            .take(
+                limits.address_messages_per_second,
+                limits.address_burst,
+                now,
+            )
+    }
+
+    /// The games the address's clients created or joined, over or not.
+    pub(crate) fn games_of(&self, address: IpAddr) -> Vec<GameId> {
+        let games = self.games.lock().unwrap();
+        games.get(&address).into_iter().flatten().copied().collect()
+    }
+
+    /// Notes a game the address's clients created or joined.
+    pub(crate) fn entered(&self, address: IpAddr, game_id: GameId) {
+        self.games.lock().unwrap().entry(address).or_default().insert(game_id);
+    }
+
+    /// Forgets the address's games but the `live` ones.
+    pub(crate) fn keep_games(&self, address: IpAddr, live: &[GameId]) {
+        let mut games = self.games.lock().unwrap();
+        if let Some(held) = games.get_mut(&address) {
+            held.retain(|game_id| live.contains(game_id));
+            if held.is_empty() {
+                games.remove(&address);
+            }
+        }
+    }
+
+    /// Drops the buckets of addresses quiet long enough to have refilled,
+    /// which would start out full anyway.
+    pub(crate) fn forget_idle(&self, now: Instant) {
+        let limits = self.limits();
+        self.addresses.lock().unwrap().retain(|_, bucket| {
+            bucket.refill(
+                limits.address_messages_per_second,
+                limits.address_burst,
+                now,
+            );
+            bucket.tokens < f64::from(limits.address_burst)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::full(2, start);
+        assert!(bucket.take(4, 2, start).is_ok());
+        assert!(bucket.take(4, 2, start).is_ok());
+        assert_eq!(bucket.take(4, 2, start), Err(Duration::from_millis(250)));
+        assert!(bucket
+            .take(4, 2, start + Duration::from_millis(250))
+            .is_ok());
+        // Refills no further than the burst
+        let later = start + Duration::from_secs(10);
+        for _ in 0..2 {
+            assert!(bucket.take(4, 2, later).is_ok());
+        }
+        assert!(bucket.take(4, 2, later).is_err());
+    }
+
+    #[test]
+    fn test_address_buckets() {
+        let limiter = Limiter::default();
+        let limits = Limits {
+            address_messages_per_second: 1,
+            address_burst: 1,
+            ..Limits::default()
+        };
+        limiter.set_limits(limits);
+        let start = Instant::now();
+        let (home, away) = (
+            IpAddr::from(Ipv4Addr::LOCALHOST),
+            IpAddr::from([192, 0, 2, 1]),
+        );
+        assert!(limiter.take(home, &limits, start).is_ok());
+        assert!(limiter.take(home, &limits, start).is_err());
+        assert!(limiter.take(away, &limits, start).is_ok());
+
+        // Games count until they are found to be gone
+        let (kept, gone) = (GameId::new(), GameId::new());
+        limiter.entered(home, kept);
+        limiter.entered(home, gone);
+        limiter.keep_games(home, &[kept]);
+        assert_eq!(limiter.games_of(home), [kept]);
+        limiter.keep_games(home, &[]);
+        assert!(limiter.games.lock().unwrap().is_empty());
+
+        limiter.forget_idle(start);
+        assert_eq!(limiter.addresses.lock().unwrap().len(), 2);
+        limiter.forget_idle(start + Duration::from_secs(1));
+        assert!(limiter.addresses.lock().unwrap().is_empty());
+        assert!(limits.validate().is_ok());
+        assert!(Limits { burst: 0, ..limits }.validate().is_err());
+    }
+}
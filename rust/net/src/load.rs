@@ -0,0 +1,221 @@
+//! Load profiles and reports for the `loadtest` harness. Each profile is a
+//! fixed, repeatable traffic shape, so reports from different runs (and
+//! different builds) can be compared when sizing infrastructure.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoadProfile {
+    /// Many small tables playing at a relaxed pace
+    CasualEvening,
+    /// Every table of a tournament starts at once and plays fast
+    TournamentBurst,
+    /// Lots of mostly idle two-player games with the odd move
+    CorrespondenceHeavy,
+}
+
+/// Traffic shape of a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileSpec {
+    pub tables: usize,
+    pub players_per_table: usize,
+    pub rounds: u32,
+    /// Pause before each move
+    pub think_time: Duration,
+    /// Tables are started evenly over this period
+    pub ramp_up: Duration,
+}
+
+impl LoadProfile {
+    pub const ALL: [LoadProfile; 3] = [
+        LoadProfile::CasualEvening,
+        LoadProfile::TournamentBurst,
+        LoadProfile::CorrespondenceHeavy,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoadProfile::CasualEvening => "casual-evening",
+            LoadProfile::TournamentBurst => "tournament-burst",
+            LoadProfile::CorrespondenceHeavy => "correspondence-heavy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|profile| profile.name() == name)
+    }
+
+    pub fn spec(&self) -> ProfileSpec {
+        match self {
+            LoadProfile::CasualEvening => ProfileSpec {
+                tables: 50,
+                players_per_table: 4,
+                rounds: 3,
+                think_time: Duration::from_millis(20),
+                ramp_up: Duration::from_secs(2),
+            },
+            LoadProfile::TournamentBurst => ProfileSpec {
+                tables: 200,
+                players_per_table: 4,
+                rounds: 2,
+                think_time: Duration::from_millis(2),
+                ramp_up: Duration::ZERO,
+            },
+            LoadProfile::CorrespondenceHeavy => ProfileSpec {
+                tables: 500,
+                players_per_table: 2,
+                rounds: 1,
+                think_time: Duration::from_millis(100),
+                ramp_up: Duration::from_secs(1),
+            },
+        }
+    }
+}
+
+/// Measurements of one run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadReport {
+    pub profile: LoadProfile,
+    pub requests: u64,
+    pub errors: u64,
+    pub duration_ms: u64,
+    pub requests_per_sec: f64,
+    pub p50_latency_us: u64,
+    pub p95_latency_us: u64,
+    pub p99_latency_us: u64,
+}
+
+impl LoadReport {
+    pub fn new(
+        profile: LoadProfile,
+        mut latencies_us: Vec<u64>,
+        errors: u64,
+        duration: Duration,
+    ) -> Self {
+        latencies_us.sort_unstable();
+        let percentile = |p: f64| {
+            if latencies_us.is_empty() {
+                return 0;
+            }
+            let rank = (p * latencies_us.len() as f64).ceil() as usize;
+            latencies_us[rank.clamp(1, latencies_us.len()) - 1]
+        };
+
+        let requests = latencies_us.len() as u64;
+        Self {
+            profile,
+            requests,
+            errors,
+            duration_ms: duration.as_millis() as u64,
+            requests_per_sec: requests as f64 / duration.as_secs_f64().max(f64::EPSILON),
+            p50_latency_us: percentile(0.50),
+            p95_latency_us: percentile(0.95),
+            p99_latency_us: percentile(0.99),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// One line per metric showing how this run moved from `baseline`.
+    pub fn compare(&self, baseline: &LoadReport) -> Result<Vec<String>, String> {
+        if self.profile != baseline.profile {
+            return Err(format!(
+                "Cannot compare a {} run with a {} baseline",
+                self.profile.name(),
+                baseline.profile.name()
+            ));
+        }
+
+        let line = |metric: &str, before: f64, after: f64| {
+            let change = if before == 0.0 {
+                0.0
+            } else {
+                (after - before) / before * 100.0
+            };
+            format!(
+                "{:<16} {:>12.1} -> {:>12.1} ({:+.1}%)",
+                metric, before, after, change
+            )
+        };
+
+        Ok(vec![
+            line(
+                "requests/s",
+                baseline.requests_per_sec,
+                self.requests_per_sec,
+            ),
+            line(
+                "p50 latency us",
+                baseline.p50_latency_us as f64,
+                self.p50_latency_us as f64,
+            ),
+            line(
+                "p95 latency us",
+                baseline.p95_latency_us as f64,
+                self.p95_latency_us as f64,
+            ),
+            line(
+                "p99 latency us",
+                baseline.p99_latency_us as f64,
+                self.p99_latency_us as f64,
+            ),
+            line("errors", baseline.errors as f64, self.errors as f64),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_percentiles() {
+        let latencies = (1..=100).rev().collect();
+        let report = LoadReport::new(
+            LoadProfile::TournamentBurst,
+            latencies,
+            0,
+            Duration::from_secs(2),
+        );
+
+        assert_eq!(report.requests, 100);
+        assert_eq!(report.requests_per_sec, 50.0);
+        assert_eq!(report.p50_latency_us, 50);
+        assert_eq!(report.p95_latency_us, 95);
+        assert_eq!(report.p99_latency_us, 99);
+    }
+
+    #[test]
+    fn test_compare_reports() {
+        let before = LoadReport::new(
+            LoadProfile::CasualEvening,
+            vec![100; 10],
+            0,
+            Duration::from_secs(1),
+        );
+        let after = LoadReport::new(
+            LoadProfile::CasualEvening,
+            vec![150; 10],
+            0,
+            Duration::from_secs(1),
+        );
+
+        let lines = after.compare(&before).unwrap();
+        assert!(lines[1].contains("+50.0%"));
+
+        let other = LoadReport::new(
+            LoadProfile::TournamentBurst,
+            vec![],
+            0,
+            Duration::from_secs(1),
+        );
+        assert!(after.compare(&other).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! The lobby: what clients see of the games on a server before joining one.
+
+use crate::{GameId, InviteToken, JoinCode, SessionToken};
+use game_core::variant::DEFAULT_VARIANT;
+use game_core::GameState;
+use serde::{Deserialize, Serialize};
+
+/// Who can find and join a game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    /// Listed in the lobby for anyone to join
+    Public,
+    /// Joined by those given its id or join code, and the password if it
+    /// has one
+    Private { password: Option<String> },
+    /// Joined only with an invitation from the host
+    InviteOnly,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility::Private { password: None }
+    }
+}
+
+/// What a player joins a protected game with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Access {
+    Password(String),
+    /// Gets past a password too, and is used up by joining
+    Invite(InviteToken),
+    /// A player's session token, which lets them look at their own game
+    Seat(SessionToken),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GameStatus {
+    /// No round has been dealt yet
+    Open,
+    InProgress,
+    Finished,
+}
+
+impl GameStatus {
+    pub fn of(game: &GameState) -> Self {
+        if game.is_game_over() {
+            GameStatus::Finished
+        } else if game.round_state.deck_commitment.is_some() {
+            GameStatus::InProgress
+        } else {
+            GameStatus::Open
+        }
+    }
+}
+
+/// One line of the lobby.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub game_id: GameId,
+    pub join_code: JoinCode,
+    pub variant: String,
+    pub status: GameStatus,
+    pub player_count: usize,
+}
+
+impl GameSummary {
+    pub fn new(game_id: GameId, join_code: JoinCode, game: &GameState) -> Self {
+        Self {
+            game_id,
+            join_code,
+            variant: game
+                .config
+                .variant
+                .clone()
+                .unwrap_or_else(|| DEFAULT_VARIANT.to_string()),
+            status: GameStatus::of(game),
+            player_count: game.players.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let mut game = GameState::new();
+        game.add_player("p1".to_string(), "Alice".to_string());
+        assert_eq!(GameStatus::of(&game), GameStatus::Open);
+
+        game.start_round().unwrap();
+        let summary = GameSummary::new(GameId::new(), JoinCode::random(), &game);
+        assert_eq!(summary.status, GameStatus::InProgress);
+        assert_eq!(summary.player_count, 1);
+        assert_eq!(summary.variant, DEFAULT_VARIANT);
+    }
+}
@@ -0,0 +1,304 @@
+//! Quick play: players queue up without picking a game, and are seated
+//! together in a new game as soon as enough of them are waiting.
+//!
+//! Ranked quick play has a queue of its own, where players are only seated
+//! with others whose rating is within `rating_band` of theirs; see `ratings`.
+
+use crate::{PlayerId, Response};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// How many players quick play seats together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Matchmaking {
+    pub min_players: usize,
+    /// A match is made as soon as this many players are waiting
+    pub max_players: usize,
+    /// How long the first player in the queue waits for a full table before
+    /// settling for `min_players`
+    pub max_wait: Duration,
+    /// How far apart the ratings of players ranked quick play seats together
+    /// may be. It widens by as much again for each `max_wait` a player has
+    /// waited, so nobody waits forever for opponents close enough.
+    pub rating_band: u32,
+}
+
+impl Default for Matchmaking {
+    fn default() -> Self {
+        Self {
+            min_players: 3,
+            max_players: 4,
+            max_wait: Duration::from_secs(30),
+            rating_band: 200,
+        }
+    }
+}
+
+impl Matchmaking {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_players < 2 {
+            return Err("Quick play needs at least 2 players a game".to_string());
+        }
+        if self.rating_band == 0 {
+            return Err("Ranked quick play needs a rating band of at least 1".to_string());
+        }
+        if self.max_players < self.min_players {
+            return Err(format!(
+                "Quick play can't seat at most {} players but at least {}",
+                self.max_players, self.min_players
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A player in the queue.
+#[derive(Debug)]
+pub(crate) struct Waiting {
+    pub(crate) player_id: PlayerId,
+    pub(crate) player_name: String,
+    /// Account a ranked player is rated under, and their rating
+    pub(crate) rated: Option<(String, f64)>,
+    since: Instant,
+    /// Where to send the `MatchFound`, once the client listens for it
+    notify: Option<oneshot::Sender<Response>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Queue {
+    waiting: Vec<Waiting>,
+    /// Matches of players whose client wasn't listening yet
+    unclaimed: HashMap<PlayerId, Response>,
+}
+
+impl Queue {
+    pub(crate) fn join(&mut self, player_name: String, now: Instant) -> PlayerId {
+        self.push(player_name, None, now)
+    }
+
+    /// Queues a ranked player, rated `rating` under `account`.
+    pub(crate) fn join_ranked(&mut self, player_name: String, account: String, rating: f64, now: Instant) -> PlayerId {
+        self.push(player_name, Some((account, rating)), now)
+    }
+
+    fn push(&mut self, player_name: String, rated: Option<(String, f64)>, now: Instant) -> PlayerId {
+        let player_id = PlayerId::new();
+        self.waiting.push(Waiting {
+            player_id,
+            player_name,
+            rated,
+            since: now,
+            notify: None,
+        });
+        player_id
+    }
+
+    pub(crate) fn leave(&mut self, player_id: PlayerId) -> bool {
+        let before = self.waiting.len();
+        self.waiting.retain(|w| w.player_id != player_id);
+        self.waiting.len() < before
+    }
+
+    pub(crate) fn contains(&self, player_id: PlayerId) -> bool {
+        self.waiting.iter().any(|w| w.player_id == player_id)
+    }
+
+    /// Whether a ranked player is waiting under `account`.
+    pub(crate) fn has_account(&self, account: &str) -> bool {
+        self.waiting
+            .iter()
+            .any(|w| w.rated.as_ref().is_some_and(|(rated, _)| rated == account))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Where the player's `MatchFound` will arrive. `None` if they are
+    /// neither queued nor matched.
+    pub(crate) fn listen(&mut self, player_id: PlayerId) -> Option<oneshot::Receiver<Response>> {
+        let (tx, rx) = oneshot::channel();
+        if let Some(found) = self.unclaimed.remove(&player_id) {
+            let _ = tx.send(found);
+            return Some(rx);
+        }
+        let waiting = self.waiting.iter_mut().find(|w| w.player_id == player_id)?;
+        waiting.notify = Some(tx);
+        Some(rx)
+    }
+
+    /// Takes the players of the next match, first come first served: a full
+    /// table, or `min_players` once the longest waiting of them has waited
+    /// `max_wait`. Players whose client stopped listening leave the queue
+    /// first.
+    pub(crate) fn next_match(&mut self, rules: &Matchmaking, now: Instant) -> Option<Vec<Waiting>> {
+        self.drop_gone();
+        let first = self.waiting.first()?;
+        let size = match_size(rules, self.waiting.len(), now.duration_since(first.since))?;
+        Some(self.waiting.drain(..size).collect())
+    }
+
+    /// Takes the players of the next ranked match: the longest waiting player
+    /// who can be matched, with the players after them whose rating is within
+    /// their band, seated as `next_match` would.
+    pub(crate) fn next_ranked_match(&mut self, rules: &Matchmaking, now: Instant) -> Option<Vec<Waiting>> {
+        self.drop_gone();
+        let rating = |waiting: &Waiting| waiting.rated.as_ref().map_or(0.0, |(_, rating)| *rating);
+        for (first, anchor) in self.waiting.iter().enumerate() {
+            let waited = now.duration_since(anchor.since);
+            let widened = 1 + (waited.as_millis() / rules.max_wait.as_millis().max(1)) as u32;
+            let band = f64::from(rules.rating_band.saturating_mul(widened));
+            let close: Vec<usize> = (first..self.waiting.len())
+                .filter(|&i| (rating(&self.waiting[i]) - rating(anchor)).abs() <= band)
+                .collect();
+            let Some(size) = match_size(rules, close.len(), waited) else {
+                continue;
+            };
+            // Taken from the back, so the indices left stay valid
+            let mut players: Vec<Waiting> = close[..size].iter().rev().map(|&i| self.waiting.remove(i)).collect();
+            players.reverse();
+            return Some(players);
+        }
+        None
+    }
+
+    /// Takes players whose client stopped listening out of the queue.
+    fn drop_gone(&mut self) {
+        self.waiting
+            .retain(|w| !w.notify.as_ref().is_some_and(|notify| notify.is_closed()));
+    }
+
+    /// Sends a matched player their `MatchFound`, or keeps it until their
+    /// client listens.
+    pub(crate) fn deliver(&mut self, waiting: Waiting, found: Response) {
+        let found = match waiting.notify {
+            Some(notify) => match notify.send(found) {
+                Ok(()) => return,
+                Err(found) => found,
+            },
+            None => found,
+        };
+        self.unclaimed.insert(waiting.player_id, found);
+    }
+}
+
+/// How many of `available` players to seat, the first of whom has waited
+/// `waited`: a full table, or `min_players` once they waited `max_wait`.
+fn match_size(rules: &Matchmaking, available: usize, waited: Duration) -> Option<usize> {
+    if available >= rules.max_players {
+        Some(rules.max_players)
+    } else if available >= rules.min_players && waited >= rules.max_wait {
+        Some(available)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_match() {
+        let rules = Matchmaking::default();
+        let mut queue = Queue::default();
+        let start = Instant::now();
+        let players: Vec<PlayerId> = (0..5)
+            .map(|i| queue.join(format!("Player {}", i), start))
+            .collect();
+
+        let table = queue.next_match(&rules, start).unwrap();
+        let seated: Vec<PlayerId> = table.iter().map(|w| w.player_id).collect();
+        assert_eq!(seated, players[..4]);
+        assert_eq!(queue.len(), 1);
+
+        // Three players make a game only once the first has waited long enough
+        queue.join("Player 5".to_string(), start);
+        queue.join("Player 6".to_string(), start);
+        assert!(queue.next_match(&rules, start).is_none());
+        let later = start + rules.max_wait;
+        assert_eq!(queue.next_match(&rules, later).unwrap().len(), 3);
+        assert!(queue.next_match(&rules, later).is_none());
+
+        assert!(rules.validate().is_ok());
+        let backwards = Matchmaking {
+            min_players: 4,
+            max_players: 3,
+            ..rules
+        };
+        assert!(backwards.validate().is_err());
+    }
+
+    #[test]
+    fn test_next_ranked_match() {
+        let rules = Matchmaking {
+            min_players: 2,
+            max_players: 3,
+            ..Matchmaking::default()
+        };
+        let mut queue = Queue::default();
+        let start = Instant::now();
+        let mut join = |name: &str, rating: f64| queue.join_ranked(name.to_string(), name.to_string(), rating, start);
+        let outlier = join("Outlier", 2200.0);
+        let ada = join("Ada", 1500.0);
+        let bo = join("Bo", 1650.0);
+        join("Cy", 1420.0);
+        let di = join("Di", 1300.0);
+
+        // The outlier has nobody close enough; Ada, Bo and Cy make a table
+        let table = queue.next_ranked_match(&rules, start).unwrap();
+        let seated: Vec<PlayerId> = table.iter().map(|w| w.player_id).collect();
+        assert_eq!(seated[..2], [ada, bo]);
+        assert_eq!(table[2].rated, Some(("Cy".to_string(), 1420.0)));
+        assert!(queue.next_ranked_match(&rules, start).is_none());
+
+        // The band widens as they wait, until 900 apart is close enough
+        assert!(queue.next_ranked_match(&rules, start + rules.max_wait).is_none());
+        let table = queue.next_ranked_match(&rules, start + rules.max_wait * 4).unwrap();
+        let seated: Vec<PlayerId> = table.iter().map(|w| w.player_id).collect();
+        assert_eq!(seated, [outlier, di]);
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn test_dropped_clients_leave() {
+        let rules = Matchmaking::default();
+        let mut queue = Queue::default();
+        let start = Instant::now();
+        let gone = queue.join("Gone".to_string(), start);
+        drop(queue.listen(gone).unwrap());
+        for i in 0..3 {
+            queue.join(format!("Player {}", i), start);
+        }
+        assert!(queue.next_match(&rules, start).is_none());
+        assert_eq!(queue.len(), 3);
+        assert!(queue.leave(queue.waiting[0].player_id));
+        assert!(!queue.leave(gone));
+    }
+
+    #[test]
+    fn test_deliver() {
+        let mut queue = Queue::default();
+        let start = Instant::now();
+        let early = queue.join("Early".to_string(), start);
+        let late = queue.join("Late".to_string(), start);
+        let mut listening = queue.listen(early).unwrap();
+
+        let rules = Matchmaking {
+            min_players: 2,
+            max_players: 2,
+            ..Matchmaking::default()
+        };
+        for waiting in queue.next_match(&rules, start).unwrap() {
+            queue.deliver(waiting, Response::Pong);
+        }
+        assert!(matches!(listening.try_recv(), Ok(Response::Pong)));
+        // Kept for the client that wasn't listening yet
+        assert!(matches!(
+            queue.listen(late).unwrap().try_recv(),
+            Ok(Response::Pong)
+        ));
+        assert!(queue.listen(late).is_none());
+    }
+}
@@ -0,0 +1,51 @@
+//! Results of recent moves by id, so a client retrying a move it never heard
+//! back about gets the original result instead of moving twice.
+
+use crate::{MoveId, Response};
+use std::collections::{HashMap, VecDeque};
+
+/// Moves remembered per game. Clients only retry their latest move, so a
+/// few rounds of turns is plenty.
+const MOVE_LOG_LEN: usize = 64;
+
+#[derive(Debug, Default)]
+pub(crate) struct MoveLog {
+    /// Oldest first, to know which to forget
+    order: VecDeque<MoveId>,
+    results: HashMap<MoveId, Response>,
+}
+
+impl MoveLog {
+    pub(crate) fn get(&self, move_id: &MoveId) -> Option<&Response> {
+        self.results.get(move_id)
+    }
+
+    pub(crate) fn record(&mut self, move_id: MoveId, result: Response) {
+        if self.results.insert(move_id, result).is_some() {
+            return;
+        }
+        self.order.push_back(move_id);
+        if self.order.len() > MOVE_LOG_LEN {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forgets_oldest() {
+        let mut log = MoveLog::default();
+        let ids: Vec<MoveId> = (0..=MOVE_LOG_LEN).map(|_| MoveId::new()).collect();
+        for id in &ids {
+            log.record(*id, Response::Pong);
+        }
+        assert!(log.get(&ids[0]).is_none());
+        assert!(matches!(log.get(&ids[MOVE_LOG_LEN]), Some(Response::Pong)));
+        assert_eq!(log.order.len(), MOVE_LOG_LEN);
+    }
+}
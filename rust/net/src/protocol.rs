@@ -0,0 +1,3618 @@
+use crate::auth;
+use crate::expiry::{Expiry, ExpiryEvent};
+use crate::handshake;
+use crate::i18n::{text, Text};
+use crate::heartbeat::{Heartbeat, Liveness};
+use crate::leaderboard::{self, Leaderboards, PAGE_SIZE};
+use crate::lobby::{Access, GameStatus, GameSummary, Visibility};
+use crate::matchmaking::{Matchmaking, Queue, Waiting};
+use crate::move_log::MoveLog;
+use crate::ratings::Ratings;
+use crate::rounds::{Intermission, RoundFlow};
+use crate::shard::Place;
+use crate::store::Unsaved;
+use crate::tournament::Tournaments;
+use crate::turn_clock::{AutoPlay, TurnClock, STAND_IN_STRATEGY};
+use crate::webhook::LifecycleEvent;
+use crate::{
+    Emoji, Encoding, GameId, InviteToken, JoinCode, Message, MoveId, PlayerId, Rating, Response, SessionToken,
+    TrustLevel,
+};
+use game_core::bots::{parse_strategy, Strategy};
+use game_core::skip_vote::SkipVoteOutcome;
+use game_core::variant::{VariantRegistry, DEFAULT_VARIANT};
+use game_core::{GameMove, GameState};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, oneshot};
+use tracing::field;
+
+/// Seed of games created by `JoinGame`, as with `GameState::new`.
+const NEW_GAME_SEED: u64 = 42;
+
+/// State updates a slow subscriber can fall behind by. Each update carries the
+/// whole state, so one that lags only misses intermediate states.
+const UPDATE_BACKLOG: usize = 16;
+
+/// Lifecycle events a slow subscriber can fall behind by.
+pub(crate) const LIFECYCLE_BACKLOG: usize = 256;
+
+/// Players one deck serves; games shuffling several decks together seat
+/// that many per deck.
+pub(crate) const SEATS_PER_DECK: usize = 8;
+
+/// Sans-IO core of the game protocol: messages go in, responses come out.
+///
+/// It owns every game but does no networking, locking or async work, so it can
+/// be driven directly in tests, wrapped by `GameServer`, or embedded in a
+/// client that hosts a table itself. Transports only move bytes in and out of
+/// `handle_bytes`.
+#[derive(Default)]
+pub struct ProtocolEngine {
+    pub(crate) games: HashMap<GameId, GameState>,
+    /// When the current turn of each game began, for skip votes
+    turn_started: HashMap<GameId, Instant>,
+    /// Variants new games can be created with
+    pub(crate) variants: VariantRegistry,
+    /// Strategies of the seats the server plays, by player id. They are not
+    /// part of a `ServerSnapshot`, so a handover leaves those seats to time out.
+    bots: HashMap<GameId, HashMap<String, Box<dyn Strategy>>>,
+    /// Strategies playing for the players of each game who run out of time,
+    /// where its turn clock says so. Like bots, not part of a `ServerSnapshot`.
+    stand_ins: HashMap<GameId, Box<dyn Strategy>>,
+    /// Moves bots made that transports haven't picked up yet
+    bot_moves: Vec<(GameId, GameMove)>,
+    /// Where the `StateUpdate` of each followed game is published
+    updates: HashMap<GameId, broadcast::Sender<Response>>,
+    /// Seat each session token was issued for
+    pub(crate) sessions: HashMap<SessionToken, (GameId, PlayerId)>,
+    heartbeat: Heartbeat,
+    liveness: Liveness,
+    /// Games paused until their disconnected players are back; see
+    /// `Heartbeat::pause`
+    paused_for_disconnect: HashSet<GameId>,
+    /// Who can find and join each game
+    pub(crate) visibility: HashMap<GameId, Visibility>,
+    pub(crate) join_codes: HashMap<GameId, JoinCode>,
+    /// Invitations not used yet, and the game each is for
+    pub(crate) invites: HashMap<InviteToken, GameId>,
+    /// Player running each game, who alone may invite, kick and close. The
+    /// first player to join a game hosts it.
+    pub(crate) hosts: HashMap<GameId, PlayerId>,
+    matchmaking: Matchmaking,
+    queue: Queue,
+    /// Players waiting for a ranked game
+    ranked_queue: Queue,
+    /// Account each seat of a ranked game still to be rated is rated under
+    pub(crate) ranked: HashMap<GameId, HashMap<PlayerId, String>>,
+    /// Ratings of the players of ranked games, kept by the engine running
+    /// the queues
+    pub(crate) ratings: Ratings,
+    /// Account each seat of signed-in players plays under, in games that
+    /// have any
+    pub(crate) accounts: HashMap<GameId, HashMap<PlayerId, String>>,
+    /// The boards players are ranked on, shared by every shard of a server
+    pub(crate) leaderboards: Arc<Mutex<Leaderboards>>,
+    /// Results of each game's recent moves that came with an id
+    move_logs: HashMap<GameId, MoveLog>,
+    round_flow: RoundFlow,
+    /// Clock of games created without one
+    turn_clock: Option<TurnClock>,
+    /// The wait after each game's last scored round
+    intermissions: HashMap<GameId, Intermission>,
+    /// Who voted for a rematch of each finished game
+    rematch_votes: HashMap<GameId, HashSet<PlayerId>>,
+    /// The game each finished game was rematched as
+    rematches: HashMap<GameId, GameId>,
+    pub(crate) expiry: Expiry,
+    /// When each game last changed, for its expiry
+    pub(crate) changed: HashMap<GameId, Instant>,
+    /// When each game was created, or first changed in this process
+    pub(crate) created: HashMap<GameId, Instant>,
+    /// Games to save or delete, once a `GameStore` keeps them
+    pub(crate) unsaved: Option<Unsaved>,
+    /// Tournaments being played, by the engine running the queues
+    pub(crate) tournaments: Tournaments,
+    /// Where the engine sits when a `GameServer` spreads games over several
+    shard: Option<Place>,
+    /// Where games being created, started, scored and finished are announced,
+    /// once anyone listens
+    lifecycle: Option<broadcast::Sender<LifecycleEvent>>,
+}
+
+impl ProtocolEngine {
+    pub fn new() -> Self {
+        Self::with_variants(VariantRegistry::builtin())
+    }
+
+    /// An engine whose games can use the given variants, e.g. the built-in
+    /// ones plus some of the embedder's own.
+    pub fn with_variants(variants: VariantRegistry) -> Self {
+        Self {
+            games: HashMap::new(),
+            turn_started: HashMap::new(),
+            variants,
+            bots: HashMap::new(),
+            stand_ins: HashMap::new(),
+            bot_moves: Vec::new(),
+            updates: HashMap::new(),
+            sessions: HashMap::new(),
+            heartbeat: Heartbeat::default(),
+            liveness: Liveness::default(),
+            paused_for_disconnect: HashSet::new(),
+            visibility: HashMap::new(),
+            join_codes: HashMap::new(),
+            invites: HashMap::new(),
+            hosts: HashMap::new(),
+            matchmaking: Matchmaking::default(),
+            queue: Queue::default(),
+            ranked_queue: Queue::default(),
+            ranked: HashMap::new(),
+            ratings: Ratings::default(),
+            accounts: HashMap::new(),
+            leaderboards: Arc::default(),
+            move_logs: HashMap::new(),
+            round_flow: RoundFlow::default(),
+            turn_clock: None,
+            intermissions: HashMap::new(),
+            rematch_votes: HashMap::new(),
+            rematches: HashMap::new(),
+            expiry: Expiry::default(),
+            changed: HashMap::new(),
+            created: HashMap::new(),
+            unsaved: None,
+            tournaments: Tournaments::default(),
+            shard: None,
+            lifecycle: None,
+        }
+    }
+
+    /// An engine for one shard of a `GameServer`, that only creates games
+    /// belonging to it, announcing their lifecycle where the other shards do
+    /// and ranking players on the same boards.
+    pub(crate) fn in_shard(
+        place: Place,
+        lifecycle: broadcast::Sender<LifecycleEvent>,
+        leaderboards: Arc<Mutex<Leaderboards>>,
+    ) -> Self {
+        Self {
+            shard: Some(place),
+            lifecycle: Some(lifecycle),
+            leaderboards,
+            ..Self::new()
+        }
+    }
+
+    /// Where games being created, started, scored and finished from now on
+    /// are announced; see `webhook`.
+    pub fn subscribe_lifecycle(&mut self) -> broadcast::Receiver<LifecycleEvent> {
+        self.lifecycle
+            .get_or_insert_with(|| broadcast::channel(LIFECYCLE_BACKLOG).0)
+            .subscribe()
+    }
+
+    fn announce(&self, event: LifecycleEvent) {
+        if let Some(lifecycle) = &self.lifecycle {
+            // Nobody may be listening
+            let _ = lifecycle.send(event);
+        }
+    }
+
+    /// Handles one message inside a `message` span naming the game and the
+    /// acting player, so everything it causes is traced under them.
+    pub fn handle(&mut self, message: Message) -> Response {
+        let span = message_span(&message);
+        let _entered = span.enter();
+        let response = match message {
+            Message::JoinGame {
+                player_name,
+                game_id,
+                team,
+                variant,
+                code,
+                access,
+            } => self.join_game(player_name, game_id, code, access, team, variant),
+            Message::CreateGame {
+                rules,
+                visibility,
+                turn_clock,
+            } => match self.create_game(rules.as_deref(), visibility, turn_clock) {
+                Ok(game_id) => {
+                    let invite = InviteToken::new();
+                    self.invites.insert(invite, game_id);
+                    Response::GameCreated {
+                        game_id,
+                        join_code: self.join_code(game_id),
+                        invite: Some(invite),
+                    }
+                }
+                Err(message) => Response::Error { message },
+            },
+            Message::ListGames => self.list_games(),
+            Message::StartGame { game_id, host } => {
+                match host.map(|host| self.check_host(game_id, host, text!("host_only_start"))) {
+                    Some(Err(message)) => Response::Error { message },
+                    _ => self.start_game(game_id),
+                }
+            }
+            Message::MakeMove {
+                game_id,
+                game_move,
+                move_id,
+            } => self.make_move_once(game_id, game_move, move_id),
+            Message::GetGameState { game_id, access } => self.get_game_state(game_id, access),
+            Message::Spectate { game_id, access } => match self.check_viewer(game_id, access) {
+                Ok(()) => Response::Spectating {
+                    game_id,
+                    game_state: Box::new(self.games[&game_id].public_view()),
+                },
+                Err(message) => Response::Error { message },
+            },
+            // Connections of a server with an `Auth` sign in themselves
+            Message::Authenticate { .. } => Response::Error {
+                message: text!("no_auth"),
+            },
+            Message::AckState { game_id, state_hash } => self.ack_state(game_id, state_hash),
+            Message::LeaveGame { game_id, player_id } => self.leave_game(game_id, player_id),
+            Message::SyncState {
+                game_id,
+                game_state,
+            } => self.sync_state(game_id, *game_state),
+            Message::Hello {
+                protocol_version,
+                encodings,
+                features,
+                locale,
+            } => handshake::welcome(protocol_version, &encodings, &features, locale.as_deref()),
+            Message::NegotiateEncoding { offered } => Response::EncodingSelected {
+                encoding: Encoding::negotiate(&offered),
+            },
+            Message::VoteSkipTurn { game_id, voter_id } => self.vote_skip_turn(game_id, voter_id),
+            Message::AddBot {
+                game_id,
+                host,
+                difficulty,
+                player_name,
+            } => self.add_bot(game_id, host, &difficulty, player_name),
+            Message::Reconnect { token } => self.reconnect(token),
+            Message::InvitePlayer { game_id, host } => self.invite_player(game_id, host),
+            Message::KickPlayer {
+                game_id,
+                host,
+                player_id,
+            } => self.kick_player(game_id, host, player_id),
+            Message::TransferHost { game_id, host, to } => self.transfer_host(game_id, host, to),
+            Message::CloseGame { game_id, host } => self.close_game(game_id, host),
+            Message::QuickPlay { player_name } => self.quick_play(player_name),
+            Message::RankedQuickPlay { player_name, account } => self.ranked_quick_play(player_name, account),
+            Message::LeaveQueue { player_id } => {
+                if self.queue.leave(player_id) || self.ranked_queue.leave(player_id) {
+                    Response::LeftQueue { player_id }
+                } else {
+                    Response::Error {
+                        message: text!("not_queued"),
+                    }
+                }
+            }
+            Message::RequestRematch { game_id, player_id } => self.request_rematch(game_id, player_id),
+            Message::Ready { game_id, player_id } => self.ready(game_id, player_id),
+            Message::Reaction {
+                game_id,
+                player_id,
+                emoji,
+            } => self.react(game_id, player_id, emoji),
+            Message::GetLeaderboard {
+                board,
+                among,
+                offset,
+                limit,
+            } => {
+                let (entries, total) =
+                    self.leaderboards
+                        .lock()
+                        .unwrap()
+                        .page(&board, &among, offset, limit.unwrap_or(PAGE_SIZE));
+                Response::Leaderboard { board, entries, total }
+            }
+            Message::StartSoloGame { account: Some(account) } => Response::SoloGameStarted {
+                seed: self.leaderboards.lock().unwrap().start_solo(&account),
+            },
+            Message::SubmitSoloGame {
+                player_name,
+                game_state,
+                account: Some(account),
+            } => self.submit_solo_game(player_name, &account, &game_state),
+            // Guests' names are anyone's to take, so they aren't ranked
+            Message::StartSoloGame { account: None } | Message::SubmitSoloGame { account: None, .. } => {
+                Response::Error {
+                    message: text!("sign_in_scores"),
+                }
+            }
+            // Friends are kept for connections signed in to a `GameServer`
+            Message::AddFriend { .. }
+            | Message::RemoveFriend { .. }
+            | Message::ListFriends
+            | Message::InviteFriend { .. } => Response::Error {
+                message: text!("sign_in_friends"),
+            },
+            Message::CreateTournament {
+                name,
+                format,
+                entrants,
+            } => self.create_tournament(name, format, entrants),
+            Message::JoinTournament {
+                tournament_id,
+                player_name,
+                account,
+            } => self.join_tournament(tournament_id, player_name, account),
+            Message::GetTournament { tournament_id } => self.get_tournament(tournament_id),
+            Message::Ping => Response::Pong,
+        };
+
+        if let Some(game_id) = changed_game(&response) {
+            self.publish(game_id);
+        }
+        tracing::debug!(response = response.kind(), "handled");
+        response
+    }
+
+    /// Follows a game: the receiver gets a `StateUpdate` every time the game
+    /// changes, bot moves and timed-out turns included. `None` if there is no
+    /// such game.
+    pub fn subscribe(&mut self, game_id: GameId) -> Option<broadcast::Receiver<Response>> {
+        if !self.games.contains_key(&game_id) {
+            return None;
+        }
+        let updates = self
+            .updates
+            .entry(game_id)
+            .or_insert_with(|| broadcast::channel(UPDATE_BACKLOG).0);
+        Some(updates.subscribe())
+    }
+
+    /// Sends the game's current state to its followers, if it has any.
+    pub(crate) fn publish(&mut self, game_id: GameId) {
+        let Some(game) = self.games.get(&game_id) else {
+            return;
+        };
+        let update = Response::StateUpdate {
+            game_id,
+            game_state: Box::new(game.public_view()),
+        };
+        self.touch(game_id);
+        self.notify(game_id, update);
+        self.announce_round(game_id);
+    }
+
+    /// Notes that the game changed, for its expiry and to save it.
+    fn touch(&mut self, game_id: GameId) {
+        let now = Instant::now();
+        self.changed.insert(game_id, now);
+        self.created.entry(game_id).or_insert(now);
+        if let Some(unsaved) = &mut self.unsaved {
+            unsaved.changed(game_id);
+        }
+    }
+
+    /// Sends the table the result of a round that was just scored, and starts
+    /// waiting for its players to be ready for the next.
+    fn announce_round(&mut self, game_id: GameId) {
+        let Some(game) = self.games.get(&game_id) else {
+            return;
+        };
+        let Some(summary) = game.history.last().filter(|_| game.round_state.is_scored) else {
+            return;
+        };
+        if self
+            .intermissions
+            .get(&game_id)
+            .is_some_and(|intermission| intermission.round_number == summary.round_number)
+        {
+            return;
+        }
+        let result = Response::RoundResult {
+            game_id,
+            summary: summary.clone(),
+            game_over: game.is_game_over(),
+            deck_reveal: game.deck_reveal().map(Box::new),
+        };
+        self.announce(LifecycleEvent::RoundFinished {
+            game_id,
+            summary: summary.clone(),
+        });
+        let game_over = game.is_game_over();
+        if game_over {
+            self.announce(LifecycleEvent::over(game_id, game));
+        }
+        self.intermissions
+            .insert(game_id, Intermission::new(summary.round_number, Instant::now()));
+        self.notify(game_id, result);
+        if game_over {
+            self.count_wins(game_id);
+            self.rate_game(game_id);
+            if self.tournaments.plays(game_id) {
+                let game = self.games[&game_id].clone();
+                self.tournament_game_over(game_id, &game);
+            }
+        }
+    }
+
+    /// The account a seat plays under: its player's, if signed in, or else
+    /// their name.
+    fn account_of(&self, game_id: GameId, player_id: &str) -> Option<&String> {
+        self.accounts.get(&game_id)?.get(&player_id.parse().ok()?)
+    }
+
+    /// Adds a game that just ended to its signed-in winners' wins. Only
+    /// games between people count: bots and guests never win, and a game
+    /// needs two players who didn't leave, neither of them a bot.
+    fn count_wins(&mut self, game_id: GameId) {
+        let Some(game) = self.games.get(&game_id) else {
+            return;
+        };
+        let bots = self.bots.get(&game_id);
+        let is_bot = |player_id: &String| bots.is_some_and(|bots| bots.contains_key(player_id));
+        if game.players.iter().filter(|p| !is_bot(&p.id)).count() < 2 {
+            return;
+        }
+        let mut winners = HashMap::new();
+        for winner in game.outcome.iter().flat_map(|outcome| &outcome.winner_ids) {
+            if let (Some(account), Some(player)) = (self.account_of(game_id, winner), game.player(winner)) {
+                winners.entry(account).or_insert(&player.name);
+            }
+        }
+        let mut leaderboards = self.leaderboards.lock().unwrap();
+        for (account, name) in winners {
+            leaderboards.won(account, name);
+        }
+    }
+
+    /// Rates the players of a ranked game that just ended. Players who left
+    /// it before the end lose to everyone who stayed.
+    fn rate_game(&mut self, game_id: GameId) {
+        let (Some(game), Some(accounts)) = (self.games.get(&game_id), self.ranked.remove(&game_id)) else {
+            return;
+        };
+        let winners = game.outcome.as_ref().map(|outcome| &outcome.winner_ids);
+        let mut results: Vec<(String, u32, bool)> = accounts
+            .iter()
+            .map(|(player_id, account)| {
+                let player_id = player_id.to_string();
+                let score = game.player(&player_id).map_or(0, |player| player.score);
+                let won = winners.is_some_and(|winners| winners.contains(&player_id));
+                (account.clone(), score, won)
+            })
+            .collect();
+        results.sort();
+        self.ratings.record(&results);
+
+        let mut leaderboards = self.leaderboards.lock().unwrap();
+        for (player_id, account) in &accounts {
+            let name = game.player(&player_id.to_string()).map_or(account.as_str(), |player| &player.name);
+            leaderboards.rated(account, name, self.ratings.get(account).rating);
+        }
+    }
+
+    /// Ranks a finished solo game the player submitted; see `leaderboard`.
+    fn submit_solo_game(&mut self, player_name: String, account: &str, game: &GameState) -> Response {
+        match leaderboard::check_solo_game(game) {
+            Ok(score) => {
+                let mut leaderboards = self.leaderboards.lock().unwrap();
+                // Players could otherwise try seeds until one dealt them well
+                if score.date.is_none() && !leaderboards.finish_solo(account, game.seed) {
+                    return Response::Error {
+                        message: text!("solo_not_dealt"),
+                    };
+                }
+                let best_score = leaderboards.played_solo(account, &player_name, &score);
+                Response::SoloScoreRecorded {
+                    total_score: score.total_score,
+                    best_score,
+                }
+            }
+            Err(message) => Response::Error { message },
+        }
+    }
+
+    /// The rating of a player of ranked games; see `ratings`.
+    pub fn rating(&self, account: &str) -> Rating {
+        self.ratings.get(account)
+    }
+
+    /// Notes the account a signed-in player plays a seat under, for the
+    /// leaderboards.
+    pub(crate) fn set_account(&mut self, game_id: GameId, player_id: PlayerId, account: String) {
+        if self.games.contains_key(&game_id) {
+            self.accounts.entry(game_id).or_default().insert(player_id, account);
+        }
+    }
+
+    /// The players a game between rounds waits on before the next: everyone
+    /// still playing, except bots and disconnected players. `None` if the
+    /// game isn't between rounds, or is over.
+    fn between_rounds(&self, game_id: GameId) -> Option<Vec<PlayerId>> {
+        let game = self.games.get(&game_id)?;
+        let intermission = self.intermissions.get(&game_id)?;
+        let scored = game.history.last().map(|summary| summary.round_number);
+        if !game.round_state.is_scored || game.is_game_over() || scored != Some(intermission.round_number) {
+            return None;
+        }
+        let eliminated: Vec<PlayerId> = game
+            .players
+            .iter()
+            .filter(|p| p.eliminated)
+            .filter_map(|p| protocol_id(&p.id))
+            .collect();
+        let mut waiting_on = self.deciding_players(game_id);
+        waiting_on.retain(|player_id| !eliminated.contains(player_id));
+        Some(waiting_on)
+    }
+
+    fn ready(&mut self, game_id: GameId, player_id: PlayerId) -> Response {
+        if !self.is_seated(game_id, player_id) {
+            return Response::Error {
+                message: text!("table_only_ready"),
+            };
+        }
+        let (Some(waiting_on), Some(intermission)) =
+            (self.between_rounds(game_id), self.intermissions.get_mut(&game_id))
+        else {
+            return Response::Error {
+                message: text!("no_round_waiting"),
+            };
+        };
+
+        intermission.ready(player_id);
+        let ready = intermission.ready_count(&waiting_on);
+        let recorded = Response::ReadyRecorded {
+            game_id,
+            player_id,
+            ready,
+            needed: waiting_on.len(),
+        };
+        self.notify(game_id, recorded.clone());
+        if ready == waiting_on.len() {
+            self.next_round(game_id);
+        }
+        recorded
+    }
+
+    /// The players still at a game's table who have a say in what happens
+    /// next: everyone but bots and disconnected players.
+    fn deciding_players(&self, game_id: GameId) -> Vec<PlayerId> {
+        let Some(game) = self.games.get(&game_id) else {
+            return Vec::new();
+        };
+        game.players
+            .iter()
+            .filter(|p| !self.liveness.is_disconnected(&p.id))
+            .filter_map(|p| protocol_id(&p.id))
+            .filter(|player_id| !self.is_bot(game_id, *player_id))
+            .collect()
+    }
+
+    fn request_rematch(&mut self, game_id: GameId, player_id: PlayerId) -> Response {
+        let Some(game) = self.games.get(&game_id) else {
+            return Response::Error {
+                message: text!("game_not_found"),
+            };
+        };
+        if !self.is_seated(game_id, player_id) {
+            return Response::Error {
+                message: text!("table_only_rematch"),
+            };
+        }
+        if self.tournaments.plays(game_id) {
+            return Response::Error {
+                message: text!("tournament_rematch"),
+            };
+        }
+        if !game.is_game_over() {
+            return Response::Error {
+                message: text!("game_not_over"),
+            };
+        }
+        // Late votes are pointed at the rematch already under way
+        if let Some(&new_game_id) = self.rematches.get(&game_id) {
+            return Response::RematchStarted { game_id, new_game_id };
+        }
+
+        let deciding = self.deciding_players(game_id);
+        let votes = self.rematch_votes.entry(game_id).or_default();
+        votes.insert(player_id);
+        let votes = deciding.iter().filter(|p| votes.contains(p)).count();
+        let voted = Response::RematchVoted {
+            game_id,
+            player_id,
+            votes,
+            needed: deciding.len(),
+        };
+        self.notify(game_id, voted.clone());
+        if votes < deciding.len() {
+            return voted;
+        }
+
+        match self.start_rematch(game_id) {
+            Ok(new_game_id) => {
+                let started = Response::RematchStarted { game_id, new_game_id };
+                self.notify(game_id, started.clone());
+                // Everyone asked for it, so nobody is left to wait on
+                self.next_round(new_game_id);
+                started
+            }
+            Err(message) => Response::Error { message },
+        }
+    }
+
+    /// Sets up a new game with the seats, rules, host and visibility of a
+    /// finished one. Its bots, session tokens and followers move over to it.
+    fn start_rematch(&mut self, game_id: GameId) -> Result<GameId, Text> {
+        let old = self.games.get(&game_id).ok_or(text!("game_not_found"))?.clone();
+        let visibility = self.visibility.get(&game_id).cloned().unwrap_or_default();
+        let new_game_id = self.create_game(old.config.variant.as_deref(), visibility, None)?;
+
+        let game = self.games.get_mut(&new_game_id).expect("game was just created");
+        // Handicaps are in the config, so it comes before the players
+        game.config = old.config.clone();
+        for player in &old.players {
+            game.add_player(player.id.clone(), player.name.clone());
+            game.set_team(&player.id, player.team).expect("player was just added");
+        }
+
+        if let Some(bots) = self.bots.remove(&game_id) {
+            self.bots.insert(new_game_id, bots);
+        }
+        if let Some(stand_in) = self.stand_ins.remove(&game_id) {
+            self.stand_ins.insert(new_game_id, stand_in);
+        }
+        for seat in self.sessions.values_mut() {
+            if seat.0 == game_id {
+                seat.0 = new_game_id;
+            }
+        }
+        if let Some(accounts) = self.accounts.get(&game_id) {
+            self.accounts.insert(new_game_id, accounts.clone());
+        }
+        if let Some(&host) = self.hosts.get(&game_id) {
+            self.hosts.insert(new_game_id, host);
+        }
+        // Whoever follows the old game follows the rematch
+        if let Some(updates) = self.updates.get(&game_id) {
+            self.updates.insert(new_game_id, updates.clone());
+        }
+        self.rematch_votes.remove(&game_id);
+        self.rematches.insert(game_id, new_game_id);
+        Ok(new_game_id)
+    }
+
+    /// Starts the next round of every game whose players are all ready, or
+    /// have been waited on for `RoundFlow::ready_timeout`. Returns those games.
+    pub fn check_rounds(&mut self) -> Vec<GameId> {
+        let now = Instant::now();
+        let mut due: Vec<GameId> = self
+            .intermissions
+            .iter()
+            .filter(|(game_id, intermission)| {
+                self.between_rounds(**game_id)
+                    .is_some_and(|waiting_on| intermission.is_over(&waiting_on, &self.round_flow, now))
+            })
+            .map(|(game_id, _)| *game_id)
+            .collect();
+        due.sort();
+        due.retain(|game_id| self.next_round(*game_id));
+        due
+    }
+
+    /// Starts the game's next round and tells its followers. False if it
+    /// can't start, e.g. because the game is paused.
+    fn next_round(&mut self, game_id: GameId) -> bool {
+        let started = matches!(self.start_game(game_id), Response::GameStarted { .. });
+        if started {
+            self.publish(game_id);
+        }
+        started
+    }
+
+    pub fn set_round_flow(&mut self, round_flow: RoundFlow) {
+        self.round_flow = round_flow;
+    }
+
+    /// Gives games created from now on without a turn clock this one.
+    pub fn set_turn_clock(&mut self, turn_clock: Option<TurnClock>) -> Result<(), String> {
+        if let Some(clock) = &turn_clock {
+            clock.validate().map_err(String::from)?;
+        }
+        self.turn_clock = turn_clock;
+        Ok(())
+    }
+
+    /// Drops the games that went stale; see `expiry`. Their followers are
+    /// told the game was closed.
+    pub fn sweep_expired(&mut self) -> Vec<ExpiryEvent> {
+        let now = Instant::now();
+        let mut expired: Vec<_> = self
+            .games
+            .iter()
+            .filter_map(|(game_id, game)| {
+                // Games imported from another process count from when the sweep first sees them
+                let changed = self.changed.get(game_id).copied().unwrap_or(now);
+                let reason = self.expiry.reason(game, now.duration_since(changed))?;
+                Some((*game_id, reason))
+            })
+            .collect();
+        expired.sort_by_key(|(game_id, _)| *game_id);
+        for game_id in self.games.keys() {
+            self.changed.entry(*game_id).or_insert(now);
+        }
+        self.sweep_tournaments(now);
+
+        expired
+            .into_iter()
+            .filter_map(|(game_id, reason)| {
+                self.notify(game_id, Response::GameClosed { game_id });
+                let game_state = self.forget_game(game_id)?;
+                Some(ExpiryEvent {
+                    game_id,
+                    reason,
+                    game_state: Box::new(game_state),
+                })
+            })
+            .collect()
+    }
+
+    pub fn set_expiry(&mut self, expiry: Expiry) {
+        self.expiry = expiry;
+    }
+
+    /// Sends a response to the game's followers, if it has any.
+    pub(crate) fn notify(&mut self, game_id: GameId, response: Response) {
+        let Some(updates) = self.updates.get(&game_id) else {
+            return;
+        };
+        tracing::debug!(%game_id, response = response.kind(), followers = updates.receiver_count(), "broadcast");
+        if updates.send(response).is_err() {
+            // Everyone stopped following
+            self.updates.remove(&game_id);
+        }
+    }
+
+    pub fn set_heartbeat(&mut self, heartbeat: Heartbeat) {
+        self.heartbeat = heartbeat;
+    }
+
+    /// Records that a player's client was heard from. Transports call this
+    /// for every message, and only players seen this way can be marked
+    /// disconnected. A game paused for its disconnected players is resumed
+    /// once the last of them is back.
+    pub fn seen(&mut self, game_id: GameId, player_id: PlayerId) {
+        if !self.liveness.seen(game_id, player_id, Instant::now()) {
+            return;
+        }
+        self.notify(game_id, Response::PlayerReconnected { game_id, player_id });
+        let everyone_back = self
+            .games
+            .get(&game_id)
+            .is_some_and(|game| !self.liveness.any_disconnected(game.players.iter().map(|p| p.id.as_str())));
+        if everyone_back && self.paused_for_disconnect.contains(&game_id) {
+            // Still paused: resuming by hand takes it off the set
+            let _ = self.resume_game(game_id);
+        }
+    }
+
+    /// Marks players who missed too many heartbeats as disconnected, telling
+    /// their games, and pauses their games or stays for disconnected players
+    /// whose turn it is if the `Heartbeat` says so. Returns the newly
+    /// disconnected players.
+    pub fn check_heartbeats(&mut self) -> Vec<(GameId, PlayerId)> {
+        let gone = self.liveness.check(self.heartbeat.timeout(), Instant::now());
+        for &(game_id, player_id) in &gone {
+            self.notify(game_id, Response::PlayerDisconnected { game_id, player_id });
+            if self.heartbeat.pause && self.pause_for_disconnect(game_id) {
+                self.paused_for_disconnect.insert(game_id);
+            }
+        }
+
+        if self.heartbeat.auto_stay {
+            let game_ids: Vec<GameId> = self.games.keys().copied().collect();
+            for game_id in game_ids {
+                if self.stay_for_disconnected(game_id) {
+                    self.turn_started.insert(game_id, Instant::now());
+                    self.play_bots(game_id);
+                    self.publish(game_id);
+                }
+            }
+        }
+        gone
+    }
+
+    pub fn set_matchmaking(&mut self, matchmaking: Matchmaking) -> Result<(), String> {
+        matchmaking.validate()?;
+        self.matchmaking = matchmaking;
+        Ok(())
+    }
+
+    /// Where the `MatchFound` of a player `QuickPlay` queued will arrive,
+    /// even if the match was made already. `None` if the player is neither
+    /// queued nor waiting to hear of their match. Dropping the receiver takes
+    /// the player out of the queue.
+    pub fn wait_for_match(&mut self, player_id: PlayerId) -> Option<oneshot::Receiver<Response>> {
+        self.queue
+            .listen(player_id)
+            .or_else(|| self.ranked_queue.listen(player_id))
+    }
+
+    /// Starts the games of quick play players who have waited long enough to
+    /// settle for a smaller table. Returns the ids of the new games.
+    pub fn check_queue(&mut self) -> Vec<GameId> {
+        let mut game_ids = Vec::new();
+        for (waiting, found) in self.make_matches() {
+            if let Response::MatchFound { game_id, .. } = &found {
+                if !game_ids.contains(game_id) {
+                    game_ids.push(*game_id);
+                }
+            }
+            self.deliver(waiting, found);
+        }
+        game_ids
+    }
+
+    fn quick_play(&mut self, player_name: String) -> Response {
+        let player_id = self.queue.join(player_name, Instant::now());
+        self.queued(player_id)
+    }
+
+    /// Queues a signed-in player for a ranked game. Ratings are kept by
+    /// account, so guests, whose names anyone can take, aren't rated, and an
+    /// account waits in the queue once, never to be matched with itself.
+    fn ranked_quick_play(&mut self, player_name: String, account: Option<String>) -> Response {
+        let Some(account) = account else {
+            return Response::Error {
+                message: text!("sign_in_ranked"),
+            };
+        };
+        if self.ranked_queue.has_account(&account) {
+            return Response::Error {
+                message: text!("ranked_queued"),
+            };
+        }
+        let rating = self.ratings.get(&account).rating;
+        let player_id = self
+            .ranked_queue
+            .join_ranked(player_name, account, rating, Instant::now());
+        self.queued(player_id)
+    }
+
+    /// The answer to a player who just queued: their `MatchFound` if the
+    /// queue could match them at once, or else where they stand.
+    fn queued(&mut self, player_id: PlayerId) -> Response {
+        let mut own = None;
+        for (waiting, found) in self.make_matches() {
+            if waiting.player_id == player_id {
+                own = Some(found);
+            } else {
+                self.deliver(waiting, found);
+            }
+        }
+        let queue = if self.ranked_queue.contains(player_id) {
+            &self.ranked_queue
+        } else {
+            &self.queue
+        };
+        own.unwrap_or(Response::Queued {
+            player_id,
+            players_waiting: queue.len(),
+        })
+    }
+
+    /// Sends a matched player their `MatchFound` through the queue they
+    /// waited in.
+    fn deliver(&mut self, waiting: Waiting, found: Response) {
+        match waiting.rated {
+            Some(_) => self.ranked_queue.deliver(waiting, found),
+            None => self.queue.deliver(waiting, found),
+        }
+    }
+
+    /// Seats every match the queues can make in a new game and starts it.
+    /// Returns each matched player with their `MatchFound`.
+    fn make_matches(&mut self) -> Vec<(Waiting, Response)> {
+        let now = Instant::now();
+        let mut tables = Vec::new();
+        while let Some(players) = self.queue.next_match(&self.matchmaking, now) {
+            tables.push(players);
+        }
+        while let Some(players) = self.ranked_queue.next_ranked_match(&self.matchmaking, now) {
+            tables.push(players);
+        }
+
+        let mut matched = Vec::new();
+        for players in tables {
+            let game_id = self
+                .create_game(None, Visibility::default(), None)
+                .expect("the default variant is always registered");
+            let game = self.games.get_mut(&game_id).expect("game was just created");
+            for waiting in &players {
+                game.add_player(waiting.player_id.to_string(), waiting.player_name.clone());
+            }
+            if let Response::Error { message } = self.start_game(game_id) {
+                unreachable!("a new game of {} players can start: {}", players.len(), message);
+            }
+
+            self.hosts.insert(game_id, players[0].player_id);
+            let accounts: HashMap<PlayerId, String> = players
+                .iter()
+                .filter_map(|waiting| Some((waiting.player_id, waiting.rated.as_ref()?.0.clone())))
+                .collect();
+            if !accounts.is_empty() {
+                self.ranked.insert(game_id, accounts);
+            }
+            let join_code = self.join_code(game_id);
+            let game_state = Box::new(self.games[&game_id].public_view());
+            for waiting in players {
+                let session_token = SessionToken::new();
+                self.sessions.insert(session_token, (game_id, waiting.player_id));
+                let found = Response::MatchFound {
+                    game_id,
+                    join_code: join_code.clone(),
+                    player_id: waiting.player_id,
+                    session_token,
+                    game_state: game_state.clone(),
+                };
+                matched.push((waiting, found));
+            }
+        }
+        matched
+    }
+
+    /// Pauses the game if a round of it is under way and it isn't paused
+    /// already. True if it was paused.
+    fn pause_for_disconnect(&mut self, game_id: GameId) -> bool {
+        let under_way = self
+            .games
+            .get(&game_id)
+            .is_some_and(|game| game.round_state.round_number > 0 && !game.round_state.is_finished);
+        under_way && self.pause_game(game_id).is_ok()
+    }
+
+    /// Stays for disconnected players for as long as one is up. True if any
+    /// move was made.
+    fn stay_for_disconnected(&mut self, game_id: GameId) -> bool {
+        let Some(game) = self.games.get_mut(&game_id) else {
+            return false;
+        };
+        let mut stayed = false;
+        while let Some(player) = game
+            .current_player()
+            .filter(|p| self.liveness.is_disconnected(&p.id))
+        {
+            let stay = GameMove::Stay {
+                player_id: player.id.clone(),
+            };
+            if !game.legal_moves(&player.id).contains(&stay) {
+                break;
+            }
+            // Staying is legal, and scoring a finished round can't fail
+            let _ = game
+                .make_move_at(stay, now_ms())
+                .and_then(|()| score_if_finished(game));
+            stayed = true;
+        }
+        stayed
+    }
+
+    /// Handles a message received over a connection with the given trust level,
+    /// rejecting message types that level is not allowed to send.
+    pub fn handle_with_trust(&mut self, trust: TrustLevel, message: Message) -> Response {
+        if !trust.permits(&message) {
+            return Response::Error {
+                message: text!("not_permitted", format!("{:?}", trust)),
+            };
+        }
+
+        self.handle(message)
+    }
+
+    fn join_game(
+        &mut self,
+        player_name: String,
+        game_id: Option<GameId>,
+        code: Option<JoinCode>,
+        access: Option<Access>,
+        team: Option<u8>,
+        variant: Option<String>,
+    ) -> Response {
+        let game_id = match code {
+            Some(code) => match self.game_with_code(&code) {
+                Some(id) if game_id.is_none_or(|game_id| game_id == id) => Some(id),
+                Some(_) => {
+                    return Response::Error {
+                        message: text!("join_code_other_game", code),
+                    }
+                }
+                None => {
+                    return Response::Error {
+                        message: text!("no_join_code", code),
+                    }
+                }
+            },
+            None => game_id,
+        };
+
+        let (game_id, game) = if let Some(id) = game_id {
+            let Some(game) = self.games.get(&id) else {
+                return Response::Error {
+                    message: text!("game_not_found"),
+                };
+            };
+            if self.tournaments.plays(id) {
+                return Response::Error {
+                    message: text!("tournament_pairing_only"),
+                };
+            }
+            if GameStatus::of(game) != GameStatus::Open {
+                return Response::Error {
+                    message: text!("game_started"),
+                };
+            }
+            if is_full(game) {
+                return Response::Error {
+                    message: text!("game_full"),
+                };
+            }
+            let playing = game.config.variant.as_deref().unwrap_or(DEFAULT_VARIANT);
+            if variant.as_deref().is_some_and(|wanted| wanted != playing) {
+                return Response::Error {
+                    message: text!("wrong_variant", playing),
+                };
+            }
+            if let Err(message) = self.admit(id, access) {
+                return Response::Error { message };
+            }
+            (id, self.games.get_mut(&id).unwrap())
+        } else {
+            let id = match self.create_game(variant.as_deref(), Visibility::default(), None) {
+                Ok(id) => id,
+                Err(message) => return Response::Error { message },
+            };
+            (id, self.games.get_mut(&id).unwrap())
+        };
+
+        let player_id = PlayerId::new();
+        game.add_player(player_id.to_string(), player_name);
+        game.set_team(&player_id.to_string(), team).expect("player was just added");
+        let engine_rules_version = game.engine_rules_version;
+
+        let session_token = SessionToken::new();
+        self.sessions.insert(session_token, (game_id, player_id));
+        self.hosts.entry(game_id).or_insert(player_id);
+        Response::GameJoined {
+            game_id,
+            join_code: self.join_code(game_id),
+            player_id,
+            engine_rules_version,
+            session_token: Some(session_token),
+        }
+    }
+
+    /// Checks that whoever joins the game with `access` may do so, using up
+    /// their invitation if they came with one. A game keeps its visibility
+    /// with or without a host, so whoever hosts it next was let in too.
+    fn admit(&mut self, game_id: GameId, access: Option<Access>) -> Result<(), Text> {
+        if let Some(Access::Invite(invite)) = &access {
+            if self.invites.get(invite) != Some(&game_id) {
+                return Err(text!("invalid_invitation"));
+            }
+            self.invites.remove(invite);
+            return Ok(());
+        }
+        self.check_password(game_id, access)
+    }
+
+    /// Checks that whoever watches the game or asks for its state with
+    /// `access` may see it: those who could join it, and its players.
+    fn check_viewer(&self, game_id: GameId, access: Option<Access>) -> Result<(), Text> {
+        if !self.games.contains_key(&game_id) {
+            return Err(text!("game_not_found"));
+        }
+        match &access {
+            Some(Access::Invite(invite)) if self.invites.get(invite) == Some(&game_id) => Ok(()),
+            Some(Access::Seat(token)) if self.sessions.get(token).is_some_and(|seat| seat.0 == game_id) => Ok(()),
+            _ => self.check_password(game_id, access),
+        }
+    }
+
+    /// Checks `access` against the game's visibility, invitations aside.
+    fn check_password(&self, game_id: GameId, access: Option<Access>) -> Result<(), Text> {
+        match self.visibility.get(&game_id).cloned().unwrap_or_default() {
+            Visibility::Public | Visibility::Private { password: None } => Ok(()),
+            Visibility::Private {
+                password: Some(password),
+            } => match access {
+                Some(Access::Password(given)) if given == password => Ok(()),
+                Some(Access::Password(_)) => Err(text!("wrong_password")),
+                _ => Err(text!("password_needed")),
+            },
+            Visibility::InviteOnly => Err(text!("invite_only")),
+        }
+    }
+
+    /// Checks that `host` is the session token of the game's host, and
+    /// returns the host. Anyone else is refused with `denied`.
+    fn check_host(&self, game_id: GameId, host: SessionToken, denied: Text) -> Result<PlayerId, Text> {
+        if !self.games.contains_key(&game_id) {
+            return Err(text!("game_not_found"));
+        }
+        match (self.sessions.get(&host), self.hosts.get(&game_id)) {
+            (Some(&(seat_game, player_id)), Some(&host_id)) if seat_game == game_id && player_id == host_id => {
+                Ok(host_id)
+            }
+            _ => Err(denied),
+        }
+    }
+
+    pub(crate) fn is_seated(&self, game_id: GameId, player_id: PlayerId) -> bool {
+        self.games
+            .get(&game_id)
+            .is_some_and(|game| game.players.iter().any(|p| p.id == player_id.to_string()))
+    }
+
+    fn is_bot(&self, game_id: GameId, player_id: PlayerId) -> bool {
+        self.bots
+            .get(&game_id)
+            .is_some_and(|bots| bots.contains_key(&player_id.to_string()))
+    }
+
+    /// Issues an invitation to the game, which only its host may do.
+    fn invite_player(&mut self, game_id: GameId, host: SessionToken) -> Response {
+        if let Err(message) = self.check_host(game_id, host, text!("host_only_invite")) {
+            return Response::Error { message };
+        }
+
+        let invite = InviteToken::new();
+        self.invites.insert(invite, game_id);
+        Response::PlayerInvited { game_id, invite }
+    }
+
+    fn react(&mut self, game_id: GameId, player_id: PlayerId, emoji: Emoji) -> Response {
+        if !self.is_seated(game_id, player_id) {
+            return Response::Error {
+                message: text!("table_only_react"),
+            };
+        }
+        let reacted = Response::Reacted {
+            game_id,
+            player_id,
+            emoji,
+        };
+        self.notify(game_id, reacted.clone());
+        reacted
+    }
+
+    /// Removes a player from the game on the host's say-so.
+    fn kick_player(&mut self, game_id: GameId, host: SessionToken, player_id: PlayerId) -> Response {
+        let host_id = match self.check_host(game_id, host, text!("host_only_kick")) {
+            Ok(host_id) => host_id,
+            Err(message) => return Response::Error { message },
+        };
+        if player_id == host_id {
+            return Response::Error {
+                message: text!("host_kicks_self"),
+            };
+        }
+        if !self.is_seated(game_id, player_id) {
+            return Response::Error {
+                message: text!("no_such_player"),
+            };
+        }
+
+        self.leave_game(game_id, player_id);
+        self.notify(game_id, Response::PlayerKicked { game_id, player_id });
+        Response::PlayerKicked { game_id, player_id }
+    }
+
+    fn transfer_host(&mut self, game_id: GameId, host: SessionToken, to: PlayerId) -> Response {
+        if let Err(message) = self.check_host(game_id, host, text!("host_only_transfer")) {
+            return Response::Error { message };
+        }
+        if !self.is_seated(game_id, to) || self.is_bot(game_id, to) {
+            return Response::Error {
+                message: text!("host_not_seated"),
+            };
+        }
+
+        self.hosts.insert(game_id, to);
+        self.notify(game_id, Response::HostTransferred { game_id, host: to });
+        Response::HostTransferred { game_id, host: to }
+    }
+
+    /// Ends the game for everyone and forgets it, its seats and its codes.
+    fn close_game(&mut self, game_id: GameId, host: SessionToken) -> Response {
+        if let Err(message) = self.check_host(game_id, host, text!("host_only_close")) {
+            return Response::Error { message };
+        }
+
+        self.notify(game_id, Response::GameClosed { game_id });
+        self.forget_game(game_id);
+        Response::GameClosed { game_id }
+    }
+
+    /// Forgets a game, its seats and its codes, returning its last state.
+    fn forget_game(&mut self, game_id: GameId) -> Option<GameState> {
+        // Dropping the sender ends every follower's subscription
+        self.updates.remove(&game_id);
+        let game = self.games.remove(&game_id);
+        for player_id in game.iter().flat_map(|game| &game.players).filter_map(|p| p.id.parse().ok()) {
+            self.liveness.forget(player_id);
+        }
+        self.turn_started.remove(&game_id);
+        self.paused_for_disconnect.remove(&game_id);
+        self.bots.remove(&game_id);
+        self.stand_ins.remove(&game_id);
+        self.bot_moves.retain(|(id, _)| *id != game_id);
+        self.sessions.retain(|_, (id, _)| *id != game_id);
+        self.visibility.remove(&game_id);
+        if let Some(code) = self.join_codes.remove(&game_id) {
+            self.release_code(&code);
+        }
+        self.invites.retain(|_, id| *id != game_id);
+        self.hosts.remove(&game_id);
+        self.move_logs.remove(&game_id);
+        self.intermissions.remove(&game_id);
+        self.rematch_votes.remove(&game_id);
+        self.rematches.remove(&game_id);
+        self.ranked.remove(&game_id);
+        self.accounts.remove(&game_id);
+        self.changed.remove(&game_id);
+        self.created.remove(&game_id);
+        if let Some(unsaved) = &mut self.unsaved {
+            unsaved.forgotten(game_id);
+        }
+        if let Some(game) = &game {
+            self.forget_tournament_game(game_id, game);
+        }
+        game
+    }
+
+    pub(crate) fn create_game(
+        &mut self,
+        variant: Option<&str>,
+        visibility: Visibility,
+        turn_clock: Option<TurnClock>,
+    ) -> Result<GameId, Text> {
+        let variant = variant.unwrap_or(DEFAULT_VARIANT);
+        let mut game = self
+            .variants
+            .new_game(variant, NEW_GAME_SEED)
+            .map_err(|_| self.unknown_variant(variant))?;
+        let stand_in = match turn_clock.or(self.turn_clock) {
+            Some(clock) => {
+                clock.validate()?;
+                game.config.turn_time_limit_ms = Some(clock.limit_ms());
+                match clock.auto_play {
+                    AutoPlay::Stay => None,
+                    AutoPlay::BotMove => Some(parse_strategy(STAND_IN_STRATEGY).expect("the stand-in is a strategy")),
+                }
+            }
+            None => None,
+        };
+        let game_id = loop {
+            let game_id = GameId::new();
+            if self.shard.as_ref().is_none_or(|place| place.owns(game_id)) {
+                break game_id;
+            }
+        };
+        self.games.insert(game_id, game);
+        self.touch(game_id);
+        self.visibility.insert(game_id, visibility);
+        if let Some(stand_in) = stand_in {
+            self.stand_ins.insert(game_id, stand_in);
+        }
+        self.join_code(game_id);
+        self.announce(LifecycleEvent::GameCreated {
+            game_id,
+            variant: variant.to_string(),
+        });
+        Ok(game_id)
+    }
+
+    fn unknown_variant(&self, variant: &str) -> Text {
+        let known = self.variants.names().collect::<Vec<_>>().join(", ");
+        text!("unknown_variant", variant, known)
+    }
+
+    /// The game's join code, drawing one if it has none yet.
+    pub(crate) fn join_code(&mut self, game_id: GameId) -> JoinCode {
+        if let Some(code) = self.join_codes.get(&game_id) {
+            return code.clone();
+        }
+        let code = loop {
+            let code = JoinCode::random();
+            if self.game_with_code(&code).is_none() && self.claim_code(&code) {
+                break code;
+            }
+        };
+        self.join_codes.insert(game_id, code.clone());
+        code
+    }
+
+    /// Takes a code on behalf of the other shards, if any. False if a game
+    /// on another shard has it.
+    pub(crate) fn claim_code(&self, code: &JoinCode) -> bool {
+        self.shard.as_ref().is_none_or(|place| place.claim(code))
+    }
+
+    fn release_code(&self, code: &JoinCode) {
+        if let Some(place) = &self.shard {
+            place.release(code);
+        }
+    }
+
+    pub(crate) fn game_with_code(&self, code: &JoinCode) -> Option<GameId> {
+        self.join_codes
+            .iter()
+            .find(|(_, c)| *c == code)
+            .map(|(game_id, _)| *game_id)
+    }
+
+    pub(crate) fn list_games(&self) -> Response {
+        let mut games: Vec<GameSummary> = self
+            .visibility
+            .iter()
+            .filter(|(_, visibility)| **visibility == Visibility::Public)
+            .filter_map(|(game_id, _)| {
+                let code = self.join_codes.get(game_id)?.clone();
+                Some(GameSummary::new(*game_id, code, self.games.get(game_id)?))
+            })
+            .collect();
+        games.sort_by_key(|summary| (summary.status, summary.game_id));
+        Response::GameList { games }
+    }
+
+    /// Hands a dropped client its seat back, with the game as it is now. The
+    /// token stays valid until the player leaves.
+    fn reconnect(&mut self, token: SessionToken) -> Response {
+        let Some(&(game_id, player_id)) = self.sessions.get(&token) else {
+            return Response::Error {
+                message: text!("unknown_session"),
+            };
+        };
+        let seated = self.games.get(&game_id).filter(|game| {
+            game.players.iter().any(|p| p.id == player_id.to_string())
+        });
+        let Some(game) = seated else {
+            self.sessions.remove(&token);
+            return Response::Error {
+                message: text!("seat_gone"),
+            };
+        };
+
+        Response::Reconnected {
+            game_id,
+            player_id,
+            game_state: Box::new(game.public_view()),
+        }
+    }
+
+    pub(crate) fn start_game(&mut self, game_id: GameId) -> Response {
+        if let Some(game) = self.games.get_mut(&game_id) {
+            let started = game.history.is_empty().then(|| LifecycleEvent::started(game_id, game));
+            match game.start_round() {
+                Ok(()) => {
+                    if let Some(started) = started {
+                        self.announce(started);
+                    }
+                    self.turn_started.insert(game_id, Instant::now());
+                    self.play_bots(game_id);
+                    Response::GameStarted { game_id }
+                }
+                Err(err) => Response::Error { message: err.into() },
+            }
+        } else {
+            Response::Error {
+                message: text!("game_not_found"),
+            }
+        }
+    }
+
+    /// Makes a move, unless one with the same id was made already, in which
+    /// case its result is returned again.
+    fn make_move_once(&mut self, game_id: GameId, game_move: GameMove, move_id: Option<MoveId>) -> Response {
+        let Some(move_id) = move_id else {
+            return self.make_move(game_id, game_move);
+        };
+        if let Some(result) = self.move_logs.get(&game_id).and_then(|log| log.get(&move_id)) {
+            return result.clone();
+        }
+        let result = self.make_move(game_id, game_move);
+        if self.games.contains_key(&game_id) {
+            self.move_logs.entry(game_id).or_default().record(move_id, result.clone());
+        }
+        result
+    }
+
+    fn make_move(&mut self, game_id: GameId, game_move: GameMove) -> Response {
+        if let Some(game) = self.games.get_mut(&game_id) {
+            let _entered = move_span(game_id, &game_move).entered();
+            match game.make_move_at(game_move, now_ms()).and_then(|()| score_if_finished(game)) {
+                Ok(()) => {
+                    tracing::info!("move applied");
+                    // Hash the state the mover can compute, before any bot replies
+                    let state_hash = game.public_view().state_hash();
+                    self.turn_started.insert(game_id, Instant::now());
+                    self.play_bots(game_id);
+                    Response::MoveAccepted { state_hash, game_id }
+                }
+                Err(err) => Response::Error { message: err.into() },
+            }
+        } else {
+            Response::Error {
+                message: text!("game_not_found"),
+            }
+        }
+    }
+
+    fn vote_skip_turn(&mut self, game_id: GameId, voter_id: PlayerId) -> Response {
+        if let Some(game) = self.games.get_mut(&game_id) {
+            let elapsed_ms = self
+                .turn_started
+                .get(&game_id)
+                .map(|started| started.elapsed().as_millis() as u64)
+                .unwrap_or(0);
+
+            let outcome = game
+                .vote_skip_turn(&voter_id.to_string(), elapsed_ms)
+                .and_then(|outcome| score_if_finished(game).map(|()| outcome));
+            match outcome {
+                Ok(SkipVoteOutcome::Recorded { votes, needed }) => Response::SkipVoteRecorded {
+                    game_id,
+                    votes,
+                    needed,
+                },
+                Ok(SkipVoteOutcome::Skipped { player_id }) => {
+                    self.turn_started.insert(game_id, Instant::now());
+                    self.play_bots(game_id);
+                    match protocol_id(&player_id) {
+                        Some(player_id) => Response::TurnSkipped { game_id, player_id },
+                        // Clients can't be told whose turn it was; the state shows it moved on
+                        None => Response::GameState {
+                            game_state: Box::new(self.games[&game_id].public_view()),
+                        },
+                    }
+                }
+                Err(err) => Response::Error { message: err.into() },
+            }
+        } else {
+            Response::Error {
+                message: text!("game_not_found"),
+            }
+        }
+    }
+
+    /// Seats a bot, which only the host may do, and only before the game
+    /// starts.
+    fn add_bot(
+        &mut self,
+        game_id: GameId,
+        host: SessionToken,
+        difficulty: &str,
+        player_name: Option<String>,
+    ) -> Response {
+        if let Err(message) = self.check_host(game_id, host, text!("host_only_bots")) {
+            return Response::Error { message };
+        }
+        if self.tournaments.plays(game_id) {
+            return Response::Error {
+                message: text!("tournament_pairing_only"),
+            };
+        }
+        let Ok(strategy) = parse_strategy(difficulty) else {
+            return Response::Error {
+                message: text!("unknown_strategy", difficulty),
+            };
+        };
+        let game = self.games.get_mut(&game_id).expect("the host's game exists");
+        if GameStatus::of(game) != GameStatus::Open {
+            return Response::Error {
+                message: text!("bots_before_start"),
+            };
+        }
+        if is_full(game) {
+            return Response::Error {
+                message: text!("game_full"),
+            };
+        }
+
+        let player_id = PlayerId::new();
+        let player_name = player_name.unwrap_or_else(|| format!("{} bot", difficulty));
+        game.add_player(player_id.to_string(), player_name);
+        let engine_rules_version = game.engine_rules_version;
+        self.bots
+            .entry(game_id)
+            .or_default()
+            .insert(player_id.to_string(), strategy);
+        self.play_bots(game_id);
+
+        Response::GameJoined {
+            game_id,
+            join_code: self.join_code(game_id),
+            player_id,
+            engine_rules_version,
+            session_token: None,
+        }
+    }
+
+    /// Plays every bot whose turn it is, until a person is up or the round
+    /// is over. Bot moves are timed and scored like anyone else's.
+    fn play_bots(&mut self, game_id: GameId) {
+        let (Some(game), Some(bots)) = (self.games.get_mut(&game_id), self.bots.get_mut(&game_id)) else {
+            return;
+        };
+
+        let mut played = false;
+        while let Some(strategy) = game.current_player().and_then(|p| bots.get_mut(&p.id)) {
+            let Some(game_move) = game.bot_move(strategy.as_mut()) else {
+                break;
+            };
+            let _entered = move_span(game_id, &game_move).entered();
+            // Bots only choose legal moves, and scoring a finished round can't fail
+            let _ = game
+                .make_move_at(game_move.clone(), now_ms())
+                .and_then(|()| score_if_finished(game));
+            tracing::info!("bot move applied");
+            self.bot_moves.push((game_id, game_move));
+            played = true;
+        }
+        if played {
+            self.turn_started.insert(game_id, Instant::now());
+        }
+    }
+
+    /// Moves bots made since the last call, oldest first, for transports to
+    /// broadcast like the moves players send.
+    pub fn take_bot_moves(&mut self) -> Vec<(GameId, GameMove)> {
+        std::mem::take(&mut self.bot_moves)
+    }
+
+    fn get_game_state(&mut self, game_id: GameId, access: Option<Access>) -> Response {
+        match self.check_viewer(game_id, access) {
+            Ok(()) => Response::GameState {
+                game_state: Box::new(self.games[&game_id].public_view()),
+            },
+            Err(message) => Response::Error { message },
+        }
+    }
+
+    /// Checks the state a client says it holds against the game's, sending
+    /// the whole state back when they differ. The connection keeps track of
+    /// the hash for its `StateDelta`s.
+    fn ack_state(&mut self, game_id: GameId, state_hash: u64) -> Response {
+        let Some(game) = self.games.get(&game_id) else {
+            return Response::Error {
+                message: text!("game_not_found"),
+            };
+        };
+        let expected = game.public_view().state_hash();
+        if state_hash == expected {
+            return Response::StateAcked { game_id };
+        }
+        tracing::warn!(
+            %game_id,
+            reported = state_hash,
+            expected,
+            events = game.events.len(),
+            "client state diverged, resyncing"
+        );
+        Response::Resync {
+            game_id,
+            full_state: Box::new(game.public_view()),
+            last_event_id: game.events.len() as u64,
+        }
+    }
+
+    pub(crate) fn leave_game(&mut self, game_id: GameId, player_id: PlayerId) -> Response {
+        if let Some(game) = self.games.get_mut(&game_id) {
+            game.players.retain(|p| p.id != player_id.to_string());
+            if let Some(bots) = self.bots.get_mut(&game_id) {
+                bots.remove(&player_id.to_string());
+            }
+            self.sessions.retain(|_, seat| *seat != (game_id, player_id));
+            self.liveness.forget(player_id);
+            if self.hosts.get(&game_id) == Some(&player_id) {
+                self.hand_over_hosting(game_id);
+            }
+            Response::PlayerLeft { game_id, player_id }
+        } else {
+            Response::Error {
+                message: text!("game_not_found"),
+            }
+        }
+    }
+
+    /// Makes the first human player left the host of a game whose host left,
+    /// telling its followers. With none left, whoever joins next hosts it.
+    fn hand_over_hosting(&mut self, game_id: GameId) {
+        let next = self.games.get(&game_id).and_then(|game| {
+            game.players
+                .iter()
+                .filter_map(|p| p.id.parse::<PlayerId>().ok())
+                .find(|&player_id| !self.is_bot(game_id, player_id))
+        });
+        match next {
+            Some(host) => {
+                self.hosts.insert(game_id, host);
+                self.notify(game_id, Response::HostTransferred { game_id, host });
+            }
+            None => {
+                self.hosts.remove(&game_id);
+            }
+        }
+    }
+
+    fn sync_state(&mut self, game_id: GameId, mut game_state: GameState) -> Response {
+        if let Some(player) = game_state.players.iter().find(|p| p.id.parse::<PlayerId>().is_err()) {
+            return Response::Error {
+                message: text!("invalid_player_id", player.id),
+            };
+        }
+        if self.variants.restore_scoring(&mut game_state).is_err() {
+            let variant = game_state.config.variant.as_deref().unwrap_or_default();
+            return Response::Error {
+                message: self.unknown_variant(variant),
+            };
+        }
+        self.games.insert(game_id, game_state);
+        self.join_code(game_id);
+        Response::StateSynced { game_id }
+    }
+
+    /// Pauses a game, e.g. because a player disconnected. Moves and skip votes
+    /// are rejected until it is resumed.
+    pub fn pause_game(&mut self, game_id: GameId) -> Result<(), String> {
+        let game = self.games.get_mut(&game_id).ok_or("Game not found")?;
+        game.pause(now_ms())?;
+        self.publish(game_id);
+        Ok(())
+    }
+
+    /// Resumes a paused game. The pause is taken off the current turn's
+    /// clock, so nobody can vote to skip a turn that was only waiting on it.
+    pub fn resume_game(&mut self, game_id: GameId) -> Result<(), String> {
+        let game = self.games.get_mut(&game_id).ok_or("Game not found")?;
+        game.resume(now_ms())?;
+        self.paused_for_disconnect.remove(&game_id);
+
+        let paused = game
+            .pauses
+            .last()
+            .and_then(|period| Some(period.resumed_at_ms? - period.paused_at_ms))
+            .unwrap_or(0);
+        if let Some(started) = self.turn_started.get_mut(&game_id) {
+            *started += Duration::from_millis(paused);
+        }
+        self.play_bots(game_id);
+        self.publish(game_id);
+        Ok(())
+    }
+
+    /// Auto-stays every player whose turn timer ran out (see
+    /// `game_core::timer`) and scores rounds that ended that way. Returns
+    /// the `(game_id, player_id)` of each timed-out turn.
+    pub fn tick_turn_timers(&mut self) -> Vec<(GameId, PlayerId)> {
+        let now = now_ms();
+        let mut timed_out: Vec<(GameId, String, GameMove)> = Vec::new();
+        for (game_id, game) in &mut self.games {
+            let _entered = tracing::info_span!("turn_timers", %game_id).entered();
+            let mut played: Vec<(String, GameMove)> = self
+                .stand_ins
+                .get_mut(game_id)
+                .and_then(|stand_in| play_for_timed_out(game, stand_in.as_mut(), now))
+                .into_iter()
+                .collect();
+            played.extend(game.tick(now).into_iter().map(|player_id| {
+                let stay = GameMove::Stay {
+                    player_id: player_id.clone(),
+                };
+                (player_id, stay)
+            }));
+            if played.is_empty() {
+                continue;
+            }
+            for (player_id, _) in &played {
+                tracing::info!(%player_id, "turn timed out");
+            }
+            // Scoring can't fail here: the round has just finished
+            let _ = score_if_finished(game);
+            self.turn_started.insert(*game_id, Instant::now());
+            timed_out.extend(played.into_iter().map(|(player_id, game_move)| (*game_id, player_id, game_move)));
+        }
+        for (game_id, player_id, game_move) in &timed_out {
+            let Some(player_id) = protocol_id(player_id) else {
+                continue;
+            };
+            let response = Response::TurnTimedOut {
+                game_id: *game_id,
+                player_id,
+                game_move: game_move.clone(),
+            };
+            self.notify(*game_id, response);
+        }
+        let game_ids: Vec<GameId> = timed_out.iter().map(|(game_id, ..)| *game_id).collect();
+        for game_id in game_ids {
+            self.play_bots(game_id);
+            self.publish(game_id);
+        }
+        timed_out
+            .into_iter()
+            .filter_map(|(game_id, player_id, _)| Some((game_id, protocol_id(&player_id)?)))
+            .collect()
+    }
+
+    /// Decodes one message, handles it and encodes the response. Undecodable
+    /// input is answered with an `Error` response rather than dropped.
+    pub fn handle_bytes(&mut self, encoding: Encoding, trust: TrustLevel, bytes: &[u8]) -> Vec<u8> {
+        let response = match encoding.decode::<Message>(bytes) {
+            Ok(message) => self.handle_with_trust(trust, message),
+            Err(err) => Response::Error {
+                message: text!("invalid_json", err),
+            },
+        };
+
+        encoding.encode(&response).unwrap_or_else(|err| {
+            // Responses always encode; fall back to JSON if the codec itself failed
+            serde_json::to_vec(&Response::Error { message: err.into() }).unwrap_or_default()
+        })
+    }
+}
+
+/// Span of one handled message. Its fields are left empty for messages that
+/// name no game or act for no player.
+fn message_span(message: &Message) -> tracing::Span {
+    let span = tracing::info_span!(
+        "message",
+        kind = message.kind(),
+        game_id = field::Empty,
+        player_id = field::Empty
+    );
+    if let Some(game_id) = message.game_id() {
+        span.record("game_id", field::display(game_id));
+    }
+    if let Some(Ok(player_id)) = auth::acting_player(message) {
+        span.record("player_id", field::display(player_id));
+    }
+    span
+}
+
+/// Span of one move, which rule-level events of `game_core` (feature
+/// `tracing`) are traced under.
+fn move_span(game_id: GameId, game_move: &GameMove) -> tracing::Span {
+    let (action, player_id) = match game_move {
+        GameMove::Draw { player_id } => ("draw", player_id),
+        GameMove::Stay { player_id } => ("stay", player_id),
+    };
+    tracing::info_span!("move", %game_id, %player_id, action)
+}
+
+/// The game a response says was changed, whose followers should be updated.
+fn changed_game(response: &Response) -> Option<GameId> {
+    match response {
+        Response::GameJoined { game_id, .. }
+        | Response::GameStarted { game_id }
+        | Response::MoveAccepted { game_id, .. }
+        | Response::PlayerLeft { game_id, .. }
+        | Response::StateSynced { game_id }
+        | Response::SkipVoteRecorded { game_id, .. }
+        | Response::TurnSkipped { game_id, .. }
+        | Response::MatchFound { game_id, .. }
+        | Response::RematchStarted {
+            new_game_id: game_id,
+            ..
+        }
+        | Response::PlayerKicked { game_id, .. } => Some(*game_id),
+        Response::GameState { .. }
+        | Response::Error { .. }
+        | Response::RateLimited { .. }
+        | Response::EncodingSelected { .. }
+        | Response::Welcome { .. }
+        | Response::Reconnected { .. }
+        | Response::Pong
+        | Response::PlayerInvited { .. }
+        | Response::Queued { .. }
+        | Response::LeftQueue { .. }
+        | Response::HostTransferred { .. }
+        | Response::RoundResult { .. }
+        | Response::ReadyRecorded { .. }
+        | Response::TurnTimedOut { .. }
+        | Response::Authenticated { .. }
+        | Response::Spectating { .. }
+        | Response::RematchVoted { .. }
+        | Response::Reacted { .. }
+        | Response::GameClosed { .. }
+        | Response::GameCreated { .. }
+        | Response::GameList { .. }
+        | Response::StateUpdate { .. }
+        | Response::StateDelta { .. }
+        | Response::StateAcked { .. }
+        | Response::Resync { .. }
+        | Response::PlayerDisconnected { .. }
+        | Response::PlayerReconnected { .. }
+        | Response::Leaderboard { .. }
+        | Response::SoloGameStarted { .. }
+        | Response::SoloScoreRecorded { .. }
+        | Response::FriendAdded { .. }
+        | Response::FriendRequested { .. }
+        | Response::FriendRequest { .. }
+        | Response::FriendRemoved { .. }
+        | Response::FriendList { .. }
+        | Response::FriendInvited { .. }
+        | Response::GameInvite { .. }
+        | Response::TournamentCreated { .. }
+        | Response::TournamentJoined { .. }
+        | Response::Tournament { .. }
+        | Response::TournamentUpdated { .. } => None,
+    }
+}
+
+// Rounds are scored as soon as the last player is done, so clients never
+// have to ask for it.
+fn score_if_finished(game: &mut GameState) -> Result<(), String> {
+    if game.round_state.is_finished && !game.round_state.is_scored {
+        game.finish_round()?;
+    }
+    Ok(())
+}
+
+/// Makes the stand-in's move for the current player if their time ran out,
+/// as `GameState::tick` would stay for them. Returns who was played for and
+/// the move.
+fn play_for_timed_out(game: &mut GameState, stand_in: &mut dyn Strategy, now: u64) -> Option<(String, GameMove)> {
+    let expired = game
+        .round_state
+        .turn_deadline_ms
+        .is_some_and(|deadline| now >= deadline);
+    if !expired || game.round_state.is_finished || game.is_paused() {
+        return None;
+    }
+    let player_id = game.current_player()?.id.clone();
+    let game_move = game.bot_move(stand_in)?;
+    game.make_move_at(game_move.clone(), now).ok()?;
+    Some((player_id, game_move))
+}
+
+// Players who joined through the protocol or were synced in have valid ids,
+// but stored and imported games are taken as they are, so theirs may not parse.
+fn protocol_id(player_id: &str) -> Option<PlayerId> {
+    player_id.parse().ok()
+}
+
+/// Whether every seat the game's decks serve is taken.
+pub(crate) fn is_full(game: &GameState) -> bool {
+    game.players.len() >= SEATS_PER_DECK * game.config.deck.num_decks() as usize
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lobby::Access;
+    use crate::{ExpiryReason, GameStatus};
+    use crate::ratings::INITIAL_RATING;
+    use crate::Board;
+    use game_core::daily::{DAILY_ROUNDS, DAILY_TARGET_SCORE};
+    use game_core::solo::SOLO_PLAYER_ID;
+
+    #[test]
+    fn test_engine_without_runtime() {
+        let mut engine = ProtocolEngine::new();
+
+        let join = Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let bytes = Encoding::Json.encode(&join).unwrap();
+        let reply = engine.handle_bytes(Encoding::Json, TrustLevel::UntrustedPeer, &bytes);
+
+        let game_id = match Encoding::Json.decode::<Response>(&reply).unwrap() {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+
+        match engine.handle(Message::StartGame { game_id, host: None }) {
+            Response::GameStarted { .. } => {}
+            other => panic!("Expected GameStarted response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_skip_vote_needs_minimum_wait() {
+        let mut engine = ProtocolEngine::new();
+        let game_id = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let voter_id = match engine.handle(Message::JoinGame {
+            player_name: "Bob".to_string(),
+            game_id: Some(game_id),
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined { player_id, .. } => player_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.handle(Message::StartGame {
+            game_id,
+            host: None,
+        });
+
+        // The turn just started, so Alice can't be skipped yet
+        match engine.handle(Message::VoteSkipTurn { game_id, voter_id }) {
+            Response::Error { message } => assert!(message.contains("long enough")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_round_scored_when_last_player_stays() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.handle(Message::StartGame {
+            game_id,
+            host: None,
+        });
+
+        engine.handle(Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+            move_id: None,
+        });
+
+        let game = &engine.games[&game_id];
+        assert!(game.round_state.is_scored);
+        assert_eq!(game.history.len(), 1);
+    }
+
+    #[test]
+    fn test_bots_play_their_turns() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id, host) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id,
+                player_id,
+                session_token,
+                ..
+            } => (game_id, player_id, session_token.unwrap()),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        match engine.handle(Message::AddBot {
+            game_id,
+            host,
+            difficulty: "psychic".to_string(),
+            player_name: None,
+        }) {
+            Response::Error { message } => assert!(message.contains("Unknown strategy")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        let bot_id = match engine.handle(Message::AddBot {
+            game_id,
+            host,
+            difficulty: "stay-at:0".to_string(),
+            player_name: None,
+        }) {
+            Response::GameJoined { player_id, .. } => player_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.handle(Message::StartGame { game_id, host: None });
+
+        // The bot stays as soon as Alice's move hands it the turn
+        engine.handle(Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+            move_id: None,
+        });
+        let game = &engine.games[&game_id];
+        assert!(game.players[1].has_stayed);
+        assert!(game.round_state.is_scored);
+        assert_eq!(game.timed_moves.len(), 2);
+        assert_eq!(game.players[1].name, "stay-at:0 bot");
+        assert_eq!(
+            engine.take_bot_moves(),
+            vec![(
+                game_id,
+                GameMove::Stay {
+                    player_id: bot_id.to_string()
+                }
+            )]
+        );
+        assert!(engine.take_bot_moves().is_empty());
+    }
+
+    #[test]
+    fn test_only_the_host_adds_bots_before_the_start() {
+        let mut engine = ProtocolEngine::new();
+        let join = |engine: &mut ProtocolEngine, name: &str, game_id: Option<GameId>| {
+            match engine.handle(Message::JoinGame {
+                player_name: name.to_string(),
+                game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            }) {
+                Response::GameJoined {
+                    game_id, session_token, ..
+                } => (game_id, session_token.unwrap()),
+                other => panic!("Expected GameJoined response, got {:?}", other),
+            }
+        };
+        let (game_id, alice) = join(&mut engine, "Alice", None);
+        let (_, bob) = join(&mut engine, "Bob", Some(game_id));
+        let add_bot = |host| Message::AddBot {
+            game_id,
+            host,
+            difficulty: "easy".to_string(),
+            player_name: None,
+        };
+        let error = |response: Response| match response {
+            Response::Error { message } => message,
+            other => panic!("Expected Error response, got {:?}", other),
+        };
+
+        assert_eq!(error(engine.handle(add_bot(bob))), "Only the host can add bots");
+        for _ in 2..SEATS_PER_DECK {
+            assert!(matches!(engine.handle(add_bot(alice)), Response::GameJoined { .. }));
+        }
+        assert_eq!(error(engine.handle(add_bot(alice))), "The game is full");
+        assert_eq!(
+            error(engine.handle(Message::JoinGame {
+                player_name: "Carol".to_string(),
+                game_id: Some(game_id),
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            })),
+            "The game is full"
+        );
+
+        engine.handle(Message::StartGame { game_id, host: None });
+        assert_eq!(
+            error(engine.handle(add_bot(alice))),
+            "Bots can only be added before the game starts"
+        );
+    }
+
+    #[test]
+    fn test_state_updates() {
+        let mut engine = ProtocolEngine::new();
+        assert!(engine.subscribe(GameId::new()).is_none());
+        let (game_id, player_id, host) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id,
+                player_id,
+                session_token,
+                ..
+            } => (game_id, player_id, session_token.unwrap()),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let mut updates = engine.subscribe(game_id).unwrap();
+        let mut next_update = || match updates.try_recv() {
+            Ok(Response::StateUpdate { game_state, .. }) => game_state,
+            other => panic!("Expected StateUpdate, got {:?}", other),
+        };
+
+        engine.handle(Message::AddBot {
+            game_id,
+            host,
+            difficulty: "stay-at:0".to_string(),
+            player_name: None,
+        });
+        assert_eq!(next_update().players.len(), 2);
+        engine.handle(Message::StartGame { game_id, host: None });
+        assert_eq!(next_update().round_state.round_number, 1);
+
+        // The bot's reply comes in the same update as the move
+        engine.handle(Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+            move_id: None,
+        });
+        assert!(next_update().round_state.is_scored);
+        assert!(matches!(updates.try_recv(), Ok(Response::RoundResult { .. })));
+
+        // Failed moves and reads change nothing
+        engine.handle(Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+            move_id: None,
+        });
+        engine.handle(Message::GetGameState { game_id, access: None });
+        assert!(matches!(
+            updates.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_state_updates_hide_the_deck_order() {
+        let mut engine = ProtocolEngine::new();
+        let mut game_id = None;
+        for name in ["Alice", "Bob"] {
+            match engine.handle(Message::JoinGame {
+                player_name: name.to_string(),
+                game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            }) {
+                Response::GameJoined { game_id: id, .. } => game_id = Some(id),
+                other => panic!("Expected GameJoined response, got {:?}", other),
+            }
+        }
+        let game_id = game_id.unwrap();
+        engine.start_game(game_id);
+        let mut updates = engine.subscribe(game_id).unwrap();
+        engine.publish(game_id);
+
+        let update = updates.try_recv().unwrap();
+        let json = serde_json::to_string(&update).unwrap();
+        let game = &engine.games[&game_id];
+        assert!(!json.contains(&serde_json::to_string(&game.deck.cards).unwrap()));
+        assert!(!json.contains(&format!("\"seed\":{}", game.seed)));
+        match update {
+            Response::StateUpdate { game_state, .. } => {
+                let mut remaining = game.deck.cards.clone();
+                remaining.sort();
+                assert_ne!(remaining, game.deck.cards);
+                assert_eq!(game_state.deck.cards, remaining);
+            }
+            other => panic!("Expected StateUpdate, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id, token) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id,
+                player_id,
+                session_token: Some(token),
+                ..
+            } => (game_id, player_id, token),
+            other => panic!("Expected GameJoined response with a token, got {:?}", other),
+        };
+
+        // Tokens can be used again and again
+        for _ in 0..2 {
+            match engine.handle(Message::Reconnect { token }) {
+                Response::Reconnected {
+                    game_id: id,
+                    player_id: player,
+                    game_state,
+                } => {
+                    assert_eq!((id, player), (game_id, player_id));
+                    assert_eq!(game_state.players[0].name, "Alice");
+                }
+                other => panic!("Expected Reconnected response, got {:?}", other),
+            }
+        }
+
+        match engine.handle(Message::Reconnect {
+            token: SessionToken::new(),
+        }) {
+            Response::Error { message } => assert_eq!(message, "Unknown session token"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+
+        // Leaving gives the seat up for good
+        engine.handle(Message::LeaveGame { game_id, player_id });
+        assert!(matches!(
+            engine.handle(Message::Reconnect { token }),
+            Response::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_disconnected_players() {
+        let mut engine = ProtocolEngine::new();
+        let join = |engine: &mut ProtocolEngine, name: &str, game_id| match engine.handle(
+            Message::JoinGame {
+                player_name: name.to_string(),
+                game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            },
+        ) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let (game_id, alice) = join(&mut engine, "Alice", None);
+        let (_, bob) = join(&mut engine, "Bob", Some(game_id));
+        join(&mut engine, "Carol", Some(game_id));
+        engine.handle(Message::StartGame { game_id, host: None });
+        let mut updates = engine.subscribe(game_id).unwrap();
+
+        engine.set_heartbeat(Heartbeat {
+            interval: Duration::from_millis(20),
+            missed_limit: 1,
+            auto_stay: true,
+            pause: false,
+        });
+        // Carol never connected over a transport, so she is never timed out
+        engine.seen(game_id, alice);
+        engine.seen(game_id, bob);
+        std::thread::sleep(Duration::from_millis(30));
+        engine.seen(game_id, bob);
+        assert_eq!(engine.check_heartbeats(), vec![(game_id, alice)]);
+        assert!(engine.check_heartbeats().is_empty());
+
+        // Alice was up, so the server stayed for her
+        assert!(matches!(
+            updates.try_recv(),
+            Ok(Response::PlayerDisconnected { player_id, .. }) if player_id == alice
+        ));
+        match updates.try_recv() {
+            Ok(Response::StateUpdate { game_state, .. }) => {
+                assert!(game_state.players[0].has_stayed);
+                assert_eq!(game_state.current_player().unwrap().id, bob.to_string());
+            }
+            other => panic!("Expected StateUpdate, got {:?}", other),
+        }
+
+        engine.seen(game_id, alice);
+        assert!(matches!(
+            updates.try_recv(),
+            Ok(Response::PlayerReconnected { player_id, .. }) if player_id == alice
+        ));
+        assert!(matches!(engine.handle(Message::Ping), Response::Pong));
+    }
+
+    #[test]
+    fn test_join_with_team_preference() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: Some(1),
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+
+        let game = &engine.games[&game_id];
+        assert_eq!(game.players[0].id, player_id.to_string());
+        assert_eq!(game.players[0].team, Some(1));
+    }
+
+    #[test]
+    fn test_join_with_variant() {
+        let mut engine = ProtocolEngine::new();
+        let join = |game_id: Option<GameId>, variant: Option<&str>| Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id,
+            team: None,
+            variant: variant.map(str::to_string),
+            code: None,
+            access: None,
+        };
+
+        let game_id = match engine.handle(join(None, Some("blitz"))) {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        assert_eq!(engine.games[&game_id].config.target_score, 100);
+
+        match engine.handle(join(Some(game_id), Some("classic"))) {
+            Response::Error { message } => assert!(message.contains("blitz")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        match engine.handle(join(Some(game_id), None)) {
+            Response::GameJoined { .. } => {}
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+        match engine.handle(join(None, Some("speedy"))) {
+            Response::Error { message } => assert!(message.contains("Unknown variant")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lobby() {
+        let mut engine = ProtocolEngine::new();
+        let mut create = |rules: Option<&str>, visibility| {
+            match engine.handle(Message::CreateGame {
+                rules: rules.map(str::to_string),
+                visibility,
+                turn_clock: None,
+            }) {
+                Response::GameCreated { game_id, .. } => game_id,
+                other => panic!("Expected GameCreated response, got {:?}", other),
+            }
+        };
+        let classic = create(None, Visibility::Public);
+        let blitz = create(Some("blitz"), Visibility::Public);
+        create(None, Visibility::default());
+        match engine.handle(Message::CreateGame {
+            rules: Some("speedy".to_string()),
+            visibility: Visibility::Public,
+            turn_clock: None,
+        }) {
+            Response::Error { message } => assert!(message.contains("Unknown variant")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        // Games made by joining stay private
+        engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        });
+
+        engine.handle(Message::JoinGame {
+            player_name: "Bob".to_string(),
+            game_id: Some(classic),
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        });
+        engine.handle(Message::StartGame { game_id: classic, host: None });
+        match engine.handle(Message::JoinGame {
+            player_name: "Carol".to_string(),
+            game_id: Some(classic),
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::Error { message } => assert_eq!(message, "The game has already started"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+
+        match engine.handle(Message::ListGames) {
+            Response::GameList { games } => {
+                assert_eq!(games.len(), 2);
+                assert_eq!(games[0].game_id, blitz);
+                assert_eq!(games[0].variant, "blitz");
+                assert_eq!(games[0].status, GameStatus::Open);
+                assert_eq!(games[1].game_id, classic);
+                assert_eq!(games[1].status, GameStatus::InProgress);
+                assert_eq!(games[1].player_count, 1);
+            }
+            other => panic!("Expected GameList response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_join_by_code() {
+        let mut engine = ProtocolEngine::new();
+        let join = |game_id: Option<GameId>, code: &str| Message::JoinGame {
+            player_name: "Bob".to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: Some(code.parse().unwrap()),
+            access: None,
+        };
+        let (game_id, code) = match engine.handle(Message::CreateGame {
+            rules: None,
+            visibility: Visibility::default(),
+            turn_clock: None,
+        }) {
+            Response::GameCreated { game_id, join_code, .. } => (game_id, join_code),
+            other => panic!("Expected GameCreated response, got {:?}", other),
+        };
+
+        // Codes are read case-insensitively
+        match engine.handle(join(None, &code.to_string().to_lowercase())) {
+            Response::GameJoined {
+                game_id: id,
+                join_code,
+                ..
+            } => assert_eq!((id, join_code), (game_id, code.clone())),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+        assert_eq!(engine.games[&game_id].players.len(), 1);
+
+        let unused = loop {
+            let code = JoinCode::random();
+            if code != engine.join_codes[&game_id] {
+                break code.to_string();
+            }
+        };
+        match engine.handle(join(None, &unused)) {
+            Response::Error { message } => assert!(message.starts_with("No game has the join code")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        match engine.handle(join(Some(GameId::new()), &code.to_string())) {
+            Response::Error { message } => assert!(message.contains("another game")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        assert!(serde_json::from_str::<Message>(
+            r#"{"JoinGame":{"player_name":"Bob","game_id":null,"code":"K0Q2F"}}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_quick_play() {
+        let mut engine = ProtocolEngine::new();
+        let quick_play = |name: &str| Message::QuickPlay {
+            player_name: name.to_string(),
+        };
+        let mut waiting = Vec::new();
+        for name in ["Alice", "Bob", "Carol"] {
+            match engine.handle(quick_play(name)) {
+                Response::Queued {
+                    player_id,
+                    players_waiting,
+                } => {
+                    assert_eq!(players_waiting, waiting.len() + 1);
+                    waiting.push(engine.wait_for_match(player_id).unwrap());
+                }
+                other => panic!("Expected Queued response, got {:?}", other),
+            }
+        }
+        assert!(engine.check_queue().is_empty());
+
+        // The fourth player fills the table
+        let (game_id, session_token) = match engine.handle(quick_play("Dave")) {
+            Response::MatchFound {
+                game_id,
+                session_token,
+                game_state,
+                ..
+            } => {
+                assert_eq!(game_state.players.len(), 4);
+                assert!(game_state.round_state.deck_commitment.is_some());
+                (game_id, session_token)
+            }
+            other => panic!("Expected MatchFound response, got {:?}", other),
+        };
+        for mut found in waiting {
+            match found.try_recv() {
+                Ok(Response::MatchFound { game_id: id, .. }) => assert_eq!(id, game_id),
+                other => panic!("Expected MatchFound, got {:?}", other),
+            }
+        }
+        assert!(matches!(
+            engine.handle(Message::Reconnect { token: session_token }),
+            Response::Reconnected { .. }
+        ));
+
+        let eve = match engine.handle(quick_play("Eve")) {
+            Response::Queued { player_id, .. } => player_id,
+            other => panic!("Expected Queued response, got {:?}", other),
+        };
+        assert!(matches!(
+            engine.handle(Message::LeaveQueue { player_id: eve }),
+            Response::LeftQueue { .. }
+        ));
+        assert!(matches!(
+            engine.handle(Message::LeaveQueue { player_id: eve }),
+            Response::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_ranked_quick_play() {
+        let mut engine = ProtocolEngine::new();
+        engine
+            .set_matchmaking(Matchmaking {
+                min_players: 2,
+                max_players: 2,
+                ..Matchmaking::default()
+            })
+            .unwrap();
+        let ranked = |name: &str| Message::RankedQuickPlay {
+            player_name: name.to_string(),
+            account: Some(name.to_string()),
+        };
+        // Guests aren't rated, and an account queues once
+        assert!(matches!(
+            engine.handle(Message::RankedQuickPlay {
+                player_name: "Alice".to_string(),
+                account: None,
+            }),
+            Response::Error { .. }
+        ));
+        // Far off the others' ratings, Carol waits for closer opponents
+        engine.ratings.load(HashMap::from([(
+            "Carol".to_string(),
+            Rating {
+                rating: 2100.0,
+                games: 40,
+            },
+        )]));
+        for name in ["Alice", "Carol"] {
+            assert!(matches!(engine.handle(ranked(name)), Response::Queued { .. }));
+        }
+        match engine.handle(ranked("Alice")) {
+            Response::Error { message } => assert_eq!(message, "Already queued for a ranked game"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        // Quick play doesn't mix with the ranked queue
+        assert!(matches!(
+            engine.handle(Message::QuickPlay {
+                player_name: "Dave".to_string()
+            }),
+            Response::Queued {
+                players_waiting: 1,
+                ..
+            }
+        ));
+        let game_id = match engine.handle(ranked("Bob")) {
+            Response::MatchFound { game_id, game_state, .. } => {
+                let names: Vec<&str> = game_state.players.iter().map(|p| p.name.as_str()).collect();
+                assert_eq!(names, ["Alice", "Bob"]);
+                game_id
+            }
+            other => panic!("Expected MatchFound response, got {:?}", other),
+        };
+
+        // Rated once the game is over, under the players' names
+        engine.games.get_mut(&game_id).unwrap().config.round_limit = Some(1);
+        while !engine.games[&game_id].is_game_over() {
+            let player_id = engine.games[&game_id].current_player().unwrap().id.clone();
+            engine.handle(Message::MakeMove {
+                game_id,
+                game_move: GameMove::Stay { player_id },
+                move_id: None,
+            });
+        }
+        let (alice, bob) = (engine.rating("Alice"), engine.rating("Bob"));
+        assert_eq!((alice.games, bob.games), (1, 1));
+        assert!((alice.rating + bob.rating - 2.0 * INITIAL_RATING).abs() < 1e-9);
+        assert!(!engine.ranked.contains_key(&game_id));
+        assert_eq!(engine.rating("Carol").games, 40);
+    }
+
+    #[test]
+    fn test_only_signed_in_players_are_ranked() {
+        let mut engine = ProtocolEngine::new();
+        let mut play = |accounts: [Option<&str>; 2], one_leaves: bool| {
+            let mut game_id = None;
+            for (name, account) in ["Alice", "Bob"].into_iter().zip(accounts) {
+                let (id, player_id) = match engine.handle(Message::JoinGame {
+                    player_name: name.to_string(),
+                    game_id,
+                    team: None,
+                    variant: None,
+                    code: None,
+                    access: None,
+                }) {
+                    Response::GameJoined { game_id, player_id, .. } => (game_id, player_id),
+                    other => panic!("Expected GameJoined response, got {:?}", other),
+                };
+                game_id = Some(id);
+                if let Some(account) = account {
+                    engine.set_account(id, player_id, account.to_string());
+                }
+            }
+            let game_id = game_id.unwrap();
+            engine.handle(Message::StartGame { game_id, host: None });
+            // Alice comes in at the target, so she wins after the round
+            let game = engine.games.get_mut(&game_id).unwrap();
+            game.players[0].score = game.config.target_score;
+            if one_leaves {
+                let player_id = engine.games[&game_id].players[1].id.parse().unwrap();
+                engine.handle(Message::LeaveGame { game_id, player_id });
+            }
+            while !engine.games[&game_id].is_game_over() {
+                let player_id = engine.games[&game_id].current_player().unwrap().id.clone();
+                engine.handle(Message::MakeMove {
+                    game_id,
+                    game_move: GameMove::Stay { player_id },
+                    move_id: None,
+                });
+            }
+            engine.leaderboards.lock().unwrap().page(&Board::Wins, &[], 0, PAGE_SIZE).1
+        };
+
+        // Guests don't win, nor does whoever is left alone at the table
+        assert_eq!(play([None, None], false), 0);
+        assert_eq!(play([Some("ada"), Some("bo")], true), 0);
+        assert_eq!(play([Some("ada"), Some("bo")], false), 1);
+
+        // Solo games are ranked from the seed the server dealt, once
+        let start = |account: Option<&str>| Message::StartSoloGame {
+            account: account.map(str::to_string),
+        };
+        assert!(matches!(engine.handle(start(None)), Response::Error { .. }));
+        let Response::SoloGameStarted { seed } = engine.handle(start(Some("ada"))) else {
+            panic!("Expected SoloGameStarted response");
+        };
+        let play_solo = |seed: u64| {
+            let mut game = GameState::new_solo(seed, DAILY_ROUNDS, DAILY_TARGET_SCORE);
+            while !game.is_game_over() {
+                game.start_round().unwrap();
+                game.player_stay(SOLO_PLAYER_ID).unwrap();
+                game.finish_round().unwrap();
+            }
+            Message::SubmitSoloGame {
+                player_name: "Ada".to_string(),
+                game_state: Box::new(game),
+                account: Some("ada".to_string()),
+            }
+        };
+        match engine.handle(play_solo(seed.wrapping_add(1))) {
+            Response::Error { message } => assert_eq!(message, "Only solo games the server dealt are ranked"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        assert!(matches!(engine.handle(play_solo(seed)), Response::SoloScoreRecorded { .. }));
+        assert!(matches!(engine.handle(play_solo(seed)), Response::Error { .. }));
+    }
+
+    #[test]
+    fn test_host_controls() {
+        let mut engine = ProtocolEngine::new();
+        let join = |engine: &mut ProtocolEngine, name: &str, game_id: Option<GameId>| {
+            match engine.handle(Message::JoinGame {
+                player_name: name.to_string(),
+                game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            }) {
+                Response::GameJoined {
+                    game_id,
+                    player_id,
+                    session_token,
+                    ..
+                } => (game_id, player_id, session_token.unwrap()),
+                other => panic!("Expected GameJoined response, got {:?}", other),
+            }
+        };
+        let (game_id, alice, alice_token) = join(&mut engine, "Alice", None);
+        let (_, bob, bob_token) = join(&mut engine, "Bob", Some(game_id));
+        let bot = match engine.handle(Message::AddBot {
+            game_id,
+            host: alice_token,
+            difficulty: "easy".to_string(),
+            player_name: None,
+        }) {
+            Response::GameJoined { player_id, .. } => player_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let (_, carol, carol_token) = join(&mut engine, "Carol", Some(game_id));
+        let mut updates = engine.subscribe(game_id).unwrap();
+        let error = |response: Response| match response {
+            Response::Error { message } => message,
+            other => panic!("Expected Error response, got {:?}", other),
+        };
+
+        assert_eq!(
+            error(engine.handle(Message::CloseGame {
+                game_id,
+                host: bob_token,
+            })),
+            "Only the host can close the game"
+        );
+        assert_eq!(
+            error(engine.handle(Message::StartGame {
+                game_id,
+                host: Some(bob_token),
+            })),
+            "Only the host can start the game"
+        );
+        assert_eq!(
+            error(engine.handle(Message::TransferHost {
+                game_id,
+                host: alice_token,
+                to: bot,
+            })),
+            "Only a player seated in the game can host it"
+        );
+        assert!(matches!(
+            engine.handle(Message::KickPlayer {
+                game_id,
+                host: alice_token,
+                player_id: alice,
+            }),
+            Response::Error { .. }
+        ));
+
+        assert!(matches!(
+            engine.handle(Message::TransferHost {
+                game_id,
+                host: alice_token,
+                to: bob,
+            }),
+            Response::HostTransferred { host, .. } if host == bob
+        ));
+        assert!(matches!(updates.try_recv(), Ok(Response::HostTransferred { host, .. }) if host == bob));
+        assert!(matches!(
+            engine.handle(Message::KickPlayer {
+                game_id,
+                host: alice_token,
+                player_id: bob,
+            }),
+            Response::Error { .. }
+        ));
+
+        assert!(matches!(
+            engine.handle(Message::KickPlayer {
+                game_id,
+                host: bob_token,
+                player_id: alice,
+            }),
+            Response::PlayerKicked { .. }
+        ));
+        assert!(matches!(updates.try_recv(), Ok(Response::PlayerKicked { player_id, .. }) if player_id == alice));
+        assert!(matches!(updates.try_recv(), Ok(Response::StateUpdate { .. })));
+        assert!(matches!(
+            engine.handle(Message::Reconnect { token: alice_token }),
+            Response::Error { .. }
+        ));
+
+        // A leaving host hands over to the next human player, skipping bots
+        engine.handle(Message::LeaveGame {
+            game_id,
+            player_id: bob,
+        });
+        assert_eq!(engine.hosts[&game_id], carol);
+        assert!(matches!(updates.try_recv(), Ok(Response::HostTransferred { host, .. }) if host == carol));
+        assert!(matches!(updates.try_recv(), Ok(Response::StateUpdate { .. })));
+
+        assert!(matches!(
+            engine.handle(Message::CloseGame {
+                game_id,
+                host: carol_token,
+            }),
+            Response::GameClosed { .. }
+        ));
+        assert!(matches!(updates.try_recv(), Ok(Response::GameClosed { .. })));
+        assert!(matches!(
+            updates.try_recv(),
+            Err(broadcast::error::TryRecvError::Closed)
+        ));
+        assert!(!engine.games.contains_key(&game_id));
+        assert!(engine.sessions.is_empty());
+        assert!(engine.join_codes.is_empty());
+    }
+
+    #[test]
+    fn test_reactions() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let mut updates = engine.subscribe(game_id).unwrap();
+
+        let react = |player_id| Message::Reaction {
+            game_id,
+            player_id,
+            emoji: Emoji::Fire,
+        };
+        assert!(matches!(
+            engine.handle(react(player_id)),
+            Response::Reacted {
+                emoji: Emoji::Fire,
+                ..
+            }
+        ));
+        // Pushed to the table, without a state update
+        assert!(matches!(updates.try_recv(), Ok(Response::Reacted { .. })));
+        assert!(updates.try_recv().is_err());
+
+        assert!(matches!(engine.handle(react(PlayerId::new())), Response::Error { .. }));
+    }
+
+    #[test]
+    fn test_round_flow() {
+        let mut engine = ProtocolEngine::new();
+        let join = |player_name: &str, game_id: Option<GameId>| Message::JoinGame {
+            player_name: player_name.to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let (game_id, alice) = match engine.handle(join("Alice", None)) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let bob = match engine.handle(join("Bob", Some(game_id))) {
+            Response::GameJoined { player_id, .. } => player_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let ready = |player_id| Message::Ready { game_id, player_id };
+        assert!(matches!(engine.handle(ready(alice)), Response::Error { .. }));
+
+        let play_round = |engine: &mut ProtocolEngine| {
+            while !engine.games[&game_id].round_state.is_finished {
+                let player_id = engine.games[&game_id].current_player().unwrap().id.clone();
+                engine.handle(Message::MakeMove {
+                    game_id,
+                    game_move: GameMove::Stay { player_id },
+                    move_id: None,
+                });
+            }
+        };
+        engine.handle(Message::StartGame { game_id, host: None });
+        let commitment = engine.games[&game_id].round_state.deck_commitment.clone().unwrap();
+        let mut updates = engine.subscribe(game_id).unwrap();
+        play_round(&mut engine);
+
+        let mut pushed = Vec::new();
+        while let Ok(response) = updates.try_recv() {
+            pushed.push(response);
+        }
+        match pushed.last() {
+            Some(Response::RoundResult {
+                summary,
+                game_over,
+                deck_reveal,
+                ..
+            }) => {
+                assert_eq!(summary.round_number, 1);
+                assert!(!game_over);
+                assert!(deck_reveal.as_ref().unwrap().verify(&commitment));
+            }
+            other => panic!("Expected RoundResult response, got {:?}", other),
+        }
+
+        // The next round starts once everyone is ready
+        assert!(matches!(
+            engine.handle(ready(alice)),
+            Response::ReadyRecorded {
+                ready: 1,
+                needed: 2,
+                ..
+            }
+        ));
+        assert!(engine.games[&game_id].round_state.is_scored);
+        assert!(matches!(
+            engine.handle(ready(bob)),
+            Response::ReadyRecorded {
+                ready: 2,
+                needed: 2,
+                ..
+            }
+        ));
+        assert!(!engine.games[&game_id].round_state.is_scored);
+        assert!(engine.check_rounds().is_empty());
+
+        // Or once the wait is over
+        play_round(&mut engine);
+        engine.set_round_flow(RoundFlow {
+            ready_timeout: Duration::ZERO,
+        });
+        assert_eq!(engine.check_rounds(), vec![game_id]);
+        assert_eq!(engine.games[&game_id].round_state.round_number, 3);
+    }
+
+    #[test]
+    fn test_turn_clock() {
+        let mut engine = ProtocolEngine::new();
+        let timed_game = |engine: &mut ProtocolEngine, auto_play| {
+            let turn_clock = Some(TurnClock { seconds: 5, auto_play });
+            let game_id = match engine.handle(Message::CreateGame {
+                rules: None,
+                visibility: Visibility::default(),
+                turn_clock,
+            }) {
+                Response::GameCreated { game_id, .. } => game_id,
+                other => panic!("Expected GameCreated response, got {:?}", other),
+            };
+            for player_name in ["Alice", "Bob"] {
+                engine.handle(Message::JoinGame {
+                    player_name: player_name.to_string(),
+                    game_id: Some(game_id),
+                    team: None,
+                    variant: None,
+                    code: None,
+                    access: None,
+                });
+            }
+            engine.handle(Message::StartGame { game_id, host: None });
+            // Starts the clock, then runs it out
+            assert!(engine.tick_turn_timers().is_empty());
+            engine.games.get_mut(&game_id).unwrap().round_state.turn_deadline_ms = Some(now_ms());
+            (game_id, engine.subscribe(game_id).unwrap())
+        };
+
+        for auto_play in [AutoPlay::Stay, AutoPlay::BotMove] {
+            let (game_id, mut updates) = timed_game(&mut engine, auto_play);
+            let player_id = engine.games[&game_id].current_player().unwrap().id.clone();
+            assert_eq!(engine.tick_turn_timers(), vec![(game_id, protocol_id(&player_id).unwrap())]);
+
+            match updates.try_recv() {
+                Ok(Response::TurnTimedOut {
+                    player_id: timed_out,
+                    game_move,
+                    ..
+                }) => {
+                    assert_eq!(timed_out.to_string(), player_id);
+                    if auto_play == AutoPlay::Stay {
+                        assert_eq!(game_move, GameMove::Stay { player_id: player_id.clone() });
+                    }
+                }
+                other => panic!("Expected TurnTimedOut response, got {:?}", other),
+            }
+            // The table moves on to the next player
+            assert!(matches!(updates.try_recv(), Ok(Response::StateUpdate { .. })));
+            let game = &engine.games[&game_id];
+            assert_ne!(game.current_player().unwrap().id, player_id);
+        }
+
+        assert!(matches!(
+            engine.handle(Message::CreateGame {
+                rules: None,
+                visibility: Visibility::default(),
+                turn_clock: Some(TurnClock {
+                    seconds: 0,
+                    auto_play: AutoPlay::Stay,
+                }),
+            }),
+            Response::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_rematch() {
+        let mut engine = ProtocolEngine::new();
+        // As a public server's clients send them
+        let send = |engine: &mut ProtocolEngine, message| engine.handle_with_trust(TrustLevel::UntrustedPeer, message);
+        let join = |player_name: &str, game_id: Option<GameId>| Message::JoinGame {
+            player_name: player_name.to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let (game_id, alice, token) = match send(&mut engine, join("Alice", None)) {
+            Response::GameJoined {
+                game_id,
+                player_id,
+                session_token: Some(token),
+                ..
+            } => (game_id, player_id, token),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let bob = match send(&mut engine, join("Bob", Some(game_id))) {
+            Response::GameJoined { player_id, .. } => player_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        send(&mut engine, Message::AddBot {
+            game_id,
+            host: token,
+            difficulty: "stay-at:0".to_string(),
+            player_name: None,
+        });
+        let rematch = |player_id| Message::RequestRematch { game_id, player_id };
+        assert!(matches!(send(&mut engine, rematch(alice)), Response::Error { .. }));
+
+        // A one round game
+        engine.games.get_mut(&game_id).unwrap().config.round_limit = Some(1);
+        send(&mut engine, Message::StartGame { game_id, host: Some(token) });
+        while !engine.games[&game_id].is_game_over() {
+            let player_id = engine.games[&game_id].current_player().unwrap().id.clone();
+            send(&mut engine, Message::MakeMove {
+                game_id,
+                game_move: GameMove::Stay { player_id },
+                move_id: None,
+            });
+        }
+        let mut updates = engine.subscribe(game_id).unwrap();
+
+        // Bots don't vote
+        assert!(matches!(
+            send(&mut engine, rematch(alice)),
+            Response::RematchVoted {
+                votes: 1,
+                needed: 2,
+                ..
+            }
+        ));
+        engine.take_bot_moves();
+        let new_game_id = match send(&mut engine, rematch(bob)) {
+            Response::RematchStarted { new_game_id, .. } => new_game_id,
+            other => panic!("Expected RematchStarted response, got {:?}", other),
+        };
+        let (old, new) = (&engine.games[&game_id], &engine.games[&new_game_id]);
+        let seats = |game: &GameState| game.players.iter().map(|p| p.id.clone()).collect::<Vec<_>>();
+        assert_eq!(seats(old), seats(new));
+        assert_eq!(new.config, old.config);
+        assert!(new.history.is_empty());
+        assert!(matches!(
+            send(&mut engine, rematch(alice)),
+            Response::RematchStarted { new_game_id: id, .. } if id == new_game_id
+        ));
+
+        // Followers and session tokens move over to the rematch
+        let mut pushed = Vec::new();
+        while let Ok(response) = updates.try_recv() {
+            pushed.push(response);
+        }
+        assert!(matches!(pushed.last(), Some(Response::StateUpdate { game_id: id, .. }) if *id == new_game_id));
+        match send(&mut engine, Message::Reconnect { token }) {
+            Response::Reconnected { game_id: id, .. } => assert_eq!(id, new_game_id),
+            other => panic!("Expected Reconnected response, got {:?}", other),
+        }
+        // It started once everyone voted, and the bot plays its seat in it too
+        while engine.take_bot_moves().is_empty() {
+            let player_id = engine.games[&new_game_id].current_player().unwrap().id.clone();
+            let stay = Message::MakeMove {
+                game_id: new_game_id,
+                game_move: GameMove::Stay { player_id },
+                move_id: None,
+            };
+            assert!(matches!(send(&mut engine, stay), Response::MoveAccepted { .. }));
+        }
+    }
+
+    #[test]
+    fn test_expiry() {
+        let mut engine = ProtocolEngine::new();
+        engine.set_expiry(Expiry {
+            lobby_ttl: Duration::ZERO,
+            ..Expiry::default()
+        });
+        let join = Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let (lobby, token) = match engine.handle(join.clone()) {
+            Response::GameJoined {
+                game_id,
+                session_token: Some(token),
+                ..
+            } => (game_id, token),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let playing = match engine.handle(join) {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.handle(Message::StartGame { game_id: playing, host: None });
+        let mut updates = engine.subscribe(lobby).unwrap();
+
+        let expired = engine.sweep_expired();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].game_id, lobby);
+        assert_eq!(expired[0].reason, ExpiryReason::AbandonedLobby);
+        assert_eq!(expired[0].game_state.players[0].name, "Alice");
+        assert!(matches!(
+            updates.try_recv(),
+            Ok(Response::GameClosed { game_id }) if game_id == lobby
+        ));
+        assert!(!engine.games.contains_key(&lobby));
+        assert!(matches!(
+            engine.handle(Message::Reconnect { token }),
+            Response::Error { .. }
+        ));
+        // Games in progress are kept until nobody has moved for a while
+        assert!(engine.games.contains_key(&playing));
+        assert!(engine.sweep_expired().is_empty());
+        engine.set_expiry(Expiry {
+            idle_ttl: Duration::ZERO,
+            ..Expiry::default()
+        });
+        let expired = engine.sweep_expired();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].game_id, playing);
+        assert_eq!(expired[0].reason, ExpiryReason::AbandonedGame);
+        assert!(!engine.games.contains_key(&playing));
+    }
+
+    #[test]
+    fn test_protected_games() {
+        let mut engine = ProtocolEngine::new();
+        let join = |game_id: GameId, access: Option<Access>| Message::JoinGame {
+            player_name: "Bob".to_string(),
+            game_id: Some(game_id),
+            team: None,
+            variant: None,
+            code: None,
+            access,
+        };
+        let mut create = |visibility: Visibility| match engine.handle(Message::CreateGame {
+            rules: None,
+            visibility,
+            turn_clock: None,
+        }) {
+            Response::GameCreated { game_id, invite, .. } => (game_id, Access::Invite(invite.unwrap())),
+            other => panic!("Expected GameCreated response, got {:?}", other),
+        };
+        let (locked, owner) = create(Visibility::Private {
+            password: Some("hunter2".to_string()),
+        });
+        let (invite_only, invite_only_owner) = create(Visibility::InviteOnly);
+        let error = |response: Response| match response {
+            Response::Error { message } => message,
+            other => panic!("Expected Error response, got {:?}", other),
+        };
+
+        // Nobody gets in first without the password, but the creator comes
+        // in with their invitation and hosts the game
+        assert_eq!(error(engine.handle(join(locked, None))), "This game needs a password");
+        let locked_host = match engine.handle(join(locked, Some(owner))) {
+            Response::GameJoined { session_token, .. } => session_token.unwrap(),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        assert_eq!(error(engine.handle(join(locked, None))), "This game needs a password");
+        assert_eq!(
+            error(engine.handle(join(locked, Some(Access::Password("hunter3".to_string()))))),
+            "Wrong password"
+        );
+        assert!(matches!(
+            engine.handle(join(locked, Some(Access::Password("hunter2".to_string())))),
+            Response::GameJoined { .. }
+        ));
+
+        // Invite-only games let the others in with the host's invitations
+        let host = match engine.handle(join(invite_only, Some(invite_only_owner))) {
+            Response::GameJoined { session_token, .. } => session_token.unwrap(),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        assert_eq!(error(engine.handle(join(invite_only, None))), "This game is invite-only");
+        assert_eq!(
+            error(engine.handle(Message::InvitePlayer {
+                game_id: locked,
+                host,
+            })),
+            "Only the host can invite players"
+        );
+
+        let invite = match engine.handle(Message::InvitePlayer {
+            game_id: invite_only,
+            host,
+        }) {
+            Response::PlayerInvited { game_id, invite } => {
+                assert_eq!(game_id, invite_only);
+                invite
+            }
+            other => panic!("Expected PlayerInvited response, got {:?}", other),
+        };
+
+        // They are watched and shown only to those who could join, and to
+        // their players
+        let spectate = |game_id, access| Message::Spectate { game_id, access };
+        let get = |game_id, access| Message::GetGameState { game_id, access };
+        let password = || Some(Access::Password("hunter2".to_string()));
+        assert_eq!(error(engine.handle(spectate(locked, None))), "This game needs a password");
+        assert_eq!(error(engine.handle(get(invite_only, None))), "This game is invite-only");
+        assert!(matches!(engine.handle(spectate(locked, password())), Response::Spectating { .. }));
+        assert!(matches!(
+            engine.handle(get(locked, Some(Access::Seat(locked_host)))),
+            Response::GameState { .. }
+        ));
+        assert_eq!(
+            error(engine.handle(get(invite_only, Some(Access::Seat(locked_host))))),
+            "This game is invite-only"
+        );
+        // Watching doesn't use the invitation up
+        assert!(matches!(
+            engine.handle(spectate(invite_only, Some(Access::Invite(invite)))),
+            Response::Spectating { .. }
+        ));
+        assert_eq!(
+            error(engine.handle(join(locked, Some(Access::Invite(invite))))),
+            "Invalid invitation"
+        );
+        assert!(matches!(
+            engine.handle(join(invite_only, Some(Access::Invite(invite)))),
+            Response::GameJoined { .. }
+        ));
+        assert_eq!(engine.games[&invite_only].players.len(), 2);
+        // Invitations are used up by joining
+        assert_eq!(
+            error(engine.handle(join(invite_only, Some(Access::Invite(invite))))),
+            "Invalid invitation"
+        );
+
+        // A game its players all left still wants the password
+        let seated: Vec<PlayerId> = engine.games[&locked].players.iter().map(|p| p.id.parse().unwrap()).collect();
+        for player_id in seated {
+            engine.leave_game(locked, player_id);
+        }
+        assert!(!engine.hosts.contains_key(&locked));
+        assert_eq!(error(engine.handle(join(locked, None))), "This game needs a password");
+        assert_eq!(
+            error(engine.handle(Message::AddBot {
+                game_id: locked,
+                host: locked_host,
+                difficulty: "easy".to_string(),
+                player_name: None,
+            })),
+            "Only the host can add bots"
+        );
+    }
+
+    #[test]
+    fn test_retried_moves_apply_once() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.handle(Message::StartGame { game_id, host: None });
+
+        let draw = Message::MakeMove {
+            game_id,
+            game_move: GameMove::Draw {
+                player_id: player_id.to_string(),
+            },
+            move_id: Some(MoveId::new()),
+        };
+        let first = match engine.handle(draw.clone()) {
+            Response::MoveAccepted { state_hash, .. } => state_hash,
+            other => panic!("Expected MoveAccepted response, got {:?}", other),
+        };
+        let cards = engine.games[&game_id].players[0].hand.cards.len();
+
+        match engine.handle(draw) {
+            Response::MoveAccepted { state_hash, .. } => assert_eq!(state_hash, first),
+            other => panic!("Expected MoveAccepted response, got {:?}", other),
+        }
+        assert_eq!(engine.games[&game_id].players[0].hand.cards.len(), cards);
+        assert_eq!(engine.games[&game_id].public_view().state_hash(), first);
+    }
+
+    #[test]
+    fn test_diverged_state_is_resynced() {
+        let mut engine = ProtocolEngine::new();
+        let game_id = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let state_hash = engine.games[&game_id].public_view().state_hash();
+
+        assert!(matches!(
+            engine.handle(Message::AckState { game_id, state_hash }),
+            Response::StateAcked { .. }
+        ));
+        match engine.handle(Message::AckState {
+            game_id,
+            state_hash: state_hash.wrapping_add(1),
+        }) {
+            Response::Resync {
+                full_state,
+                last_event_id,
+                ..
+            } => {
+                assert_eq!(full_state.state_hash(), state_hash);
+                assert_eq!(last_event_id, full_state.events.len() as u64);
+            }
+            other => panic!("Expected Resync response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_is_announced() {
+        let mut engine = ProtocolEngine::new();
+        let mut lifecycle = engine.subscribe_lifecycle();
+        let join = |player_name: &str, game_id: Option<GameId>| Message::JoinGame {
+            player_name: player_name.to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let game_id = match engine.handle(join("Alice", None)) {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.handle(join("Bob", Some(game_id)));
+        assert!(matches!(
+            lifecycle.try_recv(),
+            Ok(LifecycleEvent::GameCreated { variant, .. }) if variant == DEFAULT_VARIANT
+        ));
+
+        // A one round game
+        engine.games.get_mut(&game_id).unwrap().config.round_limit = Some(1);
+        engine.handle(Message::StartGame { game_id, host: None });
+        assert!(matches!(
+            lifecycle.try_recv(),
+            Ok(LifecycleEvent::GameStarted { players, .. }) if players == ["Alice", "Bob"]
+        ));
+        while !engine.games[&game_id].is_game_over() {
+            let player_id = engine.games[&game_id].current_player().unwrap().id.clone();
+            engine.handle(Message::MakeMove {
+                game_id,
+                game_move: GameMove::Stay { player_id },
+                move_id: None,
+            });
+        }
+        assert!(matches!(
+            lifecycle.try_recv(),
+            Ok(LifecycleEvent::RoundFinished { summary, .. }) if summary.round_number == 1
+        ));
+        match lifecycle.try_recv() {
+            Ok(LifecycleEvent::GameOver { scores, .. }) => assert_eq!(scores.len(), 2),
+            other => panic!("Expected GameOver, got {:?}", other),
+        }
+        assert!(lifecycle.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_paused_game_rejects_moves() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.handle(Message::StartGame {
+            game_id,
+            host: None,
+        });
+
+        engine.pause_game(game_id).unwrap();
+        let stay = Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+            move_id: None,
+        };
+        match engine.handle(stay.clone()) {
+            Response::Error { message } => assert_eq!(message, "Game is paused"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+
+        engine.resume_game(game_id).unwrap();
+        match engine.handle(stay) {
+            Response::MoveAccepted { .. } => {}
+            other => panic!("Expected MoveAccepted response, got {:?}", other),
+        }
+        assert!(engine.resume_game(GameId::new()).is_err());
+    }
+
+    #[test]
+    fn test_disconnects_pause_the_round() {
+        let mut engine = ProtocolEngine::new();
+        let join = |engine: &mut ProtocolEngine, name: &str, game_id| match engine.handle(Message::JoinGame {
+            player_name: name.to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        let (game_id, alice) = join(&mut engine, "Alice", None);
+        let (_, bob) = join(&mut engine, "Bob", Some(game_id));
+        engine.handle(Message::StartGame { game_id, host: None });
+        engine.set_heartbeat(Heartbeat {
+            interval: Duration::from_millis(20),
+            missed_limit: 1,
+            auto_stay: true,
+            pause: true,
+        });
+
+        // Both go quiet, so the round waits for both
+        engine.seen(game_id, alice);
+        engine.seen(game_id, bob);
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(engine.check_heartbeats().len(), 2);
+        let game = &engine.games[&game_id];
+        assert!(game.is_paused());
+        assert!(!game.players[0].has_stayed);
+
+        engine.seen(game_id, bob);
+        assert!(engine.games[&game_id].is_paused());
+        engine.seen(game_id, alice);
+        assert!(!engine.games[&game_id].is_paused());
+        let stay = Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: alice.to_string(),
+            },
+            move_id: None,
+        };
+        assert!(matches!(engine.handle(stay), Response::MoveAccepted { .. }));
+
+        // A game paused by hand stays paused when players come back
+        std::thread::sleep(Duration::from_millis(30));
+        engine.seen(game_id, alice);
+        engine.check_heartbeats();
+        engine.resume_game(game_id).unwrap();
+        engine.pause_game(game_id).unwrap();
+        engine.seen(game_id, bob);
+        assert!(engine.games[&game_id].is_paused());
+    }
+
+    #[test]
+    fn test_turn_timer_auto_stays_and_scores() {
+        let mut engine = ProtocolEngine::new();
+        let (game_id, player_id) = match engine.handle(Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }) {
+            Response::GameJoined {
+                game_id, player_id, ..
+            } => (game_id, player_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        engine.games.get_mut(&game_id).unwrap().config.turn_time_limit_ms = Some(0);
+        engine.handle(Message::StartGame {
+            game_id,
+            host: None,
+        });
+
+        assert_eq!(engine.tick_turn_timers(), vec![(game_id, player_id)]);
+        let game = &engine.games[&game_id];
+        assert!(game.round_state.is_scored);
+        assert!(engine.tick_turn_timers().is_empty());
+    }
+
+    #[test]
+    fn test_sync_rejects_invalid_player_ids() {
+        let mut engine = ProtocolEngine::new();
+        let mut game = GameState::new();
+        game.add_player("0".to_string(), "Alice".to_string());
+        let sync = |game: &GameState| Message::SyncState {
+            game_id: GameId::new(),
+            game_state: Box::new(game.clone()),
+        };
+
+        match engine.handle(sync(&game)) {
+            Response::Error { message } => assert!(message.contains("Invalid player id")),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        game.players[0].id = PlayerId::new().to_string();
+        match engine.handle(sync(&game)) {
+            Response::StateSynced { .. } => {}
+            other => panic!("Expected StateSynced response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_imported_games_keep_their_player_ids() {
+        let mut engine = ProtocolEngine::new();
+        let mut game = GameState::new_with_seed(3);
+        game.add_player("0".to_string(), "Alice".to_string());
+        game.add_player("1".to_string(), "Bob".to_string());
+        game.start_round().unwrap();
+        game.player_stay("0").unwrap();
+        game.player_stay("1").unwrap();
+        game.finish_round().unwrap();
+        let game_id = GameId::new();
+        engine.import_games(crate::ServerSnapshot {
+            games: HashMap::from([(game_id, game)]),
+            ..Default::default()
+        });
+
+        // Players whose ids aren't the protocol's have no say between rounds
+        engine.announce_round(game_id);
+        assert!(engine.deciding_players(game_id).is_empty());
+        assert_eq!(engine.between_rounds(game_id), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_engine_rejects_garbage() {
+        let mut engine = ProtocolEngine::new();
+        let reply = engine.handle_bytes(Encoding::Json, TrustLevel::UntrustedPeer, b"not json");
+
+        assert!(matches!(
+            Encoding::Json.decode::<Response>(&reply).unwrap(),
+            Response::Error { .. }
+        ));
+    }
+}
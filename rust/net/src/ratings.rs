@@ -0,0 +1,182 @@
+//! Player ratings, kept for ranked games: those `RankedQuickPlay` matches.
+//!
+//! Ratings are Elo ratings, generalized to tables of more than two: a game
+//! counts as a match between every two players at the table, won by the one
+//! who won the game or else scored more, and drawn between equal scores.
+//! Each player's rating moves by the sum of their matches' changes, scaled
+//! down by the number of opponents so a full table weighs as much as a duel.
+//! Players who left a ranked game before it ended lose to everyone.
+//!
+//! Players are rated under their account's `Identity::user_id`, so only
+//! signed-in players play ranked games. A `GameStore` keeps the ratings,
+//! saving those that changed with the games.
+
+use crate::store::Rebase;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Rating of a player who never played a ranked game.
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// Most a rating moves in one game.
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    /// Ranked games played
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: INITIAL_RATING,
+            games: 0,
+        }
+    }
+}
+
+/// What the games rated since moved the rating by, moved again.
+impl Rebase for Rating {
+    fn rebase(&self, base: &Self, saved: &Self) -> Self {
+        Self {
+            rating: saved.rating + self.rating - base.rating,
+            games: (saved.games + self.games).saturating_sub(base.games),
+        }
+    }
+}
+
+/// How a player finished a ranked game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Standing {
+    pub rating: f64,
+    pub score: u32,
+    /// Whether the player is one of the game's winners
+    pub won: bool,
+}
+
+/// The ratings of players who finished a game as `standings`, in the same
+/// order.
+pub fn rate(standings: &[Standing]) -> Vec<f64> {
+    let opponents = standings.len().saturating_sub(1).max(1) as f64;
+    standings
+        .iter()
+        .enumerate()
+        .map(|(seat, player)| {
+            let change: f64 = standings
+                .iter()
+                .enumerate()
+                .filter(|(other_seat, _)| *other_seat != seat)
+                .map(|(_, other)| {
+                    let expected = 1.0 / (1.0 + 10f64.powf((other.rating - player.rating) / 400.0));
+                    let actual = match (player.won, player.score).cmp(&(other.won, other.score)) {
+                        std::cmp::Ordering::Greater => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Less => 0.0,
+                    };
+                    actual - expected
+                })
+                .sum();
+            player.rating + K_FACTOR * change / opponents
+        })
+        .collect()
+}
+
+/// Every rated player's rating, and which of them changed since the last
+/// save.
+#[derive(Debug, Default)]
+pub(crate) struct Ratings {
+    ratings: HashMap<String, Rating>,
+    unsaved: HashSet<String>,
+}
+
+impl Ratings {
+    pub(crate) fn get(&self, account: &str) -> Rating {
+        self.ratings.get(account).copied().unwrap_or_default()
+    }
+
+    /// Rates a finished game, given each account's score and whether it won.
+    pub(crate) fn record(&mut self, results: &[(String, u32, bool)]) {
+        let standings: Vec<Standing> = results
+            .iter()
+            .map(|(account, score, won)| Standing {
+                rating: self.get(account).rating,
+                score: *score,
+                won: *won,
+            })
+            .collect();
+        for ((account, ..), rating) in results.iter().zip(rate(&standings)) {
+            let entry = self.ratings.entry(account.clone()).or_default();
+            entry.rating = rating;
+            entry.games += 1;
+            self.unsaved.insert(account.clone());
+        }
+    }
+
+    /// Takes the ratings a store kept, over those known.
+    pub(crate) fn load(&mut self, ratings: HashMap<String, Rating>) {
+        self.ratings.extend(ratings);
+    }
+
+    /// The ratings that changed since they were last taken.
+    pub(crate) fn take_unsaved(&mut self) -> Vec<(String, Rating)> {
+        let mut changed: Vec<(String, Rating)> = std::mem::take(&mut self.unsaved)
+            .into_iter()
+            .map(|account| {
+                let rating = self.get(&account);
+                (account, rating)
+            })
+            .collect();
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+        changed
+    }
+
+    /// Has the ratings saved again with the next save, e.g. after it failed.
+    pub(crate) fn mark_unsaved(&mut self, accounts: impl IntoIterator<Item = String>) {
+        self.unsaved.extend(accounts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standing(rating: f64, score: u32, won: bool) -> Standing {
+        Standing { rating, score, won }
+    }
+
+    #[test]
+    fn test_rate() {
+        // Equals: the winner takes half of K from the loser
+        let rated = rate(&[standing(1500.0, 210, true), standing(1500.0, 150, false)]);
+        assert_eq!(rated, vec![1516.0, 1484.0]);
+
+        // An upset moves ratings further than an expected result
+        let expected = rate(&[standing(1700.0, 200, true), standing(1300.0, 100, false)]);
+        let upset = rate(&[standing(1700.0, 100, false), standing(1300.0, 200, true)]);
+        assert!(expected[0] - 1700.0 < 1700.0 - upset[0]);
+
+        // A tiebreak winner beats the player they tied with
+        let rated = rate(&[standing(1500.0, 200, false), standing(1500.0, 200, true), standing(1500.0, 90, false)]);
+        assert!(rated[1] > rated[0] && rated[0] > rated[2]);
+        // Ratings only move between the players
+        let total: f64 = rated.iter().sum();
+        assert!((total - 4500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_record() {
+        let mut ratings = Ratings::default();
+        assert_eq!(ratings.get("ada"), Rating::default());
+        ratings.record(&[("ada".to_string(), 205, true), ("bo".to_string(), 120, false)]);
+        assert_eq!(ratings.get("ada"), Rating { rating: 1516.0, games: 1 });
+        assert_eq!(ratings.get("bo").rating, 1484.0);
+
+        let unsaved = ratings.take_unsaved();
+        assert_eq!(unsaved.iter().map(|(account, _)| account.as_str()).collect::<Vec<_>>(), ["ada", "bo"]);
+        assert!(ratings.take_unsaved().is_empty());
+        ratings.mark_unsaved(["bo".to_string()]);
+        assert_eq!(ratings.take_unsaved(), vec![("bo".to_string(), ratings.get("bo"))]);
+    }
+}
@@ -0,0 +1,54 @@
+//! Quick reactions players send to the table, like tapping a thumbs up when
+//! someone flips a Flip 7. Unlike chat they come from a fixed set, so there is
+//! nothing to moderate.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Emoji {
+    ThumbsUp,
+    Laugh,
+    Wow,
+    Sad,
+    Angry,
+    Fire,
+    Clap,
+}
+
+impl Emoji {
+    pub const ALL: [Emoji; 7] = [
+        Emoji::ThumbsUp,
+        Emoji::Laugh,
+        Emoji::Wow,
+        Emoji::Sad,
+        Emoji::Angry,
+        Emoji::Fire,
+        Emoji::Clap,
+    ];
+
+    /// How clients show the reaction.
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            Emoji::ThumbsUp => "👍",
+            Emoji::Laugh => "😂",
+            Emoji::Wow => "😮",
+            Emoji::Sad => "😢",
+            Emoji::Angry => "😠",
+            Emoji::Fire => "🔥",
+            Emoji::Clap => "👏",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_glyphs_are_distinct() {
+        let glyphs: HashSet<&str> = Emoji::ALL.iter().map(Emoji::glyph).collect();
+        assert_eq!(glyphs.len(), Emoji::ALL.len());
+        assert_eq!(serde_json::to_string(&Emoji::Fire).unwrap(), "\"Fire\"");
+    }
+}
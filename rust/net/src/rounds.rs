@@ -0,0 +1,82 @@
+//! Rounds run by the server: as soon as a round is scored its result goes out
+//! to the table, and the next round starts once every player still at the
+//! table says they are ready, or the wait runs out, until someone reaches the
+//! target score.
+
+use crate::PlayerId;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// How long the table waits between rounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundFlow {
+    /// The next round starts after this long, even if not everyone is ready
+    pub ready_timeout: Duration,
+}
+
+impl Default for RoundFlow {
+    fn default() -> Self {
+        Self {
+            ready_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The wait after a scored round.
+#[derive(Debug)]
+pub(crate) struct Intermission {
+    /// The round that was scored
+    pub(crate) round_number: u32,
+    since: Instant,
+    ready: HashSet<PlayerId>,
+}
+
+impl Intermission {
+    pub(crate) fn new(round_number: u32, now: Instant) -> Self {
+        Self {
+            round_number,
+            since: now,
+            ready: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn ready(&mut self, player_id: PlayerId) {
+        self.ready.insert(player_id);
+    }
+
+    /// How many of the players the table waits on are ready.
+    pub(crate) fn ready_count(&self, waiting_on: &[PlayerId]) -> usize {
+        waiting_on.iter().filter(|p| self.ready.contains(p)).count()
+    }
+
+    /// Whether the next round can start: everyone waited on is ready, or
+    /// the wait timed out.
+    pub(crate) fn is_over(&self, waiting_on: &[PlayerId], flow: &RoundFlow, now: Instant) -> bool {
+        self.ready_count(waiting_on) == waiting_on.len()
+            || now.duration_since(self.since) >= flow.ready_timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intermission() {
+        let flow = RoundFlow::default();
+        let start = Instant::now();
+        let players = [PlayerId::new(), PlayerId::new()];
+        let mut intermission = Intermission::new(1, start);
+
+        intermission.ready(players[0]);
+        intermission.ready(PlayerId::new());
+        assert_eq!(intermission.ready_count(&players), 1);
+        assert!(!intermission.is_over(&players, &flow, start));
+        assert!(intermission.is_over(&players, &flow, start + flow.ready_timeout));
+
+        intermission.ready(players[1]);
+        assert!(intermission.is_over(&players, &flow, start));
+        // Nobody to wait on, e.g. a table of bots
+        assert!(Intermission::new(2, start).is_over(&[], &flow, start));
+    }
+}
@@ -0,0 +1,245 @@
+//! Games spread over several `ProtocolEngine`s, each behind its own lock, so
+//! a slow or busy table only holds up the games that share its shard rather
+//! than every game on the server.
+//!
+//! A game's shard follows from its id, so messages about a game go straight
+//! to the engine that has it. Lookups by join code or session token ask each
+//! shard in turn, new games are dealt out round robin, and the quick play
+//! queue lives on the first shard, which also seats the games it matches.
+//! The shards rank players on the same leaderboards, which any of them reads.
+
+use crate::leaderboard::Leaderboards;
+use crate::webhook::LifecycleEvent;
+use crate::{GameId, GameSummary, JoinCode, Message, ProtocolEngine, Response};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, RwLock};
+
+/// Shards a `GameServer` starts with.
+pub const DEFAULT_SHARDS: usize = 16;
+
+/// Where an engine sits among the shards of a server.
+#[derive(Debug, Clone)]
+pub(crate) struct Place {
+    pub(crate) index: usize,
+    pub(crate) count: usize,
+    /// Join codes in use on any shard, so no two games share one
+    codes: Arc<Mutex<HashSet<JoinCode>>>,
+}
+
+impl Place {
+    pub(crate) fn owns(&self, game_id: GameId) -> bool {
+        game_id.shard(self.count) == self.index
+    }
+
+    /// Takes the code for one of this shard's games. False if another game
+    /// has it already.
+    pub(crate) fn claim(&self, code: &JoinCode) -> bool {
+        self.codes.lock().unwrap().insert(code.clone())
+    }
+
+    pub(crate) fn release(&self, code: &JoinCode) {
+        self.codes.lock().unwrap().remove(code);
+    }
+}
+
+pub(crate) struct Shards {
+    engines: Vec<RwLock<ProtocolEngine>>,
+    /// Shard the next new game goes to
+    next: AtomicUsize,
+}
+
+impl Shards {
+    pub(crate) fn new(count: usize, lifecycle: broadcast::Sender<LifecycleEvent>) -> Self {
+        assert!(count > 0, "A server needs at least one shard");
+        let codes = Arc::new(Mutex::new(HashSet::new()));
+        let leaderboards = Arc::new(Mutex::new(Leaderboards::default()));
+        let engines = (0..count)
+            .map(|index| {
+                let place = Place {
+                    index,
+                    count,
+                    codes: codes.clone(),
+                };
+                RwLock::new(ProtocolEngine::in_shard(place, lifecycle.clone(), leaderboards.clone()))
+            })
+            .collect();
+        Self {
+            engines,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn all(&self) -> &[RwLock<ProtocolEngine>] {
+        &self.engines
+    }
+
+    /// The engine that has, or would have, the game.
+    pub(crate) fn of(&self, game_id: GameId) -> &RwLock<ProtocolEngine> {
+        &self.engines[game_id.shard(self.engines.len())]
+    }
+
+    /// The engine running the quick play queue.
+    pub(crate) fn lobby(&self) -> &RwLock<ProtocolEngine> {
+        &self.engines[0]
+    }
+
+    /// The engine the next new game goes to.
+    fn next(&self) -> &RwLock<ProtocolEngine> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.engines[index % self.engines.len()]
+    }
+
+    /// The engine that handles the message, or `None` for one every shard
+    /// answers a part of.
+    pub(crate) async fn route(&self, message: &Message) -> Option<&RwLock<ProtocolEngine>> {
+        let engine = match message {
+            Message::JoinGame {
+                game_id: Some(game_id),
+                ..
+            } => self.of(*game_id),
+            Message::JoinGame {
+                code: Some(code), ..
+            } => {
+                self.find(|engine| engine.game_with_code(code).is_some())
+                    .await
+            }
+            Message::JoinGame { .. } | Message::CreateGame { .. } => self.next(),
+            Message::Reconnect { token } => {
+                self.find(|engine| engine.sessions.contains_key(token))
+                    .await
+            }
+            Message::StartGame { game_id, .. }
+            | Message::MakeMove { game_id, .. }
+            | Message::GetGameState { game_id, .. }
+            | Message::Spectate { game_id, .. }
+            | Message::AckState { game_id, .. }
+            | Message::LeaveGame { game_id, .. }
+            | Message::SyncState { game_id, .. }
+            | Message::VoteSkipTurn { game_id, .. }
+            | Message::AddBot { game_id, .. }
+            | Message::InvitePlayer { game_id, .. }
+            | Message::KickPlayer { game_id, .. }
+            | Message::TransferHost { game_id, .. }
+            | Message::CloseGame { game_id, .. }
+            | Message::RequestRematch { game_id, .. }
+            | Message::Ready { game_id, .. }
+            | Message::Reaction { game_id, .. } => self.of(*game_id),
+            Message::QuickPlay { .. }
+            | Message::RankedQuickPlay { .. }
+            | Message::LeaveQueue { .. }
+            | Message::CreateTournament { .. }
+            | Message::JoinTournament { .. }
+            | Message::GetTournament { .. } => self.lobby(),
+            Message::Hello { .. }
+            | Message::Authenticate { .. }
+            | Message::NegotiateEncoding { .. }
+            | Message::GetLeaderboard { .. }
+            | Message::StartSoloGame { .. }
+            | Message::SubmitSoloGame { .. }
+            | Message::AddFriend { .. }
+            | Message::RemoveFriend { .. }
+            | Message::ListFriends
+            | Message::InviteFriend { .. }
+            | Message::Ping => {
+                self.next()
+            }
+            Message::ListGames => return None,
+        };
+        Some(engine)
+    }
+
+    /// The first engine the test holds for, or the lobby's, which will
+    /// answer that nothing was found.
+    async fn find(&self, test: impl Fn(&ProtocolEngine) -> bool) -> &RwLock<ProtocolEngine> {
+        for engine in &self.engines {
+            if test(&*engine.read().await) {
+                return engine;
+            }
+        }
+        self.lobby()
+    }
+
+    /// The public games of every shard, in one `GameList`.
+    pub(crate) async fn list_games(&self) -> Response {
+        let mut games: Vec<GameSummary> = Vec::new();
+        for engine in &self.engines {
+            if let Response::GameList { games: listed } = engine.read().await.list_games() {
+                games.extend(listed);
+            }
+        }
+        games.sort_by_key(|summary| (summary.status, summary.game_id));
+        Response::GameList { games }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameServer, Visibility};
+
+    fn join(code: Option<JoinCode>) -> Message {
+        Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code,
+            access: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_games_spread_over_shards() {
+        let server = GameServer::with_shards(4);
+        let mut codes = HashSet::new();
+        for _ in 0..4 {
+            match server
+                .handle_message(Message::CreateGame {
+                    rules: None,
+                    visibility: Visibility::Public,
+                    turn_clock: None,
+                })
+                .await
+            {
+                Response::GameCreated { join_code, .. } => assert!(codes.insert(join_code)),
+                other => panic!("Expected GameCreated response, got {:?}", other),
+            }
+        }
+        for engine in server.shards.all() {
+            assert_eq!(engine.read().await.games.len(), 1);
+        }
+        match server.handle_message(Message::ListGames).await {
+            Response::GameList { games } => assert_eq!(games.len(), 4),
+            other => panic!("Expected GameList response, got {:?}", other),
+        }
+
+        // Join codes and session tokens are found on whichever shard has them
+        for code in codes {
+            let (game_id, token) = match server.handle_message(join(Some(code))).await {
+                Response::GameJoined {
+                    game_id,
+                    session_token: Some(token),
+                    ..
+                } => (game_id, token),
+                other => panic!("Expected GameJoined response, got {:?}", other),
+            };
+            assert!(server
+                .shards
+                .of(game_id)
+                .read()
+                .await
+                .games
+                .contains_key(&game_id));
+            match server.handle_message(Message::Reconnect { token }).await {
+                Response::Reconnected { game_id: id, .. } => assert_eq!(id, game_id),
+                other => panic!("Expected Reconnected response, got {:?}", other),
+            }
+        }
+        assert!(matches!(
+            server.handle_message(join(Some(JoinCode::random()))).await,
+            Response::Error { .. }
+        ));
+    }
+}
@@ -0,0 +1,1441 @@
+//! Persistence of games, so those in flight survive a server restart. A
+//! `GameServer` given a `GameStore` restores the games saved in it, then
+//! saves whatever a message changes before answering it, and deletes every
+//! game closed or expired; see `GameServer::save_games` for the changes made
+//! between messages, e.g. by bots and turn clocks.
+//!
+//! Each game is saved as a `ServerSnapshot` of that game alone, so its
+//! sessions, join code and host come back with it. As with a handover, bot
+//! seats are left to time out after a restart. Players' ratings, leaderboard
+//! records and friend lists are saved along with the games, those that
+//! changed with each save; see `ratings`, `leaderboard` and `friends`.
+//!
+//! With the `sqlite` feature, `SqliteStore` keeps games in an SQLite database:
+//! a snapshot of each, and its event log, appended to as the game goes on.
+//!
+//! With the `redis` feature, `RedisStore` keeps them in Redis, where several
+//! server instances behind a load balancer can share them. A message for a
+//! game another instance saved since, or one this instance never held, is
+//! handled on the game as last saved. Saves are optimistic: one only goes
+//! through if nobody saved the game since the instance last loaded or saved
+//! it. If somebody did, the game changed on two instances at once: the first
+//! save wins, and the other instance takes the game as it was saved, resyncs
+//! its clients and answers the message that lost with an error. Ratings,
+//! records and friend lists are merged instead: what changed on an instance
+//! since it last loaded or saved an account's is applied to what was saved of
+//! it since; see `Rebase`.
+
+use crate::friends::FriendList;
+use crate::i18n::{text, Text};
+use crate::leaderboard::PlayerRecord;
+use crate::{GameId, GameServer, Message, ProtocolEngine, Rating, Response, ServerSnapshot};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedMutexGuard, RwLock};
+
+/// Where games are saved.
+pub trait GameStore: Send + Sync {
+    /// Saves the game, replacing what was saved of it before, unless it
+    /// was saved by another server since.
+    fn save(&self, game_id: GameId, snapshot: &ServerSnapshot) -> Result<Saved, String>;
+    /// The game as last saved, if it was.
+    fn load(&self, game_id: GameId) -> Result<Option<ServerSnapshot>, String>;
+    /// The game as last saved, if another server saved it since this one
+    /// last loaded or saved it. Stores only one server uses never have newer
+    /// games than it.
+    fn load_newer(&self, _game_id: GameId) -> Result<Option<ServerSnapshot>, String> {
+        Ok(None)
+    }
+    /// Every game saved.
+    fn list(&self) -> Result<Vec<GameId>, String>;
+    fn delete(&self, game_id: GameId) -> Result<(), String>;
+    /// Every rated player's rating, by account. Stores that don't keep
+    /// ratings have none.
+    fn load_ratings(&self) -> Result<HashMap<String, Rating>, String> {
+        Ok(HashMap::new())
+    }
+    /// Saves the players' ratings, replacing what was saved of theirs.
+    fn save_ratings(&self, _ratings: &[(String, Rating)]) -> Result<(), String> {
+        Ok(())
+    }
+    /// Every player's leaderboard record, by account, as for ratings.
+    fn load_records(&self) -> Result<HashMap<String, PlayerRecord>, String> {
+        Ok(HashMap::new())
+    }
+    /// Saves the players' leaderboard records, replacing what was saved of
+    /// theirs.
+    fn save_records(&self, _records: &[(String, PlayerRecord)]) -> Result<(), String> {
+        Ok(())
+    }
+    /// Every signed-in player's friend list, by account, as for ratings.
+    fn load_friends(&self) -> Result<HashMap<String, FriendList>, String> {
+        Ok(HashMap::new())
+    }
+    /// Saves the players' friend lists, replacing what was saved of theirs.
+    fn save_friends(&self, _friends: &[(String, FriendList)]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A value saved by account, which several servers sharing a store may change
+/// at once.
+pub trait Rebase: Default {
+    /// The value with what changed from `base` to it applied to `saved`
+    /// instead.
+    fn rebase(&self, base: &Self, saved: &Self) -> Self;
+}
+
+/// What became of a save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saved {
+    Saved,
+    /// Another server sharing the store saved the game since this one last
+    /// loaded or saved it, so this save was dropped
+    Outdated,
+}
+
+/// Games an engine changed or forgot since they were last saved.
+#[derive(Debug, Default)]
+pub(crate) struct Unsaved {
+    changed: HashSet<GameId>,
+    forgotten: HashSet<GameId>,
+}
+
+impl Unsaved {
+    pub(crate) fn changed(&mut self, game_id: GameId) {
+        self.forgotten.remove(&game_id);
+        self.changed.insert(game_id);
+    }
+
+    pub(crate) fn forgotten(&mut self, game_id: GameId) {
+        self.changed.remove(&game_id);
+        self.forgotten.insert(game_id);
+    }
+}
+
+/// A lock on each game being saved, held from taking what to save of it until
+/// the store has it, so that saves of a game reach the store in the order they
+/// were taken without its shard being held while they do.
+#[derive(Default)]
+pub(crate) struct SaveLocks(Mutex<HashMap<GameId, Arc<tokio::sync::Mutex<()>>>>);
+
+impl SaveLocks {
+    fn of(&self, game_id: GameId) -> Arc<tokio::sync::Mutex<()>> {
+        self.0.lock().unwrap().entry(game_id).or_default().clone()
+    }
+
+    async fn lock(&self, game_id: GameId) -> OwnedMutexGuard<()> {
+        self.of(game_id).lock_owned().await
+    }
+
+    fn try_lock(&self, game_id: GameId) -> Option<OwnedMutexGuard<()>> {
+        self.of(game_id).try_lock_owned().ok()
+    }
+
+    /// Forgets the locks nobody holds or waits for.
+    fn prune(&self) {
+        self.0.lock().unwrap().retain(|_, lock| Arc::strong_count(lock) > 1);
+    }
+}
+
+impl ProtocolEngine {
+    /// Starts keeping track of the games to save.
+    fn track_unsaved(&mut self) {
+        self.unsaved.get_or_insert_with(Unsaved::default);
+    }
+
+    /// Each game to save, with what to save of it, or `None` to delete it.
+    fn take_unsaved(&mut self) -> Vec<(GameId, Option<ServerSnapshot>)> {
+        let Some(unsaved) = self.unsaved.as_mut().map(std::mem::take) else {
+            return Vec::new();
+        };
+        let mut games: Vec<_> = unsaved
+            .changed
+            .into_iter()
+            .map(|game_id| (game_id, self.export_game(game_id)))
+            .chain(unsaved.forgotten.into_iter().map(|game_id| (game_id, None)))
+            .collect();
+        games.sort_by_key(|(game_id, _)| *game_id);
+        games
+    }
+
+    /// What to save of the game, as for `take_unsaved`, if it is to be saved.
+    fn take_unsaved_game(&mut self, game_id: GameId) -> Option<(GameId, Option<ServerSnapshot>)> {
+        let unsaved = self.unsaved.as_mut()?;
+        if unsaved.changed.remove(&game_id) {
+            Some((game_id, self.export_game(game_id)))
+        } else if unsaved.forgotten.remove(&game_id) {
+            Some((game_id, None))
+        } else {
+            None
+        }
+    }
+
+    /// Takes the games as another server saved them, or as they were before a
+    /// change that failed to save, and sends them to their followers.
+    fn reload(&mut self, snapshot: ServerSnapshot) {
+        // Seats and invites the snapshot doesn't have are gone with the change
+        self.sessions.retain(|_, (game_id, _)| !snapshot.games.contains_key(game_id));
+        self.invites.retain(|_, game_id| !snapshot.games.contains_key(game_id));
+        for game_id in self.import_games(snapshot) {
+            if let Some(game) = self.games.get(&game_id) {
+                let update = Response::StateUpdate {
+                    game_id,
+                    game_state: Box::new(game.public_view()),
+                };
+                self.notify(game_id, update);
+            }
+        }
+    }
+
+    /// Has the game saved, or deleted if it is gone, with the next save.
+    fn mark_unsaved(&mut self, game_id: GameId) {
+        let exists = self.games.contains_key(&game_id);
+        if let Some(unsaved) = &mut self.unsaved {
+            if exists {
+                unsaved.changed(game_id);
+            } else {
+                unsaved.forgotten(game_id);
+            }
+        }
+    }
+}
+
+impl GameServer {
+    /// Restores the games saved in the store, next to any the server has, and
+    /// saves games to it from now on. Returns the ids of the restored games.
+    pub async fn set_store(&self, store: Arc<dyn GameStore>) -> Result<Vec<GameId>, String> {
+        let reading = store.clone();
+        let (saved, ratings, records, friends) = tokio::task::spawn_blocking(move || {
+            let mut saved = ServerSnapshot::default();
+            for game_id in reading.list()? {
+                if let Some(game) = reading.load(game_id)? {
+                    saved.merge(game);
+                }
+            }
+            let (ratings, records) = (reading.load_ratings()?, reading.load_records()?);
+            Ok::<_, String>((saved, ratings, records, reading.load_friends()?))
+        })
+        .await
+        .map_err(|err| err.to_string())??;
+
+        for engine in self.shards.all() {
+            engine.write().await.track_unsaved();
+        }
+        let mut lobby = self.shards.lobby().write().await;
+        lobby.ratings.load(ratings);
+        lobby.leaderboards.lock().unwrap().load(records);
+        drop(lobby);
+        self.friends.lock().unwrap().load(friends);
+        *self.store.write().unwrap() = Some(store);
+        Ok(self.import_games(saved).await)
+    }
+
+    /// Saves the games that changed since the last save to the store, and
+    /// deletes those that were closed or expired, then saves the ratings,
+    /// leaderboard records and friend lists that changed. Returns how many games it saved or
+    /// deleted. Whatever failed to save is tried again with the next save, and
+    /// games another server saved first are reloaded. `run_timers` saves
+    /// every tick. Games a message is being handled on are left for it, or
+    /// the next save.
+    pub async fn save_games(&self) -> Result<usize, String> {
+        let Some(store) = self.store.read().unwrap().clone() else {
+            return Ok(0);
+        };
+        self.save_locks.prune();
+        let mut unsaved = Vec::new();
+        let mut saving = Vec::new();
+        for engine in self.shards.all() {
+            let mut engine = engine.write().await;
+            for (game_id, snapshot) in engine.take_unsaved() {
+                match self.save_locks.try_lock(game_id) {
+                    Some(lock) => {
+                        saving.push(lock);
+                        unsaved.push((game_id, snapshot));
+                    }
+                    None => engine.mark_unsaved(game_id),
+                }
+            }
+        }
+        let (ratings, records) = {
+            let mut lobby = self.shards.lobby().write().await;
+            let records = lobby.leaderboards.lock().unwrap().take_unsaved();
+            (lobby.ratings.take_unsaved(), records)
+        };
+        let friends = self.friends.lock().unwrap().take_unsaved();
+        if unsaved.is_empty() && ratings.is_empty() && records.is_empty() && friends.is_empty() {
+            return Ok(0);
+        }
+
+        let count = unsaved.len();
+        let saved = tokio::task::spawn_blocking(move || {
+            let GamesSaved {
+                failed,
+                newer,
+                mut error,
+            } = save_each(&*store, unsaved);
+            let ratings_failed = match store.save_ratings(&ratings) {
+                Ok(()) => Vec::new(),
+                Err(err) => {
+                    error = Some(err);
+                    ratings.into_iter().map(|(account, _)| account).collect()
+                }
+            };
+            let records_failed = match store.save_records(&records) {
+                Ok(()) => Vec::new(),
+                Err(err) => {
+                    error = Some(err);
+                    records.into_iter().map(|(account, _)| account).collect()
+                }
+            };
+            let friends_failed = match store.save_friends(&friends) {
+                Ok(()) => Vec::new(),
+                Err(err) => {
+                    error = Some(err);
+                    friends.into_iter().map(|(account, _)| account).collect()
+                }
+            };
+            (failed, newer, ratings_failed, records_failed, friends_failed, error)
+        })
+        .await
+        .map_err(|err| err.to_string())?;
+        let (failed, newer, ratings_failed, records_failed, friends_failed, error) = saved;
+
+        for game_id in failed {
+            self.shards.of(game_id).write().await.mark_unsaved(game_id);
+        }
+        if !ratings_failed.is_empty() || !records_failed.is_empty() {
+            let mut lobby = self.shards.lobby().write().await;
+            lobby.ratings.mark_unsaved(ratings_failed);
+            lobby.leaderboards.lock().unwrap().mark_unsaved(records_failed);
+        }
+        self.friends.lock().unwrap().mark_unsaved(friends_failed);
+        for (game_id, snapshot) in newer {
+            self.shards.of(game_id).write().await.reload(snapshot);
+        }
+        match error {
+            Some(err) => Err(err),
+            None => Ok(count),
+        }
+    }
+
+    /// Handles a message on the engine, through the store if there is one:
+    /// the message's game is loaded first if this server doesn't hold it, or
+    /// another server saved it since, and what the message changed of the game
+    /// it is for, or created or joined, is saved before it is answered. If
+    /// that doesn't save, the change is undone and the message answered with
+    /// an error. Other games it changed are left for the next save.
+    ///
+    /// The engine is only locked to handle the message, not while the store
+    /// is read or written: the game is locked instead, so that messages for
+    /// the same game still take turns. The store's own check catches saves
+    /// of the game by other servers in the meantime.
+    pub(crate) async fn handle_stored(
+        &self,
+        engine: &RwLock<ProtocolEngine>,
+        message: Message,
+        handle: impl FnOnce(&mut ProtocolEngine, Message) -> Response,
+    ) -> Response {
+        let Some(store) = self.store.read().unwrap().clone() else {
+            return handle(&mut *engine.write().await, message);
+        };
+        let mut target = message.game_id();
+        let mut turn = None;
+        if let Some(game_id) = target {
+            turn = Some(self.save_locks.lock(game_id).await);
+            let held = engine.read().await.games.contains_key(&game_id);
+            let reading = store.clone();
+            let loaded = tokio::task::spawn_blocking(move || match held {
+                true => reading.load_newer(game_id),
+                false => reading.load(game_id),
+            })
+            .await
+            .map_err(|err| err.to_string());
+            match loaded.and_then(|loaded| loaded) {
+                Ok(Some(snapshot)) => engine.write().await.reload(snapshot),
+                Ok(None) => {}
+                Err(err) => return Response::Error { message: err.into() },
+            }
+        }
+
+        let mut handling = engine.write().await;
+        let before = target.and_then(|game_id| handling.export_game(game_id));
+        let response = handle(&mut handling, message);
+        if target.is_none() {
+            target = match &response {
+                Response::GameCreated { game_id, .. } | Response::GameJoined { game_id, .. } => Some(*game_id),
+                _ => None,
+            };
+            if let Some(game_id) = target {
+                // The game is only locked after the engine, so wait for it
+                // without holding the engine
+                drop(handling);
+                turn = Some(self.save_locks.lock(game_id).await);
+                handling = engine.write().await;
+            }
+        }
+        let Some(unsaved) = target.and_then(|game_id| handling.take_unsaved_game(game_id)) else {
+            return response;
+        };
+        drop(handling);
+        let saved = tokio::task::spawn_blocking(move || save_each(&*store, vec![unsaved]))
+            .await
+            .map_err(|err| err.to_string());
+        let GamesSaved { failed, newer, error } = match saved {
+            Ok(saved) => saved,
+            Err(err) => return Response::Error { message: err.into() },
+        };
+
+        let mut engine = engine.write().await;
+        let mut lost = None;
+        for game_id in failed {
+            lost = error.clone().map(Text::from);
+            match &before {
+                Some(before) => engine.reload(before.clone()),
+                // Created by the message, so it is kept for the next save
+                None => engine.mark_unsaved(game_id),
+            }
+        }
+        for (_, snapshot) in newer {
+            lost = Some(text!("game_changed_elsewhere"));
+            engine.reload(snapshot);
+        }
+        drop(turn);
+        match lost {
+            Some(message) => Response::Error { message },
+            None => response,
+        }
+    }
+}
+
+/// What became of saving games to a store.
+struct GamesSaved {
+    /// Games that failed to save, to try again
+    failed: Vec<GameId>,
+    /// Games another server saved first, as it saved them
+    newer: Vec<(GameId, ServerSnapshot)>,
+    error: Option<String>,
+}
+
+/// Saves each game, or deletes it if it is gone.
+fn save_each(store: &dyn GameStore, unsaved: Vec<(GameId, Option<ServerSnapshot>)>) -> GamesSaved {
+    let mut failed = Vec::new();
+    let mut newer = Vec::new();
+    let mut error = None;
+    for (game_id, snapshot) in unsaved {
+        let saved = match &snapshot {
+            Some(snapshot) => store.save(game_id, snapshot),
+            None => store.delete(game_id).map(|()| Saved::Saved),
+        };
+        let reloaded = match saved {
+            Ok(Saved::Saved) => continue,
+            Ok(Saved::Outdated) => store.load(game_id),
+            Err(err) => Err(err),
+        };
+        match reloaded {
+            Ok(Some(snapshot)) => newer.push((game_id, snapshot)),
+            // Closed by the other server in the meantime
+            Ok(None) => {}
+            Err(err) => {
+                failed.push(game_id);
+                error = Some(err);
+            }
+        }
+    }
+    GamesSaved { failed, newer, error }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{GameStore, Saved};
+    use crate::friends::FriendList;
+    use crate::leaderboard::PlayerRecord;
+    use crate::{GameId, Rating, ServerSnapshot};
+    use game_core::events::GameEvent;
+    use rusqlite::{params, Connection, OptionalExtension};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS games (
+            game_id TEXT PRIMARY KEY,
+            -- The game's ServerSnapshot, with the events of its state left out
+            snapshot TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS events (
+            game_id TEXT NOT NULL,
+            seq INTEGER NOT NULL,
+            event TEXT NOT NULL,
+            PRIMARY KEY (game_id, seq)
+        );
+        CREATE TABLE IF NOT EXISTS ratings (
+            account TEXT PRIMARY KEY,
+            rating REAL NOT NULL,
+            games INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS records (
+            account TEXT PRIMARY KEY,
+            -- The player's PlayerRecord
+            record TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS friends (
+            account TEXT PRIMARY KEY,
+            -- The player's FriendList
+            list TEXT NOT NULL
+        );";
+
+    fn sql_error(err: rusqlite::Error) -> String {
+        format!("Game store error: {}", err)
+    }
+
+    fn json(value: &impl serde::Serialize) -> Result<String, String> {
+        serde_json::to_string(value).map_err(|err| err.to_string())
+    }
+
+    /// Keeps games in an SQLite database: a snapshot of each, and its event
+    /// log, which a save only appends the new events to.
+    pub struct SqliteStore {
+        connection: Mutex<Connection>,
+    }
+
+    impl SqliteStore {
+        /// Opens the database at the path, creating it if need be.
+        pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+            Self::with(Connection::open(path))
+        }
+
+        /// A database in memory, gone once the store is dropped.
+        pub fn open_in_memory() -> Result<Self, String> {
+            Self::with(Connection::open_in_memory())
+        }
+
+        fn with(connection: rusqlite::Result<Connection>) -> Result<Self, String> {
+            let connection = connection.map_err(sql_error)?;
+            connection.execute_batch(SCHEMA).map_err(sql_error)?;
+            Ok(Self {
+                connection: Mutex::new(connection),
+            })
+        }
+    }
+
+    impl GameStore for SqliteStore {
+        fn save(&self, game_id: GameId, snapshot: &ServerSnapshot) -> Result<Saved, String> {
+            let mut snapshot = snapshot.clone();
+            let game = snapshot
+                .games
+                .get_mut(&game_id)
+                .ok_or("The snapshot doesn't have the game")?;
+            let events = std::mem::take(&mut game.events);
+            let id = game_id.to_string();
+
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(sql_error)?;
+            let (logged, last): (i64, Option<String>) = tx
+                .query_row(
+                    "SELECT COUNT(*), (SELECT event FROM events WHERE game_id = ?1 ORDER BY seq DESC LIMIT 1)
+                     FROM events WHERE game_id = ?1",
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(sql_error)?;
+            // The log only grows, unless the state was replaced, e.g. by a `SyncState`
+            let logged = logged as usize;
+            let continues = match logged.checked_sub(1) {
+                None => true,
+                Some(last_seq) => match events.get(last_seq) {
+                    Some(event) => Some(json(event)?) == last,
+                    None => false,
+                },
+            };
+            let from = if continues {
+                logged
+            } else {
+                tx.execute("DELETE FROM events WHERE game_id = ?1", params![id])
+                    .map_err(sql_error)?;
+                0
+            };
+            {
+                let mut insert = tx
+                    .prepare("INSERT INTO events (game_id, seq, event) VALUES (?1, ?2, ?3)")
+                    .map_err(sql_error)?;
+                for (seq, event) in events.iter().enumerate().skip(from) {
+                    insert
+                        .execute(params![id, seq as i64, json(event)?])
+                        .map_err(sql_error)?;
+                }
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO games (game_id, snapshot) VALUES (?1, ?2)",
+                params![id, json(&snapshot)?],
+            )
+            .map_err(sql_error)?;
+            tx.commit().map_err(sql_error)?;
+            Ok(Saved::Saved)
+        }
+
+        fn load(&self, game_id: GameId) -> Result<Option<ServerSnapshot>, String> {
+            let id = game_id.to_string();
+            let connection = self.connection.lock().unwrap();
+            let Some(snapshot) = connection
+                .query_row(
+                    "SELECT snapshot FROM games WHERE game_id = ?1",
+                    params![id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+                .map_err(sql_error)?
+            else {
+                return Ok(None);
+            };
+            let mut snapshot: ServerSnapshot =
+                serde_json::from_str(&snapshot).map_err(|err| err.to_string())?;
+
+            let mut select = connection
+                .prepare("SELECT event FROM events WHERE game_id = ?1 ORDER BY seq")
+                .map_err(sql_error)?;
+            let events = select
+                .query_map(params![id], |row| row.get::<_, String>(0))
+                .map_err(sql_error)?
+                .map(|event| {
+                    let event = event.map_err(sql_error)?;
+                    serde_json::from_str::<GameEvent>(&event).map_err(|err| err.to_string())
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            if let Some(game) = snapshot.games.get_mut(&game_id) {
+                game.events = events;
+            }
+            Ok(Some(snapshot))
+        }
+
+        fn list(&self) -> Result<Vec<GameId>, String> {
+            let connection = self.connection.lock().unwrap();
+            let mut select = connection
+                .prepare("SELECT game_id FROM games ORDER BY game_id")
+                .map_err(sql_error)?;
+            let ids = select
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(sql_error)?
+                .map(|id| {
+                    let id = id.map_err(sql_error)?;
+                    id.parse().map_err(|_| format!("Invalid game id {}", id))
+                })
+                .collect();
+            ids
+        }
+
+        fn delete(&self, game_id: GameId) -> Result<(), String> {
+            let id = game_id.to_string();
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(sql_error)?;
+            tx.execute("DELETE FROM events WHERE game_id = ?1", params![id])
+                .map_err(sql_error)?;
+            tx.execute("DELETE FROM games WHERE game_id = ?1", params![id])
+                .map_err(sql_error)?;
+            tx.commit().map_err(sql_error)
+        }
+
+        fn load_ratings(&self) -> Result<HashMap<String, Rating>, String> {
+            let connection = self.connection.lock().unwrap();
+            let mut select = connection
+                .prepare("SELECT account, rating, games FROM ratings")
+                .map_err(sql_error)?;
+            let ratings = select
+                .query_map([], |row| {
+                    let rating = Rating {
+                        rating: row.get(1)?,
+                        games: row.get(2)?,
+                    };
+                    Ok((row.get::<_, String>(0)?, rating))
+                })
+                .map_err(sql_error)?
+                .collect::<rusqlite::Result<_>>()
+                .map_err(sql_error);
+            ratings
+        }
+
+        fn save_ratings(&self, ratings: &[(String, Rating)]) -> Result<(), String> {
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(sql_error)?;
+            {
+                let mut upsert = tx
+                    .prepare("INSERT OR REPLACE INTO ratings (account, rating, games) VALUES (?1, ?2, ?3)")
+                    .map_err(sql_error)?;
+                for (account, rating) in ratings {
+                    upsert
+                        .execute(params![account, rating.rating, rating.games])
+                        .map_err(sql_error)?;
+                }
+            }
+            tx.commit().map_err(sql_error)
+        }
+
+        fn load_records(&self) -> Result<HashMap<String, PlayerRecord>, String> {
+            self.load_by_account("SELECT account, record FROM records")
+        }
+
+        fn save_records(&self, records: &[(String, PlayerRecord)]) -> Result<(), String> {
+            self.save_by_account("INSERT OR REPLACE INTO records (account, record) VALUES (?1, ?2)", records)
+        }
+
+        fn load_friends(&self) -> Result<HashMap<String, FriendList>, String> {
+            self.load_by_account("SELECT account, list FROM friends")
+        }
+
+        fn save_friends(&self, friends: &[(String, FriendList)]) -> Result<(), String> {
+            self.save_by_account("INSERT OR REPLACE INTO friends (account, list) VALUES (?1, ?2)", friends)
+        }
+    }
+
+    impl SqliteStore {
+        /// Every row `select` reads, of an account and a value as JSON.
+        fn load_by_account<T: DeserializeOwned>(&self, select: &str) -> Result<HashMap<String, T>, String> {
+            let connection = self.connection.lock().unwrap();
+            let mut select = connection.prepare(select).map_err(sql_error)?;
+            let rows = select
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+                .map_err(sql_error)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(sql_error)?;
+            rows.into_iter()
+                .map(|(account, value)| {
+                    let value = serde_json::from_str(&value).map_err(|err| err.to_string())?;
+                    Ok((account, value))
+                })
+                .collect()
+        }
+
+        /// Runs `upsert` for each account with its value as JSON, in one
+        /// transaction.
+        fn save_by_account<T: Serialize>(&self, upsert: &str, values: &[(String, T)]) -> Result<(), String> {
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(sql_error)?;
+            {
+                let mut upsert = tx.prepare(upsert).map_err(sql_error)?;
+                for (account, value) in values {
+                    upsert.execute(params![account, json(value)?]).map_err(sql_error)?;
+                }
+            }
+            tx.commit().map_err(sql_error)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use game_core::GameState;
+
+        #[test]
+        fn test_sqlite_store() {
+            let store = SqliteStore::open_in_memory().unwrap();
+            let game_id = GameId::new();
+            let mut game = GameState::new();
+            game.add_player("p1".to_string(), "Alice".to_string());
+            let mut snapshot = ServerSnapshot::default();
+            snapshot.games.insert(game_id, game.clone());
+            store.save(game_id, &snapshot).unwrap();
+
+            // Saving again appends the new events to the log
+            game.start_round().unwrap();
+            snapshot.games.insert(game_id, game.clone());
+            store.save(game_id, &snapshot).unwrap();
+            let loaded = store.load(game_id).unwrap().unwrap();
+            assert_eq!(json(&loaded).unwrap(), json(&snapshot).unwrap());
+
+            // As does a state that was replaced, which starts the log over
+            let mut replaced = GameState::new();
+            replaced.add_player("p2".to_string(), "Bob".to_string());
+            replaced.start_round().unwrap();
+            snapshot.games.insert(game_id, replaced);
+            store.save(game_id, &snapshot).unwrap();
+            let loaded = store.load(game_id).unwrap().unwrap();
+            assert_eq!(json(&loaded).unwrap(), json(&snapshot).unwrap());
+
+            assert_eq!(store.list().unwrap(), vec![game_id]);
+            store.delete(game_id).unwrap();
+            assert!(store.load(game_id).unwrap().is_none());
+            assert!(store.list().unwrap().is_empty());
+
+            let rating = Rating {
+                rating: 1516.0,
+                games: 1,
+            };
+            store.save_ratings(&[("user-1".to_string(), rating)]).unwrap();
+            store.save_ratings(&[("user-1".to_string(), Rating { games: 2, ..rating })]).unwrap();
+            let ratings = store.load_ratings().unwrap();
+            assert_eq!(ratings, HashMap::from([("user-1".to_string(), Rating { games: 2, ..rating })]));
+
+            let record = PlayerRecord {
+                name: "Alice".to_string(),
+                wins: 3,
+                best_solo: Some(82),
+                ..PlayerRecord::default()
+            };
+            store.save_records(&[("user-1".to_string(), record.clone())]).unwrap();
+            assert_eq!(store.load_records().unwrap(), HashMap::from([("user-1".to_string(), record)]));
+
+            let list = FriendList {
+                name: "Alice".to_string(),
+                friends: ["user-2".to_string()].into(),
+                ..FriendList::default()
+            };
+            store.save_friends(&[("user-1".to_string(), list.clone())]).unwrap();
+            assert_eq!(store.load_friends().unwrap(), HashMap::from([("user-1".to_string(), list)]));
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+
+#[cfg(feature = "redis")]
+mod redis_store {
+    use super::{GameStore, Rebase, Saved};
+    use crate::friends::FriendList;
+    use crate::leaderboard::PlayerRecord;
+    use crate::{GameId, Rating, ServerSnapshot};
+    use game_core::events::GameEvent;
+    use redis::{Commands, Connection};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Set of the ids of every saved game
+    const GAMES_KEY: &str = "flip7:games";
+
+    /// Hash of a game's `version`, bumped by every save, and `snapshot`, its
+    /// `ServerSnapshot` with the events of its state left out
+    fn game_key(game_id: GameId) -> String {
+        format!("flip7:game:{}", game_id)
+    }
+
+    /// List of the game's events, oldest first
+    fn events_key(game_id: GameId) -> String {
+        format!("flip7:events:{}", game_id)
+    }
+
+    /// Hash of each rated player's `Rating`, by account
+    const RATINGS_KEY: &str = "flip7:ratings";
+
+    /// Hash of each player's leaderboard `PlayerRecord`, by account
+    const RECORDS_KEY: &str = "flip7:records";
+
+    /// Hash of each signed-in player's `FriendList`, by account
+    const FRIENDS_KEY: &str = "flip7:friends";
+
+    fn redis_error(err: redis::RedisError) -> String {
+        format!("Game store error: {}", err)
+    }
+
+    fn json(value: &impl serde::Serialize) -> Result<String, String> {
+        serde_json::to_string(value).map_err(|err| err.to_string())
+    }
+
+    fn invalid(err: serde_json::Error) -> redis::RedisError {
+        (redis::ErrorKind::TypeError, "Invalid saved value", err.to_string()).into()
+    }
+
+    /// Keeps games in Redis, for server instances sharing them. Saves are
+    /// optimistic: a game is only saved over if its version is still the one
+    /// this instance last loaded or saved. Values by account are rebased on
+    /// what was saved of them since instead.
+    pub struct RedisStore {
+        connection: Mutex<Connection>,
+        /// Version of each game this instance last loaded or saved
+        versions: Mutex<HashMap<GameId, u64>>,
+        /// Each value by account as this instance last loaded or saved it, as
+        /// JSON, by hash and account
+        bases: Mutex<HashMap<(&'static str, String), String>>,
+    }
+
+    impl RedisStore {
+        /// Connects to the server at the URL, e.g. `redis://127.0.0.1/`.
+        pub fn open(url: &str) -> Result<Self, String> {
+            let client = redis::Client::open(url).map_err(redis_error)?;
+            Ok(Self {
+                connection: Mutex::new(client.get_connection().map_err(redis_error)?),
+                versions: Mutex::new(HashMap::new()),
+                bases: Mutex::new(HashMap::new()),
+            })
+        }
+
+        fn known_version(&self, game_id: GameId) -> Option<u64> {
+            self.versions.lock().unwrap().get(&game_id).copied()
+        }
+    }
+
+    impl GameStore for RedisStore {
+        fn save(&self, game_id: GameId, snapshot: &ServerSnapshot) -> Result<Saved, String> {
+            let mut snapshot = snapshot.clone();
+            let game = snapshot
+                .games
+                .get_mut(&game_id)
+                .ok_or("The snapshot doesn't have the game")?;
+            let events = std::mem::take(&mut game.events);
+            let events: Vec<String> = events.iter().map(json).collect::<Result<_, _>>()?;
+            let snapshot = json(&snapshot)?;
+            let (key, events_key) = (game_key(game_id), events_key(game_id));
+            let expected = self.known_version(game_id);
+
+            let mut connection = self.connection.lock().unwrap();
+            // Retried from the top whenever the game changes between WATCH and EXEC
+            let saved = redis::transaction(&mut *connection, &[&key], |connection, pipe| {
+                let version: Option<u64> = connection.hget(&key, "version")?;
+                if version != expected {
+                    return Ok(Some(None));
+                }
+                // The log only grows, unless the state was replaced, e.g. by a `SyncState`
+                let logged: usize = connection.llen(&events_key)?;
+                let last: Option<String> = connection.lindex(&events_key, -1)?;
+                let continues = logged == 0
+                    || (logged <= events.len() && last.as_ref() == Some(&events[logged - 1]));
+                let from = if continues {
+                    logged
+                } else {
+                    pipe.del(&events_key).ignore();
+                    0
+                };
+                if from < events.len() {
+                    pipe.rpush(&events_key, &events[from..]).ignore();
+                }
+                let next = version.unwrap_or(0) + 1;
+                pipe.hset_multiple(
+                    &key,
+                    &[
+                        ("version", next.to_string()),
+                        ("snapshot", snapshot.clone()),
+                    ],
+                )
+                .ignore()
+                .sadd(GAMES_KEY, game_id.to_string())
+                .ignore();
+                let done: Option<()> = pipe.query(connection)?;
+                Ok(done.map(|()| Some(next)))
+            })
+            .map_err(redis_error)?;
+
+            match saved {
+                Some(version) => {
+                    self.versions.lock().unwrap().insert(game_id, version);
+                    Ok(Saved::Saved)
+                }
+                None => Ok(Saved::Outdated),
+            }
+        }
+
+        fn load(&self, game_id: GameId) -> Result<Option<ServerSnapshot>, String> {
+            let (key, events_key) = (game_key(game_id), events_key(game_id));
+            let mut connection = self.connection.lock().unwrap();
+            let (version, snapshot, events): (Option<u64>, Option<String>, Vec<String>) =
+                redis::pipe()
+                    .atomic()
+                    .hget(&key, "version")
+                    .hget(&key, "snapshot")
+                    .lrange(&events_key, 0, -1)
+                    .query(&mut *connection)
+                    .map_err(redis_error)?;
+            let (Some(version), Some(snapshot)) = (version, snapshot) else {
+                return Ok(None);
+            };
+
+            let mut snapshot: ServerSnapshot =
+                serde_json::from_str(&snapshot).map_err(|err| err.to_string())?;
+            let events = events
+                .iter()
+                .map(|event| {
+                    serde_json::from_str::<GameEvent>(event).map_err(|err| err.to_string())
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            if let Some(game) = snapshot.games.get_mut(&game_id) {
+                game.events = events;
+            }
+            self.versions.lock().unwrap().insert(game_id, version);
+            Ok(Some(snapshot))
+        }
+
+        fn load_newer(&self, game_id: GameId) -> Result<Option<ServerSnapshot>, String> {
+            let version: Option<u64> = {
+                let mut connection = self.connection.lock().unwrap();
+                connection.hget(game_key(game_id), "version").map_err(redis_error)?
+            };
+            // A game deleted since is left to the server that deleted it to close
+            if version.is_none() || version == self.known_version(game_id) {
+                return Ok(None);
+            }
+            self.load(game_id)
+        }
+
+        fn list(&self) -> Result<Vec<GameId>, String> {
+            let mut connection = self.connection.lock().unwrap();
+            let ids: Vec<String> = connection.smembers(GAMES_KEY).map_err(redis_error)?;
+            let mut ids = ids
+                .into_iter()
+                .map(|id| id.parse().map_err(|_| format!("Invalid game id {}", id)))
+                .collect::<Result<Vec<GameId>, String>>()?;
+            ids.sort();
+            Ok(ids)
+        }
+
+        fn delete(&self, game_id: GameId) -> Result<(), String> {
+            let (key, events_key) = (game_key(game_id), events_key(game_id));
+            let mut connection = self.connection.lock().unwrap();
+            let _: () = redis::pipe()
+                .atomic()
+                .del(&key)
+                .ignore()
+                .del(&events_key)
+                .ignore()
+                .srem(GAMES_KEY, game_id.to_string())
+                .ignore()
+                .query(&mut *connection)
+                .map_err(redis_error)?;
+            self.versions.lock().unwrap().remove(&game_id);
+            Ok(())
+        }
+
+        fn load_ratings(&self) -> Result<HashMap<String, Rating>, String> {
+            self.load_by_account(RATINGS_KEY)
+        }
+
+        fn save_ratings(&self, ratings: &[(String, Rating)]) -> Result<(), String> {
+            self.save_by_account(RATINGS_KEY, ratings)
+        }
+
+        fn load_records(&self) -> Result<HashMap<String, PlayerRecord>, String> {
+            self.load_by_account(RECORDS_KEY)
+        }
+
+        fn save_records(&self, records: &[(String, PlayerRecord)]) -> Result<(), String> {
+            self.save_by_account(RECORDS_KEY, records)
+        }
+
+        fn load_friends(&self) -> Result<HashMap<String, FriendList>, String> {
+            self.load_by_account(FRIENDS_KEY)
+        }
+
+        fn save_friends(&self, friends: &[(String, FriendList)]) -> Result<(), String> {
+            self.save_by_account(FRIENDS_KEY, friends)
+        }
+    }
+
+    impl RedisStore {
+        /// The values of a hash of JSON values by account.
+        fn load_by_account<T: DeserializeOwned>(&self, key: &'static str) -> Result<HashMap<String, T>, String> {
+            let mut connection = self.connection.lock().unwrap();
+            let values: HashMap<String, String> = connection.hgetall(key).map_err(redis_error)?;
+            let mut bases = self.bases.lock().unwrap();
+            values
+                .into_iter()
+                .map(|(account, value)| {
+                    let parsed = serde_json::from_str(&value).map_err(|err| err.to_string())?;
+                    bases.insert((key, account.clone()), value);
+                    Ok((account, parsed))
+                })
+                .collect()
+        }
+
+        /// Saves values to a hash of JSON values by account, each rebased on
+        /// what another instance saved of it since this one last loaded or
+        /// saved it.
+        fn save_by_account<T>(&self, key: &'static str, values: &[(String, T)]) -> Result<(), String>
+        where
+            T: Serialize + DeserializeOwned + Rebase,
+        {
+            if values.is_empty() {
+                return Ok(());
+            }
+            let accounts: Vec<&str> = values.iter().map(|(account, _)| account.as_str()).collect();
+            let ours = values
+                .iter()
+                .map(|(account, value)| Ok((account.clone(), json(value)?)))
+                .collect::<Result<Vec<(String, String)>, String>>()?;
+            let bases: Vec<Option<String>> = {
+                let bases = self.bases.lock().unwrap();
+                accounts.iter().map(|account| bases.get(&(key, account.to_string())).cloned()).collect()
+            };
+
+            let mut connection = self.connection.lock().unwrap();
+            // Retried from the top whenever the hash changes between WATCH and EXEC
+            redis::transaction(&mut *connection, &[key], |connection, pipe| {
+                let saved: Vec<Option<String>> = redis::cmd("HMGET").arg(key).arg(&accounts).query(connection)?;
+                let mut merged = Vec::with_capacity(values.len());
+                for (((account, value), saved), base) in values.iter().zip(&saved).zip(&bases) {
+                    let value = match (saved, base) {
+                        (Some(saved), base) if Some(saved) != base.as_ref() => {
+                            let saved: T = serde_json::from_str(saved).map_err(invalid)?;
+                            let base: T = match base {
+                                Some(base) => serde_json::from_str(base).map_err(invalid)?,
+                                None => T::default(),
+                            };
+                            serde_json::to_string(&value.rebase(&base, &saved)).map_err(invalid)?
+                        }
+                        _ => serde_json::to_string(value).map_err(invalid)?,
+                    };
+                    merged.push((account.clone(), value));
+                }
+                pipe.hset_multiple(key, &merged).ignore();
+                pipe.query::<Option<()>>(connection)
+            })
+            .map_err(redis_error)?;
+
+            // What this instance has of each is now what later changes are made to
+            let mut bases = self.bases.lock().unwrap();
+            for (account, value) in ours {
+                bases.insert((key, account), value);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Message, Response};
+    use std::collections::HashMap;
+    use std::sync::{mpsc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        games: Mutex<HashMap<GameId, ServerSnapshot>>,
+        /// Games another server saved since this one last loaded them
+        newer: Mutex<HashSet<GameId>>,
+        /// Games another server saves before this one next does
+        outdated: Mutex<HashSet<GameId>>,
+        records: Mutex<HashMap<String, PlayerRecord>>,
+        /// Makes the next save say it started, then wait to be let through
+        stall: Mutex<Option<(mpsc::Sender<()>, mpsc::Receiver<()>)>>,
+    }
+
+    impl GameStore for MemoryStore {
+        fn save(&self, game_id: GameId, snapshot: &ServerSnapshot) -> Result<Saved, String> {
+            let stall = self.stall.lock().unwrap().take();
+            if let Some((started, release)) = stall {
+                started.send(()).unwrap();
+                release.recv().unwrap();
+            }
+            if self.outdated.lock().unwrap().remove(&game_id) {
+                return Ok(Saved::Outdated);
+            }
+            self.games.lock().unwrap().insert(game_id, snapshot.clone());
+            Ok(Saved::Saved)
+        }
+
+        fn load(&self, game_id: GameId) -> Result<Option<ServerSnapshot>, String> {
+            Ok(self.games.lock().unwrap().get(&game_id).cloned())
+        }
+
+        fn load_newer(&self, game_id: GameId) -> Result<Option<ServerSnapshot>, String> {
+            match self.newer.lock().unwrap().remove(&game_id) {
+                true => self.load(game_id),
+                false => Ok(None),
+            }
+        }
+
+        fn list(&self) -> Result<Vec<GameId>, String> {
+            Ok(self.games.lock().unwrap().keys().copied().collect())
+        }
+
+        fn delete(&self, game_id: GameId) -> Result<(), String> {
+            self.games.lock().unwrap().remove(&game_id);
+            Ok(())
+        }
+
+        fn load_records(&self) -> Result<HashMap<String, PlayerRecord>, String> {
+            Ok(self.records.lock().unwrap().clone())
+        }
+
+        fn save_records(&self, records: &[(String, PlayerRecord)]) -> Result<(), String> {
+            self.records.lock().unwrap().extend(records.iter().cloned());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_games_survive_restart() {
+        let store = Arc::new(MemoryStore::default());
+        let server = GameServer::new();
+        assert!(server.set_store(store.clone()).await.unwrap().is_empty());
+        let join = Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let (game_id, token) = match server.handle_message(join).await {
+            Response::GameJoined {
+                game_id,
+                session_token: Some(token),
+                ..
+            } => (game_id, token),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        server.handle_message(Message::StartGame { game_id, host: None }).await;
+        // Saved before they were answered
+        assert_eq!(server.save_games().await, Ok(0));
+        assert_eq!(store.load(game_id).unwrap().unwrap().games[&game_id].round_state.round_number, 1);
+
+        // A new process picks up where the old one stopped
+        let restarted = GameServer::new();
+        assert_eq!(
+            restarted.set_store(store.clone()).await.unwrap(),
+            vec![game_id]
+        );
+        match restarted.handle_message(Message::Reconnect { token }).await {
+            Response::Reconnected { game_state, .. } => {
+                assert_eq!(game_state.round_state.round_number, 1)
+            }
+            other => panic!("Expected Reconnected response, got {:?}", other),
+        }
+
+        // Closed games are deleted
+        restarted
+            .handle_message(Message::CloseGame {
+                game_id,
+                host: token,
+            })
+            .await;
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_game_saved_elsewhere_first() {
+        let store = Arc::new(MemoryStore::default());
+        let server = GameServer::new();
+        server.set_store(store.clone()).await.unwrap();
+        let join = |player_name: &str, game_id: Option<GameId>| Message::JoinGame {
+            player_name: player_name.to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let game_id = match server.handle_message(join("Alice", None)).await {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+
+        // Another server renamed the player, so the game is started as it saved it
+        let mut elsewhere = store.load(game_id).unwrap().unwrap();
+        elsewhere.games.get_mut(&game_id).unwrap().players[0].name = "Alicia".to_string();
+        store.save(game_id, &elsewhere).unwrap();
+        store.newer.lock().unwrap().insert(game_id);
+        server.handle_message(Message::StartGame { game_id, host: None }).await;
+        let saved = store.load(game_id).unwrap().unwrap();
+        assert_eq!(saved.games[&game_id].players[0].name, "Alicia");
+        assert_eq!(saved.games[&game_id].round_state.round_number, 1);
+
+        // Another server moved first, so this one's move is refused and its clients resync
+        let mut updates = server.subscribe(game_id).await.unwrap();
+        store.outdated.lock().unwrap().insert(game_id);
+        let player_id = saved.games[&game_id].players[0].id.clone();
+        let stay = Message::MakeMove {
+            game_id,
+            game_move: game_core::GameMove::Stay { player_id },
+            move_id: None,
+        };
+        assert!(matches!(server.handle_message(stay).await, Response::Error { .. }));
+        let resynced = std::iter::from_fn(|| updates.try_recv().ok()).last();
+        match resynced {
+            Some(Response::StateUpdate { game_state, .. }) => {
+                assert_eq!(game_state.players[0].name, "Alicia");
+                assert_eq!(game_state.events.len(), saved.games[&game_id].events.len());
+            }
+            other => panic!("Expected StateUpdate, got {:?}", other),
+        }
+        assert_eq!(server.save_games().await, Ok(0));
+
+        // A game only another server held is found in the store
+        let other = GameServer::new();
+        other.set_store(store.clone()).await.unwrap();
+        let game_id = match other.handle_message(join("Bob", None)).await {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        match server.handle_message(join("Carol", Some(game_id))).await {
+            Response::GameJoined { .. } => {
+                let saved = store.load(game_id).unwrap().unwrap();
+                assert_eq!(saved.games[&game_id].players.len(), 2);
+            }
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_saves_dont_hold_the_shard() {
+        let store = Arc::new(MemoryStore::default());
+        let server = GameServer::with_shards(1);
+        server.set_store(store.clone()).await.unwrap();
+        let create = || Message::CreateGame {
+            rules: None,
+            visibility: Default::default(),
+            turn_clock: None,
+        };
+        let mut game_ids = Vec::new();
+        for _ in 0..2 {
+            match server.handle_message(create()).await {
+                Response::GameCreated { game_id, .. } => game_ids.push(game_id),
+                other => panic!("Expected GameCreated response, got {:?}", other),
+            }
+        }
+
+        let (started, saving) = mpsc::channel();
+        let (release, waiting) = mpsc::channel();
+        *store.stall.lock().unwrap() = Some((started, waiting));
+        let join = Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: Some(game_ids[0]),
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let joining = tokio::spawn({
+            let server = server.clone();
+            async move { server.handle_message(join).await }
+        });
+        tokio::task::spawn_blocking(move || saving.recv()).await.unwrap().unwrap();
+
+        // The other game on the shard is answered while the first one saves
+        let state = Message::GetGameState {
+            game_id: game_ids[1],
+            access: None,
+        };
+        let answered = tokio::time::timeout(Duration::from_secs(5), server.handle_message(state)).await;
+        assert!(matches!(answered, Ok(Response::GameState { .. })));
+
+        release.send(()).unwrap();
+        assert!(matches!(joining.await.unwrap(), Response::GameJoined { .. }));
+        let saved = store.load(game_ids[0]).unwrap().unwrap();
+        assert_eq!(saved.games[&game_ids[0]].players.len(), 1);
+    }
+
+    #[test]
+    fn test_rebase() {
+        // Both servers rated a game and counted a win since they last saved
+        let base = Rating::default();
+        let ours = Rating {
+            rating: base.rating + 10.0,
+            games: 1,
+        };
+        let saved = Rating {
+            rating: base.rating - 4.0,
+            games: 1,
+        };
+        let rebased = ours.rebase(&base, &saved);
+        assert_eq!((rebased.rating, rebased.games), (base.rating + 6.0, 2));
+
+        let base = PlayerRecord {
+            name: "Alice".to_string(),
+            wins: 3,
+            best_solo: Some(150),
+            ..PlayerRecord::default()
+        };
+        let ours = PlayerRecord {
+            wins: 4,
+            best_solo: Some(120),
+            ..base.clone()
+        };
+        let saved = PlayerRecord {
+            wins: 5,
+            best_solo: Some(180),
+            ..base.clone()
+        };
+        let rebased = ours.rebase(&base, &saved);
+        assert_eq!((rebased.wins, rebased.best_solo), (6, Some(180)));
+
+        let list = |friends: &[&str]| FriendList {
+            name: "Alice".to_string(),
+            friends: friends.iter().map(|friend| friend.to_string()).collect(),
+            requests: Default::default(),
+        };
+        // One server removed Bob, the other added Dan
+        let rebased = list(&["carol"]).rebase(&list(&["bob", "carol"]), &list(&["bob", "carol", "dan"]));
+        assert_eq!(rebased, list(&["carol", "dan"]));
+    }
+
+    #[tokio::test]
+    async fn test_leaderboards_survive_restart() {
+        let store = Arc::new(MemoryStore::default());
+        let server = GameServer::new();
+        server.set_store(store.clone()).await.unwrap();
+        let join = |player_name: &str, game_id: Option<GameId>| Message::JoinGame {
+            player_name: player_name.to_string(),
+            game_id,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let game_id = match server.handle_message(join("Alice", None)).await {
+            Response::GameJoined { game_id, player_id, .. } => {
+                server.set_account(game_id, player_id, "alice".to_string()).await;
+                game_id
+            }
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        match server.handle_message(join("Bob", Some(game_id))).await {
+            Response::GameJoined { player_id, .. } => server.set_account(game_id, player_id, "bob".to_string()).await,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+        server.handle_message(Message::StartGame { game_id, host: None }).await;
+        // Alice comes in at the target, so she wins after the round
+        let mut engine = server.shards.of(game_id).write().await;
+        let game = engine.games.get_mut(&game_id).unwrap();
+        game.players[0].score = game.config.target_score;
+        drop(engine);
+        let winners = loop {
+            let game = match server.handle_message(Message::GetGameState { game_id, access: None }).await {
+                Response::GameState { game_state } => game_state,
+                other => panic!("Expected GameState response, got {:?}", other),
+            };
+            if game.is_game_over() {
+                break game
+                    .outcome
+                    .iter()
+                    .flat_map(|outcome| &outcome.winner_ids)
+                    .map(|id| game.player(id).unwrap().name.clone())
+                    .collect::<Vec<_>>();
+            }
+            let player_id = game.current_player().unwrap().id.clone();
+            server
+                .handle_message(Message::MakeMove {
+                    game_id,
+                    game_move: game_core::GameMove::Stay { player_id },
+                    move_id: None,
+                })
+                .await;
+        };
+        server.save_games().await.unwrap();
+
+        // The wins are counted again by a new process, on any shard
+        let restarted = GameServer::new();
+        restarted.set_store(store).await.unwrap();
+        let board = Message::GetLeaderboard {
+            board: crate::Board::Wins,
+            among: Vec::new(),
+            offset: 0,
+            limit: None,
+        };
+        match restarted.handle_message(board).await {
+            Response::Leaderboard { entries, total, .. } => {
+                assert_eq!(winners, ["Alice"]);
+                assert_eq!(total, winners.len());
+                let names: Vec<String> = entries.iter().map(|entry| entry.name.clone()).collect();
+                assert_eq!(names, winners);
+                assert!(entries.iter().all(|entry| entry.value == 1));
+            }
+            other => panic!("Expected Leaderboard response, got {:?}", other),
+        }
+    }
+}
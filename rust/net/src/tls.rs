@@ -0,0 +1,121 @@
+//! TLS for the TCP and WebSocket transports, so servers on the public
+//! internet don't send session tokens and moves in the clear. A transport
+//! given a `TlsAcceptor` with `with_tls` does the handshake on every
+//! connection before the first message.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+
+pub use tokio_rustls::TlsAcceptor;
+
+/// Where the server's certificate and key are, as PEM files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// The certificate chain, leaf first
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl TlsConfig {
+    /// Reads the certificate and key into an acceptor transports can share.
+    pub fn acceptor(&self) -> Result<TlsAcceptor, String> {
+        let certs = CertificateDer::pem_file_iter(&self.cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| format!("Cannot read certificates from {}: {}", self.cert_path.display(), err))?;
+        if certs.is_empty() {
+            return Err(format!("No certificates in {}", self.cert_path.display()));
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.key_path)
+            .map_err(|err| format!("Cannot read a private key from {}: {}", self.key_path.display(), err))?;
+
+        let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|err| format!("Invalid certificate or key: {}", err))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Framing, GameServer, Message, Response, TcpTransport, Transport, TrustLevel};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    /// A self-signed certificate for localhost, written out as PEM files.
+    fn localhost_cert(dir: &std::path::Path) -> (TlsConfig, CertificateDer<'static>) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::create_dir_all(dir).unwrap();
+        let config = TlsConfig {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+        };
+        std::fs::write(&config.cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&config.key_path, certified.key_pair.serialize_pem()).unwrap();
+        (config, certified.cert.der().clone())
+    }
+
+    #[tokio::test]
+    async fn test_join_over_tls() {
+        let dir = std::env::temp_dir().join(format!("flip7-tls-{}", std::process::id()));
+        let (config, cert) = localhost_cert(&dir);
+        let acceptor = config.acceptor().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::Lines)
+            .await
+            .unwrap()
+            .with_tls(acceptor);
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+        let client = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let name = "localhost".try_into().unwrap();
+        let stream = TlsConnector::from(Arc::new(client)).connect(name, stream).await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+
+        let join = Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let mut line = serde_json::to_vec(&join).unwrap();
+        line.push(b'\n');
+        writer.write_all(&line).await.unwrap();
+        let reply = BufReader::new(reader).lines().next_line().await.unwrap().unwrap();
+        match serde_json::from_str(&reply).unwrap() {
+            Response::GameJoined { .. } => {}
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let config = TlsConfig {
+            cert_path: PathBuf::from("/nonexistent/cert.pem"),
+            key_path: PathBuf::from("/nonexistent/key.pem"),
+        };
+        let Err(message) = config.acceptor() else {
+            panic!("Expected an error for missing files");
+        };
+        assert!(message.contains("/nonexistent/cert.pem"));
+    }
+}
@@ -0,0 +1,721 @@
+//! Tournaments: a fixed number of entrants playing heads-up games, round
+//! after round, until one of them wins.
+//!
+//! `CreateTournament` sets one up for a number of entrants, who sign up with
+//! `JoinTournament`, seeded in the order they join. Each entrant is given a
+//! player id and a session token, which stand for their seat in every game
+//! the tournament seats them in. Once the last entrant joins, the server
+//! pairs the first round, creates and starts a game for each pairing, and
+//! pushes a `TournamentUpdated` to every entrant's connection; players take
+//! their seats with a `Reconnect`. The next round is paired as soon as the
+//! last game of a round ends. Nobody else joins or adds bots to those
+//! games, and an account, or a connection, enters a tournament once.
+//!
+//! Single elimination seeds a standard bracket, where the top seeds get the
+//! byes of a field that isn't a power of two, and knocks out the loser of
+//! each game. Swiss plays a set number of rounds, pairing entrants with as
+//! many wins as each other who haven't met yet; the most wins take it, with
+//! the points scored over every game breaking ties.
+//!
+//! A game is won by the winner of the game itself or, in a game that ended
+//! without one or was closed before the end, by the player who scored more,
+//! or else the higher placed. Tournaments are held by the engine running
+//! the queues, with their games; unlike the games, they aren't saved.
+
+use crate::i18n::{text, Text};
+use crate::{GameId, PlayerId, ProtocolEngine, Response, SessionToken, TournamentId, Visibility};
+use game_core::GameState;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+/// Most entrants a tournament takes.
+pub const MAX_ENTRANTS: usize = 256;
+
+/// Most pairings tried for a Swiss round without rematches, before settling
+/// for one with some.
+const PAIRING_TRIES: usize = 10_000;
+
+/// Tournament updates a slow follower can fall behind by.
+const UPDATE_BACKLOG: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Format {
+    /// Losers are out; the last player standing wins
+    SingleElimination,
+    /// Every entrant plays every one of the rounds
+    Swiss { rounds: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entrant {
+    pub player_id: PlayerId,
+    pub name: String,
+    /// Games won, byes included
+    pub wins: u32,
+    /// Points scored over every game, which break ties between equal wins
+    pub points: u32,
+    pub eliminated: bool,
+}
+
+/// Two entrants to play each other, or one given a bye.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pairing {
+    pub players: Vec<PlayerId>,
+    /// Game they play, which a bye has none of
+    pub game_id: Option<GameId>,
+    pub winner: Option<PlayerId>,
+}
+
+/// A tournament's bracket, as clients are shown it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tournament {
+    pub tournament_id: TournamentId,
+    pub name: String,
+    pub format: Format,
+    /// Entrants it starts with, once that many joined
+    pub size: usize,
+    /// In seeding order
+    pub entrants: Vec<Entrant>,
+    /// Pairings of every round so far
+    pub rounds: Vec<Vec<Pairing>>,
+    pub winner: Option<PlayerId>,
+}
+
+impl Tournament {
+    pub fn new(name: String, format: Format, size: usize) -> Result<Self, Text> {
+        if !(2..=MAX_ENTRANTS).contains(&size) {
+            return Err(text!("tournament_size", MAX_ENTRANTS));
+        }
+        if let Format::Swiss { rounds } = format {
+            if rounds == 0 || rounds >= size {
+                return Err(text!("swiss_rounds", size, size - 1));
+            }
+        }
+        Ok(Self {
+            tournament_id: TournamentId::new(),
+            name,
+            format,
+            size,
+            entrants: Vec::new(),
+            rounds: Vec::new(),
+            winner: None,
+        })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entrants.len() == self.size
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    /// Signs up the next entrant, returning their player id.
+    pub fn join(&mut self, player_name: String) -> Result<PlayerId, Text> {
+        if self.is_full() {
+            return Err(text!("tournament_full"));
+        }
+        let player_id = PlayerId::new();
+        self.entrants.push(Entrant {
+            player_id,
+            name: player_name,
+            wins: 0,
+            points: 0,
+            eliminated: false,
+        });
+        Ok(player_id)
+    }
+
+    /// The entrants from first place down.
+    pub fn standings(&self) -> Vec<&Entrant> {
+        let mut standings: Vec<&Entrant> = self.entrants.iter().collect();
+        // Stable, so equals stay in seeding order
+        standings.sort_by_key(|entrant| (entrant.eliminated, std::cmp::Reverse((entrant.wins, entrant.points))));
+        standings
+    }
+
+    /// Whether every pairing of the round being played has a winner.
+    pub fn round_over(&self) -> bool {
+        self.rounds
+            .last()
+            .is_none_or(|round| round.iter().all(|pairing| pairing.winner.is_some()))
+    }
+
+    /// Pairs the next round, once the last is over, or names the winner if
+    /// it was the last. Byes are won at once. Returns whether a round was
+    /// paired.
+    pub fn pair_next_round(&mut self) -> bool {
+        if self.is_over() || !self.is_full() || !self.round_over() {
+            return false;
+        }
+        let pairs = match self.format {
+            Format::SingleElimination => self.knockout_pairs(),
+            Format::Swiss { rounds } if self.rounds.len() < rounds => self.swiss_pairs(),
+            Format::Swiss { .. } => Vec::new(),
+        };
+        if pairs.is_empty() {
+            self.winner = self.standings().first().map(|entrant| entrant.player_id);
+            return false;
+        }
+        let round = pairs
+            .into_iter()
+            .map(|players| {
+                let winner = (players.len() == 1).then(|| players[0]);
+                Pairing {
+                    players,
+                    game_id: None,
+                    winner,
+                }
+            })
+            .collect();
+        self.rounds.push(round);
+        for bye in self.rounds.last().into_iter().flatten().filter(|pairing| pairing.players.len() == 1) {
+            if let Some(entrant) = self.entrants.iter_mut().find(|entrant| Some(entrant.player_id) == bye.winner) {
+                entrant.wins += 1;
+            }
+        }
+        true
+    }
+
+    /// The pairs of the next single elimination round: the first by the
+    /// standard bracket, those after by its winners, in bracket order. None
+    /// once one player is left.
+    fn knockout_pairs(&self) -> Vec<Vec<PlayerId>> {
+        let Some(last) = self.rounds.last() else {
+            let size = self.size.next_power_of_two();
+            let mut seeds = vec![0];
+            while seeds.len() < size {
+                let count = seeds.len() * 2;
+                seeds = seeds.iter().flat_map(|&seed| [seed, count - 1 - seed]).collect();
+            }
+            return seeds
+                .chunks(2)
+                .map(|pair| {
+                    pair.iter()
+                        .filter_map(|&seed| self.entrants.get(seed))
+                        .map(|entrant| entrant.player_id)
+                        .collect()
+                })
+                .collect();
+        };
+        let winners: Vec<PlayerId> = last.iter().filter_map(|pairing| pairing.winner).collect();
+        if winners.len() < 2 {
+            return Vec::new();
+        }
+        winners.chunks(2).map(|pair| pair.to_vec()).collect()
+    }
+
+    /// The pairs of the next Swiss round: each entrant, from first place
+    /// down, with the next one they haven't played yet, as long as that lets
+    /// everyone below be paired the same way. If nobody can be paired so,
+    /// each takes the next one they haven't played or else the next one.
+    /// With an odd field, the lowest placed entrant who hasn't had a bye sits
+    /// out.
+    fn swiss_pairs(&self) -> Vec<Vec<PlayerId>> {
+        let met = |a: PlayerId, b: PlayerId| {
+            self.rounds
+                .iter()
+                .flatten()
+                .any(|pairing| pairing.players.contains(&a) && pairing.players.contains(&b))
+        };
+        let mut unpaired: Vec<PlayerId> = self.standings().iter().map(|entrant| entrant.player_id).collect();
+        let mut pairs = Vec::new();
+        if unpaired.len() % 2 == 1 {
+            let had_bye = |player_id: PlayerId| {
+                self.rounds
+                    .iter()
+                    .flatten()
+                    .any(|pairing| pairing.players == [player_id])
+            };
+            let bye = unpaired.iter().rposition(|&player_id| !had_bye(player_id));
+            pairs.push(vec![unpaired.remove(bye.unwrap_or(unpaired.len() - 1))]);
+        }
+        let mut tries = PAIRING_TRIES;
+        if let Some(fresh) = pair_off(&unpaired, &met, &mut tries) {
+            pairs.extend(fresh);
+            return pairs;
+        }
+        while !unpaired.is_empty() {
+            let first = unpaired.remove(0);
+            let other = unpaired.iter().position(|&other| !met(first, other)).unwrap_or(0);
+            pairs.push(vec![first, unpaired.remove(other)]);
+        }
+        pairs
+    }
+
+    /// Records how a game of the round being played ended. Returns false if
+    /// it isn't one of the round's games still undecided.
+    pub fn record(&mut self, game_id: GameId, winner: PlayerId, points: &[(PlayerId, u32)]) -> bool {
+        let knockout = self.format == Format::SingleElimination;
+        let Some(pairing) = self
+            .rounds
+            .last_mut()
+            .into_iter()
+            .flatten()
+            .find(|pairing| pairing.game_id == Some(game_id) && pairing.winner.is_none())
+        else {
+            return false;
+        };
+        pairing.winner = Some(winner);
+        let players = pairing.players.clone();
+        for entrant in self.entrants.iter_mut().filter(|entrant| players.contains(&entrant.player_id)) {
+            if entrant.player_id == winner {
+                entrant.wins += 1;
+            } else if knockout {
+                entrant.eliminated = true;
+            }
+            let scored = points.iter().find(|(player_id, _)| *player_id == entrant.player_id);
+            entrant.points += scored.map_or(0, |(_, points)| *points);
+        }
+        true
+    }
+
+    /// Who won a game of the tournament that ended, or was closed, as it
+    /// stands: see the module docs.
+    fn winner_of(&self, game: &GameState, players: &[PlayerId]) -> Option<PlayerId> {
+        let winners = game.outcome.iter().flat_map(|outcome| &outcome.winner_ids);
+        let won = winners
+            .filter_map(|winner| winner.parse().ok())
+            .find(|winner| players.contains(winner));
+        let score = |player_id: &PlayerId| game.player(&player_id.to_string()).map_or(0, |player| player.score);
+        // The higher placed player comes first in a pairing
+        won.or_else(|| players.iter().rev().max_by_key(|player_id| score(player_id)).copied())
+    }
+}
+
+/// Pairs every player with one they haven't met, higher placed players
+/// first, if there is a way to within the `tries`.
+fn pair_off(
+    unpaired: &[PlayerId],
+    met: &impl Fn(PlayerId, PlayerId) -> bool,
+    tries: &mut usize,
+) -> Option<Vec<Vec<PlayerId>>> {
+    let Some((&first, rest)) = unpaired.split_first() else {
+        return Some(Vec::new());
+    };
+    for (index, &other) in rest.iter().enumerate() {
+        if met(first, other) {
+            continue;
+        }
+        *tries = tries.checked_sub(1)?;
+        let mut left = rest.to_vec();
+        left.remove(index);
+        if let Some(mut pairs) = pair_off(&left, met, tries) {
+            pairs.insert(0, vec![first, other]);
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+/// A tournament held by an engine, with what only the server knows of it.
+struct Held {
+    tournament: Tournament,
+    tokens: HashMap<PlayerId, SessionToken>,
+    /// Accounts of the signed-in entrants
+    accounts: HashSet<String>,
+    updates: broadcast::Sender<Response>,
+    /// When it last changed, for its expiry
+    changed: Instant,
+}
+
+/// The tournaments an engine holds, and which of its games they play.
+#[derive(Default)]
+pub(crate) struct Tournaments {
+    held: HashMap<TournamentId, Held>,
+    games: HashMap<GameId, TournamentId>,
+}
+
+impl Tournaments {
+    /// Whether the game is one of a tournament's.
+    pub(crate) fn plays(&self, game_id: GameId) -> bool {
+        self.games.contains_key(&game_id)
+    }
+}
+
+impl ProtocolEngine {
+    pub(crate) fn create_tournament(&mut self, name: String, format: Format, entrants: usize) -> Response {
+        match Tournament::new(name, format, entrants) {
+            Ok(tournament) => {
+                let tournament_id = tournament.tournament_id;
+                let held = Held {
+                    tournament,
+                    tokens: HashMap::new(),
+                    accounts: HashSet::new(),
+                    updates: broadcast::channel(UPDATE_BACKLOG).0,
+                    changed: Instant::now(),
+                };
+                self.tournaments.held.insert(tournament_id, held);
+                Response::TournamentCreated { tournament_id }
+            }
+            Err(message) => Response::Error { message },
+        }
+    }
+
+    /// Signs a player up, and starts the tournament once they were the last
+    /// entrant it waited for.
+    pub(crate) fn join_tournament(
+        &mut self,
+        tournament_id: TournamentId,
+        player_name: String,
+        account: Option<String>,
+    ) -> Response {
+        let Some(held) = self.tournaments.held.get_mut(&tournament_id) else {
+            return Response::Error {
+                message: text!("tournament_not_found"),
+            };
+        };
+        if account.as_ref().is_some_and(|account| held.accounts.contains(account)) {
+            return Response::Error {
+                message: text!("tournament_joined"),
+            };
+        }
+        let player_id = match held.tournament.join(player_name) {
+            Ok(player_id) => player_id,
+            Err(message) => return Response::Error { message },
+        };
+        held.accounts.extend(account);
+        let session_token = SessionToken::new();
+        held.tokens.insert(player_id, session_token);
+        held.changed = Instant::now();
+        if held.tournament.is_full() {
+            self.play_round(tournament_id);
+        }
+        Response::TournamentJoined {
+            tournament_id,
+            player_id,
+            session_token,
+            tournament: Box::new(self.tournaments.held[&tournament_id].tournament.clone()),
+        }
+    }
+
+    pub(crate) fn get_tournament(&self, tournament_id: TournamentId) -> Response {
+        match self.tournaments.held.get(&tournament_id) {
+            Some(held) => Response::Tournament {
+                tournament: Box::new(held.tournament.clone()),
+            },
+            None => Response::Error {
+                message: text!("tournament_not_found"),
+            },
+        }
+    }
+
+    /// Follows a tournament: the receiver gets a `TournamentUpdated` every
+    /// time its bracket changes. `None` if there is no such tournament.
+    pub fn subscribe_tournament(&mut self, tournament_id: TournamentId) -> Option<broadcast::Receiver<Response>> {
+        Some(self.tournaments.held.get(&tournament_id)?.updates.subscribe())
+    }
+
+    /// Pairs the tournament's next round and starts a game for each of its
+    /// pairings, then tells its followers.
+    fn play_round(&mut self, tournament_id: TournamentId) {
+        let Some(held) = self.tournaments.held.get_mut(&tournament_id) else {
+            return;
+        };
+        held.changed = Instant::now();
+        // A round of nothing but byes is over as soon as it is paired
+        while held.tournament.pair_next_round() && held.tournament.round_over() {}
+        let tokens = held.tokens.clone();
+        let tournament = held.tournament.clone();
+        let round = tournament.rounds.len().saturating_sub(1);
+
+        for (index, pairing) in tournament.rounds.last().into_iter().flatten().enumerate() {
+            if pairing.winner.is_some() {
+                continue;
+            }
+            // Its pairing is seated here, and nobody else may join
+            let game_id = self
+                .create_game(None, Visibility::InviteOnly, None)
+                .expect("the default variant is always registered");
+            let game = self.games.get_mut(&game_id).expect("game was just created");
+            for player_id in &pairing.players {
+                let entrant = tournament.entrants.iter().find(|entrant| entrant.player_id == *player_id);
+                let name = entrant.map_or_else(String::new, |entrant| entrant.name.clone());
+                game.add_player(player_id.to_string(), name);
+                if let Some(&token) = tokens.get(player_id) {
+                    self.sessions.insert(token, (game_id, *player_id));
+                }
+            }
+            if let Response::Error { message } = self.start_game(game_id) {
+                unreachable!("a new game of two players can start: {}", message);
+            }
+            self.hosts.insert(game_id, pairing.players[0]);
+            self.tournaments.games.insert(game_id, tournament_id);
+            let held = self.tournaments.held.get_mut(&tournament_id).expect("tournament is held");
+            held.tournament.rounds[round][index].game_id = Some(game_id);
+        }
+        self.tournament_updated(tournament_id);
+    }
+
+    /// Records the result of a tournament game that ended or is being
+    /// forgotten, and pairs the next round if it was the last of its round.
+    pub(crate) fn tournament_game_over(&mut self, game_id: GameId, game: &GameState) {
+        let Some(&tournament_id) = self.tournaments.games.get(&game_id) else {
+            return;
+        };
+        let Some(held) = self.tournaments.held.get_mut(&tournament_id) else {
+            return;
+        };
+        let pairing = held.tournament.rounds.iter().flatten().find(|pairing| pairing.game_id == Some(game_id));
+        let Some(players) = pairing.filter(|pairing| pairing.winner.is_none()).map(|pairing| pairing.players.clone())
+        else {
+            return;
+        };
+        let Some(winner) = held.tournament.winner_of(game, &players) else {
+            return;
+        };
+        let points: Vec<(PlayerId, u32)> = players
+            .iter()
+            .map(|player_id| (*player_id, game.player(&player_id.to_string()).map_or(0, |player| player.score)))
+            .collect();
+        held.tournament.record(game_id, winner, &points);
+        held.changed = Instant::now();
+        if held.tournament.round_over() {
+            self.play_round(tournament_id);
+        } else {
+            self.tournament_updated(tournament_id);
+        }
+    }
+
+    /// Forgets the game's place in its tournament, deciding it first if it
+    /// is being dropped before the end.
+    pub(crate) fn forget_tournament_game(&mut self, game_id: GameId, game: &GameState) {
+        self.tournament_game_over(game_id, game);
+        self.tournaments.games.remove(&game_id);
+    }
+
+    /// Drops tournaments that ended, or never filled up, as long ago as
+    /// finished games and lobbies expire after, and those left with none of
+    /// their games as long ago as idle games do.
+    pub(crate) fn sweep_tournaments(&mut self, now: Instant) {
+        let expiry = self.expiry;
+        let Tournaments { held, games } = &mut self.tournaments;
+        held.retain(|tournament_id, held| {
+            let ttl = if held.tournament.is_over() {
+                expiry.finished_ttl
+            } else if held.tournament.rounds.is_empty() {
+                expiry.lobby_ttl
+            } else if games.values().any(|id| id == tournament_id) {
+                return true;
+            } else {
+                expiry.idle_ttl
+            };
+            now.duration_since(held.changed) < ttl
+        });
+    }
+
+    fn tournament_updated(&mut self, tournament_id: TournamentId) {
+        if let Some(held) = self.tournaments.held.get(&tournament_id) {
+            let update = Response::TournamentUpdated {
+                tournament: Box::new(held.tournament.clone()),
+            };
+            // Nobody may be following
+            let _ = held.updates.send(update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Message;
+    use game_core::GameMove;
+
+    fn tournament(format: Format, size: usize) -> Tournament {
+        let mut tournament = Tournament::new("Weekly".to_string(), format, size).unwrap();
+        for seed in 1..=size {
+            tournament.join(format!("Seed {}", seed)).unwrap();
+        }
+        tournament
+    }
+
+    /// Lets the higher placed player of each pairing of the round win by the
+    /// given points.
+    fn play(tournament: &mut Tournament, points: u32) {
+        let round = tournament.rounds.last().unwrap().clone();
+        for (index, pairing) in round.iter().enumerate() {
+            if pairing.winner.is_some() {
+                continue;
+            }
+            let game_id = GameId::new();
+            tournament.rounds.last_mut().unwrap()[index].game_id = Some(game_id);
+            let scored = [(pairing.players[0], points), (pairing.players[1], 0)];
+            assert!(tournament.record(game_id, pairing.players[0], &scored));
+        }
+    }
+
+    fn seeds(tournament: &Tournament, players: &[PlayerId]) -> Vec<usize> {
+        let seed = |player_id: &PlayerId| {
+            let position = tournament.entrants.iter().position(|entrant| entrant.player_id == *player_id);
+            position.unwrap() + 1
+        };
+        players.iter().map(seed).collect()
+    }
+
+    /// The seeds of each pairing of the round.
+    fn round(tournament: &Tournament, round: usize) -> Vec<Vec<usize>> {
+        tournament.rounds[round]
+            .iter()
+            .map(|pairing| seeds(tournament, &pairing.players))
+            .collect()
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Tournament::new("Solo".to_string(), Format::SingleElimination, 1).is_err());
+        assert!(Tournament::new("Long".to_string(), Format::Swiss { rounds: 4 }, 4).is_err());
+        let mut tournament = tournament(Format::Swiss { rounds: 3 }, 4);
+        assert_eq!(tournament.join("Late".to_string()), Err(text!("tournament_full")));
+    }
+
+    #[test]
+    fn test_single_elimination() {
+        let mut tournament = tournament(Format::SingleElimination, 6);
+        assert!(tournament.pair_next_round());
+        // The top two seeds get the byes of a field of 6 in a bracket of 8
+        assert_eq!(round(&tournament, 0), [vec![1], vec![4, 5], vec![2], vec![3, 6]]);
+        assert_eq!(tournament.entrants[0].wins, 1);
+        assert!(!tournament.round_over());
+        assert!(!tournament.pair_next_round());
+
+        play(&mut tournament, 100);
+        assert!(tournament.entrants[4].eliminated);
+        assert!(tournament.pair_next_round());
+        assert_eq!(round(&tournament, 1), [vec![1, 4], vec![2, 3]]);
+        play(&mut tournament, 100);
+        assert!(tournament.pair_next_round());
+        play(&mut tournament, 100);
+        assert!(!tournament.pair_next_round());
+        assert_eq!(tournament.winner, Some(tournament.entrants[0].player_id));
+    }
+
+    #[test]
+    fn test_swiss() {
+        let mut tournament = tournament(Format::Swiss { rounds: 3 }, 5);
+        for round in 0..3 {
+            assert!(tournament.pair_next_round());
+            play(&mut tournament, 10 * (round + 1));
+        }
+        assert!(!tournament.pair_next_round());
+
+        // Nobody met twice, and everyone sat out at most once
+        let mut games: Vec<Vec<usize>> = Vec::new();
+        let mut byes: Vec<usize> = Vec::new();
+        for pairing in tournament.rounds.iter().flatten() {
+            let mut players = seeds(&tournament, &pairing.players);
+            players.sort();
+            match players.len() {
+                1 => byes.push(players[0]),
+                _ => games.push(players),
+            }
+        }
+        assert_eq!(games.len(), 6);
+        games.sort();
+        games.dedup();
+        assert_eq!(games.len(), 6);
+        byes.sort();
+        byes.dedup();
+        assert_eq!(byes.len(), 3);
+
+        let standings = tournament.standings();
+        assert_eq!(tournament.winner, Some(standings[0].player_id));
+        assert!(standings.windows(2).all(|pair| (pair[0].wins, pair[0].points) >= (pair[1].wins, pair[1].points)));
+    }
+
+    #[test]
+    fn test_tournament_games() {
+        let mut engine = ProtocolEngine::new();
+        let create = Message::CreateTournament {
+            name: "Weekly".to_string(),
+            format: Format::SingleElimination,
+            entrants: 2,
+        };
+        let Response::TournamentCreated { tournament_id } = engine.handle(create) else {
+            panic!("Expected TournamentCreated response");
+        };
+        let mut updates = engine.subscribe_tournament(tournament_id).unwrap();
+        let join = |player_name: &str| Message::JoinTournament {
+            tournament_id,
+            player_name: player_name.to_string(),
+            account: Some(player_name.to_lowercase()),
+        };
+        let alice = match engine.handle(join("Alice")) {
+            Response::TournamentJoined {
+                player_id, session_token, ..
+            } => (player_id, session_token),
+            other => panic!("Expected TournamentJoined response, got {:?}", other),
+        };
+        match engine.handle(join("Alice")) {
+            Response::Error { message } => assert_eq!(message, "Already in this tournament"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        let (token, tournament) = match engine.handle(join("Bob")) {
+            Response::TournamentJoined {
+                session_token,
+                tournament,
+                ..
+            } => (session_token, tournament),
+            other => panic!("Expected TournamentJoined response, got {:?}", other),
+        };
+        assert!(matches!(engine.handle(join("Cy")), Response::Error { .. }));
+        assert!(matches!(updates.try_recv(), Ok(Response::TournamentUpdated { .. })));
+
+        // Nobody else gets a seat at the final, not even a bot of a player's
+        let pairing = &tournament.rounds[0][0];
+        let host = if pairing.players[0] == alice.0 { alice.1 } else { token };
+        let error = |response: Response| match response {
+            Response::Error { message } => message,
+            other => panic!("Expected Error response, got {:?}", other),
+        };
+        assert_eq!(
+            error(engine.handle(Message::JoinGame {
+                player_name: "Cy".to_string(),
+                game_id: pairing.game_id,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            })),
+            "Tournament games only seat their pairing"
+        );
+        assert_eq!(
+            error(engine.handle(Message::AddBot {
+                game_id: pairing.game_id.unwrap(),
+                host,
+                difficulty: "easy".to_string(),
+                player_name: None,
+            })),
+            "Tournament games only seat their pairing"
+        );
+
+        // The final is started, and Bob takes his seat with his token
+        let game_id = tournament.rounds[0][0].game_id.unwrap();
+        match engine.handle(Message::Reconnect { token }) {
+            Response::Reconnected { game_id: seat, .. } => assert_eq!(seat, game_id),
+            other => panic!("Expected Reconnected response, got {:?}", other),
+        }
+        let rematch = Message::RequestRematch {
+            game_id,
+            player_id: tournament.entrants[0].player_id,
+        };
+        // A one round final
+        engine.games.get_mut(&game_id).unwrap().config.round_limit = Some(1);
+        while !engine.games[&game_id].is_game_over() {
+            let player_id = engine.games[&game_id].current_player().unwrap().id.clone();
+            let stay = Message::MakeMove {
+                game_id,
+                game_move: GameMove::Stay { player_id },
+                move_id: None,
+            };
+            engine.handle(stay);
+        }
+        let Response::Tournament { tournament } = engine.handle(Message::GetTournament { tournament_id }) else {
+            panic!("Expected Tournament response");
+        };
+        assert!(tournament.is_over());
+        assert_eq!(tournament.winner, tournament.rounds[0][0].winner);
+        assert!(matches!(engine.handle(rematch), Response::Error { .. }));
+    }
+}
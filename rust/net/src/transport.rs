@@ -0,0 +1,1685 @@
+//! Transports carry the `Message`/`Response` protocol between clients and a
+//! `GameServer`. `TcpTransport` reads framed messages off raw TCP; the
+//! WebSocket one (feature `websocket`) serves browsers. Both can wrap their
+//! connections in TLS (feature `tls`).
+
+use crate::auth::{self, Auth, Identity};
+use crate::delta;
+use crate::i18n::{text, Locale, Text};
+use crate::handshake::PROTOCOL_VERSION;
+use crate::limits::{Limit, TokenBucket};
+use crate::{Encoding, GameId, GameServer, Message, PlayerId, Response, TournamentId, TrustLevel};
+use game_core::GameState;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// How often turn timers are checked while serving.
+const TIMER_TICK: Duration = Duration::from_secs(1);
+
+/// How often stale games are looked for while serving.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Hashes of the states of a game recently sent to a client using deltas, so
+/// one acknowledging a slightly older state isn't sent its state again.
+const RECENT_STATES: usize = 8;
+
+/// Updates queued for a client reading them slower than they come. Past
+/// that, the broadcasts of its games lag and it skips to their latest state.
+const UPDATE_BUFFER: usize = 32;
+
+/// Largest frame or line read; a client sending more is disconnected.
+/// Smaller messages can still be over the server's `Limits`.
+pub const MAX_FRAME_LEN: u32 = 1 << 20;
+
+/// How messages are delimited on a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Framing {
+    /// One JSON message per line. Connections stay on JSON, so encoding
+    /// negotiation always selects it.
+    Lines,
+    /// Each message is preceded by its length as a big-endian `u32`. Starts in
+    /// JSON and switches to whatever `Hello` or `NegotiateEncoding` selects.
+    LengthPrefixed,
+}
+
+/// A listener clients connect to.
+pub trait Transport {
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Accepts connections until the task is dropped. Every client gets the
+    /// transport's trust level; player apps should be `UntrustedPeer`. The
+    /// server's timers aren't run; see `run_timers`.
+    fn serve(self, server: GameServer) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+/// One client's conversation with the server, whatever carries it. The
+/// client follows every game it joins or reconnects to: their `StateUpdate`s come out of
+/// `next_update` until its last player there leaves. So do the `MatchFound`s
+/// of players it queued for quick play, whose games it then follows too,
+/// and for a signed-in client, the friend requests and game invitations
+/// pushed to its account; see `friends`. The client also follows the
+/// brackets of the tournaments it joined.
+/// Public for transports kept out of this crate, like `flip7-p2p`.
+pub struct Session {
+    server: GameServer,
+    trust: TrustLevel,
+    encoding: Encoding,
+    /// Whether the transport can carry encodings other than JSON
+    switches_encoding: bool,
+    /// Whether the client has sent anything yet, which only `Hello` may be
+    greeted: bool,
+    /// Whether the client asked for `StateDelta`s in its `Hello`
+    deltas: bool,
+    /// Language errors are translated into
+    locale: Locale,
+    /// How clients sign in, if the server wants them to
+    auth: Option<Auth>,
+    /// Who the client signed in as
+    identity: Option<Identity>,
+    /// Id of the connection among those of the signed-in account; see
+    /// `friends`
+    connection: Option<u64>,
+    /// Game the signed-in client's friends see it playing, if any
+    presence: Option<GameId>,
+    /// Where the client connected from
+    address: IpAddr,
+    /// The connection's message rate; see `limits`
+    bucket: TokenBucket,
+    /// Games the client created that are still open
+    created: HashSet<GameId>,
+    /// What a client using deltas was sent of each game
+    sent: HashMap<GameId, Sent>,
+    /// Game of each player the client joined as
+    players: HashMap<PlayerId, GameId>,
+    /// Tasks forwarding the updates of each followed game
+    following: HashMap<GameId, JoinHandle<()>>,
+    /// Tasks waiting for the match of each queued player, which go on to
+    /// forward the updates of its game
+    queued: HashMap<PlayerId, JoinHandle<()>>,
+    /// Tasks forwarding the updates of each tournament joined
+    tournaments: HashMap<TournamentId, JoinHandle<()>>,
+    updates_tx: mpsc::Sender<Response>,
+    updates: mpsc::Receiver<Response>,
+}
+
+impl Session {
+    pub fn new(server: GameServer, trust: TrustLevel, switches_encoding: bool, address: IpAddr) -> Self {
+        let (updates_tx, updates) = mpsc::channel(UPDATE_BUFFER);
+        Self {
+            auth: server.auth(),
+            identity: None,
+            connection: None,
+            presence: None,
+            address,
+            bucket: TokenBucket::full(server.limiter.limits().burst, Instant::now()),
+            created: HashSet::new(),
+            server,
+            trust,
+            encoding: Encoding::Json,
+            switches_encoding,
+            greeted: false,
+            deltas: false,
+            locale: Locale::default(),
+            sent: HashMap::new(),
+            players: HashMap::new(),
+            following: HashMap::new(),
+            queued: HashMap::new(),
+            tournaments: HashMap::new(),
+            updates_tx,
+            updates,
+        }
+    }
+
+    /// Encoding of the next message in and reply out.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// The next update of a followed game. Never completes while the client
+    /// follows none, and is safe to cancel.
+    pub async fn next_update(&mut self) -> Response {
+        let update = self
+            .updates
+            .recv()
+            .await
+            .expect("the session holds a sender");
+        match &update {
+            Response::MatchFound { game_id, player_id, .. } => {
+                // The task that waited for the match now follows the game
+                if let Some(task) = self.queued.remove(player_id) {
+                    self.following.insert(*game_id, task);
+                }
+                self.players.insert(*player_id, *game_id);
+                self.claim_seat(*game_id, *player_id).await;
+            }
+            Response::PlayerKicked { game_id, player_id } => self.left(*game_id, *player_id),
+            Response::GameClosed { game_id } => self.closed(*game_id),
+            Response::RematchStarted { game_id, new_game_id } => self.rematched(*game_id, *new_game_id),
+            _ => {}
+        }
+        self.show_presence();
+        if let Response::MatchFound {
+            game_id, game_state, ..
+        } = &update
+        {
+            self.record(*game_id, game_state);
+        }
+        self.shrink(update)
+    }
+
+    /// Remembers a state of the game sent to a client using deltas, which
+    /// counts as the one it holds.
+    fn record(&mut self, game_id: GameId, game_state: &GameState) {
+        if !self.deltas {
+            return;
+        }
+        let sent = self.sent.entry(game_id).or_default();
+        let hash = game_state.state_hash();
+        sent.acked = Some(hash);
+        sent.last = Some(game_state.clone());
+        sent.recent.push_back(hash);
+        if sent.recent.len() > RECENT_STATES {
+            sent.recent.pop_front();
+        }
+    }
+
+    /// Turns a `StateUpdate` into a `StateDelta` from the state the client
+    /// holds, if the server knows it and the change replays.
+    fn shrink(&mut self, update: Response) -> Response {
+        let Response::StateUpdate { game_id, game_state } = update else {
+            return update;
+        };
+        if !self.deltas {
+            return Response::StateUpdate { game_id, game_state };
+        }
+
+        let delta = self.sent.get(&game_id).and_then(|sent| {
+            let acked = sent.acked?;
+            let base = sent.last.as_ref().filter(|_| sent.recent.back() == Some(&acked))?;
+            let (events, moves) = delta::between(base, &game_state)?;
+            Some(Response::StateDelta {
+                game_id,
+                base_hash: acked,
+                events,
+                moves,
+                state_hash: game_state.state_hash(),
+            })
+        });
+        self.record(game_id, &game_state);
+        delta.unwrap_or(Response::StateUpdate { game_id, game_state })
+    }
+
+    /// Encodes a response in the connection's encoding, with errors in its
+    /// locale.
+    pub fn encode(&self, response: &Response) -> io::Result<Vec<u8>> {
+        let translated;
+        let response = match response {
+            Response::Error { message } if self.locale != Locale::En => {
+                translated = Response::Error {
+                    message: message.in_locale(self.locale),
+                };
+                &translated
+            }
+            response => response,
+        };
+        self.encoding
+            .encode(response)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Tells the server the account a signed-in client's seat plays under.
+    async fn claim_seat(&self, game_id: GameId, player_id: PlayerId) {
+        if let Some(identity) = &self.identity {
+            self.server.set_account(game_id, player_id, identity.user_id.clone()).await;
+        }
+    }
+
+    /// Tells the server which game a signed-in client has a seat in, for
+    /// its friends to see.
+    fn show_presence(&mut self) {
+        let (Some(identity), Some(connection)) = (&self.identity, self.connection) else {
+            return;
+        };
+        let game = self.players.values().next().copied();
+        if game != self.presence {
+            self.presence = game;
+            self.server.set_presence(&identity.user_id, connection, game);
+        }
+    }
+
+    /// Answers the friends messages, which only signed-in clients can send.
+    async fn friends(&self, message: Message) -> Response {
+        let Some(identity) = &self.identity else {
+            return Response::Error {
+                message: text!("sign_in_friends"),
+            };
+        };
+        let account = &identity.user_id;
+        match message {
+            Message::AddFriend { friend_id } => self.server.add_friend(account, friend_id),
+            Message::RemoveFriend { friend_id } => self.server.remove_friend(account, friend_id),
+            Message::ListFriends => self.server.list_friends(account),
+            Message::InviteFriend { friend_id, game_id } => {
+                let seat = self.players.iter().find(|(_, id)| **id == game_id).map(|(player_id, _)| *player_id);
+                self.server.invite_friend(account, seat, friend_id, game_id).await
+            }
+            message => self.server.handle_message_with_trust(self.trust, message).await,
+        }
+    }
+
+    async fn follow(&mut self, game_id: GameId) {
+        if self.following.contains_key(&game_id) {
+            return;
+        }
+        let Some(updates) = self.server.subscribe(game_id).await else {
+            return;
+        };
+        let task = tokio::spawn(forward_updates(updates, self.updates_tx.clone()));
+        self.following.insert(game_id, task);
+    }
+
+    async fn follow_tournament(&mut self, tournament_id: TournamentId) {
+        if self.tournaments.contains_key(&tournament_id) {
+            return;
+        }
+        let Some(updates) = self.server.subscribe_tournament(tournament_id).await else {
+            return;
+        };
+        let task = tokio::spawn(forward_updates(updates, self.updates_tx.clone()));
+        self.tournaments.insert(tournament_id, task);
+    }
+
+    /// Waits in the background for the match of a queued player, then passes
+    /// on its `MatchFound` and follows its game.
+    async fn wait_for_match(&mut self, player_id: PlayerId) {
+        let Some(found) = self.server.wait_for_match(player_id).await else {
+            return;
+        };
+        let server = self.server.clone();
+        let forward = self.updates_tx.clone();
+        let task = tokio::spawn(async move {
+            let Ok(found) = found.await else {
+                return;
+            };
+            let Response::MatchFound { game_id, .. } = &found else {
+                return;
+            };
+            server.seen([(*game_id, player_id)]).await;
+            // Subscribe first, so no update goes missing after the match
+            let updates = server.subscribe(*game_id).await;
+            if forward.send(found).await.is_err() {
+                return;
+            }
+            if let Some(updates) = updates {
+                forward_updates(updates, forward).await;
+            }
+        });
+        self.queued.insert(player_id, task);
+    }
+
+    fn unfollow(&mut self, game_id: GameId) {
+        if let Some(task) = self.following.remove(&game_id) {
+            task.abort();
+        }
+    }
+
+    /// Stops following a game once the client's last player there is gone.
+    fn left(&mut self, game_id: GameId, player_id: PlayerId) {
+        if self.players.remove(&player_id).is_some() && !self.players.values().any(|id| *id == game_id) {
+            self.unfollow(game_id);
+        }
+    }
+
+    fn closed(&mut self, game_id: GameId) {
+        self.players.retain(|_, id| *id != game_id);
+        self.created.remove(&game_id);
+        self.unfollow(game_id);
+    }
+
+    /// Carries the client's seats and subscription in a finished game over
+    /// to its rematch, whose updates come through the same subscription.
+    fn rematched(&mut self, game_id: GameId, new_game_id: GameId) {
+        for seat in self.players.values_mut() {
+            if *seat == game_id {
+                *seat = new_game_id;
+            }
+        }
+        if let Some(task) = self.following.remove(&game_id) {
+            self.following.insert(new_game_id, task);
+        }
+        if self.created.remove(&game_id) {
+            self.created.insert(new_game_id);
+        }
+    }
+
+    fn authenticate(&mut self, token: &str) -> Response {
+        let Some(auth) = &self.auth else {
+            return Response::Error {
+                message: text!("no_auth"),
+            };
+        };
+        match auth.authenticator.authenticate(token) {
+            Ok(identity) => {
+                self.sign_out();
+                self.connection = Some(self.server.connect_friend(&identity, self.updates_tx.clone()));
+                self.identity = Some(identity.clone());
+                self.show_presence();
+                Response::Authenticated { identity }
+            }
+            Err(message) => Response::Error { message },
+        }
+    }
+
+    /// Takes the connection off the account it signed in to, if any.
+    fn sign_out(&mut self) {
+        if let (Some(identity), Some(connection)) = (&self.identity, self.connection.take()) {
+            self.server.disconnect_friend(&identity.user_id, connection);
+        }
+        self.presence = None;
+    }
+
+    /// Holds an untrusted client to acting for the seats it joined or
+    /// queued for, signed in or not. Relays and servers act for anyone.
+    fn check_own_seat(&self, message: &Message) -> Result<(), Text> {
+        if self.trust != TrustLevel::UntrustedPeer {
+            return Ok(());
+        }
+        let Some(player_id) = auth::acting_player(message) else {
+            return Ok(());
+        };
+        let own = player_id.is_ok_and(|player_id| {
+            self.players.contains_key(&player_id) || self.queued.contains_key(&player_id)
+        });
+        if !own {
+            return Err(text!("own_seats_only"));
+        }
+        Ok(())
+    }
+
+    /// Holds a message to what the client may do on a server with an
+    /// `Auth`: guests may only spectate unless the server lets them play,
+    /// and signed-in clients play under their identity.
+    fn check_signed_in(&self, mut message: Message) -> Result<Message, Text> {
+        let Some(auth) = &self.auth else {
+            return Ok(message);
+        };
+        let Some(identity) = &self.identity else {
+            match message {
+                Message::RankedQuickPlay { .. } => return Err(text!("sign_in_ranked")),
+                Message::StartSoloGame { .. } | Message::SubmitSoloGame { .. } => {
+                    return Err(text!("sign_in_scores"))
+                }
+                Message::AddFriend { .. }
+                | Message::RemoveFriend { .. }
+                | Message::ListFriends
+                | Message::InviteFriend { .. } => return Err(text!("sign_in_friends")),
+                _ => {}
+            }
+            if auth.guests_may_play || auth::guest_may_send(&message) {
+                return Ok(message);
+            }
+            return Err(text!("sign_in_to_play"));
+        };
+
+        match &mut message {
+            Message::JoinGame { player_name, .. } | Message::QuickPlay { player_name } => {
+                *player_name = identity.name.clone();
+            }
+            Message::RankedQuickPlay { player_name, account }
+            | Message::SubmitSoloGame {
+                player_name, account, ..
+            }
+            | Message::JoinTournament {
+                player_name, account, ..
+            } => {
+                *player_name = identity.name.clone();
+                *account = Some(identity.user_id.clone());
+            }
+            Message::StartSoloGame { account } => *account = Some(identity.user_id.clone()),
+            _ => {}
+        }
+        Ok(message)
+    }
+
+    /// Why a message from an untrusted client isn't handled, if it is too
+    /// large or comes too fast; see `limits`. Other clients aren't limited.
+    fn throttle(&mut self, frame: &[u8]) -> Option<Response> {
+        if self.trust != TrustLevel::UntrustedPeer {
+            return None;
+        }
+        let limits = self.server.limiter.limits();
+        if frame.len() > limits.max_message_len {
+            return Some(Response::RateLimited {
+                limit: Limit::MessageSize,
+                retry_after_ms: None,
+            });
+        }
+        let now = Instant::now();
+        let taken = self
+            .bucket
+            .take(limits.messages_per_second, limits.burst, now)
+            .map_err(|wait| (Limit::Messages, wait))
+            .and_then(|()| {
+                self.server
+                    .limiter
+                    .take(self.address, &limits, now)
+                    .map_err(|wait| (Limit::AddressMessages, wait))
+            });
+        taken.err().map(|(limit, wait)| Response::RateLimited {
+            limit,
+            retry_after_ms: Some(wait.as_millis() as u64 + 1),
+        })
+    }
+
+    /// Whether the message would take an untrusted client into more games
+    /// than it may be in at once. A tournament joined counts as one.
+    fn over_game_limit(&self, message: &Message) -> bool {
+        if self.trust != TrustLevel::UntrustedPeer
+            || !matches!(
+                message,
+                Message::CreateGame { .. }
+                    | Message::JoinGame { .. }
+                    | Message::QuickPlay { .. }
+                    | Message::RankedQuickPlay { .. }
+                    | Message::JoinTournament { .. }
+            )
+        {
+            return false;
+        }
+        let games: HashSet<&GameId> = self.players.values().chain(&self.created).collect();
+        games.len() + self.queued.len() + self.tournaments.len() >= self.server.limiter.limits().max_games_per_client
+    }
+
+    /// Passes a message on to the server, keeping track of what the
+    /// connection needs to know on the way.
+    async fn dispatch(&mut self, message: Message) -> Response {
+        match message {
+            Message::AckState { game_id, state_hash } => {
+                let sent = self.sent.entry(game_id).or_default();
+                sent.acked = Some(state_hash);
+                // A state it was sent lately: the updates since are on their way
+                if sent.recent.contains(&state_hash) {
+                    return Response::StateAcked { game_id };
+                }
+                let ack = Message::AckState { game_id, state_hash };
+                self.server.handle_message_with_trust(self.trust, ack).await
+            }
+            Message::NegotiateEncoding { .. } if !self.switches_encoding => Response::EncodingSelected {
+                encoding: Encoding::Json,
+            },
+            Message::JoinTournament { tournament_id, .. } if self.tournaments.contains_key(&tournament_id) => {
+                Response::Error {
+                    message: text!("tournament_joined"),
+                }
+            }
+            message @ (Message::AddFriend { .. }
+            | Message::RemoveFriend { .. }
+            | Message::ListFriends
+            | Message::InviteFriend { .. }) => self.friends(message).await,
+            message => self.server.handle_request(self.trust, self.address, message).await,
+        }
+    }
+
+    /// Handles one message and encodes the reply. Any message, even one
+    /// that fails, counts as a heartbeat of the client's players.
+    pub async fn handle(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        if let Some(limited) = self.throttle(frame) {
+            return self.encode(&limited);
+        }
+        if !self.players.is_empty() {
+            let seats = self.players.iter().map(|(player_id, game_id)| (*game_id, *player_id));
+            self.server.seen(seats).await;
+        }
+        let first = !std::mem::replace(&mut self.greeted, true);
+        let decoded = self.encoding.decode::<Message>(frame);
+        // Even a client turned away is told why in its language
+        if let (true, Ok(Message::Hello { locale: Some(tag), .. })) = (first, &decoded) {
+            self.locale = Locale::from_tag(tag).unwrap_or_default();
+        }
+        let response = match decoded {
+            Ok(Message::Hello { .. }) if !first => Response::Error {
+                message: text!("hello_first"),
+            },
+            Ok(Message::Hello {
+                protocol_version,
+                features,
+                locale,
+                ..
+            }) if !self.switches_encoding => {
+                let hello = Message::Hello {
+                    protocol_version,
+                    encodings: vec![Encoding::Json],
+                    features,
+                    locale,
+                };
+                self.server.handle_message_with_trust(self.trust, hello).await
+            }
+            Ok(Message::Authenticate { token }) if self.auth.is_some() => self.authenticate(&token),
+            Ok(message) => match self.check_own_seat(&message).and_then(|()| self.check_signed_in(message)) {
+                Ok(message) if self.over_game_limit(&message) => Response::RateLimited {
+                    limit: Limit::Games,
+                    retry_after_ms: None,
+                },
+                Ok(message) => self.dispatch(message).await,
+                Err(message) => Response::Error { message },
+            },
+            // Likely a newer client that skipped the handshake
+            Err(err) => Response::Error {
+                message: text!("invalid_message", PROTOCOL_VERSION, err),
+            },
+        };
+
+        let reply = self.encode(&response)?;
+        if let Response::Reconnected {
+            game_id, game_state, ..
+        }
+        | Response::MatchFound {
+            game_id, game_state, ..
+        }
+        | Response::Resync {
+            game_id,
+            full_state: game_state,
+            ..
+        } = &response
+        {
+            self.record(*game_id, game_state);
+        }
+        match response {
+            // The reply to the negotiation itself still goes out in the old encoding
+            Response::EncodingSelected { encoding } => self.encoding = encoding,
+            Response::Welcome {
+                encoding, features, ..
+            } => {
+                self.encoding = encoding;
+                self.deltas = features.iter().any(|feature| feature == "state-deltas");
+            }
+            // Bot seats come without a token; the server plays those
+            Response::GameJoined {
+                game_id,
+                player_id,
+                session_token: Some(_),
+                ..
+            }
+            | Response::Reconnected {
+                game_id, player_id, ..
+            }
+            | Response::MatchFound {
+                game_id, player_id, ..
+            } => {
+                self.players.insert(player_id, game_id);
+                self.server.seen([(game_id, player_id)]).await;
+                self.claim_seat(game_id, player_id).await;
+                self.follow(game_id).await;
+            }
+            Response::Spectating { game_id, .. } => self.follow(game_id).await,
+            Response::TournamentJoined { tournament_id, .. } => self.follow_tournament(tournament_id).await,
+            Response::PlayerLeft { game_id, player_id } | Response::PlayerKicked { game_id, player_id } => {
+                self.left(game_id, player_id)
+            }
+            Response::GameCreated { game_id, .. } => {
+                self.created.insert(game_id);
+            }
+            Response::GameClosed { game_id } => self.closed(game_id),
+            Response::RematchStarted { game_id, new_game_id } => self.rematched(game_id, new_game_id),
+            Response::Queued { player_id, .. } => self.wait_for_match(player_id).await,
+            Response::LeftQueue { player_id } => {
+                if let Some(task) = self.queued.remove(&player_id) {
+                    task.abort();
+                }
+            }
+            _ => {}
+        }
+        self.show_presence();
+        Ok(reply)
+    }
+}
+
+/// What a client using state deltas was sent of one game.
+#[derive(Default)]
+struct Sent {
+    /// The last state sent, the only one deltas are taken from
+    last: Option<GameState>,
+    /// Hashes of the recently sent states, newest last
+    recent: VecDeque<u64>,
+    /// Hash of the state the client holds
+    acked: Option<u64>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.sign_out();
+        for task in self.following.values().chain(self.queued.values()).chain(self.tournaments.values()) {
+            task.abort();
+        }
+    }
+}
+
+/// Passes a game's updates on to a session until either side goes away.
+/// A session that falls behind holds this up, so its own receiver lags
+/// rather than the updates piling up.
+async fn forward_updates(mut updates: broadcast::Receiver<Response>, forward: mpsc::Sender<Response>) {
+    loop {
+        match updates.recv().await {
+            Ok(update) => {
+                if forward.send(update).await.is_err() {
+                    break;
+                }
+            }
+            // Updates carry the whole state, so the next one catches up
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Hands every accepted connection to `connect` on its own task.
+pub(crate) async fn accept_loop<F, Fut>(
+    listener: &TcpListener,
+    server: &GameServer,
+    connect: F,
+) -> io::Result<()>
+where
+    F: Fn(GameServer, TcpStream, SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<()>> + Send + 'static,
+{
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let connection = connect(server.clone(), stream, addr);
+        let span = tracing::info_span!("connection", peer = %addr);
+        tokio::spawn(
+            async move {
+                // A broken connection only ends that client's session
+                if let Err(err) = connection.await {
+                    tracing::debug!(%err, "connection failed");
+                }
+            }
+            .instrument(span),
+        );
+    }
+}
+
+/// Ticks the turn timers, saves games and sweeps out stale ones, until the
+/// task is dropped. A server runs it once, next to however many transports
+/// serve it: each tick saves games, starts rounds and fills queues.
+pub async fn run_timers(server: &GameServer) -> std::convert::Infallible {
+    let mut timers = tokio::time::interval(TIMER_TICK);
+    let mut sweeps = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = timers.tick() => {
+                server.tick_turn_timers().await;
+                server.check_heartbeats().await;
+                server.check_queue().await;
+                server.check_rounds().await;
+                server.limiter.forget_idle(Instant::now());
+                // Clients see bot moves in their game's `StateUpdate`s;
+                // don't let the queue pile up
+                server.take_bot_moves().await;
+                // Games that fail to save are tried again on the next tick
+                let _ = server.save_games().await;
+            }
+            _ = sweeps.tick() => {
+                server.sweep_expired().await;
+            }
+        }
+    }
+}
+
+pub struct TcpTransport {
+    listener: TcpListener,
+    trust: TrustLevel,
+    framing: Framing,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsAcceptor>,
+}
+
+impl TcpTransport {
+    pub async fn bind(addr: impl ToSocketAddrs, trust: TrustLevel, framing: Framing) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr).await?, trust, framing))
+    }
+
+    pub fn from_listener(listener: TcpListener, trust: TrustLevel, framing: Framing) -> Self {
+        Self {
+            listener,
+            trust,
+            framing,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Has every client do a TLS handshake before its first message.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(self, acceptor: crate::tls::TlsAcceptor) -> Self {
+        Self {
+            tls: Some(acceptor),
+            ..self
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    async fn serve(self, server: GameServer) -> io::Result<()> {
+        let (trust, framing) = (self.trust, self.framing);
+        #[cfg(feature = "tls")]
+        if let Some(tls) = self.tls {
+            return accept_loop(&self.listener, &server, |server, stream, addr| {
+                let accepting = tls.accept(stream);
+                async move { handle_connection(server, accepting.await?, addr, trust, framing).await }
+            })
+            .await;
+        }
+        accept_loop(&self.listener, &server, |server, stream, addr| {
+            handle_connection(server, stream, addr, trust, framing)
+        })
+        .await
+    }
+}
+
+async fn handle_connection<S>(
+    server: GameServer,
+    stream: S,
+    addr: SocketAddr,
+    trust: TrustLevel,
+    framing: Framing,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+
+    // Reads run on their own task: a partly read frame must survive an
+    // update being written in the meantime
+    let (frames_tx, mut frames) = mpsc::channel(1);
+    let reads = tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        loop {
+            let frame = read_frame(&mut reader, framing).await;
+            let last = !matches!(frame, Ok(Some(_)));
+            if frames_tx.send(frame).await.is_err() || last {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session::new(server, trust, framing == Framing::LengthPrefixed, addr.ip());
+    let result = async {
+        loop {
+            let reply = tokio::select! {
+                frame = frames.recv() => match frame {
+                    Some(Ok(Some(frame))) => session.handle(&frame).await?,
+                    Some(Err(err)) => return Err(err),
+                    Some(Ok(None)) | None => return Ok(()),
+                },
+                update = session.next_update() => session.encode(&update)?,
+            };
+            match framing {
+                Framing::Lines => {
+                    writer.write_all(&reply).await?;
+                    writer.write_all(b"\n").await?;
+                }
+                Framing::LengthPrefixed => {
+                    writer.write_all(&(reply.len() as u32).to_be_bytes()).await?;
+                    writer.write_all(&reply).await?;
+                }
+            }
+        }
+    }
+    .await;
+    reads.abort();
+    result
+}
+
+/// The next message, or `None` once the client has closed the connection.
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    framing: Framing,
+) -> io::Result<Option<Vec<u8>>> {
+    match framing {
+        Framing::Lines => loop {
+            let mut line = Vec::new();
+            let mut limited = (&mut *reader).take(u64::from(MAX_FRAME_LEN) + 1);
+            if limited.read_until(b'\n', &mut line).await? == 0 {
+                return Ok(None);
+            }
+            if line.len() > MAX_FRAME_LEN as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Line of over {} bytes is too long", MAX_FRAME_LEN),
+                ));
+            }
+            if !line.trim_ascii().is_empty() {
+                return Ok(Some(line));
+            }
+        },
+        Framing::LengthPrefixed => {
+            let mut len = [0; 4];
+            match reader.read_exact(&mut len).await {
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err),
+            }
+            let len = u32::from_be_bytes(len);
+            if len > MAX_FRAME_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Frame of {} bytes is too long", len),
+                ));
+            }
+            let mut frame = vec![0; len as usize];
+            reader.read_exact(&mut frame).await?;
+            Ok(Some(frame))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Access, Authenticator, Limits, Matchmaking, Presence, Visibility};
+    use game_core::GameMove;
+
+    async fn start(framing: Framing) -> TcpStream {
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, framing)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+        TcpStream::connect(addr).await.unwrap()
+    }
+
+    fn join() -> Message {
+        Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        }
+    }
+
+    async fn request(stream: &mut TcpStream, encoding: Encoding, message: &Message) -> Response {
+        let bytes = encoding.encode(message).unwrap();
+        stream.write_all(&(bytes.len() as u32).to_be_bytes()).await.unwrap();
+        stream.write_all(&bytes).await.unwrap();
+        receive(stream, encoding).await
+    }
+
+    async fn receive(stream: &mut TcpStream, encoding: Encoding) -> Response {
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).await.unwrap();
+        let mut reply = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut reply).await.unwrap();
+        encoding.decode(&reply).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_length_prefixed() {
+        let mut stream = start(Framing::LengthPrefixed).await;
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &join()).await,
+            Response::GameJoined { .. }
+        ));
+
+        let negotiate = Message::NegotiateEncoding {
+            offered: Encoding::supported(),
+        };
+        let Response::EncodingSelected { encoding } =
+            request(&mut stream, Encoding::Json, &negotiate).await
+        else {
+            panic!("Expected EncodingSelected response");
+        };
+        assert_eq!(encoding, Encoding::supported()[0]);
+        assert!(matches!(
+            request(&mut stream, encoding, &join()).await,
+            Response::GameJoined { .. }
+        ));
+
+        // Announcing an oversized frame ends the connection
+        stream.write_all(&(MAX_FRAME_LEN + 1).to_be_bytes()).await.unwrap();
+        assert_eq!(stream.read(&mut [0; 1]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_hello() {
+        let mut stream = start(Framing::LengthPrefixed).await;
+        let hello = Message::Hello {
+            protocol_version: PROTOCOL_VERSION + 1,
+            encodings: Encoding::supported(),
+            features: vec!["quick-play".to_string()],
+            locale: None,
+        };
+        let Response::Welcome {
+            protocol_version,
+            encoding,
+            features,
+            ..
+        } = request(&mut stream, Encoding::Json, &hello).await
+        else {
+            panic!("Expected Welcome response");
+        };
+        assert_eq!(protocol_version, PROTOCOL_VERSION);
+        assert_eq!(encoding, Encoding::supported()[0]);
+        assert_eq!(features, vec!["quick-play".to_string()]);
+
+        assert!(matches!(
+            request(&mut stream, encoding, &join()).await,
+            Response::GameJoined { .. }
+        ));
+        assert!(matches!(
+            request(&mut stream, encoding, &hello).await,
+            Response::Error { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_errors_in_the_client_locale() {
+        let mut stream = start(Framing::LengthPrefixed).await;
+        let hello = Message::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            encodings: vec![Encoding::Json],
+            features: Vec::new(),
+            locale: Some("fr-FR".to_string()),
+        };
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &hello).await,
+            Response::Welcome {
+                locale: Locale::Fr,
+                ..
+            }
+        ));
+        let get = Message::GetGameState {
+            game_id: GameId::new(),
+            access: None,
+        };
+        match request(&mut stream, Encoding::Json, &get).await {
+            Response::Error { message } => assert_eq!(message, "Partie introuvable"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+    }
+
+    struct Tokens;
+
+    impl Authenticator for Tokens {
+        fn authenticate(&self, token: &str) -> Result<Identity, Text> {
+            match token {
+                "alice" => Ok(Identity {
+                    user_id: "user-1".to_string(),
+                    name: "Alice".to_string(),
+                }),
+                "bob" => Ok(Identity {
+                    user_id: "user-2".to_string(),
+                    name: "Bob".to_string(),
+                }),
+                _ => Err(text!("unknown_token")),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth() {
+        let server = GameServer::new();
+        server.set_auth(Auth::required(Tokens));
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::AuthoritativeServer, Framing::LengthPrefixed)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(server));
+        let mut player = TcpStream::connect(addr).await.unwrap();
+        let mut guest = TcpStream::connect(addr).await.unwrap();
+
+        // Guests can't take a seat
+        assert!(matches!(
+            request(&mut player, Encoding::Json, &join()).await,
+            Response::Error { .. }
+        ));
+        let sign_in = |token: &str| Message::Authenticate {
+            token: token.to_string(),
+        };
+        assert!(matches!(
+            request(&mut player, Encoding::Json, &sign_in("mallory")).await,
+            Response::Error { .. }
+        ));
+        assert!(matches!(
+            request(&mut player, Encoding::Json, &sign_in("alice")).await,
+            Response::Authenticated { .. }
+        ));
+
+        // Players play under their identity, and only for themselves
+        let game_id = match request(&mut player, Encoding::Json, &join()).await {
+            Response::GameJoined { game_id, .. } => game_id,
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        };
+        match request(&mut player, Encoding::Json, &Message::GetGameState { game_id, access: None }).await {
+            Response::GameState { game_state } => assert_eq!(game_state.players[0].name, "Alice"),
+            other => panic!("Expected GameState response, got {:?}", other),
+        }
+        let move_for_someone_else = Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: PlayerId::new().to_string(),
+            },
+            move_id: None,
+        };
+        assert!(matches!(
+            request(&mut player, Encoding::Json, &move_for_someone_else).await,
+            Response::Error { .. }
+        ));
+
+        // Guests can still watch
+        assert!(matches!(
+            request(&mut guest, Encoding::Json, &Message::Spectate { game_id, access: None }).await,
+            Response::Spectating { .. }
+        ));
+        request(&mut player, Encoding::Json, &Message::StartGame { game_id, host: None }).await;
+        assert!(matches!(
+            receive(&mut guest, Encoding::Json).await,
+            Response::StateUpdate { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_friends() {
+        let server = GameServer::new();
+        server.set_auth(Auth::required(Tokens));
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::LengthPrefixed)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(server));
+        let mut alice = TcpStream::connect(addr).await.unwrap();
+        let mut bob = TcpStream::connect(addr).await.unwrap();
+        let add = |friend_id: &str| Message::AddFriend {
+            friend_id: friend_id.to_string(),
+        };
+
+        // Guests have no friends
+        match request(&mut alice, Encoding::Json, &Message::ListFriends).await {
+            Response::Error { message } => assert_eq!(message, "Sign in to have friends"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        for (stream, token) in [(&mut alice, "alice"), (&mut bob, "bob")] {
+            let sign_in = Message::Authenticate {
+                token: token.to_string(),
+            };
+            assert!(matches!(
+                request(stream, Encoding::Json, &sign_in).await,
+                Response::Authenticated { .. }
+            ));
+        }
+
+        // Alice asks, Bob is told and adds her back
+        assert!(matches!(
+            request(&mut alice, Encoding::Json, &add("user-2")).await,
+            Response::FriendRequested { .. }
+        ));
+        match receive(&mut bob, Encoding::Json).await {
+            Response::FriendRequest { from, name } => assert_eq!((from.as_str(), name.as_str()), ("user-1", "Alice")),
+            other => panic!("Expected FriendRequest, got {:?}", other),
+        }
+        assert!(matches!(
+            request(&mut bob, Encoding::Json, &add("user-1")).await,
+            Response::FriendAdded { .. }
+        ));
+        assert!(matches!(receive(&mut alice, Encoding::Json).await, Response::FriendAdded { .. }));
+
+        // Bob sees Alice at her table, invite-only, and is invited to it
+        let create = Message::CreateGame {
+            rules: None,
+            visibility: Visibility::InviteOnly,
+            turn_clock: None,
+        };
+        let (game_id, owner) = match request(&mut alice, Encoding::Json, &create).await {
+            Response::GameCreated { game_id, invite, .. } => (game_id, invite),
+            other => panic!("Expected GameCreated response, got {:?}", other),
+        };
+        let invite_alice = Message::InviteFriend {
+            friend_id: "user-1".to_string(),
+            game_id,
+        };
+        match request(&mut bob, Encoding::Json, &invite_alice).await {
+            Response::Error { message } => assert_eq!(message, "Only players in the game can invite friends to it"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        let take_seat = Message::JoinGame {
+            player_name: String::new(),
+            game_id: Some(game_id),
+            team: None,
+            variant: None,
+            code: None,
+            access: owner.map(Access::Invite),
+        };
+        assert!(matches!(
+            request(&mut alice, Encoding::Json, &take_seat).await,
+            Response::GameJoined { .. }
+        ));
+        match request(&mut bob, Encoding::Json, &Message::ListFriends).await {
+            Response::FriendList { friends, requests } => {
+                assert!(requests.is_empty());
+                assert_eq!(friends[0].name, "Alice");
+                assert_eq!(friends[0].presence, Presence::InGame { game_id });
+            }
+            other => panic!("Expected FriendList response, got {:?}", other),
+        }
+        let invite = Message::InviteFriend {
+            friend_id: "user-2".to_string(),
+            game_id,
+        };
+        assert!(matches!(
+            request(&mut alice, Encoding::Json, &invite).await,
+            Response::FriendInvited { .. }
+        ));
+        let (join_code, access) = match receive(&mut bob, Encoding::Json).await {
+            Response::GameInvite {
+                from, join_code, invite, ..
+            } if from == "user-1" => (join_code, Access::Invite(invite)),
+            other => panic!("Expected GameInvite, got {:?}", other),
+        };
+        let join_by_code = Message::JoinGame {
+            player_name: String::new(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: Some(join_code),
+            access: Some(access),
+        };
+        match request(&mut bob, Encoding::Json, &join_by_code).await {
+            Response::GameJoined { game_id: joined, .. } => assert_eq!(joined, game_id),
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+
+        // Once Bob is gone, he can't be invited
+        drop(bob);
+        loop {
+            match request(&mut alice, Encoding::Json, &invite).await {
+                Response::Error { message } => {
+                    assert_eq!(message, "user-2 isn't online");
+                    break;
+                }
+                // Updates of the game Bob joined come first
+                Response::StateUpdate { .. } => continue,
+                // The server hasn't seen the connection close yet
+                Response::FriendInvited { .. } => tokio::time::sleep(Duration::from_millis(10)).await,
+                other => panic!("Expected Error response, got {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_own_seats_only() {
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::LengthPrefixed)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+        let mut alice = TcpStream::connect(addr).await.unwrap();
+        let mut mallory = TcpStream::connect(addr).await.unwrap();
+        let Response::GameJoined { game_id, player_id, .. } = request(&mut alice, Encoding::Json, &join()).await else {
+            panic!("Expected GameJoined response");
+        };
+
+        // Without any Auth, a client still can't act for a seat it didn't join
+        let leave = Message::LeaveGame { game_id, player_id };
+        match request(&mut mallory, Encoding::Json, &leave).await {
+            Response::Error { message } => assert_eq!(message, "Players can only act for their own seats"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+        assert!(matches!(
+            request(&mut alice, Encoding::Json, &leave).await,
+            Response::PlayerLeft { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_host_starts_the_game() {
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::LengthPrefixed)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+        let mut alice = TcpStream::connect(addr).await.unwrap();
+        let mut bob = TcpStream::connect(addr).await.unwrap();
+        let Response::GameJoined {
+            game_id,
+            session_token: Some(host),
+            ..
+        } = request(&mut alice, Encoding::Json, &join()).await
+        else {
+            panic!("Expected GameJoined response");
+        };
+        let join_bob = Message::JoinGame {
+            player_name: "Bob".to_string(),
+            game_id: Some(game_id),
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        let Response::GameJoined {
+            session_token: Some(bob_token),
+            ..
+        } = request(&mut bob, Encoding::Json, &join_bob).await
+        else {
+            panic!("Expected GameJoined response");
+        };
+
+        // Neither without a token nor with another player's
+        let start = |host| Message::StartGame { game_id, host };
+        assert!(matches!(
+            request(&mut bob, Encoding::Json, &start(None)).await,
+            Response::Error { .. }
+        ));
+        match request(&mut bob, Encoding::Json, &start(Some(bob_token))).await {
+            Response::Error { message } => assert_eq!(message, "Only the host can start the game"),
+            other => panic!("Expected Error response, got {:?}", other),
+        }
+
+        let mut reply = request(&mut alice, Encoding::Json, &start(Some(host))).await;
+        // Updates of Bob joining come first
+        while matches!(reply, Response::StateUpdate { .. }) {
+            reply = receive(&mut alice, Encoding::Json).await;
+        }
+        assert!(matches!(reply, Response::GameStarted { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits() {
+        let server = GameServer::new();
+        server
+            .set_limits(Limits {
+                messages_per_second: 1,
+                burst: 3,
+                max_games_per_client: 1,
+                max_games_per_address: 2,
+                max_message_len: 256,
+                ..Limits::default()
+            })
+            .unwrap();
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::LengthPrefixed)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(server));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &join()).await,
+            Response::GameJoined { .. }
+        ));
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &join()).await,
+            Response::RateLimited {
+                limit: Limit::Games,
+                retry_after_ms: None
+            }
+        ));
+        // Nor do new connections get around the address's limit
+        let mut again = TcpStream::connect(addr).await.unwrap();
+        assert!(matches!(
+            request(&mut again, Encoding::Json, &join()).await,
+            Response::GameJoined { .. }
+        ));
+        let mut once_more = TcpStream::connect(addr).await.unwrap();
+        assert!(matches!(
+            request(&mut once_more, Encoding::Json, &join()).await,
+            Response::RateLimited {
+                limit: Limit::AddressGames,
+                retry_after_ms: None
+            }
+        ));
+        // Too large to handle, but doesn't count against the rate
+        let shout = Message::QuickPlay {
+            player_name: "A".repeat(300),
+        };
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &shout).await,
+            Response::RateLimited {
+                limit: Limit::MessageSize,
+                ..
+            }
+        ));
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &Message::Ping).await,
+            Response::Pong
+        ));
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &Message::Ping).await,
+            Response::RateLimited {
+                limit: Limit::Messages,
+                retry_after_ms: Some(_)
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_state_deltas() {
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::AuthoritativeServer, Framing::LengthPrefixed)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let hello = Message::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            encodings: vec![Encoding::Json],
+            features: vec!["state-deltas".to_string()],
+            locale: None,
+        };
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &hello).await,
+            Response::Welcome { features, .. } if features == ["state-deltas"]
+        ));
+        let Response::GameJoined {
+            game_id,
+            player_id,
+            session_token: Some(host),
+            ..
+        } = request(&mut stream, Encoding::Json, &join()).await
+        else {
+            panic!("Expected GameJoined response");
+        };
+        let add_bot = Message::AddBot {
+            game_id,
+            host,
+            difficulty: "easy".to_string(),
+            player_name: None,
+        };
+        request(&mut stream, Encoding::Json, &add_bot).await;
+        // Nothing to take a delta from yet
+        assert!(matches!(
+            receive(&mut stream, Encoding::Json).await,
+            Response::StateUpdate { .. }
+        ));
+
+        request(&mut stream, Encoding::Json, &Message::StartGame { game_id, host: None }).await;
+        let mut game = match receive(&mut stream, Encoding::Json).await {
+            Response::StateUpdate { game_state, .. } => *game_state,
+            Response::StateDelta { .. } => panic!("Expected a StateUpdate for the fresh commitment"),
+            other => panic!("Expected StateUpdate, got {:?}", other),
+        };
+
+        // The player stays and the bot plays out the round: events only
+        let stay = Message::MakeMove {
+            game_id,
+            game_move: GameMove::Stay {
+                player_id: player_id.to_string(),
+            },
+            move_id: None,
+        };
+        request(&mut stream, Encoding::Json, &stay).await;
+        match receive(&mut stream, Encoding::Json).await {
+            Response::StateDelta {
+                base_hash,
+                events,
+                moves,
+                state_hash,
+                ..
+            } => {
+                assert_eq!(base_hash, game.state_hash());
+                game = delta::apply(&game, &events, &moves).unwrap();
+                assert_eq!(game.state_hash(), state_hash);
+            }
+            other => panic!("Expected StateDelta, got {:?}", other),
+        }
+        assert!(matches!(
+            receive(&mut stream, Encoding::Json).await,
+            Response::RoundResult { .. }
+        ));
+
+        // One a little behind is only waiting for updates
+        let ack = Message::AckState {
+            game_id,
+            state_hash: game.state_hash(),
+        };
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &ack).await,
+            Response::StateAcked { .. }
+        ));
+
+        // A client acknowledging a state the server never sent gets it all
+        let ack = Message::AckState {
+            game_id,
+            state_hash: 42,
+        };
+        match request(&mut stream, Encoding::Json, &ack).await {
+            Response::Resync {
+                full_state,
+                last_event_id,
+                ..
+            } => {
+                assert_eq!(last_event_id, full_state.events.len() as u64);
+                assert!(full_state.events.starts_with(&game.events));
+            }
+            other => panic!("Expected Resync, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_joined_clients_get_updates() {
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::Lines)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+
+        async fn send(writer: &mut tokio::net::tcp::OwnedWriteHalf, message: &Message) {
+            let mut line = serde_json::to_vec(message).unwrap();
+            line.push(b'\n');
+            writer.write_all(&line).await.unwrap();
+        }
+        async fn receive(lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>) -> Response {
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+        }
+
+        let (reader, mut alice) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut alice_lines = BufReader::new(reader).lines();
+        send(&mut alice, &join()).await;
+        let Response::GameJoined {
+            game_id,
+            session_token: Some(host),
+            ..
+        } = receive(&mut alice_lines).await
+        else {
+            panic!("Expected GameJoined response");
+        };
+
+        let (reader, mut bob) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut bob_lines = BufReader::new(reader).lines();
+        let bob_joins = Message::JoinGame {
+            player_name: "Bob".to_string(),
+            game_id: Some(game_id),
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        send(&mut bob, &bob_joins).await;
+        assert!(matches!(receive(&mut bob_lines).await, Response::GameJoined { .. }));
+
+        // Alice hears about Bob without asking
+        match receive(&mut alice_lines).await {
+            Response::StateUpdate { game_state, .. } => assert_eq!(game_state.players.len(), 2),
+            other => panic!("Expected StateUpdate, got {:?}", other),
+        }
+
+        // And both hear about a bot Alice seats
+        let add_bot = Message::AddBot {
+            game_id,
+            host,
+            difficulty: "easy".to_string(),
+            player_name: None,
+        };
+        send(&mut alice, &add_bot).await;
+        assert!(matches!(receive(&mut alice_lines).await, Response::GameJoined { .. }));
+        for lines in [&mut alice_lines, &mut bob_lines] {
+            match receive(lines).await {
+                Response::StateUpdate { game_state, .. } => assert_eq!(game_state.players.len(), 3),
+                other => panic!("Expected StateUpdate, got {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quick_play() {
+        let server = GameServer::new();
+        let pairs = Matchmaking {
+            min_players: 2,
+            max_players: 2,
+            ..Matchmaking::default()
+        };
+        server.set_matchmaking(pairs).await.unwrap();
+        let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer, Framing::LengthPrefixed)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(server));
+
+        let quick_play = |name: &str| Message::QuickPlay {
+            player_name: name.to_string(),
+        };
+        let mut alice = TcpStream::connect(addr).await.unwrap();
+        assert!(matches!(
+            request(&mut alice, Encoding::Json, &quick_play("Alice")).await,
+            Response::Queued { players_waiting: 1, .. }
+        ));
+
+        let mut bob = TcpStream::connect(addr).await.unwrap();
+        let Response::MatchFound {
+            game_id,
+            player_id: bob_id,
+            game_state,
+            ..
+        } = request(&mut bob, Encoding::Json, &quick_play("Bob")).await
+        else {
+            panic!("Expected MatchFound response");
+        };
+        assert_eq!(game_state.players.len(), 2);
+
+        // Alice is told of the match, and follows the game from then on
+        match receive(&mut alice, Encoding::Json).await {
+            Response::MatchFound { game_id: id, .. } => assert_eq!(id, game_id),
+            other => panic!("Expected MatchFound, got {:?}", other),
+        }
+        let leave = Message::LeaveGame {
+            game_id,
+            player_id: bob_id,
+        };
+        assert!(matches!(
+            request(&mut bob, Encoding::Json, &leave).await,
+            Response::PlayerLeft { .. }
+        ));
+        match receive(&mut alice, Encoding::Json).await {
+            Response::StateUpdate { game_state, .. } => assert_eq!(game_state.players.len(), 1),
+            other => panic!("Expected StateUpdate, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_on_new_connection() {
+        let mut stream = start(Framing::LengthPrefixed).await;
+        let addr = stream.peer_addr().unwrap();
+        let Response::GameJoined {
+            game_id,
+            session_token: Some(token),
+            ..
+        } = request(&mut stream, Encoding::Json, &join()).await
+        else {
+            panic!("Expected GameJoined response with a session token");
+        };
+        drop(stream);
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        match request(&mut stream, Encoding::Json, &Message::Reconnect { token }).await {
+            Response::Reconnected { game_state, .. } => assert_eq!(game_state.players.len(), 1),
+            other => panic!("Expected Reconnected response, got {:?}", other),
+        }
+
+        // The new connection follows the game again
+        let add_bot = Message::AddBot {
+            game_id,
+            host: token,
+            difficulty: "easy".to_string(),
+            player_name: None,
+        };
+        assert!(matches!(
+            request(&mut stream, Encoding::Json, &add_bot).await,
+            Response::GameJoined {
+                session_token: None,
+                ..
+            }
+        ));
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).await.unwrap();
+        let mut update = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut update).await.unwrap();
+        assert!(matches!(
+            Encoding::Json.decode(&update).unwrap(),
+            Response::StateUpdate { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lines_stay_on_json() {
+        let stream = start(Framing::Lines).await;
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        let negotiate = Message::NegotiateEncoding {
+            offered: vec![Encoding::Binary, Encoding::Json],
+        };
+        let mut line = serde_json::to_vec(&negotiate).unwrap();
+        line.extend_from_slice(b"\n\nnot json\n");
+        writer.write_all(&line).await.unwrap();
+
+        let reply = lines.next_line().await.unwrap().unwrap();
+        assert!(matches!(
+            serde_json::from_str(&reply).unwrap(),
+            Response::EncodingSelected {
+                encoding: Encoding::Json
+            }
+        ));
+        let reply = lines.next_line().await.unwrap().unwrap();
+        assert!(matches!(
+            serde_json::from_str(&reply).unwrap(),
+            Response::Error { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_slow_reader_skips_ahead() {
+        let (broadcast_tx, updates) = broadcast::channel(2);
+        let (forward, mut received) = mpsc::channel(1);
+        let task = tokio::spawn(forward_updates(updates, forward));
+        let games: Vec<GameId> = (0..5).map(|_| GameId::new()).collect();
+        for game_id in &games {
+            broadcast_tx.send(Response::GameClosed { game_id: *game_id }).unwrap();
+        }
+        drop(broadcast_tx);
+
+        // Only what the buffers hold is left, ending with the latest update
+        let mut got = Vec::new();
+        while let Some(Response::GameClosed { game_id }) = received.recv().await {
+            got.push(game_id);
+        }
+        assert!(got.len() <= 3);
+        assert_eq!(got.last(), games.last());
+        task.await.unwrap();
+    }
+}
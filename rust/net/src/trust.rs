@@ -0,0 +1,63 @@
+use crate::Message;
+use serde::{Deserialize, Serialize};
+
+/// How much a connection is trusted, which decides the messages it may send.
+///
+/// Only the authoritative server may push whole game states; relays forward
+/// lobby and move traffic, and untrusted peers may only act as players
+/// and host their own games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrustLevel {
+    AuthoritativeServer,
+    TrustedRelay,
+    UntrustedPeer,
+}
+
+impl TrustLevel {
+    pub fn permits(&self, message: &Message) -> bool {
+        match self {
+            TrustLevel::AuthoritativeServer => true,
+            TrustLevel::TrustedRelay => !matches!(message, Message::SyncState { .. }),
+            TrustLevel::UntrustedPeer => matches!(
+                message,
+                Message::Hello { .. }
+                    | Message::Authenticate { .. }
+                    | Message::JoinGame { .. }
+                    | Message::CreateGame { .. }
+                    | Message::ListGames
+                    | Message::InvitePlayer { .. }
+                    | Message::KickPlayer { .. }
+                    | Message::TransferHost { .. }
+                    | Message::CloseGame { .. }
+                    // Starting takes the host's session token, checked like kicking
+                    | Message::StartGame { host: Some(_), .. }
+                    | Message::QuickPlay { .. }
+                    | Message::RankedQuickPlay { .. }
+                    | Message::LeaveQueue { .. }
+                    | Message::MakeMove { .. }
+                    | Message::GetGameState { .. }
+                    | Message::Spectate { .. }
+                    | Message::AckState { .. }
+                    | Message::LeaveGame { .. }
+                    | Message::NegotiateEncoding { .. }
+                    | Message::VoteSkipTurn { .. }
+                    | Message::AddBot { .. }
+                    | Message::Reconnect { .. }
+                    | Message::RequestRematch { .. }
+                    | Message::Ready { .. }
+                    | Message::Reaction { .. }
+                    | Message::GetLeaderboard { .. }
+                    | Message::StartSoloGame { .. }
+                    | Message::SubmitSoloGame { .. }
+                    | Message::AddFriend { .. }
+                    | Message::RemoveFriend { .. }
+                    | Message::ListFriends
+                    | Message::InviteFriend { .. }
+                    | Message::CreateTournament { .. }
+                    | Message::JoinTournament { .. }
+                    | Message::GetTournament { .. }
+                    | Message::Ping
+            ),
+        }
+    }
+}
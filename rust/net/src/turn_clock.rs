@@ -0,0 +1,60 @@
+//! Turn clocks games can be created with. A player who doesn't act in time is
+//! played for, so one player gone AFK can't freeze the table.
+//!
+//! The time limit is part of the game's `GameConfig` and survives a handover;
+//! bot moves for timed-out players don't, and fall back to staying.
+
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::{text, Text};
+
+/// Longest turn a clock may allow.
+pub const MAX_TURN_SECONDS: u32 = 600;
+
+/// Strategy that plays for timed-out players when the clock says so.
+pub(crate) const STAND_IN_STRATEGY: &str = "medium";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnClock {
+    pub seconds: u32,
+    #[serde(default)]
+    pub auto_play: AutoPlay,
+}
+
+/// What the server does for a player whose time ran out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AutoPlay {
+    #[default]
+    Stay,
+    /// Whatever a medium bot would do in their place
+    BotMove,
+}
+
+impl TurnClock {
+    pub fn validate(&self) -> Result<(), Text> {
+        if self.seconds == 0 || self.seconds > MAX_TURN_SECONDS {
+            return Err(text!("turn_seconds", MAX_TURN_SECONDS));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn limit_ms(&self) -> u64 {
+        u64::from(self.seconds) * 1_000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let clock: TurnClock = serde_json::from_str(r#"{"seconds":20}"#).unwrap();
+        assert_eq!(clock.auto_play, AutoPlay::Stay);
+        assert_eq!(clock.limit_ms(), 20_000);
+        assert!(clock.validate().is_ok());
+        for seconds in [0, MAX_TURN_SECONDS + 1] {
+            assert!(TurnClock { seconds, ..clock }.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Milestones of each game's life, for integrations that don't play: chat
+//! bots announcing games, analytics pipelines counting them.
+//!
+//! Engines announce a `LifecycleEvent` when a game is created, starts, has a
+//! round scored and ends; follow them with `GameServer::subscribe_lifecycle`.
+//! With the `webhooks` feature, `Webhooks` POSTs each of them as JSON to the
+//! URLs a deployment registers.
+
+use crate::GameId;
+use game_core::outcome::GameOutcome;
+use game_core::{GameState, RoundSummary};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum LifecycleEvent {
+    GameCreated { game_id: GameId, variant: String },
+    /// The first round was dealt
+    GameStarted { game_id: GameId, players: Vec<String> },
+    RoundFinished { game_id: GameId, summary: RoundSummary },
+    GameOver {
+        game_id: GameId,
+        outcome: Option<GameOutcome>,
+        scores: Vec<FinalScore>,
+    },
+}
+
+/// Where a player ended up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalScore {
+    pub player_id: String,
+    pub name: String,
+    pub score: u32,
+}
+
+impl LifecycleEvent {
+    pub fn game_id(&self) -> GameId {
+        match self {
+            LifecycleEvent::GameCreated { game_id, .. }
+            | LifecycleEvent::GameStarted { game_id, .. }
+            | LifecycleEvent::RoundFinished { game_id, .. }
+            | LifecycleEvent::GameOver { game_id, .. } => *game_id,
+        }
+    }
+
+    pub(crate) fn started(game_id: GameId, game: &GameState) -> Self {
+        LifecycleEvent::GameStarted {
+            game_id,
+            players: game.players.iter().map(|player| player.name.clone()).collect(),
+        }
+    }
+
+    pub(crate) fn over(game_id: GameId, game: &GameState) -> Self {
+        LifecycleEvent::GameOver {
+            game_id,
+            outcome: game.outcome.clone(),
+            scores: game
+                .players
+                .iter()
+                .map(|player| FinalScore {
+                    player_id: player.id.clone(),
+                    name: player.name.clone(),
+                    score: player.score,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+pub use posting::Webhooks;
+
+#[cfg(feature = "webhooks")]
+mod posting {
+    use super::LifecycleEvent;
+    use std::time::Duration;
+    use tokio::sync::broadcast;
+
+    /// How long an endpoint has to answer each POST.
+    const POST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// URLs every lifecycle event is POSTed to, in the order they happen.
+    /// A failed POST is logged and not retried.
+    pub struct Webhooks {
+        urls: Vec<String>,
+        client: reqwest::Client,
+    }
+
+    impl Webhooks {
+        pub fn new(urls: Vec<String>) -> Result<Self, String> {
+            let client = reqwest::Client::builder()
+                .timeout(POST_TIMEOUT)
+                .build()
+                .map_err(|err| err.to_string())?;
+            Ok(Self { urls, client })
+        }
+
+        /// Posts the events until their sender is dropped, e.g. those of
+        /// `GameServer::subscribe_lifecycle`.
+        pub async fn run(self, mut events: broadcast::Receiver<LifecycleEvent>) {
+            loop {
+                match events.recv().await {
+                    Ok(event) => self.post(&event).await,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!(missed, "webhooks fell behind; events dropped");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        }
+
+        async fn post(&self, event: &LifecycleEvent) {
+            for url in &self.urls {
+                let sent = self.client.post(url).json(event).send().await;
+                match sent.and_then(|response| response.error_for_status()) {
+                    Ok(_) => tracing::debug!(%url, game_id = %event.game_id(), "webhook posted"),
+                    Err(err) => tracing::warn!(%url, game_id = %event.game_id(), %err, "webhook failed"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "webhooks"))]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    async fn test_posts_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (events, receiver) = broadcast::channel(4);
+        tokio::spawn(Webhooks::new(vec![url]).unwrap().run(receiver));
+        let event = LifecycleEvent::GameCreated {
+            game_id: GameId::new(),
+            variant: "classic".to_string(),
+        };
+        events.send(event.clone()).unwrap();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let body = loop {
+            let mut chunk = [0; 1024];
+            let len = stream.read(&mut chunk).await.unwrap();
+            request.extend_from_slice(&chunk[..len]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length: ")?.parse().ok())
+                    .unwrap();
+                if body.len() >= length {
+                    assert!(head.starts_with("POST /hook "));
+                    break body.to_string();
+                }
+            }
+        };
+        stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+        assert_eq!(serde_json::from_str::<LifecycleEvent>(&body).unwrap(), event);
+    }
+}
@@ -0,0 +1,156 @@
+//! WebSocket transport, for browser and React Native clients that cannot open
+//! raw TCP sockets. Each WebSocket message carries one protocol message: text
+//! frames hold JSON, and binary frames whatever `Hello` or `NegotiateEncoding`
+//! selected.
+
+use crate::transport::{accept_loop, Session, Transport, MAX_FRAME_LEN};
+use crate::{Encoding, GameServer, TrustLevel};
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message as Frame;
+
+pub struct WebSocketTransport {
+    listener: TcpListener,
+    trust: TrustLevel,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tls::TlsAcceptor>,
+}
+
+impl WebSocketTransport {
+    pub async fn bind(addr: impl ToSocketAddrs, trust: TrustLevel) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr).await?, trust))
+    }
+
+    pub fn from_listener(listener: TcpListener, trust: TrustLevel) -> Self {
+        Self {
+            listener,
+            trust,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Serves `wss://` rather than `ws://`.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(self, acceptor: crate::tls::TlsAcceptor) -> Self {
+        Self {
+            tls: Some(acceptor),
+            ..self
+        }
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    async fn serve(self, server: GameServer) -> io::Result<()> {
+        let trust = self.trust;
+        #[cfg(feature = "tls")]
+        if let Some(tls) = self.tls {
+            return accept_loop(&self.listener, &server, |server, stream, addr| {
+                let accepting = tls.accept(stream);
+                async move { handle_connection(server, accepting.await?, addr, trust).await }
+            })
+            .await;
+        }
+        accept_loop(&self.listener, &server, |server, stream, addr| {
+            handle_connection(server, stream, addr, trust)
+        })
+        .await
+    }
+}
+
+async fn handle_connection<S>(server: GameServer, stream: S, addr: SocketAddr, trust: TrustLevel) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_FRAME_LEN as usize),
+        ..WebSocketConfig::default()
+    };
+    let mut socket = tokio_tungstenite::accept_async_with_config(stream, Some(config))
+        .await
+        .map_err(io::Error::other)?;
+    let mut session = Session::new(server, trust, true, addr.ip());
+
+    loop {
+        // Read before handling: the reply to a negotiation is in the old encoding
+        let encoding = session.encoding();
+        let reply = tokio::select! {
+            frame = socket.next() => {
+                let frame = match frame {
+                    Some(frame) => frame.map_err(io::Error::other)?,
+                    None => break,
+                };
+                match frame {
+                    Frame::Text(text) => session.handle(text.as_bytes()).await?,
+                    Frame::Binary(bytes) => session.handle(&bytes).await?,
+                    Frame::Close(_) => break,
+                    // Pings are answered by the socket itself
+                    _ => continue,
+                }
+            }
+            update = session.next_update() => session.encode(&update)?,
+        };
+
+        let reply = match encoding {
+            Encoding::Json => Frame::Text(String::from_utf8(reply).map_err(io::Error::other)?),
+            Encoding::Binary => Frame::Binary(reply),
+        };
+        socket.send(reply).await.map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Message, Response};
+    use tokio_tungstenite::connect_async;
+
+    #[tokio::test]
+    async fn test_join_over_websocket() {
+        let transport = WebSocketTransport::bind("127.0.0.1:0", TrustLevel::UntrustedPeer)
+            .await
+            .unwrap();
+        let addr = transport.local_addr().unwrap();
+        tokio::spawn(transport.serve(GameServer::new()));
+
+        let (mut socket, _) = connect_async(format!("ws://{}", addr)).await.unwrap();
+        let join = Message::JoinGame {
+            player_name: "Alice".to_string(),
+            game_id: None,
+            team: None,
+            variant: None,
+            code: None,
+            access: None,
+        };
+        socket
+            .send(Frame::Text(serde_json::to_string(&join).unwrap()))
+            .await
+            .unwrap();
+
+        match socket.next().await.unwrap().unwrap() {
+            Frame::Text(reply) => match serde_json::from_str(&reply).unwrap() {
+                Response::GameJoined { .. } => {}
+                other => panic!("Expected GameJoined response, got {:?}", other),
+            },
+            other => panic!("Expected a text frame, got {:?}", other),
+        }
+
+        socket.send(Frame::Text("not json".to_string())).await.unwrap();
+        match socket.next().await.unwrap().unwrap() {
+            Frame::Text(reply) => assert!(matches!(
+                serde_json::from_str(&reply).unwrap(),
+                Response::Error { .. }
+            )),
+            other => panic!("Expected a text frame, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Serverless play over WebRTC data channels, for LAN and party games with
+//! no hosted server. One player's device is the host: it runs the
+//! `GameServer` in-process, plays its own seat through it directly, and
+//! serves everyone else, its guests, over a data channel each, one JSON
+//! message or reply per data channel message.
+//!
+//! Peers connect without a signaling server. A guest's `Signal::Offer` gets
+//! to the host by copy-paste or QR code, and the host's `Signal::Answer` goes
+//! back the same way. Each holds the whole SDP with every ICE candidate
+//! already gathered, so nothing else needs to be exchanged.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use net::transport::{run_timers, Session};
+use net::{GameServer, Message, Response, TrustLevel};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+
+/// How long a host waits for a guest it answered to open its channel.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Label of the data channel games are played over.
+const CHANNEL_LABEL: &str = "flip7";
+
+/// What peers pass each other out of band to connect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Signal {
+    /// From a guest to the host
+    Offer { sdp: String },
+    /// From the host back to the guest
+    Answer { sdp: String },
+}
+
+impl Signal {
+    /// One line of text, to copy-paste or show as a QR code.
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("signals serialize");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let json = URL_SAFE_NO_PAD
+            .decode(text.trim())
+            .map_err(|_| "Not a connection code".to_string())?;
+        serde_json::from_slice(&json).map_err(|_| "Not a connection code".to_string())
+    }
+}
+
+/// Serves a `GameServer`'s games to guests, and keeps its timers running
+/// for as long as it is kept.
+pub struct P2pHost {
+    server: GameServer,
+    ice_servers: Vec<String>,
+    timers: JoinHandle<()>,
+}
+
+impl P2pHost {
+    pub fn new(server: GameServer) -> Self {
+        let timers = {
+            let server = server.clone();
+            tokio::spawn(async move {
+                match run_timers(&server).await {}
+            })
+        };
+        Self {
+            server,
+            ice_servers: Vec::new(),
+            timers,
+        }
+    }
+
+    /// STUN or TURN servers to gather candidates from, e.g.
+    /// `stun:stun.l.google.com:19302`, for guests outside the local network.
+    pub fn with_ice_servers(mut self, ice_servers: Vec<String>) -> Self {
+        self.ice_servers = ice_servers;
+        self
+    }
+
+    /// Takes a guest's offer and returns the answer to pass back. The guest
+    /// is served once its channel opens, as an untrusted peer: games are
+    /// started by the host.
+    pub async fn accept(&self, offer: &Signal) -> Result<Signal, String> {
+        let Signal::Offer { sdp } = offer else {
+            return Err("Expected an offer".to_string());
+        };
+        let peer = new_peer(&self.ice_servers).await?;
+        let (channels_tx, mut channels) = mpsc::unbounded_channel();
+        peer.on_data_channel(Box::new(move |channel| {
+            // Listen right away, so the guest's first message isn't missed
+            let frames = frames_of(&channel);
+            let _ = channels_tx.send((channel, frames));
+            Box::pin(async {})
+        }));
+
+        let offer = RTCSessionDescription::offer(sdp.clone()).map_err(|err| format!("Invalid offer: {}", err))?;
+        peer.set_remote_description(offer)
+            .await
+            .map_err(|err| format!("Invalid offer: {}", err))?;
+        let answer = peer.create_answer(None).await.map_err(|err| err.to_string())?;
+        let sdp = describe(&peer, answer).await?;
+
+        // The task keeps the connection for as long as the guest is served
+        let server = self.server.clone();
+        tokio::spawn(async move {
+            if let Ok(Some((channel, frames))) = tokio::time::timeout(CONNECT_TIMEOUT, channels.recv()).await {
+                if let Err(err) = serve_channel(server, channel, frames).await {
+                    tracing::debug!(%err, "guest connection failed");
+                }
+            }
+            let _ = peer.close().await;
+        });
+        Ok(Signal::Answer { sdp })
+    }
+}
+
+impl Drop for P2pHost {
+    fn drop(&mut self) {
+        self.timers.abort();
+    }
+}
+
+/// A guest's connection to a host.
+pub struct P2pGuest {
+    peer: Arc<RTCPeerConnection>,
+    channel: Arc<RTCDataChannel>,
+    opened: Option<oneshot::Receiver<()>>,
+    frames: mpsc::UnboundedReceiver<Option<DataChannelMessage>>,
+}
+
+impl P2pGuest {
+    /// Starts connecting, returning the offer to pass to the host.
+    pub async fn offer(ice_servers: &[String]) -> Result<(Self, Signal), String> {
+        let peer = new_peer(ice_servers).await?;
+        let channel = peer
+            .create_data_channel(CHANNEL_LABEL, None)
+            .await
+            .map_err(|err| err.to_string())?;
+        let (opened_tx, opened) = oneshot::channel();
+        channel.on_open(Box::new(move || {
+            let _ = opened_tx.send(());
+            Box::pin(async {})
+        }));
+        let frames = frames_of(&channel);
+
+        let offer = peer.create_offer(None).await.map_err(|err| err.to_string())?;
+        let sdp = describe(&peer, offer).await?;
+        let guest = Self {
+            peer,
+            channel,
+            opened: Some(opened),
+            frames,
+        };
+        Ok((guest, Signal::Offer { sdp }))
+    }
+
+    /// Finishes connecting with the host's answer, once the channel is open.
+    pub async fn connect(&mut self, answer: &Signal) -> Result<(), String> {
+        let Signal::Answer { sdp } = answer else {
+            return Err("Expected an answer".to_string());
+        };
+        let opened = self.opened.take().ok_or("Already connected")?;
+        let answer = RTCSessionDescription::answer(sdp.clone()).map_err(|err| format!("Invalid answer: {}", err))?;
+        self.peer
+            .set_remote_description(answer)
+            .await
+            .map_err(|err| format!("Invalid answer: {}", err))?;
+        match tokio::time::timeout(CONNECT_TIMEOUT, opened).await {
+            Ok(Ok(())) => Ok(()),
+            _ => Err("Could not reach the host".to_string()),
+        }
+    }
+
+    pub async fn send(&self, message: &Message) -> Result<(), String> {
+        let json = serde_json::to_string(message).map_err(|err| err.to_string())?;
+        self.channel.send_text(json).await.map(|_| ()).map_err(|err| err.to_string())
+    }
+
+    /// The next reply or update from the host, or `None` once the channel
+    /// is closed.
+    pub async fn recv(&mut self) -> Option<Response> {
+        loop {
+            let frame = self.frames.recv().await.flatten()?;
+            // Likely something a newer host added
+            if let Ok(response) = serde_json::from_slice(&frame.data) {
+                return Some(response);
+            }
+        }
+    }
+
+    pub async fn close(&self) {
+        let _ = self.peer.close().await;
+    }
+}
+
+async fn new_peer(ice_servers: &[String]) -> Result<Arc<RTCPeerConnection>, String> {
+    let mut config = RTCConfiguration::default();
+    if !ice_servers.is_empty() {
+        config.ice_servers = vec![RTCIceServer {
+            urls: ice_servers.to_vec(),
+            ..Default::default()
+        }];
+    }
+    let peer = APIBuilder::new()
+        .build()
+        .new_peer_connection(config)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(Arc::new(peer))
+}
+
+/// Sets a local offer or answer and returns its SDP once every candidate is
+/// gathered, since there is no signaling server to trickle them through.
+async fn describe(peer: &RTCPeerConnection, description: RTCSessionDescription) -> Result<String, String> {
+    let mut gathered = peer.gathering_complete_promise().await;
+    peer.set_local_description(description)
+        .await
+        .map_err(|err| err.to_string())?;
+    let _ = gathered.recv().await;
+    let local = peer.local_description().await.ok_or("No local description")?;
+    Ok(local.sdp)
+}
+
+/// A channel's messages, followed by `None` once it closes.
+fn frames_of(channel: &RTCDataChannel) -> mpsc::UnboundedReceiver<Option<DataChannelMessage>> {
+    let (frames_tx, frames) = mpsc::unbounded_channel();
+    let closed_tx = frames_tx.clone();
+    channel.on_message(Box::new(move |frame| {
+        let _ = frames_tx.send(Some(frame));
+        Box::pin(async {})
+    }));
+    channel.on_close(Box::new(move || {
+        let _ = closed_tx.send(None);
+        Box::pin(async {})
+    }));
+    frames
+}
+
+async fn serve_channel(
+    server: GameServer,
+    channel: Arc<RTCDataChannel>,
+    mut frames: mpsc::UnboundedReceiver<Option<DataChannelMessage>>,
+) -> io::Result<()> {
+    // Guests have no address of their own to the server's limits
+    let mut session = Session::new(server, TrustLevel::UntrustedPeer, false, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    loop {
+        let reply = tokio::select! {
+            frame = frames.recv() => match frame.flatten() {
+                Some(frame) => session.handle(&frame.data).await?,
+                None => break,
+            },
+            update = session.next_update() => session.encode(&update)?,
+        };
+        let reply = String::from_utf8(reply).map_err(io::Error::other)?;
+        channel.send_text(reply).await.map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_codes() {
+        let offer = Signal::Offer {
+            sdp: "v=0\r\no=- 1 2 IN IP4 127.0.0.1\r\n".to_string(),
+        };
+        let code = offer.encode();
+        assert!(!code.contains(['\n', '+', '/', '=']));
+        assert_eq!(Signal::decode(&format!(" {}\n", code)).unwrap(), offer);
+        assert!(Signal::decode("not a code").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_guest_joins_host() {
+        let host = P2pHost::new(GameServer::new());
+        let (mut guest, offer) = P2pGuest::offer(&[]).await.unwrap();
+        let answer = host.accept(&Signal::decode(&offer.encode()).unwrap()).await.unwrap();
+        assert!(matches!(answer, Signal::Answer { .. }));
+        assert!(host.accept(&answer).await.is_err());
+        tokio::time::timeout(Duration::from_secs(20), guest.connect(&answer))
+            .await
+            .unwrap()
+            .unwrap();
+
+        guest
+            .send(&Message::JoinGame {
+                player_name: "Alice".to_string(),
+                game_id: None,
+                team: None,
+                variant: None,
+                code: None,
+                access: None,
+            })
+            .await
+            .unwrap();
+        match tokio::time::timeout(Duration::from_secs(5), guest.recv()).await.unwrap() {
+            Some(Response::GameJoined { .. }) => {}
+            other => panic!("Expected GameJoined response, got {:?}", other),
+        }
+        guest.close().await;
+    }
+}
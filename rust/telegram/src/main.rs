@@ -0,0 +1,287 @@
+//! Telegram bot playing Flip7 in group chats.
+//!
+//! Usage:
+//!   TELOXIDE_TOKEN=... flip7_telegram
+//!
+//! Each chat has one table: /new opens it, /join takes a seat, /start deals
+//! the first round once everyone is in, /state shows it again and /leave
+//! gives the seat up. The table is posted to the chat after every move, with
+//! Draw and Stay buttons for whoever's turn it is. That player also gets
+//! their hand, their chance of busting and the advisor's advice in a private
+//! chat with the bot, which they have to have started once.
+//!
+//! Every seat plays through a `flip7-client` connection of its own, to the
+//! server `FLIP7_SERVER` points at (`tcp://HOST:PORT`), which has to trust
+//! the bot as a relay to let it start games. Without it the bot runs a server
+//! in-process. Traces go to stderr, filtered by `RUST_LOG`.
+
+mod render;
+mod table;
+
+use flip7_client::Client;
+use futures_util::StreamExt;
+use game_core::events::GameEvent;
+use game_core::GameState;
+use net::{Framing, GameServer, TcpTransport, Transport, TrustLevel};
+use render::Choice;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use table::{Seat, Tables};
+use teloxide::prelude::*;
+use teloxide::types::{ChatId, User};
+use teloxide::utils::command::BotCommands;
+use tokio::task::JoinHandle;
+use tracing_subscriber::EnvFilter;
+
+/// How long a burst of events, like a round being dealt, may take to arrive
+/// before it is posted as one message.
+const SETTLE: Duration = Duration::from_millis(250);
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase", description = "Flip7, one table per chat:")]
+enum Command {
+    #[command(description = "show these commands")]
+    Help,
+    #[command(description = "open a table and sit at it")]
+    New,
+    #[command(description = "sit at the table")]
+    Join,
+    #[command(description = "deal the first round")]
+    Start,
+    #[command(description = "show the table")]
+    State,
+    #[command(description = "give up your seat")]
+    Leave,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+    let server = match std::env::var("FLIP7_SERVER") {
+        Ok(url) => url,
+        Err(_) => match host().await {
+            Ok(addr) => addr,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let bot = Bot::from_env();
+    let handler = dptree::entry()
+        .branch(Update::filter_message().filter_command::<Command>().endpoint(command))
+        .branch(Update::filter_callback_query().endpoint(button));
+    Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![Tables::new(server)])
+        .enable_ctrlc_handler()
+        .build()
+        .dispatch()
+        .await;
+    ExitCode::SUCCESS
+}
+
+/// Runs a server for the bot alone, trusting it to start games.
+async fn host() -> Result<String, String> {
+    let transport = TcpTransport::bind("127.0.0.1:0", TrustLevel::TrustedRelay, Framing::Lines)
+        .await
+        .map_err(|err| format!("Cannot start the game server: {}", err))?;
+    let addr = transport.local_addr().map_err(|err| err.to_string())?;
+    let server = GameServer::new();
+    let timers = server.clone();
+    tokio::spawn(async move { net::run_timers(&timers).await });
+    tokio::spawn(async move {
+        if let Err(err) = transport.serve(server).await {
+            tracing::error!(%err, "game server stopped");
+        }
+    });
+    Ok(addr.to_string())
+}
+
+async fn command(bot: Bot, msg: Message, cmd: Command, tables: Tables) -> ResponseResult<()> {
+    let chat = msg.chat.id;
+    let reply = if msg.chat.is_private() {
+        // Telegram sends /start when a user opens a private chat
+        match cmd {
+            Command::Start => Ok("Your hand and advice will come here when it's your turn.".to_string()),
+            _ => Err("Play from a group: add me to one and open a table with /new.".to_string()),
+        }
+    } else {
+        match (cmd, msg.from.as_ref()) {
+            (Command::Help, _) => Ok(Command::descriptions().to_string()),
+            (_, None) => return Ok(()),
+            (Command::New, Some(user)) => open(&bot, chat, user, &tables).await,
+            (Command::Join, Some(user)) => join(chat, user, &tables).await,
+            (Command::Start, Some(user)) => start(chat, user, &tables).await,
+            (Command::State, Some(_)) => show(&bot, chat, &tables).await,
+            (Command::Leave, Some(user)) => leave(&bot, chat, user, &tables).await,
+        }
+    };
+    match reply {
+        Ok(text) if text.is_empty() => {}
+        Ok(text) | Err(text) => {
+            bot.send_message(chat, text).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn open(bot: &Bot, chat: ChatId, user: &User, tables: &Tables) -> Result<String, String> {
+    if tables.has_table(chat) {
+        return Err("This chat already has a table; /join it".to_string());
+    }
+    let name = user.full_name();
+    let client = Client::connect(&tables.server).await?;
+    let seat = client.join(&name).await?;
+    let client = Arc::new(client);
+    let first = Seat {
+        user: user.id,
+        name: name.clone(),
+        player_id: seat.player_id,
+        client: client.clone(),
+    };
+    tables.open(chat, seat.game_id, first)?;
+    tables.watch(chat, watch(bot.clone(), chat, tables.clone(), client));
+    Ok(format!("{} opened a table. /join to sit down, /start once everyone is in.", name))
+}
+
+async fn join(chat: ChatId, user: &User, tables: &Tables) -> Result<String, String> {
+    let game_id = tables.game_id(chat).ok_or("No table here yet; open one with /new")?;
+    if tables.seat(chat, user.id).is_some() {
+        return Err("You already sit at this table".to_string());
+    }
+    let name = user.full_name();
+    let client = Client::connect(&tables.server).await?;
+    let seat = client.join_game(&name, game_id).await?;
+    let seat = Seat {
+        user: user.id,
+        name: name.clone(),
+        player_id: seat.player_id,
+        client: Arc::new(client),
+    };
+    tables.sit(chat, seat)?;
+    Ok(format!("{} sits down", name))
+}
+
+async fn start(chat: ChatId, user: &User, tables: &Tables) -> Result<String, String> {
+    let seat = tables.seat(chat, user.id).ok_or("Only players at the table can start it")?;
+    seat.client.start().await?;
+    // The watcher posts the round once it is dealt
+    Ok(String::new())
+}
+
+async fn show(bot: &Bot, chat: ChatId, tables: &Tables) -> Result<String, String> {
+    let seat = tables.first_seat(chat).ok_or("No table here yet; open one with /new")?;
+    let game = seat.client.state().ok_or("The table has no game yet")?;
+    post(bot, chat, tables, &[], &game).await;
+    Ok(String::new())
+}
+
+async fn leave(bot: &Bot, chat: ChatId, user: &User, tables: &Tables) -> Result<String, String> {
+    let (seat, rewatch) = tables.stand(chat, user.id)?;
+    let game_id = tables.game_id(chat);
+    if let Some(game_id) = game_id {
+        let leave = net::Message::LeaveGame {
+            game_id,
+            player_id: seat.player_id,
+        };
+        seat.client.request(leave).await?;
+    }
+    if rewatch {
+        if let Some(next) = tables.first_seat(chat) {
+            tables.watch(chat, watch(bot.clone(), chat, tables.clone(), next.client));
+        }
+    }
+    match game_id {
+        Some(_) => Ok(format!("{} left the table", seat.name)),
+        None => Ok(format!("{} left; the table is closed", seat.name)),
+    }
+}
+
+async fn button(bot: Bot, query: CallbackQuery, tables: Tables) -> ResponseResult<()> {
+    let chat = query.message.as_ref().map(|message| message.chat().id);
+    let choice = query.data.as_deref().and_then(Choice::from_data);
+    let played = match (chat, choice) {
+        (Some(chat), Some(choice)) => match tables.seat(chat, query.from.id) {
+            Some(seat) => match choice {
+                Choice::Draw => seat.client.draw().await.map(|_| ()),
+                Choice::Stay => seat.client.stay().await.map(|_| ()),
+            },
+            None => Err("You don't sit at this table".to_string()),
+        },
+        _ => Err("This table is closed".to_string()),
+    };
+    let answer = bot.answer_callback_query(query.id);
+    match played {
+        Ok(()) => answer.await?,
+        Err(err) => answer.text(err).show_alert(true).await?,
+    };
+    Ok(())
+}
+
+/// Posts the game to the chat after every burst of events `client` gets.
+fn watch(bot: Bot, chat: ChatId, tables: Tables, client: Arc<Client>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut events = client.events();
+        while let Some(event) = events.next().await {
+            let mut burst = vec![event];
+            while let Ok(Some(event)) = tokio::time::timeout(SETTLE, events.next()).await {
+                burst.push(event);
+            }
+            let Some(game) = client.state() else {
+                continue;
+            };
+            post(&bot, chat, &tables, &burst, &game).await;
+            if game.is_game_over() {
+                let _ = bot.send_message(chat, "Open another table with /new").await;
+                tables.close(chat);
+                return;
+            }
+        }
+    })
+}
+
+/// Posts the table, with buttons while it can be played, and tells the
+/// player whose turn it is their hand privately.
+async fn post(bot: &Bot, chat: ChatId, tables: &Tables, events: &[GameEvent], game: &GameState) {
+    let text = render::table(events, game);
+    let sent = if render::playable(game) {
+        bot.send_message(chat, text).reply_markup(render::keyboard()).await
+    } else {
+        bot.send_message(chat, text).await
+    };
+    match sent {
+        Ok(message) => {
+            if let Some(previous) = tables.posted(chat, message.id) {
+                let _ = bot.edit_message_reply_markup(chat, previous).await;
+            }
+        }
+        Err(err) => tracing::warn!(%err, chat = chat.0, "cannot post the table"),
+    }
+
+    if !render::playable(game) {
+        return;
+    }
+    let Some(seat) = game.current_player().and_then(|player| tables.seat_of(chat, &player.id)) else {
+        return;
+    };
+    let Some(view) = game.player_view(&seat.player_id.to_string()) else {
+        return;
+    };
+    // The advisor plays rollouts; keep them off the runtime's threads
+    let note = tokio::task::spawn_blocking(move || {
+        let advice = game_core::advisor::recommend(&view);
+        render::private_note(&view, &advice)
+    });
+    let Ok(note) = note.await else {
+        return;
+    };
+    if let Err(err) = bot.send_message(seat.user, note).await {
+        // Most likely the player never opened a private chat with the bot
+        tracing::debug!(%err, user = seat.user.0, "cannot message the player");
+    }
+}
@@ -0,0 +1,163 @@
+//! What the bot writes: the table posted to the group, the private notes
+//! sent to the player whose turn it is, and the buttons they play with.
+
+use game_core::advisor::{Action, Recommendation};
+use game_core::bots::PlayerView;
+use game_core::events::GameEvent;
+use game_core::pause::GamePhase;
+use game_core::GameState;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// A move made with a button under the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Draw,
+    Stay,
+}
+
+impl Choice {
+    /// The callback data of its button.
+    pub fn data(self) -> &'static str {
+        match self {
+            Choice::Draw => "draw",
+            Choice::Stay => "stay",
+        }
+    }
+
+    pub fn from_data(data: &str) -> Option<Self> {
+        match data {
+            "draw" => Some(Choice::Draw),
+            "stay" => Some(Choice::Stay),
+            _ => None,
+        }
+    }
+}
+
+pub fn keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("Draw", Choice::Draw.data()),
+        InlineKeyboardButton::callback("Stay", Choice::Stay.data()),
+    ]])
+}
+
+/// Whether the table gets buttons: only while someone can play.
+pub fn playable(game: &GameState) -> bool {
+    game.phase() == GamePhase::InRound
+}
+
+/// One line on what happened, or `None` for what the table already shows.
+pub fn describe(event: &GameEvent, game: &GameState) -> Option<String> {
+    let name = |player_id: &str| {
+        game.player(player_id)
+            .map_or_else(|| player_id.to_string(), |player| player.name.clone())
+    };
+    let line = match event {
+        GameEvent::RoundStarted { round_number } => format!("Round {} is dealt", round_number),
+        GameEvent::CardDealt { .. } => return None,
+        GameEvent::CardDrawn { player_id, card } => format!("{} draws {}", name(player_id), card),
+        GameEvent::PlayerBusted { player_id } => format!("{} busts!", name(player_id)),
+        GameEvent::PlayerStayed { player_id } => format!("{} stays", name(player_id)),
+        GameEvent::TurnSkipped { player_id } => format!("{}'s turn was skipped", name(player_id)),
+        GameEvent::TurnTimedOut { player_id } => format!("{} ran out of time and stays", name(player_id)),
+        GameEvent::PlayerEliminated { player_id } => format!("{} is knocked out", name(player_id)),
+        GameEvent::SuddenDeath { player_ids } => {
+            let names: Vec<String> = player_ids.iter().map(|id| name(id)).collect();
+            format!("Sudden death between {}", names.join(" and "))
+        }
+        GameEvent::GameWon { player_ids, .. } => {
+            let names: Vec<String> = player_ids.iter().map(|id| name(id)).collect();
+            format!("{} won the game!", names.join(" and "))
+        }
+        GameEvent::GamePaused { .. } => "The game is paused".to_string(),
+        GameEvent::GameResumed { .. } => "The game is back on".to_string(),
+    };
+    Some(line)
+}
+
+/// The message posted to the group: what just happened, then the table.
+pub fn table(events: &[GameEvent], game: &GameState) -> String {
+    let mut text: Vec<String> = events.iter().filter_map(|event| describe(event, game)).collect();
+    if !text.is_empty() {
+        text.push(String::new());
+    }
+    text.push(game.to_string());
+    if playable(game) {
+        if let Some(player) = game.current_player() {
+            text.push(format!("\n{} to play", player.name));
+        }
+    }
+    text.join("\n")
+}
+
+/// The note sent to a player privately when their turn comes.
+pub fn private_note(view: &PlayerView, advice: &Recommendation) -> String {
+    let action = match advice.action {
+        Action::Draw => "draw",
+        Action::Stay => "stay",
+    };
+    format!(
+        "Your turn. Hand {}, score {} of {}.\nChance the next card busts you: {:.0}%\nAdvice: {}. {}",
+        view.hand,
+        view.score,
+        view.target_score,
+        advice.bust_probability * 100.0,
+        action,
+        advice.explanation
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_core::advisor;
+
+    fn game() -> GameState {
+        let mut game = GameState::new_with_seed(7);
+        game.add_player("p1".to_string(), "Ada".to_string());
+        game.add_player("p2".to_string(), "Brice".to_string());
+        game
+    }
+
+    #[test]
+    fn test_choice_data_round_trips() {
+        for choice in [Choice::Draw, Choice::Stay] {
+            assert_eq!(Choice::from_data(choice.data()), Some(choice));
+        }
+        assert_eq!(Choice::from_data("fold"), None);
+    }
+
+    #[test]
+    fn test_table() {
+        let mut game = game();
+        game.start_round().unwrap();
+        assert!(playable(&game));
+        let events = [
+            GameEvent::CardDealt {
+                player_id: "p1".to_string(),
+                card: game_core::Card::new(4),
+            },
+            GameEvent::PlayerStayed {
+                player_id: "p2".to_string(),
+            },
+            GameEvent::GameWon {
+                player_ids: vec!["p1".to_string()],
+                team: None,
+                reason: game_core::outcome::WinReason::HighestScore,
+            },
+        ];
+        let text = table(&events, &game);
+        assert!(text.starts_with("Brice stays\nAda won the game!\n\nRound 1"), "{}", text);
+        assert!(text.ends_with("Ada to play"), "{}", text);
+    }
+
+    #[test]
+    fn test_private_note() {
+        let mut game = game();
+        game.start_round().unwrap();
+        let view = game.player_view("p1").unwrap();
+        let advice = advisor::recommend(&view);
+        let note = private_note(&view, &advice);
+        assert!(note.starts_with(&format!("Your turn. Hand {}, score 0 of", view.hand)), "{}", note);
+        assert!(note.contains(&advice.explanation));
+    }
+}
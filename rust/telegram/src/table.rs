@@ -0,0 +1,146 @@
+//! The games the bot runs, one per chat, and who sits at each.
+
+use flip7_client::{Client, GameId, PlayerId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use teloxide::types::{ChatId, MessageId, UserId};
+use tokio::task::JoinHandle;
+
+/// A Telegram user's seat, played through a connection of its own.
+#[derive(Clone)]
+pub struct Seat {
+    pub user: UserId,
+    pub name: String,
+    pub player_id: PlayerId,
+    pub client: Arc<Client>,
+}
+
+/// A chat's game.
+pub struct Table {
+    pub game_id: GameId,
+    seats: Vec<Seat>,
+    /// Posts the game's events to the chat, following the first seat
+    watcher: Option<JoinHandle<()>>,
+    /// The last table posted, whose buttons come off when the next one is
+    last_posted: Option<MessageId>,
+}
+
+impl Drop for Table {
+    fn drop(&mut self) {
+        if let Some(watcher) = self.watcher.take() {
+            watcher.abort();
+        }
+    }
+}
+
+/// Every chat's table, and the server their games are played on.
+#[derive(Clone)]
+pub struct Tables {
+    pub server: String,
+    chats: Arc<Mutex<HashMap<ChatId, Table>>>,
+}
+
+impl Tables {
+    pub fn new(server: String) -> Self {
+        Self {
+            server,
+            chats: Arc::default(),
+        }
+    }
+
+    pub fn has_table(&self, chat: ChatId) -> bool {
+        self.chats.lock().unwrap().contains_key(&chat)
+    }
+
+    /// Opens a table with its first seat.
+    pub fn open(&self, chat: ChatId, game_id: GameId, seat: Seat) -> Result<(), String> {
+        let mut chats = self.chats.lock().unwrap();
+        if chats.contains_key(&chat) {
+            return Err("This chat already has a table".to_string());
+        }
+        let table = Table {
+            game_id,
+            seats: vec![seat],
+            watcher: None,
+            last_posted: None,
+        };
+        chats.insert(chat, table);
+        Ok(())
+    }
+
+    pub fn game_id(&self, chat: ChatId) -> Option<GameId> {
+        self.chats.lock().unwrap().get(&chat).map(|table| table.game_id)
+    }
+
+    pub fn seat(&self, chat: ChatId, user: UserId) -> Option<Seat> {
+        let chats = self.chats.lock().unwrap();
+        chats.get(&chat)?.seats.iter().find(|seat| seat.user == user).cloned()
+    }
+
+    /// The seat playing `player_id`.
+    pub fn seat_of(&self, chat: ChatId, player_id: &str) -> Option<Seat> {
+        let chats = self.chats.lock().unwrap();
+        let table = chats.get(&chat)?;
+        table.seats.iter().find(|seat| seat.player_id.to_string() == player_id).cloned()
+    }
+
+    /// The seat whose connection the chat's events are followed through.
+    pub fn first_seat(&self, chat: ChatId) -> Option<Seat> {
+        self.chats.lock().unwrap().get(&chat)?.seats.first().cloned()
+    }
+
+    pub fn sit(&self, chat: ChatId, seat: Seat) -> Result<(), String> {
+        let mut chats = self.chats.lock().unwrap();
+        let table = chats.get_mut(&chat).ok_or("This chat has no table")?;
+        if table.seats.iter().any(|taken| taken.user == seat.user) {
+            return Err(format!("{} already sits at this table", seat.name));
+        }
+        table.seats.push(seat);
+        Ok(())
+    }
+
+    /// Takes the user's seat away. The chat's table closes with its last
+    /// seat; returns whether the events need following through another.
+    pub fn stand(&self, chat: ChatId, user: UserId) -> Result<(Seat, bool), String> {
+        let mut chats = self.chats.lock().unwrap();
+        let table = chats.get_mut(&chat).ok_or("This chat has no table")?;
+        let index = table
+            .seats
+            .iter()
+            .position(|seat| seat.user == user)
+            .ok_or("You don't sit at this table")?;
+        let seat = table.seats.remove(index);
+        if table.seats.is_empty() {
+            chats.remove(&chat);
+            return Ok((seat, false));
+        }
+        Ok((seat, index == 0))
+    }
+
+    /// Has `watcher` follow the chat's events, in place of the one that did.
+    pub fn watch(&self, chat: ChatId, watcher: JoinHandle<()>) {
+        match self.chats.lock().unwrap().get_mut(&chat) {
+            Some(table) => {
+                if let Some(previous) = table.watcher.replace(watcher) {
+                    previous.abort();
+                }
+            }
+            None => watcher.abort(),
+        }
+    }
+
+    /// Records the table just posted and returns the one posted before.
+    pub fn posted(&self, chat: ChatId, message: MessageId) -> Option<MessageId> {
+        let mut chats = self.chats.lock().unwrap();
+        chats.get_mut(&chat)?.last_posted.replace(message)
+    }
+
+    /// Closes the chat's table, once its game is over.
+    pub fn close(&self, chat: ChatId) {
+        // Taken out first: the watcher may be the task closing it
+        let table = self.chats.lock().unwrap().remove(&chat);
+        if let Some(mut table) = table {
+            table.watcher.take();
+        }
+    }
+}